@@ -0,0 +1,234 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+// These benchmarks call into the library directly using in-memory buffers for stdin and stdout so
+// that only the utilities themselves are measured (rather than process creation and pipes).  Run
+// them using `cargo bench` and compare results between revisions to catch regressions.
+
+#[macro_use]
+extern crate criterion;
+extern crate libmesabox as mesabox;
+extern crate tempfile;
+
+use criterion::Criterion;
+use mesabox::{LockError, UtilData, UtilWrite};
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, Write};
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::result::Result as StdResult;
+use tempfile::TempDir;
+
+const LARGE_FILE_SIZE: usize = 16 * 1024 * 1024;
+const SMALL_FILE_COUNT: usize = 500;
+const SMALL_FILE_SIZE: usize = 512;
+const LONG_LINE_SIZE: usize = 64 * 1024;
+const LONG_LINE_COUNT: usize = 256;
+
+/// Output that accepts a fixed number of bytes and then fails (which allows utilities that write
+/// forever, like `yes`, to be benchmarked).
+struct LimitedOutput {
+    remaining: usize,
+}
+
+impl LimitedOutput {
+    fn new(limit: usize) -> Self {
+        Self { remaining: limit }
+    }
+}
+
+impl Write for LimitedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.remaining == 0 {
+            return Err(io::Error::new(io::ErrorKind::BrokenPipe, "output limit reached"));
+        }
+        let len = buf.len().min(self.remaining);
+        self.remaining -= len;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> UtilWrite<'a> for LimitedOutput {
+    type Lock = &'a mut Self;
+
+    fn lock<'b: 'a>(&'b mut self) -> StdResult<Self::Lock, LockError> {
+        Ok(self)
+    }
+}
+
+struct Inputs {
+    // kept around so the directory is removed once the benchmarks finish
+    _dir: TempDir,
+    large_file: PathBuf,
+    small_files: Vec<PathBuf>,
+    long_lines: Vec<u8>,
+}
+
+impl Inputs {
+    fn new() -> Self {
+        let dir = TempDir::new().unwrap();
+
+        let large_file = dir.path().join("large");
+        write_file(&large_file, &text_data(LARGE_FILE_SIZE));
+
+        let small_data = text_data(SMALL_FILE_SIZE);
+        let small_files = (0..SMALL_FILE_COUNT)
+            .map(|i| {
+                let path = dir.path().join(format!("small{}", i));
+                write_file(&path, &small_data);
+                path
+            })
+            .collect();
+
+        let mut long_lines = vec![b'x'; LONG_LINE_SIZE * LONG_LINE_COUNT];
+        for line in long_lines.chunks_mut(LONG_LINE_SIZE) {
+            line[LONG_LINE_SIZE - 1] = b'\n';
+        }
+
+        Self {
+            _dir: dir,
+            large_file: large_file,
+            small_files: small_files,
+            long_lines: long_lines,
+        }
+    }
+}
+
+// generates lines of varying length (between 1 and 80 bytes, including the newline)
+fn text_data(size: usize) -> Vec<u8> {
+    let mut data = Vec::with_capacity(size);
+    let mut line_len = 0;
+    while data.len() < size {
+        if line_len == data.len() % 79 {
+            data.push(b'\n');
+            line_len = 0;
+        } else {
+            data.push(b'a' + (data.len() % 26) as u8);
+            line_len += 1;
+        }
+    }
+    data
+}
+
+fn write_file(path: &Path, data: &[u8]) {
+    File::create(path).unwrap().write_all(data).unwrap();
+}
+
+fn run<O>(input: &[u8], output: &mut O, args: &[OsString])
+where
+    O: for<'b> UtilWrite<'b>,
+{
+    let mut input = input;
+    let mut error = io::sink();
+    let mut setup = UtilData::new(&mut input, output, &mut error, iter::empty(), None);
+
+    // utilities like yes are expected to fail here, so just ignore the result
+    let _ = mesabox::execute(&mut setup, &mut args.iter());
+}
+
+fn args<I, S>(iter: I) -> Vec<OsString>
+where
+    I: IntoIterator<Item = S>,
+    S: Into<OsString>,
+{
+    iter.into_iter().map(|s| s.into()).collect()
+}
+
+#[cfg(feature = "head")]
+fn bench_head(c: &mut Criterion, inputs: &Inputs) {
+    let bytes_args = args(vec![
+        OsString::from("head"),
+        OsString::from("-c"),
+        OsString::from(LARGE_FILE_SIZE.to_string()),
+        inputs.large_file.clone().into_os_string(),
+    ]);
+    c.bench_function("head -c (large file)", move |b| {
+        b.iter(|| run(&[], &mut io::sink(), &bytes_args))
+    });
+
+    let lines_args = args(vec![
+        OsString::from("head"),
+        OsString::from("-n"),
+        OsString::from(LARGE_FILE_SIZE.to_string()),
+        inputs.large_file.clone().into_os_string(),
+    ]);
+    c.bench_function("head -n (large file)", move |b| {
+        b.iter(|| run(&[], &mut io::sink(), &lines_args))
+    });
+
+    let long_lines = inputs.long_lines.clone();
+    let long_args = args(vec!["head", "-n", "-1"]);
+    c.bench_function("head -n -1 (long lines)", move |b| {
+        b.iter(|| run(&long_lines, &mut io::sink(), &long_args))
+    });
+
+    let mut many_args = args(vec!["head", "-n", "5"]);
+    many_args.extend(inputs.small_files.iter().map(|p| p.clone().into_os_string()));
+    c.bench_function("head -n 5 (many small files)", move |b| {
+        b.iter(|| run(&[], &mut io::sink(), &many_args))
+    });
+}
+
+#[cfg(feature = "cat")]
+fn bench_cat(c: &mut Criterion, inputs: &Inputs) {
+    let large_args = args(vec![
+        OsString::from("cat"),
+        inputs.large_file.clone().into_os_string(),
+    ]);
+    c.bench_function("cat (large file)", move |b| {
+        b.iter(|| run(&[], &mut io::sink(), &large_args))
+    });
+
+    let mut many_args = args(vec!["cat"]);
+    many_args.extend(inputs.small_files.iter().map(|p| p.clone().into_os_string()));
+    c.bench_function("cat (many small files)", move |b| {
+        b.iter(|| run(&[], &mut io::sink(), &many_args))
+    });
+
+    let long_lines = inputs.long_lines.clone();
+    let number_args = args(vec!["cat", "-n"]);
+    c.bench_function("cat -n (long lines)", move |b| {
+        b.iter(|| run(&long_lines, &mut io::sink(), &number_args))
+    });
+}
+
+#[cfg(feature = "yes")]
+fn bench_yes(c: &mut Criterion, _inputs: &Inputs) {
+    let default_args = args(vec!["yes"]);
+    c.bench_function("yes (16 MiB)", move |b| {
+        b.iter(|| run(&[], &mut LimitedOutput::new(LARGE_FILE_SIZE), &default_args))
+    });
+
+    let long_string = "y".repeat(LONG_LINE_SIZE);
+    let long_args = args(vec!["yes", &long_string]);
+    c.bench_function("yes (16 MiB, long string)", move |b| {
+        b.iter(|| run(&[], &mut LimitedOutput::new(LARGE_FILE_SIZE), &long_args))
+    });
+}
+
+fn benches(c: &mut Criterion) {
+    let inputs = Inputs::new();
+
+    #[cfg(feature = "cat")]
+    bench_cat(c, &inputs);
+    #[cfg(feature = "head")]
+    bench_head(c, &inputs);
+    #[cfg(feature = "yes")]
+    bench_yes(c, &inputs);
+
+    // silence the warning when none of the benchmarked utilities are enabled
+    let _ = inputs;
+}
+
+criterion_group!(utils, benches);
+criterion_main!(utils);
@@ -0,0 +1,53 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+// Treats the input as a list of NUL-separated arguments to head, which exercises the rewriting of
+// obsolete arguments along with the regular option parsing.
+
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate libmesabox as mesabox;
+
+use mesabox::UtilData;
+use std::ffi::OsString;
+use std::iter;
+use std::path::PathBuf;
+
+const INPUT: &[u8] = b"first\nsecond\nthird\n\nfifth\nsixth without newline";
+
+#[cfg(unix)]
+fn to_os_string(bytes: &[u8]) -> OsString {
+    use std::os::unix::ffi::OsStringExt;
+
+    OsString::from_vec(bytes.to_owned())
+}
+
+#[cfg(not(unix))]
+fn to_os_string(bytes: &[u8]) -> OsString {
+    OsString::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+fuzz_target!(|data: &[u8]| {
+    // relative paths will fail to open due to current_dir below, but absolute paths (such as
+    // /dev/zero, or @/dev/zero for a list of files) would let the fuzzer wander off into the
+    // filesystem, so skip them
+    let args = data
+        .split(|&b| b == 0)
+        .filter(|arg| !arg.starts_with(b"/") && !arg.starts_with(b"@/"))
+        .map(to_os_string);
+    let args = iter::once(OsString::from("head")).chain(args).collect::<Vec<_>>();
+
+    let mut stdin = INPUT;
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    let current_dir = Some(PathBuf::from("/nonexistent"));
+    let mut setup = UtilData::new(&mut stdin, &mut stdout, &mut stderr, iter::empty(), current_dir);
+
+    let _ = mesabox::execute(&mut setup, &mut args.iter());
+});
@@ -0,0 +1,43 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+// Feeds arbitrary strings to the number parsers used by head (which in turn use
+// util::parse_num_with_suffix() and util::parse_obsolete_num()).
+
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate libmesabox as mesabox;
+
+use mesabox::UtilData;
+use std::io;
+use std::iter;
+use std::str;
+
+const INPUT: &[u8] = b"1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+
+fn run(args: &[&str]) {
+    let mut stdin = INPUT;
+    let mut stdout = io::sink();
+    let mut stderr = io::sink();
+    let mut setup = UtilData::new(&mut stdin, &mut stdout, &mut stderr, iter::empty(), None);
+
+    // we only care about panics, so the result is irrelevant
+    let _ = mesabox::execute(&mut setup, &mut args.iter());
+}
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(s) = str::from_utf8(data) {
+        run(&["head", "-c", s]);
+        run(&["head", "-n", s]);
+
+        // the obsolete form (e.g. -4k)
+        let obsolete = format!("-{}", s);
+        run(&["head", &obsolete]);
+    }
+});
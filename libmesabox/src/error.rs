@@ -1,8 +1,11 @@
 use failure::{Error, Fail};
 use std::ffi::OsString;
 use std::fmt::{self, Display};
+use std::io;
+use std::path::{Path, PathBuf};
 
 use super::ExitCode;
+use util;
 
 #[derive(Debug)]
 pub struct MesaError {
@@ -24,6 +27,23 @@ impl MesaError {
         self.exitcode = code;
         self
     }
+
+    /// Whether this error was caused by writing to a pipe without any readers.
+    pub fn is_broken_pipe(&self) -> bool {
+        self.err
+            .as_ref()
+            .map(|err| util::is_broken_pipe(err.cause()))
+            .unwrap_or(false)
+    }
+
+    /// Whether this error occurred writing to the output (see `OutputWriter`), in which case
+    /// there is no point in processing any further inputs.
+    pub fn is_output_error(&self) -> bool {
+        self.err
+            .as_ref()
+            .map(|err| util::is_output_error(err.cause()))
+            .unwrap_or(false)
+    }
 }
 
 impl<E: Fail + Send + Sync + 'static> From<E> for MesaError {
@@ -53,3 +73,72 @@ impl Display for MesaError {
 pub struct LockError {
     file: String,
 }
+
+/// The operation that was being performed on a path when a `PathError` occurred.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PathOperation {
+    Access,
+    OpenDir,
+    OpenRead,
+    OpenWrite,
+    Read,
+    Write,
+    Stat,
+}
+
+/// An `io::Error` that occurred while operating on a path (for messages like
+/// "cannot open 'foo' for reading: No such file or directory").
+#[derive(Debug)]
+pub struct PathError {
+    pub operation: PathOperation,
+    pub path: PathBuf,
+    pub err: io::Error,
+}
+
+impl PathError {
+    pub fn new<P: AsRef<Path>>(operation: PathOperation, path: P, err: io::Error) -> Self {
+        Self {
+            operation: operation,
+            path: path.as_ref().to_owned(),
+            err: err,
+        }
+    }
+
+    pub fn kind(&self) -> io::ErrorKind {
+        self.err.kind()
+    }
+}
+
+impl Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path = self.path.display();
+        match self.operation {
+            PathOperation::Access => write!(f, "cannot access '{}'", path)?,
+            PathOperation::OpenDir => write!(f, "cannot open directory '{}'", path)?,
+            PathOperation::OpenRead => write!(f, "cannot open '{}' for reading", path)?,
+            PathOperation::OpenWrite => write!(f, "cannot open '{}' for writing", path)?,
+            PathOperation::Read => write!(f, "error reading '{}'", path)?,
+            PathOperation::Write => write!(f, "error writing '{}'", path)?,
+            PathOperation::Stat => write!(f, "cannot stat '{}'", path)?,
+        }
+        write!(f, ": {}", io_error_msg(&self.err))
+    }
+}
+
+impl Fail for PathError {
+    fn cause(&self) -> Option<&Fail> {
+        Some(&self.err)
+    }
+}
+
+// io::Error appends " (os error N)" to the system's message, which looks out of place in the
+// messages we display
+pub(crate) fn io_error_msg(err: &io::Error) -> String {
+    let msg = err.to_string();
+    if err.raw_os_error().is_some() {
+        if let Some(idx) = msg.rfind(" (os error ") {
+            return msg[..idx].to_owned();
+        }
+    }
+    msg
+}
@@ -40,16 +40,122 @@ use clap::Arg;
 use uucore::encoding::{self, Data, Format};
 
 use std::ffi::{OsStr, OsString};
-use std::fs::File;
-use std::io::{BufReader, Read, Write};
+use std::io::{self, BufReader, Read, Write};
 use std::result::Result as StdResult;
 use std::str::FromStr;
 
+const BUF_SIZE: usize = 32 * 1024;
+
+const BASE32_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+const BASE64_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// marks bytes outside of the alphabet in the decoding table
+const INVALID: u8 = 0xff;
+// marks the padding character (in the table and in groups)
+const PAD: u8 = 0xfe;
+
+#[derive(Debug, Fail)]
+enum DecodeError {
+    #[fail(display = "invalid input")]
+    InvalidInput,
+}
+
 struct Options {
     line_wrap: usize,
     decode: bool,
     ignore_garbage: bool,
     format: Format,
+    buffer_size: usize,
+}
+
+/// Decodes base32 or base64 one chunk of input at a time.  A group of characters (four for
+/// base64 and eight for base32) can be split between chunks, so the part of a group seen so far
+/// is kept until the rest of it arrives.
+struct Decoder {
+    table: [u8; 256],
+    // the number of characters in a group and the number of bits each one holds
+    group_size: usize,
+    bits: usize,
+    ignore_garbage: bool,
+    group: [u8; 8],
+    len: usize,
+}
+
+impl Decoder {
+    fn new(format: Format, ignore_garbage: bool) -> Self {
+        let (alphabet, group_size, bits) = match format {
+            Format::Base32 => (BASE32_ALPHABET, 8, 5),
+            Format::Base64 => (BASE64_ALPHABET, 4, 6),
+        };
+        let mut table = [INVALID; 256];
+        for (i, &c) in alphabet.iter().enumerate() {
+            table[c as usize] = i as u8;
+        }
+        table[b'=' as usize] = PAD;
+
+        Self {
+            table: table,
+            group_size: group_size,
+            bits: bits,
+            ignore_garbage: ignore_garbage,
+            group: [0; 8],
+            len: 0,
+        }
+    }
+
+    /// Decode `input`, adding the data from each group it completes to `output`.  When this
+    /// fails, `output` still holds the data decoded before the error.
+    fn decode(&mut self, input: &[u8], output: &mut Vec<u8>) -> StdResult<(), DecodeError> {
+        for &c in input {
+            let value = self.table[c as usize];
+            if value == INVALID {
+                // line breaks from wrapping (or any other spacing) are never part of the data
+                if c.is_ascii_whitespace() || self.ignore_garbage {
+                    continue;
+                }
+                return Err(DecodeError::InvalidInput);
+            }
+
+            self.group[self.len] = value;
+            self.len += 1;
+            if self.len == self.group_size {
+                self.len = 0;
+                self.decode_group(output)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the input did not stop partway through a group.
+    fn finish(&self) -> StdResult<(), DecodeError> {
+        if self.len == 0 {
+            Ok(())
+        } else {
+            Err(DecodeError::InvalidInput)
+        }
+    }
+
+    fn decode_group(&self, output: &mut Vec<u8>) -> StdResult<(), DecodeError> {
+        let group = &self.group[..self.group_size];
+
+        // padding may only fill out the end of a group, and only so far that the characters
+        // before it still make up whole bytes
+        let count = group.iter().position(|&v| v == PAD).unwrap_or(self.group_size);
+        if group[count..].iter().any(|&v| v != PAD) {
+            return Err(DecodeError::InvalidInput);
+        }
+        let total_bits = count * self.bits;
+        if count == 0 || (count < self.group_size && total_bits % 8 >= self.bits) {
+            return Err(DecodeError::InvalidInput);
+        }
+
+        let value = group[..count].iter().fold(0u64, |acc, &v| acc << self.bits | u64::from(v));
+        let bytes = total_bits / 8;
+        for i in 0..bytes {
+            output.push((value >> (total_bits - 8 * (i + 1))) as u8);
+        }
+        Ok(())
+    }
 }
 
 pub(crate) fn execute_base<S, T>(
@@ -100,12 +206,12 @@ where
         decode: decode,
         ignore_garbage: ignore_garbage,
         format: format,
+        buffer_size: setup.buffer_size().unwrap_or(BUF_SIZE),
     };
 
     match matches.value_of_os("FILE") {
         Some(filename) if filename != OsStr::new("-") => {
-            let path = util::actual_path(&setup.current_dir(), filename);
-            let file = File::open(path)?;
+            let file = util::open_file(&setup.current_dir(), filename)?;
 
             let mut output = setup.output();
             let output = output.lock()?;
@@ -125,17 +231,43 @@ where
     W: Write,
     S: Read,
 {
+    if options.decode {
+        return decode_data(output, source, &options);
+    }
+
     let mut data = Data::new(source, options.format)
         .line_wrap(options.line_wrap)
         .ignore_garbage(options.ignore_garbage);
+    encoding::wrap_write(output, options.line_wrap, data.encode())?;
 
-    if options.decode {
-        let s = data.decode()?;
-        output.write_all(&s)?;
-    } else {
-        encoding::wrap_write(output, options.line_wrap, data.encode())?;
+    Ok(())
+}
+
+fn decode_data<W, S>(mut output: W, mut source: S, options: &Options) -> Result<()>
+where
+    W: Write,
+    S: Read,
+{
+    let mut decoder = Decoder::new(options.format, options.ignore_garbage);
+    let mut buffer = vec![0; options.buffer_size];
+    let mut decoded = Vec::with_capacity(options.buffer_size);
+
+    loop {
+        let len = match source.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        decoded.clear();
+        let res = decoder.decode(&buffer[..len], &mut decoded);
+        // like GNU base64, write out everything decoded before any invalid input
+        output.write_all(&decoded)?;
+        res?;
     }
 
+    decoder.finish()?;
     Ok(())
 }
 
@@ -149,3 +281,63 @@ fn is_valid_wrap(val: &OsStr) -> StdResult<(), OsString> {
         )))
     }
 }
+
+#[cfg(test)]
+fn decode_chunks(format: Format, ignore_garbage: bool, chunks: &[&[u8]]) -> Option<Vec<u8>> {
+    let mut decoder = Decoder::new(format, ignore_garbage);
+    let mut output = vec![];
+    for chunk in chunks {
+        decoder.decode(chunk, &mut output).ok()?;
+    }
+    decoder.finish().ok()?;
+    Some(output)
+}
+
+#[test]
+fn base_decode_split() {
+    let cases: &[(Format, &[u8], &[u8])] = &[
+        (Format::Base64, b"aGVsbG8sIHdv\ncmxkIQ==\n", b"hello, world!"),
+        (Format::Base64, b" YW Jj\r\nZA=\t=\n", b"abcd"),
+        (Format::Base64, b"YQ==YWI=", b"aab"),
+        (Format::Base32, b"NBSWY3DP\nEB3W64TM\nMQ======\n", b"hello world"),
+        (Format::Base32, b"ME======MFRA====MFRGG===MFRGGZA=", b"aababcabcd"),
+    ];
+
+    // the result must not depend on where the input is split
+    for &(format, input, expected) in cases {
+        for i in 0..input.len() + 1 {
+            for j in i..input.len() + 1 {
+                let chunks = [&input[..i], &input[i..j], &input[j..]];
+                let output = decode_chunks(format, false, &chunks);
+                assert_eq!(output.as_ref().map(|v| &v[..]), Some(expected), "{:?}", chunks);
+            }
+        }
+    }
+}
+
+#[test]
+fn base_decode_invalid() {
+    let cases: &[(Format, &[u8])] = &[
+        (Format::Base64, b"YQ"),
+        (Format::Base64, b"YQ="),
+        (Format::Base64, b"Y==="),
+        (Format::Base64, b"=YQ="),
+        (Format::Base64, b"YQ=a"),
+        (Format::Base64, b"YWJj\0"),
+        (Format::Base32, b"ME====="),
+        (Format::Base32, b"M======="),
+        (Format::Base32, b"MFR====="),
+        (Format::Base32, b"mfra===="),
+    ];
+
+    for &(format, input) in cases {
+        for i in 0..input.len() + 1 {
+            let chunks = [&input[..i], &input[i..]];
+            assert_eq!(decode_chunks(format, false, &chunks), None, "{:?}", chunks);
+        }
+    }
+
+    // garbage is skipped with -i, but padding in the wrong place is still invalid
+    assert_eq!(decode_chunks(Format::Base64, true, &[b"Y.Q*=\0="]), Some(b"a".to_vec()));
+    assert_eq!(decode_chunks(Format::Base64, true, &[b"Y=Q="]), None);
+}
@@ -0,0 +1,341 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use util::userdb;
+use util::{self, ExitCode, RawObjectWrapper};
+use {ArgsIter, MesaError, Result, UtilRead, UtilSetup, UtilWrite};
+
+use clap::{App, AppSettings, Arg};
+use libc;
+use std::ffi::{CString, OsStr, OsString};
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::result::Result as StdResult;
+
+pub(crate) const NAME: &str = "chroot";
+pub(crate) const DESCRIPTION: &str = "Run a command with a different root directory";
+
+const AFTER_HELP: &str = "
+If no command is given, run '${SHELL} -i' (the default is '/bin/sh -i').
+";
+
+// these match the exit codes used by GNU chroot
+const EXIT_CANCELED: ExitCode = 125;
+const EXIT_CANNOT_INVOKE: ExitCode = 126;
+const EXIT_ENOENT: ExitCode = 127;
+
+#[derive(Debug, Fail)]
+enum ChrootError {
+    #[fail(display = "invalid user '{}'", _0)]
+    InvalidUser(String),
+
+    #[fail(display = "invalid group '{}'", _0)]
+    InvalidGroup(String),
+
+    #[fail(display = "no group specified for unknown uid: {}", _0)]
+    NoGroup(libc::uid_t),
+
+    #[fail(display = "invalid group list '{}'", _0)]
+    InvalidGroupList(String),
+
+    #[fail(display = "option --skip-chdir only permitted if NEWROOT is old '/'")]
+    SkipChdir,
+
+    #[fail(display = "cannot change root directory to '{}': {}", _0, _1)]
+    Chroot(String, #[cause] io::Error),
+
+    #[fail(display = "cannot chdir to root directory: {}", _0)]
+    Chdir(#[cause] io::Error),
+
+    #[fail(display = "failed to set supplemental groups: {}", _0)]
+    SetGroups(#[cause] io::Error),
+
+    #[fail(display = "failed to set group-ID: {}", _0)]
+    SetGid(#[cause] io::Error),
+
+    #[fail(display = "failed to set user-ID: {}", _0)]
+    SetUid(#[cause] io::Error),
+
+    #[fail(display = "failed to run command '{}': {}", _0, _1)]
+    Exec(String, #[cause] io::Error),
+}
+
+type ChrootResult<T> = StdResult<T, ChrootError>;
+
+/// The user and group given by `--userspec=USER:GROUP`.
+#[derive(Default)]
+struct UserSpec {
+    uid: Option<libc::uid_t>,
+    gid: Option<libc::gid_t>,
+    /// Name of the user as found in the user database (needed to determine the user's
+    /// supplementary groups)
+    name: Option<CString>,
+}
+
+struct Options<'a> {
+    newroot: &'a Path,
+    skip_chdir: bool,
+    user: Option<UserSpec>,
+    groups: Option<Vec<libc::gid_t>>,
+}
+
+pub fn execute<S, T>(setup: &mut S, args: T) -> Result<()>
+where
+    S: UtilSetup,
+    T: ArgsIter,
+{
+    let matches = create_app().get_matches_from_safe(args)?;
+
+    // user and group names are resolved using the current root's databases, so this needs to
+    // occur before we call chroot(2)
+    let user = match matches.value_of("userspec") {
+        Some(spec) => Some(canceled(parse_userspec(spec))?),
+        None => None,
+    };
+    let groups = match matches.value_of("groups") {
+        Some(list) => Some(canceled(parse_groups(list))?),
+        None => None,
+    };
+
+    let newroot =
+        util::actual_path(&setup.current_dir(), matches.value_of_os("NEWROOT").unwrap())
+            .into_owned();
+
+    let options = Options {
+        newroot: &newroot,
+        skip_chdir: matches.is_present("skip-chdir"),
+        user: user,
+        groups: groups,
+    };
+
+    let (progname, mut command) = match matches.values_of_os("COMMAND") {
+        Some(mut values) => {
+            let progname = values.next().unwrap();
+            let mut command = Command::new(progname);
+            command.args(values);
+            (progname.to_os_string(), command)
+        }
+        None => {
+            let shell = setup
+                .env()
+                .get_nonempty("SHELL")
+                .map(|value| value.to_owned())
+                .unwrap_or_else(|| OsString::from("/bin/sh"));
+            let mut command = Command::new(&shell);
+            command.arg("-i");
+            (shell, command)
+        }
+    };
+    command.env_clear().envs(setup.env().iter());
+
+    // NOTE: we need to duplicate the fds as from_raw_fd() takes ownership
+    if let Some(fd) = setup.input().raw_object() {
+        let fd = RawObjectWrapper::new(fd, true, false).dup()?;
+        command.stdin(unsafe { Stdio::from_raw_fd(fd.raw_value()) });
+    }
+    if let Some(fd) = setup.output().raw_object() {
+        let fd = RawObjectWrapper::new(fd, false, true).dup()?;
+        command.stdout(unsafe { Stdio::from_raw_fd(fd.raw_value()) });
+    }
+    if let Some(fd) = setup.error().raw_object() {
+        let fd = RawObjectWrapper::new(fd, false, true).dup()?;
+        command.stderr(unsafe { Stdio::from_raw_fd(fd.raw_value()) });
+    }
+
+    canceled(change_root(&options))?;
+    canceled(drop_privileges(&options))?;
+
+    // if this returns at all the command could not be started
+    let err = command.exec();
+    let code = if err.kind() == io::ErrorKind::NotFound {
+        EXIT_ENOENT
+    } else {
+        EXIT_CANNOT_INVOKE
+    };
+    let err = ChrootError::Exec(progname.to_string_lossy().into_owned(), err);
+    Err(MesaError::from(err).with_exitcode(code))
+}
+
+fn create_app() -> App<'static, 'static> {
+    util_app!(NAME)
+        .after_help(AFTER_HELP)
+        .setting(AppSettings::TrailingVarArg)
+        .arg(
+            Arg::with_name("userspec")
+                .long("userspec")
+                .takes_value(true)
+                .value_name("USER:GROUP")
+                .help("specify user and group (ID or name) to use"),
+        )
+        .arg(
+            Arg::with_name("groups")
+                .long("groups")
+                .takes_value(true)
+                .value_name("G_LIST")
+                .help("specify supplementary groups as g1,g2,..,gN"),
+        )
+        .arg(
+            Arg::with_name("skip-chdir")
+                .long("skip-chdir")
+                .help("do not change working directory to '/'"),
+        )
+        .arg(Arg::with_name("NEWROOT").index(1).required(true))
+        .arg(Arg::with_name("COMMAND").index(2).multiple(true))
+}
+
+/// Convert an error encountered before the command is run into one using the exit code GNU
+/// chroot uses for such failures.
+fn canceled<T>(res: ChrootResult<T>) -> Result<T> {
+    res.map_err(|e| MesaError::from(e).with_exitcode(EXIT_CANCELED))
+}
+
+fn change_root(options: &Options) -> ChrootResult<()> {
+    if options.skip_chdir && !is_old_root(options.newroot) {
+        return Err(ChrootError::SkipChdir);
+    }
+
+    let root = path_to_cstring(options.newroot.as_os_str())
+        .ok_or_else(|| chroot_error(options.newroot, io::ErrorKind::InvalidInput.into()))?;
+    if unsafe { libc::chroot(root.as_ptr()) } != 0 {
+        return Err(chroot_error(options.newroot, io::Error::last_os_error()));
+    }
+
+    if !options.skip_chdir {
+        let slash = CString::new("/").unwrap();
+        if unsafe { libc::chdir(slash.as_ptr()) } != 0 {
+            return Err(ChrootError::Chdir(io::Error::last_os_error()));
+        }
+    }
+
+    Ok(())
+}
+
+// the order of the calls below matters, as once we give up our user ID we may no longer be
+// allowed to change our groups
+fn drop_privileges(options: &Options) -> ChrootResult<()> {
+    let gid = options.user.as_ref().and_then(|user| user.gid);
+
+    if let Some(ref groups) = options.groups {
+        set_groups(groups)?;
+    } else if let Some(ref user) = options.user {
+        match (&user.name, gid) {
+            (Some(name), Some(gid)) => {
+                if unsafe { libc::initgroups(name.as_ptr(), gid as _) } != 0 {
+                    return Err(ChrootError::SetGroups(io::Error::last_os_error()));
+                }
+            }
+            (None, Some(gid)) if user.uid.is_some() => set_groups(&[gid])?,
+            _ => {}
+        }
+    }
+
+    if let Some(gid) = gid {
+        if unsafe { libc::setgid(gid) } != 0 {
+            return Err(ChrootError::SetGid(io::Error::last_os_error()));
+        }
+    }
+
+    if let Some(uid) = options.user.as_ref().and_then(|user| user.uid) {
+        if unsafe { libc::setuid(uid) } != 0 {
+            return Err(ChrootError::SetUid(io::Error::last_os_error()));
+        }
+    }
+
+    Ok(())
+}
+
+fn set_groups(groups: &[libc::gid_t]) -> ChrootResult<()> {
+    if unsafe { libc::setgroups(groups.len() as _, groups.as_ptr()) } != 0 {
+        Err(ChrootError::SetGroups(io::Error::last_os_error()))
+    } else {
+        Ok(())
+    }
+}
+
+fn is_old_root(path: &Path) -> bool {
+    fs::canonicalize(path)
+        .map(|path| path == Path::new("/"))
+        .unwrap_or(false)
+}
+
+fn chroot_error(path: &Path, err: io::Error) -> ChrootError {
+    ChrootError::Chroot(path.to_string_lossy().into_owned(), err)
+}
+
+fn path_to_cstring(path: &OsStr) -> Option<CString> {
+    CString::new(path.as_bytes()).ok()
+}
+
+/// Parse a specification of the form `USER[:GROUP]`, where USER may be a name or a numeric ID.
+/// If the group is empty or missing, the user's login group is used.
+fn parse_userspec(spec: &str) -> ChrootResult<UserSpec> {
+    let (user, group) = match spec.find(':') {
+        Some(idx) => (&spec[..idx], Some(&spec[idx + 1..])),
+        None => (spec, None),
+    };
+
+    let mut result = UserSpec::default();
+    let mut login_gid = None;
+
+    if !user.is_empty() {
+        match userdb::user_by_name(user) {
+            Some(entry) => {
+                result.uid = Some(entry.uid);
+                result.name = Some(entry.name);
+                login_gid = Some(entry.gid);
+            }
+            None => {
+                let uid = user
+                    .parse()
+                    .map_err(|_| ChrootError::InvalidUser(user.to_string()))?;
+                result.uid = Some(uid);
+                // a numeric ID still needs the login group and supplementary groups of the user
+                if let Some(entry) = userdb::user_by_uid(uid) {
+                    result.name = Some(entry.name);
+                    login_gid = Some(entry.gid);
+                }
+            }
+        }
+    }
+
+    result.gid = match group {
+        Some(group) if !group.is_empty() => Some(resolve_group(group)?),
+        Some(_) => Some(login_gid.ok_or_else(|| ChrootError::InvalidUser(user.to_string()))?),
+        None => login_gid,
+    };
+
+    // otherwise we would keep our own group (and supplementary groups) after changing user
+    match (result.uid, result.gid) {
+        (Some(uid), None) => Err(ChrootError::NoGroup(uid)),
+        _ => Ok(result),
+    }
+}
+
+fn parse_groups(list: &str) -> ChrootResult<Vec<libc::gid_t>> {
+    list.split(',')
+        .map(|group| {
+            if group.is_empty() {
+                Err(ChrootError::InvalidGroupList(list.to_string()))
+            } else {
+                resolve_group(group)
+            }
+        })
+        .collect()
+}
+
+fn resolve_group(group: &str) -> ChrootResult<libc::gid_t> {
+    userdb::group_by_name(group)
+        .map(|entry| entry.gid)
+        .or_else(|| group.parse().ok())
+        .ok_or_else(|| ChrootError::InvalidGroup(group.to_string()))
+}
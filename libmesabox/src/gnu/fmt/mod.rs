@@ -0,0 +1,408 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use util;
+use {ArgsIter, MesaError, Result, UtilRead, UtilSetup, UtilWrite};
+
+use clap::Arg;
+use std::ffi::OsStr;
+use std::io::{BufRead, BufReader, Write};
+use std::result::Result as StdResult;
+
+pub(crate) const NAME: &str = "fmt";
+pub(crate) const DESCRIPTION: &str = "Reformat paragraphs of text to fit a width";
+
+const AFTER_HELP: &str = "
+Paragraphs are separated by blank lines and by changes in indentation.  Words are packed onto each
+line of a paragraph for as long as they fit within WIDTH, keeping the indentation of the
+paragraph.  The end of a sentence (a word ending in '.', '?' or '!' at the end of a line or
+followed by two spaces) is followed by two spaces.
+";
+
+const DEFAULT_WIDTH: &str = "75";
+const TAB_WIDTH: usize = 8;
+
+struct Options {
+    width: usize,
+    split_only: bool,
+    uniform: bool,
+    prefix: Vec<u8>,
+    crown: bool,
+    tagged: bool,
+}
+
+/// A line that is part of a paragraph, split into its parts.
+struct Line {
+    // the whitespace before the prefix and the prefix itself
+    lead: Vec<u8>,
+    indent: Vec<u8>,
+    text: Vec<u8>,
+}
+
+struct Word<'a> {
+    text: &'a [u8],
+    // the number of spaces to put after the word if it is followed by another on the same line
+    space: usize,
+}
+
+struct Formatter<'a, W: Write + 'a> {
+    output: &'a mut W,
+    options: &'a Options,
+    paragraph: Vec<Line>,
+}
+
+impl<'a, W: Write> Formatter<'a, W> {
+    fn new(output: &'a mut W, options: &'a Options) -> Self {
+        Self {
+            output: output,
+            options: options,
+            paragraph: vec![],
+        }
+    }
+
+    fn format<R: BufRead>(&mut self, mut input: R) -> Result<()> {
+        let mut line = vec![];
+        loop {
+            line.clear();
+            if input.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            if line.last() == Some(&b'\n') {
+                line.pop();
+            }
+            self.add_line(&line)?;
+        }
+        self.flush_paragraph()
+    }
+
+    fn add_line(&mut self, line: &[u8]) -> Result<()> {
+        let parsed = match self.parse_line(line) {
+            Some(parsed) => parsed,
+            None => {
+                // lines without the prefix and blank lines are left alone
+                self.flush_paragraph()?;
+                self.output.write_all(line)?;
+                self.output.write_all(b"\n")?;
+                return Ok(());
+            }
+        };
+
+        if !self.continues_paragraph(&parsed) {
+            self.flush_paragraph()?;
+        }
+        self.paragraph.push(parsed);
+        Ok(())
+    }
+
+    // split `line` into its parts, or return None if it is not part of any paragraph
+    fn parse_line(&self, line: &[u8]) -> Option<Line> {
+        let lead_len = leading_space(line);
+        if !line[lead_len..].starts_with(&self.options.prefix) {
+            return None;
+        }
+        let lead_len = lead_len + self.options.prefix.len();
+        let rest = &line[lead_len..];
+        let indent_len = leading_space(rest);
+        let text = trim_end(&rest[indent_len..]);
+        if text.is_empty() {
+            return None;
+        }
+
+        Some(Line {
+            lead: line[..lead_len].to_vec(),
+            indent: rest[..indent_len].to_vec(),
+            text: text.to_vec(),
+        })
+    }
+
+    fn continues_paragraph(&self, line: &Line) -> bool {
+        let first = match self.paragraph.first() {
+            Some(first) => first,
+            None => return true,
+        };
+        if self.options.split_only || line.lead != first.lead {
+            return false;
+        }
+
+        if self.options.crown || self.options.tagged {
+            match self.paragraph.get(1) {
+                // the second line sets the indentation of the rest of the paragraph, but with -t
+                // it has to differ from the first
+                Some(second) => line.indent == second.indent,
+                None => !self.options.tagged || line.indent != first.indent,
+            }
+        } else {
+            line.indent == first.indent
+        }
+    }
+
+    fn flush_paragraph(&mut self) -> Result<()> {
+        if !self.paragraph.is_empty() {
+            format_paragraph(self.output, &self.paragraph, self.options)?;
+            self.paragraph.clear();
+        }
+        Ok(())
+    }
+}
+
+/// Write the words of `paragraph` to `output`, packing as many onto each line as fit.
+fn format_paragraph<W: Write>(output: &mut W, paragraph: &[Line], options: &Options) -> Result<()> {
+    let first = &paragraph[0];
+    let rest_indent = paragraph.get(1).map(|line| &line.indent).unwrap_or(&first.indent);
+    let words = split_words(paragraph, options.uniform);
+
+    let mut current = 0;
+    let mut first_line = true;
+    while current < words.len() {
+        let indent = if first_line { &first.indent } else { rest_indent };
+        first_line = false;
+
+        output.write_all(&first.lead)?;
+        output.write_all(indent)?;
+        let mut column = columns(&first.lead, 0);
+        column = columns(indent, column);
+
+        // the first word goes on the line even if it is too long
+        output.write_all(words[current].text)?;
+        column += display_width(words[current].text);
+        current += 1;
+        while current < words.len() {
+            let space = words[current - 1].space;
+            let width = display_width(words[current].text);
+            if column + space + width > options.width {
+                break;
+            }
+            for _ in 0..space {
+                output.write_all(b" ")?;
+            }
+            output.write_all(words[current].text)?;
+            column += space + width;
+            current += 1;
+        }
+        output.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
+
+fn split_words(paragraph: &[Line], uniform: bool) -> Vec<Word> {
+    let mut words = vec![];
+    for line in paragraph {
+        let text = &line.text[..];
+        let mut pos = 0;
+        while pos < text.len() {
+            let end = pos + text[pos..].iter().take_while(|&&b| !is_space(b)).count();
+            let gap = text[end..].iter().take_while(|&&b| is_space(b)).count();
+            let word = &text[pos..end];
+
+            // the end of the line counts as the end of a sentence if the word looks like one
+            let at_end = end + gap == text.len();
+            let sentence_end = ends_sentence(word) && (at_end || gap >= 2);
+            let space = if sentence_end {
+                2
+            } else if uniform || at_end {
+                1
+            } else {
+                gap
+            };
+            words.push(Word {
+                text: word,
+                space: space,
+            });
+            pos = end + gap;
+        }
+    }
+    words
+}
+
+// whether `word` ends in a period, question mark or exclamation mark (possibly followed by closing
+// quotes or parentheses)
+fn ends_sentence(word: &[u8]) -> bool {
+    let closing = word.iter()
+        .rev()
+        .take_while(|&&b| b == b'"' || b == b'\'' || b == b')' || b == b']')
+        .count();
+    match word[..word.len() - closing].last() {
+        Some(&b'.') | Some(&b'?') | Some(&b'!') => true,
+        _ => false,
+    }
+}
+
+fn is_space(byte: u8) -> bool {
+    byte == b' ' || byte == b'\t'
+}
+
+fn leading_space(data: &[u8]) -> usize {
+    data.iter().take_while(|&&b| is_space(b)).count()
+}
+
+fn trim_end(data: &[u8]) -> &[u8] {
+    let len = data.len() - data.iter().rev().take_while(|&&b| is_space(b) || b == b'\r').count();
+    &data[..len]
+}
+
+// the column reached by writing `data` starting at `column` (where tabs move to the next tab stop)
+fn columns(data: &[u8], column: usize) -> usize {
+    data.iter().fold(column, |column, &byte| match byte {
+        b'\t' => (column / TAB_WIDTH + 1) * TAB_WIDTH,
+        _ if is_continuation(byte) => column,
+        _ => column + 1,
+    })
+}
+
+// the number of characters in `data`, counting invalid UTF-8 bytes as one character each
+fn display_width(data: &[u8]) -> usize {
+    data.iter().filter(|&&byte| !is_continuation(byte)).count()
+}
+
+fn is_continuation(byte: u8) -> bool {
+    byte & 0xc0 == 0x80
+}
+
+pub fn execute<S, T>(setup: &mut S, args: T) -> Result<()>
+where
+    S: UtilSetup,
+    T: ArgsIter,
+{
+    let matches = {
+        let app = util_app!(NAME)
+                    .after_help(AFTER_HELP)
+                    .arg(Arg::with_name("width")
+                            .short("w")
+                            .long("width")
+                            .takes_value(true)
+                            .value_name("WIDTH")
+                            .default_value(DEFAULT_WIDTH)
+                            .validator(is_valid_width)
+                            .help("Fit lines within WIDTH columns"))
+                    .arg(Arg::with_name("split-only")
+                            .short("s")
+                            .long("split-only")
+                            .help("Split long lines, but do not join short ones"))
+                    .arg(Arg::with_name("uniform-spacing")
+                            .short("u")
+                            .long("uniform-spacing")
+                            .help("Put one space between words and two after sentences"))
+                    .arg(Arg::with_name("prefix")
+                            .short("p")
+                            .long("prefix")
+                            .takes_value(true)
+                            .value_name("PREFIX")
+                            .help("Only reformat lines starting with PREFIX (after any whitespace), keeping the prefix on each line"))
+                    .arg(Arg::with_name("crown-margin")
+                            .short("c")
+                            .long("crown-margin")
+                            .help("Keep the indentation of the first two lines of each paragraph, using that of the second for the rest"))
+                    .arg(Arg::with_name("tagged-paragraph")
+                            .short("t")
+                            .long("tagged-paragraph")
+                            .help("Like --crown-margin, but the indentation of the first line must differ from the second"))
+                    .arg(Arg::with_name("FILES")
+                            .index(1)
+                            .multiple(true));
+
+        app.get_matches_from_safe(args)?
+    };
+
+    // this .unwrap() is fine because of the validator
+    let options = Options {
+        width: matches.value_of("width").unwrap().parse().unwrap(),
+        split_only: matches.is_present("split-only"),
+        uniform: matches.is_present("uniform-spacing"),
+        prefix: matches
+            .value_of("prefix")
+            .map(|prefix| prefix.as_bytes().to_vec())
+            .unwrap_or_default(),
+        crown: matches.is_present("crown-margin"),
+        tagged: matches.is_present("tagged-paragraph"),
+    };
+
+    let current_dir = setup.current_dir().map(|p| p.to_owned());
+    let (input, output, error) = setup.stdio();
+    let mut output = output.lock()?;
+    let mut err_stream = error.lock()?;
+
+    // reading from stdin without any operands is handled exactly like an operand of "-"
+    let files: Vec<&OsStr> = match matches.values_of_os("FILES") {
+        Some(values) => values.collect(),
+        None => vec![OsStr::new("-")],
+    };
+
+    let mut result = Ok(());
+    for &file in &files {
+        let res = {
+            let mut formatter = Formatter::new(&mut output, &options);
+            if file == OsStr::new("-") {
+                input
+                    .lock()
+                    .map_err(MesaError::from)
+                    .and_then(|stdin| formatter.format(stdin))
+            } else {
+                util::open_file(&current_dir, file)
+                    .and_then(|file| formatter.format(BufReader::new(file)))
+            }
+        };
+
+        if let Err(mut e) = res {
+            display_msg!(err_stream, "{}", e)?;
+            e.err = None;
+            result = Err(e);
+        }
+    }
+    output.flush()?;
+
+    result
+}
+
+fn is_valid_width(val: String) -> StdResult<(), String> {
+    match val.parse::<usize>() {
+        Ok(width) if width > 0 => Ok(()),
+        _ => Err(format!("invalid width: '{}'", val)),
+    }
+}
+
+#[cfg(test)]
+fn fmt_str(input: &str, width: usize, uniform: bool) -> String {
+    let options = Options {
+        width: width,
+        split_only: false,
+        uniform: uniform,
+        prefix: vec![],
+        crown: false,
+        tagged: false,
+    };
+    let mut output = vec![];
+    Formatter::new(&mut output, &options).format(input.as_bytes()).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn fmt_packing() {
+    assert_eq!(fmt_str("a b c d e f\n", 5, false), "a b c\nd e f\n");
+    assert_eq!(fmt_str("one\ntwo\n\nthree\n", 75, false), "one two\n\nthree\n");
+    // words longer than the width are left alone
+    assert_eq!(fmt_str("a verylongword b\n", 4, false), "a\nverylongword\nb\n");
+    // the width counts characters rather than bytes
+    assert_eq!(fmt_str("ñññ ñññ\n", 7, false), "ñññ ñññ\n");
+}
+
+#[test]
+fn fmt_spacing() {
+    assert_eq!(fmt_str("End.\nNext  word.  Last\n", 75, false), "End.  Next  word.  Last\n");
+    assert_eq!(fmt_str("End.\nNext  word.  Last\n", 75, true), "End.  Next word.  Last\n");
+    // abbreviations in the middle of a line are not the end of a sentence
+    assert_eq!(fmt_str("e.g. this\n", 75, true), "e.g. this\n");
+    assert_eq!(fmt_str("\"Quote.\"\nNext\n", 75, true), "\"Quote.\"  Next\n");
+}
+
+#[test]
+fn fmt_columns() {
+    assert_eq!(columns(b"\t", 0), 8);
+    assert_eq!(columns(b"ab\t", 3), 8);
+    assert_eq!(columns("añ".as_bytes(), 0), 2);
+}
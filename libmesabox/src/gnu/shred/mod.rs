@@ -0,0 +1,364 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use util;
+use {ArgsIter, PathError, PathOperation, Result, UtilSetup, UtilWrite};
+
+use clap::Arg;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::result::Result as StdResult;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub(crate) const NAME: &str = "shred";
+pub(crate) const DESCRIPTION: &str = "Overwrite files to make their contents hard to recover";
+
+const AFTER_HELP: &str = "
+Each FILE is overwritten with random data ITERATIONS times (and then with zeros if --zero is
+given), and the data is flushed to the disk after each pass.
+
+CAUTION: this only works if the filesystem overwrites data in place.  It is NOT effective on
+copy-on-write filesystems (such as btrfs or ZFS), on filesystems that journal data (such as ext3
+or ext4 mounted with data=journal), on RAID, on filesystems that keep snapshots or backups, or on
+SSDs and other devices that remap writes.  The contents may also survive in swap or in caches.
+";
+
+const DEFAULT_ITERATIONS: &str = "3";
+
+const BUF_SIZE: usize = 64 * 1024;
+
+// the block size assumed if the filesystem does not report one
+const DEFAULT_BLOCK_SIZE: u64 = 512;
+
+#[derive(Debug, Fail)]
+enum ShredError {
+    #[fail(display = "{}: invalid file type", _0)]
+    InvalidFileType(String),
+
+    #[fail(display = "{}: cannot shred append-only file descriptor", _0)]
+    AppendOnly(String),
+
+    #[fail(display = "{}: failed to remove: {}", _0, _1)]
+    Remove(String, #[cause] io::Error),
+
+    #[fail(display = "{}: failed to sync: {}", _0, _1)]
+    Sync(String, #[cause] io::Error),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Pass {
+    Random,
+    Zero,
+}
+
+struct Options {
+    iterations: usize,
+    zero: bool,
+    remove: bool,
+    size: Option<u64>,
+    exact: bool,
+    verbose: bool,
+}
+
+/// A xorshift64* generator.  The data only has to be unrelated to what it overwrites rather than
+/// cryptographically strong, so this is plenty (and much faster than reading /dev/urandom).
+struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    fn new() -> Self {
+        let mut seed = [0; 8];
+        let state = match File::open("/dev/urandom").and_then(|mut file| file.read_exact(&mut seed)) {
+            Ok(()) => seed.iter().fold(0, |state, &b| state << 8 | u64::from(b)),
+            Err(_) => {
+                let time = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|time| time.as_secs() << 30 ^ u64::from(time.subsec_nanos()))
+                    .unwrap_or(0);
+                time ^ u64::from(process::id()) << 16
+            }
+        };
+        // the generator would be stuck at zero
+        Rng {
+            state: if state == 0 { 0x9e37_79b9_7f4a_7c15 } else { state },
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn fill(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(8) {
+            let value = self.next();
+            for (i, b) in chunk.iter_mut().enumerate() {
+                *b = (value >> (i * 8)) as u8;
+            }
+        }
+    }
+}
+
+pub fn execute<S, T>(setup: &mut S, args: T) -> Result<()>
+where
+    S: UtilSetup,
+    T: ArgsIter,
+{
+    let matches = {
+        let app = util_app!(NAME)
+                    .after_help(AFTER_HELP)
+                    .arg(Arg::with_name("iterations")
+                            .short("n")
+                            .long("iterations")
+                            .takes_value(true)
+                            .value_name("ITERATIONS")
+                            .default_value(DEFAULT_ITERATIONS)
+                            .validator(is_valid_count)
+                            .help("Overwrite with random data ITERATIONS times"))
+                    .arg(Arg::with_name("zero")
+                            .short("z")
+                            .long("zero")
+                            .help("Finish with a pass of zeros to hide the shredding"))
+                    .arg(Arg::with_name("remove")
+                            .short("u")
+                            .long("remove")
+                            .help("Truncate and remove each FILE after overwriting it"))
+                    .arg(Arg::with_name("size")
+                            .short("s")
+                            .long("size")
+                            .takes_value(true)
+                            .value_name("SIZE")
+                            .validator(is_valid_size)
+                            .help("Only shred the first SIZE bytes (suffixes like K, M and G are allowed)"))
+                    .arg(Arg::with_name("exact")
+                            .short("x")
+                            .long("exact")
+                            .help("Do not round the size of regular files up to a whole block"))
+                    .arg(Arg::with_name("verbose")
+                            .short("v")
+                            .long("verbose")
+                            .help("Show the progress of each pass"))
+                    .arg(Arg::with_name("FILE")
+                            .index(1)
+                            .multiple(true)
+                            .required(true));
+
+        app.get_matches_from_safe(args)?
+    };
+
+    // these .unwrap()s are fine because of the validators above
+    let options = Options {
+        iterations: matches.value_of("iterations").unwrap().parse().unwrap(),
+        zero: matches.is_present("zero"),
+        remove: matches.is_present("remove"),
+        size: matches
+            .value_of("size")
+            .map(|size| util::parse_num_with_suffix(size).unwrap() as u64),
+        exact: matches.is_present("exact"),
+        verbose: matches.is_present("verbose"),
+    };
+
+    let current_dir = setup.current_dir().map(|p| p.to_owned());
+    let stderr = setup.error();
+    let mut stderr = stderr.lock()?;
+
+    let mut rng = Rng::new();
+    let mut buffer = vec![0; BUF_SIZE];
+    let mut result = Ok(());
+    // FILE is required, so this .unwrap() is fine
+    for name in matches.values_of_os("FILE").unwrap() {
+        let path = util::actual_path(&current_dir, name);
+        let mut shredder = Shredder {
+            name: name,
+            path: &path,
+            options: &options,
+            rng: &mut rng,
+            buffer: &mut buffer,
+        };
+        if let Err(mut e) = shredder.shred(&mut stderr) {
+            display_msg!(stderr, "{}", e)?;
+            e.err = None;
+            result = Err(e);
+        }
+    }
+
+    result
+}
+
+struct Shredder<'a> {
+    name: &'a OsStr,
+    path: &'a Path,
+    options: &'a Options,
+    rng: &'a mut Rng,
+    buffer: &'a mut Vec<u8>,
+}
+
+impl<'a> Shredder<'a> {
+    fn shred<E: Write>(&mut self, stderr: &mut E) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .open(self.path)
+            .map_err(|e| PathError::new(PathOperation::OpenWrite, self.name, e))?;
+        let metadata = file
+            .metadata()
+            .map_err(|e| PathError::new(PathOperation::Stat, self.name, e))?;
+        let file_type = metadata.file_type();
+        let display_name = self.name.to_string_lossy().into_owned();
+
+        if !file_type.is_file() && !file_type.is_block_device() && !file_type.is_char_device() {
+            return Err(ShredError::InvalidFileType(display_name).into());
+        }
+
+        let size = match self.options.size {
+            Some(size) => size,
+            None if file_type.is_file() => {
+                let block_size = match metadata.blksize() {
+                    0 => DEFAULT_BLOCK_SIZE,
+                    size => size,
+                };
+                // the rest of the last block may still hold old data, so it is overwritten too
+                if self.options.exact {
+                    metadata.len()
+                } else {
+                    (metadata.len() + block_size - 1) / block_size * block_size
+                }
+            }
+            None => file
+                .seek(SeekFrom::End(0))
+                .map_err(|e| PathError::new(PathOperation::Stat, self.name, e))?,
+        };
+
+        let passes = self.options.iterations + if self.options.zero { 1 } else { 0 };
+        for number in 1..passes + 1 {
+            let pass = if number > self.options.iterations {
+                Pass::Zero
+            } else {
+                Pass::Random
+            };
+            if self.options.verbose {
+                let pattern = match pass {
+                    Pass::Random => "random",
+                    Pass::Zero => "000000",
+                };
+                display_msg!(stderr, "{}: pass {}/{} ({})...", display_name, number, passes, pattern)?;
+            }
+            self.overwrite(&mut file, size, pass)?;
+        }
+
+        if self.options.remove {
+            self.remove(&mut file, stderr)?;
+        }
+        Ok(())
+    }
+
+    // overwrite the first `size` bytes of `file` and wait for the data to reach the disk
+    fn overwrite(&mut self, file: &mut File, size: u64, pass: Pass) -> Result<()> {
+        let display_name = self.name.to_string_lossy().into_owned();
+
+        let pos = file
+            .seek(SeekFrom::Start(0))
+            .map_err(|e| PathError::new(PathOperation::Write, self.name, e))?;
+        // a file opened with O_APPEND elsewhere would just grow rather than be overwritten
+        if pos != 0 {
+            return Err(ShredError::AppendOnly(display_name).into());
+        }
+
+        if pass == Pass::Zero {
+            for b in self.buffer.iter_mut() {
+                *b = 0;
+            }
+        }
+
+        let mut remaining = size;
+        while remaining > 0 {
+            let len = remaining.min(self.buffer.len() as u64) as usize;
+            if pass == Pass::Random {
+                self.rng.fill(&mut self.buffer[..len]);
+            }
+            file.write_all(&self.buffer[..len])
+                .map_err(|e| PathError::new(PathOperation::Write, self.name, e))?;
+            remaining -= len as u64;
+        }
+
+        file.sync_data()
+            .map_err(|e| ShredError::Sync(display_name, e).into())
+    }
+
+    // truncate the file and then rename it a few times (to hide its name) before removing it
+    fn remove<E: Write>(&mut self, file: &mut File, stderr: &mut E) -> Result<()> {
+        let display_name = self.name.to_string_lossy().into_owned();
+        if self.options.verbose {
+            display_msg!(stderr, "{}: removing", display_name)?;
+        }
+
+        // devices cannot be truncated, but they are still removed
+        if file.metadata().map(|m| m.is_file()).unwrap_or(false) {
+            file.set_len(0)
+                .and_then(|_| file.sync_all())
+                .map_err(|e| PathError::new(PathOperation::Write, self.name, e))?;
+        }
+
+        let mut path = self.path.to_path_buf();
+        let mut shown = PathBuf::from(self.name);
+        let name_len = path.file_name().map(|name| name.len()).unwrap_or(0);
+        for len in (1..name_len + 1).rev() {
+            let new_name = OsString::from("0".repeat(len));
+            let new_path = path.with_file_name(&new_name);
+            if new_path == path || fs::symlink_metadata(&new_path).is_ok() {
+                continue;
+            }
+            if fs::rename(&path, &new_path).is_err() {
+                break;
+            }
+            let new_shown = shown.with_file_name(&new_name);
+            if self.options.verbose {
+                display_msg!(stderr, "{}: renamed to {}", shown.display(), new_shown.display())?;
+            }
+            path = new_path;
+            shown = new_shown;
+        }
+
+        fs::remove_file(&path).map_err(|e| ShredError::Remove(display_name.clone(), e))?;
+        if self.options.verbose {
+            display_msg!(stderr, "{}: removed", display_name)?;
+        }
+        Ok(())
+    }
+}
+
+fn is_valid_count(val: String) -> StdResult<(), String> {
+    val.parse::<usize>()
+        .map(|_| ())
+        .map_err(|_| format!("'{}' is not a valid number of iterations", val))
+}
+
+fn is_valid_size(val: String) -> StdResult<(), String> {
+    match util::parse_num_with_suffix(&val) {
+        Some(_) => Ok(()),
+        None => Err(format!("'{}' is not a valid size", val)),
+    }
+}
+
+#[test]
+fn shred_rng() {
+    let mut rng = Rng { state: 1 };
+    let mut buf = [0; 20];
+    rng.fill(&mut buf);
+    assert!(buf.iter().any(|&b| b != 0));
+
+    let mut other = [0; 20];
+    rng.fill(&mut other);
+    assert_ne!(buf, other);
+}
@@ -31,6 +31,7 @@
 //     SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 //
 
+use util;
 use {ArgsIter, Result, UtilSetup, UtilWrite};
 
 use clap::Arg;
@@ -38,14 +39,12 @@ use std::borrow::Cow;
 use std::ffi::OsStr;
 use std::io::Write;
 
-use util::OsStrExt;
-
 pub(crate) const NAME: &str = "yes";
 pub(crate) const DESCRIPTION: &str =
     "Repeatedly print 'y' or a series of user-provided strings to stdout";
 
 // it's possible that using a smaller or larger buffer might provide better performance on some
-// systems, but this is probably good enough
+// systems, but this is probably good enough (it can be changed using MESABOX_BUFSIZE if not)
 const BUF_SIZE: usize = 16 * 1024;
 
 pub fn execute<S, T>(setup: &mut S, args: T) -> Result<()>
@@ -54,11 +53,29 @@ where
     T: ArgsIter,
 {
     let matches = {
-        let app = util_app!(NAME).arg(Arg::with_name("STRING").index(1).multiple(true));
+        let app = util_app!(NAME)
+                    .arg(Arg::with_name("number")
+                            .long("number")
+                            .conflicts_with("STRING")
+                            .help("Print 1, 2, 3, ... on successive lines rather than a string"))
+                    .arg(Arg::with_name("STRING")
+                            .index(1)
+                            .multiple(true));
 
         app.get_matches_from_safe(args)?
     };
 
+    let buffer_size = setup.buffer_size().unwrap_or(BUF_SIZE);
+
+    if matches.is_present("number") {
+        let mut counter = Counter::new();
+        return run_generator(setup, buffer_size, |buffer| {
+            buffer.extend_from_slice(&counter.digits);
+            buffer.push(b'\n');
+            counter.increment();
+        });
+    }
+
     let string = if let Some(mut values) = matches.values_of_os("STRING") {
         let start = values.next().unwrap().to_owned();
         let mut result = values.fold(start, |mut res, s| {
@@ -73,10 +90,10 @@ where
         Cow::Borrowed(OsStr::new("y\n"))
     };
 
-    let bytes = string.try_as_bytes()?;
+    let bytes = util::os_str_as_bytes(&string);
 
-    let mut buffer = [0; BUF_SIZE];
-    let bytes = prepare_buffer(bytes, &mut buffer);
+    let mut buffer = vec![0; buffer_size];
+    let bytes = prepare_buffer(&bytes, &mut buffer);
 
     run(setup, bytes)?;
 
@@ -84,10 +101,10 @@ where
 }
 
 #[cfg(not(feature = "latency"))]
-fn prepare_buffer<'a>(input: &'a [u8], buffer: &'a mut [u8; BUF_SIZE]) -> &'a [u8] {
-    if input.len() < BUF_SIZE / 2 {
+fn prepare_buffer<'a>(input: &'a [u8], buffer: &'a mut [u8]) -> &'a [u8] {
+    if input.len() < buffer.len() / 2 {
         let mut size = 0;
-        while size < BUF_SIZE - input.len() {
+        while size < buffer.len() - input.len() {
             let (_, right) = buffer.split_at_mut(size);
             right[..input.len()].copy_from_slice(input);
             size += input.len();
@@ -99,7 +116,7 @@ fn prepare_buffer<'a>(input: &'a [u8], buffer: &'a mut [u8; BUF_SIZE]) -> &'a [u
 }
 
 #[cfg(feature = "latency")]
-fn prepare_buffer<'a>(input: &'a [u8], _buffer: &'a mut [u8; BUF_SIZE]) -> &'a [u8] {
+fn prepare_buffer<'a>(input: &'a [u8], _buffer: &'a mut [u8]) -> &'a [u8] {
     input
 }
 
@@ -113,3 +130,62 @@ where
         stdout.write_all(bytes)?;
     }
 }
+
+/// Write the lines produced by `next_line` (which appends a line to the buffer it is given) until
+/// writing fails.  This is slower than `run()`, so it is only used when the lines differ.
+pub fn run_generator<S, F>(setup: &mut S, buffer_size: usize, mut next_line: F) -> Result<()>
+where
+    S: UtilSetup,
+    F: FnMut(&mut Vec<u8>),
+{
+    // as with prepare_buffer(), the output is written a line at a time for the latency feature
+    let threshold = if cfg!(feature = "latency") { 1 } else { buffer_size };
+
+    let stdout = setup.output();
+    let mut stdout = stdout.lock()?;
+    let mut buffer = Vec::with_capacity(buffer_size);
+    loop {
+        while buffer.len() < threshold {
+            next_line(&mut buffer);
+        }
+        stdout.write_all(&buffer)?;
+        buffer.clear();
+    }
+}
+
+/// A decimal counter kept as ASCII digits, so it can be written without formatting and never
+/// overflows.
+struct Counter {
+    digits: Vec<u8>,
+}
+
+impl Counter {
+    fn new() -> Self {
+        Counter { digits: vec![b'1'] }
+    }
+
+    fn increment(&mut self) {
+        for digit in self.digits.iter_mut().rev() {
+            if *digit == b'9' {
+                *digit = b'0';
+            } else {
+                *digit += 1;
+                return;
+            }
+        }
+        self.digits.insert(0, b'1');
+    }
+}
+
+#[test]
+fn yes_counter() {
+    let mut counter = Counter::new();
+    for _ in 1..99 {
+        counter.increment();
+    }
+    assert_eq!(counter.digits, b"99");
+    counter.increment();
+    assert_eq!(counter.digits, b"100");
+    counter.increment();
+    assert_eq!(counter.digits, b"101");
+}
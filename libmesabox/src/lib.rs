@@ -59,17 +59,24 @@ extern crate log;
 extern crate tempfile;
 #[cfg(feature = "regex")]
 extern crate regex;
+#[cfg(feature = "flate2")]
+extern crate flate2;
+#[cfg(feature = "xz2")]
+extern crate xz2;
+#[cfg(feature = "zstd")]
+extern crate zstd;
 
 use clap::{App, SubCommand};
-use std::env::{self, VarsOs};
+use std::env;
 use std::ffi::{OsStr, OsString};
-use std::io::{self, BufRead, Read, Stderr, Stdin, Stdout, Write};
+use std::io::{self, BufRead, BufReader, Read, Stderr, Stdin, Stdout, Write};
 use std::iter;
 use std::path::{Path, PathBuf};
 use std::result::Result as StdResult;
 
 pub use util::{
-    AsRawObject, ExitCode, RawObject, UtilReadDyn, UtilWriteDyn, EXIT_FAILURE, EXIT_SUCCESS,
+    AsRawObject, EnvVars, ExitCode, RawObject, UtilReadDyn, UtilWriteDyn, WriteError,
+    EXIT_FAILURE, EXIT_SUCCESS,
 };
 
 pub use error::*;
@@ -85,6 +92,41 @@ mod setup;
 #[allow(dead_code)]
 mod util;
 
+// these utilities rely on APIs that simply do not exist outside of Unix-like systems, so rather
+// than fail with a confusing error somewhere in their implementation we refuse to build them at all
+macro_rules! unix_only {
+    ($($feature:tt),+) => {
+        $(
+            #[cfg(all(not(unix), feature = $feature))]
+            compile_error!(concat!(
+                "the ",
+                $feature,
+                " utility is only supported on Unix-like systems"
+            ));
+        )+
+    }
+}
+
+unix_only!(
+    "chmod",
+    "chroot",
+    "errno",
+    "find",
+    "getty",
+    "init",
+    "ls",
+    "mountpoint",
+    "nc",
+    "ping",
+    "reset",
+    "sh",
+    "shred",
+    "stty",
+    "tee",
+    "watch",
+    "xargs",
+);
+
 include!("util/build/execute.rs");
 
 macro_rules! generate_fns {
@@ -95,38 +137,47 @@ macro_rules! generate_fns {
 
 include!("util_list.rs");
 
-pub struct UtilData<'b, 'c, 'd, I, O, E, T>
+pub struct UtilData<'b, 'c, 'd, I, O, E>
 where
     I: for<'a> UtilRead<'a> + 'b,
     O: for<'a> UtilWrite<'a> + 'c,
     E: for<'a> UtilWrite<'a> + 'd,
-    T: Iterator<Item = (OsString, OsString)>,
 {
     pub stdin: &'b mut I,
     pub stdout: &'c mut O,
     pub stderr: &'d mut E,
-    pub env: T,
+    pub env: EnvVars,
     pub current_dir: Option<PathBuf>,
+    pub buffer_size: Option<usize>,
+    pub write_error: WriteError,
 }
 
-impl<'b, 'c, 'd, I, O, E, T> UtilData<'b, 'c, 'd, I, O, E, T>
+impl<'b, 'c, 'd, I, O, E> UtilData<'b, 'c, 'd, I, O, E>
 where
     I: for<'a> UtilRead<'a>,
     O: for<'a> UtilWrite<'a>,
     E: for<'a> UtilWrite<'a>,
-    T: Iterator<Item = (OsString, OsString)>,
 {
-    pub fn new(
+    /// Create the setup for a utility.  The settings that can be changed using environment
+    /// variables (such as `buffer_size`) are read from `env` rather than from the environment of
+    /// the process.
+    pub fn new<T>(
         stdin: &'b mut I,
         stdout: &'c mut O,
         stderr: &'d mut E,
         env: T,
         current_dir: Option<PathBuf>,
-    ) -> Self {
+    ) -> Self
+    where
+        T: IntoIterator<Item = (OsString, OsString)>,
+    {
+        let env: EnvVars = env.into_iter().collect();
         Self {
             stdin: stdin,
             stdout: stdout,
             stderr: stderr,
+            buffer_size: util::buffer_size_from_env(&env),
+            write_error: WriteError::from_env(&env),
             env: env,
             current_dir: current_dir,
         }
@@ -137,7 +188,6 @@ pub trait UtilSetup {
     type Input: for<'a> UtilRead<'a>;
     type Output: for<'a> UtilWrite<'a>;
     type Error: for<'a> UtilWrite<'a>;
-    type Env: Iterator<Item = (OsString, OsString)>;
 
     fn input<'a, 'b: 'a>(&'b mut self) -> &'a mut Self::Input;
     fn output<'a, 'b: 'a>(&'b mut self) -> &'a mut Self::Output;
@@ -151,22 +201,30 @@ pub trait UtilSetup {
         &'a mut Self::Error,
     );
 
-    fn env(&mut self) -> &mut Self::Env;
+    /// The environment variables of the utility, which are not necessarily the same as those of
+    /// the process.
+    fn env(&self) -> &EnvVars;
 
     fn current_dir(&self) -> Option<&Path>;
+
+    /// The size requested for I/O buffers (through `MESABOX_BUFSIZE`), if any.  Utilities that
+    /// use their own buffers fall back to their usual size when this is `None`.
+    fn buffer_size(&self) -> Option<usize>;
+
+    /// The policy for errors writing to outputs (through `MESABOX_OUTPUT_ERROR`).  By default, a
+    /// broken pipe quietly ends the utility with a successful exit code.
+    fn write_error(&self) -> WriteError;
 }
 
-impl<'b, 'c, 'd, I, O, E, T> UtilSetup for UtilData<'b, 'c, 'd, I, O, E, T>
+impl<'b, 'c, 'd, I, O, E> UtilSetup for UtilData<'b, 'c, 'd, I, O, E>
 where
     I: for<'a> UtilRead<'a>,
     O: for<'a> UtilWrite<'a>,
     E: for<'a> UtilWrite<'a>,
-    T: Iterator<Item = (OsString, OsString)>,
 {
     type Input = I;
     type Output = O;
     type Error = E;
-    type Env = T;
 
     fn input<'a, 'e: 'a>(&'e mut self) -> &'a mut Self::Input {
         self.stdin
@@ -190,13 +248,21 @@ where
         (self.stdin, self.stdout, self.stderr)
     }
 
-    fn env(&mut self) -> &mut Self::Env {
-        &mut self.env
+    fn env(&self) -> &EnvVars {
+        &self.env
     }
 
     fn current_dir(&self) -> Option<&Path> {
         self.current_dir.as_ref().map(|p| p.as_path())
     }
+
+    fn buffer_size(&self) -> Option<usize> {
+        self.buffer_size
+    }
+
+    fn write_error(&self) -> WriteError {
+        self.write_error
+    }
 }
 
 pub trait LockableRead<'a>: Read + Send + Sync {
@@ -260,7 +326,7 @@ impl EasyUtil {
         T: IntoIterator<IntoIter = V, Item = U>,
         U: Into<OsString> + AsRef<OsStr> + Clone,
         V: ArgsIter<ArgItem = U>,
-        F: Fn(&mut UtilData<'a, 'a, 'a, Stdin, Stdout, Stderr, VarsOs>, V) -> Result<ExitCode>,
+        F: Fn(&mut UtilData<'a, 'a, 'a, Stdin, Stdout, Stderr>, V) -> Result<ExitCode>,
     {
         let mut data = UtilData::new(
             &mut self.stdin,
@@ -273,6 +339,27 @@ impl EasyUtil {
     }
 }
 
+// the streams given to run_util(), used directly by the utility
+struct StreamInput<R>(BufReader<R>);
+
+struct StreamOutput<W>(W);
+
+impl<R: Read> Read for StreamInput<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl<W: Write> Write for StreamOutput<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
 pub fn execute<S, T, U, V>(setup: &mut S, args: T) -> Result<ExitCode>
 where
     S: UtilSetup,
@@ -301,6 +388,80 @@ where
     res
 }
 
+/// Run the utility `name` with the arguments `args` (not including the name itself) entirely
+/// within the current process, returning its exit code.
+///
+/// The utility reads its input from `stdin` and writes its output and error messages to `stdout`
+/// and `stderr`, just as it would when run as a separate program (including messages for invalid
+/// arguments and the output of `--help`).  It runs with the environment variables and the current
+/// directory of the process.  The streams are used as the utility runs, so it only reads as much
+/// of `stdin` as it needs and its output is written out as it is produced.  An error is only
+/// returned for `name` not being one of the utilities included in the build or for failing to
+/// flush the output.
+///
+/// Most utilities only depend on their arguments, streams and environment, so they can be run
+/// from several threads at once.  The exceptions change state shared by the whole process:
+///
+/// * `sh` changes the current directory of the process for `cd`, and may run other utilities
+/// * `chroot`, `getty` and `init` change the root directory, session or signal handling of the
+///   process and are not meant to be run as part of another program at all
+/// * `ping` waits for `SIGINT` and sends it to the whole process when done
+/// * `sed -i` and `sponge` install signal handlers to remove their temporary files when
+///   interrupted
+/// * `tee -i` ignores `SIGINT` while it runs
+///
+/// `find -exec`, `xargs` and `watch` are safe to run concurrently, but they do spawn child
+/// processes.  As the streams given here are not file descriptors the commands could inherit,
+/// `find -exec` and `xargs` collect the output of each command and write it to `stdout` and
+/// `stderr` once the command finishes.  Commands run by `find -exec` still read the standard
+/// input of the process rather than `stdin`.
+pub fn run_util<N, T, U, R, W, E>(
+    name: N,
+    args: T,
+    stdin: R,
+    stdout: W,
+    stderr: E,
+) -> io::Result<ExitCode>
+where
+    N: AsRef<OsStr>,
+    T: IntoIterator<Item = U>,
+    U: Into<OsString>,
+    R: Read + Send + Sync,
+    W: Write + Send + Sync,
+    E: Write + Send + Sync,
+{
+    let name = name.as_ref();
+
+    let mut input = StreamInput(BufReader::new(stdin));
+    let mut output = StreamOutput(stdout);
+    let mut error = StreamOutput(stderr);
+
+    let mut setup = UtilData::new(&mut input, &mut output, &mut error, env::vars_os(), None);
+    let mut args = iter::once(name.to_os_string()).chain(args.into_iter().map(Into::into));
+
+    let res = match execute_util(&mut setup, name, &mut args) {
+        Some(res) => handle_util_result(&mut setup, name, res),
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{}: utility not found", name.to_string_lossy()),
+            ))
+        }
+    };
+    let code = res.unwrap_or_else(|f| {
+        // broken pipes have already been handled according to setup.write_error
+        if f.err.is_some() {
+            let _ = writeln!(setup.stderr, "{}", f);
+        }
+        f.exitcode
+    });
+
+    setup.stdout.flush()?;
+    setup.stderr.flush()?;
+
+    Ok(code)
+}
+
 fn start<S, T>(setup: &mut S, args: &mut T) -> Option<Result<ExitCode>>
 where
     S: UtilSetup,
@@ -333,6 +494,10 @@ where
                 }
             }
         }
+        // broken pipes are handled here so that every utility treats them the same way
+        if setup.write_error().ignores(&mesa_err) {
+            return Ok(EXIT_SUCCESS);
+        }
         // TODO: check for --help and -V/--version probably
         if mesa_err.progname.is_none() {
             mesa_err.progname = Some(filename.to_os_string());
@@ -155,7 +155,6 @@ where
         values: &mut Option<OsValues<'b>>,
         mut archive: tar::Archive<R>,
     ) -> Result<()> {
-        use std::os::unix::ffi::OsStrExt;
         use std::str;
         // XXX: this is not complete, need to handle options and such
 
@@ -163,7 +162,7 @@ where
             Some(ref mut vals) => {
                 let mut set = GlobSetBuilder::new();
                 for val in vals {
-                    set.add(Glob::new(str::from_utf8(val.as_bytes())?)?);
+                    set.add(Glob::new(str::from_utf8(&util::os_str_as_bytes(val))?)?);
                 }
                 Some(set.build()?)
             }
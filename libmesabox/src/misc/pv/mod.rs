@@ -0,0 +1,451 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use util::progress::{human_size, Progress};
+use util::ratelimit::RateLimiter;
+use util;
+use {ArgsIter, MesaError, Result, UtilRead, UtilSetup, UtilWrite};
+
+use clap::{Arg, ArgMatches};
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::result::Result as StdResult;
+use std::time::Duration;
+
+pub(crate) const NAME: &str = "pv";
+pub(crate) const DESCRIPTION: &str = "Copy data to standard output while showing its progress";
+
+const BUF_SIZE: usize = 64 * 1024;
+
+const MARKER: &str = "<=>";
+
+/// The parts of the progress line that are shown.
+#[derive(Clone, Copy, Default)]
+struct Components {
+    bytes: bool,
+    timer: bool,
+    eta: bool,
+    rate: bool,
+    average_rate: bool,
+    progress: bool,
+}
+
+impl Components {
+    fn from_matches(matches: &ArgMatches) -> Self {
+        let components = Self {
+            bytes: matches.is_present("bytes"),
+            timer: matches.is_present("timer"),
+            eta: matches.is_present("eta"),
+            rate: matches.is_present("rate"),
+            average_rate: matches.is_present("average-rate"),
+            progress: matches.is_present("progress"),
+        };
+        // like the original pv, everything but the average rate is shown by default
+        if components.is_empty() {
+            Self {
+                bytes: true,
+                timer: true,
+                eta: true,
+                rate: true,
+                average_rate: false,
+                progress: true,
+            }
+        } else {
+            components
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        !(self.bytes || self.timer || self.eta || self.rate || self.average_rate || self.progress)
+    }
+}
+
+/// Produces the progress lines.
+struct Meter {
+    components: Components,
+    size: Option<u64>,
+    width: usize,
+    // the bytes copied and the elapsed time at the previous report, for the current rate
+    last: (u64, Duration),
+    reports: usize,
+}
+
+impl Meter {
+    fn format(&mut self, current: u64, elapsed: Duration) -> String {
+        let mut left = vec![];
+        if self.components.bytes {
+            left.push(human_size(current));
+        }
+        if self.components.timer {
+            left.push(format_time(elapsed));
+        }
+        if self.components.rate {
+            let (last_bytes, last_elapsed) = self.last;
+            let rate = rate(current - last_bytes, elapsed - last_elapsed);
+            left.push(format!("[{}/s]", human_size(rate)));
+        }
+        if self.components.average_rate {
+            left.push(format!("[{}/s]", human_size(rate(current, elapsed))));
+        }
+
+        let mut right = vec![];
+        if self.components.eta {
+            if let Some(size) = self.size {
+                let rate = rate(current, elapsed);
+                let eta = if current >= size {
+                    Some(Duration::from_secs(0))
+                } else if rate > 0 {
+                    Some(Duration::from_secs((size - current + rate - 1) / rate))
+                } else {
+                    None
+                };
+                match eta {
+                    Some(eta) => right.push(format!("ETA {}", format_time(eta))),
+                    None => right.push("ETA --:--:--".to_owned()),
+                }
+            }
+        }
+
+        if self.components.progress {
+            let used: usize = left.iter().chain(right.iter()).map(|part| part.len() + 1).sum();
+            // the last column is left empty so that terminals don't wrap the line
+            let available = self.width.saturating_sub(used + 1);
+            if let Some(bar) = self.progress_bar(current, available) {
+                left.push(bar);
+            }
+        }
+
+        self.last = (current, elapsed);
+        self.reports += 1;
+
+        left.extend(right);
+        left.join(" ")
+    }
+
+    // a bar filling `width` columns that fills up as the data is copied (or, if the total size is
+    // unknown, a marker that moves back and forth to show that something is happening)
+    fn progress_bar(&self, current: u64, width: usize) -> Option<String> {
+        match self.size {
+            Some(size) => {
+                let percent = if size > 0 {
+                    (current.min(size) * 100 / size) as usize
+                } else {
+                    100
+                };
+                let percent_str = format!(" {:3}%", percent);
+                let inner = match width.checked_sub(2 + percent_str.len()) {
+                    Some(inner) if inner > 0 => inner,
+                    _ => return None,
+                };
+                let filled = inner * percent / 100;
+                let mut bar = "[".to_owned();
+                if filled > 0 {
+                    bar.push_str(&"=".repeat(filled - 1));
+                    bar.push('>');
+                }
+                bar.push_str(&" ".repeat(inner - filled));
+                bar.push(']');
+                bar.push_str(&percent_str);
+                Some(bar)
+            }
+            None => {
+                let inner = match width.checked_sub(2) {
+                    Some(inner) if inner >= MARKER.len() => inner,
+                    _ => return None,
+                };
+                // bounce between the two ends of the bar
+                let span = inner - MARKER.len();
+                let pos = if span == 0 {
+                    0
+                } else {
+                    let step = self.reports % (span * 2);
+                    if step <= span {
+                        step
+                    } else {
+                        span * 2 - step
+                    }
+                };
+                Some(format!(
+                    "[{}{}{}]",
+                    " ".repeat(pos),
+                    MARKER,
+                    " ".repeat(span - pos)
+                ))
+            }
+        }
+    }
+}
+
+pub fn execute<S, T>(setup: &mut S, args: T) -> Result<()>
+where
+    S: UtilSetup,
+    T: ArgsIter,
+{
+    let matches = {
+        let app = util_app!(NAME)
+                    .arg(Arg::with_name("progress")
+                            .short("p")
+                            .long("progress")
+                            .help("Show a progress bar"))
+                    .arg(Arg::with_name("timer")
+                            .short("t")
+                            .long("timer")
+                            .help("Show the time elapsed since the start"))
+                    .arg(Arg::with_name("eta")
+                            .short("e")
+                            .long("eta")
+                            .help("Show the estimated time until the copy is finished (if the size is known)"))
+                    .arg(Arg::with_name("rate")
+                            .short("r")
+                            .long("rate")
+                            .help("Show the current transfer rate"))
+                    .arg(Arg::with_name("bytes")
+                            .short("b")
+                            .long("bytes")
+                            .help("Show the amount of data copied so far"))
+                    .arg(Arg::with_name("average-rate")
+                            .short("a")
+                            .long("average-rate")
+                            .help("Show the average transfer rate since the start"))
+                    .arg(Arg::with_name("force")
+                            .short("f")
+                            .long("force")
+                            .help("Show the progress line even if standard error is not a terminal"))
+                    .arg(Arg::with_name("size")
+                            .short("s")
+                            .long("size")
+                            .takes_value(true)
+                            .value_name("SIZE")
+                            .validator(is_valid_size)
+                            .help("Assume the total amount of data is SIZE bytes (which is needed for -e and a full progress bar when reading from a pipe)"))
+                    .arg(Arg::with_name("rate-limit")
+                            .short("L")
+                            .long("rate-limit")
+                            .takes_value(true)
+                            .value_name("RATE")
+                            .validator(is_valid_rate)
+                            .help("Copy at most RATE bytes per second"))
+                    .arg(Arg::with_name("FILES")
+                            .index(1)
+                            .multiple(true));
+
+        app.get_matches_from_safe(args)?
+    };
+
+    // these .unwrap()s are fine because of the validators
+    let rate_limit = matches
+        .value_of("rate-limit")
+        .map(|rate| util::parse_num_with_suffix(rate).unwrap() as u64);
+    let files: Vec<&OsStr> = match matches.values_of_os("FILES") {
+        Some(files) => files.collect(),
+        None => vec![OsStr::new("-")],
+    };
+
+    let current_dir = setup.current_dir().map(|p| p.to_owned());
+    let size = match matches.value_of("size") {
+        Some(size) => Some(util::parse_num_with_suffix(size).unwrap() as u64),
+        None => total_size(&current_dir, &files),
+    };
+
+    let stderr_fd = setup.error().raw_object();
+    let mut progress = Progress::new(stderr_fd, size);
+    if matches.is_present("force") {
+        progress.force();
+    }
+    let mut meter = Meter {
+        components: Components::from_matches(&matches),
+        size: size,
+        width: util::terminal_width(setup.env(), stderr_fd),
+        last: (0, Duration::from_secs(0)),
+        reports: 0,
+    };
+
+    let (input, output, error) = setup.stdio();
+    let mut output = output.lock()?;
+    let mut err_stream = error.lock()?;
+
+    let limiter = rate_limit.map(RateLimiter::new);
+    let chunk_size = match limiter {
+        Some(ref limiter) => (limiter.chunk_size() as usize).min(BUF_SIZE),
+        None => BUF_SIZE,
+    };
+    let mut copier = Copier {
+        buffer: vec![0; chunk_size],
+        copied: 0,
+        limiter: limiter,
+    };
+
+    let mut result = Ok(());
+    for file in files {
+        let res = if file == OsStr::new("-") {
+            input.lock().map_err(MesaError::from).and_then(|mut stdin| {
+                copier.copy(&mut stdin, &mut output, &mut err_stream, &mut progress, &mut meter)
+            })
+        } else {
+            util::open_file(&current_dir, file).and_then(|mut file| {
+                copier.copy(&mut file, &mut output, &mut err_stream, &mut progress, &mut meter)
+            })
+        };
+
+        if let Err(mut e) = res {
+            if e.is_broken_pipe() {
+                return Err(e);
+            }
+            display_msg!(err_stream, "{}", e)?;
+            e.err = None;
+            result = Err(e);
+        }
+    }
+
+    output.flush()?;
+    let copied = copier.copied;
+    progress.finish_with(&mut err_stream, copied, |current, elapsed| {
+        meter.format(current, elapsed)
+    })?;
+
+    result
+}
+
+struct Copier {
+    buffer: Vec<u8>,
+    copied: u64,
+    limiter: Option<RateLimiter>,
+}
+
+impl Copier {
+    fn copy<R, W, E>(
+        &mut self,
+        input: &mut R,
+        output: &mut W,
+        err_stream: &mut E,
+        progress: &mut Progress,
+        meter: &mut Meter,
+    ) -> Result<()>
+    where
+        R: Read,
+        W: Write,
+        E: Write,
+    {
+        loop {
+            let count = match input.read(&mut self.buffer) {
+                Ok(0) => return Ok(()),
+                Ok(count) => count,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            output.write_all(&self.buffer[..count])?;
+            self.copied += count as u64;
+
+            if let Some(ref mut limiter) = self.limiter {
+                limiter.consume(count as u64);
+            }
+
+            progress.update_with(err_stream, self.copied, |current, elapsed| {
+                meter.format(current, elapsed)
+            })?;
+        }
+    }
+}
+
+// the combined size of `files`, if they are all regular files
+fn total_size(current_dir: &Option<PathBuf>, files: &[&OsStr]) -> Option<u64> {
+    let mut total = 0;
+    for file in files {
+        if *file == OsStr::new("-") {
+            return None;
+        }
+        match fs::metadata(util::actual_path(current_dir, file)) {
+            Ok(ref meta) if meta.is_file() => total += meta.len(),
+            _ => return None,
+        }
+    }
+    Some(total)
+}
+
+fn rate(bytes: u64, elapsed: Duration) -> u64 {
+    let secs = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9;
+    if secs > 0.0 {
+        (bytes as f64 / secs) as u64
+    } else {
+        0
+    }
+}
+
+// format `time` as H:MM:SS
+fn format_time(time: Duration) -> String {
+    let secs = time.as_secs();
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+fn is_valid_size(val: String) -> StdResult<(), String> {
+    match util::parse_num_with_suffix(&val) {
+        Some(_) => Ok(()),
+        None => Err(format!("invalid size '{}'", val)),
+    }
+}
+
+fn is_valid_rate(val: String) -> StdResult<(), String> {
+    match util::parse_num_with_suffix(&val) {
+        Some(0) => Err(format!("rate must be at least 1: '{}'", val)),
+        Some(_) => Ok(()),
+        None => Err(format!("invalid rate '{}'", val)),
+    }
+}
+
+#[cfg(test)]
+fn test_meter(components: Components, size: Option<u64>, width: usize) -> Meter {
+    Meter {
+        components: components,
+        size: size,
+        width: width,
+        last: (0, Duration::from_secs(0)),
+        reports: 0,
+    }
+}
+
+#[test]
+fn meter_lines() {
+    let all = Components {
+        bytes: true,
+        timer: true,
+        eta: true,
+        rate: true,
+        average_rate: true,
+        progress: false,
+    };
+    let mut meter = test_meter(all, Some(4000), 80);
+    assert_eq!(
+        meter.format(1000, Duration::from_secs(1)),
+        "1.0 kB 0:00:01 [1.0 kB/s] [1.0 kB/s] ETA 0:00:03"
+    );
+    // the current rate only counts what was copied since the previous line
+    assert_eq!(
+        meter.format(1500, Duration::from_secs(2)),
+        "1.5 kB 0:00:02 [500 B/s] [750 B/s] ETA 0:00:04"
+    );
+
+    let bar = Components {
+        progress: true,
+        ..Components::default()
+    };
+    let mut meter = test_meter(bar, Some(100), 20);
+    assert_eq!(meter.format(50, Duration::from_secs(1)), "[=====>      ]  50%");
+    assert_eq!(meter.format(100, Duration::from_secs(2)), "[===========>] 100%");
+
+    let mut meter = test_meter(bar, None, 10);
+    assert_eq!(meter.format(0, Duration::from_secs(0)), "[<=>    ]");
+    assert_eq!(meter.format(0, Duration::from_secs(0)), "[ <=>   ]");
+}
+
+#[test]
+fn times() {
+    assert_eq!(format_time(Duration::from_secs(5)), "0:00:05");
+    assert_eq!(format_time(Duration::from_secs(3 * 3600 + 61)), "3:01:01");
+}
@@ -0,0 +1,161 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use error::io_error_msg;
+use {ArgsIter, MesaError, Result, UtilSetup, UtilWrite, EXIT_FAILURE};
+
+use clap::{Arg, ArgGroup};
+use nix::errno::Errno;
+use std::io::{self, Write};
+
+pub(crate) const NAME: &str = "errno";
+pub(crate) const DESCRIPTION: &str = "Look up errno values by number or name";
+
+// no system uses errno values anywhere close to this large
+const MAX_ERRNO: i32 = 4096;
+
+#[derive(Debug, Fail)]
+enum ErrnoError {
+    #[fail(display = "unknown errno value or name '{}'", _0)]
+    Unknown(String),
+}
+
+/// One known errno value.
+struct Entry {
+    name: String,
+    value: i32,
+    message: String,
+}
+
+impl Entry {
+    fn new(errno: Errno) -> Self {
+        let value = errno as i32;
+        Self {
+            name: format!("{:?}", errno),
+            value: value,
+            message: io_error_msg(&io::Error::from_raw_os_error(value)),
+        }
+    }
+
+    fn write<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        writeln!(output, "{} {} {}", self.name, self.value, self.message)
+    }
+}
+
+// every errno value nix knows about, in numerical order
+fn entries() -> Vec<Entry> {
+    (1..MAX_ERRNO)
+        .map(Errno::from_i32)
+        .filter(|&errno| errno != Errno::UnknownErrno)
+        .map(Entry::new)
+        .collect()
+}
+
+// find the entry for a number (like 2) or a name (like ENOENT, in any case)
+fn lookup<'a>(entries: &'a [Entry], query: &str) -> Option<&'a Entry> {
+    match query.parse::<i32>() {
+        Ok(value) => entries.iter().find(|entry| entry.value == value),
+        Err(_) => entries.iter().find(|entry| entry.name.eq_ignore_ascii_case(query)),
+    }
+}
+
+// whether every word in `words` appears in the message of `entry` (ignoring case)
+fn matches_search(entry: &Entry, words: &[String]) -> bool {
+    let message = entry.message.to_lowercase();
+    words.iter().all(|word| message.contains(word))
+}
+
+pub fn execute<S, T>(setup: &mut S, args: T) -> Result<()>
+where
+    S: UtilSetup,
+    T: ArgsIter,
+{
+    let matches = {
+        let app = util_app!(NAME)
+                    .group(ArgGroup::with_name("mode")
+                            .args(&["list", "search"]))
+                    .arg(Arg::with_name("list")
+                            .short("l")
+                            .long("list")
+                            .conflicts_with("WORDS")
+                            .help("List every known errno value"))
+                    .arg(Arg::with_name("search")
+                            .short("s")
+                            .long("search")
+                            .requires("WORDS")
+                            .help("List the errno values whose messages contain all of WORDS"))
+                    .arg(Arg::with_name("WORDS")
+                            .index(1)
+                            .multiple(true)
+                            .required_unless("list")
+                            .help("The numbers or names of errno values to show"));
+
+        app.get_matches_from_safe(args)?
+    };
+
+    let entries = entries();
+    let words = matches.values_of_lossy("WORDS").unwrap_or_else(Vec::new);
+
+    let (_, output, error) = setup.stdio();
+    let mut output = output.lock()?;
+
+    if matches.is_present("list") {
+        for entry in &entries {
+            entry.write(&mut output)?;
+        }
+        return Ok(());
+    }
+
+    if matches.is_present("search") {
+        let words: Vec<String> = words.iter().map(|word| word.to_lowercase()).collect();
+        let mut found = false;
+        for entry in entries.iter().filter(|entry| matches_search(entry, &words)) {
+            entry.write(&mut output)?;
+            found = true;
+        }
+        return if found {
+            Ok(())
+        } else {
+            Err(MesaError::new(None, EXIT_FAILURE, None))
+        };
+    }
+
+    let mut err_stream = error.lock()?;
+    let mut result = Ok(());
+    for word in &words {
+        match lookup(&entries, word) {
+            Some(entry) => entry.write(&mut output)?,
+            None => {
+                let mut e = MesaError::from(ErrnoError::Unknown(word.clone()));
+                display_msg!(err_stream, "{}", e)?;
+                e.err = None;
+                result = Err(e);
+            }
+        }
+    }
+
+    result
+}
+
+#[test]
+fn errno_lookup() {
+    let entries = entries();
+
+    let entry = lookup(&entries, "ENOENT").unwrap();
+    assert_eq!(entry.value, Errno::ENOENT as i32);
+    assert_eq!(entry.message, "No such file or directory");
+    assert_eq!(lookup(&entries, "enoent").unwrap().value, entry.value);
+    assert_eq!(lookup(&entries, &entry.value.to_string()).unwrap().name, "ENOENT");
+
+    assert!(lookup(&entries, "ENOTANERROR").is_none());
+    assert!(lookup(&entries, "0").is_none());
+
+    let words = vec!["such".to_owned(), "file".to_owned()];
+    assert!(matches_search(entry, &words));
+    assert!(!matches_search(lookup(&entries, "EPERM").unwrap(), &words));
+}
@@ -0,0 +1,111 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use util;
+use util::backup::{self, Backup};
+use util::inplace::InPlace;
+use util::spill::{self, SpillBuffer};
+use {ArgsIter, PathError, PathOperation, Result, UtilRead, UtilSetup, UtilWrite};
+
+use clap::Arg;
+use std::ffi::OsStr;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+pub(crate) const NAME: &str = "sponge";
+pub(crate) const DESCRIPTION: &str = "Soak up all of standard input before writing it to a file";
+
+const AFTER_HELP: &str = "
+As nothing is written until the input has ended, FILE may also be one of the files the input is
+being read from (as in 'sort file | sponge file').  A FILE that is a regular file is replaced
+atomically, keeping its permissions, so it never appears partially written.
+
+With --backup, the old contents of a FILE that is replaced are kept as a backup.  CONTROL is one of
+none (or off), numbered (or t), existing (or nil), and simple (or never), defaulting to
+VERSION_CONTROL or existing.  Simple backups end with '~' unless changed by --suffix or
+SIMPLE_BACKUP_SUFFIX.
+";
+
+pub fn execute<S, T>(setup: &mut S, args: T) -> Result<()>
+where
+    S: UtilSetup,
+    T: ArgsIter,
+{
+    let matches = {
+        let app = util_app!(NAME)
+                    .after_help(AFTER_HELP)
+                    .arg(Arg::with_name("append")
+                            .short("a")
+                            .long("append")
+                            .help("Append to FILE rather than replacing it"))
+                    .args(&backup::backup_args())
+                    .arg(Arg::with_name("FILE")
+                            .index(1)
+                            .help("The file to write to (standard output if not given)"));
+
+        app.get_matches_from_safe(args)?
+    };
+
+    let append = matches.is_present("append");
+    let backup = Backup::from_matches(&matches, setup.env())?;
+    let current_dir = setup.current_dir().map(|p| p.to_owned());
+    let (input, output, _) = setup.stdio();
+
+    // large inputs go to a temporary file rather than being held in memory
+    let mut buffer = SpillBuffer::new(spill::DEFAULT_THRESHOLD);
+    io::copy(&mut input.lock()?, &mut buffer)?;
+
+    match matches.value_of_os("FILE") {
+        Some(path) => write_file(&current_dir, path, &mut buffer, append, &backup),
+        None => {
+            let mut output = output.lock()?;
+            io::copy(&mut buffer.reader()?, &mut output)?;
+            output.flush()?;
+            Ok(())
+        }
+    }
+}
+
+fn write_file(
+    current_dir: &Option<PathBuf>,
+    path: &OsStr,
+    buffer: &mut SpillBuffer,
+    append: bool,
+    backup: &Backup,
+) -> Result<()> {
+    let actual = util::actual_path(current_dir, path);
+
+    if !append {
+        // the target of a symbolic link is replaced rather than the link itself
+        if let Ok(target) = fs::canonicalize(&actual) {
+            match fs::metadata(&target) {
+                Ok(ref metadata) if metadata.is_file() => {
+                    let mut replacement = InPlace::new(&target, metadata)?;
+                    io::copy(&mut buffer.reader()?, replacement.file())
+                        .map_err(|e| PathError::new(PathOperation::Write, path, e))?;
+                    return replacement.commit_with_backup(backup).map(|_| ());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // new files and things like devices and pipes are just written to directly
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(append)
+        .truncate(!append)
+        .open(&actual)
+        .map_err(|e| PathError::new(PathOperation::OpenWrite, path, e))?;
+    io::copy(&mut buffer.reader()?, &mut file)
+        .map_err(|e| PathError::new(PathOperation::Write, path, e))?;
+
+    Ok(())
+}
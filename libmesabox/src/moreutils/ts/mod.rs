@@ -0,0 +1,278 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use util::LineDelimiter;
+use {ArgsIter, Result, UtilRead, UtilSetup, UtilWrite};
+
+use chrono::format::{self, Item, Parsed, StrftimeItems};
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, TimeZone};
+use clap::{Arg, ArgGroup};
+use std::io::Write;
+use std::result::Result as StdResult;
+use std::str;
+
+pub(crate) const NAME: &str = "ts";
+pub(crate) const DESCRIPTION: &str = "Prefix each line of the input with a timestamp";
+
+const AFTER_HELP: &str = "
+FORMAT uses the conversions of strftime(3).  The default is '%b %d %H:%M:%S', or '%H:%M:%S' with
+-i and -s.
+
+With -r, timestamps at the start of each line (in the default format, like 'Oct 15 10:53:00', or
+like '2018-10-15 10:53:00') are replaced by how long ago they were, or by the time in FORMAT if it
+is given.  Other lines are left alone.
+";
+
+const DEFAULT_FORMAT: &str = "%b %d %H:%M:%S";
+const DEFAULT_RELATIVE_FORMAT: &str = "%H:%M:%S";
+
+// the timestamps recognized by -r, along with their lengths and whether they include the year
+const KNOWN_FORMATS: &[(&str, usize, bool)] = &[
+    ("%Y-%m-%dT%H:%M:%S", 19, true),
+    ("%Y-%m-%d %H:%M:%S", 19, true),
+    ("%b %e %H:%M:%S", 15, false),
+];
+
+const AGO_UNITS: &[(i64, &str)] = &[
+    (365 * 24 * 60 * 60, "y"),
+    (24 * 60 * 60, "d"),
+    (60 * 60, "h"),
+    (60, "m"),
+    (1, "s"),
+];
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Mode {
+    Absolute,
+    // the time since the previous line
+    Incremental,
+    // the time since ts started
+    SinceStart,
+    // convert the timestamps already in the input
+    Convert,
+}
+
+struct Stamper {
+    mode: Mode,
+    format: Option<String>,
+    start: DateTime<Local>,
+    last: DateTime<Local>,
+}
+
+impl Stamper {
+    fn new(mode: Mode, format: Option<String>) -> Self {
+        let now = Local::now();
+        Self {
+            mode: mode,
+            format: format,
+            start: now,
+            last: now,
+        }
+    }
+
+    fn write_line<W: Write>(&mut self, output: &mut W, line: &[u8]) -> Result<()> {
+        let now = Local::now();
+        match self.mode {
+            Mode::Absolute => {
+                let format = self.format.as_ref().map(|f| &f[..]).unwrap_or(DEFAULT_FORMAT);
+                write!(output, "{} ", now.format(format))?;
+            }
+            Mode::Incremental | Mode::SinceStart => {
+                let since = if self.mode == Mode::Incremental {
+                    self.last
+                } else {
+                    self.start
+                };
+                let elapsed = now.signed_duration_since(since);
+                // formatting the elapsed time as a time after the epoch gives e.g. 00:01:05
+                let time = NaiveDateTime::from_timestamp(
+                    elapsed.num_seconds(),
+                    (elapsed.num_nanoseconds().unwrap_or(0) % 1_000_000_000) as u32,
+                );
+                let format = self.format
+                    .as_ref()
+                    .map(|f| &f[..])
+                    .unwrap_or(DEFAULT_RELATIVE_FORMAT);
+                write!(output, "{} ", time.format(format))?;
+            }
+            Mode::Convert => {
+                if let Some((time, len)) = parse_timestamp(line, now) {
+                    match self.format {
+                        Some(ref format) => write!(output, "{}", time.format(format))?,
+                        None => {
+                            let secs = now.signed_duration_since(time).num_seconds();
+                            write!(output, "{}", ago(secs))?;
+                        }
+                    }
+                    output.write_all(&line[len..])?;
+                    self.last = now;
+                    return Ok(());
+                }
+            }
+        }
+        output.write_all(line)?;
+        self.last = now;
+
+        Ok(())
+    }
+}
+
+pub fn execute<S, T>(setup: &mut S, args: T) -> Result<()>
+where
+    S: UtilSetup,
+    T: ArgsIter,
+{
+    let matches = {
+        let app = util_app!(NAME)
+                    .after_help(AFTER_HELP)
+                    .group(ArgGroup::with_name("mode")
+                            .args(&["incremental", "since-start", "relative"]))
+                    .arg(Arg::with_name("incremental")
+                            .short("i")
+                            .help("Show the time since the previous line"))
+                    .arg(Arg::with_name("since-start")
+                            .short("s")
+                            .help("Show the time since ts started"))
+                    .arg(Arg::with_name("relative")
+                            .short("r")
+                            .help("Convert the timestamps in the input rather than adding new ones"))
+                    .arg(Arg::with_name("FORMAT")
+                            .index(1)
+                            .validator(is_valid_format));
+
+        app.get_matches_from_safe(args)?
+    };
+
+    let mode = if matches.is_present("incremental") {
+        Mode::Incremental
+    } else if matches.is_present("since-start") {
+        Mode::SinceStart
+    } else if matches.is_present("relative") {
+        Mode::Convert
+    } else {
+        Mode::Absolute
+    };
+    let mut stamper = Stamper::new(mode, matches.value_of("FORMAT").map(|f| f.to_owned()));
+
+    let (input, output, _) = setup.stdio();
+    let mut input = input.lock()?;
+    let mut output = output.lock()?;
+
+    let mut line = vec![];
+    loop {
+        line.clear();
+        if LineDelimiter::Newline.read_line(&mut input, &mut line)? == 0 {
+            break;
+        }
+        stamper.write_line(&mut output, &line)?;
+        // the point of the timestamps is to see when lines arrive, so don't hold on to them
+        output.flush()?;
+    }
+
+    Ok(())
+}
+
+fn is_valid_format(val: String) -> StdResult<(), String> {
+    if StrftimeItems::new(&val).any(|item| item == Item::Error) {
+        Err(format!("'{}' is not a valid format", val))
+    } else {
+        Ok(())
+    }
+}
+
+// find a timestamp in one of KNOWN_FORMATS at the start of `line`, returning it along with its
+// length.  timestamps without a year are assumed to be from within the past year
+fn parse_timestamp(line: &[u8], now: DateTime<Local>) -> Option<(DateTime<Local>, usize)> {
+    for &(fmt, len, has_year) in KNOWN_FORMATS {
+        let text = match line.get(..len).and_then(|text| str::from_utf8(text).ok()) {
+            Some(text) => text,
+            None => continue,
+        };
+
+        let mut parsed = Parsed::new();
+        if format::parse(&mut parsed, text, StrftimeItems::new(fmt)).is_err() {
+            continue;
+        }
+        if has_year {
+            if let Some(time) = to_local(&parsed) {
+                return Some((time, len));
+            }
+        } else {
+            let _ = parsed.set_year(i64::from(now.year()));
+            let this_year = to_local(&parsed);
+            match this_year {
+                Some(ref time) if *time > now => {
+                    parsed.year = None;
+                    let _ = parsed.set_year(i64::from(now.year() - 1));
+                    if let Some(time) = to_local(&parsed) {
+                        return Some((time, len));
+                    }
+                }
+                Some(time) => return Some((time, len)),
+                None => {}
+            }
+        }
+    }
+
+    None
+}
+
+fn to_local(parsed: &Parsed) -> Option<DateTime<Local>> {
+    let naive = parsed.to_naive_datetime_with_offset(0).ok()?;
+    Local.from_local_datetime(&naive).single()
+}
+
+// describe a number of seconds in the past using its two largest units (like "1h5m ago")
+fn ago(secs: i64) -> String {
+    if secs <= 0 {
+        return "just now".to_owned();
+    }
+
+    let mut result = String::new();
+    let mut remaining = secs;
+    let units = AGO_UNITS.iter().skip_while(|&&(size, _)| size > secs).take(2);
+    for &(size, name) in units {
+        let count = remaining / size;
+        remaining %= size;
+        if count > 0 {
+            result.push_str(&format!("{}{}", count, name));
+        }
+    }
+    result.push_str(" ago");
+    result
+}
+
+#[test]
+fn ago_units() {
+    assert_eq!(ago(0), "just now");
+    assert_eq!(ago(5), "5s ago");
+    assert_eq!(ago(65), "1m5s ago");
+    assert_eq!(ago(3600), "1h ago");
+    assert_eq!(ago(3725), "1h2m ago");
+    assert_eq!(ago(2 * 24 * 60 * 60 + 30), "2d ago");
+}
+
+#[test]
+fn timestamps() {
+    let now = Local.ymd(2018, 10, 15).and_hms(12, 0, 0);
+
+    let (time, len) = parse_timestamp(b"2018-10-15 11:59:00 event\n", now).unwrap();
+    assert_eq!(len, 19);
+    assert_eq!(time, Local.ymd(2018, 10, 15).and_hms(11, 59, 0));
+    assert!(parse_timestamp(b"2018-10-15T11:59:00", now).is_some());
+
+    // the year is guessed so that the time is in the past
+    let (time, len) = parse_timestamp(b"Oct  5 08:00:00 event", now).unwrap();
+    assert_eq!(len, 15);
+    assert_eq!(time, Local.ymd(2018, 10, 5).and_hms(8, 0, 0));
+    let (time, _) = parse_timestamp(b"Dec 24 08:00:00 event", now).unwrap();
+    assert_eq!(time, Local.ymd(2017, 12, 24).and_hms(8, 0, 0));
+
+    assert!(parse_timestamp(b"no timestamp here", now).is_none());
+    assert!(parse_timestamp(b"2018-10-15", now).is_none());
+}
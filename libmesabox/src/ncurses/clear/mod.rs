@@ -0,0 +1,112 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use util::terminfo::{self, TermInfo};
+use util::{self, EnvVars};
+use {ArgsIter, Result, UtilSetup, UtilWrite};
+
+use clap::{Arg, ArgMatches};
+use std::io::Write;
+
+pub(crate) const NAME: &str = "clear";
+pub(crate) const DESCRIPTION: &str = "Clear the terminal screen";
+
+const AFTER_HELP: &str = "
+The sequence used is read from the terminfo entry for the terminal (given by TERM if -T is not
+used).  If there is no entry, the sequence for ANSI terminals is used.  Nothing is written if
+standard output is not a terminal unless --force is given.
+";
+
+// used when there is no terminfo entry for the terminal
+const ANSI_CLEAR: &[u8] = b"\x1b[H\x1b[2J";
+const ANSI_CLEAR_SCROLLBACK: &[u8] = b"\x1b[3J";
+
+pub fn execute<S, T>(setup: &mut S, args: T) -> Result<()>
+where
+    S: UtilSetup,
+    T: ArgsIter,
+{
+    let matches = {
+        let app = util_app!(NAME)
+                    .after_help(AFTER_HELP)
+                    .arg(Arg::with_name("keep-scrollback")
+                            .short("x")
+                            .help("Do not clear the scrollback buffer"))
+                    .arg(term_arg())
+                    .arg(Arg::with_name("force")
+                            .short("f")
+                            .long("force")
+                            .help("Write the sequence even if standard output is not a terminal"));
+
+        app.get_matches_from_safe(args)?
+    };
+
+    let info = terminal_info(setup.env(), &matches);
+    let sequence = clear_sequence(info.as_ref(), !matches.is_present("keep-scrollback"));
+
+    let output = setup.output();
+    if !matches.is_present("force") && !util::is_tty(output.raw_object()) {
+        return Ok(());
+    }
+
+    let mut output = output.lock()?;
+    output.write_all(&sequence)?;
+    output.flush()?;
+
+    Ok(())
+}
+
+/// Create the `-T TYPE` argument, which is read by `terminal_info()`.
+pub(crate) fn term_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("term")
+        .short("T")
+        .takes_value(true)
+        .value_name("TYPE")
+        .help("Use the terminal type TYPE rather than the value of TERM")
+}
+
+/// Find the terminfo entry for the terminal given by `-T` or `TERM`.
+pub(crate) fn terminal_info(env: &EnvVars, matches: &ArgMatches) -> Option<TermInfo> {
+    let term = matches
+        .value_of_os("term")
+        .or_else(|| env.get_nonempty("TERM"))?;
+    TermInfo::load(env, term)
+}
+
+/// Build the sequence that clears the screen (and the scrollback buffer if `scrollback` is set)
+/// of the terminal described by `info`.
+pub(crate) fn clear_sequence(info: Option<&TermInfo>, scrollback: bool) -> Vec<u8> {
+    let info = match info {
+        Some(info) => info,
+        None if scrollback => return [ANSI_CLEAR, ANSI_CLEAR_SCROLLBACK].concat(),
+        None => return ANSI_CLEAR.to_vec(),
+    };
+
+    let mut result = match info.string("clear") {
+        Some(clear) => clear.to_vec(),
+        // going to the top left corner and clearing to the end of the screen does the same thing
+        None => match (info.string("home"), info.string("ed")) {
+            (Some(home), Some(ed)) => [home, ed].concat(),
+            _ => vec![],
+        },
+    };
+    if scrollback {
+        if let Some(e3) = info.string("E3") {
+            result.extend_from_slice(e3);
+        }
+    }
+    terminfo::strip_padding(&result)
+}
+
+#[test]
+fn clear_defaults() {
+    assert_eq!(clear_sequence(None, true), b"\x1b[H\x1b[2J\x1b[3J");
+    assert_eq!(clear_sequence(None, false), b"\x1b[H\x1b[2J");
+    // a terminal (like TERM=dumb) that cannot clear its screen just gets nothing
+    assert!(clear_sequence(Some(&TermInfo::default()), true).is_empty());
+}
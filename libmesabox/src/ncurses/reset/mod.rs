@@ -0,0 +1,85 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use super::clear;
+use posix::stty::{self, SttyError, Terminal};
+use util::{self, terminfo};
+use {ArgsIter, Result, UtilRead, UtilSetup, UtilWrite};
+
+use nix::libc;
+use std::io::{self, Write};
+
+pub(crate) const NAME: &str = "reset";
+pub(crate) const DESCRIPTION: &str = "Restore the terminal to a usable state";
+
+const AFTER_HELP: &str = "
+The terminal settings are reset the way 'stty sane' resets them, using the first of standard
+error, standard output and standard input that is a terminal.  The reset sequences from the
+terminfo entry for the terminal (given by TERM if -T is not used) are then written to standard
+output followed by the sequence that clears the screen, unless standard output is not a terminal.
+";
+
+pub fn execute<S, T>(setup: &mut S, args: T) -> Result<()>
+where
+    S: UtilSetup,
+    T: ArgsIter,
+{
+    let matches = {
+        let app = util_app!(NAME)
+                    .after_help(AFTER_HELP)
+                    .arg(clear::term_arg());
+
+        app.get_matches_from_safe(args)?
+    };
+
+    let terminal = find_terminal(setup)?;
+    let mut termios = terminal.get_attr()?;
+    stty::make_sane(&mut termios);
+    terminal.set_attr(&termios)?;
+
+    let info = clear::terminal_info(setup.env(), &matches);
+    let mut sequence = vec![];
+    if let Some(ref info) = info {
+        for name in &["rs1", "rs2", "rs3"] {
+            if let Some(cap) = info.string(name) {
+                sequence.extend_from_slice(&terminfo::strip_padding(cap));
+            }
+        }
+    }
+    sequence.extend_from_slice(&clear::clear_sequence(info.as_ref(), true));
+
+    let output = setup.output();
+    if util::is_tty(output.raw_object()) {
+        let mut output = output.lock()?;
+        output.write_all(&sequence)?;
+        output.flush()?;
+    }
+
+    Ok(())
+}
+
+fn find_terminal<S: UtilSetup>(setup: &mut S) -> Result<Terminal> {
+    let candidates = [
+        ("standard error", setup.error().raw_object()),
+        ("standard output", setup.output().raw_object()),
+        ("standard input", setup.input().raw_object()),
+    ];
+    for &(name, obj) in &candidates {
+        if let Some(obj) = obj {
+            if util::is_tty(Some(obj)) {
+                return Ok(Terminal {
+                    fd: obj.raw_value(),
+                    name: name.to_owned(),
+                });
+            }
+        }
+    }
+
+    let err = io::Error::from_raw_os_error(libc::ENOTTY);
+    Err(SttyError::Device(candidates[0].0.to_owned(), err).into())
+}
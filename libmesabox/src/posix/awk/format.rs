@@ -0,0 +1,417 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+/// An argument to `sprintf()`, which may be used as either a number or a string.
+pub struct Arg {
+    pub num: f64,
+    pub string: String,
+    /// Whether the value is primarily a number (used by `%c`)
+    pub is_num: bool,
+}
+
+#[derive(Default)]
+struct Spec {
+    left: bool,
+    plus: bool,
+    space: bool,
+    alt: bool,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+/// Format `args` according to the printf-style format string `fmt`.  Missing arguments are
+/// treated as empty strings/zero and extra arguments are ignored.
+pub fn sprintf(fmt: &str, args: &[Arg]) -> String {
+    let empty = Arg {
+        num: 0.0,
+        string: String::new(),
+        is_num: false,
+    };
+    let mut args = args.iter();
+    let mut next_arg = || args.next().unwrap_or(&empty);
+
+    let mut result = String::new();
+    let mut chars = fmt.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if ch != '%' {
+            result.push(ch);
+            continue;
+        }
+
+        let mut spec = Spec::default();
+        let mut raw = String::from("%");
+        while let Some(&flag) = chars.peek() {
+            match flag {
+                '-' => spec.left = true,
+                '+' => spec.plus = true,
+                ' ' => spec.space = true,
+                '#' => spec.alt = true,
+                '0' => spec.zero = true,
+                _ => break,
+            }
+            raw.push(flag);
+            chars.next();
+        }
+
+        if chars.peek() == Some(&'*') {
+            chars.next();
+            raw.push('*');
+            let width = next_arg().num as i64;
+            if width < 0 {
+                spec.left = true;
+            }
+            spec.width = width.abs() as usize;
+        } else {
+            spec.width = read_int(&mut chars, &mut raw);
+        }
+
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            raw.push('.');
+            if chars.peek() == Some(&'*') {
+                chars.next();
+                raw.push('*');
+                let precision = next_arg().num as i64;
+                spec.precision = if precision < 0 {
+                    None
+                } else {
+                    Some(precision as usize)
+                };
+            } else {
+                spec.precision = Some(read_int(&mut chars, &mut raw));
+            }
+        }
+
+        let conv = match chars.next() {
+            Some(conv) => conv,
+            None => {
+                result.push_str(&raw);
+                break;
+            }
+        };
+
+        let formatted = match conv {
+            '%' => {
+                result.push('%');
+                continue;
+            }
+            'd' | 'i' => format_signed(&spec, next_arg().num),
+            'o' | 'x' | 'X' | 'u' => format_unsigned(&spec, conv, next_arg().num),
+            'e' | 'E' | 'f' | 'F' | 'g' | 'G' => format_float(&spec, conv, next_arg().num),
+            'c' => {
+                let arg = next_arg();
+                let text = if arg.is_num {
+                    ::std::char::from_u32(arg.num as u32)
+                        .map(|c| c.to_string())
+                        .unwrap_or_default()
+                } else {
+                    arg.string.chars().take(1).collect()
+                };
+                pad(&spec, text, false)
+            }
+            's' => {
+                let arg = next_arg();
+                let text = match spec.precision {
+                    Some(precision) => arg.string.chars().take(precision).collect(),
+                    None => arg.string.clone(),
+                };
+                pad(&spec, text, false)
+            }
+            _ => {
+                // unknown conversions are output as-is
+                raw.push(conv);
+                raw
+            }
+        };
+        result.push_str(&formatted);
+    }
+
+    result
+}
+
+/// Convert a number to a string as awk does, printing integers without a fractional part and
+/// using `fmt` (i.e. `CONVFMT` or `OFMT`) for everything else.
+pub fn number_to_string(num: f64, fmt: &str) -> String {
+    if num == num.trunc() && num.abs() < 1e16 {
+        format!("{}", num as i64)
+    } else if num.is_nan() {
+        "nan".to_owned()
+    } else if num.is_infinite() {
+        if num > 0.0 { "inf" } else { "-inf" }.to_owned()
+    } else {
+        sprintf(
+            fmt,
+            &[Arg {
+                num: num,
+                string: String::new(),
+                is_num: true,
+            }],
+        )
+    }
+}
+
+fn read_int<I: Iterator<Item = char>>(
+    chars: &mut ::std::iter::Peekable<I>,
+    raw: &mut String,
+) -> usize {
+    let mut value = 0usize;
+    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+        raw.push(chars.next().unwrap());
+        value = value.saturating_mul(10).saturating_add(digit as usize);
+    }
+    value
+}
+
+/// Pad `text` to the field width.  If `numeric` is true, zero padding goes after any sign.
+fn pad(spec: &Spec, text: String, numeric: bool) -> String {
+    let len = text.chars().count();
+    if len >= spec.width {
+        return text;
+    }
+
+    let fill = spec.width - len;
+    if spec.left {
+        text + &" ".repeat(fill)
+    } else if numeric && spec.zero {
+        let sign_len = match text.chars().next() {
+            Some('-') | Some('+') | Some(' ') => 1,
+            _ => 0,
+        };
+        let mut result = text[..sign_len].to_owned();
+        result.push_str(&"0".repeat(fill));
+        result.push_str(&text[sign_len..]);
+        result
+    } else {
+        " ".repeat(fill) + &text
+    }
+}
+
+fn sign_prefix(spec: &Spec, negative: bool) -> &'static str {
+    if negative {
+        "-"
+    } else if spec.plus {
+        "+"
+    } else if spec.space {
+        " "
+    } else {
+        ""
+    }
+}
+
+fn format_signed(spec: &Spec, num: f64) -> String {
+    let value = if num.is_nan() { 0 } else { num.trunc() as i64 };
+    let mut digits = value.abs().to_string();
+    if let Some(precision) = spec.precision {
+        if precision == 0 && value == 0 {
+            digits.clear();
+        }
+        while digits.len() < precision {
+            digits.insert(0, '0');
+        }
+    }
+
+    let text = format!("{}{}", sign_prefix(spec, value < 0), digits);
+    pad(spec, text, spec.precision.is_none())
+}
+
+fn format_unsigned(spec: &Spec, conv: char, num: f64) -> String {
+    let value = if num < 0.0 {
+        num.trunc() as i64 as u64
+    } else {
+        num.trunc() as u64
+    };
+    let mut digits = match conv {
+        'o' => format!("{:o}", value),
+        'x' => format!("{:x}", value),
+        'X' => format!("{:X}", value),
+        _ => value.to_string(),
+    };
+    if let Some(precision) = spec.precision {
+        if precision == 0 && value == 0 {
+            digits.clear();
+        }
+        while digits.len() < precision {
+            digits.insert(0, '0');
+        }
+    }
+
+    if spec.alt && value != 0 {
+        match conv {
+            'o' if !digits.starts_with('0') => digits.insert(0, '0'),
+            'x' => digits.insert_str(0, "0x"),
+            'X' => digits.insert_str(0, "0X"),
+            _ => {}
+        }
+    }
+
+    pad(spec, digits, spec.precision.is_none())
+}
+
+fn format_float(spec: &Spec, conv: char, num: f64) -> String {
+    let precision = spec.precision.unwrap_or(6);
+    let upper = conv.is_uppercase();
+
+    let body = if num.is_nan() {
+        "nan".to_owned()
+    } else if num.is_infinite() {
+        "inf".to_owned()
+    } else {
+        let abs = num.abs();
+        match conv {
+            'e' | 'E' => format_exp(abs, precision, spec.alt),
+            'f' | 'F' => {
+                let mut text = format!("{:.*}", precision, abs);
+                if spec.alt && precision == 0 {
+                    text.push('.');
+                }
+                text
+            }
+            _ => format_general(abs, precision, spec.alt),
+        }
+    };
+    let body = if upper { body.to_uppercase() } else { body };
+
+    let text = format!(
+        "{}{}",
+        sign_prefix(spec, num.is_sign_negative() && !num.is_nan()),
+        body
+    );
+    pad(spec, text, num.is_finite())
+}
+
+/// Format a non-negative number like C's `%e`, with at least two digits in the exponent.
+fn format_exp(num: f64, precision: usize, alt: bool) -> String {
+    let text = format!("{:.*e}", precision, num);
+    let (mantissa, exp) = split_exp(&text);
+    let mut result = mantissa.to_owned();
+    if alt && precision == 0 {
+        result.push('.');
+    }
+    let sign = if exp < 0 { '-' } else { '+' };
+    result.push_str(&format!("e{}{:02}", sign, exp.abs()));
+    result
+}
+
+fn split_exp(text: &str) -> (&str, i32) {
+    let pos = text.find('e').unwrap();
+    (&text[..pos], text[pos + 1..].parse().unwrap())
+}
+
+/// Format a non-negative number like C's `%g`.
+fn format_general(num: f64, precision: usize, alt: bool) -> String {
+    let precision = if precision == 0 { 1 } else { precision };
+
+    // the exponent after rounding to the requested number of significant digits
+    let exp = if num == 0.0 {
+        0
+    } else {
+        split_exp(&format!("{:.*e}", precision - 1, num)).1
+    };
+
+    let mut text = if exp < -4 || exp >= precision as i32 {
+        format_exp(num, precision - 1, alt)
+    } else {
+        format!("{:.*}", (precision as i32 - 1 - exp) as usize, num)
+    };
+
+    if !alt {
+        // strip trailing zeros from the fractional part
+        let (mantissa, suffix) = match text.find('e') {
+            Some(pos) => (text[..pos].to_owned(), text[pos..].to_owned()),
+            None => (text.clone(), String::new()),
+        };
+        if mantissa.contains('.') {
+            let trimmed = mantissa.trim_right_matches('0').trim_right_matches('.');
+            text = format!("{}{}", trimmed, suffix);
+        }
+    }
+
+    text
+}
+
+#[cfg(test)]
+fn num_arg(num: f64) -> Arg {
+    Arg {
+        num: num,
+        string: num.to_string(),
+        is_num: true,
+    }
+}
+
+#[cfg(test)]
+fn str_arg(s: &str) -> Arg {
+    Arg {
+        num: 0.0,
+        string: s.to_owned(),
+        is_num: false,
+    }
+}
+
+#[test]
+fn sprintf_integers() {
+    assert_eq!(
+        sprintf(
+            "%d|%5d|%-5d|%05d",
+            &[num_arg(42.9), num_arg(-7.0), num_arg(3.0), num_arg(-12.0)]
+        ),
+        "42|   -7|3    |-0012"
+    );
+    assert_eq!(
+        sprintf(
+            "%x %X %#o %u",
+            &[num_arg(255.0), num_arg(255.0), num_arg(8.0), num_arg(10.0)]
+        ),
+        "ff FF 010 10"
+    );
+    assert_eq!(sprintf("%+d %.3d", &[num_arg(5.0), num_arg(7.0)]), "+5 007");
+}
+
+#[test]
+fn sprintf_floats() {
+    assert_eq!(
+        sprintf("%.2f %e", &[num_arg(3.14159), num_arg(12345.678)]),
+        "3.14 1.234568e+04"
+    );
+    assert_eq!(
+        sprintf(
+            "%g %g %g",
+            &[num_arg(0.0001), num_arg(123456789.0), num_arg(100.0)]
+        ),
+        "0.0001 1.23457e+08 100"
+    );
+    assert_eq!(
+        sprintf("%8.3f|%-8.1E|", &[num_arg(-1.5), num_arg(0.05)]),
+        "  -1.500|5.0E-02 |"
+    );
+}
+
+#[test]
+fn sprintf_strings() {
+    assert_eq!(
+        sprintf(
+            "%s-%5s-%-3s-%.2s",
+            &[str_arg("a"), str_arg("b"), str_arg("c"), str_arg("xyz")]
+        ),
+        "a-    b-c  -xy"
+    );
+    assert_eq!(
+        sprintf("%c%c %d%%", &[num_arg(65.0), str_arg("bc")]),
+        "Ab 0%"
+    );
+    assert_eq!(sprintf("%*d|", &[num_arg(4.0), num_arg(1.0)]), "   1|");
+}
+
+#[test]
+fn number_conversion() {
+    assert_eq!(number_to_string(3.0, "%.6g"), "3");
+    assert_eq!(number_to_string(-0.5, "%.6g"), "-0.5");
+    assert_eq!(number_to_string(1.0 / 3.0, "%.6g"), "0.333333");
+    assert_eq!(number_to_string(1.0 / 3.0, "%.2f"), "0.33");
+}
@@ -0,0 +1,842 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use super::format::{self, Arg};
+use super::parser::{self, BinaryOp, Builtin, CompareOp, Expr, Pattern, Program, Stmt};
+use Result;
+
+use regex::Regex;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::Write;
+
+// the largest field number (and value of NF) that can be used, so that assigning to a field far
+// beyond the end of the record cannot make the field list grow without bounds
+const MAX_FIELD: f64 = i32::MAX as f64;
+
+#[derive(Debug, Fail)]
+#[fail(display = "{}", _0)]
+pub struct RuntimeError(String);
+
+#[derive(Clone, Debug)]
+pub enum Value {
+    Uninit,
+    Num(f64),
+    Str(String),
+    /// A string from the input (or the command line) that is treated as a number if it looks
+    /// like one
+    StrNum(String),
+}
+
+impl Value {
+    pub fn to_num(&self) -> f64 {
+        match *self {
+            Value::Uninit => 0.0,
+            Value::Num(num) => num,
+            Value::Str(ref s) | Value::StrNum(ref s) => parse_num_prefix(s),
+        }
+    }
+
+    pub fn to_string(&self, convfmt: &str) -> String {
+        match *self {
+            Value::Uninit => String::new(),
+            Value::Num(num) => format::number_to_string(num, convfmt),
+            Value::Str(ref s) | Value::StrNum(ref s) => s.clone(),
+        }
+    }
+
+    fn is_true(&self) -> bool {
+        match *self {
+            Value::Uninit => false,
+            Value::Num(num) => num != 0.0,
+            Value::Str(ref s) => !s.is_empty(),
+            Value::StrNum(ref s) => match parse_num(s) {
+                Some(num) => num != 0.0,
+                None => !s.is_empty(),
+            },
+        }
+    }
+
+    /// The value as a number if it should be compared numerically.
+    fn numeric(&self) -> Option<f64> {
+        match *self {
+            Value::Uninit => Some(0.0),
+            Value::Num(num) => Some(num),
+            Value::Str(_) => None,
+            Value::StrNum(ref s) => parse_num(s),
+        }
+    }
+}
+
+/// Parse a string that consists entirely of a number (ignoring surrounding blanks).
+fn parse_num(s: &str) -> Option<f64> {
+    let trimmed = s.trim_matches(|c| c == ' ' || c == '\t' || c == '\n');
+    if trimmed.is_empty() {
+        return None;
+    }
+    let len = num_prefix_len(trimmed);
+    if len == trimmed.len() {
+        trimmed.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// Parse the longest prefix of the string that looks like a number (like `strtod()`).
+fn parse_num_prefix(s: &str) -> f64 {
+    let trimmed = s.trim_left_matches(|c| c == ' ' || c == '\t' || c == '\n');
+    let len = num_prefix_len(trimmed);
+    trimmed[..len].parse().unwrap_or(0.0)
+}
+
+fn num_prefix_len(s: &str) -> usize {
+    let bytes = s.as_bytes();
+    let mut pos = 0;
+    if pos < bytes.len() && (bytes[pos] == b'+' || bytes[pos] == b'-') {
+        pos += 1;
+    }
+
+    let digits_start = pos;
+    while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+        pos += 1;
+    }
+    let mut digits = pos - digits_start;
+    if pos < bytes.len() && bytes[pos] == b'.' {
+        let frac_start = pos + 1;
+        let mut end = frac_start;
+        while end < bytes.len() && bytes[end].is_ascii_digit() {
+            end += 1;
+        }
+        digits += end - frac_start;
+        if digits > 0 {
+            pos = end;
+        }
+    }
+    if digits == 0 {
+        return 0;
+    }
+
+    if pos < bytes.len() && (bytes[pos] == b'e' || bytes[pos] == b'E') {
+        let mut end = pos + 1;
+        if end < bytes.len() && (bytes[end] == b'+' || bytes[end] == b'-') {
+            end += 1;
+        }
+        let exp_start = end;
+        while end < bytes.len() && bytes[end].is_ascii_digit() {
+            end += 1;
+        }
+        if end > exp_start {
+            pos = end;
+        }
+    }
+
+    pos
+}
+
+pub enum Flow {
+    Normal,
+    Next,
+    Exit,
+    Break,
+    Continue,
+}
+
+pub struct Interp<'a, O: Write> {
+    program: &'a Program,
+    output: O,
+    vars: HashMap<String, Value>,
+    // fields[0] is the whole record
+    fields: Vec<String>,
+    regex_cache: HashMap<String, Regex>,
+    // whether each range pattern is currently active
+    ranges: Vec<bool>,
+    pub exit_code: i32,
+}
+
+impl<'a, O: Write> Interp<'a, O> {
+    pub fn new(program: &'a Program, output: O) -> Self {
+        let mut vars = HashMap::new();
+        for &(name, value) in &[
+            ("FS", " "),
+            ("OFS", " "),
+            ("ORS", "\n"),
+            ("RS", "\n"),
+            ("SUBSEP", "\x1c"),
+            ("CONVFMT", "%.6g"),
+            ("OFMT", "%.6g"),
+            ("FILENAME", ""),
+        ] {
+            vars.insert(name.to_owned(), Value::Str(value.to_owned()));
+        }
+        vars.insert("NR".to_owned(), Value::Num(0.0));
+        vars.insert("FNR".to_owned(), Value::Num(0.0));
+        vars.insert("RSTART".to_owned(), Value::Num(0.0));
+        vars.insert("RLENGTH".to_owned(), Value::Num(-1.0));
+
+        Self {
+            program: program,
+            output: output,
+            vars: vars,
+            fields: vec![String::new()],
+            regex_cache: HashMap::new(),
+            ranges: vec![false; program.rules.len()],
+            exit_code: 0,
+        }
+    }
+
+    pub fn set_var(&mut self, name: &str, value: Value) -> Result<()> {
+        if name == "NF" {
+            let nf = value.to_num();
+            self.set_nf(nf)?;
+        } else {
+            self.vars.insert(name.to_owned(), value);
+        }
+        Ok(())
+    }
+
+    fn var_string(&self, name: &str) -> String {
+        let convfmt = self.convfmt();
+        self.vars
+            .get(name)
+            .map(|v| v.to_string(&convfmt))
+            .unwrap_or_default()
+    }
+
+    fn convfmt(&self) -> String {
+        match self.vars.get("CONVFMT") {
+            Some(&Value::Str(ref s)) | Some(&Value::StrNum(ref s)) => s.clone(),
+            _ => "%.6g".to_owned(),
+        }
+    }
+
+    /// The record separator, which may only be a single byte.
+    pub fn record_separator(&self) -> u8 {
+        self.var_string("RS").bytes().next().unwrap_or(b'\n')
+    }
+
+    pub fn run_begin(&mut self) -> Result<Flow> {
+        let program = self.program;
+        for block in &program.begin {
+            if let Flow::Exit = self.exec_block(block)? {
+                return Ok(Flow::Exit);
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    pub fn run_end(&mut self) -> Result<()> {
+        let program = self.program;
+        for block in &program.end {
+            if let Flow::Exit = self.exec_block(block)? {
+                break;
+            }
+        }
+        self.output.flush()?;
+        Ok(())
+    }
+
+    /// Whether any input needs to be read (i.e. there are rules or `END` actions).
+    pub fn needs_input(&self) -> bool {
+        !self.program.rules.is_empty() || !self.program.end.is_empty()
+    }
+
+    pub fn start_file(&mut self, filename: &str) {
+        self.vars
+            .insert("FILENAME".to_owned(), Value::Str(filename.to_owned()));
+        self.vars.insert("FNR".to_owned(), Value::Num(0.0));
+    }
+
+    /// Run all the rules against a record.
+    pub fn run_record(&mut self, record: String) -> Result<Flow> {
+        for name in &["NR", "FNR"] {
+            let count = self.vars.get(*name).map(|v| v.to_num()).unwrap_or(0.0);
+            self.vars.insert(name.to_string(), Value::Num(count + 1.0));
+        }
+        self.set_record(record);
+
+        let program = self.program;
+        for (i, rule) in program.rules.iter().enumerate() {
+            let matched = match rule.pattern {
+                Pattern::All => true,
+                Pattern::Expr(ref expr) => self.eval(expr)?.is_true(),
+                Pattern::Range(ref start, ref end) => {
+                    if self.ranges[i] {
+                        if self.eval(end)?.is_true() {
+                            self.ranges[i] = false;
+                        }
+                        true
+                    } else if self.eval(start)?.is_true() {
+                        // the end pattern is also checked against the starting record
+                        self.ranges[i] = !self.eval(end)?.is_true();
+                        true
+                    } else {
+                        false
+                    }
+                }
+            };
+            if !matched {
+                continue;
+            }
+
+            let flow = match rule.action {
+                Some(ref action) => self.exec_block(action)?,
+                None => {
+                    self.print_record()?;
+                    Flow::Normal
+                }
+            };
+            match flow {
+                Flow::Next => break,
+                Flow::Exit => return Ok(Flow::Exit),
+                _ => {}
+            }
+        }
+
+        Ok(Flow::Normal)
+    }
+
+    fn print_record(&mut self) -> Result<()> {
+        let ors = self.var_string("ORS");
+        self.output.write_all(self.fields[0].as_bytes())?;
+        self.output.write_all(ors.as_bytes())?;
+        Ok(())
+    }
+
+    fn set_record(&mut self, record: String) {
+        self.fields.truncate(1);
+        self.fields[0] = record;
+        self.split_record();
+    }
+
+    fn split_record(&mut self) {
+        let fs = self.var_string("FS");
+        let regex = if fs == " " || (fs.chars().count() == 1 && fs != "\\") {
+            None
+        } else {
+            // an invalid separator just leaves the record unsplit
+            self.regex(&fs).ok()
+        };
+
+        let mut fields: Vec<String> = {
+            let record = &self.fields[0];
+            if fs == " " {
+                record
+                    .split(|c| c == ' ' || c == '\t' || c == '\n')
+                    .filter(|f| !f.is_empty())
+                    .map(|f| f.to_owned())
+                    .collect()
+            } else if record.is_empty() {
+                vec![]
+            } else {
+                match regex {
+                    Some(re) => re.split(record).map(|f| f.to_owned()).collect(),
+                    None if fs.chars().count() == 1 => {
+                        record.split(fs.as_str()).map(|f| f.to_owned()).collect()
+                    }
+                    None => vec![record.clone()],
+                }
+            }
+        };
+        self.fields.truncate(1);
+        self.fields.append(&mut fields);
+    }
+
+    /// Rebuild `$0` from the fields after one of them has changed.
+    fn rebuild_record(&mut self) {
+        let ofs = self.var_string("OFS");
+        self.fields[0] = self.fields[1..].join(&ofs);
+    }
+
+    fn set_nf(&mut self, nf: f64) -> Result<()> {
+        let nf = if nf < 0.0 { 0 } else { check_field_limit(nf)? };
+        self.fields.resize(nf + 1, String::new());
+        self.rebuild_record();
+        Ok(())
+    }
+
+    fn get_field(&self, index: usize) -> Value {
+        match self.fields.get(index) {
+            Some(field) => Value::StrNum(field.clone()),
+            None => Value::Uninit,
+        }
+    }
+
+    fn set_field(&mut self, index: usize, value: String) {
+        if index == 0 {
+            self.fields[0] = value;
+            self.split_record();
+        } else {
+            if index >= self.fields.len() {
+                self.fields.resize(index + 1, String::new());
+            }
+            self.fields[index] = value;
+            self.rebuild_record();
+        }
+    }
+
+    fn field_index(&mut self, expr: &Expr) -> Result<usize> {
+        let index = self.eval(expr)?.to_num();
+        if index < 0.0 {
+            return Err(RuntimeError(format!(
+                "trying to access out of range field {}",
+                index as i64
+            ))
+            .into());
+        }
+        check_field_limit(index)
+    }
+
+    fn regex(&mut self, pattern: &str) -> Result<Regex> {
+        if let Some(re) = self.regex_cache.get(pattern) {
+            return Ok(re.clone());
+        }
+        let re = parser::compile_regex(pattern)
+            .map_err(|e| RuntimeError(format!("invalid regex '{}': {}", pattern, e)))?;
+        self.regex_cache.insert(pattern.to_owned(), re.clone());
+        Ok(re)
+    }
+
+    /// Get the regex used by an expression that is on the right-hand side of `~` or used as the
+    /// first argument of functions like `sub()`.
+    fn regex_operand(&mut self, expr: &Expr) -> Result<Regex> {
+        match *expr {
+            Expr::Regex(ref re) => Ok(re.clone()),
+            _ => {
+                let convfmt = self.convfmt();
+                let pattern = self.eval(expr)?.to_string(&convfmt);
+                self.regex(&pattern)
+            }
+        }
+    }
+
+    fn eval_string(&mut self, expr: &Expr) -> Result<String> {
+        let convfmt = self.convfmt();
+        Ok(self.eval(expr)?.to_string(&convfmt))
+    }
+
+    fn eval_num(&mut self, expr: &Expr) -> Result<f64> {
+        Ok(self.eval(expr)?.to_num())
+    }
+
+    fn assign(&mut self, target: &Expr, value: Value) -> Result<()> {
+        match *target {
+            Expr::Var(ref name) => self.set_var(name, value)?,
+            Expr::Field(ref index) => {
+                let index = self.field_index(index)?;
+                let convfmt = self.convfmt();
+                self.set_field(index, value.to_string(&convfmt));
+            }
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+
+    fn get_var(&self, name: &str) -> Value {
+        if name == "NF" {
+            return Value::Num((self.fields.len() - 1) as f64);
+        }
+        self.vars.get(name).cloned().unwrap_or(Value::Uninit)
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Result<Value> {
+        Ok(match *expr {
+            Expr::Num(num) => Value::Num(num),
+            Expr::Str(ref s) => Value::Str(s.clone()),
+            Expr::Regex(ref re) => bool_value(re.is_match(&self.fields[0])),
+            Expr::Var(ref name) => self.get_var(name),
+            Expr::Field(ref index) => {
+                let index = self.field_index(index)?;
+                self.get_field(index)
+            }
+            Expr::Group(ref list) => {
+                // outside of print, (a, b) is only meaningful as an array subscript
+                let subsep = self.var_string("SUBSEP");
+                let mut parts = vec![];
+                for item in list {
+                    parts.push(self.eval_string(item)?);
+                }
+                Value::Str(parts.join(&subsep))
+            }
+            Expr::Assign(op, ref target, ref value) => {
+                let mut value = self.eval(value)?;
+                if let Some(op) = op {
+                    let current = self.eval(target)?.to_num();
+                    value = Value::Num(arithmetic(op, current, value.to_num())?);
+                }
+                self.assign(target, value.clone())?;
+                value
+            }
+            Expr::Cond(ref cond, ref then, ref otherwise) => {
+                if self.eval(cond)?.is_true() {
+                    self.eval(then)?
+                } else {
+                    self.eval(otherwise)?
+                }
+            }
+            Expr::And(ref lhs, ref rhs) => {
+                bool_value(self.eval(lhs)?.is_true() && self.eval(rhs)?.is_true())
+            }
+            Expr::Or(ref lhs, ref rhs) => {
+                bool_value(self.eval(lhs)?.is_true() || self.eval(rhs)?.is_true())
+            }
+            Expr::Not(ref operand) => bool_value(!self.eval(operand)?.is_true()),
+            Expr::Neg(ref operand) => Value::Num(-self.eval_num(operand)?),
+            Expr::Pos(ref operand) => Value::Num(self.eval_num(operand)?),
+            Expr::Binary(op, ref lhs, ref rhs) => {
+                let lhs = self.eval_num(lhs)?;
+                let rhs = self.eval_num(rhs)?;
+                Value::Num(arithmetic(op, lhs, rhs)?)
+            }
+            Expr::Compare(op, ref lhs, ref rhs) => {
+                let lhs = self.eval(lhs)?;
+                let rhs = self.eval(rhs)?;
+                let ordering = match (lhs.numeric(), rhs.numeric()) {
+                    (Some(l), Some(r)) => l.partial_cmp(&r),
+                    _ => {
+                        let convfmt = self.convfmt();
+                        Some(lhs.to_string(&convfmt).cmp(&rhs.to_string(&convfmt)))
+                    }
+                };
+                bool_value(match ordering {
+                    Some(ordering) => compare(op, ordering),
+                    None => op == CompareOp::NotEqual,
+                })
+            }
+            Expr::Match(negate, ref lhs, ref rhs) => {
+                let text = self.eval_string(lhs)?;
+                let re = self.regex_operand(rhs)?;
+                bool_value(re.is_match(&text) != negate)
+            }
+            Expr::Concat(ref lhs, ref rhs) => {
+                let mut result = self.eval_string(lhs)?;
+                result.push_str(&self.eval_string(rhs)?);
+                Value::Str(result)
+            }
+            Expr::IncDec {
+                ref target,
+                delta,
+                prefix,
+            } => {
+                let current = self.eval_num(target)?;
+                self.assign(target, Value::Num(current + delta))?;
+                Value::Num(if prefix { current + delta } else { current })
+            }
+            Expr::Call(builtin, ref args) => self.call(builtin, args)?,
+        })
+    }
+
+    fn call(&mut self, builtin: Builtin, args: &[Expr]) -> Result<Value> {
+        Ok(match builtin {
+            Builtin::Length => {
+                let text = match args.first() {
+                    Some(arg) => self.eval_string(arg)?,
+                    None => self.fields[0].clone(),
+                };
+                Value::Num(text.chars().count() as f64)
+            }
+            Builtin::Substr => {
+                let text = self.eval_string(&args[0])?;
+                let len = text.chars().count() as f64;
+                let start = round(self.eval_num(&args[1])?);
+                let end = match args.get(2) {
+                    Some(arg) => start + round(self.eval_num(arg)?),
+                    None => len + 1.0,
+                };
+                // clamp to the characters that actually exist (positions start at 1)
+                let start = start.max(1.0);
+                let end = end.min(len + 1.0);
+                if start >= end {
+                    Value::Str(String::new())
+                } else {
+                    let skip = start as usize - 1;
+                    let take = (end - start) as usize;
+                    Value::Str(text.chars().skip(skip).take(take).collect())
+                }
+            }
+            Builtin::Index => {
+                let text = self.eval_string(&args[0])?;
+                let needle = self.eval_string(&args[1])?;
+                let pos = text
+                    .find(&needle)
+                    .map(|i| text[..i].chars().count() + 1)
+                    .unwrap_or(0);
+                Value::Num(pos as f64)
+            }
+            Builtin::Match => {
+                let text = self.eval_string(&args[0])?;
+                let re = self.regex_operand(&args[1])?;
+                let (start, length) = match re.find(&text) {
+                    Some(m) => (
+                        text[..m.start()].chars().count() as f64 + 1.0,
+                        m.as_str().chars().count() as f64,
+                    ),
+                    None => (0.0, -1.0),
+                };
+                self.vars.insert("RSTART".to_owned(), Value::Num(start));
+                self.vars.insert("RLENGTH".to_owned(), Value::Num(length));
+                Value::Num(start)
+            }
+            Builtin::Sub | Builtin::Gsub => {
+                let re = self.regex_operand(&args[0])?;
+                let replacement = self.eval_string(&args[1])?;
+                let text = self.eval_string(&args[2])?;
+                let (result, count) =
+                    substitute(&re, &replacement, &text, builtin == Builtin::Gsub);
+                if count > 0 {
+                    self.assign(&args[2], Value::Str(result))?;
+                }
+                Value::Num(count as f64)
+            }
+            Builtin::Sprintf => {
+                let fmt = self.eval_string(&args[0])?;
+                Value::Str(self.sprintf(&fmt, &args[1..])?)
+            }
+            Builtin::ToLower => Value::Str(self.eval_string(&args[0])?.to_lowercase()),
+            Builtin::ToUpper => Value::Str(self.eval_string(&args[0])?.to_uppercase()),
+            Builtin::Int => Value::Num(self.eval_num(&args[0])?.trunc()),
+            Builtin::Sqrt => Value::Num(self.eval_num(&args[0])?.sqrt()),
+            Builtin::Exp => Value::Num(self.eval_num(&args[0])?.exp()),
+            Builtin::Log => Value::Num(self.eval_num(&args[0])?.ln()),
+            Builtin::Sin => Value::Num(self.eval_num(&args[0])?.sin()),
+            Builtin::Cos => Value::Num(self.eval_num(&args[0])?.cos()),
+            Builtin::Atan2 => {
+                let y = self.eval_num(&args[0])?;
+                let x = self.eval_num(&args[1])?;
+                Value::Num(y.atan2(x))
+            }
+            // rejected by the parser
+            Builtin::Split => unreachable!(),
+        })
+    }
+
+    fn sprintf(&mut self, fmt: &str, args: &[Expr]) -> Result<String> {
+        let convfmt = self.convfmt();
+        let mut values = Vec::with_capacity(args.len());
+        for arg in args {
+            let value = self.eval(arg)?;
+            values.push(Arg {
+                num: value.to_num(),
+                string: value.to_string(&convfmt),
+                is_num: match value {
+                    Value::Num(_) => true,
+                    _ => false,
+                },
+            });
+        }
+        Ok(format::sprintf(fmt, &values))
+    }
+
+    fn exec_block(&mut self, stmts: &[Stmt]) -> Result<Flow> {
+        for stmt in stmts {
+            match self.exec(stmt)? {
+                Flow::Normal => {}
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn exec(&mut self, stmt: &Stmt) -> Result<Flow> {
+        match *stmt {
+            Stmt::Print(ref args) => {
+                if args.is_empty() {
+                    self.print_record()?;
+                } else {
+                    let ofs = self.var_string("OFS");
+                    let ors = self.var_string("ORS");
+                    let ofmt = self.var_string("OFMT");
+                    for (i, arg) in args.iter().enumerate() {
+                        if i > 0 {
+                            self.output.write_all(ofs.as_bytes())?;
+                        }
+                        // numbers are output using OFMT rather than CONVFMT
+                        let text = self.eval(arg)?.to_string(&ofmt);
+                        self.output.write_all(text.as_bytes())?;
+                    }
+                    self.output.write_all(ors.as_bytes())?;
+                }
+            }
+            Stmt::Printf(ref args) => {
+                let fmt = self.eval_string(&args[0])?;
+                let text = self.sprintf(&fmt, &args[1..])?;
+                self.output.write_all(text.as_bytes())?;
+            }
+            Stmt::Expr(ref expr) => {
+                self.eval(expr)?;
+            }
+            Stmt::If(ref cond, ref then, ref otherwise) => {
+                if self.eval(cond)?.is_true() {
+                    return self.exec(then);
+                } else if let Some(ref otherwise) = *otherwise {
+                    return self.exec(otherwise);
+                }
+            }
+            Stmt::While(ref cond, ref body) => {
+                while self.eval(cond)?.is_true() {
+                    match self.exec(body)? {
+                        Flow::Break => break,
+                        Flow::Normal | Flow::Continue => {}
+                        flow => return Ok(flow),
+                    }
+                }
+            }
+            Stmt::DoWhile(ref body, ref cond) => loop {
+                match self.exec(body)? {
+                    Flow::Break => break,
+                    Flow::Normal | Flow::Continue => {}
+                    flow => return Ok(flow),
+                }
+                if !self.eval(cond)?.is_true() {
+                    break;
+                }
+            },
+            Stmt::For(ref init, ref cond, ref step, ref body) => {
+                if let Some(ref init) = *init {
+                    self.eval(init)?;
+                }
+                loop {
+                    if let Some(ref cond) = *cond {
+                        if !self.eval(cond)?.is_true() {
+                            break;
+                        }
+                    }
+                    match self.exec(body)? {
+                        Flow::Break => break,
+                        Flow::Normal | Flow::Continue => {}
+                        flow => return Ok(flow),
+                    }
+                    if let Some(ref step) = *step {
+                        self.eval(step)?;
+                    }
+                }
+            }
+            Stmt::Block(ref stmts) => return self.exec_block(stmts),
+            Stmt::Next => return Ok(Flow::Next),
+            Stmt::Exit(ref code) => {
+                if let Some(ref code) = *code {
+                    self.exit_code = self.eval_num(code)? as i32;
+                }
+                return Ok(Flow::Exit);
+            }
+            Stmt::Break => return Ok(Flow::Break),
+            Stmt::Continue => return Ok(Flow::Continue),
+        }
+        Ok(Flow::Normal)
+    }
+}
+
+fn bool_value(value: bool) -> Value {
+    Value::Num(if value { 1.0 } else { 0.0 })
+}
+
+fn round(num: f64) -> f64 {
+    // awk implementations round half to even here
+    let rounded = num.round();
+    if (num - num.trunc()).abs() == 0.5 && rounded % 2.0 != 0.0 {
+        rounded - num.signum()
+    } else {
+        rounded
+    }
+}
+
+fn arithmetic(op: BinaryOp, lhs: f64, rhs: f64) -> Result<f64> {
+    Ok(match op {
+        BinaryOp::Add => lhs + rhs,
+        BinaryOp::Sub => lhs - rhs,
+        BinaryOp::Mul => lhs * rhs,
+        BinaryOp::Div | BinaryOp::Mod if rhs == 0.0 => {
+            return Err(RuntimeError("division by zero".to_owned()).into())
+        }
+        BinaryOp::Div => lhs / rhs,
+        BinaryOp::Mod => lhs % rhs,
+        BinaryOp::Pow => lhs.powf(rhs),
+    })
+}
+
+fn compare(op: CompareOp, ordering: Ordering) -> bool {
+    match op {
+        CompareOp::Less => ordering == Ordering::Less,
+        CompareOp::LessEq => ordering != Ordering::Greater,
+        CompareOp::Equal => ordering == Ordering::Equal,
+        CompareOp::NotEqual => ordering != Ordering::Equal,
+        CompareOp::Greater => ordering == Ordering::Greater,
+        CompareOp::GreaterEq => ordering != Ordering::Less,
+    }
+}
+
+/// Replace the first (or every, if `global` is true) match of `re` in `text`.  In the
+/// replacement, `&` stands for the matched text and `\&` for a literal ampersand.
+fn substitute(re: &Regex, replacement: &str, text: &str, global: bool) -> (String, usize) {
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+    let mut count = 0;
+
+    for m in re.find_iter(text) {
+        result.push_str(&text[last..m.start()]);
+
+        let mut chars = replacement.chars();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '&' => result.push_str(m.as_str()),
+                '\\' => match chars.next() {
+                    Some('&') => result.push('&'),
+                    Some('\\') => result.push('\\'),
+                    Some(other) => {
+                        result.push('\\');
+                        result.push(other);
+                    }
+                    None => result.push('\\'),
+                },
+                _ => result.push(ch),
+            }
+        }
+
+        last = m.end();
+        count += 1;
+        if !global {
+            break;
+        }
+    }
+    result.push_str(&text[last..]);
+
+    (result, count)
+}
+
+fn check_field_limit(index: f64) -> Result<usize> {
+    // NOTE: this also rejects NaN
+    if !(index <= MAX_FIELD) {
+        return Err(RuntimeError(format!("field index too large: {}", index)).into());
+    }
+    Ok(index as usize)
+}
+
+#[test]
+fn string_to_number() {
+    assert_eq!(parse_num_prefix(" 12abc"), 12.0);
+    assert_eq!(parse_num_prefix("-1.5e2x"), -150.0);
+    assert_eq!(parse_num_prefix("1e"), 1.0);
+    assert_eq!(parse_num_prefix(".x"), 0.0);
+    assert_eq!(parse_num(" 3.5 "), Some(3.5));
+    assert_eq!(parse_num("3.5a"), None);
+    assert_eq!(parse_num(""), None);
+}
+
+#[test]
+fn substitutions() {
+    let re = Regex::new("o+").unwrap();
+    assert_eq!(
+        substitute(&re, "[&]", "foo boo", false),
+        ("f[oo] boo".to_owned(), 1)
+    );
+    assert_eq!(
+        substitute(&re, "\\&", "foo boo", true),
+        ("f& b&".to_owned(), 2)
+    );
+    let re = Regex::new("x*").unwrap();
+    assert_eq!(substitute(&re, "-", "abc", true), ("-a-b-c-".to_owned(), 4));
+}
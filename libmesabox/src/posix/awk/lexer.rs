@@ -0,0 +1,448 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use std::result::Result as StdResult;
+
+#[derive(Debug, Fail)]
+#[fail(display = "syntax error at source line {}: {}", line, msg)]
+pub struct SyntaxError {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl SyntaxError {
+    pub fn new<S: Into<String>>(line: usize, msg: S) -> Self {
+        Self {
+            line: line,
+            msg: msg.into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    Number(f64),
+    Str(String),
+    Regex(String),
+    Name(String),
+
+    Begin,
+    End,
+    If,
+    Else,
+    While,
+    For,
+    Do,
+    Break,
+    Continue,
+    Next,
+    Exit,
+    Print,
+    Printf,
+
+    Newline,
+    LBrace,
+    RBrace,
+    LParen,
+    RParen,
+    Semicolon,
+    Comma,
+
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Caret,
+    Not,
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+    Equal,
+    NotEqual,
+    Match,
+    NoMatch,
+    And,
+    Or,
+    Question,
+    Colon,
+    Dollar,
+    Pipe,
+    Append,
+
+    Assign,
+    AddAssign,
+    SubAssign,
+    MulAssign,
+    DivAssign,
+    ModAssign,
+    PowAssign,
+    Incr,
+    Decr,
+
+    Eof,
+}
+
+impl Token {
+    /// Whether a `/` after this token is division rather than the start of a regex.
+    fn ends_operand(&self) -> bool {
+        match *self {
+            Token::Number(_)
+            | Token::Str(_)
+            | Token::Name(_)
+            | Token::RParen
+            | Token::Dollar
+            | Token::Incr
+            | Token::Decr => true,
+            _ => false,
+        }
+    }
+}
+
+/// Split the program text into tokens, each paired with the line it appeared on.
+pub fn tokenize(src: &str) -> StdResult<Vec<(Token, usize)>, SyntaxError> {
+    let mut lexer = Lexer {
+        chars: src.chars().collect(),
+        pos: 0,
+        line: 1,
+    };
+    let mut tokens: Vec<(Token, usize)> = vec![];
+
+    loop {
+        let regex_allowed = tokens.last().map(|t| !t.0.ends_operand()).unwrap_or(true);
+        let (token, line) = lexer.next_token(regex_allowed)?;
+        let done = token == Token::Eof;
+        tokens.push((token, line));
+        if done {
+            break;
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Lexer {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Lexer {
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).cloned()
+    }
+
+    /// Read the next token, returning it along with the line it is on.
+    fn next_token(&mut self, regex_allowed: bool) -> StdResult<(Token, usize), SyntaxError> {
+        // skip whitespace, comments, and escaped newlines
+        loop {
+            match self.peek_at(0) {
+                Some(' ') | Some('\t') | Some('\r') => self.pos += 1,
+                Some('\\') if self.peek_at(1) == Some('\n') => {
+                    self.pos += 2;
+                    self.line += 1;
+                }
+                Some('#') => {
+                    while self.peek_at(0).map(|ch| ch != '\n').unwrap_or(false) {
+                        self.pos += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+
+        let line = self.line;
+        let ch = match self.peek_at(0) {
+            Some(ch) => ch,
+            None => return Ok((Token::Eof, line)),
+        };
+        self.pos += 1;
+
+        let token = match ch {
+            '\n' => {
+                self.line += 1;
+                Token::Newline
+            }
+            '"' => Token::Str(self.read_string()?),
+            '/' if regex_allowed => Token::Regex(self.read_regex()?),
+            '0'...'9' => {
+                self.pos -= 1;
+                Token::Number(self.read_number())
+            }
+            '.' if self.peek_at(0).map(|c| c.is_ascii_digit()).unwrap_or(false) => {
+                self.pos -= 1;
+                Token::Number(self.read_number())
+            }
+            'a'...'z' | 'A'...'Z' | '_' => {
+                let start = self.pos - 1;
+                while self
+                    .peek_at(0)
+                    .map(|c| c.is_ascii_alphanumeric() || c == '_')
+                    .unwrap_or(false)
+                {
+                    self.pos += 1;
+                }
+                let word: String = self.chars[start..self.pos].iter().collect();
+                keyword(&word).unwrap_or(Token::Name(word))
+            }
+            '{' => Token::LBrace,
+            '}' => Token::RBrace,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            ';' => Token::Semicolon,
+            ',' => Token::Comma,
+            '?' => Token::Question,
+            ':' => Token::Colon,
+            '$' => Token::Dollar,
+            '~' => Token::Match,
+            '+' => self.choose(&[('+', Token::Incr), ('=', Token::AddAssign)], Token::Plus),
+            '-' => self.choose(&[('-', Token::Decr), ('=', Token::SubAssign)], Token::Minus),
+            '*' => self.choose(&[('=', Token::MulAssign)], Token::Star),
+            '/' => self.choose(&[('=', Token::DivAssign)], Token::Slash),
+            '%' => self.choose(&[('=', Token::ModAssign)], Token::Percent),
+            '^' => self.choose(&[('=', Token::PowAssign)], Token::Caret),
+            '!' => self.choose(&[('=', Token::NotEqual), ('~', Token::NoMatch)], Token::Not),
+            '>' => self.choose(
+                &[('=', Token::GreaterEq), ('>', Token::Append)],
+                Token::Greater,
+            ),
+            '<' => self.choose(&[('=', Token::LessEq)], Token::Less),
+            '=' => self.choose(&[('=', Token::Equal)], Token::Assign),
+            '&' if self.peek_at(0) == Some('&') => {
+                self.pos += 1;
+                Token::And
+            }
+            '|' => self.choose(&[('|', Token::Or)], Token::Pipe),
+            _ => {
+                let msg = format!("unexpected character '{}'", ch);
+                return Err(SyntaxError::new(self.line, msg));
+            }
+        };
+
+        Ok((token, line))
+    }
+
+    fn choose(&mut self, options: &[(char, Token)], default: Token) -> Token {
+        if let Some(next) = self.peek_at(0) {
+            for &(ch, ref token) in options {
+                if ch == next {
+                    self.pos += 1;
+                    return token.clone();
+                }
+            }
+        }
+        default
+    }
+
+    fn read_number(&mut self) -> f64 {
+        let start = self.pos;
+        while self.peek_at(0).map(|c| c.is_ascii_digit()).unwrap_or(false) {
+            self.pos += 1;
+        }
+        if self.peek_at(0) == Some('.') {
+            self.pos += 1;
+            while self.peek_at(0).map(|c| c.is_ascii_digit()).unwrap_or(false) {
+                self.pos += 1;
+            }
+        }
+        if self.peek_at(0) == Some('e') || self.peek_at(0) == Some('E') {
+            let digits_at = match self.peek_at(1) {
+                Some('+') | Some('-') => 2,
+                _ => 1,
+            };
+            if self
+                .peek_at(digits_at)
+                .map(|c| c.is_ascii_digit())
+                .unwrap_or(false)
+            {
+                self.pos += digits_at;
+                while self.peek_at(0).map(|c| c.is_ascii_digit()).unwrap_or(false) {
+                    self.pos += 1;
+                }
+            }
+        }
+
+        let text: String = self.chars[start..self.pos].iter().collect();
+        text.parse().unwrap_or(0.0)
+    }
+
+    fn read_string(&mut self) -> StdResult<String, SyntaxError> {
+        let start = self.pos;
+        loop {
+            match self.peek_at(0) {
+                None | Some('\n') => {
+                    return Err(SyntaxError::new(self.line, "unterminated string"));
+                }
+                Some('"') => break,
+                Some('\\') if self.peek_at(1).is_some() => self.pos += 2,
+                Some(_) => self.pos += 1,
+            }
+        }
+        let raw: String = self.chars[start..self.pos].iter().collect();
+        self.pos += 1;
+        Ok(unescape(&raw))
+    }
+
+    fn read_regex(&mut self) -> StdResult<String, SyntaxError> {
+        let mut result = String::new();
+        let mut in_bracket = false;
+        loop {
+            let ch = match self.peek_at(0) {
+                None | Some('\n') => return Err(SyntaxError::new(self.line, "unterminated regex")),
+                Some(ch) => ch,
+            };
+            self.pos += 1;
+
+            match ch {
+                '/' if !in_bracket => break,
+                '\\' => match self.peek_at(0) {
+                    Some('/') => {
+                        self.pos += 1;
+                        result.push('/');
+                    }
+                    Some(next) if next != '\n' => {
+                        self.pos += 1;
+                        result.push('\\');
+                        result.push(next);
+                    }
+                    _ => result.push('\\'),
+                },
+                '[' if !in_bracket => {
+                    in_bracket = true;
+                    result.push(ch);
+                    // a ] right after the opening bracket is part of the set
+                    if self.peek_at(0) == Some('^') {
+                        self.pos += 1;
+                        result.push('^');
+                    }
+                    if self.peek_at(0) == Some(']') {
+                        self.pos += 1;
+                        result.push(']');
+                    }
+                }
+                ']' if in_bracket => {
+                    in_bracket = false;
+                    result.push(ch);
+                }
+                _ => result.push(ch),
+            }
+        }
+        Ok(result)
+    }
+}
+
+fn keyword(word: &str) -> Option<Token> {
+    Some(match word {
+        "BEGIN" => Token::Begin,
+        "END" => Token::End,
+        "if" => Token::If,
+        "else" => Token::Else,
+        "while" => Token::While,
+        "for" => Token::For,
+        "do" => Token::Do,
+        "break" => Token::Break,
+        "continue" => Token::Continue,
+        "next" => Token::Next,
+        "exit" => Token::Exit,
+        "print" => Token::Print,
+        "printf" => Token::Printf,
+        _ => return None,
+    })
+}
+
+/// Process the escape sequences allowed in string literals (and in values given with -v).
+pub fn unescape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            result.push(ch);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some('a') => result.push('\x07'),
+            Some('b') => result.push('\x08'),
+            Some('f') => result.push('\x0c'),
+            Some('v') => result.push('\x0b'),
+            Some(digit @ '0'...'7') => {
+                let mut value = digit.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(d) => {
+                            value = value * 8 + d;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                result.push((value as u8) as char);
+            }
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+fn token_kinds(src: &str) -> Vec<Token> {
+    tokenize(src).unwrap().into_iter().map(|t| t.0).collect()
+}
+
+#[test]
+fn tokenize_regex_vs_division() {
+    let tokens = token_kinds("a / 2 ~ /x\\/y/");
+    assert_eq!(
+        tokens,
+        vec![
+            Token::Name("a".to_owned()),
+            Token::Slash,
+            Token::Number(2.0),
+            Token::Match,
+            Token::Regex("x/y".to_owned()),
+            Token::Eof,
+        ]
+    );
+}
+
+#[test]
+fn tokenize_operators() {
+    let tokens = token_kinds("x+=1e3;y!~z&&$1>=.5");
+    assert_eq!(
+        tokens,
+        vec![
+            Token::Name("x".to_owned()),
+            Token::AddAssign,
+            Token::Number(1000.0),
+            Token::Semicolon,
+            Token::Name("y".to_owned()),
+            Token::NoMatch,
+            Token::Name("z".to_owned()),
+            Token::And,
+            Token::Dollar,
+            Token::Number(1.0),
+            Token::GreaterEq,
+            Token::Number(0.5),
+            Token::Eof,
+        ]
+    );
+}
+
+#[test]
+fn unescape_sequences() {
+    assert_eq!(unescape(r"a\tb\n"), "a\tb\n");
+    assert_eq!(unescape(r#"\101\"\\"#), "A\"\\");
+    assert_eq!(unescape(r"\/"), "/");
+}
@@ -0,0 +1,270 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use util;
+use {ArgsIter, MesaError, PathError, PathOperation, Result, UtilRead, UtilSetup, UtilWrite};
+
+use clap::{AppSettings, Arg, ArgMatches};
+use std::ffi::OsStr;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+
+use self::interp::{Flow, Interp, Value};
+
+mod format;
+mod interp;
+mod lexer;
+mod parser;
+
+pub(crate) const NAME: &str = "awk";
+pub(crate) const DESCRIPTION: &str = "Scan and process patterns in text";
+
+// used for syntax errors, runtime errors, and unreadable input files
+const EXIT_ERROR: i32 = 2;
+
+#[derive(Debug, Fail)]
+#[fail(display = "no program given")]
+struct NoProgramError;
+
+#[derive(Debug, Fail)]
+#[fail(display = "invalid variable assignment: {}", _0)]
+struct AssignmentError(String);
+
+pub fn execute<S, T>(setup: &mut S, args: T) -> Result<()>
+where
+    S: UtilSetup,
+    T: ArgsIter,
+{
+    let matches = {
+        let app = util_app!(NAME)
+                    .setting(AppSettings::TrailingVarArg)
+                    .arg(Arg::with_name("field-separator")
+                            .short("F")
+                            .takes_value(true)
+                            .value_name("FS")
+                            .help("Use FS as the input field separator"))
+                    .arg(Arg::with_name("assign")
+                            .short("v")
+                            .takes_value(true)
+                            .value_name("VAR=VALUE")
+                            .multiple(true)
+                            .number_of_values(1)
+                            .help("Assign VALUE to VAR before the program is run"))
+                    .arg(Arg::with_name("progfile")
+                            .short("f")
+                            .takes_value(true)
+                            .value_name("PROGFILE")
+                            .multiple(true)
+                            .number_of_values(1)
+                            .help("Read the program from PROGFILE instead of the first operand"))
+                    .arg(Arg::with_name("ARGS")
+                            .value_name("PROGRAM] [ARGUMENT")
+                            .index(1)
+                            .multiple(true)
+                            .allow_hyphen_values(true));
+
+        app.get_matches_from_safe(args)?
+    };
+
+    let current_dir = setup.current_dir().map(|p| p.to_owned());
+
+    let mut operands: Vec<&OsStr> = matches
+        .values_of_os("ARGS")
+        .map(|values| values.collect())
+        .unwrap_or_default();
+
+    let source = collect_program(&matches, &current_dir, &mut operands)?;
+    let program =
+        parser::parse(&source).map_err(|e| MesaError::from(e).with_exitcode(EXIT_ERROR))?;
+
+    let (input, stdout, stderr) = setup.stdio();
+    let mut stdout = stdout.lock()?;
+    let mut stderr = stderr.lock()?;
+
+    let mut interp = Interp::new(&program, &mut stdout);
+
+    if let Some(fs) = matches.value_of_lossy("field-separator") {
+        let fs = if fs == "t" {
+            "\t".to_owned()
+        } else {
+            lexer::unescape(&fs)
+        };
+        interp.set_var("FS", Value::Str(fs))?;
+    }
+    if let Some(assignments) = matches.values_of_lossy("assign") {
+        for assignment in assignments {
+            match parse_assignment(&assignment) {
+                Some((name, value)) => {
+                    interp.set_var(name, value).map_err(|e| e.with_exitcode(EXIT_ERROR))?
+                }
+                None => return Err(AssignmentError(assignment.clone()).into()),
+            }
+        }
+    }
+
+    let mut exitcode = 0;
+    let result = run(
+        &mut interp,
+        input,
+        &mut stderr,
+        &current_dir,
+        &operands,
+        &mut exitcode,
+    );
+    result.map_err(|e| e.with_exitcode(EXIT_ERROR))?;
+
+    if interp.exit_code != 0 {
+        exitcode = interp.exit_code;
+    }
+    if exitcode == 0 {
+        Ok(())
+    } else {
+        Err(MesaError::new(None, exitcode, None))
+    }
+}
+
+/// Combine the programs given by -f, or use the first operand if there are none.
+fn collect_program(
+    matches: &ArgMatches,
+    current_dir: &Option<PathBuf>,
+    operands: &mut Vec<&OsStr>,
+) -> Result<String> {
+    if let Some(files) = matches.values_of_os("progfile") {
+        let mut source = String::new();
+        for file in files {
+            util::open_file(current_dir, file)?
+                .read_to_string(&mut source)
+                .map_err(|e| PathError::new(PathOperation::Read, file, e))?;
+            source.push('\n');
+        }
+        return Ok(source);
+    }
+
+    if operands.is_empty() {
+        return Err(NoProgramError.into());
+    }
+    Ok(operands.remove(0).to_string_lossy().into_owned())
+}
+
+/// Split an operand like `name=value` into the variable name and its value.
+fn parse_assignment(arg: &str) -> Option<(&str, Value)> {
+    let pos = arg.find('=')?;
+    let name = &arg[..pos];
+
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(ch) if ch.is_ascii_alphabetic() || ch == '_' => {}
+        _ => return None,
+    }
+    if !chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_') {
+        return None;
+    }
+
+    Some((name, Value::StrNum(lexer::unescape(&arg[pos + 1..]))))
+}
+
+fn run<'a, O, I, E>(
+    interp: &mut Interp<'a, O>,
+    input: &mut I,
+    stderr: &mut E,
+    current_dir: &Option<PathBuf>,
+    operands: &[&OsStr],
+    exitcode: &mut i32,
+) -> Result<()>
+where
+    O: Write,
+    I: for<'b> UtilRead<'b>,
+    E: Write,
+{
+    let mut exited = match interp.run_begin()? {
+        Flow::Exit => true,
+        _ => false,
+    };
+
+    if !exited && interp.needs_input() {
+        let mut stdin = Some(input.lock()?);
+        let mut read_file = false;
+
+        for &operand in operands {
+            let operand_str = operand.to_string_lossy();
+            if let Some((name, value)) = parse_assignment(&operand_str) {
+                interp.set_var(name, value)?;
+                continue;
+            }
+
+            read_file = true;
+            let flow = if operand == OsStr::new("-") {
+                // stdin can only be read once anyway
+                match stdin.take() {
+                    Some(mut stdin) => process(interp, &mut stdin, operand)?,
+                    None => continue,
+                }
+            } else {
+                match util::open_file(current_dir, operand) {
+                    Ok(file) => process(interp, &mut BufReader::new(file), operand)?,
+                    Err(e) => {
+                        display_msg!(stderr, "{}", e)?;
+                        *exitcode = EXIT_ERROR;
+                        continue;
+                    }
+                }
+            };
+
+            if let Flow::Exit = flow {
+                exited = true;
+                break;
+            }
+        }
+
+        if !read_file && !exited {
+            if let Some(mut stdin) = stdin.take() {
+                process(interp, &mut stdin, OsStr::new("-"))?;
+            }
+        }
+    }
+
+    interp.run_end()
+}
+
+/// Run the program against every record in `reader`.
+fn process<'a, O, R>(interp: &mut Interp<'a, O>, reader: &mut R, name: &OsStr) -> Result<Flow>
+where
+    O: Write,
+    R: BufRead,
+{
+    interp.start_file(&name.to_string_lossy());
+
+    loop {
+        // RS may be changed by the program at any time
+        let separator = interp.record_separator();
+        let mut data = vec![];
+        let n = reader
+            .read_until(separator, &mut data)
+            .map_err(|e| PathError::new(PathOperation::Read, name, e))?;
+        if n == 0 {
+            return Ok(Flow::Normal);
+        }
+        if data.last() == Some(&separator) {
+            data.pop();
+        }
+
+        let record = String::from_utf8_lossy(&data).into_owned();
+        if let Flow::Exit = interp.run_record(record)? {
+            return Ok(Flow::Exit);
+        }
+    }
+}
+
+#[test]
+fn assignments() {
+    assert!(parse_assignment("x=1").is_some());
+    assert!(parse_assignment("_a1=").is_some());
+    assert!(parse_assignment("1x=1").is_none());
+    assert!(parse_assignment("file.txt").is_none());
+    assert!(parse_assignment("a.b=c").is_none());
+}
@@ -0,0 +1,755 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use super::lexer::{self, SyntaxError, Token};
+use util::posix_regex;
+
+use regex::Regex;
+use std::result::Result as StdResult;
+
+type ParseResult<T> = StdResult<T, SyntaxError>;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CompareOp {
+    Less,
+    LessEq,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEq,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Builtin {
+    Length,
+    Substr,
+    Index,
+    Split,
+    Sub,
+    Gsub,
+    Match,
+    Sprintf,
+    ToLower,
+    ToUpper,
+    Int,
+    Sqrt,
+    Exp,
+    Log,
+    Sin,
+    Cos,
+    Atan2,
+}
+
+impl Builtin {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "length" => Builtin::Length,
+            "substr" => Builtin::Substr,
+            "index" => Builtin::Index,
+            "split" => Builtin::Split,
+            "sub" => Builtin::Sub,
+            "gsub" => Builtin::Gsub,
+            "match" => Builtin::Match,
+            "sprintf" => Builtin::Sprintf,
+            "tolower" => Builtin::ToLower,
+            "toupper" => Builtin::ToUpper,
+            "int" => Builtin::Int,
+            "sqrt" => Builtin::Sqrt,
+            "exp" => Builtin::Exp,
+            "log" => Builtin::Log,
+            "sin" => Builtin::Sin,
+            "cos" => Builtin::Cos,
+            "atan2" => Builtin::Atan2,
+            _ => return None,
+        })
+    }
+
+    /// The minimum and maximum number of arguments accepted.
+    fn arity(self) -> (usize, usize) {
+        match self {
+            Builtin::Length => (0, 1),
+            Builtin::Substr => (2, 3),
+            Builtin::Index | Builtin::Match | Builtin::Atan2 => (2, 2),
+            Builtin::Sub | Builtin::Gsub => (2, 3),
+            Builtin::Sprintf => (1, usize::max_value()),
+            _ => (1, 1),
+        }
+    }
+}
+
+pub enum Expr {
+    Num(f64),
+    Str(String),
+    /// A regex on its own, which matches against `$0`
+    Regex(Regex),
+    Var(String),
+    Field(Box<Expr>),
+    /// A parenthesized list of expressions like `(a, b)`, only really useful with `print`
+    Group(Vec<Expr>),
+    Assign(Option<BinaryOp>, Box<Expr>, Box<Expr>),
+    Cond(Box<Expr>, Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Pos(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Compare(CompareOp, Box<Expr>, Box<Expr>),
+    /// `lhs ~ rhs` (or `lhs !~ rhs` if the flag is true)
+    Match(bool, Box<Expr>, Box<Expr>),
+    Concat(Box<Expr>, Box<Expr>),
+    /// Increment (or decrement, with a negative delta) before or after evaluating
+    IncDec {
+        target: Box<Expr>,
+        delta: f64,
+        prefix: bool,
+    },
+    Call(Builtin, Vec<Expr>),
+}
+
+impl Expr {
+    fn is_lvalue(&self) -> bool {
+        match *self {
+            Expr::Var(_) | Expr::Field(_) => true,
+            _ => false,
+        }
+    }
+}
+
+pub enum Stmt {
+    Print(Vec<Expr>),
+    Printf(Vec<Expr>),
+    Expr(Expr),
+    If(Expr, Box<Stmt>, Option<Box<Stmt>>),
+    While(Expr, Box<Stmt>),
+    DoWhile(Box<Stmt>, Expr),
+    For(Option<Expr>, Option<Expr>, Option<Expr>, Box<Stmt>),
+    Block(Vec<Stmt>),
+    Next,
+    Exit(Option<Expr>),
+    Break,
+    Continue,
+}
+
+pub enum Pattern {
+    All,
+    Expr(Expr),
+    Range(Expr, Expr),
+}
+
+pub struct Rule {
+    pub pattern: Pattern,
+    /// The action to run (`None` means print the record)
+    pub action: Option<Vec<Stmt>>,
+}
+
+pub struct Program {
+    pub begin: Vec<Vec<Stmt>>,
+    pub rules: Vec<Rule>,
+    pub end: Vec<Vec<Stmt>>,
+}
+
+pub fn parse(src: &str) -> ParseResult<Program> {
+    let mut parser = Parser {
+        tokens: lexer::tokenize(src)?,
+        pos: 0,
+    };
+    parser.parse_program()
+}
+
+/// Compile an awk regex (which is a POSIX extended regex).
+pub fn compile_regex(pattern: &str) -> StdResult<Regex, String> {
+    let translated = posix_regex::translate(pattern, true).map_err(|e| e.to_string())?;
+    Regex::new(&translated).map_err(|e| e.to_string())
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].0.clone();
+        if token != Token::Eof {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn error<T>(&self, msg: &str) -> ParseResult<T> {
+        let (ref token, line) = self.tokens[self.pos];
+        let msg = match *token {
+            Token::Eof => format!("{} at end of program", msg),
+            Token::Newline => format!("{} at end of line", msg),
+            ref token => format!("{} near {:?}", msg, token),
+        };
+        Err(SyntaxError::new(line, msg))
+    }
+
+    fn expect(&mut self, token: Token) -> ParseResult<()> {
+        if *self.peek() == token {
+            self.next();
+            Ok(())
+        } else {
+            self.error(&format!("expected {:?}", token))
+        }
+    }
+
+    fn skip_newlines(&mut self) {
+        while *self.peek() == Token::Newline {
+            self.next();
+        }
+    }
+
+    fn skip_terminators(&mut self) {
+        while *self.peek() == Token::Newline || *self.peek() == Token::Semicolon {
+            self.next();
+        }
+    }
+
+    fn parse_program(&mut self) -> ParseResult<Program> {
+        let mut program = Program {
+            begin: vec![],
+            rules: vec![],
+            end: vec![],
+        };
+
+        self.skip_terminators();
+        while *self.peek() != Token::Eof {
+            match *self.peek() {
+                Token::Begin => {
+                    self.next();
+                    program.begin.push(self.parse_block()?);
+                }
+                Token::End => {
+                    self.next();
+                    program.end.push(self.parse_block()?);
+                }
+                Token::LBrace => {
+                    let action = self.parse_block()?;
+                    program.rules.push(Rule {
+                        pattern: Pattern::All,
+                        action: Some(action),
+                    });
+                }
+                _ => {
+                    let first = self.parse_expr(false)?;
+                    let pattern = if *self.peek() == Token::Comma {
+                        self.next();
+                        self.skip_newlines();
+                        Pattern::Range(first, self.parse_expr(false)?)
+                    } else {
+                        Pattern::Expr(first)
+                    };
+                    let action = if *self.peek() == Token::LBrace {
+                        Some(self.parse_block()?)
+                    } else {
+                        None
+                    };
+                    program.rules.push(Rule {
+                        pattern: pattern,
+                        action: action,
+                    });
+                }
+            }
+
+            // items that end with an action do not need a terminator
+            let after_action = self.tokens[self.pos - 1].0 == Token::RBrace;
+            match *self.peek() {
+                Token::Newline | Token::Semicolon | Token::Eof => self.skip_terminators(),
+                _ if after_action => {}
+                _ => return self.error("unexpected token"),
+            }
+        }
+
+        Ok(program)
+    }
+
+    fn parse_block(&mut self) -> ParseResult<Vec<Stmt>> {
+        self.expect(Token::LBrace)?;
+        let mut stmts = vec![];
+        loop {
+            self.skip_terminators();
+            if *self.peek() == Token::RBrace {
+                self.next();
+                return Ok(stmts);
+            }
+            stmts.push(self.parse_stmt()?);
+        }
+    }
+
+    fn parse_stmt(&mut self) -> ParseResult<Stmt> {
+        let stmt = match *self.peek() {
+            Token::LBrace => return Ok(Stmt::Block(self.parse_block()?)),
+            Token::Semicolon => {
+                self.next();
+                return Ok(Stmt::Block(vec![]));
+            }
+            Token::If => {
+                self.next();
+                self.expect(Token::LParen)?;
+                let cond = self.parse_expr(false)?;
+                self.expect(Token::RParen)?;
+                self.skip_newlines();
+                let then = self.parse_stmt()?;
+
+                // look past any terminators for an else
+                let saved = self.pos;
+                self.skip_terminators();
+                let otherwise = if *self.peek() == Token::Else {
+                    self.next();
+                    self.skip_newlines();
+                    Some(Box::new(self.parse_stmt()?))
+                } else {
+                    self.pos = saved;
+                    None
+                };
+                return Ok(Stmt::If(cond, Box::new(then), otherwise));
+            }
+            Token::While => {
+                self.next();
+                self.expect(Token::LParen)?;
+                let cond = self.parse_expr(false)?;
+                self.expect(Token::RParen)?;
+                if *self.peek() == Token::Semicolon {
+                    self.next();
+                    return Ok(Stmt::While(cond, Box::new(Stmt::Block(vec![]))));
+                }
+                self.skip_newlines();
+                return Ok(Stmt::While(cond, Box::new(self.parse_stmt()?)));
+            }
+            Token::Do => {
+                self.next();
+                self.skip_newlines();
+                let body = self.parse_stmt()?;
+                self.skip_terminators();
+                self.expect(Token::While)?;
+                self.expect(Token::LParen)?;
+                let cond = self.parse_expr(false)?;
+                self.expect(Token::RParen)?;
+                Stmt::DoWhile(Box::new(body), cond)
+            }
+            Token::For => {
+                self.next();
+                self.expect(Token::LParen)?;
+                let init = self.parse_opt_expr(Token::Semicolon)?;
+                self.expect(Token::Semicolon)?;
+                self.skip_newlines();
+                let cond = self.parse_opt_expr(Token::Semicolon)?;
+                self.expect(Token::Semicolon)?;
+                self.skip_newlines();
+                let step = self.parse_opt_expr(Token::RParen)?;
+                self.expect(Token::RParen)?;
+                if *self.peek() == Token::Semicolon {
+                    self.next();
+                    return Ok(Stmt::For(init, cond, step, Box::new(Stmt::Block(vec![]))));
+                }
+                self.skip_newlines();
+                return Ok(Stmt::For(init, cond, step, Box::new(self.parse_stmt()?)));
+            }
+            _ => self.parse_simple_stmt()?,
+        };
+
+        // simple statements must be followed by a terminator (or the end of the block)
+        match *self.peek() {
+            Token::Semicolon | Token::Newline => {
+                self.next();
+            }
+            Token::RBrace | Token::Eof => {}
+            _ => return self.error("unexpected token"),
+        }
+
+        Ok(stmt)
+    }
+
+    fn parse_opt_expr(&mut self, end: Token) -> ParseResult<Option<Expr>> {
+        if *self.peek() == end {
+            Ok(None)
+        } else {
+            self.parse_expr(false).map(Some)
+        }
+    }
+
+    fn parse_simple_stmt(&mut self) -> ParseResult<Stmt> {
+        Ok(match self.next() {
+            Token::Print => Stmt::Print(self.parse_print_args()?),
+            Token::Printf => {
+                let args = self.parse_print_args()?;
+                if args.is_empty() {
+                    return self.error("printf requires a format");
+                }
+                Stmt::Printf(args)
+            }
+            Token::Next => Stmt::Next,
+            Token::Break => Stmt::Break,
+            Token::Continue => Stmt::Continue,
+            Token::Exit => match *self.peek() {
+                Token::Semicolon | Token::Newline | Token::RBrace | Token::Eof => Stmt::Exit(None),
+                _ => Stmt::Exit(Some(self.parse_expr(false)?)),
+            },
+            _ => {
+                self.pos -= 1;
+                Stmt::Expr(self.parse_expr(false)?)
+            }
+        })
+    }
+
+    fn parse_print_args(&mut self) -> ParseResult<Vec<Expr>> {
+        let mut args = match *self.peek() {
+            Token::Semicolon | Token::Newline | Token::RBrace | Token::Eof => vec![],
+            _ => self.parse_expr_list(true)?,
+        };
+
+        match *self.peek() {
+            Token::Greater | Token::Append | Token::Pipe => {
+                return self.error("output redirection is not supported")
+            }
+            _ => {}
+        }
+
+        // print (a, b) is the same as print a, b
+        if args.len() == 1 {
+            if let Expr::Group(_) = args[0] {
+                if let Some(Expr::Group(list)) = args.pop() {
+                    args = list;
+                }
+            }
+        }
+
+        Ok(args)
+    }
+
+    fn parse_expr_list(&mut self, no_gt: bool) -> ParseResult<Vec<Expr>> {
+        let mut list = vec![self.parse_expr(no_gt)?];
+        while *self.peek() == Token::Comma {
+            self.next();
+            self.skip_newlines();
+            list.push(self.parse_expr(no_gt)?);
+        }
+        Ok(list)
+    }
+
+    /// Parse an expression.  If `no_gt` is true, `>` is not treated as a comparison (outside of
+    /// parentheses) so that it can be used for redirection by `print`.
+    fn parse_expr(&mut self, no_gt: bool) -> ParseResult<Expr> {
+        let lhs = self.parse_ternary(no_gt)?;
+
+        let op = match *self.peek() {
+            Token::Assign => None,
+            Token::AddAssign => Some(BinaryOp::Add),
+            Token::SubAssign => Some(BinaryOp::Sub),
+            Token::MulAssign => Some(BinaryOp::Mul),
+            Token::DivAssign => Some(BinaryOp::Div),
+            Token::ModAssign => Some(BinaryOp::Mod),
+            Token::PowAssign => Some(BinaryOp::Pow),
+            _ => return Ok(lhs),
+        };
+        if !lhs.is_lvalue() {
+            return self.error("invalid assignment target");
+        }
+        self.next();
+        self.skip_newlines();
+        let rhs = self.parse_expr(no_gt)?;
+
+        Ok(Expr::Assign(op, Box::new(lhs), Box::new(rhs)))
+    }
+
+    fn parse_ternary(&mut self, no_gt: bool) -> ParseResult<Expr> {
+        let cond = self.parse_or(no_gt)?;
+        if *self.peek() != Token::Question {
+            return Ok(cond);
+        }
+
+        self.next();
+        self.skip_newlines();
+        let then = self.parse_expr(no_gt)?;
+        self.skip_newlines();
+        self.expect(Token::Colon)?;
+        self.skip_newlines();
+        let otherwise = self.parse_expr(no_gt)?;
+
+        Ok(Expr::Cond(
+            Box::new(cond),
+            Box::new(then),
+            Box::new(otherwise),
+        ))
+    }
+
+    fn parse_or(&mut self, no_gt: bool) -> ParseResult<Expr> {
+        let mut lhs = self.parse_and(no_gt)?;
+        while *self.peek() == Token::Or {
+            self.next();
+            self.skip_newlines();
+            let rhs = self.parse_and(no_gt)?;
+            lhs = Expr::Or(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self, no_gt: bool) -> ParseResult<Expr> {
+        let mut lhs = self.parse_match(no_gt)?;
+        while *self.peek() == Token::And {
+            self.next();
+            self.skip_newlines();
+            let rhs = self.parse_match(no_gt)?;
+            lhs = Expr::And(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_match(&mut self, no_gt: bool) -> ParseResult<Expr> {
+        let mut lhs = self.parse_comparison(no_gt)?;
+        loop {
+            let negate = match *self.peek() {
+                Token::Match => false,
+                Token::NoMatch => true,
+                _ => return Ok(lhs),
+            };
+            self.next();
+            let rhs = self.parse_comparison(no_gt)?;
+            lhs = Expr::Match(negate, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn parse_comparison(&mut self, no_gt: bool) -> ParseResult<Expr> {
+        let lhs = self.parse_concat()?;
+        let op = match *self.peek() {
+            Token::Less => CompareOp::Less,
+            Token::LessEq => CompareOp::LessEq,
+            Token::Equal => CompareOp::Equal,
+            Token::NotEqual => CompareOp::NotEqual,
+            Token::Greater if !no_gt => CompareOp::Greater,
+            Token::GreaterEq => CompareOp::GreaterEq,
+            _ => return Ok(lhs),
+        };
+        self.next();
+        let rhs = self.parse_concat()?;
+        Ok(Expr::Compare(op, Box::new(lhs), Box::new(rhs)))
+    }
+
+    fn parse_concat(&mut self) -> ParseResult<Expr> {
+        let mut lhs = self.parse_additive()?;
+        loop {
+            // unary operators cannot start the right-hand side as `a -1` is a subtraction
+            match *self.peek() {
+                Token::Number(_)
+                | Token::Str(_)
+                | Token::Name(_)
+                | Token::Dollar
+                | Token::LParen
+                | Token::Incr
+                | Token::Decr => {}
+                _ => return Ok(lhs),
+            }
+            let rhs = self.parse_additive()?;
+            lhs = Expr::Concat(Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn parse_additive(&mut self) -> ParseResult<Expr> {
+        let mut lhs = self.parse_multiplicative()?;
+        loop {
+            let op = match *self.peek() {
+                Token::Plus => BinaryOp::Add,
+                Token::Minus => BinaryOp::Sub,
+                _ => return Ok(lhs),
+            };
+            self.next();
+            let rhs = self.parse_multiplicative()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn parse_multiplicative(&mut self) -> ParseResult<Expr> {
+        let mut lhs = self.parse_unary()?;
+        loop {
+            let op = match *self.peek() {
+                Token::Star => BinaryOp::Mul,
+                Token::Slash => BinaryOp::Div,
+                Token::Percent => BinaryOp::Mod,
+                _ => return Ok(lhs),
+            };
+            self.next();
+            let rhs = self.parse_unary()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn parse_unary(&mut self) -> ParseResult<Expr> {
+        match *self.peek() {
+            Token::Not => {
+                self.next();
+                Ok(Expr::Not(Box::new(self.parse_unary()?)))
+            }
+            Token::Minus => {
+                self.next();
+                Ok(Expr::Neg(Box::new(self.parse_unary()?)))
+            }
+            Token::Plus => {
+                self.next();
+                Ok(Expr::Pos(Box::new(self.parse_unary()?)))
+            }
+            _ => self.parse_power(),
+        }
+    }
+
+    fn parse_power(&mut self) -> ParseResult<Expr> {
+        let base = self.parse_postfix()?;
+        if *self.peek() != Token::Caret {
+            return Ok(base);
+        }
+        self.next();
+        // ^ is right-associative and binds tighter than unary minus on its left only
+        let exponent = self.parse_unary()?;
+        Ok(Expr::Binary(
+            BinaryOp::Pow,
+            Box::new(base),
+            Box::new(exponent),
+        ))
+    }
+
+    fn parse_postfix(&mut self) -> ParseResult<Expr> {
+        let expr = self.parse_primary()?;
+        if expr.is_lvalue() {
+            let delta = match *self.peek() {
+                Token::Incr => 1.0,
+                Token::Decr => -1.0,
+                _ => return Ok(expr),
+            };
+            self.next();
+            return Ok(Expr::IncDec {
+                target: Box::new(expr),
+                delta: delta,
+                prefix: false,
+            });
+        }
+        Ok(expr)
+    }
+
+    fn parse_primary(&mut self) -> ParseResult<Expr> {
+        let line = self.tokens[self.pos].1;
+        match self.next() {
+            Token::Number(num) => Ok(Expr::Num(num)),
+            Token::Str(s) => Ok(Expr::Str(s)),
+            Token::Regex(pattern) => compile_regex(&pattern)
+                .map(Expr::Regex)
+                .map_err(|e| SyntaxError::new(line, e)),
+            Token::Dollar => {
+                let index = match *self.peek() {
+                    Token::Incr | Token::Decr | Token::Minus => self.parse_unary()?,
+                    _ => self.parse_primary()?,
+                };
+                Ok(Expr::Field(Box::new(index)))
+            }
+            token @ Token::Incr | token @ Token::Decr => {
+                let target = self.parse_primary()?;
+                if !target.is_lvalue() {
+                    return self.error("invalid increment target");
+                }
+                Ok(Expr::IncDec {
+                    target: Box::new(target),
+                    delta: if token == Token::Incr { 1.0 } else { -1.0 },
+                    prefix: true,
+                })
+            }
+            Token::LParen => {
+                self.skip_newlines();
+                let mut list = self.parse_expr_list(false)?;
+                self.skip_newlines();
+                self.expect(Token::RParen)?;
+                if list.len() == 1 {
+                    Ok(list.pop().unwrap())
+                } else {
+                    Ok(Expr::Group(list))
+                }
+            }
+            Token::Minus | Token::Not | Token::Plus => {
+                self.pos -= 1;
+                self.parse_unary()
+            }
+            Token::Name(name) => match Builtin::from_name(&name) {
+                Some(builtin) => self.parse_call(builtin),
+                None => Ok(Expr::Var(name)),
+            },
+            _ => {
+                self.pos -= 1;
+                self.error("syntax error")
+            }
+        }
+    }
+
+    fn parse_call(&mut self, builtin: Builtin) -> ParseResult<Expr> {
+        let mut args = vec![];
+        if *self.peek() == Token::LParen {
+            self.next();
+            self.skip_newlines();
+            if *self.peek() != Token::RParen {
+                args = self.parse_expr_list(false)?;
+            }
+            self.skip_newlines();
+            self.expect(Token::RParen)?;
+        } else if builtin != Builtin::Length {
+            return self.error("expected (");
+        }
+
+        let (min, max) = builtin.arity();
+        if args.len() < min || args.len() > max {
+            return self.error("wrong number of arguments");
+        }
+
+        match builtin {
+            Builtin::Sub | Builtin::Gsub => {
+                if args.len() == 2 {
+                    args.push(Expr::Field(Box::new(Expr::Num(0.0))));
+                } else if !args[2].is_lvalue() {
+                    return self.error("the target of sub/gsub must be a variable or field");
+                }
+            }
+            Builtin::Split => return self.error("arrays are not supported"),
+            _ => {}
+        }
+
+        Ok(Expr::Call(builtin, args))
+    }
+}
+
+#[test]
+fn parse_programs() {
+    assert!(parse("BEGIN { x = 1 } $1 > 2 { print $1, $2 } END { print NR }").is_ok());
+    assert!(parse("/foo/,/bar/").is_ok());
+    assert!(parse("{ if (x) print; else { y++ } }\n").is_ok());
+    assert!(parse("{ for (i = 1; i <= NF; i++) s += $i }").is_ok());
+    assert!(parse("{ while (i < 3) i++; do i--; while (i > 0) }").is_ok());
+    assert!(parse("{ printf \"%d\\n\", -$1 ^ 2 }").is_ok());
+    assert!(parse("{ sub(/a/, \"b\"); n = gsub(\"x\", \"y\", $2) }").is_ok());
+}
+
+#[test]
+fn parse_errors() {
+    assert!(parse("{ print $1 > \"file\" }").is_err());
+    assert!(parse("{ 1 = 2 }").is_err());
+    assert!(parse("{ substr(1) }").is_err());
+    assert!(parse("{ gsub(/a/, \"b\", 1) }").is_err());
+    assert!(parse("BEGIN { print ( }").is_err());
+    assert!(parse("{ x = 1 y = 2 }").is_err());
+}
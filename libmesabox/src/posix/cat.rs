@@ -38,9 +38,11 @@
 use clap::{App, Arg};
 use std::ffi::OsStr;
 use std::fs::{metadata, File};
-use std::io::{self, BufRead, Read, Write};
+use std::io::{self, BufRead, Cursor, Read, Write};
 use std::iter;
 use std::path::Path;
+use util::binary::{self, BinaryFileError};
+use util::linenum;
 use util::{self, is_tty};
 use {ArgsIter, LockError, Result, UtilRead, UtilSetup, UtilWrite};
 
@@ -56,6 +58,9 @@ pub const NAME: &str = "cat";
 pub const DESCRIPTION: &str = "Concatenate FILE(s), or standard input, to standard output
 With no FILE, or when FILE is -, read standard input.";
 
+// the default size of the buffer used when the output needs to be transformed line by line
+const LINE_BUF_SIZE: usize = 1024 * 31;
+
 #[derive(PartialEq)]
 enum NumberingMode {
     NumberNone,
@@ -92,6 +97,10 @@ enum CatError {
     /// Denotes an error caused by one of stdin, stdout, or stderr failing to lock
     #[fail(display = "{}", _0)]
     Lock(#[cause] LockError),
+
+    /// A file that looks binary was not shown (with `--binary-check`)
+    #[fail(display = "{}", _0)]
+    Binary(#[cause] BinaryFileError),
 }
 
 impl From<io::Error> for CatError {
@@ -201,6 +210,8 @@ where
     stderr: E,
     current_dir: Option<&'a Path>,
     interactive: bool,
+    buffer_size: Option<usize>,
+    binary_check: bool,
 }
 
 impl<'c, I, O, E> Cat<'c, I, O, E>
@@ -215,6 +226,8 @@ where
         stderr: E,
         current_dir: Option<&'c Path>,
         interactive: bool,
+        buffer_size: Option<usize>,
+        binary_check: bool,
     ) -> Self {
         Self {
             stdin: stdin,
@@ -222,6 +235,8 @@ where
             stderr: stderr,
             current_dir: current_dir,
             interactive: interactive,
+            buffer_size: buffer_size,
+            binary_check: binary_check,
         }
     }
 
@@ -240,6 +255,7 @@ where
         let mut error_count = 0;
 
         let writer = &mut self.stdout;
+        let buffer_size = self.buffer_size;
 
         for file in files {
             let res = Self::open_and_exec(
@@ -247,8 +263,12 @@ where
                 &mut self.stdin,
                 self.current_dir,
                 self.interactive,
+                self.binary_check,
                 |handle| {
-                    io::copy(handle.reader, writer).map_err(|err| CatError::Input {
+                    match buffer_size {
+                        Some(size) => util::copy_with_buffer(handle.reader, writer, size),
+                        None => io::copy(handle.reader, writer),
+                    }.map_err(|err| CatError::Input {
                         err: err,
                         path: file.to_string_lossy().into_owned(),
                     })?;
@@ -256,6 +276,10 @@ where
                 },
             );
             if let Err(error) = res {
+                // nothing else can be written once standard output is gone
+                if util::is_broken_pipe(&error) {
+                    return Err(error);
+                }
                 display_msg!(self.stderr, "{}", error)?;
                 error_count += 1;
             }
@@ -288,6 +312,9 @@ where
 
         for file in files {
             if let Err(error) = self.write_file_lines(&file, options, &mut state) {
+                if util::is_broken_pipe(&error) {
+                    return Err(error);
+                }
                 display_msg!(self.stderr, "{}", error)?;
                 error_count += 1;
             }
@@ -308,7 +335,7 @@ where
         options: &OutputOptions<'b>,
         state: &mut OutputState,
     ) -> CatResult<()> {
-        let mut in_buf = [0; 1024 * 31];
+        let mut in_buf = vec![0; self.buffer_size.unwrap_or(LINE_BUF_SIZE)];
         // TODO: maybe pass the callback as well so it can become an FnMut and be reused?
         let writer = &mut self.stdout;
         Self::open_and_exec(
@@ -316,6 +343,7 @@ where
             &mut self.stdin,
             self.current_dir,
             self.interactive,
+            self.binary_check,
             |handle| {
                 let mut one_blank_kept = false;
 
@@ -333,7 +361,8 @@ where
                                 one_blank_kept = true;
                                 if state.at_line_start && options.number == NumberingMode::NumberAll
                                 {
-                                    write!(writer, "{0:6}\t", state.line_number)?;
+                                    let number = state.line_number as i64;
+                                    linenum::DEFAULT_FORMAT.write(writer, number)?;
                                     state.line_number += 1;
                                 }
                                 writer.write_all(options.end_of_line.as_bytes())?;
@@ -347,7 +376,7 @@ where
                         }
                         one_blank_kept = false;
                         if state.at_line_start && options.number != NumberingMode::NumberNone {
-                            write!(writer, "{0:6}\t", state.line_number)?;
+                            linenum::DEFAULT_FORMAT.write(writer, state.line_number as i64)?;
                             state.line_number += 1;
                         }
 
@@ -390,16 +419,14 @@ where
         stdin: &mut I,
         current_dir: Option<&Path>,
         interactive: bool,
+        binary_check: bool,
         func: T,
     ) -> CatResult<()>
     where
         T: FnOnce(InputHandle<&mut Read>) -> CatResult<()>,
     {
         if path == "-" {
-            return func(InputHandle {
-                reader: stdin,
-                is_interactive: interactive,
-            });
+            return Self::check_and_exec("-", stdin, interactive, binary_check, func);
         }
 
         // XXX: determine if buffering the file/socket would be of any benefit (we are already
@@ -414,25 +441,54 @@ where
                     .and_then(|sock| sock.shutdown(Shutdown::Write).map(|_| sock))
                     .map_err(|err| CatError::Input {
                         err: err,
-                        path: lossy_path.into_owned(),
+                        path: lossy_path.to_string(),
                     })?;
-                func(InputHandle {
-                    reader: &mut socket as &mut Read,
-                    is_interactive: false,
-                })
+                Self::check_and_exec(&lossy_path, &mut socket, false, binary_check, func)
             }
             _ => {
                 let mut file = File::open(path).map_err(|err| CatError::Input {
                     err: err,
-                    path: lossy_path.into_owned(),
+                    path: lossy_path.to_string(),
                 })?;
-                func(InputHandle {
-                    reader: &mut file as &mut Read,
-                    is_interactive: false,
-                })
+                Self::check_and_exec(&lossy_path, &mut file, false, binary_check, func)
             }
         }
     }
+
+    /// Calls `func` with an InputHandle for `reader`, unless `binary_check` is set and the input
+    /// looks binary
+    fn check_and_exec<T>(
+        path: &str,
+        reader: &mut Read,
+        interactive: bool,
+        binary_check: bool,
+        func: T,
+    ) -> CatResult<()>
+    where
+        T: FnOnce(InputHandle<&mut Read>) -> CatResult<()>,
+    {
+        if !binary_check {
+            return func(InputHandle {
+                reader: reader,
+                is_interactive: interactive,
+            });
+        }
+
+        let start = binary::read_start(reader).map_err(|err| CatError::Input {
+            err: err,
+            path: path.to_owned(),
+        })?;
+        if binary::looks_binary(&start) {
+            return Err(CatError::Binary(BinaryFileError(path.to_owned())));
+        }
+
+        // the data that was checked still has to be written
+        let mut reader = Cursor::new(start).chain(reader);
+        func(InputHandle {
+            reader: &mut reader as &mut Read,
+            is_interactive: interactive,
+        })
+    }
 }
 
 type CatResult<T> = ::std::result::Result<T, CatError>;
@@ -466,10 +522,12 @@ where
         || matches.is_present("t");
     options.squeeze_blank = matches.is_present("squeeze-blank");
 
+    let binary_check = binary::is_checked(&matches, setup.output().raw_object());
+
     if let Some(files) = matches.values_of_os("FILES") {
-        run(setup, files, options)
+        run(setup, files, options, binary_check)
     } else {
-        run(setup, iter::once(OsStr::new("-")), options)
+        run(setup, iter::once(OsStr::new("-")), options, binary_check)
     }
 }
 
@@ -520,10 +578,17 @@ fn create_app() -> App<'static, 'static> {
                 .long("show-nonprinting")
                 .help("use ^ and M- notation, except for LF (\\n) and TAB (\\t)"),
         )
+        .arg(binary::binary_check_arg())
+        .arg(binary::text_arg())
         .arg(Arg::with_name("FILES").index(1).multiple(true))
 }
 
-fn run<'a, 'b, S, T>(setup: &mut S, files: T, mut options: OutputOptions<'b>) -> Result<()>
+fn run<'a, 'b, S, T>(
+    setup: &mut S,
+    files: T,
+    mut options: OutputOptions<'b>,
+    binary_check: bool,
+) -> Result<()>
 where
     S: UtilSetup,
     T: Iterator<Item = &'a OsStr>,
@@ -533,6 +598,7 @@ where
     let interactive = is_tty(setup.input().raw_object());
     // XXX: should current_dir() just return Option<Rc<Path>> or something similar to avoid the cloning?
     let curdir = setup.current_dir().map(|p| p.to_path_buf());
+    let buffer_size = setup.buffer_size();
     let (input, output, error) = setup.stdio();
     let stdin = input.lock()?;
     let stdout = output.lock()?;
@@ -544,6 +610,8 @@ where
         stderr,
         curdir.as_ref().map(|p| p.as_path()),
         interactive,
+        buffer_size,
+        binary_check,
     );
 
     if can_write_fast {
@@ -31,8 +31,10 @@
 //     SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 //
 
+use util::dirfd::{Dir, EntryKind, FollowLinks};
+use util::preserve_root::{self, PreserveRoot};
 use util;
-use {ArgsIter, MesaError, Result, UtilSetup, UtilWrite};
+use {ArgsIter, MesaError, PathError, PathOperation, Result, UtilSetup, UtilWrite};
 
 use clap::{AppSettings, Arg, ArgGroup, OsValues};
 use std::ffi::{OsStr, OsString};
@@ -43,7 +45,6 @@ use std::path::{Path, PathBuf};
 use std::result::Result as StdResult;
 use uucore::fs::display_permissions_unix;
 use uucore::mode;
-use walkdir::WalkDir;
 
 const NAME: &str = "chmod";
 pub(crate) const DESCRIPTION: &str = "Change the file permissions of given files";
@@ -96,8 +97,9 @@ impl Message {
 
 struct Options<'a> {
     verbosity: Verbosity,
-    preserve_root: bool,
+    preserve_root: PreserveRoot,
     recursive: bool,
+    follow: FollowLinks,
     fmode: Option<u32>,
     cmode: Option<&'a str>,
     current_dir: Option<PathBuf>,
@@ -116,18 +118,24 @@ where
                             .long("recursive")
                             .short("R")
                             .help("change files and directories recursively"))
+                    .arg(Arg::with_name("follow-operands")
+                            .short("H")
+                            .overrides_with_all(&["follow-all", "follow-none"])
+                            .help("if a command line argument is a symbolic link to a directory, traverse it"))
+                    .arg(Arg::with_name("follow-all")
+                            .short("L")
+                            .overrides_with_all(&["follow-operands", "follow-none"])
+                            .help("traverse every symbolic link to a directory encountered"))
+                    .arg(Arg::with_name("follow-none")
+                            .short("P")
+                            .overrides_with_all(&["follow-operands", "follow-all"])
+                            .help("do not traverse any symbolic links (the default)"))
                     .arg(Arg::with_name("reference")
                             .long("reference")
                             .takes_value(true)
                             .value_name("RFILE")
                             .help("use RFILE's mode instead of provided MODE values"))
-                    .arg(Arg::with_name("preserve-root")
-                            .long("preserve-root")
-                            .help("fail to operate recursively on '/'"))
-                    .arg(Arg::with_name("no-preserve-root")
-                            .long("no-preserve-root")
-                            .overrides_with("preserve-root")
-                            .help("do not treat '/' specially (the default)"))
+                    .args(&preserve_root::preserve_root_args())
                     .arg(Arg::with_name("verbose")
                             .long("verbose")
                             .short("v")
@@ -169,9 +177,16 @@ where
         Verbosity::None
     };
 
-    let preserve_root = matches.is_present("preserve-root");
+    let preserve_root = PreserveRoot::from_matches(&matches);
 
     let recursive = matches.is_present("recursive");
+    let follow = if matches.is_present("follow-all") {
+        FollowLinks::Always
+    } else if matches.is_present("follow-operands") {
+        FollowLinks::Operands
+    } else {
+        FollowLinks::Never
+    };
     let fmode = match matches.value_of_os("reference") {
         Some(ref_file) => Some(fs::metadata(ref_file)
             .map(|data| data.mode())
@@ -190,6 +205,7 @@ where
         verbosity: verbosity,
         preserve_root: preserve_root,
         recursive: recursive,
+        follow: follow,
         fmode: fmode,
         cmode: matches.value_of("MODE"),
         current_dir: current_dir,
@@ -247,7 +263,13 @@ where
         for filename in files {
             let file = util::actual_path(&options.current_dir, filename);
 
-            r |= if file.is_dir() && options.recursive {
+            // -H, -L and -P only matter when recursing, as symlinks are followed otherwise
+            let skip = options.recursive && !options.follow.operands() && is_symlink(&file);
+
+            r |= if skip {
+                // like the symlinks found while recursing, these are left alone
+                Ok(0)
+            } else if file.is_dir() && options.recursive {
                 self.chmod_dir(options, &mut msgs, &file)
             } else {
                 let res = chmod_file(options, &mut msgs, &file);
@@ -263,28 +285,102 @@ where
         options: &Options,
         msgs: &mut [Option<Message>; 2],
         file: &Path,
+    ) -> Result<i32> {
+        if let Err(e) = options.preserve_root.check(file) {
+            display_msg!(self.stderr, "{}", e)?;
+            display_msg!(self.stderr, "{}", e.hint())?;
+            return Ok(1);
+        }
+
+        match Dir::open(file, options.follow.operands()) {
+            Ok(dir) => self.chmod_tree(options, msgs, &dir, file, &mut vec![]),
+            Err(e) => {
+                let err = PathError::new(PathOperation::OpenDir, file, e);
+                display_msg!(self.stderr, "{}", err)?;
+                Ok(1)
+            }
+        }
+    }
+
+    // everything below the operand is changed relative to an open directory so that replacing part
+    // of the tree with a symlink while we are working on it cannot send us somewhere else (unless
+    // we were asked to follow symlinks with -L).  `ancestors` holds the device and inode numbers
+    // of the directories above `dir`
+    fn chmod_tree(
+        &mut self,
+        options: &Options,
+        msgs: &mut [Option<Message>; 2],
+        dir: &Dir,
+        path: &Path,
+        ancestors: &mut Vec<(u64, u64)>,
     ) -> Result<i32> {
         let mut r = 0;
 
-        if !options.preserve_root || file != Path::new("/") {
-            let walker = WalkDir::new(file).contents_first(true);
-            for entry in walker {
-                match entry {
-                    Ok(entry) => {
-                        r |= chmod_file(options, msgs, &entry.path());
+        let depth = ancestors.len();
+        let dir_stat = dir.stat();
+        if let Ok(ref stat) = dir_stat {
+            ancestors.push((stat.dev, stat.ino));
+        }
+
+        let follow = options.follow.entries();
+        match dir.entries() {
+            Ok(names) => for name in names {
+                let child = path.join(&name);
+                let stat = match dir.stat_at(&name, follow) {
+                    Ok(stat) => stat,
+                    Err(e) => {
+                        let err = PathError::new(PathOperation::Stat, &child, e);
+                        display_msg!(self.stderr, "{}", err)?;
+                        r = 1;
+                        continue;
+                    }
+                };
+                match stat.kind {
+                    // like in GNU chmod, symlinks found while recursing are ignored without -L
+                    EntryKind::Symlink => {}
+                    // with -L, a symlink can lead back to a directory we are already in
+                    EntryKind::Dir if ancestors.contains(&(stat.dev, stat.ino)) => {}
+                    EntryKind::Dir => match dir.open_at(&name, follow) {
+                        Ok(subdir) => {
+                            r |= self.chmod_tree(options, msgs, &subdir, &child, ancestors)?
+                        }
+                        Err(e) => {
+                            let err = PathError::new(PathOperation::OpenDir, &child, e);
+                            display_msg!(self.stderr, "{}", err)?;
+                            r = 1;
+                        }
+                    },
+                    EntryKind::Other => {
+                        let fperm = stat.mode & 0o7777;
+                        r |= chmod_with(options, msgs, &child, fperm, false, |mode| {
+                            dir.chmod_at(&name, &stat, mode, follow)
+                        });
                         self.write_msgs(msgs)?;
                     }
-                    Err(f) => display_msg!(self.stderr, "{}", f)?,
                 }
+            },
+            Err(e) => {
+                let err = PathError::new(PathOperation::OpenDir, path, e);
+                display_msg!(self.stderr, "{}", err)?;
+                r = 1;
+            }
+        }
+
+        // the directory itself is changed last so that taking away our own access to it cannot
+        // keep us from reaching its contents
+        ancestors.truncate(depth);
+        match dir_stat {
+            Ok(stat) => {
+                let fperm = stat.mode & 0o7777;
+                r |= chmod_with(options, msgs, path, fperm, true, |mode| dir.chmod(mode));
+            }
+            Err(e) => {
+                let err = PathError::new(PathOperation::Stat, path, e);
+                display_msg!(self.stderr, "{}", err)?;
+                r = 1;
             }
-        } else {
-            display_msg!(
-                self.stderr,
-                "could not change permissions of directory '{}'",
-                file.display()
-            )?;
-            r = 1;
         }
+        self.write_msgs(msgs)?;
 
         Ok(r)
     }
@@ -303,9 +399,15 @@ where
     }
 }
 
+fn is_symlink(file: &Path) -> bool {
+    fs::symlink_metadata(file)
+        .map(|meta| meta.file_type().is_symlink())
+        .unwrap_or(false)
+}
+
 #[cfg(any(unix, target_os = "redox"))]
 fn chmod_file(options: &Options, msgs: &mut [Option<Message>; 2], file: &Path) -> i32 {
-    let mut fperm = match fs::metadata(file) {
+    let fperm = match fs::metadata(file) {
         Ok(meta) => meta.mode() & 0o7777,
         Err(err) => {
             if options.verbosity != Verbosity::Quiet {
@@ -318,8 +420,26 @@ fn chmod_file(options: &Options, msgs: &mut [Option<Message>; 2], file: &Path) -
             return 1;
         }
     };
+    chmod_with(options, msgs, file, fperm, file.is_dir(), |mode| {
+        fs::set_permissions(file, fs::Permissions::from_mode(mode))
+    })
+}
+
+// apply the requested mode to a file with the permissions `fperm`, using `set` to change them
+#[cfg(any(unix, target_os = "redox"))]
+fn chmod_with<F>(
+    options: &Options,
+    msgs: &mut [Option<Message>; 2],
+    file: &Path,
+    mut fperm: u32,
+    is_dir: bool,
+    mut set: F,
+) -> i32
+where
+    F: FnMut(u32) -> io::Result<()>,
+{
     match options.fmode {
-        Some(mode) => change_file(options, msgs, fperm, mode, file),
+        Some(mode) => change_file(options, msgs, fperm, mode, file, &mut set),
         None => {
             let cmode_unwrapped = options.cmode.clone().unwrap();
             for mode in cmode_unwrapped.split(',') {
@@ -328,11 +448,11 @@ fn chmod_file(options: &Options, msgs: &mut [Option<Message>; 2], file: &Path) -
                 let result = if mode.contains(arr) {
                     mode::parse_numeric(fperm, mode)
                 } else {
-                    mode::parse_symbolic(fperm, mode, file.is_dir())
+                    mode::parse_symbolic(fperm, mode, is_dir)
                 };
                 match result {
                     Ok(mode) => {
-                        change_file(options, msgs, fperm, mode, file);
+                        change_file(options, msgs, fperm, mode, file, &mut set);
                         fperm = mode;
                     }
                     Err(f) => {
@@ -349,13 +469,17 @@ fn chmod_file(options: &Options, msgs: &mut [Option<Message>; 2], file: &Path) -
 }
 
 #[cfg(unix)]
-fn change_file(
+fn change_file<F>(
     options: &Options,
     msgs: &mut [Option<Message>; 2],
     fperm: u32,
     mode: u32,
     file: &Path,
-) -> i32 {
+    set: &mut F,
+) -> i32
+where
+    F: FnMut(u32) -> io::Result<()>,
+{
     if fperm == mode {
         if options.verbosity == Verbosity::Verbose {
             msgs[0] = Some(Message::stdout(format!(
@@ -370,7 +494,7 @@ fn change_file(
 
     let mut exitcode = 0;
 
-    let res = fs::set_permissions(file, fs::Permissions::from_mode(mode));
+    let res = set(mode);
     if let Err(err) = res {
         let mut count = 0;
         if options.verbosity != Verbosity::Quiet {
@@ -0,0 +1,400 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use util;
+use {ArgsIter, MesaError, PathError, PathOperation, Result, UtilRead, UtilSetup, UtilWrite};
+
+use clap::{Arg, ArgMatches};
+use std::ffi::OsStr;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::result::Result as StdResult;
+
+pub(crate) const NAME: &str = "cmp";
+pub(crate) const DESCRIPTION: &str = "Compare two files byte by byte";
+
+// these match the exit codes used by GNU cmp
+const EXIT_DIFFERENT: i32 = 1;
+const EXIT_TROUBLE: i32 = 2;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum OutputMode {
+    /// Report the first difference
+    First,
+    /// List every difference
+    List,
+    /// Only report differences through the exit status
+    Silent,
+}
+
+struct Options {
+    mode: OutputMode,
+    print_bytes: bool,
+    skip: (u64, u64),
+    limit: Option<u64>,
+}
+
+struct Input<'a> {
+    name: &'a OsStr,
+    reader: Box<BufRead + 'a>,
+}
+
+pub fn execute<S, T>(setup: &mut S, args: T) -> Result<()>
+where
+    S: UtilSetup,
+    T: ArgsIter,
+{
+    let matches = {
+        let app = util_app!(NAME)
+                    .arg(Arg::with_name("print-bytes")
+                            .short("b")
+                            .long("print-bytes")
+                            .help("Print the differing bytes"))
+                    .arg(Arg::with_name("ignore-initial")
+                            .short("i")
+                            .long("ignore-initial")
+                            .takes_value(true)
+                            .value_name("SKIP")
+                            .validator(is_valid_skip)
+                            .help("Skip the first SKIP bytes of both inputs (or SKIP1:SKIP2 to skip different amounts)"))
+                    .arg(Arg::with_name("verbose")
+                            .short("l")
+                            .long("verbose")
+                            .help("Output the byte number and differing byte values of every difference"))
+                    .arg(Arg::with_name("bytes")
+                            .short("n")
+                            .long("bytes")
+                            .takes_value(true)
+                            .value_name("LIMIT")
+                            .validator(is_valid_limit)
+                            .help("Compare at most LIMIT bytes"))
+                    .arg(Arg::with_name("silent")
+                            .short("s")
+                            .long("silent")
+                            .alias("quiet")
+                            .conflicts_with("verbose")
+                            .help("Output nothing and only return an exit status"))
+                    .arg(Arg::with_name("FILE1")
+                            .index(1)
+                            .required(true))
+                    .arg(Arg::with_name("FILE2")
+                            .index(2));
+
+        app.get_matches_from_safe(args)
+            .map_err(|e| MesaError::from(e).with_exitcode(EXIT_TROUBLE))?
+    };
+
+    let options = Options {
+        mode: if matches.is_present("silent") {
+            OutputMode::Silent
+        } else if matches.is_present("verbose") {
+            OutputMode::List
+        } else {
+            OutputMode::First
+        },
+        print_bytes: matches.is_present("print-bytes"),
+        skip: matches
+            .value_of("ignore-initial")
+            .and_then(parse_skip)
+            .unwrap_or((0, 0)),
+        limit: parse_limit(&matches),
+    };
+
+    let name1 = matches.value_of_os("FILE1").unwrap();
+    let name2 = matches.value_of_os("FILE2").unwrap_or(OsStr::new("-"));
+
+    // standard input can only be read once, so it is always identical to itself
+    if name1 == "-" && name2 == "-" {
+        return Ok(());
+    }
+
+    let current_dir = setup.current_dir().map(|p| p.to_owned());
+    let (stdin, stdout, stderr) = setup.stdio();
+
+    let differ = {
+        let mut stdin = if name1 == "-" || name2 == "-" {
+            Some(stdin.lock()?)
+        } else {
+            None
+        };
+        let input1 = open_input(name1, &current_dir, &mut stdin)
+            .map_err(|e| e.with_exitcode(EXIT_TROUBLE))?;
+        let input2 = open_input(name2, &current_dir, &mut stdin)
+            .map_err(|e| e.with_exitcode(EXIT_TROUBLE))?;
+
+        let mut cmp = Comparer {
+            output: stdout.lock()?,
+            stderr: stderr.lock()?,
+            options: &options,
+        };
+        cmp.compare(input1, input2)
+            .map_err(|e| e.with_exitcode(EXIT_TROUBLE))?
+    };
+
+    if differ {
+        Err(MesaError::new(None, EXIT_DIFFERENT, None))
+    } else {
+        Ok(())
+    }
+}
+
+fn is_valid_skip(val: String) -> StdResult<(), String> {
+    parse_skip(&val)
+        .map(|_| ())
+        .ok_or_else(|| format!("invalid --ignore-initial value '{}'", val))
+}
+
+fn is_valid_limit(val: String) -> StdResult<(), String> {
+    val.parse::<u64>()
+        .map(|_| ())
+        .map_err(|_| format!("invalid --bytes value '{}'", val))
+}
+
+/// Parse SKIP or SKIP1:SKIP2.
+fn parse_skip(val: &str) -> Option<(u64, u64)> {
+    let mut parts = val.splitn(2, ':');
+    let skip1 = parts.next()?.parse().ok()?;
+    let skip2 = match parts.next() {
+        Some(part) => part.parse().ok()?,
+        None => skip1,
+    };
+    Some((skip1, skip2))
+}
+
+fn open_input<'a, R>(
+    name: &'a OsStr,
+    current_dir: &Option<PathBuf>,
+    stdin: &mut Option<R>,
+) -> Result<Input<'a>>
+where
+    R: BufRead + 'a,
+{
+    let reader: Box<BufRead + 'a> = if name == "-" {
+        match stdin.take() {
+            Some(stdin) => Box::new(stdin),
+            None => Box::new(io::empty()),
+        }
+    } else {
+        Box::new(BufReader::new(util::open_file(current_dir, name)?))
+    };
+    Ok(Input {
+        name: name,
+        reader: reader,
+    })
+}
+
+fn parse_limit(matches: &ArgMatches) -> Option<u64> {
+    matches.value_of("bytes").map(|v| v.parse().unwrap())
+}
+
+/// Represent a byte the way `cat -v` would.
+fn printable_byte(byte: u8) -> String {
+    let mut result = String::new();
+    let mut byte = byte;
+    if byte >= 0x80 {
+        result.push_str("M-");
+        byte -= 0x80;
+    }
+    match byte {
+        0x7f => result.push_str("^?"),
+        0...0x1f => {
+            result.push('^');
+            result.push((byte + b'@') as char);
+        }
+        _ => result.push(byte as char),
+    }
+    result
+}
+
+struct Comparer<'a, O, E>
+where
+    O: Write,
+    E: Write,
+{
+    output: O,
+    stderr: E,
+    options: &'a Options,
+}
+
+impl<'a, O, E> Comparer<'a, O, E>
+where
+    O: Write,
+    E: Write,
+{
+    /// Compare the two inputs, returning whether they differ.
+    fn compare(&mut self, mut input1: Input, mut input2: Input) -> Result<bool> {
+        let (skip1, skip2) = self.options.skip;
+        skip(&mut input1, skip1)?;
+        skip(&mut input2, skip2)?;
+
+        // the number of bytes compared so far, and the line they are on
+        let mut count: u64 = 0;
+        let mut line: u64 = 1;
+        let mut differ = false;
+        let mut after_newline = false;
+
+        let (name1, name2) = (input1.name, input2.name);
+        loop {
+            let remaining = match self.options.limit {
+                Some(limit) if count >= limit => break,
+                Some(limit) => limit - count,
+                None => u64::max_value(),
+            };
+
+            let len = {
+                let buf1 = fill_buf(&mut input1)?;
+                let buf2 = fill_buf(&mut input2)?;
+
+                if buf1.is_empty() || buf2.is_empty() {
+                    if buf1.is_empty() && buf2.is_empty() {
+                        break;
+                    }
+                    // the message refers to the line containing the last byte
+                    let name = if buf1.is_empty() { name1 } else { name2 };
+                    let line = if after_newline { line - 1 } else { line };
+                    self.report_eof(name, count, line)?;
+                    return Ok(true);
+                }
+
+                let mut len = buf1.len().min(buf2.len());
+                if remaining < len as u64 {
+                    len = remaining as usize;
+                }
+                let (buf1, buf2) = (&buf1[..len], &buf2[..len]);
+
+                if buf1 == buf2 {
+                    line += buf1.iter().filter(|&&b| b == b'\n').count() as u64;
+                } else {
+                    for (i, (&byte1, &byte2)) in buf1.iter().zip(buf2.iter()).enumerate() {
+                        if byte1 != byte2 {
+                            differ = true;
+                            let offset = count + i as u64 + 1;
+                            match self.options.mode {
+                                OutputMode::Silent => return Ok(true),
+                                OutputMode::First => {
+                                    self.report_first(name1, name2, offset, line, byte1, byte2)?;
+                                    return Ok(true);
+                                }
+                                OutputMode::List => self.report_list(offset, byte1, byte2)?,
+                            }
+                        }
+                        if byte1 == b'\n' {
+                            line += 1;
+                        }
+                    }
+                }
+                after_newline = buf1[len - 1] == b'\n';
+                len
+            };
+
+            input1.reader.consume(len);
+            input2.reader.consume(len);
+            count += len as u64;
+        }
+
+        Ok(differ)
+    }
+
+    fn report_first(
+        &mut self,
+        name1: &OsStr,
+        name2: &OsStr,
+        offset: u64,
+        line: u64,
+        byte1: u8,
+        byte2: u8,
+    ) -> Result<()> {
+        write!(
+            self.output,
+            "{} {} differ: byte {}, line {}",
+            name1.to_string_lossy(),
+            name2.to_string_lossy(),
+            offset,
+            line
+        )?;
+        if self.options.print_bytes {
+            write!(
+                self.output,
+                " is {:3o} {} {:3o} {}",
+                byte1,
+                printable_byte(byte1),
+                byte2,
+                printable_byte(byte2)
+            )?;
+        }
+        writeln!(self.output)?;
+        Ok(())
+    }
+
+    fn report_list(&mut self, offset: u64, byte1: u8, byte2: u8) -> Result<()> {
+        if self.options.print_bytes {
+            writeln!(
+                self.output,
+                "{} {:3o} {:<4} {:3o} {}",
+                offset,
+                byte1,
+                printable_byte(byte1),
+                byte2,
+                printable_byte(byte2)
+            )?;
+        } else {
+            writeln!(self.output, "{} {:3o} {:3o}", offset, byte1, byte2)?;
+        }
+        Ok(())
+    }
+
+    /// Report that `name` ended after `count` bytes (when the other input did not).
+    fn report_eof(&mut self, name: &OsStr, count: u64, line: u64) -> Result<()> {
+        if self.options.mode == OutputMode::Silent {
+            return Ok(());
+        }
+
+        // anything already listed should come before the message
+        self.output.flush()?;
+
+        let name = name.to_string_lossy();
+        if count == 0 {
+            display_msg!(self.stderr, "EOF on {} which is empty", name)?;
+        } else {
+            display_msg!(self.stderr, "EOF on {} after byte {}, line {}", name, count, line)?;
+        }
+        Ok(())
+    }
+}
+
+fn fill_buf<'b>(input: &'b mut Input) -> Result<&'b [u8]> {
+    let name = input.name;
+    input
+        .reader
+        .fill_buf()
+        .map_err(|e| PathError::new(PathOperation::Read, name, e).into())
+}
+
+fn skip(input: &mut Input, count: u64) -> Result<()> {
+    let name = input.name;
+    io::copy(&mut (&mut input.reader).take(count), &mut io::sink())
+        .map_err(|e| PathError::new(PathOperation::Read, name, e))?;
+    Ok(())
+}
+
+#[test]
+fn skip_values() {
+    assert_eq!(parse_skip("10"), Some((10, 10)));
+    assert_eq!(parse_skip("1:2"), Some((1, 2)));
+    assert_eq!(parse_skip("1:"), None);
+    assert_eq!(parse_skip("x"), None);
+}
+
+#[test]
+fn printable_bytes() {
+    assert_eq!(printable_byte(b'a'), "a");
+    assert_eq!(printable_byte(b'\n'), "^J");
+    assert_eq!(printable_byte(0x7f), "^?");
+    assert_eq!(printable_byte(0xe1), "M-a");
+    assert_eq!(printable_byte(0x80), "M-^@");
+}
@@ -0,0 +1,280 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use std::io::{self, Write};
+
+use super::myers::Change;
+
+/// The lines of one of the files being compared.  Every line includes its newline (except
+/// perhaps the last one).
+pub type Lines<'a> = [&'a [u8]];
+
+/// Output in the default format, which only shows the changed lines.
+pub fn write_normal<W: Write>(
+    output: &mut W,
+    a: &Lines,
+    b: &Lines,
+    changes: &[Change],
+) -> io::Result<()> {
+    for change in changes {
+        let a_range = normal_range(change.a, change.del);
+        let b_range = normal_range(change.b, change.ins);
+        if change.ins == 0 {
+            writeln!(output, "{}d{}", a_range, change.b)?;
+        } else if change.del == 0 {
+            writeln!(output, "{}a{}", change.a, b_range)?;
+        } else {
+            writeln!(output, "{}c{}", a_range, b_range)?;
+        }
+
+        for line in &a[change.a..change.a + change.del] {
+            write_line(output, b"< ", line)?;
+        }
+        if change.del > 0 && change.ins > 0 {
+            writeln!(output, "---")?;
+        }
+        for line in &b[change.b..change.b + change.ins] {
+            write_line(output, b"> ", line)?;
+        }
+    }
+    Ok(())
+}
+
+/// Output in the unified format (`-u`), with `context` lines of context around each change.
+pub fn write_unified<W: Write>(
+    output: &mut W,
+    labels: (&str, &str),
+    a: &Lines,
+    b: &Lines,
+    changes: &[Change],
+    context: usize,
+) -> io::Result<()> {
+    writeln!(output, "--- {}", labels.0)?;
+    writeln!(output, "+++ {}", labels.1)?;
+
+    for hunk in Hunk::group(changes, a.len(), context) {
+        writeln!(
+            output,
+            "@@ -{} +{} @@",
+            unified_range(hunk.start_a, hunk.end_a),
+            unified_range(hunk.start_b, hunk.end_b)
+        )?;
+
+        let mut pos = hunk.start_a;
+        for change in hunk.changes {
+            for line in &a[pos..change.a] {
+                write_line(output, b" ", line)?;
+            }
+            for line in &a[change.a..change.a + change.del] {
+                write_line(output, b"-", line)?;
+            }
+            for line in &b[change.b..change.b + change.ins] {
+                write_line(output, b"+", line)?;
+            }
+            pos = change.a + change.del;
+        }
+        for line in &a[pos..hunk.end_a] {
+            write_line(output, b" ", line)?;
+        }
+    }
+    Ok(())
+}
+
+/// Output in the context format (`-c`), with `context` lines of context around each change.
+pub fn write_context<W: Write>(
+    output: &mut W,
+    labels: (&str, &str),
+    a: &Lines,
+    b: &Lines,
+    changes: &[Change],
+    context: usize,
+) -> io::Result<()> {
+    writeln!(output, "*** {}", labels.0)?;
+    writeln!(output, "--- {}", labels.1)?;
+
+    for hunk in Hunk::group(changes, a.len(), context) {
+        writeln!(output, "***************")?;
+
+        writeln!(output, "*** {} ****", context_range(hunk.start_a, hunk.end_a))?;
+        // each side is only shown if it has lines that changed
+        if hunk.changes.iter().any(|change| change.del > 0) {
+            let mut pos = hunk.start_a;
+            for change in hunk.changes {
+                for line in &a[pos..change.a] {
+                    write_line(output, b"  ", line)?;
+                }
+                let prefix = if change.ins > 0 { b"! " } else { b"- " };
+                for line in &a[change.a..change.a + change.del] {
+                    write_line(output, prefix, line)?;
+                }
+                pos = change.a + change.del;
+            }
+            for line in &a[pos..hunk.end_a] {
+                write_line(output, b"  ", line)?;
+            }
+        }
+
+        writeln!(output, "--- {} ----", context_range(hunk.start_b, hunk.end_b))?;
+        if hunk.changes.iter().any(|change| change.ins > 0) {
+            let mut pos = hunk.start_b;
+            for change in hunk.changes {
+                for line in &b[pos..change.b] {
+                    write_line(output, b"  ", line)?;
+                }
+                let prefix = if change.del > 0 { b"! " } else { b"+ " };
+                for line in &b[change.b..change.b + change.ins] {
+                    write_line(output, prefix, line)?;
+                }
+                pos = change.b + change.ins;
+            }
+            for line in &b[pos..hunk.end_b] {
+                write_line(output, b"  ", line)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A group of changes that are close enough to share their context lines.
+struct Hunk<'c> {
+    changes: &'c [Change],
+    start_a: usize,
+    end_a: usize,
+    start_b: usize,
+    end_b: usize,
+}
+
+impl<'c> Hunk<'c> {
+    fn group(changes: &'c [Change], len_a: usize, context: usize) -> Vec<Hunk<'c>> {
+        let mut hunks = vec![];
+        let mut start = 0;
+
+        while start < changes.len() {
+            let mut end = start + 1;
+            while end < changes.len() {
+                let prev = &changes[end - 1];
+                if changes[end].a - (prev.a + prev.del) > 2 * context {
+                    break;
+                }
+                end += 1;
+            }
+
+            let (first, last) = (&changes[start], &changes[end - 1]);
+            // the lines around a change are identical in both files, so the same amount of
+            // context is available on either side
+            let before = context.min(first.a);
+            let after = context.min(len_a - (last.a + last.del));
+            hunks.push(Hunk {
+                changes: &changes[start..end],
+                start_a: first.a - before,
+                end_a: last.a + last.del + after,
+                start_b: first.b - before,
+                end_b: last.b + last.ins + after,
+            });
+
+            start = end;
+        }
+
+        hunks
+    }
+}
+
+fn write_line<W: Write>(output: &mut W, prefix: &[u8], line: &[u8]) -> io::Result<()> {
+    output.write_all(prefix)?;
+    output.write_all(line)?;
+    if !line.ends_with(b"\n") {
+        output.write_all(b"\n\\ No newline at end of file\n")?;
+    }
+    Ok(())
+}
+
+/// Format the 0-based range starting at `start` with `count` lines (as `FIRST[,LAST]`).
+fn normal_range(start: usize, count: usize) -> String {
+    if count == 1 {
+        format!("{}", start + 1)
+    } else {
+        format!("{},{}", start + 1, start + count)
+    }
+}
+
+/// Format the range `start..end` as `FIRST[,COUNT]`, where an empty range refers to the line
+/// just before it.
+fn unified_range(start: usize, end: usize) -> String {
+    match end - start {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        count => format!("{},{}", start + 1, count),
+    }
+}
+
+/// Format the range `start..end` as `FIRST[,LAST]`, where an empty range refers to the line
+/// just before it.
+fn context_range(start: usize, end: usize) -> String {
+    if end <= start + 1 {
+        format!("{}", end)
+    } else {
+        format!("{},{}", start + 1, end)
+    }
+}
+
+#[cfg(test)]
+fn format_with<F>(a: &str, b: &str, func: F) -> String
+where
+    F: Fn(&mut Vec<u8>, &Lines, &Lines, &[Change]) -> io::Result<()>,
+{
+    let a_lines: Vec<String> = a.lines().map(|line| format!("{}\n", line)).collect();
+    let b_lines: Vec<String> = b.lines().map(|line| format!("{}\n", line)).collect();
+    let a_lines: Vec<&[u8]> = a_lines.iter().map(|line| line.as_bytes()).collect();
+    let b_lines: Vec<&[u8]> = b_lines.iter().map(|line| line.as_bytes()).collect();
+    let changes = super::myers::diff(&a_lines, &b_lines);
+
+    let mut output = vec![];
+    func(&mut output, &a_lines, &b_lines, &changes).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn normal_format() {
+    let output = format_with("a\nb\nc\n", "a\nx\nc\nd\n", |out, a, b, c| write_normal(out, a, b, c));
+    assert_eq!(output, "2c2\n< b\n---\n> x\n3a4\n> d\n");
+
+    let output = format_with("a\nb\nc\n", "c\n", |out, a, b, c| write_normal(out, a, b, c));
+    assert_eq!(output, "1,2d0\n< a\n< b\n");
+}
+
+#[test]
+fn unified_format() {
+    let a = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+    let b = "1\n2\nthree\n4\n5\n6\n7\n8\n9\n10\n11\n";
+    let output = format_with(a, b, |out, a, b, c| write_unified(out, ("a", "b"), a, b, c, 1));
+    assert_eq!(
+        output,
+        "--- a\n+++ b\n@@ -2,3 +2,3 @@\n 2\n-3\n+three\n 4\n@@ -10 +10,2 @@\n 10\n+11\n"
+    );
+
+    // nearby changes share a hunk
+    let output = format_with(a, b, |out, a, b, c| write_unified(out, ("a", "b"), a, b, c, 4));
+    assert!(output.starts_with("--- a\n+++ b\n@@ -1,10 +1,11 @@\n"));
+
+    let output = format_with("", "x\n", |out, a, b, c| write_unified(out, ("a", "b"), a, b, c, 3));
+    assert_eq!(output, "--- a\n+++ b\n@@ -0,0 +1 @@\n+x\n");
+}
+
+#[test]
+fn context_format() {
+    let output = format_with("a\nb\nc\n", "a\nx\nc\n", |out, a, b, c| {
+        write_context(out, ("a", "b"), a, b, c, 3)
+    });
+    assert_eq!(
+        output,
+        "*** a\n--- b\n***************\n*** 1,3 ****\n  a\n! b\n  c\n--- 1,3 ----\n  a\n! x\n  c\n"
+    );
+
+    let output = format_with("a\n", "a\nb\n", |out, a, b, c| write_context(out, ("a", "b"), a, b, c, 3));
+    assert_eq!(output, "*** a\n--- b\n***************\n*** 1 ****\n--- 1,2 ----\n  a\n+ b\n");
+}
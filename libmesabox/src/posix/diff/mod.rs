@@ -0,0 +1,388 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use util::{self, casefold};
+use {ArgsIter, MesaError, PathError, PathOperation, Result, UtilRead, UtilSetup, UtilWrite};
+
+use chrono::{Local, TimeZone};
+use clap::{Arg, ArgMatches};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::result::Result as StdResult;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use self::format::Lines;
+
+mod format;
+mod myers;
+
+pub(crate) const NAME: &str = "diff";
+pub(crate) const DESCRIPTION: &str = "Compare files line by line";
+
+// these match the exit codes used by GNU diff
+const EXIT_DIFFERENT: i32 = 1;
+const EXIT_TROUBLE: i32 = 2;
+
+const DEFAULT_CONTEXT: usize = 3;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum OutputFormat {
+    Normal,
+    Unified(usize),
+    Context(usize),
+    Brief,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Whitespace {
+    /// Whitespace is compared like everything else
+    Exact,
+    /// Changes in the amount of whitespace are ignored (-b)
+    IgnoreChange,
+    /// All whitespace is ignored (-w)
+    IgnoreAll,
+}
+
+struct Options {
+    format: OutputFormat,
+    ignore_case: bool,
+    whitespace: Whitespace,
+}
+
+pub fn execute<S, T>(setup: &mut S, args: T) -> Result<()>
+where
+    S: UtilSetup,
+    T: ArgsIter,
+{
+    let matches = {
+        let app = util_app!(NAME)
+                    .arg(Arg::with_name("brief")
+                            .short("q")
+                            .long("brief")
+                            .help("Only report whether the files differ"))
+                    .arg(Arg::with_name("unified")
+                            .short("u")
+                            .help("Output 3 lines of unified context"))
+                    .arg(Arg::with_name("unified-lines")
+                            .short("U")
+                            .long("unified")
+                            .takes_value(true)
+                            .value_name("NUM")
+                            .validator(is_valid_num)
+                            .help("Output NUM lines of unified context"))
+                    .arg(Arg::with_name("context")
+                            .short("c")
+                            .conflicts_with_all(&["unified", "unified-lines"])
+                            .help("Output 3 lines of copied context"))
+                    .arg(Arg::with_name("context-lines")
+                            .short("C")
+                            .long("context")
+                            .takes_value(true)
+                            .value_name("NUM")
+                            .validator(is_valid_num)
+                            .conflicts_with_all(&["unified", "unified-lines"])
+                            .help("Output NUM lines of copied context"))
+                    .arg(Arg::with_name("ignore-case")
+                            .short("i")
+                            .long("ignore-case")
+                            .help("Ignore case differences"))
+                    .arg(Arg::with_name("ignore-space-change")
+                            .short("b")
+                            .long("ignore-space-change")
+                            .help("Ignore changes in the amount of whitespace"))
+                    .arg(Arg::with_name("ignore-all-space")
+                            .short("w")
+                            .long("ignore-all-space")
+                            .help("Ignore all whitespace"))
+                    .arg(Arg::with_name("label")
+                            .long("label")
+                            .takes_value(true)
+                            .value_name("LABEL")
+                            .multiple(true)
+                            .number_of_values(1)
+                            .max_values(2)
+                            .help("Use LABEL instead of the file name and time in the header (may be given twice)"))
+                    .arg(Arg::with_name("FILE1")
+                            .index(1)
+                            .required(true))
+                    .arg(Arg::with_name("FILE2")
+                            .index(2)
+                            .required(true));
+
+        app.get_matches_from_safe(args)
+            .map_err(|e| MesaError::from(e).with_exitcode(EXIT_TROUBLE))?
+    };
+
+    let options = Options {
+        format: output_format(&matches),
+        ignore_case: matches.is_present("ignore-case"),
+        whitespace: if matches.is_present("ignore-all-space") {
+            Whitespace::IgnoreAll
+        } else if matches.is_present("ignore-space-change") {
+            Whitespace::IgnoreChange
+        } else {
+            Whitespace::Exact
+        },
+    };
+
+    let name1 = matches.value_of_os("FILE1").unwrap();
+    let name2 = matches.value_of_os("FILE2").unwrap();
+    let mut labels = matches.values_of_lossy("label").unwrap_or_default().into_iter();
+
+    let current_dir = setup.current_dir().map(|p| p.to_owned());
+    let (stdin, stdout, _) = setup.stdio();
+
+    let (data1, data2) = {
+        let mut stdin_data = None;
+        let mut read = |name: &OsStr| -> Result<Vec<u8>> {
+            if name == "-" {
+                // standard input can only be read once, so reuse the data if given twice
+                if stdin_data.is_none() {
+                    let mut data = vec![];
+                    stdin.lock()?.read_to_end(&mut data)?;
+                    stdin_data = Some(data);
+                }
+                Ok(stdin_data.clone().unwrap())
+            } else {
+                let mut data = vec![];
+                util::open_file(&current_dir, name)?
+                    .read_to_end(&mut data)
+                    .map_err(|e| PathError::new(PathOperation::Read, name, e))?;
+                Ok(data)
+            }
+        };
+
+        let data1 = read(name1).map_err(|e| e.with_exitcode(EXIT_TROUBLE))?;
+        let data2 = read(name2).map_err(|e| e.with_exitcode(EXIT_TROUBLE))?;
+        (data1, data2)
+    };
+
+    let label1 = labels.next().unwrap_or_else(|| default_label(name1, &current_dir));
+    let label2 = labels.next().unwrap_or_else(|| default_label(name2, &current_dir));
+
+    let mut output = stdout.lock()?;
+    let differ = diff(&mut output, &options, (name1, name2), (&label1, &label2), &data1, &data2)
+        .map_err(|e| e.with_exitcode(EXIT_TROUBLE))?;
+
+    if differ {
+        Err(MesaError::new(None, EXIT_DIFFERENT, None))
+    } else {
+        Ok(())
+    }
+}
+
+fn is_valid_num(val: String) -> StdResult<(), String> {
+    val.parse::<usize>()
+        .map(|_| ())
+        .map_err(|_| format!("invalid context length '{}'", val))
+}
+
+fn output_format(matches: &ArgMatches) -> OutputFormat {
+    let lines = |name| {
+        matches
+            .value_of(name)
+            .map(|v| v.parse().unwrap())
+            .unwrap_or(DEFAULT_CONTEXT)
+    };
+
+    if matches.is_present("brief") {
+        OutputFormat::Brief
+    } else if matches.is_present("unified") || matches.is_present("unified-lines") {
+        OutputFormat::Unified(lines("unified-lines"))
+    } else if matches.is_present("context") || matches.is_present("context-lines") {
+        OutputFormat::Context(lines("context-lines"))
+    } else {
+        OutputFormat::Normal
+    }
+}
+
+/// The label used in the headers of the context formats (the file name and its modification
+/// time).
+fn default_label(name: &OsStr, current_dir: &Option<PathBuf>) -> String {
+    let mtime = if name == "-" {
+        None
+    } else {
+        fs::metadata(util::actual_path(current_dir, name))
+            .and_then(|meta| meta.modified())
+            .ok()
+    };
+    let duration = mtime
+        .unwrap_or_else(SystemTime::now)
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let time = Local.timestamp(duration.as_secs() as i64, duration.subsec_nanos());
+
+    format!(
+        "{}\t{}",
+        name.to_string_lossy(),
+        time.format("%Y-%m-%d %H:%M:%S%.9f %z")
+    )
+}
+
+/// Compare the two inputs and write the differences, returning whether there were any.
+fn diff<W: Write>(
+    output: &mut W,
+    options: &Options,
+    names: (&OsStr, &OsStr),
+    labels: (&str, &str),
+    data1: &[u8],
+    data2: &[u8],
+) -> Result<bool> {
+    let brief = |output: &mut W, kind: &str| {
+        writeln!(
+            output,
+            "{} {} and {} differ",
+            kind,
+            names.0.to_string_lossy(),
+            names.1.to_string_lossy()
+        )
+    };
+
+    if options.format == OutputFormat::Brief && data1 == data2 {
+        return Ok(false);
+    }
+    // like GNU diff, don't try to show the lines of files that look binary
+    if data1.contains(&0) || data2.contains(&0) {
+        if data1 == data2 {
+            return Ok(false);
+        }
+        brief(output, "Binary files")?;
+        return Ok(true);
+    }
+
+    let lines1 = split_lines(data1);
+    let lines2 = split_lines(data2);
+
+    // lines that compare equal (after normalization) are given the same id, so the algorithm
+    // only has to compare numbers
+    let mut ids = HashMap::new();
+    let ids1 = line_ids(&lines1, options, &mut ids);
+    let ids2 = line_ids(&lines2, options, &mut ids);
+
+    let changes = myers::diff(&ids1, &ids2);
+    if changes.is_empty() {
+        return Ok(false);
+    }
+
+    match options.format {
+        OutputFormat::Normal => format::write_normal(output, &lines1, &lines2, &changes)?,
+        OutputFormat::Unified(context) => {
+            format::write_unified(output, labels, &lines1, &lines2, &changes, context)?
+        }
+        OutputFormat::Context(context) => {
+            format::write_context(output, labels, &lines1, &lines2, &changes, context)?
+        }
+        OutputFormat::Brief => brief(output, "Files")?,
+    }
+    Ok(true)
+}
+
+/// Split `data` into lines, keeping the newline at the end of each line.
+fn split_lines(data: &[u8]) -> Vec<&[u8]> {
+    let mut lines = vec![];
+    let mut start = 0;
+    for (i, &byte) in data.iter().enumerate() {
+        if byte == b'\n' {
+            lines.push(&data[start..i + 1]);
+            start = i + 1;
+        }
+    }
+    if start < data.len() {
+        lines.push(&data[start..]);
+    }
+    lines
+}
+
+fn line_ids(lines: &Lines, options: &Options, ids: &mut HashMap<Vec<u8>, usize>) -> Vec<usize> {
+    lines
+        .iter()
+        .map(|line| {
+            let key = normalize(line, options);
+            let next_id = ids.len();
+            *ids.entry(key).or_insert(next_id)
+        })
+        .collect()
+}
+
+/// Transform a line so that lines which should be considered identical become equal.
+fn normalize(line: &[u8], options: &Options) -> Vec<u8> {
+    // a missing newline at the end of the file still counts as a difference
+    let (content, newline) = if line.ends_with(b"\n") {
+        (&line[..line.len() - 1], true)
+    } else {
+        (line, false)
+    };
+
+    let folded;
+    let content = if options.ignore_case {
+        folded = casefold::fold_bytes(content);
+        &folded[..]
+    } else {
+        content
+    };
+
+    let mut key = Vec::with_capacity(line.len());
+    let mut in_space = false;
+    for &byte in content {
+        let is_space = byte.is_ascii_whitespace();
+        match options.whitespace {
+            Whitespace::IgnoreAll if is_space => {}
+            Whitespace::IgnoreChange if is_space => in_space = true,
+            _ => {
+                if in_space {
+                    key.push(b' ');
+                    in_space = false;
+                }
+                key.push(byte);
+            }
+        }
+    }
+    // with -b, trailing whitespace is dropped (as in_space is never flushed)
+
+    if newline {
+        key.push(b'\n');
+    }
+    key
+}
+
+#[cfg(test)]
+fn options(ignore_case: bool, whitespace: Whitespace) -> Options {
+    Options {
+        format: OutputFormat::Normal,
+        ignore_case: ignore_case,
+        whitespace: whitespace,
+    }
+}
+
+#[test]
+fn lines() {
+    let lines = split_lines(b"a\nb\n\nc");
+    assert_eq!(lines, vec![&b"a\n"[..], &b"b\n"[..], &b"\n"[..], &b"c"[..]]);
+    assert!(split_lines(b"").is_empty());
+}
+
+#[test]
+fn normalization() {
+    let exact = options(false, Whitespace::Exact);
+    assert_eq!(normalize(b"A  b\n", &exact), b"A  b\n");
+    assert_ne!(normalize(b"a\n", &exact), normalize(b"a", &exact));
+
+    let case = options(true, Whitespace::Exact);
+    assert_eq!(normalize(b"AbC\n", &case), b"abc\n");
+    assert_eq!(normalize("ÉTÉ\n".as_bytes(), &case), "été\n".as_bytes());
+
+    let change = options(false, Whitespace::IgnoreChange);
+    assert_eq!(normalize(b"a \t b  \n", &change), b"a b\n");
+    assert_ne!(normalize(b"ab\n", &change), normalize(b"a b\n", &change));
+
+    let all = options(false, Whitespace::IgnoreAll);
+    assert_eq!(normalize(b" a b\t\n", &all), b"ab\n");
+}
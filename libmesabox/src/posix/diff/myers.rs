@@ -0,0 +1,186 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+//! An implementation of the O((N+M)D) difference algorithm described in Eugene W. Myers' "An
+//! O(ND) Difference Algorithm and Its Variations".
+
+/// A run of differing lines: `del` lines of the first sequence starting at `a` were replaced by
+/// `ins` lines of the second sequence starting at `b`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Change {
+    pub a: usize,
+    pub del: usize,
+    pub b: usize,
+    pub ins: usize,
+}
+
+/// Find a minimal set of changes that turns `a` into `b`.
+pub fn diff<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Change> {
+    // lines shared at the start and end don't need to go through the (much slower) search
+    let prefix = a.iter().zip(b.iter()).take_while(|&(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|&(x, y)| x == y)
+        .count();
+
+    let mid_a = &a[prefix..a.len() - suffix];
+    let mid_b = &b[prefix..b.len() - suffix];
+
+    let mut deleted = vec![false; a.len()];
+    let mut inserted = vec![false; b.len()];
+    shortest_edit(
+        mid_a,
+        mid_b,
+        &mut deleted[prefix..a.len() - suffix],
+        &mut inserted[prefix..b.len() - suffix],
+    );
+
+    collect_changes(&deleted, &inserted)
+}
+
+/// Mark the lines of `a` that are deleted and the lines of `b` that are inserted in a shortest
+/// edit script.
+fn shortest_edit<T: PartialEq>(a: &[T], b: &[T], deleted: &mut [bool], inserted: &mut [bool]) {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let max = n + m;
+    if max == 0 {
+        return;
+    }
+
+    // v[k + offset] is the furthest x reached on diagonal k (where k = x - y)
+    let offset = max;
+    let mut v = vec![0isize; 2 * max as usize + 1];
+    let mut trace = vec![];
+
+    'search: for d in 0..max + 1 {
+        trace.push(v.clone());
+
+        let mut k = -d;
+        while k <= d {
+            let idx = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+
+            if x >= n && y >= m {
+                break 'search;
+            }
+            k += 2;
+        }
+    }
+
+    // walk back through the saved states to recover the path that was taken
+    let mut x = n;
+    let mut y = m;
+    for d in (1..trace.len() as isize).rev() {
+        let v = &trace[d as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && v[(k - 1 + offset) as usize] < v[(k + 1 + offset) as usize]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[(prev_k + offset) as usize];
+        let prev_y = prev_x - prev_k;
+
+        // skip over the snake of matching lines
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+        }
+
+        if x == prev_x {
+            inserted[prev_y as usize] = true;
+        } else {
+            deleted[prev_x as usize] = true;
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+}
+
+/// Group the marked lines into runs of changes.
+fn collect_changes(deleted: &[bool], inserted: &[bool]) -> Vec<Change> {
+    let mut changes = vec![];
+    let (mut i, mut j) = (0, 0);
+
+    while i < deleted.len() || j < inserted.len() {
+        let is_deleted = i < deleted.len() && deleted[i];
+        let is_inserted = j < inserted.len() && inserted[j];
+        if !is_deleted && !is_inserted {
+            i += 1;
+            j += 1;
+            continue;
+        }
+
+        let (start_a, start_b) = (i, j);
+        while i < deleted.len() && deleted[i] {
+            i += 1;
+        }
+        while j < inserted.len() && inserted[j] {
+            j += 1;
+        }
+        changes.push(Change {
+            a: start_a,
+            del: i - start_a,
+            b: start_b,
+            ins: j - start_b,
+        });
+    }
+
+    changes
+}
+
+#[cfg(test)]
+fn diff_str(a: &str, b: &str) -> Vec<(usize, usize, usize, usize)> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    diff(&a, &b)
+        .into_iter()
+        .map(|c| (c.a, c.del, c.b, c.ins))
+        .collect()
+}
+
+#[test]
+fn identical() {
+    assert_eq!(diff_str("", ""), vec![]);
+    assert_eq!(diff_str("abc", "abc"), vec![]);
+}
+
+#[test]
+fn insertions_and_deletions() {
+    assert_eq!(diff_str("", "ab"), vec![(0, 0, 0, 2)]);
+    assert_eq!(diff_str("ab", ""), vec![(0, 2, 0, 0)]);
+    assert_eq!(diff_str("ac", "abc"), vec![(1, 0, 1, 1)]);
+    assert_eq!(diff_str("abc", "ac"), vec![(1, 1, 1, 0)]);
+}
+
+#[test]
+fn replacements() {
+    assert_eq!(diff_str("abc", "axc"), vec![(1, 1, 1, 1)]);
+    assert_eq!(diff_str("abcdef", "axcdyf"), vec![(1, 1, 1, 1), (4, 1, 4, 1)]);
+}
+
+#[test]
+fn minimal() {
+    // the classic example from the paper has an edit distance of 5
+    let changes = diff(b"abcabba", b"cbabac");
+    let edits: usize = changes.iter().map(|c| c.del + c.ins).sum();
+    assert_eq!(edits, 5);
+}
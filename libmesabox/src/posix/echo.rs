@@ -6,6 +6,7 @@
 // For a copy, see the LICENSE file.
 //
 
+use util;
 use {ArgsIter, MesaError, Result, UtilSetup, UtilWrite};
 
 use std::ffi::OsStr;
@@ -13,8 +14,6 @@ use std::io::Write;
 use std::iter;
 use std::str;
 
-use util::OsStrExt;
-
 #[allow(unused)]
 pub(crate) const NAME: &str = "echo";
 pub(crate) const DESCRIPTION: &str = "Write string(s) to stdout with a trailing newline";
@@ -58,7 +57,8 @@ where
 fn write_str<W: Write>(output: &mut W, s: &OsStr) -> Result<bool> {
     let mut found_c = false;
 
-    for res in map_bytes(s.try_as_bytes()?) {
+    let bytes = util::os_str_as_bytes(s);
+    for res in map_bytes(&bytes) {
         match res {
             ByteResult::Stop => {
                 // found \c
@@ -0,0 +1,444 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use util;
+use util::glob::{GlobError, MatchFlags, Pattern};
+use {PathError, PathOperation};
+
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::PathBuf;
+use std::result::Result as StdResult;
+use std::time::SystemTime;
+
+#[derive(Debug, Fail)]
+pub enum ParseError {
+    #[fail(display = "unknown predicate '{}'", _0)]
+    UnknownPredicate(String),
+
+    #[fail(display = "missing argument to '{}'", _0)]
+    MissingArgument(String),
+
+    #[fail(display = "invalid argument '{}' to '{}'", _1, _0)]
+    InvalidArgument(String, String),
+
+    #[fail(display = "invalid pattern '{}': {}", _0, _1)]
+    InvalidPattern(String, #[cause] GlobError),
+
+    #[fail(display = "invalid expression")]
+    InvalidExpression,
+
+    #[fail(display = "unexpected ')'")]
+    UnexpectedParen,
+
+    #[fail(display = "{}", _0)]
+    Path(#[cause] PathError),
+}
+
+type ParseResult<T> = StdResult<T, ParseError>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FileKind {
+    File,
+    Dir,
+    Symlink,
+    Block,
+    Char,
+    Fifo,
+    Socket,
+}
+
+impl FileKind {
+    fn from_char(ch: char) -> Option<Self> {
+        Some(match ch {
+            'f' => FileKind::File,
+            'd' => FileKind::Dir,
+            'l' => FileKind::Symlink,
+            'b' => FileKind::Block,
+            'c' => FileKind::Char,
+            'p' => FileKind::Fifo,
+            's' => FileKind::Socket,
+            _ => return None,
+        })
+    }
+}
+
+/// A numeric test like `-size +10` (greater than), `-size -10` (less than), or `-size 10`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NumTest {
+    Less(u64),
+    Equal(u64),
+    Greater(u64),
+}
+
+impl NumTest {
+    pub fn matches(self, value: u64) -> bool {
+        match self {
+            NumTest::Less(n) => value < n,
+            NumTest::Equal(n) => value == n,
+            NumTest::Greater(n) => value > n,
+        }
+    }
+
+    /// Parse the number (and its prefix) at the start of `arg`, returning the rest.
+    fn parse(arg: &str) -> Option<(Self, &str)> {
+        let (ctor, rest): (fn(u64) -> NumTest, &str) = if arg.starts_with('+') {
+            (NumTest::Greater, &arg[1..])
+        } else if arg.starts_with('-') {
+            (NumTest::Less, &arg[1..])
+        } else {
+            (NumTest::Equal, arg)
+        };
+        let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let value = rest[..end].parse().ok()?;
+        Some((ctor(value), &rest[end..]))
+    }
+}
+
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    True,
+    Name(Pattern),
+    Path(Pattern),
+    Type(Vec<FileKind>),
+    /// The size in units of the given number of bytes (rounded up)
+    Size(NumTest, u64),
+    /// The age in days since the last modification
+    Mtime(NumTest),
+    Newer(SystemTime),
+    Prune,
+    Print,
+    Print0,
+    /// Run a command, either once per file or (if `batch` is true) with as many files as possible
+    /// at once.  `id` identifies the pending files for batched commands.
+    Exec {
+        command: Vec<OsString>,
+        batch: bool,
+        id: usize,
+    },
+}
+
+pub struct Program {
+    pub expr: Expr,
+    pub min_depth: usize,
+    pub max_depth: Option<usize>,
+    /// The number of batched `-exec ... +` commands
+    pub batches: usize,
+}
+
+pub fn parse(args: &[OsString], current_dir: &Option<PathBuf>) -> ParseResult<Program> {
+    let mut parser = Parser {
+        args: args,
+        pos: 0,
+        current_dir: current_dir,
+        min_depth: 0,
+        max_depth: None,
+        has_action: false,
+        batches: 0,
+    };
+
+    let expr = if args.is_empty() {
+        Expr::True
+    } else {
+        let expr = parser.parse_or()?;
+        if parser.pos < args.len() {
+            return Err(ParseError::UnexpectedParen);
+        }
+        expr
+    };
+
+    // without any actions, every file for which the expression is true is printed
+    let expr = if parser.has_action {
+        expr
+    } else {
+        Expr::And(Box::new(expr), Box::new(Expr::Print))
+    };
+
+    Ok(Program {
+        expr: expr,
+        min_depth: parser.min_depth,
+        max_depth: parser.max_depth,
+        batches: parser.batches,
+    })
+}
+
+struct Parser<'a> {
+    args: &'a [OsString],
+    pos: usize,
+    current_dir: &'a Option<PathBuf>,
+    min_depth: usize,
+    max_depth: Option<usize>,
+    has_action: bool,
+    batches: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a OsStr> {
+        self.args.get(self.pos).map(|arg| arg.as_os_str())
+    }
+
+    fn next_arg(&mut self, name: &str) -> ParseResult<&'a OsStr> {
+        match self.peek() {
+            Some(arg) => {
+                self.pos += 1;
+                Ok(arg)
+            }
+            None => Err(ParseError::MissingArgument(name.to_owned())),
+        }
+    }
+
+    fn next_str(&mut self, name: &str) -> ParseResult<String> {
+        Ok(self.next_arg(name)?.to_string_lossy().into_owned())
+    }
+
+    fn parse_or(&mut self) -> ParseResult<Expr> {
+        let mut lhs = self.parse_and()?;
+        while self.peek().map(|arg| arg == "-o" || arg == "-or").unwrap_or(false) {
+            self.pos += 1;
+            let rhs = self.parse_and()?;
+            lhs = Expr::Or(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self) -> ParseResult<Expr> {
+        let mut lhs = self.parse_unary()?;
+        loop {
+            match self.peek() {
+                Some(arg) if arg == "-a" || arg == "-and" => self.pos += 1,
+                // two expressions next to each other are implicitly joined by -a
+                Some(arg) if arg != "-o" && arg != "-or" && arg != ")" => {}
+                _ => return Ok(lhs),
+            }
+            let rhs = self.parse_unary()?;
+            lhs = Expr::And(Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn parse_unary(&mut self) -> ParseResult<Expr> {
+        let arg = match self.peek() {
+            Some(arg) => arg,
+            None => return Err(ParseError::InvalidExpression),
+        };
+
+        if arg == "!" || arg == "-not" {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        if arg == "(" {
+            self.pos += 1;
+            let expr = self.parse_or()?;
+            if self.peek().map(|arg| arg != ")").unwrap_or(true) {
+                return Err(ParseError::InvalidExpression);
+            }
+            self.pos += 1;
+            return Ok(expr);
+        }
+        if arg == ")" {
+            return Err(ParseError::UnexpectedParen);
+        }
+
+        self.pos += 1;
+        self.parse_primary(&arg.to_string_lossy())
+    }
+
+    fn parse_primary(&mut self, name: &str) -> ParseResult<Expr> {
+        Ok(match name {
+            "-name" | "-iname" => {
+                let pattern = self.next_str(name)?;
+                Expr::Name(glob(&pattern, name == "-iname")?)
+            }
+            "-path" | "-ipath" | "-wholename" | "-iwholename" => {
+                let pattern = self.next_str(name)?;
+                Expr::Path(glob(&pattern, name.starts_with("-i"))?)
+            }
+            "-type" => {
+                let arg = self.next_str(name)?;
+                let kinds: Option<Vec<_>> = arg
+                    .split(',')
+                    .map(|kind| {
+                        let mut chars = kind.chars();
+                        match (chars.next(), chars.next()) {
+                            (Some(ch), None) => FileKind::from_char(ch),
+                            _ => None,
+                        }
+                    })
+                    .collect();
+                Expr::Type(kinds.ok_or_else(|| invalid_argument(name, &arg))?)
+            }
+            "-size" => {
+                let arg = self.next_str(name)?;
+                let (test, suffix) = NumTest::parse(&arg).ok_or_else(|| invalid_argument(name, &arg))?;
+                let unit = match suffix {
+                    "c" => 1,
+                    "w" => 2,
+                    "" | "b" => 512,
+                    "k" => 1024,
+                    "M" => 1024 * 1024,
+                    "G" => 1024 * 1024 * 1024,
+                    _ => return Err(invalid_argument(name, &arg)),
+                };
+                Expr::Size(test, unit)
+            }
+            "-mtime" => {
+                let arg = self.next_str(name)?;
+                match NumTest::parse(&arg) {
+                    Some((test, "")) => Expr::Mtime(test),
+                    _ => return Err(invalid_argument(name, &arg)),
+                }
+            }
+            "-newer" => {
+                let file = self.next_arg(name)?;
+                let path = util::actual_path(self.current_dir, file);
+                let mtime = fs::metadata(&path)
+                    .and_then(|meta| meta.modified())
+                    .map_err(|e| ParseError::Path(PathError::new(PathOperation::Stat, file, e)))?;
+                Expr::Newer(mtime)
+            }
+            "-maxdepth" | "-mindepth" => {
+                let arg = self.next_str(name)?;
+                let depth = arg.parse().map_err(|_| invalid_argument(name, &arg))?;
+                if name == "-maxdepth" {
+                    self.max_depth = Some(depth);
+                } else {
+                    self.min_depth = depth;
+                }
+                Expr::True
+            }
+            "-true" => Expr::True,
+            "-false" => Expr::Not(Box::new(Expr::True)),
+            "-prune" => Expr::Prune,
+            "-print" => {
+                self.has_action = true;
+                Expr::Print
+            }
+            "-print0" => {
+                self.has_action = true;
+                Expr::Print0
+            }
+            "-exec" => {
+                self.has_action = true;
+                self.parse_exec(name)?
+            }
+            _ => return Err(ParseError::UnknownPredicate(name.to_owned())),
+        })
+    }
+
+    fn parse_exec(&mut self, name: &str) -> ParseResult<Expr> {
+        let mut command = vec![];
+        loop {
+            let arg = self.next_arg(name)?;
+            if arg == ";" {
+                break;
+            }
+            // a + only ends the command when it directly follows {}
+            if arg == "+" && command.last().map(|last: &OsString| last == "{}").unwrap_or(false) {
+                command.pop();
+                if command.is_empty() {
+                    return Err(ParseError::MissingArgument(name.to_owned()));
+                }
+                let id = self.batches;
+                self.batches += 1;
+                return Ok(Expr::Exec {
+                    command: command,
+                    batch: true,
+                    id: id,
+                });
+            }
+            command.push(arg.to_owned());
+        }
+
+        if command.is_empty() {
+            return Err(ParseError::MissingArgument(name.to_owned()));
+        }
+        Ok(Expr::Exec {
+            command: command,
+            batch: false,
+            id: 0,
+        })
+    }
+}
+
+fn invalid_argument(name: &str, arg: &str) -> ParseError {
+    ParseError::InvalidArgument(name.to_owned(), arg.to_owned())
+}
+
+// like GNU find, wildcards match both slashes and leading periods
+fn glob(pattern: &str, case_insensitive: bool) -> ParseResult<Pattern> {
+    let flags = MatchFlags {
+        casefold: case_insensitive,
+        ..MatchFlags::default()
+    };
+    Pattern::new(pattern, flags).map_err(|e| ParseError::InvalidPattern(pattern.to_owned(), e))
+}
+
+#[cfg(test)]
+fn parse_str(args: &str) -> ParseResult<Program> {
+    let args: Vec<OsString> = args.split_whitespace().map(OsString::from).collect();
+    parse(&args, &None)
+}
+
+#[test]
+fn precedence() {
+    // -a binds more tightly than -o and the implicit -print applies to everything
+    let program = parse_str("-name a -o -name b -type f").unwrap();
+    match program.expr {
+        Expr::And(lhs, rhs) => {
+            assert!(match *rhs {
+                Expr::Print => true,
+                _ => false,
+            });
+            match *lhs {
+                Expr::Or(_, rhs) => assert!(match *rhs {
+                    Expr::And(..) => true,
+                    _ => false,
+                }),
+                _ => panic!("expected -o"),
+            }
+        }
+        _ => panic!("expected implicit -print"),
+    }
+
+    let program = parse_str("( -name a -o -name b ) -print").unwrap();
+    assert!(match program.expr {
+        Expr::And(ref lhs, _) => match **lhs {
+            Expr::Or(..) => true,
+            _ => false,
+        },
+        _ => false,
+    });
+}
+
+#[test]
+fn options_and_actions() {
+    let program = parse_str("-maxdepth 2 -mindepth 1 -exec echo {} + -exec ls {} ;").unwrap();
+    assert_eq!(program.max_depth, Some(2));
+    assert_eq!(program.min_depth, 1);
+    assert_eq!(program.batches, 1);
+}
+
+#[test]
+fn numeric_tests() {
+    assert_eq!(NumTest::parse("+5k"), Some((NumTest::Greater(5), "k")));
+    assert_eq!(NumTest::parse("-3"), Some((NumTest::Less(3), "")));
+    assert_eq!(NumTest::parse("7"), Some((NumTest::Equal(7), "")));
+    assert_eq!(NumTest::parse("x"), None);
+}
+
+#[test]
+fn parse_errors() {
+    assert!(parse_str("-bogus").is_err());
+    assert!(parse_str("-name").is_err());
+    assert!(parse_str("-type q").is_err());
+    assert!(parse_str("-size 3x").is_err());
+    assert!(parse_str("( -name a").is_err());
+    assert!(parse_str("-name a )").is_err());
+    assert!(parse_str("-exec echo {}").is_err());
+}
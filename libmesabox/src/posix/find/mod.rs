@@ -0,0 +1,491 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use util::{self, ExitCode, RawObject, RawObjectWrapper};
+use {ArgsIter, MesaError, PathError, PathOperation, Result, UtilSetup, UtilWrite};
+
+use clap::Arg;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, Metadata};
+use std::io::{self, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::io::FromRawFd;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::SystemTime;
+use walkdir::{self, WalkDir};
+
+use self::expr::{Expr, FileKind, Program};
+
+mod expr;
+
+pub(crate) const NAME: &str = "find";
+pub(crate) const DESCRIPTION: &str = "Search for files in a directory hierarchy";
+
+// like xargs, don't build command lines longer than this for -exec ... +
+const MAX_BATCH_SIZE: usize = 128 * 1024;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// How symbolic links are treated while traversing.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Follow {
+    /// Never follow symlinks (-P)
+    Never,
+    /// Only follow symlinks given on the command-line (-H)
+    CommandLine,
+    /// Always follow symlinks (-L)
+    Always,
+}
+
+#[derive(Default)]
+struct Batch {
+    command: Vec<OsString>,
+    paths: Vec<OsString>,
+    size: usize,
+}
+
+pub fn execute<S, T>(setup: &mut S, args: T) -> Result<()>
+where
+    S: UtilSetup,
+    T: ArgsIter,
+{
+    let args: Vec<OsString> = args.map(|arg| arg.into()).collect();
+
+    // the options have to come before the paths and the expression (which look like options but
+    // cannot be handled by clap), so only the options are given to clap
+    let option_count = count_options(&args[1..]);
+    let matches = {
+        let app = util_app!(NAME)
+                    .arg(Arg::with_name("H")
+                            .short("H")
+                            .multiple(true)
+                            .help("Follow symbolic links given on the command-line"))
+                    .arg(Arg::with_name("L")
+                            .short("L")
+                            .multiple(true)
+                            .help("Follow all symbolic links"))
+                    .arg(Arg::with_name("P")
+                            .short("P")
+                            .multiple(true)
+                            .help("Never follow symbolic links (the default)"));
+
+        app.get_matches_from_safe(&args[..option_count + 1])?
+    };
+
+    // the last of -H, -L, and -P wins
+    let follow = [("H", Follow::CommandLine), ("L", Follow::Always), ("P", Follow::Never)]
+        .iter()
+        .filter_map(|&(name, follow)| {
+            matches
+                .indices_of(name)
+                .and_then(|indices| indices.max())
+                .map(|index| (index, follow))
+        })
+        .max_by_key(|&(index, _)| index)
+        .map(|(_, follow)| follow)
+        .unwrap_or(Follow::Never);
+
+    let rest = &args[option_count + 1..];
+    let path_count = rest.iter().take_while(|arg| !starts_expression(arg)).count();
+    let (paths, expression) = rest.split_at(path_count);
+
+    let current_dir = setup.current_dir().map(|p| p.to_owned());
+    let program = expr::parse(expression, &current_dir)?;
+
+    // NOTE: we need to duplicate the fds as from_raw_fd() takes ownership
+    let stdout_fd = setup.output().raw_object();
+    let stderr_fd = setup.error().raw_object();
+    let env: Vec<(OsString, OsString)> = setup.env().collect();
+
+    let (_, output, stderr) = setup.stdio();
+    let mut finder = Finder {
+        output: output.lock()?,
+        stderr: stderr.lock()?,
+        program: &program,
+        follow: follow,
+        current_dir: current_dir,
+        env: env,
+        stdout_fd: stdout_fd,
+        stderr_fd: stderr_fd,
+        now: SystemTime::now(),
+        batches: (0..program.batches).map(|_| Batch::default()).collect(),
+        exitcode: 0,
+    };
+
+    if paths.is_empty() {
+        finder.find(OsStr::new("."))?;
+    } else {
+        for path in paths {
+            finder.find(path)?;
+        }
+    }
+    finder.finish()?;
+
+    if finder.exitcode == 0 {
+        Ok(())
+    } else {
+        Err(MesaError::new(None, finder.exitcode, None))
+    }
+}
+
+/// Count the leading arguments that are options (-H, -L, -P, and those clap handles itself).
+fn count_options(args: &[OsString]) -> usize {
+    let mut count = 0;
+    for arg in args {
+        let bytes = util::os_str_as_bytes(arg);
+        if &bytes[..] == b"--" {
+            return count + 1;
+        }
+        let is_option = &bytes[..] == b"--help"
+            || &bytes[..] == b"--version"
+            || (bytes.len() > 1
+                && bytes[0] == b'-'
+                && bytes[1..].iter().all(|&b| b == b'H' || b == b'L' || b == b'P'));
+        if !is_option {
+            break;
+        }
+        count += 1;
+    }
+    count
+}
+
+fn starts_expression(arg: &OsStr) -> bool {
+    let bytes = util::os_str_as_bytes(arg);
+    (bytes.len() > 1 && bytes[0] == b'-') || &bytes[..] == b"(" || &bytes[..] == b"!"
+}
+
+/// Determine the path to display for `path` (found by walking `root`), which was given on the
+/// command-line as `operand`.
+fn display_path(operand: &OsStr, root: &Path, path: &Path) -> PathBuf {
+    match path.strip_prefix(root) {
+        Ok(relative) if relative.as_os_str().is_empty() => PathBuf::from(operand),
+        Ok(relative) => Path::new(operand).join(relative),
+        Err(_) => path.to_owned(),
+    }
+}
+
+fn file_kind(meta: &Metadata) -> Option<FileKind> {
+    let file_type = meta.file_type();
+    Some(if file_type.is_file() {
+        FileKind::File
+    } else if file_type.is_dir() {
+        FileKind::Dir
+    } else if file_type.is_symlink() {
+        FileKind::Symlink
+    } else if file_type.is_block_device() {
+        FileKind::Block
+    } else if file_type.is_char_device() {
+        FileKind::Char
+    } else if file_type.is_fifo() {
+        FileKind::Fifo
+    } else if file_type.is_socket() {
+        FileKind::Socket
+    } else {
+        return None;
+    })
+}
+
+/// Replace every `{}` in `arg` with `path`.
+fn replace_braces(arg: &OsStr, path: &OsStr) -> OsString {
+    let arg = util::os_str_as_bytes(arg);
+    let path = util::os_str_as_bytes(path);
+
+    let mut result = Vec::with_capacity(arg.len());
+    let mut rest = &arg[..];
+    while !rest.is_empty() {
+        if rest.starts_with(b"{}") {
+            result.extend_from_slice(&path);
+            rest = &rest[2..];
+        } else {
+            result.push(rest[0]);
+            rest = &rest[1..];
+        }
+    }
+    util::os_str_from_bytes(&result).into_owned()
+}
+
+/// The file currently being evaluated.
+struct Entry<'b> {
+    /// The path as displayed to the user
+    path: &'b Path,
+    meta: &'b Metadata,
+}
+
+struct Finder<'a, O, E>
+where
+    O: Write,
+    E: Write,
+{
+    output: O,
+    stderr: E,
+    program: &'a Program,
+    follow: Follow,
+    current_dir: Option<PathBuf>,
+    env: Vec<(OsString, OsString)>,
+    stdout_fd: Option<RawObject>,
+    stderr_fd: Option<RawObject>,
+    now: SystemTime,
+    batches: Vec<Batch>,
+    exitcode: ExitCode,
+}
+
+impl<'a, O, E> Finder<'a, O, E>
+where
+    O: Write,
+    E: Write,
+{
+    fn find(&mut self, operand: &OsStr) -> Result<()> {
+        let program = self.program;
+        let root = util::actual_path(&self.current_dir, operand).into_owned();
+
+        let meta = match self.follow {
+            Follow::Never => fs::symlink_metadata(&root),
+            // a dangling symlink is still found, just not followed
+            _ => fs::metadata(&root).or_else(|_| fs::symlink_metadata(&root)),
+        };
+        let meta = match meta {
+            Ok(meta) => meta,
+            Err(e) => return self.report(&PathError::new(PathOperation::Access, operand, e)),
+        };
+
+        // walkdir only descends into a symlinked root if it follows every symlink, so for -H
+        // walk the directory the symlink points to instead
+        let walk_root = if self.follow == Follow::CommandLine && meta.is_dir() {
+            root.join(".")
+        } else {
+            root
+        };
+
+        let mut walker = WalkDir::new(&walk_root)
+            .follow_links(self.follow == Follow::Always)
+            .sort_by(|a, b| a.file_name().cmp(b.file_name()));
+        if let Some(max_depth) = program.max_depth {
+            walker = walker.max_depth(max_depth);
+        }
+
+        let mut iter = walker.into_iter();
+        while let Some(entry) = iter.next() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    self.report_walk(operand, &walk_root, e)?;
+                    continue;
+                }
+            };
+
+            if entry.depth() < program.min_depth {
+                continue;
+            }
+
+            let path = display_path(operand, &walk_root, entry.path());
+            let entry_meta = if entry.depth() == 0 {
+                meta.clone()
+            } else {
+                match entry.metadata() {
+                    Ok(meta) => meta,
+                    Err(e) => {
+                        self.report_walk(operand, &walk_root, e)?;
+                        continue;
+                    }
+                }
+            };
+
+            let mut prune = false;
+            {
+                let current = Entry {
+                    path: &path,
+                    meta: &entry_meta,
+                };
+                self.eval(&program.expr, &current, &mut prune)?;
+            }
+            if prune && entry.file_type().is_dir() {
+                iter.skip_current_dir();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Evaluate `expr` for `entry`, setting `prune` if the entry should not be descended into.
+    fn eval(&mut self, expr: &Expr, entry: &Entry, prune: &mut bool) -> Result<bool> {
+        Ok(match *expr {
+            Expr::And(ref lhs, ref rhs) => {
+                self.eval(lhs, entry, prune)? && self.eval(rhs, entry, prune)?
+            }
+            Expr::Or(ref lhs, ref rhs) => {
+                self.eval(lhs, entry, prune)? || self.eval(rhs, entry, prune)?
+            }
+            Expr::Not(ref expr) => !self.eval(expr, entry, prune)?,
+            Expr::True => true,
+            Expr::Name(ref glob) => {
+                // unlike file_name(), this finds the '..' in paths like 'dir/..'
+                let name = util::path::split_basename(entry.path.as_os_str());
+                glob.is_match(Path::new(&*name))
+            }
+            Expr::Path(ref glob) => glob.is_match(entry.path),
+            Expr::Type(ref kinds) => match file_kind(entry.meta) {
+                Some(kind) => kinds.contains(&kind),
+                None => false,
+            },
+            Expr::Size(test, unit) => {
+                let size = entry.meta.len();
+                test.matches((size + unit - 1) / unit)
+            }
+            Expr::Mtime(test) => {
+                let age = entry
+                    .meta
+                    .modified()
+                    .ok()
+                    .and_then(|mtime| self.now.duration_since(mtime).ok())
+                    .map(|age| age.as_secs())
+                    .unwrap_or(0);
+                test.matches(age / SECONDS_PER_DAY)
+            }
+            Expr::Newer(time) => match entry.meta.modified() {
+                Ok(mtime) => mtime > time,
+                Err(_) => false,
+            },
+            Expr::Prune => {
+                *prune = true;
+                true
+            }
+            Expr::Print => {
+                self.output.write_all(&util::os_str_as_bytes(entry.path.as_os_str()))?;
+                self.output.write_all(b"\n")?;
+                true
+            }
+            Expr::Print0 => {
+                self.output.write_all(&util::os_str_as_bytes(entry.path.as_os_str()))?;
+                self.output.write_all(b"\0")?;
+                true
+            }
+            Expr::Exec {
+                ref command,
+                batch: false,
+                ..
+            } => {
+                let args: Vec<_> = command
+                    .iter()
+                    .map(|arg| replace_braces(arg, entry.path.as_os_str()))
+                    .collect();
+                self.run(&args)?
+            }
+            Expr::Exec {
+                ref command,
+                batch: true,
+                id,
+            } => {
+                self.add_to_batch(id, command, entry.path.as_os_str())?;
+                true
+            }
+        })
+    }
+
+    fn add_to_batch(&mut self, id: usize, command: &[OsString], path: &OsStr) -> Result<()> {
+        let len = path.len() + 1;
+        if !self.batches[id].paths.is_empty() && self.batches[id].size + len > MAX_BATCH_SIZE {
+            self.run_batch(id)?;
+        }
+
+        let batch = &mut self.batches[id];
+        if batch.paths.is_empty() {
+            batch.command = command.to_vec();
+            batch.size = command.iter().map(|arg| arg.len() + 1).sum();
+        }
+        batch.paths.push(path.to_owned());
+        batch.size += len;
+        Ok(())
+    }
+
+    fn run_batch(&mut self, id: usize) -> Result<()> {
+        let mut args = self.batches[id].command.clone();
+        args.extend(self.batches[id].paths.drain(..));
+        if !self.run(&args)? {
+            self.exitcode = 1;
+        }
+        Ok(())
+    }
+
+    /// Run any batched commands that still have files waiting.
+    fn finish(&mut self) -> Result<()> {
+        for id in 0..self.batches.len() {
+            if !self.batches[id].paths.is_empty() {
+                self.run_batch(id)?;
+            }
+        }
+        self.output.flush()?;
+        Ok(())
+    }
+
+    /// Run a command, returning whether it succeeded.
+    fn run(&mut self, args: &[OsString]) -> Result<bool> {
+        // anything printed so far has to appear before the command's output
+        self.output.flush()?;
+
+        let mut command = Command::new(&args[0]);
+        command
+            .args(&args[1..])
+            .env_clear()
+            .envs(self.env.iter().map(|&(ref key, ref value)| (key, value)));
+        if let Some(ref dir) = self.current_dir {
+            command.current_dir(dir);
+        }
+        // without a file descriptor to hand to the command (as when run by run_util()), its
+        // output is collected and written to our own output instead
+        match self.stdout_fd {
+            Some(fd) => {
+                let fd = RawObjectWrapper::new(fd, false, true).dup()?;
+                command.stdout(unsafe { Stdio::from_raw_fd(fd.raw_value()) });
+            }
+            None => {
+                command.stdout(Stdio::piped());
+            }
+        }
+        match self.stderr_fd {
+            Some(fd) => {
+                let fd = RawObjectWrapper::new(fd, false, true).dup()?;
+                command.stderr(unsafe { Stdio::from_raw_fd(fd.raw_value()) });
+            }
+            None => {
+                command.stderr(Stdio::piped());
+            }
+        }
+
+        // output() would otherwise give the command an empty standard input
+        command.stdin(Stdio::inherit());
+        match command.output() {
+            Ok(output) => {
+                self.output.write_all(&output.stdout)?;
+                self.output.flush()?;
+                self.stderr.write_all(&output.stderr)?;
+                Ok(output.status.success())
+            }
+            Err(e) => {
+                display_msg!(self.stderr, "{}: {}", args[0].to_string_lossy(), e)?;
+                self.exitcode = 1;
+                Ok(false)
+            }
+        }
+    }
+
+    fn report_walk(&mut self, operand: &OsStr, root: &Path, err: walkdir::Error) -> Result<()> {
+        let path = match err.path() {
+            Some(path) => display_path(operand, root, path),
+            None => PathBuf::from(operand),
+        };
+        let err: io::Error = err.into();
+        self.report(&PathError::new(PathOperation::OpenDir, path, err))
+    }
+
+    fn report(&mut self, err: &PathError) -> Result<()> {
+        self.exitcode = 1;
+        display_msg!(self.stderr, "{}", err)?;
+        Ok(())
+    }
+}
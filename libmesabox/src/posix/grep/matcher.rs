@@ -0,0 +1,143 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use util::posix_regex::{translate, PatternError};
+
+use regex::bytes::{Regex, RegexBuilder};
+use regex;
+use std::result::Result as StdResult;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Syntax {
+    Basic,
+    Extended,
+    Fixed,
+}
+
+pub struct MatcherOptions {
+    pub syntax: Syntax,
+    pub ignore_case: bool,
+    pub word: bool,
+    pub line: bool,
+}
+
+/// Finds matches for a set of patterns in a single line (without its delimiter).
+pub enum Matcher {
+    /// Fast path for fixed strings when no other options that affect matching are given
+    Fixed(Vec<Vec<u8>>),
+    Regex(Regex),
+}
+
+impl Matcher {
+    pub fn new(patterns: &[Vec<u8>], options: &MatcherOptions) -> StdResult<Self, PatternError> {
+        let simple = !(options.ignore_case || options.word || options.line);
+        // an empty set of patterns (e.g. from an empty -f file) never matches anything
+        if patterns.is_empty() || (options.syntax == Syntax::Fixed && simple) {
+            return Ok(Matcher::Fixed(patterns.to_vec()));
+        }
+
+        let mut combined = String::new();
+        for (i, pattern) in patterns.iter().enumerate() {
+            if i > 0 {
+                combined.push('|');
+            }
+            // patterns that aren't valid UTF-8 are matched as best we can
+            let pattern = String::from_utf8_lossy(pattern);
+            let translated = match options.syntax {
+                Syntax::Basic => translate(&pattern, false)?,
+                Syntax::Extended => translate(&pattern, true)?,
+                Syntax::Fixed => regex::escape(&pattern),
+            };
+            combined.push_str("(?:");
+            combined.push_str(&translated);
+            combined.push(')');
+        }
+
+        let combined = if options.line {
+            format!("^(?:{})$", combined)
+        } else if options.word {
+            format!(r"\b(?:{})\b", combined)
+        } else {
+            combined
+        };
+
+        let regex = RegexBuilder::new(&combined)
+            .case_insensitive(options.ignore_case)
+            .build()?;
+        Ok(Matcher::Regex(regex))
+    }
+
+    pub fn is_match(&self, line: &[u8]) -> bool {
+        match *self {
+            Matcher::Fixed(ref patterns) => patterns.iter().any(|p| find_fixed(line, p).is_some()),
+            Matcher::Regex(ref regex) => regex.is_match(line),
+        }
+    }
+
+    /// Find the first match at or after `start`, returning its bounds.
+    pub fn find_at(&self, line: &[u8], start: usize) -> Option<(usize, usize)> {
+        match *self {
+            Matcher::Fixed(ref patterns) => {
+                // find the leftmost (and then longest) match among the patterns
+                let mut best: Option<(usize, usize)> = None;
+                for pattern in patterns {
+                    if let Some(pos) = find_fixed(&line[start..], pattern) {
+                        let found = (start + pos, start + pos + pattern.len());
+                        let better = match best {
+                            Some(prev) => found.0 < prev.0 || (found.0 == prev.0 && found.1 > prev.1),
+                            None => true,
+                        };
+                        if better {
+                            best = Some(found);
+                        }
+                    }
+                }
+                best
+            }
+            Matcher::Regex(ref regex) => regex
+                .find_at(line, start)
+                .map(|m| (m.start(), m.end())),
+        }
+    }
+}
+
+fn find_fixed(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    let (&first, rest) = match needle.split_first() {
+        Some(split) => split,
+        None => return Some(0),
+    };
+    if haystack.len() < needle.len() {
+        return None;
+    }
+
+    // look for the first byte and only then compare the rest of the needle
+    let last_start = haystack.len() - needle.len();
+    let mut pos = 0;
+    while pos <= last_start {
+        match haystack[pos..last_start + 1].iter().position(|&b| b == first) {
+            Some(offset) => {
+                pos += offset;
+                if &haystack[pos + 1..pos + needle.len()] == rest {
+                    return Some(pos);
+                }
+                pos += 1;
+            }
+            None => break,
+        }
+    }
+    None
+}
+
+#[test]
+fn fixed_search() {
+    assert_eq!(find_fixed(b"hello world", b"world"), Some(6));
+    assert_eq!(find_fixed(b"hello world", b"worlds"), None);
+    assert_eq!(find_fixed(b"aab", b"ab"), Some(1));
+    assert_eq!(find_fixed(b"abc", b""), Some(0));
+    assert_eq!(find_fixed(b"", b"a"), None);
+}
@@ -0,0 +1,801 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use util::color::{self, ColorWhen, Colorizer};
+use util::glob::{MatchFlags, Pattern};
+use util::{self, LineDelimiter};
+use {ArgsIter, MesaError, PathError, PathOperation, Result, UtilRead, UtilSetup, UtilWrite};
+
+use clap::{AppSettings, Arg, ArgMatches};
+use std::collections::VecDeque;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use self::matcher::{Matcher, MatcherOptions, Syntax};
+
+mod matcher;
+
+pub(crate) const NAME: &str = "grep";
+pub(crate) const DESCRIPTION: &str = "Search for lines matching a pattern";
+
+// these match the exit codes used by GNU grep
+const EXIT_NO_MATCH: i32 = 1;
+const EXIT_TROUBLE: i32 = 2;
+
+const STDIN_LABEL: &str = "(standard input)";
+
+// the defaults GNU grep uses when GREP_COLORS is not set
+const COLOR_MATCH: &str = "01;31";
+const COLOR_FILENAME: &str = "35";
+const COLOR_LINE_NUMBER: &str = "32";
+const COLOR_SEPARATOR: &str = "36";
+
+#[derive(Debug, Fail)]
+#[fail(display = "no pattern given")]
+struct NoPatternError;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum OutputMode {
+    Lines,
+    Count,
+    FilesWithMatches,
+    FilesWithoutMatch,
+    Quiet,
+}
+
+struct Options {
+    mode: OutputMode,
+    invert: bool,
+    line_number: bool,
+    // None means only show file names when searching more than one file
+    with_filename: Option<bool>,
+    only_matching: bool,
+    no_messages: bool,
+    text: bool,
+    recursive: bool,
+    dereference: bool,
+    max_depth: Option<usize>,
+    one_file_system: bool,
+    include: Option<Vec<Pattern>>,
+    exclude: Option<Vec<Pattern>>,
+    before: usize,
+    after: usize,
+    delimiter: LineDelimiter,
+    colorizer: Colorizer,
+    current_dir: Option<PathBuf>,
+}
+
+pub fn execute<S, T>(setup: &mut S, args: T) -> Result<()>
+where
+    S: UtilSetup,
+    T: ArgsIter,
+{
+    let matches = {
+        let app = util_app!(NAME)
+                    .setting(AppSettings::DeriveDisplayOrder)
+                    .arg(Arg::with_name("extended-regexp")
+                            .short("E")
+                            .long("extended-regexp")
+                            .overrides_with_all(&["fixed-strings", "basic-regexp"])
+                            .help("PATTERNS are extended regular expressions"))
+                    .arg(Arg::with_name("fixed-strings")
+                            .short("F")
+                            .long("fixed-strings")
+                            .overrides_with_all(&["extended-regexp", "basic-regexp"])
+                            .help("PATTERNS are strings"))
+                    .arg(Arg::with_name("basic-regexp")
+                            .short("G")
+                            .long("basic-regexp")
+                            .overrides_with_all(&["extended-regexp", "fixed-strings"])
+                            .help("PATTERNS are basic regular expressions (the default)"))
+                    .arg(Arg::with_name("regexp")
+                            .short("e")
+                            .long("regexp")
+                            .takes_value(true)
+                            .value_name("PATTERNS")
+                            .multiple(true)
+                            .number_of_values(1)
+                            .allow_hyphen_values(true)
+                            .help("Use PATTERNS for matching"))
+                    .arg(Arg::with_name("file")
+                            .short("f")
+                            .long("file")
+                            .takes_value(true)
+                            .value_name("FILE")
+                            .multiple(true)
+                            .number_of_values(1)
+                            .help("Take PATTERNS from FILE"))
+                    .arg(Arg::with_name("ignore-case")
+                            .short("i")
+                            .long("ignore-case")
+                            .help("Ignore case distinctions in patterns and data"))
+                    .arg(Arg::with_name("word-regexp")
+                            .short("w")
+                            .long("word-regexp")
+                            .help("Match only whole words"))
+                    .arg(Arg::with_name("line-regexp")
+                            .short("x")
+                            .long("line-regexp")
+                            .help("Match only whole lines"))
+                    .arg(Arg::with_name("invert-match")
+                            .short("v")
+                            .long("invert-match")
+                            .help("Select non-matching lines"))
+                    .arg(Arg::with_name("count")
+                            .short("c")
+                            .long("count")
+                            .help("Print only a count of selected lines per FILE"))
+                    .arg(Arg::with_name("files-with-matches")
+                            .short("l")
+                            .long("files-with-matches")
+                            .overrides_with("files-without-match")
+                            .help("Print only names of FILEs with selected lines"))
+                    .arg(Arg::with_name("files-without-match")
+                            .short("L")
+                            .long("files-without-match")
+                            .overrides_with("files-with-matches")
+                            .help("Print only names of FILEs with no selected lines"))
+                    .arg(Arg::with_name("quiet")
+                            .short("q")
+                            .long("quiet")
+                            .alias("silent")
+                            .help("Suppress all normal output"))
+                    .arg(Arg::with_name("no-messages")
+                            .short("s")
+                            .long("no-messages")
+                            .help("Suppress error messages"))
+                    .arg(Arg::with_name("line-number")
+                            .short("n")
+                            .long("line-number")
+                            .help("Print line number with output lines"))
+                    .arg(Arg::with_name("with-filename")
+                            .short("H")
+                            .long("with-filename")
+                            .overrides_with("no-filename")
+                            .help("Print file name with output lines"))
+                    .arg(Arg::with_name("no-filename")
+                            .short("h")
+                            .long("no-filename")
+                            .overrides_with("with-filename")
+                            .help("Suppress the file name prefix on output"))
+                    .arg(Arg::with_name("only-matching")
+                            .short("o")
+                            .long("only-matching")
+                            .help("Show only nonempty parts of lines that match"))
+                    .arg(Arg::with_name("text")
+                            .short("a")
+                            .long("text")
+                            .help("Process a binary file as if it were text"))
+                    .arg(Arg::with_name("recursive")
+                            .short("r")
+                            .long("recursive")
+                            .help("Search directories recursively"))
+                    .arg(Arg::with_name("dereference-recursive")
+                            .short("R")
+                            .long("dereference-recursive")
+                            .help("Like --recursive, but follow all symlinks"))
+                    .arg(Arg::with_name("max-depth")
+                            .long("max-depth")
+                            .takes_value(true)
+                            .value_name("NUM")
+                            .validator(is_valid_depth)
+                            .help("When recursing, descend at most NUM levels below each directory"))
+                    .arg(Arg::with_name("one-file-system")
+                            .long("one-file-system")
+                            .help("When recursing, skip directories on other file systems"))
+                    .arg(Arg::with_name("include")
+                            .long("include")
+                            .takes_value(true)
+                            .value_name("GLOB")
+                            .multiple(true)
+                            .number_of_values(1)
+                            .help("When recursing, search only files whose base name matches GLOB"))
+                    .arg(Arg::with_name("exclude")
+                            .long("exclude")
+                            .takes_value(true)
+                            .value_name("GLOB")
+                            .multiple(true)
+                            .number_of_values(1)
+                            .help("When recursing, skip files whose base name matches GLOB"))
+                    .arg(Arg::with_name("after-context")
+                            .short("A")
+                            .long("after-context")
+                            .takes_value(true)
+                            .value_name("NUM")
+                            .validator(is_valid_num)
+                            .help("Print NUM lines of trailing context"))
+                    .arg(Arg::with_name("before-context")
+                            .short("B")
+                            .long("before-context")
+                            .takes_value(true)
+                            .value_name("NUM")
+                            .validator(is_valid_num)
+                            .help("Print NUM lines of leading context"))
+                    .arg(Arg::with_name("context")
+                            .short("C")
+                            .long("context")
+                            .takes_value(true)
+                            .value_name("NUM")
+                            .validator(is_valid_num)
+                            .help("Print NUM lines of output context"))
+                    .arg(color::color_arg())
+                    .arg(util::zero_terminated_arg()
+                            .alias("null-data"))
+                    .arg(Arg::with_name("ARGS")
+                            .value_name("PATTERNS] [FILE")
+                            .index(1)
+                            .multiple(true));
+
+        app.get_matches_from_safe(args)
+            .map_err(|e| MesaError::from(e).with_exitcode(EXIT_TROUBLE))?
+    };
+
+    let current_dir = setup.current_dir().map(|p| p.to_owned());
+
+    let mut operands: Vec<&OsStr> = matches
+        .values_of_os("ARGS")
+        .map(|values| values.collect())
+        .unwrap_or_default();
+
+    let patterns = collect_patterns(&matches, &current_dir, &mut operands)
+        .map_err(|e| e.with_exitcode(EXIT_TROUBLE))?;
+
+    let syntax = if matches.is_present("extended-regexp") {
+        Syntax::Extended
+    } else if matches.is_present("fixed-strings") {
+        Syntax::Fixed
+    } else {
+        Syntax::Basic
+    };
+    let matcher_opts = MatcherOptions {
+        syntax: syntax,
+        ignore_case: matches.is_present("ignore-case"),
+        word: matches.is_present("word-regexp"),
+        line: matches.is_present("line-regexp"),
+    };
+    let matcher = Matcher::new(&patterns, &matcher_opts)
+        .map_err(|e| MesaError::from(e).with_exitcode(EXIT_TROUBLE))?;
+
+    let mode = if matches.is_present("quiet") {
+        OutputMode::Quiet
+    } else if matches.is_present("files-with-matches") {
+        OutputMode::FilesWithMatches
+    } else if matches.is_present("files-without-match") {
+        OutputMode::FilesWithoutMatch
+    } else if matches.is_present("count") {
+        OutputMode::Count
+    } else {
+        OutputMode::Lines
+    };
+
+    let only_matching = matches.is_present("only-matching");
+    let context = context_value(&matches, "context").unwrap_or(0);
+    let (before, after) = if only_matching {
+        // context lines make little sense when only printing the matching parts of lines
+        (0, 0)
+    } else {
+        (
+            context_value(&matches, "before-context").unwrap_or(context),
+            context_value(&matches, "after-context").unwrap_or(context),
+        )
+    };
+
+    let with_filename = if matches.is_present("with-filename") {
+        Some(true)
+    } else if matches.is_present("no-filename") {
+        Some(false)
+    } else {
+        None
+    };
+
+    let stdout_obj = setup.output().raw_object();
+    let color_when = ColorWhen::from_matches(&matches, ColorWhen::Never);
+    let colorizer = Colorizer::new(color_when.enabled(stdout_obj, setup.env().get("NO_COLOR")));
+
+    let dereference = matches.is_present("dereference-recursive");
+    let options = Options {
+        mode: mode,
+        invert: matches.is_present("invert-match"),
+        line_number: matches.is_present("line-number"),
+        with_filename: with_filename,
+        only_matching: only_matching,
+        no_messages: matches.is_present("no-messages"),
+        text: matches.is_present("text"),
+        recursive: dereference || matches.is_present("recursive"),
+        dereference: dereference,
+        max_depth: context_value(&matches, "max-depth"),
+        one_file_system: matches.is_present("one-file-system"),
+        include: build_globs(matches.values_of("include"))?,
+        exclude: build_globs(matches.values_of("exclude"))?,
+        before: before,
+        after: after,
+        delimiter: LineDelimiter::from_matches(&matches),
+        colorizer: colorizer,
+        current_dir: current_dir,
+    };
+
+    // searching the current directory is implied for -r without any files
+    let implicit_dir = operands.is_empty() && options.recursive;
+    if operands.is_empty() {
+        operands.push(OsStr::new(if implicit_dir { "." } else { "-" }));
+    }
+    let multiple = operands.len() > 1;
+
+    let (input, stdout, stderr) = setup.stdio();
+    let mut grepper = Grepper {
+        stdout: stdout.lock()?,
+        stderr: stderr.lock()?,
+        options: &options,
+        matcher: matcher,
+        found: false,
+        error: false,
+        printed_lines: false,
+    };
+
+    for operand in operands {
+        if operand == OsStr::new("-") {
+            let stdin = input.lock()?;
+            grepper.grep_input(stdin, OsStr::new(STDIN_LABEL), multiple)?;
+        } else {
+            grepper.grep_operand(operand, multiple, implicit_dir)?;
+        }
+
+        if grepper.found && options.mode == OutputMode::Quiet {
+            break;
+        }
+    }
+
+    // with -q, finding a match is enough to succeed even if there were errors
+    if grepper.error && !(grepper.found && options.mode == OutputMode::Quiet) {
+        Err(MesaError::new(None, EXIT_TROUBLE, None))
+    } else if grepper.found {
+        Ok(())
+    } else {
+        Err(MesaError::new(None, EXIT_NO_MATCH, None))
+    }
+}
+
+/// Gather the patterns from -e and -f (or the first operand if neither were given).  Each
+/// pattern may actually contain several newline-separated patterns.
+fn collect_patterns(
+    matches: &ArgMatches,
+    current_dir: &Option<PathBuf>,
+    operands: &mut Vec<&OsStr>,
+) -> Result<Vec<Vec<u8>>> {
+    let mut patterns = vec![];
+
+    if let Some(values) = matches.values_of_os("regexp") {
+        for value in values {
+            split_patterns(&mut patterns, value);
+        }
+    }
+
+    if let Some(values) = matches.values_of_os("file") {
+        for value in values {
+            let mut data = vec![];
+            let mut file = BufReader::new(util::open_file(current_dir, value)?);
+            loop {
+                data.clear();
+                let n = file.read_until(b'\n', &mut data)
+                    .map_err(|e| PathError::new(PathOperation::Read, value, e))?;
+                if n == 0 {
+                    break;
+                }
+                if data.last() == Some(&b'\n') {
+                    data.pop();
+                }
+                patterns.push(data.clone());
+            }
+        }
+    }
+
+    if !matches.is_present("regexp") && !matches.is_present("file") {
+        if operands.is_empty() {
+            return Err(NoPatternError.into());
+        }
+        let pattern = operands.remove(0);
+        split_patterns(&mut patterns, pattern);
+    }
+
+    Ok(patterns)
+}
+
+fn split_patterns(patterns: &mut Vec<Vec<u8>>, value: &OsStr) {
+    let value = util::os_str_as_bytes(value);
+    patterns.extend(value.split(|&b| b == b'\n').map(|p| p.to_vec()));
+}
+
+fn build_globs<'a, I>(globs: Option<I>) -> Result<Option<Vec<Pattern>>>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let globs = match globs {
+        Some(globs) => globs,
+        None => return Ok(None),
+    };
+
+    let mut patterns = vec![];
+    for glob in globs {
+        let pattern = Pattern::new(glob, MatchFlags::default())
+            .map_err(|e| MesaError::from(e).with_exitcode(EXIT_TROUBLE))?;
+        patterns.push(pattern);
+    }
+    Ok(Some(patterns))
+}
+
+fn context_value(matches: &ArgMatches, name: &str) -> Option<usize> {
+    matches.value_of(name).map(|v| v.parse().unwrap())
+}
+
+fn is_valid_num(val: String) -> ::std::result::Result<(), String> {
+    val.parse::<usize>()
+        .map(|_| ())
+        .map_err(|_| format!("invalid context length argument '{}'", val))
+}
+
+fn is_valid_depth(val: String) -> ::std::result::Result<(), String> {
+    val.parse::<usize>()
+        .map(|_| ())
+        .map_err(|_| format!("invalid maximum depth '{}'", val))
+}
+
+// the device a file is on, for --one-file-system (which is only supported on Unix)
+#[cfg(unix)]
+fn device(metadata: &fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn device(_metadata: &fs::Metadata) -> Option<u64> {
+    None
+}
+
+struct Grepper<'a, O, E>
+where
+    O: Write,
+    E: Write,
+{
+    stdout: O,
+    stderr: E,
+    options: &'a Options,
+    matcher: Matcher,
+    // whether any line was selected (or file listed, for -L)
+    found: bool,
+    error: bool,
+    // whether any lines have been printed yet (to know when to print context separators)
+    printed_lines: bool,
+}
+
+impl<'a, O, E> Grepper<'a, O, E>
+where
+    O: Write,
+    E: Write,
+{
+    fn grep_operand(&mut self, operand: &OsStr, multiple: bool, implicit_dir: bool) -> Result<()> {
+        let path = util::actual_path(&self.options.current_dir, operand).into_owned();
+
+        if self.options.recursive && fs::metadata(&path).map(|m| m.is_dir()).unwrap_or(false) {
+            return self.grep_dir(operand, &path, implicit_dir);
+        }
+
+        let show_name = self.options.with_filename.unwrap_or(multiple);
+        self.grep_file(operand, &path, show_name)
+    }
+
+    fn grep_dir(&mut self, operand: &OsStr, root: &Path, implicit_dir: bool) -> Result<()> {
+        let show_name = self.options.with_filename.unwrap_or(true);
+
+        let mut walker = WalkDir::new(root)
+            .follow_links(self.options.dereference)
+            .sort_by(|a, b| a.file_name().cmp(b.file_name()));
+        if let Some(depth) = self.options.max_depth {
+            // the files directly inside `root` are at depth 1
+            walker = walker.max_depth(depth + 1);
+        }
+        let root_dev = if self.options.one_file_system {
+            fs::metadata(root).ok().as_ref().and_then(device)
+        } else {
+            None
+        };
+
+        // walkdir reports symlink loops (which can only be followed with -R) as errors, so they are
+        // skipped like any other unreadable directory
+        let walker = walker.into_iter().filter_entry(|entry| {
+            root_dev.is_none()
+                || !entry.file_type().is_dir()
+                || entry.metadata().ok().as_ref().and_then(device) == root_dev
+        });
+        for entry in walker {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    self.report(&e)?;
+                    continue;
+                }
+            };
+
+            // symlinks found while recursing are only followed for -R (in which case walkdir
+            // has already resolved them)
+            let file_type = entry.file_type();
+            if file_type.is_dir() || file_type.is_symlink() {
+                continue;
+            }
+            if !self.is_included(entry.file_name()) {
+                continue;
+            }
+
+            let relative = entry.path().strip_prefix(root).unwrap_or(entry.path());
+            let display = if implicit_dir {
+                relative.to_owned()
+            } else {
+                Path::new(operand).join(relative)
+            };
+            self.grep_file(display.as_os_str(), entry.path(), show_name)?;
+
+            if self.found && self.options.mode == OutputMode::Quiet {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_included(&self, name: &OsStr) -> bool {
+        if let Some(ref include) = self.options.include {
+            if !include.iter().any(|glob| glob.is_match(name)) {
+                return false;
+            }
+        }
+        match self.options.exclude {
+            Some(ref exclude) => !exclude.iter().any(|glob| glob.is_match(name)),
+            None => true,
+        }
+    }
+
+    fn grep_file(&mut self, name: &OsStr, path: &Path, show_name: bool) -> Result<()> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                let err = PathError::new(PathOperation::OpenRead, name, e);
+                return self.report(&err);
+            }
+        };
+        // opening a directory usually succeeds, but reading it fails with a less helpful error
+        if file.metadata().map(|m| m.is_dir()).unwrap_or(false) {
+            let err = io::Error::new(io::ErrorKind::Other, "Is a directory");
+            return self.report(&PathError::new(PathOperation::Read, name, err));
+        }
+        self.grep_input(BufReader::new(file), name, show_name)
+    }
+
+    fn report<F: ::std::fmt::Display>(&mut self, err: &F) -> Result<()> {
+        self.error = true;
+        if !self.options.no_messages {
+            display_msg!(self.stderr, "{}", err)?;
+        }
+        Ok(())
+    }
+
+    fn grep_input<R>(&mut self, mut input: R, name: &OsStr, show_name: bool) -> Result<()>
+    where
+        R: BufRead,
+    {
+        let options = self.options;
+        let delim = options.delimiter;
+        let label = if show_name { Some(name) } else { None };
+
+        let binary = match input.fill_buf() {
+            Ok(data) => !options.text && delim == LineDelimiter::Newline && data.contains(&0),
+            Err(e) => {
+                let err = PathError::new(PathOperation::Read, name, e);
+                return self.report(&err);
+            }
+        };
+
+        let mut count = 0u64;
+        let mut line_number = 0u64;
+        let mut before: VecDeque<(u64, Vec<u8>)> = VecDeque::with_capacity(options.before);
+        let mut after_left = 0;
+        let mut last_printed = None;
+        let mut buf = vec![];
+
+        loop {
+            buf.clear();
+            match delim.read_line(&mut input, &mut buf) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e) => {
+                    let err = PathError::new(PathOperation::Read, name, e);
+                    self.report(&err)?;
+                    break;
+                }
+            }
+            line_number += 1;
+
+            if buf.last() == Some(&delim.byte()) {
+                buf.pop();
+            }
+
+            let selected = self.matcher.is_match(&buf) != options.invert;
+            if !selected {
+                if after_left > 0 {
+                    self.print_line(label, line_number, &buf, false, &mut last_printed)?;
+                    after_left -= 1;
+                } else if options.before > 0 {
+                    if before.len() == options.before {
+                        before.pop_front();
+                    }
+                    before.push_back((line_number, buf.clone()));
+                }
+                continue;
+            }
+
+            count += 1;
+            match options.mode {
+                OutputMode::Lines => {}
+                OutputMode::Count => continue,
+                // nothing else in the file can change the result
+                _ => break,
+            }
+
+            if binary {
+                self.stdout.write_all(b"Binary file ")?;
+                self.stdout.write_all(&util::os_str_as_bytes(name))?;
+                self.stdout.write_all(b" matches\n")?;
+                break;
+            }
+
+            while let Some((num, line)) = before.pop_front() {
+                self.print_line(label, num, &line, false, &mut last_printed)?;
+            }
+            self.print_line(label, line_number, &buf, true, &mut last_printed)?;
+            after_left = options.after;
+        }
+
+        let found = match options.mode {
+            OutputMode::Count => {
+                if let Some(name) = label {
+                    self.write_name(name)?;
+                    self.write_separator(b':')?;
+                }
+                writeln!(self.stdout, "{}", count)?;
+                count > 0
+            }
+            OutputMode::FilesWithMatches | OutputMode::FilesWithoutMatch => {
+                let list = (count > 0) == (options.mode == OutputMode::FilesWithMatches);
+                if list {
+                    self.write_name(name)?;
+                    self.stdout.write_all(b"\n")?;
+                }
+                list
+            }
+            _ => count > 0,
+        };
+        self.found |= found;
+
+        Ok(())
+    }
+
+    fn print_line(
+        &mut self,
+        label: Option<&OsStr>,
+        line_number: u64,
+        line: &[u8],
+        selected: bool,
+        last_printed: &mut Option<u64>,
+    ) -> Result<()> {
+        let options = self.options;
+
+        if options.before > 0 || options.after > 0 {
+            // separate groups of lines that aren't adjacent (including those from other files)
+            let adjacent = match *last_printed {
+                Some(num) => num + 1 == line_number,
+                None => !self.printed_lines,
+            };
+            if !adjacent {
+                let sep = options.colorizer.paint(COLOR_SEPARATOR, "--");
+                writeln!(self.stdout, "{}", sep)?;
+            }
+        }
+        *last_printed = Some(line_number);
+        self.printed_lines = true;
+
+        let sep = if selected { b':' } else { b'-' };
+        let highlight = selected && !options.invert;
+
+        if options.only_matching {
+            if !highlight {
+                return Ok(());
+            }
+            let mut start = 0;
+            while let Some((begin, end)) = self.matcher.find_at(line, start) {
+                if begin == end {
+                    // skip over empty matches, which are never printed
+                    if end >= line.len() {
+                        break;
+                    }
+                    start = end + 1;
+                    continue;
+                }
+                self.write_prefix(label, line_number, sep)?;
+                self.write_match(&line[begin..end])?;
+                self.stdout.write_all(&[options.delimiter.byte()])?;
+                start = end;
+            }
+            return Ok(());
+        }
+
+        self.write_prefix(label, line_number, sep)?;
+        if highlight && options.colorizer.is_enabled() {
+            let mut start = 0;
+            let mut written = 0;
+            while let Some((begin, end)) = self.matcher.find_at(line, start) {
+                if begin == end {
+                    if end >= line.len() {
+                        break;
+                    }
+                    start = end + 1;
+                    continue;
+                }
+                self.stdout.write_all(&line[written..begin])?;
+                self.write_match(&line[begin..end])?;
+                written = end;
+                start = end;
+            }
+            self.stdout.write_all(&line[written..])?;
+        } else {
+            self.stdout.write_all(line)?;
+        }
+        self.stdout.write_all(&[options.delimiter.byte()])?;
+
+        Ok(())
+    }
+
+    fn write_prefix(&mut self, label: Option<&OsStr>, line_number: u64, sep: u8) -> Result<()> {
+        if let Some(name) = label {
+            self.write_name(name)?;
+            self.write_separator(sep)?;
+        }
+        if self.options.line_number {
+            let colorizer = self.options.colorizer;
+            let number = line_number.to_string();
+            write!(self.stdout, "{}", colorizer.paint(COLOR_LINE_NUMBER, &number))?;
+            self.write_separator(sep)?;
+        }
+        Ok(())
+    }
+
+    fn write_name(&mut self, name: &OsStr) -> Result<()> {
+        let colorizer = self.options.colorizer;
+        self.stdout.write_all(colorizer.start(COLOR_FILENAME).as_bytes())?;
+        self.stdout.write_all(&util::os_str_as_bytes(name))?;
+        self.stdout.write_all(colorizer.reset().as_bytes())?;
+        Ok(())
+    }
+
+    fn write_separator(&mut self, sep: u8) -> Result<()> {
+        let colorizer = self.options.colorizer;
+        self.stdout.write_all(colorizer.start(COLOR_SEPARATOR).as_bytes())?;
+        self.stdout.write_all(&[sep])?;
+        self.stdout.write_all(colorizer.reset().as_bytes())?;
+        Ok(())
+    }
+
+    fn write_match(&mut self, text: &[u8]) -> Result<()> {
+        let colorizer = self.options.colorizer;
+        self.stdout.write_all(colorizer.start(COLOR_MATCH).as_bytes())?;
+        self.stdout.write_all(text)?;
+        self.stdout.write_all(colorizer.reset().as_bytes())?;
+        Ok(())
+    }
+}
@@ -7,16 +7,24 @@
 //
 
 use util;
-use {ArgsIter, Result, UtilRead, UtilSetup, UtilWrite};
-
-use clap::{AppSettings, Arg, ArgGroup};
+use util::binary::{self, BinaryFileError};
+#[cfg(feature = "decompress")]
+use util::decompress;
+use util::lines;
+#[cfg(all(unix, feature = "mmap"))]
+use util::mmap::MappedFile;
+#[cfg(unix)]
+use util::sparse;
+use util::{FileHeader, LineDelimiter, LineFlusher, OutputWriter, RingTail};
+use {ArgsIter, PathError, PathOperation, Result, UtilRead, UtilSetup, UtilWrite};
+
+use clap::{Arg, ArgGroup};
+use std::borrow::Cow;
 use std::collections::VecDeque;
 use std::ffi::{OsStr, OsString};
 use std::fs::File;
-use std::io::{self, BufRead, BufReader, Read, Write};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
 use std::iter;
-use std::mem;
-use std::path::Path;
 use std::result::Result as StdResult;
 
 pub const NAME: &str = "head";
@@ -33,8 +41,25 @@ Providing the suffix 'b' is equivalent to multiplying NUMBER by 512.
 Please note that the maximum value for NUMBER is the maximum value of your platform's native
 integer type (so a 64-bit number on 64-bit platforms).  Therefore, some suffixes may not work at
 all on your system.
+
+With -b, files that look binary (because they contain NUL bytes near the start) are not printed
+when standard output is a terminal, unless --text is also given.
+
+If a FILE operand is of the form @LIST, the names of the files to read are taken from LIST
+instead (one per line, or separated by NUL with -z).  Use ./@NAME to read a file that really is
+named @NAME.
 ";
 
+// the size of the blocks read when working backwards from the end of the input (unless
+// MESABOX_BUFSIZE says otherwise)
+const BUF_SIZE: usize = 32 * 1024;
+
+#[derive(Debug, Fail)]
+enum HeadError {
+    #[fail(display = "--max-bytes cannot be used when printing all but the last NUMBER lines")]
+    MaxBytesWithNegativeLines,
+}
+
 enum Mode {
     Bytes((usize, bool)),
     Lines((usize, bool)),
@@ -42,7 +67,20 @@ enum Mode {
 
 struct Options {
     method: Mode,
-    previous_printed: bool,
+    // with Mode::Lines, stop after this many bytes even if not enough lines have been printed
+    max_bytes: Option<usize>,
+    delimiter: LineDelimiter,
+    header: FileHeader,
+    // decompress inputs that look compressed (only with the decompress feature)
+    decompress: bool,
+    // refuse to print inputs that look binary
+    binary_check: bool,
+    buffer_size: usize,
+    // treat every input like a pipe, never seeking in it or mapping it into memory
+    presume_input_pipe: bool,
+    // standard output as a file that holes can be made in (for copying sparse files with -c)
+    #[cfg(unix)]
+    sparse_output: Option<File>,
 }
 
 pub fn execute<S, T>(setup: &mut S, mut args: T) -> Result<()>
@@ -54,7 +92,6 @@ where
 
     let matches = {
         let app = util_app!("head")
-                    .setting(AppSettings::AllowLeadingHyphen)
                     .after_help(AFTER_HELP)
                     .group(ArgGroup::with_name("mode")
                             .arg("bytes")
@@ -64,6 +101,7 @@ where
                             .long("bytes")
                             .takes_value(true)
                             .value_name("NUMBER")
+                            .allow_hyphen_values(true)
                             .validator_os(is_valid_num)
                             .help("Print the first NUMBER bytes if NUMBER is positive; otherwise print all but the last NUMBER bytes"))
                     .arg(Arg::with_name("lines")
@@ -71,8 +109,16 @@ where
                             .long("lines")
                             .takes_value(true)
                             .value_name("NUMBER")
+                            .allow_hyphen_values(true)
                             .validator_os(is_valid_num)
                             .help("Print the first NUMBER lines if NUMBER is positive; otherwise print all but the last NUMBER lines"))
+                    .arg(Arg::with_name("max-bytes")
+                            .long("max-bytes")
+                            .takes_value(true)
+                            .value_name("NUMBER")
+                            .conflicts_with("bytes")
+                            .validator_os(is_valid_size)
+                            .help("Stop after NUMBER bytes even if fewer lines have been printed (cutting the last line short)"))
                     .arg(Arg::with_name("quiet")
                             .short("q")
                             .long("quiet")
@@ -83,9 +129,20 @@ where
                             .long("verbose")
                             .overrides_with("quiet")
                             .help("Always print file headers"))
+                    .arg(binary::binary_check_arg()
+                            .short("b"))
+                    .arg(binary::text_arg())
+                    .arg(util::header_format_arg())
+                    .arg(util::zero_terminated_arg())
+                    .arg(util::line_buffered_arg())
+                    .arg(Arg::with_name("presume-input-pipe")
+                            .long("presume-input-pipe")
+                            .hidden(true))
                     .arg(Arg::with_name("FILES")
                             .index(1)
                             .multiple(true));
+        #[cfg(feature = "decompress")]
+        let app = app.arg(decompress::decompress_arg());
 
         let res = check_obsolete(&mut args);
         match res {
@@ -113,56 +170,99 @@ where
         Mode::Lines((default_lines, true))
     };
 
+    let max_bytes = matches
+        .value_of("max-bytes")
+        .map(|num| util::parse_num_with_suffix(num).unwrap());
+    // the byte limit only makes sense when printing lines from the start of the input
+    if let (Some(_), &Mode::Lines((_, false))) = (max_bytes, &method) {
+        return Err(HeadError::MaxBytesWithNegativeLines.into());
+    }
+
     let mut options = Options {
         method: method,
-        previous_printed: false,
+        max_bytes: max_bytes,
+        delimiter: LineDelimiter::from_matches(&matches),
+        header: FileHeader::from_matches(&matches),
+        decompress: matches.is_present("decompress"),
+        binary_check: false,
+        buffer_size: setup.buffer_size().unwrap_or(BUF_SIZE),
+        presume_input_pipe: matches.is_present("presume-input-pipe"),
+        #[cfg(unix)]
+        sparse_output: None,
     };
 
     let current_dir = setup.current_dir().map(|p| p.to_owned());
     let (input, output, error) = setup.stdio();
 
-    let mut output = output.lock()?;
-    if matches.is_present("FILES") {
-        let mut result = Ok(());
-        let mut err_stream = error.lock()?;
-
-        let file_count = matches.occurrences_of("FILES");
-
-        for file in matches.values_of_os("FILES").unwrap() {
-            let filename = if (file_count > 1 && !quiet) || verbose {
-                Some(file)
+    let line_buffered = util::is_line_buffered(&matches, output.raw_object());
+    options.binary_check = binary::is_checked(&matches, output.raw_object());
+    #[cfg(unix)]
+    {
+        if let Mode::Bytes((_, true)) = options.method {
+            options.sparse_output = output.raw_object().and_then(sparse::open_output);
+        }
+    }
+    let output = OutputWriter::new(output.lock()?);
+    let mut output = LineFlusher::new(output, options.delimiter, line_buffered);
+    let mut err_stream = error.lock()?;
+
+    // reading from stdin without any operands is handled exactly like an operand of "-"
+    let files = match matches.values_of_os("FILES") {
+        Some(values) => util::expand_argfiles(&current_dir, values, options.delimiter)?,
+        None => vec![Cow::Borrowed(OsStr::new("-"))],
+    };
+    let print_headers = (files.len() > 1 && !quiet) || verbose;
+
+    let mut result = Ok(());
+    for file in &files {
+        let file = &**file;
+        let res = if file == OsStr::new("-") {
+            let filename = if print_headers {
+                Some(OsStr::new("standard input"))
             } else {
                 None
             };
-            let res = if file == OsStr::new("-") {
-                let filename = filename.map(|_| OsStr::new("standard input"));
-                handle_stdin(&mut output, input, filename, &mut options)
-            } else {
-                let path = util::actual_path(&current_dir, file);
-                handle_file(&mut output, &path, filename, &mut options)
-            };
+            handle_stdin(&mut output, input, OsStr::new("standard input"), filename, &mut options)
+        } else {
+            let filename = if print_headers { Some(file) } else { None };
+            util::open_file(&current_dir, file)
+                .and_then(|f| reject_directory(f, file))
+                .and_then(|f| handle_file(&mut output, f, file, filename, &mut options))
+        };
 
-            if let Err(mut e) = res {
-                display_msg!(err_stream, "{}", e)?;
-                e.err = None;
-                result = Err(e);
+        if let Err(mut e) = res {
+            // nothing else can be written once standard output is gone (or full)
+            if e.is_output_error() {
+                return Err(e);
             }
+            display_msg!(err_stream, "{}", e)?;
+            e.err = None;
+            result = Err(e);
         }
+    }
 
-        result
-    } else {
-        let filename = if verbose {
-            Some(OsStr::new("standard input"))
-        } else {
-            None
-        };
-        handle_stdin(output, input, filename, &mut options)
+    // anything still buffered has to be written now for errors writing it to be reported
+    output.flush()?;
+
+    result
+}
+
+// opening a directory succeeds on most platforms, but reading from it then fails with an error that
+// doesn't mention what went wrong
+fn reject_directory(file: File, name: &OsStr) -> Result<File> {
+    match file.metadata() {
+        Ok(ref meta) if meta.is_dir() => {
+            let err = io::Error::new(io::ErrorKind::Other, "Is a directory");
+            Err(PathError::new(PathOperation::Read, name, err).into())
+        }
+        _ => Ok(file),
     }
 }
 
 fn handle_stdin<I, O>(
     output: O,
     stdin: &mut I,
+    name: &OsStr,
     filename: Option<&OsStr>,
     options: &mut Options,
 ) -> Result<()>
@@ -171,23 +271,78 @@ where
     O: Write,
 {
     let stdin = stdin.lock()?;
-    handle_data(output, stdin, filename, options)
+    handle_data(output, stdin, name, filename, options)
 }
 
 fn handle_file<O: Write>(
-    output: O,
-    filename: &Path,
+    mut output: O,
+    mut file: File,
+    name: &OsStr,
     disp_filename: Option<&OsStr>,
     options: &mut Options,
 ) -> Result<()> {
-    let file = File::open(filename)?;
+    // the faster ways of copying below would skip decompression and the binary check (and they
+    // all rely on the input being a regular file, which some special files only pretend to be)
+    if options.decompress || options.binary_check || options.presume_input_pipe {
+        return handle_data(&mut output, BufReader::new(file), name, disp_filename, options);
+    }
+
+    // copying sparsely comes before mapping the file because sparse_output is only set when
+    // standard output is a regular file that can have holes, and writing out a mapping would fill
+    // in any holes that the input has
+    #[cfg(unix)]
+    {
+        if let Mode::Bytes((bytes, true)) = options.method {
+            if let Some(mut sparse_output) = options.sparse_output.take() {
+                write_header(&mut output, disp_filename, options)?;
+                // anything already written through `output` has to end up before the copied data
+                output.flush()?;
+                let res = sparse::copy_sparse(
+                    &mut file,
+                    &mut sparse_output,
+                    bytes as u64,
+                    options.buffer_size,
+                );
+                options.sparse_output = Some(sparse_output);
+                res?;
+                return Ok(());
+            }
+        }
+    }
+
+    #[cfg(all(unix, feature = "mmap"))]
+    {
+        if let Mode::Bytes((bytes, true)) = options.method {
+            if let Some(mapping) = MappedFile::new(&file, Some(bytes as u64))? {
+                write_header(&mut output, disp_filename, options)?;
+                mapping.write_to(&mut output)?;
+                return Ok(());
+            }
+        }
+    }
+
+    // when we can seek, there is no need to hold the trailing lines in memory
+    if let Mode::Lines((lines, false)) = options.method {
+        if file.metadata()?.is_file() {
+            write_header(&mut output, disp_filename, options)?;
+            return write_lines_backward_seekable(
+                output,
+                file,
+                lines,
+                options.delimiter,
+                options.buffer_size,
+            );
+        }
+    }
+
     let reader = BufReader::new(file);
-    handle_data(output, reader, disp_filename, options)
+    handle_data(&mut output, reader, name, disp_filename, options)
 }
 
 fn handle_data<W, R>(
-    mut output: W,
+    output: W,
     input: R,
+    name: &OsStr,
     filename: Option<&OsStr>,
     options: &mut Options,
 ) -> Result<()>
@@ -195,21 +350,48 @@ where
     W: Write,
     R: BufRead,
 {
-    if let Some(name) = filename {
-        let path = Path::new(name);
-        if options.previous_printed {
-            writeln!(output, "\n==> {} <==", path.display())?;
-        } else {
-            writeln!(output, "==> {} <==", path.display())?;
-            options.previous_printed = true;
+    #[cfg(feature = "decompress")]
+    {
+        if options.decompress {
+            return check_data(output, decompress::open(input)?, name, filename, options);
         }
     }
+    check_data(output, input, name, filename, options)
+}
+
+// write the header and data for the input unless it is binary and shouldn't be shown
+fn check_data<W, R>(
+    mut output: W,
+    mut input: R,
+    name: &OsStr,
+    filename: Option<&OsStr>,
+    options: &mut Options,
+) -> Result<()>
+where
+    W: Write,
+    R: BufRead,
+{
+    if options.binary_check && binary::looks_binary(input.fill_buf()?) {
+        return Err(BinaryFileError(name.to_string_lossy().into_owned()).into());
+    }
+
+    write_header(&mut output, filename, options)?;
+    write_data(output, input, options)
+}
+
+fn write_data<W, R>(mut output: W, input: R, options: &Options) -> Result<()>
+where
+    W: Write,
+    R: BufRead,
+{
     match options.method {
         Mode::Lines((lines, positive)) => {
-            if positive {
-                write_lines_forward(output, input, lines)
+            if let Some(max_bytes) = options.max_bytes {
+                write_lines_forward_limited(output, input, lines, max_bytes, options.delimiter)
+            } else if positive {
+                write_lines_forward(output, input, lines, options.delimiter)
             } else {
-                write_lines_backward(output, input, lines)
+                write_lines_backward(output, input, lines, options.delimiter)
             }
         }
         Mode::Bytes((bytes, positive)) => {
@@ -217,35 +399,77 @@ where
                 io::copy(&mut input.take(bytes as u64), &mut output)?;
                 Ok(())
             } else {
-                write_bytes_backward(output, input, bytes)
+                write_bytes_backward(output, input, bytes, options.buffer_size)
             }
         }
     }
 }
 
-fn write_lines_forward<W, R>(mut output: W, mut input: R, mut line_count: usize) -> Result<()>
+fn write_header<W: Write>(
+    output: &mut W,
+    filename: Option<&OsStr>,
+    options: &mut Options,
+) -> Result<()> {
+    if let Some(name) = filename {
+        options.header.write(output, name)?;
+    }
+
+    Ok(())
+}
+
+fn write_lines_forward<W, R>(
+    mut output: W,
+    mut input: R,
+    line_count: usize,
+    delimiter: LineDelimiter,
+) -> Result<()>
+where
+    W: Write,
+    R: BufRead,
+{
+    lines::take_range(&mut input, &mut output, 1, Some(line_count), delimiter)?;
+    Ok(())
+}
+
+// like write_lines_forward(), but stopping early once `max_bytes` bytes have been written, even in
+// the middle of a line
+fn write_lines_forward_limited<W, R>(
+    mut output: W,
+    mut input: R,
+    mut line_count: usize,
+    max_bytes: usize,
+    delimiter: LineDelimiter,
+) -> Result<()>
 where
     W: Write,
     R: BufRead,
 {
-    let mut buffer = vec![];
-    while line_count > 0 {
-        // NOTE: it would be faster to just continuously read into the buffer and then
-        //       write once, but that could potentially take a lot of memory
-        let count = input.read_until(b'\n', &mut buffer)?;
-        if count == 0 {
+    let mut remaining = max_bytes as u64;
+    let mut line = vec![];
+    while line_count > 0 && remaining > 0 {
+        line.clear();
+        // reading through take() means a huge line is never held in memory just to be cut short
+        let len = delimiter.read_line(&mut (&mut input).take(remaining), &mut line)?;
+        if len == 0 {
             break;
         }
-        output.write_all(&buffer)?;
-
-        buffer.clear();
+        output.write_all(&line)?;
+        remaining -= len as u64;
         line_count -= 1;
     }
 
     Ok(())
 }
 
-fn write_lines_backward<W, R>(mut output: W, mut input: R, mut line_count: usize) -> Result<()>
+// NOTE: as we cannot seek here, the last line_count lines must be kept in memory (so something
+//       like head -n -1000000 with very long lines will use a lot of memory).  regular files are
+//       handled by write_lines_backward_seekable() instead
+fn write_lines_backward<W, R>(
+    mut output: W,
+    mut input: R,
+    mut line_count: usize,
+    delimiter: LineDelimiter,
+) -> Result<()>
 where
     W: Write,
     R: BufRead,
@@ -254,7 +478,7 @@ where
 
     // returns true if we can just return rather than printing
     let mut read_line = |store: &mut VecDeque<_>, mut line| -> StdResult<_, io::Error> {
-        if input.read_until(b'\n', &mut line)? == 0 {
+        if delimiter.read_line(&mut input, &mut line)? == 0 {
             return Ok(true);
         }
         store.push_back(line);
@@ -282,67 +506,91 @@ where
     Ok(())
 }
 
-fn write_bytes_backward<W, R>(mut output: W, mut input: R, bytes: usize) -> Result<()>
+fn write_lines_backward_seekable<W, R>(
+    mut output: W,
+    mut input: R,
+    line_count: usize,
+    delimiter: LineDelimiter,
+    buffer_size: usize,
+) -> Result<()>
 where
     W: Write,
-    R: BufRead,
+    R: Read + Seek,
 {
-    const BUF_SIZE: usize = 32 * 1024;
-
-    // FIXME: if the user provides a byte count greater than the amount of memory available and
-    //        the file size is also greater than the amount of memory available, this will
-    //        currently exhaust memory and abort.  not sure what the best way to fix this is other
-    //        than writing to a temporary file if the size is too large (but this solution comes
-    //        with its own issues as well)
-    let size = bytes.max(32 * 1024);
-    let (mut first_buffer, mut second_buffer) = if size > BUF_SIZE {
-        // in case the byte count is larger than the amount of memory, only allocate to the size of
-        // the data read (in case the file size is much smaller than the byte count, which would
-        // mean nothing should be printed rather than the program aborting)
-        (vec![], vec![])
-    } else {
-        (Vec::with_capacity(size), Vec::with_capacity(size))
-    };
-    let mut prev_buffer = &mut first_buffer;
-    let mut cur_buffer = &mut second_buffer;
-
-    loop {
-        let n = (&mut input).take(size as u64).read_to_end(cur_buffer)?;
-        if n == size {
-            output.write_all(prev_buffer)?;
-            mem::swap(&mut prev_buffer, &mut cur_buffer);
-            cur_buffer.clear();
-        } else {
-            break;
-        }
+    let start = input.seek(SeekFrom::Current(0))?;
+    let end = input.seek(SeekFrom::End(0))?;
+    let delim = delimiter.byte();
+
+    // scan backwards from the end of the file for the start of the line_count-th line from the
+    // end (a delimiter at the very end of the file terminates the last line rather than starting
+    // a new one)
+    let mut buffer = vec![0; buffer_size];
+    let mut found = 0;
+    let mut pos = end;
+    let mut cut = None;
+    let mut first_block = true;
+    if line_count == 0 {
+        cut = Some(end);
     }
-    if cur_buffer.len() == 0 {
-        if bytes < prev_buffer.len() {
-            output.write_all(&prev_buffer[..prev_buffer.len() - bytes])?
+    while cut.is_none() && pos > start {
+        let len = (pos - start).min(buffer_size as u64) as usize;
+        pos -= len as u64;
+        input.seek(SeekFrom::Start(pos))?;
+        input.read_exact(&mut buffer[..len])?;
+
+        let mut block = &buffer[..len];
+        if first_block {
+            first_block = false;
+            if block.last() == Some(&delim) {
+                block = &block[..len - 1];
+            }
         }
-    } else {
-        if bytes < cur_buffer.len() {
-            output.write_all(prev_buffer)?;
-            output.write_all(&cur_buffer[..cur_buffer.len() - bytes])?;
-        } else {
-            let bytes = bytes - cur_buffer.len();
-            let buf_len = prev_buffer.len();
-            output.write_all(&prev_buffer[..buf_len - bytes.min(buf_len)])?;
+
+        for (i, &byte) in block.iter().enumerate().rev() {
+            if byte == delim {
+                found += 1;
+                if found == line_count {
+                    cut = Some(pos + i as u64 + 1);
+                    break;
+                }
+            }
         }
     }
 
+    if let Some(cut) = cut {
+        input.seek(SeekFrom::Start(start))?;
+        io::copy(&mut input.take(cut - start), &mut output)?;
+    }
+
     Ok(())
 }
 
-// returns the number and whether it is positive
-#[allow(unused_parens)]
+fn write_bytes_backward<W, R>(
+    mut output: W,
+    mut input: R,
+    bytes: usize,
+    buffer_size: usize,
+) -> Result<()>
+where
+    W: Write,
+    R: BufRead,
+{
+    // everything that falls out of the last `bytes` bytes should be printed
+    let mut tail = RingTail::new(bytes as u64, buffer_size);
+    tail.read_from(&mut input, &mut output)?;
+
+    Ok(())
+}
+
+// returns the number and whether it is positive.  clap hands over the value the same way for
+// every spelling (-n-5, -n -5, --lines=-5 and --lines -5), so only a single leading '-' has to be
+// handled here (anything like --5 is rejected rather than taken as -5)
 fn parse_num(s: &str) -> Option<(usize, bool)> {
     let s = s.trim();
-    let positive = (s.chars().next()? != '-');
-    let numstr = if positive {
-        s
+    let (numstr, positive) = if s.starts_with('-') {
+        (&s[1..], false)
     } else {
-        s.trim_left_matches('-')
+        (s, true)
     };
     let num = util::parse_num_with_suffix(numstr)?;
     Some((num, positive))
@@ -360,8 +608,19 @@ fn is_valid_num(val: &OsStr) -> StdResult<(), OsString> {
     }
 }
 
+fn is_valid_size(val: &OsStr) -> StdResult<(), OsString> {
+    match val.to_str().and_then(util::parse_num_with_suffix) {
+        Some(_) => Ok(()),
+        None => Err(OsString::from(format!(
+            "'{}' is not a number or is too large",
+            val.to_string_lossy()
+        ))),
+    }
+}
+
 // checks for the form -num[suffix] where suffix is one of [b, k, kb, m, mb]
 // this form is equivalent to -n num[suffix] (with the suffix translated to the modern style)
+// NOTE: only the first argument is checked, so something like `head -- -5` reads a file named -5
 fn check_obsolete<T, U>(args: &mut T) -> StdResult<(U, usize), Vec<U>>
 where
     T: Iterator<Item = U>,
@@ -0,0 +1,604 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use util::{self, utf8};
+use {ArgsIter, MesaError, Result, UtilRead, UtilSetup, UtilWrite, EXIT_FAILURE};
+
+use clap::Arg;
+use std::ffi::OsStr;
+use std::io::{BufRead, BufReader, Write};
+use std::mem;
+use std::result::Result as StdResult;
+
+pub(crate) const NAME: &str = "iconv";
+pub(crate) const DESCRIPTION: &str = "Convert text from one character encoding to another";
+
+const AFTER_HELP: &str = "
+Both encodings default to UTF-8.  Input in UTF-16 (without an explicit byte order) may start with a
+byte order mark, and is big endian if it doesn't.  Output in UTF-16 is big endian and starts with a
+byte order mark.
+
+By default, conversion stops at the first invalid input sequence or at the first character that
+cannot be represented in the target encoding.  With -c, these are left out of the output instead
+(but iconv still exits with a non-zero status).  --unicode-subst replaces characters that cannot
+be represented with FORMAT, a printf(3) format for the code point like '<U+%04X>'.
+";
+
+const DEFAULT_CHARSET: &str = "UTF-8";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Charset {
+    Utf8,
+    // UTF-16 with its byte order given by a byte order mark
+    Utf16,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+    Ascii,
+}
+
+// the names of each supported encoding (with the preferred name first)
+const CHARSETS: &[(Charset, &[&str])] = &[
+    (Charset::Utf8, &["UTF-8", "UTF8"]),
+    (Charset::Utf16, &["UTF-16", "UTF16"]),
+    (Charset::Utf16Le, &["UTF-16LE", "UTF16LE"]),
+    (Charset::Utf16Be, &["UTF-16BE", "UTF16BE"]),
+    (Charset::Latin1, &["ISO-8859-1", "ISO8859-1", "ISO_8859-1", "LATIN1", "L1"]),
+    (Charset::Ascii, &["ASCII", "US-ASCII", "ANSI_X3.4-1968"]),
+];
+
+impl Charset {
+    fn from_name(name: &str) -> Option<Self> {
+        CHARSETS
+            .iter()
+            .find(|&&(_, names)| names.iter().any(|n| n.eq_ignore_ascii_case(name)))
+            .map(|&(charset, _)| charset)
+    }
+}
+
+#[derive(Debug, Fail)]
+enum IconvError {
+    #[fail(display = "illegal input sequence at position {}", _0)]
+    IllegalInput(u64),
+
+    #[fail(display = "incomplete character at end of input (position {})", _0)]
+    IncompleteInput(u64),
+
+    #[fail(display = "cannot convert U+{:04X} at position {}", _0, _1)]
+    Unconvertible(u32, u64),
+}
+
+/// One decoded piece of the input.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Unit {
+    Char(char),
+    Invalid,
+    // the input ended in the middle of a character
+    Incomplete,
+}
+
+/// Decodes input in `charset` as it is read, keeping track of the position of each character.
+/// Characters that are split between two reads are held back until the rest arrives.
+struct Decoder {
+    charset: Charset,
+    // the position (in bytes) of the next unit
+    position: u64,
+    // the start of an incomplete character from the previous read
+    pending: Vec<u8>,
+    utf8: utf8::Decoder,
+    // the byte order of UTF-16 input (None until the byte order mark has been checked)
+    big_endian: Option<bool>,
+}
+
+impl Decoder {
+    fn new(charset: Charset) -> Self {
+        Self {
+            charset: charset,
+            position: 0,
+            pending: vec![],
+            utf8: utf8::Decoder::new(),
+            big_endian: match charset {
+                Charset::Utf16Le => Some(false),
+                Charset::Utf16Be => Some(true),
+                _ => None,
+            },
+        }
+    }
+
+    /// Decode the next piece of the input, adding the units found (and their positions) to
+    /// `units`.
+    fn decode(&mut self, data: &[u8], units: &mut Vec<(u64, Unit)>) {
+        if self.charset == Charset::Utf8 {
+            let mut position = self.position;
+            self.utf8.decode(data, |chunk| {
+                for c in chunk.valid.chars() {
+                    units.push((position, Unit::Char(c)));
+                    position += c.len_utf8() as u64;
+                }
+                if !chunk.invalid.is_empty() {
+                    units.push((position, Unit::Invalid));
+                    position += chunk.invalid.len() as u64;
+                }
+            });
+            self.position = position;
+            return;
+        }
+
+        let mut buf = mem::replace(&mut self.pending, vec![]);
+        buf.extend_from_slice(data);
+        let used = match self.charset {
+            Charset::Latin1 => {
+                for &byte in &buf {
+                    units.push((self.position, Unit::Char(char::from(byte))));
+                    self.position += 1;
+                }
+                buf.len()
+            }
+            Charset::Ascii => {
+                for &byte in &buf {
+                    let unit = if byte < 0x80 {
+                        Unit::Char(char::from(byte))
+                    } else {
+                        Unit::Invalid
+                    };
+                    units.push((self.position, unit));
+                    self.position += 1;
+                }
+                buf.len()
+            }
+            _ => self.decode_utf16(&buf, units),
+        };
+        buf.drain(..used);
+        self.pending = buf;
+    }
+
+    /// Finish decoding, adding whatever is left of an incomplete character to `units`.
+    fn finish(&mut self, units: &mut Vec<(u64, Unit)>) {
+        let position = self.position;
+        let mut incomplete = !self.pending.is_empty();
+        self.utf8.finish(|_| incomplete = true);
+        if incomplete {
+            units.push((position, Unit::Incomplete));
+        }
+        self.pending.clear();
+    }
+
+    // decode as much of `data` as possible, returning the number of bytes used
+    fn decode_utf16(&mut self, data: &[u8], units: &mut Vec<(u64, Unit)>) -> usize {
+        let mut pos = 0;
+        let big_endian = match self.big_endian {
+            Some(big_endian) => big_endian,
+            None => {
+                if data.len() < 2 {
+                    return 0;
+                }
+                let big_endian = match (data[0], data[1]) {
+                    (0xFE, 0xFF) => {
+                        pos = 2;
+                        true
+                    }
+                    (0xFF, 0xFE) => {
+                        pos = 2;
+                        false
+                    }
+                    _ => true,
+                };
+                self.position += pos as u64;
+                self.big_endian = Some(big_endian);
+                big_endian
+            }
+        };
+        let read_unit = |pos: usize| -> u32 {
+            let (high, low) = if big_endian {
+                (data[pos], data[pos + 1])
+            } else {
+                (data[pos + 1], data[pos])
+            };
+            (u32::from(high) << 8) | u32::from(low)
+        };
+
+        while pos + 2 <= data.len() {
+            let unit = read_unit(pos);
+            let (result, len) = match unit {
+                0xD800...0xDBFF => {
+                    if pos + 4 > data.len() {
+                        // the rest of the surrogate pair hasn't been read yet
+                        break;
+                    }
+                    match read_unit(pos + 2) {
+                        low @ 0xDC00...0xDFFF => {
+                            let value = 0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00);
+                            // this .unwrap() is fine as every surrogate pair is a valid character
+                            (Unit::Char(::std::char::from_u32(value).unwrap()), 4)
+                        }
+                        _ => (Unit::Invalid, 2),
+                    }
+                }
+                0xDC00...0xDFFF => (Unit::Invalid, 2),
+                // this .unwrap() is fine as the surrogates have been handled above
+                _ => (Unit::Char(::std::char::from_u32(unit).unwrap()), 2),
+            };
+            units.push((self.position, result));
+            self.position += len as u64;
+            pos += len;
+        }
+        pos
+    }
+}
+
+/// Encodes characters in `charset`.
+struct Encoder {
+    charset: Charset,
+    started: bool,
+}
+
+impl Encoder {
+    fn new(charset: Charset) -> Self {
+        Self {
+            charset: charset,
+            started: false,
+        }
+    }
+
+    /// Add `c` to `output`, returning false if it cannot be represented.
+    fn encode(&mut self, c: char, output: &mut Vec<u8>) -> bool {
+        match self.charset {
+            Charset::Utf8 => {
+                let mut buf = [0; 4];
+                output.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            }
+            Charset::Utf16 | Charset::Utf16Le | Charset::Utf16Be => {
+                if self.charset == Charset::Utf16 && !self.started {
+                    output.extend_from_slice(&[0xFE, 0xFF]);
+                }
+                let mut buf = [0; 2];
+                for &unit in c.encode_utf16(&mut buf).iter() {
+                    let (high, low) = ((unit >> 8) as u8, unit as u8);
+                    if self.charset == Charset::Utf16Le {
+                        output.extend_from_slice(&[low, high]);
+                    } else {
+                        output.extend_from_slice(&[high, low]);
+                    }
+                }
+            }
+            Charset::Latin1 | Charset::Ascii => {
+                let limit = if self.charset == Charset::Latin1 { 0x100 } else { 0x80 };
+                if c as u32 >= limit {
+                    return false;
+                }
+                output.push(c as u8);
+            }
+        }
+        self.started = true;
+        true
+    }
+}
+
+/// A printf(3) format for a code point, like `<U+%04X>`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct SubstFormat {
+    before: String,
+    zero_pad: bool,
+    width: usize,
+    conversion: char,
+    after: String,
+}
+
+impl SubstFormat {
+    fn parse(format: &str) -> Option<Self> {
+        let mut parts = (String::new(), String::new());
+        let mut conversion = None;
+        let mut chars = format.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                if conversion.is_none() {
+                    parts.0.push(c);
+                } else {
+                    parts.1.push(c);
+                }
+                continue;
+            }
+            if chars.peek() == Some(&'%') {
+                chars.next();
+                if conversion.is_none() {
+                    parts.0.push('%');
+                } else {
+                    parts.1.push('%');
+                }
+                continue;
+            }
+            if conversion.is_some() {
+                // only a single code point is given to the format
+                return None;
+            }
+
+            let zero_pad = chars.peek() == Some(&'0');
+            let mut width = String::new();
+            while let Some(&digit) = chars.peek() {
+                if !digit.is_ascii_digit() {
+                    break;
+                }
+                width.push(digit);
+                chars.next();
+            }
+            let width = if width.is_empty() { 0 } else { width.parse().ok()? };
+            match chars.next() {
+                Some(c @ 'd') | Some(c @ 'u') | Some(c @ 'x') | Some(c @ 'X') | Some(c @ 'o') => {
+                    conversion = Some((zero_pad, width, c))
+                }
+                _ => return None,
+            }
+        }
+
+        let (zero_pad, width, conversion) = conversion?;
+        Some(Self {
+            before: parts.0,
+            zero_pad: zero_pad,
+            width: width,
+            conversion: conversion,
+            after: parts.1,
+        })
+    }
+
+    fn format(&self, value: u32) -> String {
+        let digits = match self.conversion {
+            'x' => format!("{:x}", value),
+            'X' => format!("{:X}", value),
+            'o' => format!("{:o}", value),
+            _ => value.to_string(),
+        };
+        let pad = if self.zero_pad { '0' } else { ' ' };
+        let padding: String = (digits.len()..self.width).map(|_| pad).collect();
+        format!("{}{}{}{}", self.before, padding, digits, self.after)
+    }
+}
+
+struct Converter {
+    from: Charset,
+    encoder: Encoder,
+    // whether to leave out invalid input and characters that cannot be converted (-c)
+    discard: bool,
+    subst: Option<SubstFormat>,
+    // whether anything has been left out of the output
+    omitted: bool,
+}
+
+impl Converter {
+    fn convert<R: BufRead, W: Write>(&mut self, mut input: R, output: &mut W) -> Result<()> {
+        let mut decoder = Decoder::new(self.from);
+        let mut units = vec![];
+        let mut converted = vec![];
+        loop {
+            let len = {
+                let data = input.fill_buf()?;
+                decoder.decode(data, &mut units);
+                data.len()
+            };
+            if len == 0 {
+                decoder.finish(&mut units);
+            } else {
+                input.consume(len);
+            }
+
+            // everything before an error is still written out
+            let res = self.convert_units(&units, &mut converted);
+            output.write_all(&converted)?;
+            units.clear();
+            converted.clear();
+            res?;
+
+            if len == 0 {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn convert_units(&mut self, units: &[(u64, Unit)], output: &mut Vec<u8>) -> Result<()> {
+        for &(position, unit) in units {
+            match unit {
+                Unit::Char(c) => {
+                    if !self.encoder.encode(c, output) {
+                        self.convert_unrepresentable(c, position, output)?;
+                    }
+                }
+                _ if self.discard => self.omitted = true,
+                Unit::Invalid => return Err(IconvError::IllegalInput(position).into()),
+                Unit::Incomplete => return Err(IconvError::IncompleteInput(position).into()),
+            }
+        }
+        Ok(())
+    }
+
+    fn convert_unrepresentable(&mut self, c: char, position: u64, output: &mut Vec<u8>) -> Result<()> {
+        if let Some(ref subst) = self.subst {
+            let mut replacement = vec![];
+            let mut valid = true;
+            for sc in subst.format(c as u32).chars() {
+                valid = valid && self.encoder.encode(sc, &mut replacement);
+            }
+            if valid {
+                output.extend_from_slice(&replacement);
+                return Ok(());
+            }
+        }
+
+        if self.discard {
+            self.omitted = true;
+            Ok(())
+        } else {
+            Err(IconvError::Unconvertible(c as u32, position).into())
+        }
+    }
+}
+
+pub fn execute<S, T>(setup: &mut S, args: T) -> Result<()>
+where
+    S: UtilSetup,
+    T: ArgsIter,
+{
+    let matches = {
+        let app = util_app!(NAME)
+                    .after_help(AFTER_HELP)
+                    .arg(Arg::with_name("from-code")
+                            .short("f")
+                            .long("from-code")
+                            .takes_value(true)
+                            .value_name("FROM")
+                            .default_value(DEFAULT_CHARSET)
+                            .validator(is_valid_charset)
+                            .help("Convert from the encoding FROM"))
+                    .arg(Arg::with_name("to-code")
+                            .short("t")
+                            .long("to-code")
+                            .takes_value(true)
+                            .value_name("TO")
+                            .default_value(DEFAULT_CHARSET)
+                            .validator(is_valid_charset)
+                            .help("Convert to the encoding TO"))
+                    .arg(Arg::with_name("discard")
+                            .short("c")
+                            .help("Leave invalid input and characters that cannot be converted out of the output"))
+                    .arg(Arg::with_name("unicode-subst")
+                            .long("unicode-subst")
+                            .takes_value(true)
+                            .value_name("FORMAT")
+                            .validator(is_valid_subst)
+                            .help("Replace characters that cannot be converted with FORMAT"))
+                    .arg(Arg::with_name("list")
+                            .short("l")
+                            .long("list")
+                            .help("List the supported encodings"))
+                    .arg(Arg::with_name("FILES")
+                            .index(1)
+                            .multiple(true));
+
+        app.get_matches_from_safe(args)?
+    };
+
+    let current_dir = setup.current_dir().map(|p| p.to_owned());
+    let (input, output, error) = setup.stdio();
+    let mut output = output.lock()?;
+
+    if matches.is_present("list") {
+        for &(_, names) in CHARSETS {
+            writeln!(output, "{}", names.join(" "))?;
+        }
+        return Ok(());
+    }
+
+    // these .unwrap()s are fine because of the validators and default values
+    let mut converter = Converter {
+        from: Charset::from_name(matches.value_of("from-code").unwrap()).unwrap(),
+        encoder: Encoder::new(Charset::from_name(matches.value_of("to-code").unwrap()).unwrap()),
+        discard: matches.is_present("discard"),
+        subst: matches
+            .value_of("unicode-subst")
+            .map(|format| SubstFormat::parse(format).unwrap()),
+        omitted: false,
+    };
+
+    let mut err_stream = error.lock()?;
+
+    // reading from stdin without any operands is handled exactly like an operand of "-"
+    let files: Vec<&OsStr> = match matches.values_of_os("FILES") {
+        Some(values) => values.collect(),
+        None => vec![OsStr::new("-")],
+    };
+
+    let mut result = Ok(());
+    for &file in &files {
+        // unlike files that can't be opened, conversion errors stop iconv entirely
+        if file == OsStr::new("-") {
+            converter.convert(input.lock()?, &mut output)?;
+        } else {
+            match util::open_file(&current_dir, file) {
+                Ok(file) => converter.convert(BufReader::new(file), &mut output)?,
+                Err(mut e) => {
+                    display_msg!(err_stream, "{}", e)?;
+                    e.err = None;
+                    result = Err(e);
+                }
+            }
+        }
+    }
+    output.flush()?;
+
+    if converter.omitted && result.is_ok() {
+        result = Err(MesaError::new(None, EXIT_FAILURE, None));
+    }
+    result
+}
+
+fn is_valid_charset(val: String) -> StdResult<(), String> {
+    match Charset::from_name(&val) {
+        Some(_) => Ok(()),
+        None => Err(format!("unsupported encoding '{}' (see --list)", val)),
+    }
+}
+
+fn is_valid_subst(val: String) -> StdResult<(), String> {
+    match SubstFormat::parse(&val) {
+        Some(_) => Ok(()),
+        None => Err(format!("'{}' is not a format for a single code point", val)),
+    }
+}
+
+#[cfg(test)]
+fn decode_pieces(charset: Charset, pieces: &[&[u8]]) -> Vec<(u64, Unit)> {
+    let mut decoder = Decoder::new(charset);
+    let mut units = vec![];
+    for piece in pieces {
+        decoder.decode(piece, &mut units);
+    }
+    decoder.finish(&mut units);
+    units
+}
+
+#[test]
+fn iconv_utf16_split() {
+    let data = b"\xff\xfea\x00=\xd8\x00\xdez\x00";
+    let expected = vec![
+        (2, Unit::Char('a')),
+        (4, Unit::Char('\u{1f600}')),
+        (8, Unit::Char('z')),
+    ];
+    // split the data at every possible position (including in the middle of characters)
+    for i in 0..data.len() + 1 {
+        let units = decode_pieces(Charset::Utf16, &[&data[..i], &data[i..]]);
+        assert_eq!(units, expected, "split at {}", i);
+    }
+
+    assert_eq!(decode_pieces(Charset::Utf16Be, &[b"\x00a\xdc\x00"])[1], (2, Unit::Invalid));
+    assert_eq!(decode_pieces(Charset::Utf16Be, &[b"\x00a\xd8"])[1], (2, Unit::Incomplete));
+}
+
+#[test]
+fn iconv_encode() {
+    let mut output = vec![];
+    let mut encoder = Encoder::new(Charset::Utf16);
+    assert!(encoder.encode('a', &mut output));
+    assert!(encoder.encode('\u{1f600}', &mut output));
+    assert_eq!(output, b"\xfe\xff\x00a\xd8\x3d\xde\x00");
+
+    let mut encoder = Encoder::new(Charset::Latin1);
+    output.clear();
+    assert!(encoder.encode('é', &mut output));
+    assert!(!encoder.encode('€', &mut output));
+    assert_eq!(output, b"\xe9");
+}
+
+#[test]
+fn iconv_subst_format() {
+    assert_eq!(SubstFormat::parse("<U+%04X>").unwrap().format(0xe9), "<U+00E9>");
+    assert_eq!(SubstFormat::parse("%%%5d%%").unwrap().format(233), "%  233%");
+    assert!(SubstFormat::parse("%x %x").is_none());
+    assert!(SubstFormat::parse("%s").is_none());
+    assert!(SubstFormat::parse("none").is_none());
+}
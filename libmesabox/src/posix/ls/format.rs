@@ -0,0 +1,145 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use util::userdb;
+
+use std::fs::Metadata;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+const HUMAN_UNITS: [char; 8] = ['K', 'M', 'G', 'T', 'P', 'E', 'Z', 'Y'];
+
+/// Create a string like "drwxr-xr-x" describing the type and permissions of a file.
+pub fn mode_string(metadata: &Metadata) -> String {
+    let file_type = metadata.file_type();
+    let type_char = if file_type.is_dir() {
+        'd'
+    } else if file_type.is_symlink() {
+        'l'
+    } else if file_type.is_block_device() {
+        'b'
+    } else if file_type.is_char_device() {
+        'c'
+    } else if file_type.is_fifo() {
+        'p'
+    } else if file_type.is_socket() {
+        's'
+    } else {
+        '-'
+    };
+
+    let mode = metadata.permissions().mode();
+    let mut result = String::with_capacity(10);
+    result.push(type_char);
+
+    // (read bit, write bit, execute bit, special bit, special char)
+    let triples = [
+        (0o400, 0o200, 0o100, 0o4000, 's'),
+        (0o040, 0o020, 0o010, 0o2000, 's'),
+        (0o004, 0o002, 0o001, 0o1000, 't'),
+    ];
+    for &(read, write, exec, special, special_char) in &triples {
+        result.push(if mode & read != 0 { 'r' } else { '-' });
+        result.push(if mode & write != 0 { 'w' } else { '-' });
+        result.push(match (mode & exec != 0, mode & special != 0) {
+            (true, true) => special_char,
+            (false, true) => special_char.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+
+    result
+}
+
+/// Format a size like GNU's `-h` (_e.g._ 1.5K, 23M), always rounding up.
+pub fn human_size(size: u64) -> String {
+    if size < 1024 {
+        return size.to_string();
+    }
+
+    let mut value = size as f64;
+    let mut unit = 0;
+    loop {
+        value /= 1024.0;
+        if value < 1024.0 || unit == HUMAN_UNITS.len() - 1 {
+            break;
+        }
+        unit += 1;
+    }
+
+    if value < 10.0 {
+        let rounded = (value * 10.0).ceil() / 10.0;
+        if rounded < 10.0 {
+            return format!("{:.1}{}", rounded, HUMAN_UNITS[unit]);
+        }
+    }
+    format!("{}{}", value.ceil(), HUMAN_UNITS[unit])
+}
+
+/// Look up the name of the user with ID `uid`.
+pub fn user_name(uid: u32) -> Option<String> {
+    userdb::user_by_uid(uid).map(|user| user.name.to_string_lossy().into_owned())
+}
+
+/// Look up the name of the group with ID `gid`.
+pub fn group_name(gid: u32) -> Option<String> {
+    userdb::group_by_gid(gid).map(|group| group.name.to_string_lossy().into_owned())
+}
+
+/// Determine the widths of each column needed to lay out items with the given widths in as many
+/// columns as will fit in `line_width` (filling each column before moving on to the next, like
+/// GNU ls).  Returns the number of rows along with the column widths.
+pub fn column_layout(widths: &[usize], line_width: usize) -> (usize, Vec<usize>) {
+    const SEPARATOR: usize = 2;
+
+    if widths.is_empty() {
+        return (0, vec![]);
+    }
+
+    for cols in (1..widths.len() + 1).rev() {
+        let rows = (widths.len() + cols - 1) / cols;
+        // skip layouts that would leave entire columns empty
+        if (cols - 1) * rows >= widths.len() {
+            continue;
+        }
+
+        let col_widths: Vec<usize> = widths
+            .chunks(rows)
+            .map(|column| column.iter().cloned().max().unwrap_or(0))
+            .collect();
+        let total = col_widths.iter().sum::<usize>() + SEPARATOR * (col_widths.len() - 1);
+        if total <= line_width || cols == 1 {
+            let col_widths = col_widths.into_iter().map(|w| w + SEPARATOR).collect();
+            return (rows, col_widths);
+        }
+    }
+
+    unreachable!()
+}
+
+#[test]
+fn human_size_rounding() {
+    assert_eq!(human_size(0), "0");
+    assert_eq!(human_size(1023), "1023");
+    assert_eq!(human_size(1024), "1.0K");
+    assert_eq!(human_size(1025), "1.1K");
+    assert_eq!(human_size(1536), "1.5K");
+    assert_eq!(human_size(10 * 1024), "10K");
+    assert_eq!(human_size(10 * 1024 - 1), "10K");
+    assert_eq!(human_size(5 * 1024 * 1024), "5.0M");
+}
+
+#[test]
+fn column_layout_fit() {
+    // everything fits on a single line
+    assert_eq!(column_layout(&[3, 3, 3], 80), (1, vec![5, 5, 5]));
+    // 4 + 2 + 4 + 2 + 4 = 16 > 15, so use two rows instead
+    assert_eq!(column_layout(&[4, 4, 4, 4], 15), (2, vec![6, 6]));
+    // too wide for anything but a single column
+    assert_eq!(column_layout(&[50, 50], 80), (2, vec![52]));
+}
@@ -0,0 +1,636 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use util::color::{self, ColorWhen, Colorizer, LsColors};
+use util::timefmt::{self, TimeStyle};
+use util::vercmp;
+use util;
+use {ArgsIter, MesaError, PathError, PathOperation, Result, UtilSetup, UtilWrite};
+
+use clap::{AppSettings, Arg};
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, Metadata};
+use std::io::{self, Write};
+use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+mod format;
+
+pub(crate) const NAME: &str = "ls";
+pub(crate) const DESCRIPTION: &str = "List information about files";
+
+// these match the exit codes used by GNU ls
+const EXIT_MINOR: i32 = 1;
+const EXIT_SERIOUS: i32 = 2;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Hidden {
+    Ignore,
+    AlmostAll,
+    All,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Format {
+    Long,
+    SingleColumn,
+    Columns,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Sort {
+    Name,
+    Time,
+    Size,
+    Version,
+}
+
+struct Options {
+    hidden: Hidden,
+    format: Format,
+    sort: Sort,
+    reverse: bool,
+    recursive: bool,
+    directory: bool,
+    human: bool,
+    classify: bool,
+    // whether symlinks given on the command line should be followed
+    dereference_args: bool,
+    width: usize,
+    colorizer: Colorizer,
+    colors: LsColors,
+    time_style: TimeStyle,
+    current_dir: Option<PathBuf>,
+}
+
+struct Entry {
+    /// The name to display (the operand itself for command-line arguments)
+    name: OsString,
+    /// The path used to access the file
+    path: PathBuf,
+    metadata: Metadata,
+}
+
+pub fn execute<S, T>(setup: &mut S, args: T) -> Result<()>
+where
+    S: UtilSetup,
+    T: ArgsIter,
+{
+    let matches = {
+        let app = util_app!(NAME)
+                    .setting(AppSettings::DeriveDisplayOrder)
+                    .arg(Arg::with_name("all")
+                            .short("a")
+                            .long("all")
+                            .overrides_with("almost-all")
+                            .help("Do not ignore entries starting with ."))
+                    .arg(Arg::with_name("almost-all")
+                            .short("A")
+                            .long("almost-all")
+                            .overrides_with("all")
+                            .help("Do not list implied . and .."))
+                    .arg(Arg::with_name("directory")
+                            .short("d")
+                            .long("directory")
+                            .help("List directories themselves, not their contents"))
+                    .arg(Arg::with_name("classify")
+                            .short("F")
+                            .long("classify")
+                            .help("Append an indicator (one of */=@|) to entries"))
+                    .arg(Arg::with_name("human-readable")
+                            .short("h")
+                            .long("human-readable")
+                            .help("With -l, print sizes like 1K, 234M, 2G, etc."))
+                    .arg(Arg::with_name("long")
+                            .short("l")
+                            .overrides_with("one")
+                            .help("Use a long listing format"))
+                    .arg(Arg::with_name("one")
+                            .short("1")
+                            .overrides_with("long")
+                            .help("List one file per line"))
+                    .arg(Arg::with_name("reverse")
+                            .short("r")
+                            .long("reverse")
+                            .help("Reverse order while sorting"))
+                    .arg(Arg::with_name("recursive")
+                            .short("R")
+                            .long("recursive")
+                            .help("List subdirectories recursively"))
+                    .arg(Arg::with_name("size-sort")
+                            .short("S")
+                            .overrides_with_all(&["time-sort", "version-sort"])
+                            .help("Sort by file size, largest first"))
+                    .arg(Arg::with_name("time-sort")
+                            .short("t")
+                            .overrides_with_all(&["size-sort", "version-sort"])
+                            .help("Sort by modification time, newest first"))
+                    .arg(Arg::with_name("version-sort")
+                            .short("v")
+                            .overrides_with_all(&["size-sort", "time-sort"])
+                            .help("Sort by name, treating numbers within names as version numbers (so a2 comes before a10)"))
+                    .arg(color::color_arg())
+                    .arg(timefmt::time_style_arg())
+                    .arg(Arg::with_name("FILES")
+                            .index(1)
+                            .multiple(true));
+
+        app.get_matches_from_safe(args)?
+    };
+
+    let hidden = if matches.is_present("all") {
+        Hidden::All
+    } else if matches.is_present("almost-all") {
+        Hidden::AlmostAll
+    } else {
+        Hidden::Ignore
+    };
+
+    let sort = if matches.is_present("time-sort") {
+        Sort::Time
+    } else if matches.is_present("size-sort") {
+        Sort::Size
+    } else if matches.is_present("version-sort") {
+        Sort::Version
+    } else {
+        Sort::Name
+    };
+
+    let stdout_obj = setup.output().raw_object();
+    let stdout_tty = util::is_tty(stdout_obj);
+
+    let format = if matches.is_present("long") {
+        Format::Long
+    } else if matches.is_present("one") || !stdout_tty {
+        Format::SingleColumn
+    } else {
+        Format::Columns
+    };
+
+    let color_when = ColorWhen::from_matches(&matches, ColorWhen::Never);
+    let colorizer = Colorizer::new(color_when.enabled(stdout_obj, setup.env().get("NO_COLOR")));
+
+    let directory = matches.is_present("directory");
+    let classify = matches.is_present("classify");
+
+    let options = Options {
+        hidden: hidden,
+        format: format,
+        sort: sort,
+        reverse: matches.is_present("reverse"),
+        recursive: matches.is_present("recursive") && !directory,
+        directory: directory,
+        human: matches.is_present("human-readable"),
+        classify: classify,
+        dereference_args: !(directory || classify || format == Format::Long),
+        width: util::terminal_width(setup.env(), stdout_obj),
+        colorizer: colorizer,
+        colors: LsColors::from_env(setup.env()),
+        time_style: TimeStyle::from_matches(&matches, setup.env().get("TIME_STYLE"))?,
+        current_dir: setup.current_dir().map(|p| p.to_owned()),
+    };
+
+    let operands: Vec<&OsStr> = match matches.values_of_os("FILES") {
+        Some(values) => values.collect(),
+        None => vec![OsStr::new(".")],
+    };
+
+    let (_, stdout, stderr) = setup.stdio();
+    let mut lister = Lister {
+        stdout: stdout.lock()?,
+        stderr: stderr.lock()?,
+        options: &options,
+        exitcode: 0,
+        printed: false,
+        users: HashMap::new(),
+        groups: HashMap::new(),
+        now: now_secs(),
+    };
+
+    lister.list_operands(&operands)?;
+
+    if lister.exitcode == 0 {
+        Ok(())
+    } else {
+        Err(MesaError::new(None, lister.exitcode, None))
+    }
+}
+
+struct Lister<'a, O, E>
+where
+    O: Write,
+    E: Write,
+{
+    stdout: O,
+    stderr: E,
+    options: &'a Options,
+    exitcode: i32,
+    // whether anything has been printed yet (to know when to separate directories)
+    printed: bool,
+    users: HashMap<u32, String>,
+    groups: HashMap<u32, String>,
+    now: i64,
+}
+
+impl<'a, O, E> Lister<'a, O, E>
+where
+    O: Write,
+    E: Write,
+{
+    fn list_operands(&mut self, operands: &[&OsStr]) -> Result<()> {
+        let mut files = vec![];
+        let mut dirs = vec![];
+
+        for &operand in operands {
+            let path = util::actual_path(&self.options.current_dir, operand).into_owned();
+            let metadata = match self.operand_metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    let err = PathError::new(PathOperation::Access, operand, e);
+                    display_msg!(self.stderr, "{}", err)?;
+                    self.exitcode = EXIT_SERIOUS;
+                    continue;
+                }
+            };
+
+            let entry = Entry {
+                name: operand.to_owned(),
+                path: path,
+                metadata: metadata,
+            };
+            if entry.metadata.is_dir() && !self.options.directory {
+                dirs.push(entry);
+            } else {
+                files.push(entry);
+            }
+        }
+
+        self.sort(&mut files);
+        self.sort(&mut dirs);
+
+        if !files.is_empty() {
+            self.print_entries(&files, false)?;
+            self.printed = true;
+        }
+
+        let show_header = operands.len() > 1 || self.options.recursive;
+        for dir in dirs {
+            self.list_dir(&dir.name, &dir.path, show_header, true)?;
+        }
+
+        Ok(())
+    }
+
+    fn operand_metadata(&self, path: &Path) -> io::Result<Metadata> {
+        let metadata = fs::symlink_metadata(path)?;
+        if self.options.dereference_args && metadata.file_type().is_symlink() {
+            // a dangling symlink is still listed (as the symlink itself)
+            Ok(fs::metadata(path).unwrap_or(metadata))
+        } else {
+            Ok(metadata)
+        }
+    }
+
+    fn list_dir(
+        &mut self,
+        name: &OsStr,
+        path: &Path,
+        show_header: bool,
+        top_level: bool,
+    ) -> Result<()> {
+        if show_header {
+            if self.printed {
+                writeln!(self.stdout)?;
+            }
+            writeln!(self.stdout, "{}:", Path::new(name).display())?;
+        }
+        self.printed = true;
+
+        let mut entries = match self.read_dir(name, path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                display_msg!(self.stderr, "{}", e)?;
+                // only directories given on the command line are considered serious
+                let code = if top_level { EXIT_SERIOUS } else { EXIT_MINOR };
+                self.exitcode = self.exitcode.max(code);
+                return Ok(());
+            }
+        };
+        self.sort(&mut entries);
+
+        self.print_entries(&entries, true)?;
+
+        if self.options.recursive {
+            for entry in &entries {
+                let is_dot = entry.name == OsStr::new(".") || entry.name == OsStr::new("..");
+                if entry.metadata.is_dir() && !is_dot {
+                    let subname = Path::new(name).join(&entry.name).into_os_string();
+                    self.list_dir(&subname, &entry.path, true, false)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_dir(&mut self, name: &OsStr, path: &Path) -> StdResultPath<Vec<Entry>> {
+        let access_err = |e| PathError::new(PathOperation::OpenDir, name, e);
+
+        let mut entries = vec![];
+        if self.options.hidden == Hidden::All {
+            for dot in &[".", ".."] {
+                let dot_path = path.join(dot);
+                let metadata = fs::symlink_metadata(&dot_path).map_err(&access_err)?;
+                entries.push(Entry {
+                    name: OsString::from(dot),
+                    path: dot_path,
+                    metadata: metadata,
+                });
+            }
+        }
+
+        for dir_entry in fs::read_dir(path).map_err(&access_err)? {
+            let dir_entry = dir_entry.map_err(&access_err)?;
+            let entry_name = dir_entry.file_name();
+            if self.options.hidden == Hidden::Ignore
+                && util::os_str_as_bytes(&entry_name).first() == Some(&b'.')
+            {
+                continue;
+            }
+
+            let entry_path = dir_entry.path();
+            match fs::symlink_metadata(&entry_path) {
+                Ok(metadata) => entries.push(Entry {
+                    name: entry_name,
+                    path: entry_path,
+                    metadata: metadata,
+                }),
+                Err(e) => {
+                    let display = Path::new(name).join(&entry_name);
+                    let err = PathError::new(PathOperation::Access, display, e);
+                    // failing to access a single entry is not fatal
+                    let _ = display_msg!(self.stderr, "{}", err);
+                    self.exitcode = self.exitcode.max(EXIT_MINOR);
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    fn sort(&self, entries: &mut [Entry]) {
+        let by_name = |a: &Entry, b: &Entry| {
+            util::os_str_as_bytes(&a.name).cmp(&util::os_str_as_bytes(&b.name))
+        };
+
+        match self.options.sort {
+            Sort::Name => entries.sort_by(by_name),
+            Sort::Time => entries.sort_by(|a, b| {
+                let a_time = (a.metadata.mtime(), a.metadata.mtime_nsec());
+                let b_time = (b.metadata.mtime(), b.metadata.mtime_nsec());
+                b_time.cmp(&a_time).then_with(|| by_name(a, b))
+            }),
+            Sort::Size => entries.sort_by(|a, b| {
+                b.metadata
+                    .len()
+                    .cmp(&a.metadata.len())
+                    .then_with(|| by_name(a, b))
+            }),
+            Sort::Version => entries.sort_by(|a, b| {
+                let a_name = util::os_str_as_bytes(&a.name);
+                let b_name = util::os_str_as_bytes(&b.name);
+                vercmp::filevercmp(&a_name, &b_name).then_with(|| by_name(a, b))
+            }),
+        }
+
+        if self.options.reverse {
+            entries.reverse();
+        }
+    }
+
+    fn print_entries(&mut self, entries: &[Entry], show_total: bool) -> Result<()> {
+        match self.options.format {
+            Format::Long => self.print_long(entries, show_total),
+            Format::SingleColumn => {
+                for entry in entries {
+                    let name = self.display_name(entry);
+                    writeln!(self.stdout, "{}", name.text)?;
+                }
+                Ok(())
+            }
+            Format::Columns => self.print_columns(entries),
+        }
+    }
+
+    fn print_columns(&mut self, entries: &[Entry]) -> Result<()> {
+        let names: Vec<DisplayName> = entries.iter().map(|e| self.display_name(e)).collect();
+        let widths: Vec<usize> = names.iter().map(|n| n.width).collect();
+        let (rows, col_widths) = format::column_layout(&widths, self.options.width);
+
+        for row in 0..rows {
+            let mut col = 0;
+            let mut idx = row;
+            while idx < names.len() {
+                let name = &names[idx];
+                let next = idx + rows;
+                if next < names.len() {
+                    let padding = col_widths[col] - name.width;
+                    write!(self.stdout, "{}{:2$}", name.text, "", padding)?;
+                } else {
+                    write!(self.stdout, "{}", name.text)?;
+                }
+                idx = next;
+                col += 1;
+            }
+            writeln!(self.stdout)?;
+        }
+
+        Ok(())
+    }
+
+    fn print_long(&mut self, entries: &[Entry], show_total: bool) -> Result<()> {
+        if show_total {
+            let blocks: u64 = entries.iter().map(|e| e.metadata.blocks()).sum();
+            let total = if self.options.human {
+                format::human_size(blocks * 512)
+            } else {
+                ((blocks + 1) / 2).to_string()
+            };
+            writeln!(self.stdout, "total {}", total)?;
+        }
+
+        let rows: Vec<LongRow> = entries.iter().map(|e| self.long_row(e)).collect();
+
+        let link_width = rows.iter().map(|r| r.links.len()).max().unwrap_or(0);
+        let owner_width = rows.iter().map(|r| r.owner.len()).max().unwrap_or(0);
+        let group_width = rows.iter().map(|r| r.group.len()).max().unwrap_or(0);
+        let size_width = rows.iter().map(|r| r.size.len()).max().unwrap_or(0);
+
+        for row in rows {
+            writeln!(
+                self.stdout,
+                "{} {:>lw$} {:<ow$} {:<gw$} {:>sw$} {} {}",
+                row.mode,
+                row.links,
+                row.owner,
+                row.group,
+                row.size,
+                row.time,
+                row.name,
+                lw = link_width,
+                ow = owner_width,
+                gw = group_width,
+                sw = size_width
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn long_row(&mut self, entry: &Entry) -> LongRow {
+        let metadata = &entry.metadata;
+        let file_type = metadata.file_type();
+
+        let size = if file_type.is_block_device() || file_type.is_char_device() {
+            let (major, minor) = util::device_numbers(metadata.rdev());
+            format!("{}, {}", major, minor)
+        } else if self.options.human {
+            format::human_size(metadata.len())
+        } else {
+            metadata.len().to_string()
+        };
+
+        let mut name = self.display_name(entry).text;
+        if file_type.is_symlink() {
+            if let Ok(target) = fs::read_link(&entry.path) {
+                name.push_str(" -> ");
+                name.push_str(&target.to_string_lossy());
+            }
+        }
+
+        let uid = metadata.uid();
+        let gid = metadata.gid();
+        LongRow {
+            mode: format::mode_string(metadata),
+            links: metadata.nlink().to_string(),
+            owner: self.users
+                .entry(uid)
+                .or_insert_with(|| format::user_name(uid).unwrap_or_else(|| uid.to_string()))
+                .clone(),
+            group: self.groups
+                .entry(gid)
+                .or_insert_with(|| format::group_name(gid).unwrap_or_else(|| gid.to_string()))
+                .clone(),
+            size: size,
+            time: self.options
+                .time_style
+                .format(metadata.mtime(), metadata.mtime_nsec() as u32, self.now),
+            name: name,
+        }
+    }
+
+    fn display_name(&self, entry: &Entry) -> DisplayName {
+        let name = entry.name.to_string_lossy();
+        let mut width = name.chars().count();
+
+        let sgr = self.color_for(entry, &name);
+        let mut text = self.options.colorizer.paint(sgr, &name).into_owned();
+
+        // symlinks are followed in the long format, so they are not marked there
+        let is_long = self.options.format == Format::Long;
+        if self.options.classify && !(is_long && entry.metadata.file_type().is_symlink()) {
+            if let Some(indicator) = indicator(&entry.metadata) {
+                text.push(indicator);
+                width += 1;
+            }
+        }
+
+        DisplayName {
+            text: text,
+            width: width,
+        }
+    }
+
+    fn color_for(&self, entry: &Entry, name: &str) -> &str {
+        if !self.options.colorizer.is_enabled() {
+            return "";
+        }
+
+        let colors = &self.options.colors;
+        let file_type = entry.metadata.file_type();
+        let type_key = if file_type.is_dir() {
+            Some("di")
+        } else if file_type.is_symlink() {
+            if fs::metadata(&entry.path).is_ok() {
+                Some("ln")
+            } else {
+                Some("or")
+            }
+        } else if file_type.is_fifo() {
+            Some("pi")
+        } else if file_type.is_socket() {
+            Some("so")
+        } else if file_type.is_block_device() {
+            Some("bd")
+        } else if file_type.is_char_device() {
+            Some("cd")
+        } else if entry.metadata.permissions().mode() & 0o111 != 0 {
+            Some("ex")
+        } else {
+            None
+        };
+
+        type_key
+            .and_then(|key| colors.for_type(key))
+            .or_else(|| colors.for_name(name))
+            .unwrap_or("")
+    }
+}
+
+type StdResultPath<T> = ::std::result::Result<T, PathError>;
+
+struct DisplayName {
+    text: String,
+    width: usize,
+}
+
+struct LongRow {
+    mode: String,
+    links: String,
+    owner: String,
+    group: String,
+    size: String,
+    time: String,
+    name: String,
+}
+
+fn indicator(metadata: &Metadata) -> Option<char> {
+    let file_type = metadata.file_type();
+    if file_type.is_dir() {
+        Some('/')
+    } else if file_type.is_symlink() {
+        Some('@')
+    } else if file_type.is_fifo() {
+        Some('|')
+    } else if file_type.is_socket() {
+        Some('=')
+    } else if metadata.permissions().mode() & 0o111 != 0 {
+        Some('*')
+    } else {
+        None
+    }
+}
+
+fn now_secs() -> i64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(_) => 0,
+    }
+}
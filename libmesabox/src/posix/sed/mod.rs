@@ -0,0 +1,547 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use util::inplace::InPlace;
+use util::{self, LineDelimiter};
+use {ArgsIter, MesaError, PathError, PathOperation, Result, UtilRead, UtilSetup, UtilWrite};
+
+use clap::{AppSettings, Arg, ArgMatches};
+use std::collections::VecDeque;
+use std::ffi::{OsStr, OsString};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use self::script::{Command, CommandKind};
+
+mod script;
+
+pub(crate) const NAME: &str = "sed";
+pub(crate) const DESCRIPTION: &str = "Filter and transform text";
+
+// like GNU sed, use a separate exit code for input files that cannot be read
+const EXIT_BAD_INPUT: i32 = 2;
+const EXIT_BAD_EDIT: i32 = 4;
+
+#[derive(Debug, Fail)]
+#[fail(display = "no script specified")]
+struct NoScriptError;
+
+struct Options {
+    quiet: bool,
+    // whether each file should be treated separately rather than as one long stream
+    separate: bool,
+    // Some(suffix) when editing in place, where the suffix (if any) names backup files
+    in_place: Option<Option<OsString>>,
+    delimiter: LineDelimiter,
+    current_dir: Option<PathBuf>,
+}
+
+pub fn execute<S, T>(setup: &mut S, args: T) -> Result<()>
+where
+    S: UtilSetup,
+    T: ArgsIter,
+{
+    let (args, suffix) = split_in_place_suffix(args);
+
+    let matches = {
+        let app = util_app!(NAME)
+                    .setting(AppSettings::DeriveDisplayOrder)
+                    .arg(Arg::with_name("quiet")
+                            .short("n")
+                            .long("quiet")
+                            .alias("silent")
+                            .help("Suppress automatic printing of pattern space"))
+                    .arg(Arg::with_name("expression")
+                            .short("e")
+                            .long("expression")
+                            .takes_value(true)
+                            .value_name("SCRIPT")
+                            .multiple(true)
+                            .number_of_values(1)
+                            .allow_hyphen_values(true)
+                            .help("Add SCRIPT to the commands to be executed"))
+                    .arg(Arg::with_name("file")
+                            .short("f")
+                            .long("file")
+                            .takes_value(true)
+                            .value_name("SCRIPT_FILE")
+                            .multiple(true)
+                            .number_of_values(1)
+                            .help("Add the contents of SCRIPT_FILE to the commands to be executed"))
+                    .arg(Arg::with_name("in-place")
+                            .short("i")
+                            .long("in-place")
+                            .help("Edit files in place (making a backup if -iSUFFIX or --in-place=SUFFIX is given)"))
+                    .arg(Arg::with_name("regexp-extended")
+                            .short("E")
+                            .long("regexp-extended")
+                            .visible_short_alias('r')
+                            .help("Use extended regular expressions in the script"))
+                    .arg(Arg::with_name("separate")
+                            .short("s")
+                            .long("separate")
+                            .help("Consider files as separate rather than as a single continuous long stream"))
+                    .arg(util::zero_terminated_arg()
+                            .alias("null-data"))
+                    .arg(Arg::with_name("ARGS")
+                            .value_name("SCRIPT] [FILE")
+                            .index(1)
+                            .multiple(true));
+
+        app.get_matches_from_safe(args)?
+    };
+
+    let current_dir = setup.current_dir().map(|p| p.to_owned());
+
+    let mut operands: Vec<&OsStr> = matches
+        .values_of_os("ARGS")
+        .map(|values| values.collect())
+        .unwrap_or_default();
+
+    let script = collect_script(&matches, &current_dir, &mut operands)?;
+    let commands = script::parse(&script, matches.is_present("regexp-extended"))?;
+
+    let in_place = if matches.is_present("in-place") {
+        Some(suffix)
+    } else {
+        None
+    };
+    let options = Options {
+        quiet: matches.is_present("quiet"),
+        separate: in_place.is_some() || matches.is_present("separate"),
+        in_place: in_place,
+        delimiter: LineDelimiter::from_matches(&matches),
+        current_dir: current_dir,
+    };
+
+    if operands.is_empty() {
+        operands.push(OsStr::new("-"));
+    }
+
+    let (input, stdout, stderr) = setup.stdio();
+    let mut sed = Sed {
+        stderr: stderr.lock()?,
+        commands: commands,
+        options: &options,
+        quit: false,
+        writer: LineWriter {
+            delimiter: options.delimiter,
+            missing_delimiter: false,
+        },
+        exitcode: 0,
+    };
+
+    if options.in_place.is_some() {
+        for operand in operands {
+            sed.edit_in_place(operand)?;
+            if sed.quit {
+                break;
+            }
+        }
+    } else {
+        let mut stdout = stdout.lock()?;
+        let mut stdin = Some(input.lock()?);
+
+        let mut sources = VecDeque::new();
+        for operand in operands {
+            if operand == OsStr::new("-") {
+                // stdin can only be read once anyway
+                if let Some(stdin) = stdin.take() {
+                    add_source(&mut sources, operand, stdin);
+                }
+            } else if let Some(file) = sed.open(operand) {
+                add_source(&mut sources, operand, BufReader::new(file));
+            }
+        }
+
+        if options.separate {
+            for source in sources {
+                let mut input = Input::new(options.delimiter, VecDeque::from(vec![source]));
+                sed.run(&mut input, &mut stdout)?;
+                if sed.quit {
+                    break;
+                }
+            }
+        } else {
+            let mut input = Input::new(options.delimiter, sources);
+            sed.run(&mut input, &mut stdout)?;
+        }
+    }
+
+    if sed.exitcode == 0 {
+        Ok(())
+    } else {
+        Err(MesaError::new(None, sed.exitcode, None))
+    }
+}
+
+/// Pull the optional suffix out of `-iSUFFIX` and `--in-place=SUFFIX`, which clap cannot parse
+/// without also treating `-i SCRIPT` as a suffix.
+fn split_in_place_suffix<T>(args: T) -> (Vec<OsString>, Option<OsString>)
+where
+    T: ArgsIter,
+{
+    let mut result = vec![];
+    let mut suffix = None;
+    let mut takes_value = false;
+    let mut done = false;
+
+    for arg in args {
+        let arg: OsString = arg.into();
+        if done || takes_value || result.is_empty() {
+            takes_value = false;
+            result.push(arg);
+            continue;
+        }
+
+        let bytes = util::os_str_as_bytes(&arg).into_owned();
+        if bytes == b"--" {
+            done = true;
+        } else if bytes.starts_with(b"--in-place=") {
+            suffix = Some(util::os_str_from_bytes(&bytes[11..]).into_owned());
+            result.push(OsString::from("--in-place"));
+            continue;
+        } else if bytes.starts_with(b"-i") && bytes.len() > 2 {
+            suffix = Some(util::os_str_from_bytes(&bytes[2..]).into_owned());
+            result.push(OsString::from("-i"));
+            continue;
+        }
+
+        takes_value = match &bytes[..] {
+            b"-e" | b"-f" | b"--expression" | b"--file" => true,
+            _ => false,
+        };
+        result.push(arg);
+    }
+
+    (result, suffix)
+}
+
+/// Combine the scripts given by -e and -f (in the order they were given), or use the first operand
+/// if there are none.
+fn collect_script(
+    matches: &ArgMatches,
+    current_dir: &Option<PathBuf>,
+    operands: &mut Vec<&OsStr>,
+) -> Result<String> {
+    let mut pieces = vec![];
+
+    if let (Some(values), Some(indices)) =
+        (matches.values_of_os("expression"), matches.indices_of("expression"))
+    {
+        pieces.extend(indices.zip(values.map(|v| v.to_string_lossy().into_owned())));
+    }
+
+    if let (Some(values), Some(indices)) =
+        (matches.values_of_os("file"), matches.indices_of("file"))
+    {
+        for (index, value) in indices.zip(values) {
+            let mut data = String::new();
+            util::open_file(current_dir, value)?
+                .read_to_string(&mut data)
+                .map_err(|e| PathError::new(PathOperation::Read, value, e))?;
+            if data.ends_with('\n') {
+                data.pop();
+            }
+            pieces.push((index, data));
+        }
+    }
+
+    if pieces.is_empty() {
+        if operands.is_empty() {
+            return Err(NoScriptError.into());
+        }
+        let script = operands.remove(0);
+        return Ok(script.to_string_lossy().into_owned());
+    }
+
+    pieces.sort_by_key(|&(index, _)| index);
+    let pieces: Vec<String> = pieces.into_iter().map(|(_, piece)| piece).collect();
+    Ok(pieces.join("\n"))
+}
+
+fn add_source<'a, R>(sources: &mut VecDeque<(OsString, Box<BufRead + 'a>)>, name: &OsStr, reader: R)
+where
+    R: BufRead + 'a,
+{
+    sources.push_back((name.to_owned(), Box::new(reader)));
+}
+
+struct Line {
+    data: Vec<u8>,
+    // whether the line ended with the delimiter
+    terminated: bool,
+}
+
+/// A stream of lines from one or more sources that knows whether the current line is the last.
+struct Input<'a> {
+    delimiter: LineDelimiter,
+    sources: VecDeque<(OsString, Box<BufRead + 'a>)>,
+    peeked: Option<Line>,
+    errors: Vec<PathError>,
+}
+
+impl<'a> Input<'a> {
+    fn new(delimiter: LineDelimiter, sources: VecDeque<(OsString, Box<BufRead + 'a>)>) -> Self {
+        Self {
+            delimiter: delimiter,
+            sources: sources,
+            peeked: None,
+            errors: vec![],
+        }
+    }
+
+    /// Get the next line along with whether it is the last line of input.
+    fn next_line(&mut self) -> Option<(Line, bool)> {
+        let line = match self.peeked.take() {
+            Some(line) => line,
+            None => self.read_line()?,
+        };
+        self.peeked = self.read_line();
+        Some((line, self.peeked.is_none()))
+    }
+
+    fn read_line(&mut self) -> Option<Line> {
+        loop {
+            let mut data = vec![];
+            let res = match self.sources.front_mut() {
+                Some(&mut (_, ref mut reader)) => self.delimiter.read_line(reader, &mut data),
+                None => return None,
+            };
+
+            match res {
+                Ok(0) => {
+                    self.sources.pop_front();
+                }
+                Ok(_) => {
+                    let terminated = data.last() == Some(&self.delimiter.byte());
+                    if terminated {
+                        data.pop();
+                    }
+                    return Some(Line {
+                        data: data,
+                        terminated: terminated,
+                    });
+                }
+                Err(e) => {
+                    // skip the rest of this source, but keep going with the others
+                    let (name, _) = self.sources.pop_front().unwrap();
+                    self.errors.push(PathError::new(PathOperation::Read, name, e));
+                }
+            }
+        }
+    }
+}
+
+/// Writes lines while keeping track of whether the last one was missing its delimiter (because
+/// the input was), in which case the delimiter is added if anything else is written.
+struct LineWriter {
+    delimiter: LineDelimiter,
+    missing_delimiter: bool,
+}
+
+impl LineWriter {
+    fn write_line<W>(&mut self, output: &mut W, data: &[u8], terminated: bool) -> Result<()>
+    where
+        W: Write,
+    {
+        let delim = self.delimiter.byte();
+        if self.missing_delimiter {
+            output.write_all(&[delim])?;
+        }
+        output.write_all(data)?;
+        if terminated {
+            output.write_all(&[delim])?;
+        }
+        self.missing_delimiter = !terminated;
+        Ok(())
+    }
+}
+
+struct Sed<'a, E>
+where
+    E: Write,
+{
+    stderr: E,
+    commands: Vec<Command>,
+    options: &'a Options,
+    quit: bool,
+    writer: LineWriter,
+    exitcode: i32,
+}
+
+impl<'a, E> Sed<'a, E>
+where
+    E: Write,
+{
+    fn open(&mut self, operand: &OsStr) -> Option<File> {
+        match util::open_file(&self.options.current_dir, operand) {
+            Ok(file) => Some(file),
+            Err(e) => {
+                let _ = display_msg!(self.stderr, "{}", e);
+                self.exitcode = EXIT_BAD_INPUT;
+                None
+            }
+        }
+    }
+
+    fn run<W: Write>(&mut self, input: &mut Input, output: &mut W) -> Result<()> {
+        let mut line_number = 0;
+
+        while let Some((line, last)) = input.next_line() {
+            line_number += 1;
+            self.report_errors(input)?;
+
+            let mut space = line.data;
+            let mut deleted = false;
+
+            for command in &mut self.commands {
+                if !command.selects(line_number, last, &space) {
+                    continue;
+                }
+
+                match command.kind {
+                    CommandKind::Substitute(ref sub) => {
+                        if sub.apply(&mut space) && sub.print {
+                            self.writer.write_line(output, &space, true)?;
+                        }
+                    }
+                    CommandKind::Print => {
+                        self.writer.write_line(output, &space, true)?;
+                    }
+                    CommandKind::Delete => {
+                        deleted = true;
+                        break;
+                    }
+                    CommandKind::Quit => {
+                        self.quit = true;
+                        break;
+                    }
+                    CommandKind::LineNumber => {
+                        let num = line_number.to_string();
+                        self.writer.write_line(output, num.as_bytes(), true)?;
+                    }
+                }
+            }
+
+            if !deleted && !self.options.quiet {
+                self.writer.write_line(output, &space, line.terminated)?;
+            }
+
+            if self.quit {
+                break;
+            }
+        }
+
+        self.report_errors(input)?;
+        output.flush()?;
+        Ok(())
+    }
+
+    fn report_errors(&mut self, input: &mut Input) -> Result<()> {
+        for err in input.errors.drain(..) {
+            display_msg!(self.stderr, "{}", err)?;
+            self.exitcode = EXIT_BAD_INPUT;
+        }
+        Ok(())
+    }
+
+    fn edit_in_place(&mut self, operand: &OsStr) -> Result<()> {
+        let path = util::actual_path(&self.options.current_dir, operand).into_owned();
+
+        let file = match self.open(operand) {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+        let metadata = file
+            .metadata()
+            .map_err(|e| PathError::new(PathOperation::Stat, operand, e))?;
+        if !metadata.is_file() {
+            display_msg!(
+                self.stderr,
+                "couldn't edit {}: not a regular file",
+                Path::new(operand).display()
+            )?;
+            self.exitcode = EXIT_BAD_EDIT;
+            return Ok(());
+        }
+
+        let mut editor = InPlace::new(&path, &metadata)?;
+        {
+            let mut sources = VecDeque::new();
+            add_source(&mut sources, operand, BufReader::new(file));
+            let mut input = Input::new(self.options.delimiter, sources);
+            let mut output = BufWriter::new(editor.file());
+            self.writer.missing_delimiter = false;
+            self.run(&mut input, &mut output)?;
+        }
+
+        let backup = match self.options.in_place {
+            Some(Some(ref suffix)) => Some(backup_path(&self.options.current_dir, &path, suffix)),
+            _ => None,
+        };
+        editor.commit(backup.as_ref().map(|p| p.as_path()))?;
+
+        Ok(())
+    }
+}
+
+/// Determine the name of the backup for `path`.  Every `*` in `suffix` is replaced by the file's
+/// name; otherwise the suffix is simply appended to it.  If the result contains a slash, it is
+/// treated as a path of its own.
+fn backup_path(current_dir: &Option<PathBuf>, path: &Path, suffix: &OsStr) -> PathBuf {
+    let name = path.file_name().map(util::os_str_as_bytes).unwrap_or_default();
+    let suffix = util::os_str_as_bytes(suffix);
+
+    let backup: Vec<u8> = if suffix.contains(&b'*') {
+        let mut backup = vec![];
+        for &b in suffix.iter() {
+            if b == b'*' {
+                backup.extend_from_slice(&name);
+            } else {
+                backup.push(b);
+            }
+        }
+        backup
+    } else {
+        let mut backup = name.into_owned();
+        backup.extend_from_slice(&suffix);
+        backup
+    };
+
+    let backup = util::os_str_from_bytes(&backup);
+    if backup.to_string_lossy().contains('/') {
+        util::actual_path(current_dir, &*backup).into_owned()
+    } else {
+        path.with_file_name(&*backup)
+    }
+}
+
+#[test]
+fn backup_names() {
+    let path = Path::new("dir/file.txt");
+    assert_eq!(backup_path(&None, path, OsStr::new(".bak")), Path::new("dir/file.txt.bak"));
+    assert_eq!(backup_path(&None, path, OsStr::new("old_*")), Path::new("dir/old_file.txt"));
+    assert_eq!(backup_path(&None, path, OsStr::new("bak/*")), Path::new("bak/file.txt"));
+}
+
+#[test]
+fn in_place_suffix() {
+    let os_strings = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+
+    let args = vec!["sed", "-i.bak", "-e", "-ifoo", "file"];
+    let (args, suffix) = split_in_place_suffix(&mut args.into_iter());
+    assert_eq!(args, os_strings(&["sed", "-i", "-e", "-ifoo", "file"]));
+    assert_eq!(suffix, Some(OsString::from(".bak")));
+
+    let args = vec!["sed", "-i", "s/a/b/", "file"];
+    let (args, suffix) = split_in_place_suffix(&mut args.into_iter());
+    assert_eq!(args, os_strings(&["sed", "-i", "s/a/b/", "file"]));
+    assert_eq!(suffix, None);
+}
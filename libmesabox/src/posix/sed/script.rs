@@ -0,0 +1,543 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use util::posix_regex::{self, PatternError};
+
+use regex::bytes::{Regex, RegexBuilder};
+use std::iter::Peekable;
+use std::result::Result as StdResult;
+use std::str::Chars;
+
+#[derive(Debug, Fail)]
+pub enum ScriptError {
+    #[fail(display = "char {}: unknown command: `{}'", _0, _1)]
+    UnknownCommand(usize, char),
+
+    #[fail(display = "char {}: missing command", _0)]
+    MissingCommand(usize),
+
+    #[fail(display = "char {}: extra characters after command", _0)]
+    ExtraCharacters(usize),
+
+    #[fail(display = "char {}: unexpected `,'", _0)]
+    UnexpectedComma(usize),
+
+    #[fail(display = "char {}: invalid usage of line address 0", _0)]
+    LineZero(usize),
+
+    #[fail(display = "char {}: command only uses one address", _0)]
+    OneAddress(usize),
+
+    #[fail(display = "char {}: unterminated address regex", _0)]
+    UnterminatedAddress(usize),
+
+    #[fail(display = "char {}: unterminated `s' command", _0)]
+    UnterminatedSubstitute(usize),
+
+    #[fail(display = "char {}: unknown option to `s'", _0)]
+    UnknownOption(usize),
+
+    #[fail(display = "char {}: number option to `s' command may not be zero", _0)]
+    ZeroOccurrence(usize),
+
+    #[fail(display = "char {}: invalid reference \\{} on `s' command's RHS", _0, _1)]
+    InvalidReference(usize, usize),
+
+    #[fail(display = "char {}: no previous regular expression", _0)]
+    NoPreviousRegex(usize),
+
+    #[fail(display = "char {}: {}", _0, _1)]
+    Pattern(usize, #[cause] PatternError),
+}
+
+pub enum Address {
+    Line(usize),
+    Last,
+    Regex(Regex),
+}
+
+impl Address {
+    fn matches(&self, line: usize, last: bool, space: &[u8]) -> bool {
+        match *self {
+            Address::Line(num) => num == line,
+            Address::Last => last,
+            Address::Regex(ref regex) => regex.is_match(space),
+        }
+    }
+}
+
+pub enum CommandKind {
+    Substitute(Substitute),
+    Print,
+    Delete,
+    Quit,
+    LineNumber,
+}
+
+pub struct Command {
+    pub addr1: Option<Address>,
+    pub addr2: Option<Address>,
+    pub negate: bool,
+    pub kind: CommandKind,
+    // whether we are inside the range given by addr1 and addr2
+    in_range: bool,
+}
+
+impl Command {
+    /// Determine whether the command applies to the current line, updating the state of the
+    /// command's range (if it has one).
+    pub fn selects(&mut self, line: usize, last: bool, space: &[u8]) -> bool {
+        let selected = match (self.addr1.as_ref(), self.addr2.as_ref()) {
+            (None, _) => true,
+            (Some(addr1), None) => addr1.matches(line, last, space),
+            (Some(addr1), Some(addr2)) => {
+                if self.in_range {
+                    let end = match *addr2 {
+                        Address::Line(num) => line >= num,
+                        _ => addr2.matches(line, last, space),
+                    };
+                    self.in_range = !end;
+                    true
+                } else if addr1.matches(line, last, space) {
+                    // the end of the range is only checked against the lines after the start,
+                    // except for line numbers that have already been passed
+                    self.in_range = match *addr2 {
+                        Address::Line(num) => num > line,
+                        Address::Last => !last,
+                        Address::Regex(_) => true,
+                    };
+                    true
+                } else {
+                    false
+                }
+            }
+        };
+
+        selected != self.negate
+    }
+}
+
+pub enum Replacement {
+    Literal(Vec<u8>),
+    Group(usize),
+}
+
+pub struct Substitute {
+    pub regex: Regex,
+    pub replacement: Vec<Replacement>,
+    pub global: bool,
+    /// Which match to replace (starting from 1)
+    pub occurrence: usize,
+    pub print: bool,
+}
+
+impl Substitute {
+    /// Perform the substitution on `space`, returning whether anything was replaced.
+    pub fn apply(&self, space: &mut Vec<u8>) -> bool {
+        let mut result = Vec::with_capacity(space.len());
+        let mut last_end = 0;
+        let mut replaced = false;
+
+        for (i, caps) in self.regex.captures_iter(space).enumerate() {
+            if i + 1 < self.occurrence {
+                continue;
+            }
+
+            let whole = caps.get(0).unwrap();
+            result.extend_from_slice(&space[last_end..whole.start()]);
+            for part in &self.replacement {
+                match *part {
+                    Replacement::Literal(ref data) => result.extend_from_slice(data),
+                    Replacement::Group(num) => {
+                        if let Some(group) = caps.get(num) {
+                            result.extend_from_slice(group.as_bytes());
+                        }
+                    }
+                }
+            }
+            last_end = whole.end();
+            replaced = true;
+
+            if !self.global {
+                break;
+            }
+        }
+
+        if replaced {
+            result.extend_from_slice(&space[last_end..]);
+            *space = result;
+        }
+        replaced
+    }
+}
+
+/// Parse a script into the list of commands it contains.  If `extended` is true, regular
+/// expressions are treated as POSIX extended regular expressions.
+pub fn parse(script: &str, extended: bool) -> StdResult<Vec<Command>, ScriptError> {
+    let mut parser = Parser {
+        chars: script.chars().peekable(),
+        pos: 0,
+        extended: extended,
+        last_regex: None,
+    };
+    parser.parse()
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    // the number of characters consumed so far (used for error messages)
+    pos: usize,
+    extended: bool,
+    last_regex: Option<Regex>,
+}
+
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> Option<char> {
+        let ch = self.chars.next();
+        if ch.is_some() {
+            self.pos += 1;
+        }
+        ch
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().cloned()
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek() == Some(' ') || self.peek() == Some('\t') {
+            self.next();
+        }
+    }
+
+    fn parse(&mut self) -> StdResult<Vec<Command>, ScriptError> {
+        let mut commands = vec![];
+
+        loop {
+            while self.peek().map(|ch| ch.is_whitespace() || ch == ';').unwrap_or(false) {
+                self.next();
+            }
+            match self.peek() {
+                None => break,
+                Some('#') => {
+                    self.skip_comment();
+                    continue;
+                }
+                _ => {}
+            }
+
+            let addr1 = self.parse_address()?;
+            let addr2 = if self.peek() == Some(',') {
+                self.next();
+                if addr1.is_none() {
+                    return Err(ScriptError::UnexpectedComma(self.pos));
+                }
+                self.skip_spaces();
+                match self.parse_address()? {
+                    Some(addr) => Some(addr),
+                    None => return Err(ScriptError::UnexpectedComma(self.pos)),
+                }
+            } else {
+                None
+            };
+
+            self.skip_spaces();
+            let negate = if self.peek() == Some('!') {
+                self.next();
+                self.skip_spaces();
+                true
+            } else {
+                false
+            };
+
+            let kind = match self.next() {
+                Some('s') => CommandKind::Substitute(self.parse_substitute()?),
+                Some('p') => CommandKind::Print,
+                Some('d') => CommandKind::Delete,
+                Some('q') => CommandKind::Quit,
+                Some('=') => CommandKind::LineNumber,
+                Some(ch) if ch != '\n' && ch != ';' => {
+                    return Err(ScriptError::UnknownCommand(self.pos, ch))
+                }
+                _ => return Err(ScriptError::MissingCommand(self.pos)),
+            };
+
+            if let CommandKind::Quit = kind {
+                if addr2.is_some() {
+                    return Err(ScriptError::OneAddress(self.pos));
+                }
+            }
+
+            self.end_command()?;
+
+            commands.push(Command {
+                addr1: addr1,
+                addr2: addr2,
+                negate: negate,
+                kind: kind,
+                in_range: false,
+            });
+        }
+
+        Ok(commands)
+    }
+
+    fn skip_comment(&mut self) {
+        while let Some(ch) = self.next() {
+            if ch == '\n' {
+                break;
+            }
+        }
+    }
+
+    fn end_command(&mut self) -> StdResult<(), ScriptError> {
+        self.skip_spaces();
+        match self.peek() {
+            None | Some('#') => Ok(()),
+            Some(';') | Some('\n') => {
+                self.next();
+                Ok(())
+            }
+            Some(_) => {
+                self.next();
+                Err(ScriptError::ExtraCharacters(self.pos))
+            }
+        }
+    }
+
+    fn parse_address(&mut self) -> StdResult<Option<Address>, ScriptError> {
+        let addr = match self.peek() {
+            Some(ch) if ch.is_ascii_digit() => {
+                let mut num = 0usize;
+                while let Some(digit) = self.peek().and_then(|ch| ch.to_digit(10)) {
+                    self.next();
+                    num = num.saturating_mul(10).saturating_add(digit as usize);
+                }
+                if num == 0 {
+                    return Err(ScriptError::LineZero(self.pos));
+                }
+                Address::Line(num)
+            }
+            Some('$') => {
+                self.next();
+                Address::Last
+            }
+            Some('/') | Some('\\') => {
+                let delim = match self.next() {
+                    Some('\\') => self.next(),
+                    other => other,
+                };
+                let pattern = match delim {
+                    Some(delim) if delim != '\n' && delim != '\\' => self.read_delimited(delim),
+                    _ => None,
+                };
+                let pattern = pattern.ok_or(ScriptError::UnterminatedAddress(self.pos))?;
+
+                let ignore_case = self.peek() == Some('I');
+                if ignore_case {
+                    self.next();
+                }
+                Address::Regex(self.compile(&pattern, ignore_case)?)
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(Some(addr))
+    }
+
+    /// Read text until `delim` (which can be escaped with a backslash).  Other escape sequences
+    /// are left as they are.  Returns `None` if the end of the line is reached first.
+    fn read_delimited(&mut self, delim: char) -> Option<String> {
+        let mut result = String::new();
+        loop {
+            match self.next() {
+                None | Some('\n') => return None,
+                Some(ch) if ch == delim => return Some(result),
+                Some('\\') => match self.next() {
+                    Some(ch) if ch == delim => result.push(ch),
+                    // a backslash followed by an actual newline is a literal newline
+                    Some('\n') => result.push_str("\\n"),
+                    Some(ch) => {
+                        result.push('\\');
+                        result.push(ch);
+                    }
+                    None => return None,
+                },
+                Some(ch) => result.push(ch),
+            }
+        }
+    }
+
+    fn compile(&mut self, pattern: &str, ignore_case: bool) -> StdResult<Regex, ScriptError> {
+        // an empty regex means the last regex used
+        if pattern.is_empty() {
+            return self
+                .last_regex
+                .clone()
+                .ok_or(ScriptError::NoPreviousRegex(self.pos));
+        }
+
+        let translated = posix_regex::translate(pattern, self.extended)
+            .map_err(|e| ScriptError::Pattern(self.pos, e))?;
+        let regex = RegexBuilder::new(&translated)
+            .case_insensitive(ignore_case)
+            .build()
+            .map_err(|e| ScriptError::Pattern(self.pos, e.into()))?;
+
+        self.last_regex = Some(regex.clone());
+        Ok(regex)
+    }
+
+    fn parse_substitute(&mut self) -> StdResult<Substitute, ScriptError> {
+        let delim = match self.next() {
+            Some(ch) if ch != '\n' && ch != '\\' => ch,
+            _ => return Err(ScriptError::UnterminatedSubstitute(self.pos)),
+        };
+        let pattern = self
+            .read_delimited(delim)
+            .ok_or(ScriptError::UnterminatedSubstitute(self.pos))?;
+        let replacement = self
+            .read_delimited(delim)
+            .ok_or(ScriptError::UnterminatedSubstitute(self.pos))?;
+
+        let mut global = false;
+        let mut print = false;
+        let mut ignore_case = false;
+        let mut occurrence = None;
+        loop {
+            match self.peek() {
+                Some('g') => global = true,
+                Some('p') => print = true,
+                Some('i') | Some('I') => ignore_case = true,
+                Some(ch) if ch.is_ascii_digit() => {
+                    if occurrence.is_some() {
+                        return Err(ScriptError::UnknownOption(self.pos));
+                    }
+                    let mut num = 0usize;
+                    while let Some(digit) = self.peek().and_then(|ch| ch.to_digit(10)) {
+                        self.next();
+                        num = num.saturating_mul(10).saturating_add(digit as usize);
+                    }
+                    if num == 0 {
+                        return Err(ScriptError::ZeroOccurrence(self.pos));
+                    }
+                    occurrence = Some(num);
+                    continue;
+                }
+                None | Some(';') | Some('\n') | Some('#') | Some(' ') | Some('\t') => break,
+                Some(_) => {
+                    self.next();
+                    return Err(ScriptError::UnknownOption(self.pos));
+                }
+            }
+            self.next();
+        }
+
+        let regex = self.compile(&pattern, ignore_case)?;
+        let replacement = parse_replacement(&replacement);
+
+        // make sure every group referenced actually exists
+        let groups = regex.captures_len() - 1;
+        for part in &replacement {
+            if let Replacement::Group(num) = *part {
+                if num > groups {
+                    return Err(ScriptError::InvalidReference(self.pos, num));
+                }
+            }
+        }
+
+        Ok(Substitute {
+            regex: regex,
+            replacement: replacement,
+            global: global,
+            occurrence: occurrence.unwrap_or(1),
+            print: print,
+        })
+    }
+}
+
+fn parse_replacement(text: &str) -> Vec<Replacement> {
+    let mut parts = vec![];
+    let mut literal = String::new();
+
+    fn flush(parts: &mut Vec<Replacement>, literal: &mut String) {
+        if !literal.is_empty() {
+            parts.push(Replacement::Literal(literal.clone().into_bytes()));
+            literal.clear();
+        }
+    }
+
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => match chars.next() {
+                Some(digit @ '0'...'9') => {
+                    flush(&mut parts, &mut literal);
+                    parts.push(Replacement::Group(digit as usize - '0' as usize));
+                }
+                Some('n') => literal.push('\n'),
+                Some('t') => literal.push('\t'),
+                Some(ch) => literal.push(ch),
+                None => literal.push('\\'),
+            },
+            '&' => {
+                flush(&mut parts, &mut literal);
+                parts.push(Replacement::Group(0));
+            }
+            _ => literal.push(ch),
+        }
+    }
+    flush(&mut parts, &mut literal);
+
+    parts
+}
+
+#[cfg(test)]
+fn substitute(script: &str, input: &str) -> String {
+    let commands = parse(script, false).unwrap();
+    let mut space = input.as_bytes().to_vec();
+    match commands[0].kind {
+        CommandKind::Substitute(ref sub) => {
+            sub.apply(&mut space);
+        }
+        _ => panic!("not a substitution"),
+    }
+    String::from_utf8(space).unwrap()
+}
+
+#[test]
+fn substitute_flags() {
+    assert_eq!(substitute("s/a/b/", "aaa"), "baa");
+    assert_eq!(substitute("s/a/b/g", "aaa"), "bbb");
+    assert_eq!(substitute("s/a/b/2", "aaa"), "aba");
+    assert_eq!(substitute("s/a/b/2g", "aaaa"), "abbb");
+    assert_eq!(substitute("s/A/b/I", "aaa"), "baa");
+    assert_eq!(substitute("s|/|:|g", "/usr/bin"), ":usr:bin");
+    assert_eq!(substitute(r"s/\//:/", "a/b"), "a:b");
+}
+
+#[test]
+fn substitute_replacement() {
+    assert_eq!(substitute(r"s/\(a*\)\(b*\)/\2\1/", "aabbc"), "bbaac");
+    assert_eq!(substitute("s/b*/[&]/", "bbc"), "[bb]c");
+    assert_eq!(substitute(r"s/b/\&/", "abc"), "a&c");
+    assert_eq!(substitute(r"s/b/\n/", "abc"), "a\nc");
+}
+
+#[test]
+fn parse_errors() {
+    assert!(parse("s/a/b", false).is_err());
+    assert!(parse("s/a/b/x", false).is_err());
+    assert!(parse(r"s/a/\1/", false).is_err());
+    assert!(parse("0p", false).is_err());
+    assert!(parse("1,2q", false).is_err());
+    assert!(parse("k", false).is_err());
+    assert!(parse("p x", false).is_err());
+    assert!(parse("//p", false).is_err());
+    assert!(parse("/a/p; //d # comment\n$=", false).is_ok());
+}
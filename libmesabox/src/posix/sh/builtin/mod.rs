@@ -193,7 +193,6 @@ impl Builtin {
                 //        same).  ideally, we would not have to do this (as obviously static
                 //        dispatch is faster)
                 // TODO: add anything else in data to setup
-                // TODO: add export_vars to setup
                 let input_fd = input.raw_object();
                 let output_fd = output.raw_object();
                 let error_fd = error.raw_object();
@@ -209,8 +208,14 @@ impl Builtin {
                 (input, output, error)
             }
         };
-        let mut util_setup =
-            UtilData::new(&mut input, &mut output, &mut error, iter::empty(), None);
+        let mut util_setup = UtilData::new(
+            &mut input,
+            &mut output,
+            &mut error,
+            env.export_iter()
+                .map(|(key, value)| (key.to_owned(), value.to_owned())),
+            None,
+        );
         let setup = &mut util_setup;
         execute_util(
             setup,
@@ -0,0 +1,712 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use {ArgsIter, Result, UtilRead, UtilSetup, UtilWrite};
+
+use clap::{AppSettings, Arg};
+use nix::libc::{self, cc_t, tcflag_t, termios};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::mem;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, RawFd};
+
+pub(crate) const NAME: &str = "stty";
+pub(crate) const DESCRIPTION: &str = "Show or change the settings of a terminal";
+
+const AFTER_HELP: &str = "
+Without SETTINGS, the settings that differ from those set by 'sane' are shown.  SETTINGS may be:
+
+  FLAG            Turn FLAG on (or off if it is written as -FLAG).  The flags are:
+                    parenb parodd cs5 cs6 cs7 cs8 hupcl cstopb cread clocal
+                    ignbrk brkint ignpar parmrk inpck istrip inlcr igncr icrnl ixon ixany ixoff
+                    imaxbel opost onlcr ocrnl onocr onlret ofill isig icanon iexten echo echoe
+                    echok echonl noflsh tostop echoctl echoprt echoke
+  CHAR VALUE      Set the special character CHAR (intr, quit, erase, kill, eof, eol, start, stop,
+                  susp, rprnt, werase or lnext) to VALUE, which is a character, ^X for a control
+                  character, or 'undef' to disable it
+  min N, time N   With -icanon, the minimum number of characters for a read and its timeout (in
+                  tenths of a second)
+  rows N, cols N  Change the size of the terminal
+  size            Print the number of rows and columns of the terminal
+  speed           Print the speed of the terminal
+  N               Set the speed of the terminal to N baud
+  raw             Turn off input and output processing
+  -raw, cooked    Turn input and output processing back on
+  sane            Reset everything to reasonable values
+  SAVED           Restore the settings printed by -g
+";
+
+// the width at which lines of settings are wrapped
+const LINE_WIDTH: usize = 80;
+
+// the value of a special character that has been disabled
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const VDISABLE: cc_t = 0;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+const VDISABLE: cc_t = 0xff;
+
+#[derive(Debug, Fail)]
+pub(crate) enum SttyError {
+    #[fail(display = "'{}': {}", _0, _1)]
+    Device(String, #[cause] io::Error),
+
+    #[fail(display = "invalid argument '{}'", _0)]
+    InvalidArgument(String),
+
+    #[fail(display = "missing argument to '{}'", _0)]
+    MissingArgument(String),
+
+    #[fail(display = "invalid integer argument '{}'", _0)]
+    InvalidInteger(String),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Group {
+    Control,
+    Input,
+    Output,
+    Local,
+}
+
+/// A setting stored in one of the flag fields of a termios.
+struct Flag {
+    name: &'static str,
+    group: Group,
+    value: tcflag_t,
+    // the bits that make up the setting (which is more than `value` for multi-bit settings like
+    // the character size)
+    mask: tcflag_t,
+    // whether the setting can be turned off (rather than just replaced, like the character size)
+    negatable: bool,
+}
+
+macro_rules! flag {
+    ($name:expr, $group:ident, $bit:expr) => {
+        Flag {
+            name: $name,
+            group: Group::$group,
+            value: $bit,
+            mask: $bit,
+            negatable: true,
+        }
+    };
+    ($name:expr, $group:ident, $value:expr, $mask:expr) => {
+        Flag {
+            name: $name,
+            group: Group::$group,
+            value: $value,
+            mask: $mask,
+            negatable: false,
+        }
+    };
+}
+
+const FLAGS: &[Flag] = &[
+    flag!("parenb", Control, libc::PARENB),
+    flag!("parodd", Control, libc::PARODD),
+    flag!("cs5", Control, libc::CS5, libc::CSIZE),
+    flag!("cs6", Control, libc::CS6, libc::CSIZE),
+    flag!("cs7", Control, libc::CS7, libc::CSIZE),
+    flag!("cs8", Control, libc::CS8, libc::CSIZE),
+    flag!("hupcl", Control, libc::HUPCL),
+    flag!("cstopb", Control, libc::CSTOPB),
+    flag!("cread", Control, libc::CREAD),
+    flag!("clocal", Control, libc::CLOCAL),
+    flag!("ignbrk", Input, libc::IGNBRK),
+    flag!("brkint", Input, libc::BRKINT),
+    flag!("ignpar", Input, libc::IGNPAR),
+    flag!("parmrk", Input, libc::PARMRK),
+    flag!("inpck", Input, libc::INPCK),
+    flag!("istrip", Input, libc::ISTRIP),
+    flag!("inlcr", Input, libc::INLCR),
+    flag!("igncr", Input, libc::IGNCR),
+    flag!("icrnl", Input, libc::ICRNL),
+    flag!("ixon", Input, libc::IXON),
+    flag!("ixany", Input, libc::IXANY),
+    flag!("ixoff", Input, libc::IXOFF),
+    flag!("imaxbel", Input, libc::IMAXBEL),
+    flag!("opost", Output, libc::OPOST),
+    flag!("onlcr", Output, libc::ONLCR),
+    flag!("ocrnl", Output, libc::OCRNL),
+    flag!("onocr", Output, libc::ONOCR),
+    flag!("onlret", Output, libc::ONLRET),
+    flag!("ofill", Output, libc::OFILL),
+    flag!("isig", Local, libc::ISIG),
+    flag!("icanon", Local, libc::ICANON),
+    flag!("iexten", Local, libc::IEXTEN),
+    flag!("echo", Local, libc::ECHO),
+    flag!("echoe", Local, libc::ECHOE),
+    flag!("echok", Local, libc::ECHOK),
+    flag!("echonl", Local, libc::ECHONL),
+    flag!("noflsh", Local, libc::NOFLSH),
+    flag!("tostop", Local, libc::TOSTOP),
+    flag!("echoctl", Local, libc::ECHOCTL),
+    flag!("echoprt", Local, libc::ECHOPRT),
+    flag!("echoke", Local, libc::ECHOKE),
+];
+
+// the special characters, along with the values set by 'sane'
+const CHARS: &[(&str, usize, cc_t)] = &[
+    ("intr", libc::VINTR, 0x03),
+    ("quit", libc::VQUIT, 0x1c),
+    ("erase", libc::VERASE, 0x7f),
+    ("kill", libc::VKILL, 0x15),
+    ("eof", libc::VEOF, 0x04),
+    ("eol", libc::VEOL, VDISABLE),
+    ("start", libc::VSTART, 0x11),
+    ("stop", libc::VSTOP, 0x13),
+    ("susp", libc::VSUSP, 0x1a),
+    ("rprnt", libc::VREPRINT, 0x12),
+    ("werase", libc::VWERASE, 0x17),
+    ("lnext", libc::VLNEXT, 0x16),
+];
+
+const SPEEDS: &[(libc::speed_t, u32)] = &[
+    (libc::B0, 0),
+    (libc::B50, 50),
+    (libc::B75, 75),
+    (libc::B110, 110),
+    (libc::B134, 134),
+    (libc::B150, 150),
+    (libc::B200, 200),
+    (libc::B300, 300),
+    (libc::B600, 600),
+    (libc::B1200, 1200),
+    (libc::B1800, 1800),
+    (libc::B2400, 2400),
+    (libc::B4800, 4800),
+    (libc::B9600, 9600),
+    (libc::B19200, 19200),
+    (libc::B38400, 38400),
+    (libc::B57600, 57600),
+    (libc::B115200, 115200),
+    (libc::B230400, 230400),
+];
+
+const RAW: &[&str] = &[
+    "-ignbrk", "-brkint", "-ignpar", "-parmrk", "-inpck", "-istrip", "-inlcr", "-igncr", "-icrnl",
+    "-ixon", "-ixoff", "-ixany", "-imaxbel", "-opost", "-isig", "-icanon",
+];
+
+const COOKED: &[&str] = &[
+    "brkint", "ignpar", "istrip", "icrnl", "ixon", "opost", "isig", "icanon",
+];
+
+const SANE: &[&str] = &[
+    "cread", "-ignbrk", "brkint", "-inlcr", "-igncr", "icrnl", "-ixoff", "-ixany", "imaxbel",
+    "opost", "-ocrnl", "onlcr", "-onocr", "-onlret", "-ofill", "isig", "icanon", "iexten", "echo",
+    "echoe", "echok", "-echonl", "-noflsh", "-tostop", "-echoprt", "echoctl", "echoke",
+];
+
+fn flags(termios: &termios, group: Group) -> tcflag_t {
+    match group {
+        Group::Control => termios.c_cflag,
+        Group::Input => termios.c_iflag,
+        Group::Output => termios.c_oflag,
+        Group::Local => termios.c_lflag,
+    }
+}
+
+fn flags_mut(termios: &mut termios, group: Group) -> &mut tcflag_t {
+    match group {
+        Group::Control => &mut termios.c_cflag,
+        Group::Input => &mut termios.c_iflag,
+        Group::Output => &mut termios.c_oflag,
+        Group::Local => &mut termios.c_lflag,
+    }
+}
+
+// turn the flag `name` (or -name) on or off, returning false if there is no such flag
+fn set_flag(termios: &mut termios, name: &str) -> bool {
+    let (name, enable) = if name.starts_with('-') {
+        (&name[1..], false)
+    } else {
+        (name, true)
+    };
+
+    match FLAGS.iter().find(|flag| flag.name == name) {
+        Some(flag) if enable || flag.negatable => {
+            let flags = flags_mut(termios, flag.group);
+            *flags &= !flag.mask;
+            if enable {
+                *flags |= flag.value;
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+fn apply_preset(termios: &mut termios, preset: &[&str]) {
+    for name in preset {
+        set_flag(termios, name);
+    }
+}
+
+fn set_default_chars(termios: &mut termios, names: Option<&[&str]>) {
+    for &(name, index, default) in CHARS {
+        if names.map(|names| names.contains(&name)).unwrap_or(true) {
+            termios.c_cc[index] = default;
+        }
+    }
+}
+
+/// Reset `termios` to reasonable values the way `stty sane` does (which is also used by `reset`).
+/// The speed and character size are left alone.
+pub(crate) fn make_sane(termios: &mut termios) {
+    apply_preset(termios, SANE);
+    set_default_chars(termios, None);
+}
+
+// the settings used to decide what to show when stty is run without arguments
+fn sane_termios() -> termios {
+    let mut termios: termios = unsafe { mem::zeroed() };
+    termios.c_cflag = libc::CS8;
+    make_sane(&mut termios);
+    termios.c_cc[libc::VMIN] = 1;
+    termios.c_cc[libc::VTIME] = 0;
+    termios
+}
+
+// parse the value of a special character (like "^C", "x" or "undef")
+fn parse_char(value: &str) -> Option<cc_t> {
+    let bytes = value.as_bytes();
+    match bytes {
+        b"undef" | b"^-" | b"" => Some(VDISABLE),
+        b"^?" => Some(0x7f),
+        &[b'^', c] if c >= b'@' && c <= b'~' => Some(c.to_ascii_uppercase() & 0x1f),
+        &[c] => Some(c),
+        _ => None,
+    }
+}
+
+fn char_name(value: cc_t) -> String {
+    match value {
+        VDISABLE => "<undef>".to_owned(),
+        0x7f => "^?".to_owned(),
+        0...0x1f => format!("^{}", (value + 0x40) as char),
+        _ => (value as char).to_string(),
+    }
+}
+
+fn parse_number<T: ::std::str::FromStr>(value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| SttyError::InvalidInteger(value.to_owned()).into())
+}
+
+/// Format the settings the way -g prints them.
+fn saved_settings(termios: &termios) -> String {
+    let mut result = format!(
+        "{:x}:{:x}:{:x}:{:x}",
+        termios.c_iflag, termios.c_oflag, termios.c_cflag, termios.c_lflag
+    );
+    for &c in termios.c_cc.iter() {
+        result.push_str(&format!(":{:x}", c));
+    }
+    result
+}
+
+fn restore_settings(termios: &mut termios, saved: &str) -> bool {
+    let fields: Vec<&str> = saved.split(':').collect();
+    if fields.len() != 4 + termios.c_cc.len() {
+        return false;
+    }
+
+    let mut flags = [0; 4];
+    for (flag, field) in flags.iter_mut().zip(&fields[..4]) {
+        match tcflag_t::from_str_radix(field, 16) {
+            Ok(value) => *flag = value,
+            Err(_) => return false,
+        }
+    }
+    let mut chars = termios.c_cc;
+    for (c, field) in chars.iter_mut().zip(&fields[4..]) {
+        match cc_t::from_str_radix(field, 16) {
+            Ok(value) => *c = value,
+            Err(_) => return false,
+        }
+    }
+
+    termios.c_iflag = flags[0];
+    termios.c_oflag = flags[1];
+    termios.c_cflag = flags[2];
+    termios.c_lflag = flags[3];
+    termios.c_cc = chars;
+    true
+}
+
+fn speed(termios: &termios) -> Option<u32> {
+    let speed = unsafe { libc::cfgetospeed(termios) };
+    SPEEDS
+        .iter()
+        .find(|&&(value, _)| value == speed)
+        .map(|&(_, baud)| baud)
+}
+
+/// A terminal, along with a name for it to use in messages.
+pub(crate) struct Terminal {
+    pub(crate) fd: RawFd,
+    pub(crate) name: String,
+}
+
+impl Terminal {
+    fn error(&self, err: io::Error) -> SttyError {
+        SttyError::Device(self.name.clone(), err)
+    }
+
+    pub(crate) fn get_attr(&self) -> Result<termios> {
+        let mut termios: termios = unsafe { mem::zeroed() };
+        if unsafe { libc::tcgetattr(self.fd, &mut termios) } != 0 {
+            return Err(self.error(io::Error::last_os_error()).into());
+        }
+        Ok(termios)
+    }
+
+    pub(crate) fn set_attr(&self, termios: &termios) -> Result<()> {
+        if unsafe { libc::tcsetattr(self.fd, libc::TCSADRAIN, termios) } != 0 {
+            return Err(self.error(io::Error::last_os_error()).into());
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> Result<libc::winsize> {
+        let mut size: libc::winsize = unsafe { mem::zeroed() };
+        if unsafe { libc::ioctl(self.fd, libc::TIOCGWINSZ, &mut size) } != 0 {
+            return Err(self.error(io::Error::last_os_error()).into());
+        }
+        Ok(size)
+    }
+
+    fn set_size(&self, size: &libc::winsize) -> Result<()> {
+        if unsafe { libc::ioctl(self.fd, libc::TIOCSWINSZ, size) } != 0 {
+            return Err(self.error(io::Error::last_os_error()).into());
+        }
+        Ok(())
+    }
+}
+
+// open DEVICE for -F without it becoming the controlling terminal or waiting for a carrier
+fn open_device(device: &str) -> io::Result<File> {
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK | libc::O_NOCTTY)
+        .open(device)?;
+
+    let fd = file.as_raw_fd();
+    let res = unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFL);
+        if flags < 0 {
+            flags
+        } else {
+            libc::fcntl(fd, libc::F_SETFL, flags & !libc::O_NONBLOCK)
+        }
+    };
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(file)
+}
+
+pub fn execute<S, T>(setup: &mut S, args: T) -> Result<()>
+where
+    S: UtilSetup,
+    T: ArgsIter,
+{
+    let matches = {
+        let app = util_app!(NAME)
+                    .after_help(AFTER_HELP)
+                    .setting(AppSettings::AllowLeadingHyphen)
+                    .arg(Arg::with_name("all")
+                            .short("a")
+                            .long("all")
+                            .conflicts_with_all(&["save", "SETTINGS"])
+                            .help("Print all the current settings"))
+                    .arg(Arg::with_name("save")
+                            .short("g")
+                            .long("save")
+                            .conflicts_with("SETTINGS")
+                            .help("Print all the current settings in a form that stty can read back"))
+                    .arg(Arg::with_name("file")
+                            .short("F")
+                            .long("file")
+                            .takes_value(true)
+                            .value_name("DEVICE")
+                            .help("Use DEVICE rather than standard input"))
+                    .arg(Arg::with_name("SETTINGS")
+                            .index(1)
+                            .multiple(true));
+
+        app.get_matches_from_safe(args)?
+    };
+
+    // keep the device open for as long as it is being used
+    let (_device, terminal) = match matches.value_of("file") {
+        Some(name) => {
+            let device = open_device(name).map_err(|e| SttyError::Device(name.to_owned(), e))?;
+            let terminal = Terminal {
+                fd: device.as_raw_fd(),
+                name: name.to_owned(),
+            };
+            (Some(device), terminal)
+        }
+        None => {
+            let name = "standard input".to_owned();
+            let fd = match setup.input().raw_object() {
+                Some(obj) => obj.raw_value(),
+                None => {
+                    let err = io::Error::from_raw_os_error(libc::ENOTTY);
+                    return Err(SttyError::Device(name, err).into());
+                }
+            };
+            (None, Terminal { fd: fd, name: name })
+        }
+    };
+    let mut termios = terminal.get_attr()?;
+
+    let output = setup.output();
+    let mut output = output.lock()?;
+
+    if matches.is_present("save") {
+        writeln!(output, "{}", saved_settings(&termios))?;
+        return Ok(());
+    }
+
+    let settings: Vec<&str> = match matches.values_of("SETTINGS") {
+        Some(values) => values.collect(),
+        None => {
+            let all = matches.is_present("all");
+            return print_settings(&mut output, &terminal, &termios, all);
+        }
+    };
+
+    let mut changed = false;
+    let mut settings = settings.into_iter();
+    while let Some(setting) = settings.next() {
+        changed |= apply_setting(&mut output, &terminal, &mut termios, setting, &mut settings)?;
+    }
+    if changed {
+        terminal.set_attr(&termios)?;
+    }
+
+    Ok(())
+}
+
+// apply a single setting (taking any value it needs from `rest`), returning whether it changed
+// `termios`
+fn apply_setting<'a, W, I>(
+    output: &mut W,
+    terminal: &Terminal,
+    termios: &mut termios,
+    setting: &str,
+    rest: &mut I,
+) -> Result<bool>
+where
+    W: Write,
+    I: Iterator<Item = &'a str>,
+{
+    let mut value = || {
+        rest.next()
+            .ok_or_else(|| SttyError::MissingArgument(setting.to_owned()))
+    };
+
+    match setting {
+        "size" => {
+            let size = terminal.size()?;
+            writeln!(output, "{} {}", size.ws_row, size.ws_col)?;
+            return Ok(false);
+        }
+        "speed" => {
+            match speed(termios) {
+                Some(baud) => writeln!(output, "{}", baud)?,
+                None => writeln!(output, "unknown")?,
+            }
+            return Ok(false);
+        }
+        "rows" | "cols" | "columns" => {
+            let count = parse_number(value()?)?;
+            let mut size = terminal.size()?;
+            if setting == "rows" {
+                size.ws_row = count;
+            } else {
+                size.ws_col = count;
+            }
+            terminal.set_size(&size)?;
+            return Ok(false);
+        }
+        "min" => termios.c_cc[libc::VMIN] = parse_number(value()?)?,
+        "time" => termios.c_cc[libc::VTIME] = parse_number(value()?)?,
+        "raw" => {
+            apply_preset(termios, RAW);
+            termios.c_cc[libc::VMIN] = 1;
+            termios.c_cc[libc::VTIME] = 0;
+        }
+        "-raw" | "cooked" => {
+            apply_preset(termios, COOKED);
+            // on some systems, min and time share their slots with eof and eol
+            set_default_chars(termios, Some(&["eof", "eol"][..]));
+        }
+        "sane" => make_sane(termios),
+        _ => {
+            if let Some(&(_, index, _)) = CHARS.iter().find(|&&(name, _, _)| name == setting) {
+                let value = value()?;
+                termios.c_cc[index] = match parse_char(value) {
+                    Some(c) => c,
+                    None => return Err(SttyError::InvalidArgument(value.to_owned()).into()),
+                };
+            } else if setting.bytes().all(|b| b.is_ascii_digit()) {
+                let baud: u32 = parse_number(setting)?;
+                let speed = match SPEEDS.iter().find(|&&(_, b)| b == baud) {
+                    Some(&(speed, _)) => speed,
+                    None => return Err(SttyError::InvalidArgument(setting.to_owned()).into()),
+                };
+                unsafe {
+                    libc::cfsetispeed(termios, speed);
+                    libc::cfsetospeed(termios, speed);
+                }
+            } else if !set_flag(termios, setting) && !restore_settings(termios, setting) {
+                return Err(SttyError::InvalidArgument(setting.to_owned()).into());
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+// print the settings (or only those that differ from 'sane' unless `all` is set)
+fn print_settings<W: Write>(
+    output: &mut W,
+    terminal: &Terminal,
+    termios: &termios,
+    all: bool,
+) -> Result<()> {
+    let sane = sane_termios();
+
+    let mut header = vec![];
+    match speed(termios) {
+        Some(baud) => header.push(format!("speed {} baud;", baud)),
+        None => header.push("speed unknown;".to_owned()),
+    }
+    if all {
+        if let Ok(size) = terminal.size() {
+            header.push(format!("rows {}; columns {};", size.ws_row, size.ws_col));
+        }
+    }
+    write_wrapped(output, &header)?;
+
+    let mut chars = vec![];
+    for &(name, index, _) in CHARS {
+        let value = termios.c_cc[index];
+        if all || value != sane.c_cc[index] {
+            chars.push(format!("{} = {};", name, char_name(value)));
+        }
+    }
+    // min and time only matter in non-canonical mode
+    if all || termios.c_lflag & libc::ICANON == 0 {
+        for &(name, index) in &[("min", libc::VMIN), ("time", libc::VTIME)] {
+            chars.push(format!("{} = {};", name, termios.c_cc[index]));
+        }
+    }
+    write_wrapped(output, &chars)?;
+
+    for &group in &[Group::Control, Group::Input, Group::Output, Group::Local] {
+        let mut names = vec![];
+        for flag in FLAGS.iter().filter(|flag| flag.group == group) {
+            let current = flags(termios, group) & flag.mask;
+            let is_set = current == flag.value;
+            // multi-bit settings are only shown for the value that is set
+            if !flag.negatable && !is_set {
+                continue;
+            }
+            if all || current != flags(&sane, group) & flag.mask {
+                let prefix = if is_set { "" } else { "-" };
+                names.push(format!("{}{}", prefix, flag.name));
+            }
+        }
+        write_wrapped(output, &names)?;
+    }
+
+    Ok(())
+}
+
+// write `items` separated by spaces, starting a new line whenever the current one would get longer
+// than LINE_WIDTH
+fn write_wrapped<W: Write>(output: &mut W, items: &[String]) -> io::Result<()> {
+    if items.is_empty() {
+        return Ok(());
+    }
+
+    let mut column = 0;
+    for item in items {
+        if column > 0 && column + 1 + item.len() > LINE_WIDTH {
+            output.write_all(b"\n")?;
+            column = 0;
+        }
+        if column > 0 {
+            output.write_all(b" ")?;
+            column += 1;
+        }
+        output.write_all(item.as_bytes())?;
+        column += item.len();
+    }
+    output.write_all(b"\n")
+}
+
+#[test]
+fn stty_flags() {
+    let mut termios: termios = unsafe { mem::zeroed() };
+    assert!(set_flag(&mut termios, "echo"));
+    assert!(set_flag(&mut termios, "cs7"));
+    assert_eq!(termios.c_lflag, libc::ECHO);
+    assert_eq!(termios.c_cflag & libc::CSIZE, libc::CS7);
+
+    assert!(set_flag(&mut termios, "-echo"));
+    assert!(set_flag(&mut termios, "cs8"));
+    assert_eq!(termios.c_lflag, 0);
+    assert_eq!(termios.c_cflag & libc::CSIZE, libc::CS8);
+
+    assert!(!set_flag(&mut termios, "-cs8"));
+    assert!(!set_flag(&mut termios, "bogus"));
+
+    apply_preset(&mut termios, RAW);
+    assert_eq!(termios.c_lflag & libc::ICANON, 0);
+    let sane = sane_termios();
+    assert_ne!(sane.c_lflag & libc::ICANON, 0);
+    assert_eq!(sane.c_cc[libc::VINTR], 0x03);
+}
+
+#[test]
+fn stty_chars() {
+    assert_eq!(parse_char("^C"), Some(0x03));
+    assert_eq!(parse_char("^c"), Some(0x03));
+    assert_eq!(parse_char("^?"), Some(0x7f));
+    assert_eq!(parse_char("x"), Some(b'x'));
+    assert_eq!(parse_char("undef"), Some(VDISABLE));
+    assert_eq!(parse_char("ab"), None);
+
+    assert_eq!(char_name(0x03), "^C");
+    assert_eq!(char_name(0x1c), "^\\");
+    assert_eq!(char_name(0x7f), "^?");
+    assert_eq!(char_name(b'x'), "x");
+    assert_eq!(char_name(VDISABLE), "<undef>");
+}
+
+#[test]
+fn stty_saved() {
+    let termios = sane_termios();
+    let saved = saved_settings(&termios);
+
+    let mut restored: termios = unsafe { mem::zeroed() };
+    assert!(restore_settings(&mut restored, &saved));
+    assert_eq!(saved_settings(&restored), saved);
+    assert_eq!(restored.c_lflag, termios.c_lflag);
+
+    assert!(!restore_settings(&mut restored, "1:2:3:4"));
+    assert!(!restore_settings(&mut restored, "echo"));
+}
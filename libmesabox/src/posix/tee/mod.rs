@@ -0,0 +1,239 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use util::{self, WriteError, OUTPUT_ERROR_VAR};
+use {
+    ArgsIter, LockableWrite, MesaError, PathError, PathOperation, Result, UtilRead, UtilSetup,
+    UtilWrite, EXIT_FAILURE,
+};
+
+use clap::Arg;
+use nix::sys::signal::{self, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use std::ffi::OsStr;
+use std::fs::OpenOptions;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+pub(crate) const NAME: &str = "tee";
+pub(crate) const DESCRIPTION: &str = "Copy standard input to standard output and to files";
+
+const AFTER_HELP: &str = "
+MODE decides what happens when writing to an output fails:
+
+  warn         Report the error and keep writing to the other outputs
+  warn-nopipe  Like warn, but stop writing to a pipe without any readers without reporting it
+  exit         Report the error and exit
+  exit-nopipe  Like exit, but stop writing to a pipe without any readers without reporting it
+
+Without --output-error, the mode given by MESABOX_OUTPUT_ERROR is used.  If that is not set
+either, tee quietly exits when writing to a pipe without any readers and reports errors writing to
+other outputs like warn does.  tee stops reading its input once every output has failed.
+";
+
+const BUF_SIZE: usize = 8 * 1024;
+
+/// A file (or standard output) that the input is copied to.
+struct Output<'a> {
+    writer: Box<Write + 'a>,
+    // None for standard output
+    path: Option<PathBuf>,
+}
+
+impl<'a> Output<'a> {
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        self.writer.write_all(data)?;
+        self.writer.flush()
+    }
+
+    fn error(&self, err: io::Error) -> MesaError {
+        match self.path {
+            Some(ref path) => PathError::new(PathOperation::Write, path, err).into(),
+            None => util::output_error(err).into(),
+        }
+    }
+}
+
+struct Tee<'a> {
+    outputs: Vec<Output<'a>>,
+    // None for the default of GNU tee, which exits at a broken pipe and warns about other errors
+    mode: Option<WriteError>,
+    failed: bool,
+    // set once a broken pipe has ended tee in the default mode
+    stopped: bool,
+}
+
+impl<'a> Tee<'a> {
+    // write `data` to every output, dropping those that fail
+    fn write<E: Write>(&mut self, data: &[u8], stderr: &mut E) -> Result<()> {
+        let mut i = 0;
+        while i < self.outputs.len() {
+            let err = match self.outputs[i].write(data) {
+                Ok(()) => {
+                    i += 1;
+                    continue;
+                }
+                Err(e) => self.outputs.remove(i).error(e),
+            };
+
+            let mode = match self.mode {
+                Some(mode) => mode,
+                None if err.is_broken_pipe() => {
+                    self.stopped = true;
+                    return Ok(());
+                }
+                None => WriteError::Warn,
+            };
+            if mode.ignores(&err) {
+                continue;
+            }
+            // the error is reported here rather than returned so that MESABOX_OUTPUT_ERROR
+            // cannot override the mode by ignoring a broken pipe
+            display_msg!(stderr, "{}", err)?;
+            self.failed = true;
+            if mode.exits() {
+                return Err(MesaError::new(None, EXIT_FAILURE, None));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Ignores SIGINT (for -i) until dropped, so that running `tee` from `sh` leaves the handler of
+/// the shell alone afterwards.
+struct IgnoreInterrupt {
+    old: SigAction,
+}
+
+impl IgnoreInterrupt {
+    fn new() -> Result<Self> {
+        let ignore = SigAction::new(SigHandler::SigIgn, SaFlags::empty(), SigSet::empty());
+        let old = unsafe { signal::sigaction(Signal::SIGINT, &ignore)? };
+        Ok(Self { old: old })
+    }
+}
+
+impl Drop for IgnoreInterrupt {
+    fn drop(&mut self) {
+        let _ = unsafe { signal::sigaction(Signal::SIGINT, &self.old) };
+    }
+}
+
+pub fn execute<S, T>(setup: &mut S, args: T) -> Result<()>
+where
+    S: UtilSetup,
+    T: ArgsIter,
+{
+    let matches = {
+        let app = util_app!(NAME)
+                    .after_help(AFTER_HELP)
+                    .arg(Arg::with_name("append")
+                            .short("a")
+                            .long("append")
+                            .help("Append to the FILEs rather than overwriting them"))
+                    .arg(Arg::with_name("ignore-interrupts")
+                            .short("i")
+                            .long("ignore-interrupts")
+                            .help("Ignore the SIGINT signal"))
+                    .arg(Arg::with_name("nopipe")
+                            .short("p")
+                            .help("Do not report errors writing to pipes (the same as --output-error=warn-nopipe)"))
+                    .arg(Arg::with_name("output-error")
+                            .long("output-error")
+                            .takes_value(true)
+                            .value_name("MODE")
+                            .min_values(0)
+                            .require_equals(true)
+                            .possible_values(&["warn", "warn-nopipe", "exit", "exit-nopipe"])
+                            .overrides_with("nopipe")
+                            .help("Choose what to do when writing to an output fails (warn-nopipe if MODE is not given)"))
+                    .arg(Arg::with_name("FILE")
+                            .index(1)
+                            .multiple(true));
+
+        app.get_matches_from_safe(args)?
+    };
+
+    let mode = if matches.is_present("nopipe") {
+        Some(WriteError::WarnNoPipe)
+    } else if matches.is_present("output-error") {
+        let mode = matches.value_of("output-error").and_then(WriteError::from_name);
+        Some(mode.unwrap_or(WriteError::WarnNoPipe))
+    } else if setup.env().get(OUTPUT_ERROR_VAR).is_some() {
+        Some(setup.write_error())
+    } else {
+        None
+    };
+    let append = matches.is_present("append");
+
+    let _ignore = if matches.is_present("ignore-interrupts") {
+        Some(IgnoreInterrupt::new()?)
+    } else {
+        None
+    };
+
+    let current_dir = setup.current_dir().map(|p| p.to_owned());
+    let buffer_size = setup.buffer_size().unwrap_or(BUF_SIZE);
+    let (input, output, error) = setup.stdio();
+    let mut stderr = error.lock()?;
+
+    let mut tee = Tee {
+        outputs: vec![Output {
+            writer: output.lock_dyn()?,
+            path: None,
+        }],
+        mode: mode,
+        failed: false,
+        stopped: false,
+    };
+
+    if let Some(names) = matches.values_of_os("FILE") {
+        for name in names {
+            match open_file(&current_dir, name, append) {
+                Ok(output) => tee.outputs.push(output),
+                Err(e) => {
+                    let err = PathError::new(PathOperation::OpenWrite, name, e);
+                    display_msg!(stderr, "{}", err)?;
+                    tee.failed = true;
+                }
+            }
+        }
+    }
+
+    let mut input = input.lock()?;
+    let mut buffer = vec![0; buffer_size];
+    // there is no point in reading input that cannot be written anywhere
+    while !tee.outputs.is_empty() && !tee.stopped {
+        let len = match input.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(PathError::new(PathOperation::Read, "standard input", e).into()),
+        };
+        tee.write(&buffer[..len], &mut stderr)?;
+    }
+
+    if tee.failed {
+        Err(MesaError::new(None, EXIT_FAILURE, None))
+    } else {
+        Ok(())
+    }
+}
+
+fn open_file<'a>(current_dir: &Option<PathBuf>, name: &OsStr, append: bool) -> io::Result<Output<'a>> {
+    let path = util::actual_path(current_dir, name);
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(append)
+        .truncate(!append)
+        .open(&path)?;
+    Ok(Output {
+        writer: Box::new(file),
+        path: Some(PathBuf::from(name)),
+    })
+}
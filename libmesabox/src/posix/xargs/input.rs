@@ -0,0 +1,220 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+use std::result::Result as StdResult;
+
+#[derive(Debug, Fail)]
+pub enum InputError {
+    #[fail(
+        display = "unmatched {} quote; by default quotes are special to xargs unless you use the -0 option",
+        _0
+    )]
+    UnmatchedQuote(&'static str),
+
+    #[fail(display = "{}", _0)]
+    Io(#[cause] io::Error),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Separator {
+    /// Items are separated by blanks and newlines, and quotes and backslashes are processed
+    Blank,
+    /// Every line is a single item (after quote processing), as used by `-I`
+    Line,
+    /// Items are separated by the given byte, and no other processing occurs
+    Byte(u8),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Item {
+    pub arg: Vec<u8>,
+    /// Whether this item ends a logical input line (for `-L`)
+    pub end_of_line: bool,
+}
+
+/// Splits input into the items that will be passed to the command, following the rules used
+/// by findutils.
+pub struct ArgReader<R: BufRead> {
+    reader: R,
+    separator: Separator,
+    pending: VecDeque<Item>,
+}
+
+impl<R: BufRead> ArgReader<R> {
+    pub fn new(reader: R, separator: Separator) -> Self {
+        Self {
+            reader: reader,
+            separator: separator,
+            pending: VecDeque::new(),
+        }
+    }
+
+    pub fn next_item(&mut self) -> StdResult<Option<Item>, InputError> {
+        if let Separator::Byte(delim) = self.separator {
+            let mut arg = vec![];
+            let n = self.reader.read_until(delim, &mut arg).map_err(InputError::Io)?;
+            if n == 0 {
+                return Ok(None);
+            }
+            if arg.last() == Some(&delim) {
+                arg.pop();
+            }
+            return Ok(Some(Item {
+                arg: arg,
+                end_of_line: true,
+            }));
+        }
+
+        while self.pending.is_empty() {
+            let mut line = vec![];
+            let n = self.reader.read_until(b'\n', &mut line).map_err(InputError::Io)?;
+            if n == 0 {
+                return Ok(None);
+            }
+            if line.last() == Some(&b'\n') {
+                line.pop();
+            }
+            self.split_line(&line)?;
+        }
+
+        Ok(self.pending.pop_front())
+    }
+
+    fn split_line(&mut self, line: &[u8]) -> StdResult<(), InputError> {
+        if self.separator == Separator::Line {
+            let start = line.iter().position(|&b| !is_blank(b)).unwrap_or(line.len());
+            if start < line.len() {
+                let mut items = split_items(&line[start..], false)?;
+                if let Some(arg) = items.pop() {
+                    self.pending.push_back(Item {
+                        arg: arg,
+                        end_of_line: true,
+                    });
+                }
+            }
+            return Ok(());
+        }
+
+        // a line that ends with a blank is logically continued on the next line
+        let continued = line.last().map(|&b| is_blank(b)).unwrap_or(false);
+        let items = split_items(line, true)?;
+        let count = items.len();
+        for (i, arg) in items.into_iter().enumerate() {
+            self.pending.push_back(Item {
+                arg: arg,
+                end_of_line: i + 1 == count && !continued,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn is_blank(byte: u8) -> bool {
+    byte == b' ' || byte == b'\t'
+}
+
+/// Split a line into items, processing quotes and backslashes.  If `split_blanks` is false, the
+/// entire line forms a single item.
+fn split_items(line: &[u8], split_blanks: bool) -> StdResult<Vec<Vec<u8>>, InputError> {
+    let mut items = vec![];
+    let mut current = vec![];
+    let mut in_item = false;
+    let mut quote = None;
+
+    let mut iter = line.iter();
+    while let Some(&byte) = iter.next() {
+        if let Some(q) = quote {
+            if byte == q {
+                quote = None;
+            } else {
+                current.push(byte);
+            }
+            continue;
+        }
+
+        match byte {
+            b'\'' | b'"' => {
+                quote = Some(byte);
+                in_item = true;
+            }
+            b'\\' => {
+                current.push(*iter.next().unwrap_or(&b'\\'));
+                in_item = true;
+            }
+            b' ' | b'\t' if split_blanks => {
+                if in_item {
+                    items.push(current.split_off(0));
+                    in_item = false;
+                }
+            }
+            _ => {
+                current.push(byte);
+                in_item = true;
+            }
+        }
+    }
+
+    match quote {
+        Some(b'\'') => Err(InputError::UnmatchedQuote("single")),
+        Some(_) => Err(InputError::UnmatchedQuote("double")),
+        None => {
+            if in_item {
+                items.push(current);
+            }
+            Ok(items)
+        }
+    }
+}
+
+#[cfg(test)]
+fn read_all(input: &str, separator: Separator) -> Vec<(String, bool)> {
+    let mut reader = ArgReader::new(input.as_bytes(), separator);
+    let mut result = vec![];
+    while let Some(item) = reader.next_item().unwrap() {
+        result.push((String::from_utf8(item.arg).unwrap(), item.end_of_line));
+    }
+    result
+}
+
+#[test]
+fn blank_separated() {
+    let items = read_all("a  b\n\n'c d' \"e'f\"\\ g \nh", Separator::Blank);
+    let expected = vec![
+        ("a".to_owned(), false),
+        ("b".to_owned(), true),
+        ("c d".to_owned(), false),
+        ("e'f g".to_owned(), false),
+        ("h".to_owned(), true),
+    ];
+    assert_eq!(items, expected);
+}
+
+#[test]
+fn line_separated() {
+    let items = read_all("  a b  \n\n'c'd\n", Separator::Line);
+    assert_eq!(items, vec![("a b  ".to_owned(), true), ("cd".to_owned(), true)]);
+}
+
+#[test]
+fn byte_separated() {
+    let items = read_all("a b\0\0c'\0", Separator::Byte(0));
+    let expected = vec![
+        ("a b".to_owned(), true),
+        ("".to_owned(), true),
+        ("c'".to_owned(), true),
+    ];
+    assert_eq!(items, expected);
+}
+
+#[test]
+fn unmatched_quotes() {
+    let mut reader = ArgReader::new(&b"a 'b\nc'"[..], Separator::Blank);
+    assert!(reader.next_item().is_err());
+}
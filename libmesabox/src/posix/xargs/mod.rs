@@ -0,0 +1,590 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use util::{self, ExitCode, RawObject, RawObjectWrapper};
+use {ArgsIter, MesaError, Result, UtilRead, UtilSetup, UtilWrite};
+
+use clap::{AppSettings, Arg, ArgMatches};
+use libc;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Read, Write};
+use std::mem;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::process::ExitStatusExt;
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::result::Result as StdResult;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use self::input::{ArgReader, Item, Separator};
+
+mod input;
+
+pub(crate) const NAME: &str = "xargs";
+pub(crate) const DESCRIPTION: &str = "Build and execute command lines from standard input";
+
+// these match the exit codes used by findutils
+const EXIT_COMMAND_FAILED: ExitCode = 123;
+const EXIT_COMMAND_255: ExitCode = 124;
+const EXIT_COMMAND_KILLED: ExitCode = 125;
+const EXIT_CANNOT_INVOKE: ExitCode = 126;
+const EXIT_ENOENT: ExitCode = 127;
+
+// like findutils, don't use more than this by default even if the system allows it
+const DEFAULT_MAX_CHARS: usize = 128 * 1024;
+// space left for the command's own use of the argument area
+const ARG_HEADROOM: usize = 2048;
+
+// how long to sleep between checks for finished children when running in parallel
+const REAP_INTERVAL: u64 = 10;
+
+#[derive(Debug, Fail)]
+enum XargsError {
+    #[fail(display = "invalid input delimiter specification '{}'", _0)]
+    InvalidDelimiter(String),
+
+    #[fail(display = "argument line too long")]
+    ArgumentTooLong,
+
+    #[fail(display = "{}: {}", _0, _1)]
+    Exec(String, #[cause] io::Error),
+}
+
+struct Options {
+    separator: Separator,
+    max_args: Option<usize>,
+    max_lines: Option<usize>,
+    replace: Option<OsString>,
+    max_procs: usize,
+    max_chars: usize,
+    no_run_if_empty: bool,
+    trace: bool,
+    current_dir: Option<PathBuf>,
+}
+
+pub fn execute<S, T>(setup: &mut S, args: T) -> Result<()>
+where
+    S: UtilSetup,
+    T: ArgsIter,
+{
+    let matches = {
+        let app = util_app!(NAME)
+                    .setting(AppSettings::TrailingVarArg)
+                    .arg(Arg::with_name("null")
+                            .short("0")
+                            .long("null")
+                            .overrides_with("delimiter")
+                            .help("Input items are terminated by NUL rather than whitespace, and quotes and backslashes are not special"))
+                    .arg(Arg::with_name("delimiter")
+                            .short("d")
+                            .long("delimiter")
+                            .takes_value(true)
+                            .value_name("DELIM")
+                            .overrides_with("null")
+                            .help("Input items are terminated by DELIM, and quotes and backslashes are not special"))
+                    .arg(Arg::with_name("max-args")
+                            .short("n")
+                            .long("max-args")
+                            .takes_value(true)
+                            .value_name("MAX_ARGS")
+                            .validator(is_valid_count)
+                            .help("Use at most MAX_ARGS arguments per command line"))
+                    .arg(Arg::with_name("max-lines")
+                            .short("L")
+                            .long("max-lines")
+                            .takes_value(true)
+                            .value_name("MAX_LINES")
+                            .validator(is_valid_count)
+                            .help("Use at most MAX_LINES non-blank input lines per command line"))
+                    .arg(Arg::with_name("replace")
+                            .short("I")
+                            .long("replace")
+                            .takes_value(true)
+                            .value_name("REPLACE")
+                            .help("Replace REPLACE in the initial arguments with each input line"))
+                    .arg(Arg::with_name("max-procs")
+                            .short("P")
+                            .long("max-procs")
+                            .takes_value(true)
+                            .value_name("MAX_PROCS")
+                            .validator(is_valid_procs)
+                            .help("Run up to MAX_PROCS processes at a time (0 means as many as possible)"))
+                    .arg(Arg::with_name("max-chars")
+                            .short("s")
+                            .long("max-chars")
+                            .takes_value(true)
+                            .value_name("MAX_CHARS")
+                            .validator(is_valid_count)
+                            .help("Limit the length of each command line to MAX_CHARS"))
+                    .arg(Arg::with_name("no-run-if-empty")
+                            .short("r")
+                            .long("no-run-if-empty")
+                            .help("Do not run the command if there is no input"))
+                    .arg(Arg::with_name("verbose")
+                            .short("t")
+                            .long("verbose")
+                            .help("Print each command line to standard error before running it"))
+                    .arg(Arg::with_name("COMMAND")
+                            .index(1)
+                            .multiple(true));
+
+        app.get_matches_from_safe(args)?
+    };
+
+    let env: Vec<(OsString, OsString)> = setup.env().collect();
+
+    let mut command: Vec<OsString> = match matches.values_of_os("COMMAND") {
+        Some(values) => values.map(|v| v.to_owned()).collect(),
+        None => vec![OsString::from("echo")],
+    };
+
+    let options = Options {
+        separator: separator(&matches)?,
+        max_args: count_value(&matches, "max-args"),
+        max_lines: count_value(&matches, "max-lines"),
+        replace: matches.value_of_os("replace").map(|v| v.to_owned()),
+        max_procs: match count_value(&matches, "max-procs") {
+            Some(0) => usize::max_value(),
+            Some(procs) => procs,
+            None => 1,
+        },
+        max_chars: max_chars(&matches, &env),
+        no_run_if_empty: matches.is_present("no-run-if-empty"),
+        trace: matches.is_present("verbose"),
+        current_dir: setup.current_dir().map(|p| p.to_owned()),
+    };
+
+    // NOTE: we need to duplicate the fds as from_raw_fd() takes ownership
+    let stdout_fd = setup.output().raw_object();
+    let stderr_fd = setup.error().raw_object();
+
+    let (input, stdout, stderr) = setup.stdio();
+    let mut xargs = Xargs {
+        stdout: stdout.lock()?,
+        stderr: stderr.lock()?,
+        options: &options,
+        env: env,
+        stdout_fd: stdout_fd,
+        stderr_fd: stderr_fd,
+        children: vec![],
+        exitcode: 0,
+        stopped: false,
+    };
+
+    let mut reader = ArgReader::new(input.lock()?, options.separator);
+    let result = xargs.run(&mut command, &mut reader);
+
+    // let any commands that are still running finish even if something went wrong
+    let wait_result = xargs.wait_all();
+    result?;
+    wait_result?;
+
+    if xargs.exitcode == 0 {
+        Ok(())
+    } else {
+        Err(MesaError::new(None, xargs.exitcode, None))
+    }
+}
+
+fn is_valid_count(val: String) -> StdResult<(), String> {
+    match val.parse::<usize>() {
+        Ok(0) => Err(format!("value must be at least 1: '{}'", val)),
+        Ok(_) => Ok(()),
+        Err(_) => Err(format!("invalid number '{}'", val)),
+    }
+}
+
+fn is_valid_procs(val: String) -> StdResult<(), String> {
+    val.parse::<usize>()
+        .map(|_| ())
+        .map_err(|_| format!("invalid number '{}'", val))
+}
+
+fn count_value(matches: &ArgMatches, name: &str) -> Option<usize> {
+    matches.value_of(name).map(|v| v.parse().unwrap())
+}
+
+fn separator(matches: &ArgMatches) -> Result<Separator> {
+    if matches.is_present("null") {
+        return Ok(Separator::Byte(0));
+    }
+    if let Some(delim) = matches.value_of_lossy("delimiter") {
+        return parse_delimiter(&delim)
+            .map(Separator::Byte)
+            .ok_or_else(|| XargsError::InvalidDelimiter(delim.into_owned()).into());
+    }
+    if matches.is_present("replace") {
+        Ok(Separator::Line)
+    } else {
+        Ok(Separator::Blank)
+    }
+}
+
+/// Parse the argument to `-d`, which is either a single character or an escape sequence.
+fn parse_delimiter(delim: &str) -> Option<u8> {
+    let bytes = delim.as_bytes();
+    if bytes.len() == 1 {
+        return Some(bytes[0]);
+    }
+    if bytes.len() < 2 || bytes[0] != b'\\' {
+        return None;
+    }
+
+    let rest = &delim[2..];
+    match bytes[1] {
+        b'a' if rest.is_empty() => Some(b'\x07'),
+        b'b' if rest.is_empty() => Some(b'\x08'),
+        b'f' if rest.is_empty() => Some(b'\x0c'),
+        b'n' if rest.is_empty() => Some(b'\n'),
+        b'r' if rest.is_empty() => Some(b'\r'),
+        b't' if rest.is_empty() => Some(b'\t'),
+        b'v' if rest.is_empty() => Some(b'\x0b'),
+        b'\\' if rest.is_empty() => Some(b'\\'),
+        b'x' => u8::from_str_radix(rest, 16).ok(),
+        b'0'...b'7' => u8::from_str_radix(&delim[1..], 8).ok(),
+        _ => None,
+    }
+}
+
+/// Determine the maximum length of a command line, taking into account the space used by the
+/// environment.
+fn max_chars(matches: &ArgMatches, env: &[(OsString, OsString)]) -> usize {
+    let arg_max = match unsafe { libc::sysconf(libc::_SC_ARG_MAX) } {
+        size if size > 0 => size as usize,
+        _ => DEFAULT_MAX_CHARS,
+    };
+    let env_size: usize = env
+        .iter()
+        .map(|&(ref key, ref value)| key.len() + value.len() + 2 + mem::size_of::<usize>())
+        .sum();
+    let limit = arg_max.saturating_sub(env_size).saturating_sub(ARG_HEADROOM);
+
+    match count_value(matches, "max-chars") {
+        Some(chars) => chars.min(limit),
+        None => DEFAULT_MAX_CHARS.min(limit),
+    }
+}
+
+/// The space taken up by an argument on the command line (including its terminating NUL).
+fn arg_size(arg: &OsStr) -> usize {
+    arg.len() + 1
+}
+
+/// A running command.  If xargs has no file descriptors to hand to the command for its output (as
+/// when run by `run_util()`), the output is collected by threads and written to the outputs of
+/// xargs once the command finishes.
+struct Running {
+    name: OsString,
+    child: Child,
+    stdout: Option<JoinHandle<io::Result<Vec<u8>>>>,
+    stderr: Option<JoinHandle<io::Result<Vec<u8>>>>,
+}
+
+struct Xargs<'a, O, E>
+where
+    O: Write,
+    E: Write,
+{
+    stdout: O,
+    stderr: E,
+    options: &'a Options,
+    env: Vec<(OsString, OsString)>,
+    stdout_fd: Option<RawObject>,
+    stderr_fd: Option<RawObject>,
+    children: Vec<Running>,
+    exitcode: ExitCode,
+    // set once a command has failed in a way that means no more should be run
+    stopped: bool,
+}
+
+impl<'a, O, E> Xargs<'a, O, E>
+where
+    O: Write,
+    E: Write,
+{
+    fn run<R>(&mut self, command: &mut Vec<OsString>, reader: &mut ArgReader<R>) -> Result<()>
+    where
+        R: io::BufRead,
+    {
+        let options = self.options;
+        if let Some(ref replace) = options.replace {
+            return self.run_replace(command, replace, reader);
+        }
+
+        let base_len = command.len();
+        let base_size: usize = command.iter().map(|arg| arg_size(arg)).sum();
+        if base_size > self.options.max_chars {
+            return Err(XargsError::ArgumentTooLong.into());
+        }
+
+        let mut size = base_size;
+        let mut lines = 0;
+        let mut ran = false;
+
+        while let Some(Item { arg, end_of_line }) = reader.next_item()? {
+            let arg = util::os_str_from_bytes(&arg).into_owned();
+            let len = arg_size(&arg);
+            if base_size + len > self.options.max_chars {
+                return Err(XargsError::ArgumentTooLong.into());
+            }
+
+            if size + len > self.options.max_chars {
+                self.spawn(command)?;
+                ran = true;
+                command.truncate(base_len);
+                size = base_size;
+                lines = 0;
+            }
+            if self.stopped {
+                return Ok(());
+            }
+
+            command.push(arg);
+            size += len;
+            if end_of_line {
+                lines += 1;
+            }
+
+            let args = command.len() - base_len;
+            let full = self.options.max_args.map(|max| args >= max).unwrap_or(false)
+                || self.options.max_lines.map(|max| lines >= max).unwrap_or(false);
+            if full {
+                self.spawn(command)?;
+                ran = true;
+                command.truncate(base_len);
+                size = base_size;
+                lines = 0;
+                if self.stopped {
+                    return Ok(());
+                }
+            }
+        }
+
+        if command.len() > base_len || (!ran && !self.options.no_run_if_empty) {
+            self.spawn(command)?;
+        }
+        Ok(())
+    }
+
+    /// Run the command once for every input line, replacing `replace` in the initial arguments.
+    fn run_replace<R>(
+        &mut self,
+        command: &[OsString],
+        replace: &OsStr,
+        reader: &mut ArgReader<R>,
+    ) -> Result<()>
+    where
+        R: io::BufRead,
+    {
+        let replace = util::os_str_as_bytes(replace).into_owned();
+
+        while let Some(item) = reader.next_item()? {
+            let args: Vec<OsString> = command
+                .iter()
+                .map(|arg| {
+                    let arg = util::os_str_as_bytes(arg);
+                    let replaced = replace_all(&arg, &replace, &item.arg);
+                    util::os_str_from_bytes(&replaced).into_owned()
+                })
+                .collect();
+
+            let size: usize = args.iter().map(|arg| arg_size(arg)).sum();
+            if size > self.options.max_chars {
+                return Err(XargsError::ArgumentTooLong.into());
+            }
+
+            self.spawn(&args)?;
+            if self.stopped {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn spawn(&mut self, args: &[OsString]) -> Result<()> {
+        if self.options.trace {
+            let line: Vec<_> = args.iter().map(|arg| arg.to_string_lossy()).collect();
+            writeln!(self.stderr, "{}", line.join(" "))?;
+        }
+
+        while self.children.len() >= self.options.max_procs {
+            self.wait_any()?;
+        }
+        if self.stopped {
+            return Ok(());
+        }
+
+        let mut command = Command::new(&args[0]);
+        command
+            .args(&args[1..])
+            .env_clear()
+            .envs(self.env.iter().map(|&(ref key, ref value)| (key, value)))
+            .stdin(Stdio::null());
+        if let Some(ref dir) = self.options.current_dir {
+            command.current_dir(dir);
+        }
+        match self.stdout_fd {
+            Some(fd) => {
+                let fd = RawObjectWrapper::new(fd, false, true).dup()?;
+                command.stdout(unsafe { Stdio::from_raw_fd(fd.raw_value()) });
+            }
+            None => {
+                command.stdout(Stdio::piped());
+            }
+        }
+        match self.stderr_fd {
+            Some(fd) => {
+                let fd = RawObjectWrapper::new(fd, false, true).dup()?;
+                command.stderr(unsafe { Stdio::from_raw_fd(fd.raw_value()) });
+            }
+            None => {
+                command.stderr(Stdio::piped());
+            }
+        }
+
+        match command.spawn() {
+            Ok(mut child) => {
+                let stdout = child.stdout.take().map(collect_output);
+                let stderr = child.stderr.take().map(collect_output);
+                self.children.push(Running {
+                    name: args[0].clone(),
+                    child: child,
+                    stdout: stdout,
+                    stderr: stderr,
+                });
+                Ok(())
+            }
+            Err(e) => {
+                let code = if e.kind() == io::ErrorKind::NotFound {
+                    EXIT_ENOENT
+                } else {
+                    EXIT_CANNOT_INVOKE
+                };
+                let err = XargsError::Exec(args[0].to_string_lossy().into_owned(), e);
+                Err(MesaError::from(err).with_exitcode(code))
+            }
+        }
+    }
+
+    /// Wait for (at least) one of the running commands to finish.
+    fn wait_any(&mut self) -> Result<()> {
+        // with a single child there is no need to poll
+        if self.children.len() == 1 {
+            let mut running = self.children.pop().unwrap();
+            let status = running.child.wait()?;
+            return self.finish(running, status);
+        }
+
+        loop {
+            for i in 0..self.children.len() {
+                if let Some(status) = self.children[i].child.try_wait()? {
+                    let running = self.children.swap_remove(i);
+                    return self.finish(running, status);
+                }
+            }
+            thread::sleep(Duration::from_millis(REAP_INTERVAL));
+        }
+    }
+
+    fn wait_all(&mut self) -> Result<()> {
+        while !self.children.is_empty() {
+            self.wait_any()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, running: Running, status: ExitStatus) -> Result<()> {
+        if let Some(handle) = running.stdout {
+            self.stdout.write_all(&join_output(handle)?)?;
+            self.stdout.flush()?;
+        }
+        if let Some(handle) = running.stderr {
+            self.stderr.write_all(&join_output(handle)?)?;
+        }
+        self.check_status(&running.name, status)
+    }
+
+    fn check_status(&mut self, name: &OsStr, status: ExitStatus) -> Result<()> {
+        let name = name.to_string_lossy();
+        match status.code() {
+            Some(0) => {}
+            Some(255) => {
+                display_msg!(self.stderr, "{}: exited with status 255; aborting", name)?;
+                self.exitcode = EXIT_COMMAND_255;
+                self.stopped = true;
+            }
+            Some(_) => {
+                if self.exitcode == 0 {
+                    self.exitcode = EXIT_COMMAND_FAILED;
+                }
+            }
+            None => {
+                let signal = status.signal().unwrap_or(0);
+                display_msg!(self.stderr, "{}: terminated by signal {}", name, signal)?;
+                self.exitcode = EXIT_COMMAND_KILLED;
+                self.stopped = true;
+            }
+        }
+        Ok(())
+    }
+}
+
+// read everything written to `pipe` in another thread, so that a command is never blocked on a
+// full pipe while xargs waits for it to finish
+fn collect_output<R>(mut pipe: R) -> JoinHandle<io::Result<Vec<u8>>>
+where
+    R: Read + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut data = vec![];
+        pipe.read_to_end(&mut data).map(|_| data)
+    })
+}
+
+fn join_output(handle: JoinHandle<io::Result<Vec<u8>>>) -> io::Result<Vec<u8>> {
+    handle.join().unwrap_or_else(|_| {
+        Err(io::Error::new(io::ErrorKind::Other, "failed to collect the output of a command"))
+    })
+}
+
+fn replace_all(data: &[u8], from: &[u8], to: &[u8]) -> Vec<u8> {
+    if from.is_empty() {
+        return data.to_vec();
+    }
+
+    let mut result = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        if data[i..].starts_with(from) {
+            result.extend_from_slice(to);
+            i += from.len();
+        } else {
+            result.push(data[i]);
+            i += 1;
+        }
+    }
+    result
+}
+
+#[test]
+fn delimiters() {
+    assert_eq!(parse_delimiter(","), Some(b','));
+    assert_eq!(parse_delimiter("\\n"), Some(b'\n'));
+    assert_eq!(parse_delimiter("\\0"), Some(0));
+    assert_eq!(parse_delimiter("\\x41"), Some(b'A'));
+    assert_eq!(parse_delimiter("\\101"), Some(b'A'));
+    assert_eq!(parse_delimiter("ab"), None);
+    assert_eq!(parse_delimiter("\\q"), None);
+}
+
+#[test]
+fn replacement() {
+    assert_eq!(replace_all(b"a{}b{}", b"{}", b"X"), b"aXbX".to_vec());
+    assert_eq!(replace_all(b"{", b"{}", b"X"), b"{".to_vec());
+}
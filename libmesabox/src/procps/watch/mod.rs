@@ -0,0 +1,315 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use util::{self, utf8};
+use {ArgsIter, MesaError, Result, UtilSetup, UtilWrite};
+
+use chrono::Local;
+use clap::{AppSettings, Arg};
+use std::ffi::OsString;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::result::Result as StdResult;
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub(crate) const NAME: &str = "watch";
+pub(crate) const DESCRIPTION: &str = "Run a command periodically, showing its output";
+
+const DEFAULT_INTERVAL: &str = "2";
+// like procps, don't run the command more often than this
+const MIN_INTERVAL: f64 = 0.1;
+
+const CLEAR_SCREEN: &[u8] = b"\x1b[H\x1b[2J";
+const CURSOR_HOME: &[u8] = b"\x1b[H";
+const CLEAR_LINE: &[u8] = b"\x1b[K";
+const CLEAR_BELOW: &[u8] = b"\x1b[J";
+const HIGHLIGHT_START: &[u8] = b"\x1b[7m";
+const HIGHLIGHT_END: &[u8] = b"\x1b[0m";
+const BEEP: &[u8] = b"\x07";
+
+#[derive(Debug, Fail)]
+enum WatchError {
+    #[fail(display = "unable to run '{}': {}", _0, _1)]
+    Exec(String, #[cause] io::Error),
+}
+
+struct Options {
+    interval: Duration,
+    // the command line given to sh -c
+    command: OsString,
+    clear: bool,
+    differences: bool,
+    title: bool,
+    beep: bool,
+    exit_on_change: bool,
+    width: usize,
+    env: Vec<(OsString, OsString)>,
+    current_dir: Option<PathBuf>,
+}
+
+pub fn execute<S, T>(setup: &mut S, args: T) -> Result<()>
+where
+    S: UtilSetup,
+    T: ArgsIter,
+{
+    let matches = {
+        let app = util_app!(NAME)
+                    .setting(AppSettings::TrailingVarArg)
+                    .arg(Arg::with_name("interval")
+                            .short("n")
+                            .long("interval")
+                            .takes_value(true)
+                            .value_name("SECONDS")
+                            .default_value(DEFAULT_INTERVAL)
+                            .validator(is_valid_interval)
+                            .help("Run the command every SECONDS seconds"))
+                    .arg(Arg::with_name("differences")
+                            .short("d")
+                            .long("differences")
+                            .help("Highlight the parts of the output that changed since the last run"))
+                    .arg(Arg::with_name("no-clear")
+                            .long("no-clear")
+                            .help("Write each run's output after the last rather than redrawing the screen"))
+                    .arg(Arg::with_name("no-title")
+                            .short("t")
+                            .long("no-title")
+                            .help("Do not show the header line"))
+                    .arg(Arg::with_name("beep")
+                            .short("b")
+                            .long("beep")
+                            .help("Beep if the command exits with a non-zero status"))
+                    .arg(Arg::with_name("chgexit")
+                            .short("g")
+                            .long("chgexit")
+                            .help("Exit once the output of the command changes"))
+                    .arg(Arg::with_name("COMMAND")
+                            .index(1)
+                            .multiple(true)
+                            .required(true));
+
+        app.get_matches_from_safe(args)?
+    };
+
+    // like procps, the arguments are joined into a single command line for the shell
+    let mut command = OsString::new();
+    for (i, arg) in matches.values_of_os("COMMAND").unwrap().enumerate() {
+        if i > 0 {
+            command.push(" ");
+        }
+        command.push(arg);
+    }
+
+    let stdout_fd = setup.output().raw_object();
+    let options = Options {
+        // .unwrap() is fine because of the validator
+        interval: parse_interval(matches.value_of("interval").unwrap()).unwrap(),
+        command: command,
+        clear: !matches.is_present("no-clear"),
+        differences: matches.is_present("differences"),
+        title: !matches.is_present("no-title"),
+        beep: matches.is_present("beep"),
+        exit_on_change: matches.is_present("chgexit"),
+        width: util::terminal_width(setup.env(), stdout_fd),
+        env: setup.env().collect(),
+        current_dir: setup.current_dir().map(|p| p.to_owned()),
+    };
+
+    let (_, output, error) = setup.stdio();
+    let mut output = output.lock()?;
+    let mut err_stream = error.lock()?;
+
+    watch(&mut output, &mut err_stream, &options)
+}
+
+fn watch<O, E>(output: &mut O, err_stream: &mut E, options: &Options) -> Result<()>
+where
+    O: Write,
+    E: Write,
+{
+    let mut previous: Option<Vec<u8>> = None;
+    let mut next_run = Instant::now();
+    loop {
+        let result = run_command(options)?;
+        let changed = match previous {
+            Some(ref previous) => *previous != result.stdout,
+            None => false,
+        };
+
+        let mut screen = vec![];
+        if options.clear {
+            // when highlighting, the old output is overwritten in place to avoid flickering
+            if options.differences && previous.is_some() {
+                screen.extend_from_slice(CURSOR_HOME);
+            } else {
+                screen.extend_from_slice(CLEAR_SCREEN);
+            }
+        }
+        if options.title {
+            write_title(&mut screen, options)?;
+        }
+        match previous {
+            Some(ref previous) if options.differences => {
+                highlight_differences(&mut screen, previous, &result.stdout, options.clear);
+            }
+            _ => screen.extend_from_slice(&result.stdout),
+        }
+        if options.clear && options.differences {
+            screen.extend_from_slice(CLEAR_BELOW);
+        }
+        if options.beep && !result.status.success() {
+            screen.extend_from_slice(BEEP);
+        }
+
+        output.write_all(&screen)?;
+        output.flush()?;
+        err_stream.write_all(&result.stderr)?;
+        err_stream.flush()?;
+
+        if options.exit_on_change && changed {
+            return Ok(());
+        }
+        previous = Some(result.stdout);
+
+        // the interval is measured from the start of each run so that slow commands don't make
+        // the runs drift, but if a run took longer than the interval the next starts right away
+        next_run += options.interval;
+        let now = Instant::now();
+        if next_run > now {
+            thread::sleep(next_run - now);
+        } else {
+            next_run = now;
+        }
+    }
+}
+
+fn run_command(options: &Options) -> Result<Output> {
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(&options.command)
+        .env_clear()
+        .envs(options.env.iter().map(|&(ref key, ref value)| (key, value)))
+        .stdin(Stdio::null());
+    if let Some(ref dir) = options.current_dir {
+        command.current_dir(dir);
+    }
+
+    command.output().map_err(|e| {
+        let err = WatchError::Exec(options.command.to_string_lossy().into_owned(), e);
+        MesaError::from(err)
+    })
+}
+
+// write the interval and command at the left and the current time at the right of the screen
+fn write_title<W: Write>(output: &mut W, options: &Options) -> Result<()> {
+    let interval = options.interval.as_secs() as f64
+        + f64::from(options.interval.subsec_nanos()) / 1e9;
+    let left = format!("Every {:.1}s: {}", interval, options.command.to_string_lossy());
+    let right = Local::now().format("%a %b %e %H:%M:%S %Y").to_string();
+
+    let used = left.chars().count() + right.chars().count();
+    let padding = if used < options.width {
+        options.width - used
+    } else {
+        1
+    };
+    writeln!(output, "{}{:pad$}{}\n", left, "", right, pad = padding)?;
+    Ok(())
+}
+
+// write `new`, highlighting the characters that differ from those at the same position in `old`
+fn highlight_differences(output: &mut Vec<u8>, old: &[u8], new: &[u8], clear_lines: bool) {
+    let mut old_lines = old.split(|&byte| byte == b'\n');
+    let mut new_lines = new.split(|&byte| byte == b'\n').peekable();
+    while let Some(line) = new_lines.next() {
+        let old_chars = characters(old_lines.next().unwrap_or(b""));
+        let mut highlighted = false;
+        for (i, ch) in characters(line).into_iter().enumerate() {
+            let differs = old_chars.get(i).map(|&old| old != ch).unwrap_or(true);
+            if differs != highlighted {
+                output.extend_from_slice(if differs {
+                    HIGHLIGHT_START
+                } else {
+                    HIGHLIGHT_END
+                });
+                highlighted = differs;
+            }
+            output.extend_from_slice(ch);
+        }
+        if highlighted {
+            output.extend_from_slice(HIGHLIGHT_END);
+        }
+
+        // split() yields an empty piece after a trailing newline
+        if new_lines.peek().is_some() {
+            if clear_lines {
+                output.extend_from_slice(CLEAR_LINE);
+            }
+            output.push(b'\n');
+        }
+    }
+}
+
+// split `line` into characters, treating each byte of an invalid sequence as a character
+fn characters(line: &[u8]) -> Vec<&[u8]> {
+    let mut result = vec![];
+    for chunk in utf8::chunks(line) {
+        let valid = chunk.valid.as_bytes();
+        let mut indices = chunk.valid.char_indices().map(|(i, _)| i).peekable();
+        while let Some(start) = indices.next() {
+            let end = indices.peek().cloned().unwrap_or(valid.len());
+            result.push(&valid[start..end]);
+        }
+        for i in 0..chunk.invalid.len() {
+            result.push(&chunk.invalid[i..i + 1]);
+        }
+    }
+    result
+}
+
+fn parse_interval(val: &str) -> Option<Duration> {
+    match val.parse::<f64>() {
+        Ok(secs) if secs.is_finite() && secs >= 0.0 => {
+            let secs = secs.max(MIN_INTERVAL);
+            Some(Duration::new(secs.trunc() as u64, (secs.fract() * 1e9) as u32))
+        }
+        _ => None,
+    }
+}
+
+fn is_valid_interval(val: String) -> StdResult<(), String> {
+    match parse_interval(&val) {
+        Some(_) => Ok(()),
+        None => Err(format!("invalid interval '{}'", val)),
+    }
+}
+
+#[test]
+fn intervals() {
+    assert_eq!(parse_interval("2"), Some(Duration::from_secs(2)));
+    assert_eq!(parse_interval("0.5"), Some(Duration::from_millis(500)));
+    assert_eq!(parse_interval("0"), parse_interval("0.1"));
+    assert_eq!(parse_interval("-1"), None);
+    assert_eq!(parse_interval("abc"), None);
+}
+
+#[test]
+fn differences() {
+    let mut output = vec![];
+    highlight_differences(&mut output, b"abc\nxyz\n", b"abd\nxyz\nnew\n", false);
+    assert_eq!(
+        output,
+        b"ab\x1b[7md\x1b[0m\nxyz\n\x1b[7mnew\x1b[0m\n".to_vec()
+    );
+
+    let mut output = vec![];
+    highlight_differences(&mut output, "añb".as_bytes(), "aéb".as_bytes(), false);
+    assert_eq!(output, "a\x1b[7mé\x1b[0mb".as_bytes().to_vec());
+}
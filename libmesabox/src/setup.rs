@@ -1,6 +1,8 @@
-use super::{LockError, LockableRead, LockableWrite, UtilRead, UtilWrite};
+use super::{
+    LockError, LockableRead, LockableWrite, StreamInput, StreamOutput, UtilRead, UtilWrite,
+};
 use std::fs::File;
-use std::io::{self, BufRead, BufReader, BufWriter, Empty, Sink, Write};
+use std::io::{self, BufRead, BufReader, BufWriter, Empty, Read, Sink, Write};
 use std::net::TcpStream;
 use std::result::Result as StdResult;
 use util::{AsRawObject, RawObject, RawObjectWrapper, ReadableVec, UtilReadDyn, UtilWriteDyn};
@@ -65,6 +67,22 @@ impl<'a> UtilWrite<'a> for UtilWriteDyn {
     }
 }
 
+impl<'a, R: Read + Send + Sync> UtilRead<'a> for StreamInput<R> {
+    type Lock = Box<BufRead + 'a>;
+
+    fn lock<'b: 'a>(&'b mut self) -> StdResult<Self::Lock, LockError> {
+        Ok(Box::new(&mut self.0))
+    }
+}
+
+impl<'a, W: Write + Send + Sync> UtilWrite<'a> for StreamOutput<W> {
+    type Lock = Box<Write + 'a>;
+
+    fn lock<'b: 'a>(&'b mut self) -> StdResult<Self::Lock, LockError> {
+        Ok(Box::new(&mut self.0))
+    }
+}
+
 // TODO: implement for other common things like File, BufReader, etc.
 
 impl<'a, 'b> UtilRead<'a> for &'b [u8] {
@@ -0,0 +1,366 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+//! Backups of files that are about to be overwritten, as made by utilities supporting
+//! `-b`/`--backup[=CONTROL]` and `-S`/`--suffix` (like GNU `cp`, `mv`, `ln`, and `install`).
+
+use clap::{Arg, ArgMatches};
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::os_str_as_bytes;
+use EnvVars;
+
+const BACKUP: &str = "backup";
+const BACKUP_SHORT: &str = "backup-short";
+const SUFFIX: &str = "suffix";
+
+/// The environment variable that selects the backup method when `--backup` is given without one.
+pub const VERSION_CONTROL_VAR: &str = "VERSION_CONTROL";
+/// The environment variable that changes the suffix of simple backups.
+pub const SUFFIX_VAR: &str = "SIMPLE_BACKUP_SUFFIX";
+
+const DEFAULT_SUFFIX: &str = "~";
+
+const METHOD_NAMES: [(&str, BackupMethod); 8] = [
+    ("none", BackupMethod::None),
+    ("off", BackupMethod::None),
+    ("numbered", BackupMethod::Numbered),
+    ("t", BackupMethod::Numbered),
+    ("existing", BackupMethod::Existing),
+    ("nil", BackupMethod::Existing),
+    ("simple", BackupMethod::Simple),
+    ("never", BackupMethod::Simple),
+];
+
+#[derive(Fail, Debug)]
+pub enum BackupError {
+    #[fail(display = "invalid backup type '{}'", _0)]
+    InvalidMethod(String),
+
+    #[fail(display = "ambiguous backup type '{}'", _0)]
+    AmbiguousMethod(String),
+
+    #[fail(display = "cannot back up '{}': {}", _0, _1)]
+    Io(String, #[cause] io::Error),
+}
+
+/// How to name backups.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum BackupMethod {
+    /// Never make backups
+    None,
+    /// Always make numbered backups (`FILE.~N~`)
+    Numbered,
+    /// Make numbered backups of files that already have some, and simple backups otherwise
+    Existing,
+    /// Always make simple backups (`FILE` followed by the suffix)
+    Simple,
+}
+
+impl BackupMethod {
+    /// Parse one of the method names accepted by `--backup`, which may be abbreviated as long as
+    /// it stays unambiguous.
+    pub fn from_name(name: &str) -> Result<Self, BackupError> {
+        if let Some(&(_, method)) = METHOD_NAMES.iter().find(|&&(full, _)| full == name) {
+            return Ok(method);
+        }
+
+        let mut found = None;
+        for &(full, method) in METHOD_NAMES.iter() {
+            if !name.is_empty() && full.starts_with(name) {
+                match found {
+                    Some(other) if other != method => {
+                        return Err(BackupError::AmbiguousMethod(name.to_owned()))
+                    }
+                    _ => found = Some(method),
+                }
+            }
+        }
+        found.ok_or_else(|| BackupError::InvalidMethod(name.to_owned()))
+    }
+}
+
+/// Everything needed to make backups.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Backup {
+    pub method: BackupMethod,
+    pub suffix: OsString,
+}
+
+impl Backup {
+    /// Determine how to make backups using the arguments created by `backup_args()`.
+    pub fn from_matches(matches: &ArgMatches, env: &EnvVars) -> Result<Self, BackupError> {
+        let suffix = match matches.value_of_os(SUFFIX).or_else(|| env.get_nonempty(SUFFIX_VAR)) {
+            // a suffix containing a slash would put the backup in another directory
+            Some(suffix) if !os_str_as_bytes(suffix).contains(&b'/') => suffix.to_owned(),
+            _ => OsString::from(DEFAULT_SUFFIX),
+        };
+
+        // like in GNU coreutils, giving a suffix implies --backup
+        let wanted = matches.is_present(BACKUP)
+            || matches.is_present(BACKUP_SHORT)
+            || matches.is_present(SUFFIX);
+        let method = if !wanted {
+            BackupMethod::None
+        } else if let Some(name) = matches.value_of(BACKUP) {
+            BackupMethod::from_name(name)?
+        } else if let Some(name) = env.get_nonempty(VERSION_CONTROL_VAR) {
+            BackupMethod::from_name(&name.to_string_lossy())?
+        } else {
+            BackupMethod::Existing
+        };
+
+        Ok(Self {
+            method: method,
+            suffix: suffix,
+        })
+    }
+
+    /// Move `path` out of the way if it exists, returning the name of the backup (if one was
+    /// made).
+    pub fn make<P: AsRef<Path>>(&self, path: P) -> Result<Option<PathBuf>, BackupError> {
+        let path = path.as_ref();
+        self.make_backup(path, false)
+            .map_err(|e| BackupError::Io(path.display().to_string(), e))
+    }
+
+    /// Like `make()`, but the backup is made by linking `path` to it, so `path` keeps existing
+    /// until it is replaced (by renaming another file over it).  If the file system cannot link
+    /// files, `path` is moved instead.
+    pub fn link<P: AsRef<Path>>(&self, path: P) -> Result<Option<PathBuf>, BackupError> {
+        let path = path.as_ref();
+        self.make_backup(path, true)
+            .map_err(|e| BackupError::Io(path.display().to_string(), e))
+    }
+
+    fn make_backup(&self, path: &Path, keep: bool) -> io::Result<Option<PathBuf>> {
+        if self.method == BackupMethod::None || fs::symlink_metadata(path).is_err() {
+            return Ok(None);
+        }
+
+        let numbered = match self.method {
+            BackupMethod::Numbered => true,
+            BackupMethod::Existing => last_backup_number(path)? > 0,
+            _ => false,
+        };
+
+        if numbered {
+            move_to_numbered(path, keep).map(Some)
+        } else {
+            let backup = suffixed(path, &self.suffix);
+            if keep {
+                link_backup(path, &backup)?;
+            } else {
+                fs::rename(path, &backup)?;
+            }
+            Ok(Some(backup))
+        }
+    }
+}
+
+/// Create the `-b`, `--backup[=CONTROL]`, and `-S`/`--suffix` arguments.  The result should be
+/// passed to `Backup::from_matches()`.
+pub fn backup_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name(BACKUP)
+            .long(BACKUP)
+            .takes_value(true)
+            .min_values(0)
+            .require_equals(true)
+            .value_name("CONTROL")
+            .help("Make a backup of each existing destination file"),
+        Arg::with_name(BACKUP_SHORT)
+            .short("b")
+            .help("Like --backup, but does not accept an argument"),
+        Arg::with_name(SUFFIX)
+            .short("S")
+            .long(SUFFIX)
+            .takes_value(true)
+            .value_name("SUFFIX")
+            .help("Override the usual backup suffix"),
+    ]
+}
+
+fn suffixed(path: &Path, suffix: &OsStr) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn numbered(path: &Path, number: u64) -> PathBuf {
+    suffixed(path, OsStr::new(&format!(".~{}~", number)))
+}
+
+// find the highest N for which FILE.~N~ exists (or 0 if there are none)
+fn last_backup_number(path: &Path) -> io::Result<u64> {
+    let name = match path.file_name() {
+        Some(name) => os_str_as_bytes(name).into_owned(),
+        None => return Ok(0),
+    };
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let mut last: u64 = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry_name = entry?.file_name();
+        let entry_name = os_str_as_bytes(&entry_name);
+        if let Some(number) = backup_number(&name, &entry_name) {
+            last = last.max(number);
+        }
+    }
+    Ok(last)
+}
+
+fn backup_number(name: &[u8], entry: &[u8]) -> Option<u64> {
+    if !entry.starts_with(name) {
+        return None;
+    }
+    let rest = &entry[name.len()..];
+    if rest.len() < 4 || !rest.starts_with(b".~") || !rest.ends_with(b"~") {
+        return None;
+    }
+    let digits = &rest[2..rest.len() - 1];
+    if digits.is_empty() || digits[0] == b'0' || !digits.iter().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    String::from_utf8_lossy(digits).parse().ok()
+}
+
+/// Make `backup` another name for `path` (replacing any existing `backup`) so that there is never
+/// a moment when `path` does not exist, falling back to renaming `path` when that is impossible
+/// (e.g. when `backup` is on another file system).
+pub fn link_backup(path: &Path, backup: &Path) -> io::Result<()> {
+    match fs::remove_file(backup) {
+        Ok(()) => {}
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    fs::hard_link(path, backup).or_else(|_| fs::rename(path, backup))
+}
+
+// another process could make a backup of the same file at the same time, so the name is claimed by
+// creating a hard link (which fails if the name is taken) rather than by checking that it is free
+fn move_to_numbered(path: &Path, keep: bool) -> io::Result<PathBuf> {
+    let mut number = last_backup_number(path)? + 1;
+    let is_dir = fs::symlink_metadata(path)?.is_dir();
+    loop {
+        let backup = numbered(path, number);
+        if is_dir {
+            // directories cannot be hard linked
+            if fs::symlink_metadata(&backup).is_err() {
+                fs::rename(path, &backup)?;
+                return Ok(backup);
+            }
+        } else {
+            match fs::hard_link(path, &backup) {
+                Ok(()) => {
+                    if !keep {
+                        fs::remove_file(path)?;
+                    }
+                    return Ok(backup);
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e),
+            }
+        }
+        number += 1;
+    }
+}
+
+#[test]
+fn backup_method_names() {
+    assert_eq!(BackupMethod::from_name("t").unwrap(), BackupMethod::Numbered);
+    assert_eq!(BackupMethod::from_name("nil").unwrap(), BackupMethod::Existing);
+    assert_eq!(BackupMethod::from_name("num").unwrap(), BackupMethod::Numbered);
+    assert_eq!(BackupMethod::from_name("ne").unwrap(), BackupMethod::Simple);
+    assert_eq!(BackupMethod::from_name("o").unwrap(), BackupMethod::None);
+    match BackupMethod::from_name("n") {
+        Err(BackupError::AmbiguousMethod(_)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    match BackupMethod::from_name("bogus") {
+        Err(BackupError::InvalidMethod(_)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn backup_numbers() {
+    assert_eq!(backup_number(b"file", b"file.~1~"), Some(1));
+    assert_eq!(backup_number(b"file", b"file.~12~"), Some(12));
+    assert_eq!(backup_number(b"file", b"file.~~"), None);
+    assert_eq!(backup_number(b"file", b"file.~01~"), None);
+    assert_eq!(backup_number(b"file", b"file.~1a~"), None);
+    assert_eq!(backup_number(b"file", b"other.~1~"), None);
+    assert_eq!(backup_number(b"file", b"file~"), None);
+}
+
+#[cfg(feature = "tempfile")]
+#[test]
+fn backup_methods() {
+    use tempfile;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    let file = dir.join("file");
+    let backup = |method| Backup {
+        method: method,
+        suffix: OsString::from("~"),
+    };
+
+    // nothing to back up
+    assert_eq!(backup(BackupMethod::Simple).make(&file).unwrap(), None);
+
+    fs::write(&file, "1").unwrap();
+    assert_eq!(backup(BackupMethod::None).make(&file).unwrap(), None);
+    assert!(file.exists());
+
+    // existing acts like simple until there are numbered backups
+    assert_eq!(backup(BackupMethod::Existing).make(&file).unwrap(), Some(dir.join("file~")));
+    assert!(!file.exists());
+
+    fs::write(&file, "2").unwrap();
+    assert_eq!(backup(BackupMethod::Numbered).make(&file).unwrap(), Some(dir.join("file.~1~")));
+    fs::write(&file, "3").unwrap();
+    assert_eq!(backup(BackupMethod::Existing).make(&file).unwrap(), Some(dir.join("file.~2~")));
+    fs::write(&file, "4").unwrap();
+    assert_eq!(backup(BackupMethod::Simple).make(&file).unwrap(), Some(dir.join("file~")));
+
+    assert_eq!(fs::read(dir.join("file~")).unwrap(), b"4");
+    assert_eq!(fs::read(dir.join("file.~1~")).unwrap(), b"2");
+    assert_eq!(fs::read(dir.join("file.~2~")).unwrap(), b"3");
+}
+
+#[cfg(feature = "tempfile")]
+#[test]
+fn backup_links() {
+    use tempfile;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    let file = dir.join("file");
+    let backup = |method| Backup {
+        method: method,
+        suffix: OsString::from(".bak"),
+    };
+
+    fs::write(&file, "1").unwrap();
+    fs::write(dir.join("file.bak"), "0").unwrap();
+    assert_eq!(backup(BackupMethod::Simple).link(&file).unwrap(), Some(dir.join("file.bak")));
+    assert_eq!(backup(BackupMethod::Numbered).link(&file).unwrap(), Some(dir.join("file.~1~")));
+
+    // the original stays where it is
+    assert_eq!(fs::read(&file).unwrap(), b"1");
+    assert_eq!(fs::read(dir.join("file.bak")).unwrap(), b"1");
+    assert_eq!(fs::read(dir.join("file.~1~")).unwrap(), b"1");
+}
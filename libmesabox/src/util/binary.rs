@@ -0,0 +1,79 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+//! Refusing to print binary files to a terminal (for options like `head --binary-check`).
+//!
+//! As with `grep`, input is considered binary if its first block contains a NUL byte.  The check
+//! is only made when asked for and when standard output is a terminal, so output that goes
+//! anywhere else is never affected.
+
+use clap::{Arg, ArgMatches};
+use std::io::{self, Read};
+
+use super::{is_tty, RawObject};
+
+const BINARY_CHECK: &str = "binary-check";
+const TEXT: &str = "text";
+
+/// The amount of input read to decide whether it is binary.
+pub const CHECK_SIZE: usize = 8 * 1024;
+
+#[derive(Debug, Fail)]
+#[fail(display = "{}: binary file not shown (use --text to show it anyway)", _0)]
+pub struct BinaryFileError(pub String);
+
+/// Create the `--binary-check` argument.  The result should be passed to `is_checked()`.
+pub fn binary_check_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(BINARY_CHECK)
+        .long(BINARY_CHECK)
+        .help("Refuse to print files containing NUL bytes to a terminal")
+}
+
+/// Create the `--text` argument, which overrides `--binary-check`.
+pub fn text_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(TEXT)
+        .long(TEXT)
+        .help("Print files even if --binary-check finds that they are binary")
+}
+
+/// Check whether inputs should be checked before being written to `stream`.
+pub fn is_checked(matches: &ArgMatches, stream: Option<RawObject>) -> bool {
+    matches.is_present(BINARY_CHECK) && !matches.is_present(TEXT) && is_tty(stream)
+}
+
+/// Check whether `data` (the start of some input) looks binary.
+pub fn looks_binary(data: &[u8]) -> bool {
+    data.contains(&0)
+}
+
+/// Read the first block of `input` (of at most `CHECK_SIZE` bytes) for `looks_binary()`.  Only a
+/// single read is made, so this never waits for more input than the utility would have anyway.
+pub fn read_start<R: Read + ?Sized>(input: &mut R) -> io::Result<Vec<u8>> {
+    let mut buf = vec![0; CHECK_SIZE];
+    loop {
+        match input.read(&mut buf) {
+            Ok(len) => {
+                buf.truncate(len);
+                return Ok(buf);
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[test]
+fn binary_detection() {
+    assert!(looks_binary(b"ELF\x00\x01"));
+    assert!(!looks_binary(b"plain text\n"));
+    assert!(!looks_binary(b""));
+
+    let mut input = &b"\x00data"[..];
+    assert_eq!(read_start(&mut input).unwrap(), b"\x00data");
+    assert!(read_start(&mut input).unwrap().is_empty());
+}
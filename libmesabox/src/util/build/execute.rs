@@ -4,12 +4,28 @@ macro_rules! generate_executors {
     ($($group:ident { $(($util:tt, $feature:expr)),+ }),*) => {
         import_utils!($($group { $(($util, $feature)),+ }),*);
 
+        generate_executors!(@util_names $($group { $(($util, $feature)),+ }),*);
         generate_executors!(@dump_cmds $($group { $(($util, $feature)),+ }),*);
         generate_executors!(@easy_util $($group { $(($util, $feature)),+ }),*);
         generate_executors!(@execute_util $($group { $(($util, $feature)),+ }),*);
         generate_executors!(@generate_app $($group { $(($util, $feature)),+ }),*);
     };
 
+    (@util_names $($group:ident { $(($util:tt, $feature:expr)),+ }),*) => {
+        /// The names of the utilities included in this build, in the order they are listed in
+        /// `util_list.rs`.
+        pub fn util_names() -> Vec<&'static str> {
+            let mut names = vec![];
+            $($(
+                #[cfg(feature = $feature)]
+                {
+                    names.push(stringify!($util));
+                }
+            )+)*
+            names
+        }
+    };
+
     (@dump_cmds $($group:ident { $(($util:tt, $feature:expr)),+ }),*) => {
         pub fn dump_commands<S>(setup: &mut S) -> Result<ExitCode>
         where
@@ -18,12 +34,9 @@ macro_rules! generate_executors {
             let stdout = setup.output();
             let mut stdout = stdout.lock()?;
 
-            $($(
-                #[cfg(feature = $feature)]
-                {
-                    writeln!(stdout, stringify!($util))?;
-                }
-            )+)*
+            for name in util_names() {
+                writeln!(stdout, "{}", name)?;
+            }
 
             Ok(EXIT_SUCCESS)
         }
@@ -91,12 +104,7 @@ macro_rules! generate_executors {
 
         #[cfg(feature = "sh")]
         fn util_exists(name: &str) -> bool {
-            [
-                $($(
-                    #[cfg(feature = $feature)]
-                    stringify!($util),
-                )+)*
-            ].contains(&name)
+            util_names().contains(&name)
         }
     };
 
@@ -0,0 +1,138 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+//! Case folding for the case-insensitive modes of utilities (such as `diff -i`).
+//!
+//! This implements Unicode simple case folding, so every character folds to exactly one
+//! character and folded text can be compared without worrying about its length changing.  For
+//! almost every character this is the same as converting it to lowercase, so the standard library
+//! does most of the work and the few exceptions are listed in `SPECIAL_FOLDS`.
+
+use std::borrow::Cow;
+
+use super::utf8;
+
+// characters that are already lowercase but fold to a different character
+const SPECIAL_FOLDS: [(char, char); 12] = [
+    ('\u{b5}', '\u{3bc}'),    // MICRO SIGN -> GREEK SMALL LETTER MU
+    ('\u{17f}', 's'),         // LATIN SMALL LETTER LONG S
+    ('\u{3c2}', '\u{3c3}'),   // GREEK SMALL LETTER FINAL SIGMA -> SIGMA
+    ('\u{3d0}', '\u{3b2}'),   // GREEK BETA SYMBOL -> BETA
+    ('\u{3d1}', '\u{3b8}'),   // GREEK THETA SYMBOL -> THETA
+    ('\u{3d5}', '\u{3c6}'),   // GREEK PHI SYMBOL -> PHI
+    ('\u{3d6}', '\u{3c0}'),   // GREEK PI SYMBOL -> PI
+    ('\u{3f0}', '\u{3ba}'),   // GREEK KAPPA SYMBOL -> KAPPA
+    ('\u{3f1}', '\u{3c1}'),   // GREEK RHO SYMBOL -> RHO
+    ('\u{3f5}', '\u{3b5}'),   // GREEK LUNATE EPSILON SYMBOL -> EPSILON
+    ('\u{1e9b}', '\u{1e61}'), // LATIN SMALL LETTER LONG S WITH DOT ABOVE -> S WITH DOT ABOVE
+    ('\u{1fbe}', '\u{3b9}'),  // GREEK PROSGEGRAMMENI -> IOTA
+];
+
+/// Fold the case of a single character.
+pub fn fold_char(c: char) -> char {
+    if c.is_ascii() {
+        return c.to_ascii_lowercase();
+    }
+    if let Some(&(_, folded)) = SPECIAL_FOLDS.iter().find(|&&(from, _)| from == c) {
+        return folded;
+    }
+
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(folded), None) => folded,
+        // characters like U+0130 (LATIN CAPITAL LETTER I WITH DOT ABOVE) only have a full case
+        // folding, which would change the length of the text
+        _ => c,
+    }
+}
+
+/// Fold the case of `text` so that strings differing only in case compare equal.  Text that is
+/// already folded is returned as is.
+pub fn fold(text: &str) -> Cow<str> {
+    // fast path for the common case of ASCII text
+    if text.is_ascii() {
+        return if text.bytes().any(|b| b.is_ascii_uppercase()) {
+            Cow::Owned(text.to_ascii_lowercase())
+        } else {
+            Cow::Borrowed(text)
+        };
+    }
+
+    if text.chars().all(|c| fold_char(c) == c) {
+        Cow::Borrowed(text)
+    } else {
+        Cow::Owned(text.chars().map(fold_char).collect())
+    }
+}
+
+/// Like `fold()`, but for data that may not be valid UTF-8.  Bytes that are not part of a valid
+/// UTF-8 sequence are left alone.
+pub fn fold_bytes(data: &[u8]) -> Cow<[u8]> {
+    if data.is_ascii() {
+        return if data.iter().any(|b| b.is_ascii_uppercase()) {
+            Cow::Owned(data.to_ascii_lowercase())
+        } else {
+            Cow::Borrowed(data)
+        };
+    }
+
+    let mut result = Vec::with_capacity(data.len());
+    for chunk in utf8::chunks(data) {
+        result.extend_from_slice(fold(chunk.valid).as_bytes());
+        result.extend_from_slice(chunk.invalid);
+    }
+
+    if result == data {
+        Cow::Borrowed(data)
+    } else {
+        Cow::Owned(result)
+    }
+}
+
+/// Compare two strings without regard to case.
+pub fn eq(a: &str, b: &str) -> bool {
+    if a.is_ascii() && b.is_ascii() {
+        return a.eq_ignore_ascii_case(b);
+    }
+    a.chars().map(fold_char).eq(b.chars().map(fold_char))
+}
+
+#[test]
+fn fold_ascii() {
+    assert_eq!(fold("Hello, World!"), "hello, world!");
+    assert!(match fold("already folded") {
+        Cow::Borrowed(_) => true,
+        Cow::Owned(_) => false,
+    });
+}
+
+#[test]
+fn fold_unicode() {
+    assert_eq!(fold("ÀÉÎÕÜ"), "àéîõü");
+    assert_eq!(fold("ΣΊΣΥΦΟΣ"), fold("σίσυφος"));
+    assert_eq!(fold("ПРИВЕТ"), "привет");
+    assert_eq!(fold_char('\u{17f}'), 's');
+    assert_eq!(fold_char('\u{212a}'), 'k'); // KELVIN SIGN
+    // no simple folding exists, so the character stays the same
+    assert_eq!(fold_char('\u{130}'), '\u{130}');
+    assert_eq!(fold_char('ß'), 'ß');
+}
+
+#[test]
+fn fold_invalid_utf8() {
+    assert_eq!(&*fold_bytes(b"ABC\xffD\xc3\x89"), &b"abc\xffd\xc3\xa9"[..]);
+    assert_eq!(&*fold_bytes(b"\xe2\x82"), &b"\xe2\x82"[..]);
+}
+
+#[test]
+fn compare() {
+    assert!(eq("Straße", "STRAßE"));
+    assert!(eq("ΌΣΟΣ", "όσος"));
+    assert!(!eq("abc", "abd"));
+    assert!(!eq("ab", "abc"));
+}
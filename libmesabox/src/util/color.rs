@@ -0,0 +1,223 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use super::{is_tty, EnvVars, RawObject};
+use clap::{Arg, ArgMatches};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+
+const COLOR: &str = "color";
+
+// the colors used by GNU ls when LS_COLORS is not set
+const DEFAULT_LS_COLORS: &str =
+    "rs=0:di=01;34:ln=01;36:mh=00:pi=40;33:so=01;35:do=01;35:bd=40;33;01:cd=40;33;01:\
+     or=40;31;01:mi=00:su=37;41:sg=30;43:ca=30;41:tw=30;42:ow=34;42:st=37;44:ex=01;32";
+
+/// When the user would like colored output (as given by `--color=WHEN`).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ColorWhen {
+    Always,
+    Never,
+    Auto,
+}
+
+impl ColorWhen {
+    /// Parse the value given to `--color`.  The aliases accepted by the GNU utilities are
+    /// supported as well.
+    pub fn parse(s: &str) -> Option<Self> {
+        Some(match s {
+            "always" | "yes" | "force" => ColorWhen::Always,
+            "never" | "no" | "none" => ColorWhen::Never,
+            "auto" | "tty" | "if-tty" => ColorWhen::Auto,
+            _ => return None,
+        })
+    }
+
+    /// Determine the setting using the argument created by `color_arg()`.  `default` is used if
+    /// the argument was not given at all, while `--color` by itself means `always`.
+    pub fn from_matches(matches: &ArgMatches, default: ColorWhen) -> Self {
+        if !matches.is_present(COLOR) {
+            return default;
+        }
+        // the validator in color_arg() ensures the value is valid
+        matches
+            .value_of(COLOR)
+            .and_then(ColorWhen::parse)
+            .unwrap_or(ColorWhen::Always)
+    }
+
+    /// Decide whether to color output written to `stream`.  `no_color` is the value of the
+    /// `NO_COLOR` environment variable, which (when set to anything non-empty) disables color
+    /// unless it was explicitly requested with `always`.
+    pub fn enabled(self, stream: Option<RawObject>, no_color: Option<&OsStr>) -> bool {
+        match self {
+            ColorWhen::Always => true,
+            ColorWhen::Never => false,
+            ColorWhen::Auto => no_color.map(|v| v.is_empty()).unwrap_or(true) && is_tty(stream),
+        }
+    }
+}
+
+/// Create the `--color[=WHEN]` argument.
+pub fn color_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(COLOR)
+        .long(COLOR)
+        .takes_value(true)
+        .min_values(0)
+        .require_equals(true)
+        .value_name("WHEN")
+        .validator(|s| match ColorWhen::parse(&s) {
+            Some(_) => Ok(()),
+            None => Err(format!("invalid argument '{}' (expected always, never, or auto)", s)),
+        })
+        .help("Colorize the output; WHEN can be 'always' (the default if omitted), 'auto', or 'never'")
+}
+
+/// Wraps strings in SGR escape sequences when color is enabled (and leaves them alone otherwise).
+#[derive(Copy, Clone, Debug)]
+pub struct Colorizer {
+    enabled: bool,
+}
+
+impl Colorizer {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled: enabled }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Wrap `s` in the SGR sequence `sgr` (_e.g._ "01;34").  If color is disabled or `sgr` is
+    /// empty, `s` is returned unchanged.
+    pub fn paint<'a>(&self, sgr: &str, s: &'a str) -> Cow<'a, str> {
+        if self.enabled && !sgr.is_empty() {
+            Cow::Owned(format!("\x1b[{}m{}\x1b[0m", sgr, s))
+        } else {
+            Cow::Borrowed(s)
+        }
+    }
+
+    /// Get the sequence that starts `sgr` (or nothing if color is disabled).
+    pub fn start(&self, sgr: &str) -> String {
+        if self.enabled && !sgr.is_empty() {
+            format!("\x1b[{}m", sgr)
+        } else {
+            String::new()
+        }
+    }
+
+    /// Get the sequence that resets all attributes (or nothing if color is disabled).
+    pub fn reset(&self) -> &'static str {
+        if self.enabled {
+            "\x1b[0m"
+        } else {
+            ""
+        }
+    }
+}
+
+/// The colors configured using the `LS_COLORS` environment variable.
+#[derive(Clone, Debug)]
+pub struct LsColors {
+    types: HashMap<String, String>,
+    extensions: Vec<(String, String)>,
+}
+
+impl LsColors {
+    /// Parse the value of `LS_COLORS`, falling back to the GNU defaults if it is unset.
+    pub fn new(value: Option<&OsStr>) -> Self {
+        let mut colors = Self {
+            types: HashMap::new(),
+            extensions: vec![],
+        };
+        colors.parse(DEFAULT_LS_COLORS);
+        if let Some(value) = value {
+            colors.parse(&value.to_string_lossy());
+        }
+        colors
+    }
+
+    /// Find `LS_COLORS` in `env` and parse it.
+    pub fn from_env(env: &EnvVars) -> Self {
+        Self::new(env.get("LS_COLORS"))
+    }
+
+    fn parse(&mut self, value: &str) {
+        for entry in value.split(':') {
+            let mut parts = entry.splitn(2, '=');
+            let (key, sgr) = match (parts.next(), parts.next()) {
+                (Some(key), Some(sgr)) if !key.is_empty() => (key, sgr),
+                _ => continue,
+            };
+            if key.starts_with('*') {
+                let suffix = key[1..].to_owned();
+                self.extensions.retain(|&(ref ext, _)| *ext != suffix);
+                self.extensions.push((suffix, sgr.to_owned()));
+            } else {
+                self.types.insert(key.to_owned(), sgr.to_owned());
+            }
+        }
+    }
+
+    /// Get the sequence for a file type indicator such as "di" (directory) or "ex" (executable).
+    pub fn for_type(&self, indicator: &str) -> Option<&str> {
+        self.types.get(indicator).map(|s| &s[..])
+    }
+
+    /// Get the sequence for a file named `name` based on the `*.ext` style entries.
+    pub fn for_name(&self, name: &str) -> Option<&str> {
+        // later entries take precedence, like with GNU ls
+        self.extensions
+            .iter()
+            .rev()
+            .find(|&&(ref suffix, _)| name.ends_with(&suffix[..]))
+            .map(|&(_, ref sgr)| &sgr[..])
+    }
+}
+
+#[test]
+fn color_when_parse() {
+    assert_eq!(ColorWhen::parse("always"), Some(ColorWhen::Always));
+    assert_eq!(ColorWhen::parse("if-tty"), Some(ColorWhen::Auto));
+    assert_eq!(ColorWhen::parse("none"), Some(ColorWhen::Never));
+    assert_eq!(ColorWhen::parse("sometimes"), None);
+}
+
+#[test]
+fn color_when_no_color() {
+    assert!(ColorWhen::Always.enabled(None, Some(OsStr::new("1"))));
+    // NOTE: None is never a terminal, so Auto is always disabled here
+    assert!(!ColorWhen::Auto.enabled(None, None));
+    assert!(!ColorWhen::Never.enabled(None, None));
+}
+
+#[test]
+fn colorizer_paint() {
+    assert_eq!(Colorizer::new(true).paint("01;34", "dir"), "\x1b[01;34mdir\x1b[0m");
+    assert_eq!(Colorizer::new(false).paint("01;34", "dir"), "dir");
+    assert_eq!(Colorizer::new(true).paint("", "file"), "file");
+}
+
+#[test]
+fn ls_colors_parse() {
+    let colors = LsColors::new(Some(OsStr::new("di=01;33:*.tar=01;31:*.gz=00;31:bogus")));
+    assert_eq!(colors.for_type("di"), Some("01;33"));
+    assert_eq!(colors.for_type("ln"), Some("01;36"));
+    assert_eq!(colors.for_name("archive.tar"), Some("01;31"));
+    assert_eq!(colors.for_name("archive.tar.gz"), Some("00;31"));
+    assert_eq!(colors.for_name("notes.txt"), None);
+}
+
+#[test]
+fn ls_colors_from_env() {
+    let env: EnvVars = vec![("LS_COLORS", "di=01;33")].into_iter().collect();
+    assert_eq!(LsColors::from_env(&env).for_type("di"), Some("01;33"));
+    assert_eq!(LsColors::from_env(&EnvVars::new()).for_type("di"), Some("01;34"));
+}
@@ -0,0 +1,112 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+//! Transparent decompression of inputs (for options like `head -Z`).
+//!
+//! Inputs are recognized by the magic number they start with rather than by their names, so
+//! compressed data read from a pipe works just as well as a file named `foo.gz`.  Anything that
+//! does not look compressed is passed through untouched.
+
+use clap::Arg;
+use flate2::bufread::MultiGzDecoder;
+use std::io::{self, BufRead, BufReader};
+use xz2::bufread::XzDecoder;
+use zstd::stream::read::Decoder as ZstdDecoder;
+
+const DECOMPRESS: &str = "decompress";
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// The compression formats that can be read.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    Gzip,
+    Xz,
+    Zstd,
+}
+
+impl Format {
+    /// Determine the format of data starting with `header`, if it is compressed at all.
+    pub fn detect(header: &[u8]) -> Option<Self> {
+        if header.starts_with(GZIP_MAGIC) {
+            Some(Format::Gzip)
+        } else if header.starts_with(XZ_MAGIC) {
+            Some(Format::Xz)
+        } else if header.starts_with(ZSTD_MAGIC) {
+            Some(Format::Zstd)
+        } else {
+            None
+        }
+    }
+}
+
+/// Create the `-Z`/`--decompress` argument.  Decompression is never done unless asked for, as
+/// utilities are expected to pass their input along unchanged.
+pub fn decompress_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(DECOMPRESS)
+        .short("Z")
+        .long(DECOMPRESS)
+        .help("Decompress inputs compressed with gzip, xz or zstd")
+}
+
+/// Wrap `input` in a decoder if it starts with the magic number of a supported format, or return
+/// it as it is otherwise.
+pub fn open<'a, R: BufRead + 'a>(mut input: R) -> io::Result<Box<BufRead + 'a>> {
+    let format = Format::detect(fill_header(&mut input)?);
+    Ok(match format {
+        // like gzip -d, read every member of a file made by concatenating compressed files
+        Some(Format::Gzip) => Box::new(BufReader::new(MultiGzDecoder::new(input))),
+        Some(Format::Xz) => Box::new(BufReader::new(XzDecoder::new_multi_decoder(input))),
+        Some(Format::Zstd) => Box::new(BufReader::new(ZstdDecoder::with_buffer(input)?)),
+        None => Box::new(input),
+    })
+}
+
+// look at the start of `input` without consuming it.  a single read is usually enough to see the
+// whole magic number, and if it is not, the data could not be recognized anyway
+fn fill_header<R: BufRead>(input: &mut R) -> io::Result<&[u8]> {
+    loop {
+        match input.fill_buf() {
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            res => return res,
+        }
+    }
+}
+
+#[test]
+fn decompress_formats() {
+    assert_eq!(Format::detect(b"\x1f\x8b\x08\x00"), Some(Format::Gzip));
+    assert_eq!(Format::detect(b"\xfd7zXZ\x00\x00"), Some(Format::Xz));
+    assert_eq!(Format::detect(b"\x28\xb5\x2f\xfd\x00"), Some(Format::Zstd));
+    assert_eq!(Format::detect(b"\x1f"), None);
+    assert_eq!(Format::detect(b"plain text"), None);
+}
+
+#[test]
+fn decompress_gzip() {
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::{Read, Write};
+
+    let mut data = vec![];
+    for part in &["first\n", "second\n"] {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(part.as_bytes()).unwrap();
+        data.extend(encoder.finish().unwrap());
+    }
+
+    let mut result = String::new();
+    open(&data[..]).unwrap().read_to_string(&mut result).unwrap();
+    assert_eq!(result, "first\nsecond\n");
+
+    let mut result = String::new();
+    open(&b"plain\n"[..]).unwrap().read_to_string(&mut result).unwrap();
+    assert_eq!(result, "plain\n");
+}
@@ -0,0 +1,177 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use clap::{Arg, ArgMatches};
+use std::io::{self, BufRead, Write};
+
+use super::{is_tty, RawObject};
+
+const ZERO_TERMINATED: &str = "zero-terminated";
+const LINE_BUFFERED: &str = "line-buffered";
+
+/// The byte that separates "lines" of input and output for utilities supporting
+/// `-z`/`--zero-terminated`.
+///
+/// Note that this only applies to the data being processed.  Anything the utility generates itself
+/// (such as the file headers printed by `head`) is still terminated by a newline.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum LineDelimiter {
+    Newline,
+    Nul,
+}
+
+impl LineDelimiter {
+    /// Determine the delimiter using the argument created by `zero_terminated_arg()`.
+    pub fn from_matches(matches: &ArgMatches) -> Self {
+        if matches.is_present(ZERO_TERMINATED) {
+            LineDelimiter::Nul
+        } else {
+            LineDelimiter::Newline
+        }
+    }
+
+    pub fn byte(self) -> u8 {
+        match self {
+            LineDelimiter::Newline => b'\n',
+            LineDelimiter::Nul => 0,
+        }
+    }
+
+    /// Read one line (including the delimiter, if present) from `input` into `buf`, returning
+    /// the number of bytes read.  As with `BufRead::read_until()`, a return value of 0 indicates
+    /// that the end of the input was reached.
+    pub fn read_line<R>(self, input: &mut R, buf: &mut Vec<u8>) -> io::Result<usize>
+    where
+        R: BufRead + ?Sized,
+    {
+        input.read_until(self.byte(), buf)
+    }
+}
+
+impl Default for LineDelimiter {
+    fn default() -> Self {
+        LineDelimiter::Newline
+    }
+}
+
+/// Create the `-z`/`--zero-terminated` argument.  The result should be passed to
+/// `LineDelimiter::from_matches()`.
+pub fn zero_terminated_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(ZERO_TERMINATED)
+        .short("z")
+        .long(ZERO_TERMINATED)
+        .help("Line delimiter is NUL, not newline")
+}
+
+/// Create the `--line-buffered` argument.  The result should be passed to `is_line_buffered()`.
+pub fn line_buffered_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(LINE_BUFFERED)
+        .long(LINE_BUFFERED)
+        .help("Flush the output after every line")
+}
+
+/// Check whether output to `stream` should be flushed after every line, which is the case if
+/// `--line-buffered` was given or if `stream` is a terminal.
+pub fn is_line_buffered(matches: &ArgMatches, stream: Option<RawObject>) -> bool {
+    matches.is_present(LINE_BUFFERED) || is_tty(stream)
+}
+
+/// A writer that flushes the wrapped writer after every write that completes a line.  If it is
+/// not enabled, everything is simply passed through, so utilities can decide whether to use line
+/// buffering at runtime without changing the type of their output.
+pub struct LineFlusher<W: Write> {
+    inner: W,
+    delimiter: u8,
+    enabled: bool,
+}
+
+impl<W: Write> LineFlusher<W> {
+    pub fn new(inner: W, delimiter: LineDelimiter, enabled: bool) -> Self {
+        Self {
+            inner: inner,
+            delimiter: delimiter.byte(),
+            enabled: enabled,
+        }
+    }
+}
+
+impl<W: Write> Write for LineFlusher<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        if self.enabled && buf[..written].contains(&self.delimiter) {
+            self.inner.flush()?;
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+struct FlushCounter {
+    data: Vec<u8>,
+    flushes: usize,
+}
+
+#[cfg(test)]
+impl Write for FlushCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flushes += 1;
+        Ok(())
+    }
+}
+
+#[test]
+fn line_flusher() {
+    let counter = FlushCounter {
+        data: vec![],
+        flushes: 0,
+    };
+    let mut writer = LineFlusher::new(counter, LineDelimiter::Newline, true);
+    writer.write_all(b"partial").unwrap();
+    assert_eq!(writer.inner.flushes, 0);
+    writer.write_all(b" line\n").unwrap();
+    writer.write_all(b"a\0b").unwrap();
+    assert_eq!(writer.inner.flushes, 1);
+    assert_eq!(writer.inner.data, b"partial line\na\0b");
+
+    let counter = FlushCounter {
+        data: vec![],
+        flushes: 0,
+    };
+    let mut writer = LineFlusher::new(counter, LineDelimiter::Nul, false);
+    writer.write_all(b"a\0b\0").unwrap();
+    assert_eq!(writer.inner.flushes, 0);
+}
+
+#[test]
+fn read_line_nul() {
+    let mut input = &b"first\0second\nline\0third"[..];
+    let mut buf = vec![];
+
+    assert_eq!(LineDelimiter::Nul.read_line(&mut input, &mut buf).unwrap(), 6);
+    assert_eq!(buf, b"first\0");
+
+    buf.clear();
+    assert_eq!(LineDelimiter::Nul.read_line(&mut input, &mut buf).unwrap(), 12);
+    assert_eq!(buf, b"second\nline\0");
+
+    buf.clear();
+    assert_eq!(LineDelimiter::Nul.read_line(&mut input, &mut buf).unwrap(), 5);
+    assert_eq!(buf, b"third");
+
+    buf.clear();
+    assert_eq!(LineDelimiter::Nul.read_line(&mut input, &mut buf).unwrap(), 0);
+}
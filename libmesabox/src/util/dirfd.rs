@@ -0,0 +1,363 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+//! Operations relative to open directories, for utilities that modify whole directory trees (like
+//! `chmod -R`).
+//!
+//! Operating on full paths while recursing is racy: if someone replaces a directory partway down
+//! the tree with a symlink, later operations using paths through that directory end up acting on
+//! whatever the symlink points to.  Doing everything relative to a `Dir` (using `openat()`,
+//! `fstatat()`, `fchmodat()`, and friends) and only ever opening subdirectories with `O_NOFOLLOW`
+//! means a swapped-in symlink is noticed rather than followed.
+//!
+//! Symlinks are only followed when asked to, as decided by a `FollowLinks` policy (which is what
+//! the usual `-H`, `-L` and `-P` options choose between).
+
+use nix::libc;
+use std::ffi::{CStr, CString, OsStr, OsString};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::fs::File;
+use std::io;
+use std::mem;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::os::unix::io::FromRawFd;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::Path;
+
+/// Which symlinks to directories a recursive operation descends into.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FollowLinks {
+    /// Follow no symlinks at all (`-P`).
+    Never,
+    /// Follow symlinks given as operands, but not those found while recursing (`-H`).
+    Operands,
+    /// Follow every symlink (`-L`).
+    Always,
+}
+
+impl FollowLinks {
+    /// Whether a symlink given as an operand should be followed.
+    pub fn operands(self) -> bool {
+        self != FollowLinks::Never
+    }
+
+    /// Whether a symlink found while recursing should be followed.
+    pub fn entries(self) -> bool {
+        self == FollowLinks::Always
+    }
+}
+
+/// The kind of an entry in a directory, as determined by `Dir::stat_at()`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EntryKind {
+    Dir,
+    Symlink,
+    Other,
+}
+
+/// The parts of an entry's metadata needed while walking a tree.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct EntryStat {
+    pub kind: EntryKind,
+    pub mode: u32,
+    pub dev: u64,
+    pub ino: u64,
+}
+
+impl EntryStat {
+    fn new(stat: &libc::stat) -> Self {
+        let kind = match stat.st_mode & libc::S_IFMT {
+            libc::S_IFDIR => EntryKind::Dir,
+            libc::S_IFLNK => EntryKind::Symlink,
+            _ => EntryKind::Other,
+        };
+        Self {
+            kind: kind,
+            mode: stat.st_mode as u32,
+            dev: stat.st_dev as u64,
+            ino: stat.st_ino as u64,
+        }
+    }
+}
+
+/// An open directory.
+#[derive(Debug)]
+pub struct Dir {
+    fd: RawFd,
+}
+
+impl Dir {
+    /// Open the directory at `path`, failing if it is a symlink unless `follow` is set (as
+    /// utilities generally follow symlinks given as operands).
+    pub fn open<P: AsRef<Path>>(path: P, follow: bool) -> io::Result<Self> {
+        let path = c_path(path.as_ref().as_os_str())?;
+        Self::from_result(unsafe { libc::open(path.as_ptr(), dir_flags(follow)) })
+    }
+
+    /// Open the subdirectory `name`, failing if it is a symlink unless `follow` is set.
+    pub fn open_at(&self, name: &OsStr, follow: bool) -> io::Result<Self> {
+        let name = c_path(name)?;
+        Self::from_result(unsafe { libc::openat(self.fd, name.as_ptr(), dir_flags(follow)) })
+    }
+
+    fn from_result(fd: RawFd) -> io::Result<Self> {
+        if fd < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(Self { fd: fd })
+        }
+    }
+
+    /// Get the metadata of the directory itself.
+    pub fn stat(&self) -> io::Result<EntryStat> {
+        let mut stat: libc::stat = unsafe { mem::zeroed() };
+        check(unsafe { libc::fstat(self.fd, &mut stat) })?;
+        Ok(EntryStat::new(&stat))
+    }
+
+    /// Get the metadata of the entry `name` (or of what it points to if it is a symlink and
+    /// `follow` is set).
+    pub fn stat_at(&self, name: &OsStr, follow: bool) -> io::Result<EntryStat> {
+        let name = c_path(name)?;
+        let mut stat: libc::stat = unsafe { mem::zeroed() };
+        let flags = if follow { 0 } else { libc::AT_SYMLINK_NOFOLLOW };
+        check(unsafe { libc::fstatat(self.fd, name.as_ptr(), &mut stat, flags) })?;
+        Ok(EntryStat::new(&stat))
+    }
+
+    /// List the names of the entries in the directory (other than `.` and `..`), sorted so the
+    /// order does not depend on the file system.
+    pub fn entries(&self) -> io::Result<Vec<OsString>> {
+        // closedir() closes the descriptor it is given, so give it a copy
+        let fd = unsafe { libc::dup(self.fd) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let dir = unsafe { libc::fdopendir(fd) };
+        if dir.is_null() {
+            let err = io::Error::last_os_error();
+            unsafe { libc::close(fd) };
+            return Err(err);
+        }
+
+        let mut names = vec![];
+        let result = loop {
+            // readdir() only sets errno on failure, so clear it to tell errors from the end
+            unsafe { *errno_location() = 0 };
+            let entry = unsafe { libc::readdir(dir) };
+            if entry.is_null() {
+                let err = io::Error::last_os_error();
+                break match err.raw_os_error() {
+                    Some(0) | None => Ok(()),
+                    Some(_) => Err(err),
+                };
+            }
+            let name = unsafe { CStr::from_ptr((*entry).d_name.as_ptr()) }.to_bytes();
+            if name != b"." && name != b".." {
+                names.push(OsString::from_vec(name.to_vec()));
+            }
+        };
+        unsafe { libc::closedir(dir) };
+
+        result.map(|_| {
+            names.sort();
+            names
+        })
+    }
+
+    /// Change the permissions of the directory itself.
+    pub fn chmod(&self, mode: u32) -> io::Result<()> {
+        check(unsafe { libc::fchmod(self.fd, mode as libc::mode_t) })
+    }
+
+    /// Change the permissions of the entry `name`, which `stat_at(name, follow)` found to be
+    /// `stat`.  Unless `follow` is set, a symlink is never followed (even if `name` was replaced
+    /// with one after it was looked at), and on systems where it can be checked an entry that is
+    /// no longer the file described by `stat` is left alone.
+    pub fn chmod_at(
+        &self,
+        name: &OsStr,
+        stat: &EntryStat,
+        mode: u32,
+        follow: bool,
+    ) -> io::Result<()> {
+        let name = c_path(name)?;
+        if follow {
+            check(unsafe { libc::fchmodat(self.fd, name.as_ptr(), mode as libc::mode_t, 0) })
+        } else {
+            chmod_nofollow(self.fd, &name, stat, mode)
+        }
+    }
+
+    /// Remove the entry `name`, which must be an empty directory if `dir` is true.
+    pub fn unlink_at(&self, name: &OsStr, dir: bool) -> io::Result<()> {
+        let name = c_path(name)?;
+        let flags = if dir { libc::AT_REMOVEDIR } else { 0 };
+        check(unsafe { libc::unlinkat(self.fd, name.as_ptr(), flags) })
+    }
+}
+
+impl AsRawFd for Dir {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl Drop for Dir {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+// Linux does not support fchmodat() with AT_SYMLINK_NOFOLLOW, so open the entry itself (without
+// following it or needing any access to it) and change it through /proc, like glibc does
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn chmod_nofollow(dirfd: RawFd, name: &CStr, expected: &EntryStat, mode: u32) -> io::Result<()> {
+    let flags = libc::O_PATH | libc::O_NOFOLLOW | libc::O_CLOEXEC;
+    let fd = unsafe { libc::openat(dirfd, name.as_ptr(), flags) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // closes the descriptor when we are done
+    let file = unsafe { File::from_raw_fd(fd) };
+
+    let mut stat: libc::stat = unsafe { mem::zeroed() };
+    check(unsafe { libc::fstat(file.as_raw_fd(), &mut stat) })?;
+    let found = EntryStat::new(&stat);
+    if found.kind == EntryKind::Symlink {
+        return Err(io::Error::from_raw_os_error(libc::ELOOP));
+    }
+    if (found.dev, found.ino) != (expected.dev, expected.ino) {
+        return Err(io::Error::new(io::ErrorKind::Other, "file was replaced while being changed"));
+    }
+
+    let path = CString::new(format!("/proc/self/fd/{}", file.as_raw_fd())).unwrap();
+    check(unsafe { libc::chmod(path.as_ptr(), mode as libc::mode_t) })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn chmod_nofollow(dirfd: RawFd, name: &CStr, _expected: &EntryStat, mode: u32) -> io::Result<()> {
+    let flags = libc::AT_SYMLINK_NOFOLLOW;
+    check(unsafe { libc::fchmodat(dirfd, name.as_ptr(), mode as libc::mode_t, flags) })
+}
+
+fn dir_flags(follow: bool) -> libc::c_int {
+    let flags = libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC;
+    if follow {
+        flags
+    } else {
+        flags | libc::O_NOFOLLOW
+    }
+}
+
+fn c_path(path: &OsStr) -> io::Result<CString> {
+    CString::new(path.as_bytes())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path contains a NUL byte"))
+}
+
+fn check(res: libc::c_int) -> io::Result<()> {
+    if res < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "redox"))]
+unsafe fn errno_location() -> *mut libc::c_int {
+    libc::__errno_location()
+}
+
+#[cfg(any(target_os = "android", target_os = "netbsd", target_os = "openbsd"))]
+unsafe fn errno_location() -> *mut libc::c_int {
+    libc::__errno()
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "dragonfly"))]
+unsafe fn errno_location() -> *mut libc::c_int {
+    libc::__error()
+}
+
+#[cfg(feature = "tempfile")]
+#[test]
+fn dir_operations() {
+    use std::fs;
+    use std::os::unix::fs::{symlink, PermissionsExt};
+    use tempfile;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let root = temp_dir.path();
+    fs::create_dir(root.join("sub")).unwrap();
+    fs::write(root.join("sub").join("file"), "").unwrap();
+    symlink("sub", root.join("link")).unwrap();
+
+    let dir = Dir::open(root, false).unwrap();
+    assert_eq!(dir.entries().unwrap(), vec![OsString::from("link"), OsString::from("sub")]);
+    assert_eq!(dir.stat_at(OsStr::new("link"), false).unwrap().kind, EntryKind::Symlink);
+    assert_eq!(dir.stat_at(OsStr::new("sub"), false).unwrap().kind, EntryKind::Dir);
+
+    // subdirectories can only be opened through symlinks when following them
+    assert!(dir.open_at(OsStr::new("link"), false).is_err());
+    let sub = dir.open_at(OsStr::new("sub"), false).unwrap();
+    assert_eq!(sub.stat_at(OsStr::new("file"), false).unwrap().kind, EntryKind::Other);
+
+    let followed = dir.stat_at(OsStr::new("link"), true).unwrap();
+    assert_eq!(followed, sub.stat().unwrap());
+    let linked = dir.open_at(OsStr::new("link"), true).unwrap();
+    assert_eq!(linked.stat().unwrap(), followed);
+    assert!(Dir::open(root.join("link"), false).is_err());
+    assert!(Dir::open(root.join("link"), true).is_ok());
+
+    let file_stat = sub.stat_at(OsStr::new("file"), false).unwrap();
+    sub.chmod_at(OsStr::new("file"), &file_stat, 0o600, false).unwrap();
+    let mode = fs::metadata(root.join("sub").join("file")).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    sub.unlink_at(OsStr::new("file"), false).unwrap();
+    assert!(sub.entries().unwrap().is_empty());
+    dir.unlink_at(OsStr::new("link"), false).unwrap();
+    dir.unlink_at(OsStr::new("sub"), true).unwrap();
+}
+
+#[cfg(feature = "tempfile")]
+#[test]
+fn dir_chmod_at_symlink() {
+    use std::fs;
+    use std::os::unix::fs::{symlink, PermissionsExt};
+    use tempfile;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let root = temp_dir.path();
+    let target = root.join("target");
+    fs::write(&target, "").unwrap();
+    fs::set_permissions(&target, fs::Permissions::from_mode(0o644)).unwrap();
+    fs::write(root.join("entry"), "").unwrap();
+
+    let dir = Dir::open(root, false).unwrap();
+    let stat = dir.stat_at(OsStr::new("entry"), false).unwrap();
+
+    // the entry is replaced with a symlink after it was looked at
+    fs::remove_file(root.join("entry")).unwrap();
+    symlink(&target, root.join("entry")).unwrap();
+    assert!(dir.chmod_at(OsStr::new("entry"), &stat, 0o600, false).is_err());
+
+    // a symlink is not followed even if it is what was looked at
+    let link_stat = dir.stat_at(OsStr::new("entry"), false).unwrap();
+    let _ = dir.chmod_at(OsStr::new("entry"), &link_stat, 0o600, false);
+    let mode = fs::metadata(&target).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o644);
+
+    // unless following was asked for
+    let followed = dir.stat_at(OsStr::new("entry"), true).unwrap();
+    dir.chmod_at(OsStr::new("entry"), &followed, 0o600, true).unwrap();
+    let mode = fs::metadata(&target).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+}
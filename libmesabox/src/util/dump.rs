@@ -0,0 +1,165 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+//! The parts shared by utilities that dump their input in a readable form one fixed-size line at
+//! a time (like `hexdump` and `od`): skipping the start of the input, limiting how much is dumped,
+//! and replacing repeated lines with a single `*`.
+
+use std::io::{self, Read, Write};
+
+/// Options for `dump()`.
+#[derive(Clone, Debug)]
+pub struct DumpOptions {
+    /// The number of bytes shown on each line
+    pub line_len: usize,
+    /// The number of bytes to skip at the start of the input
+    pub skip: u64,
+    /// The maximum number of bytes to dump (after skipping)
+    pub limit: Option<u64>,
+    /// Whether to replace lines that are the same as the line before them with `*`
+    pub squeeze: bool,
+}
+
+impl Default for DumpOptions {
+    fn default() -> Self {
+        Self {
+            line_len: 16,
+            skip: 0,
+            limit: None,
+            squeeze: true,
+        }
+    }
+}
+
+/// Read `input` one line at a time, calling `write_line` with the offset of each line (counted
+/// from the start of the input, including any skipped bytes) and its data.  Only the last line
+/// may be shorter than `options.line_len`.  Returns the offset of the end of the data, which
+/// utilities usually print after the last line.
+pub fn dump<R, W, F>(
+    input: &mut R,
+    output: &mut W,
+    options: &DumpOptions,
+    mut write_line: F,
+) -> io::Result<u64>
+where
+    R: Read + ?Sized,
+    W: Write + ?Sized,
+    F: FnMut(&mut W, u64, &[u8]) -> io::Result<()>,
+{
+    let skipped = io::copy(&mut (&mut *input).take(options.skip), &mut io::sink())?;
+    let mut input = (&mut *input).take(options.limit.unwrap_or(u64::max_value()));
+
+    let mut offset = skipped;
+    let mut line = vec![0; options.line_len.max(1)];
+    let mut prev = vec![];
+    let mut squeezing = false;
+    loop {
+        let len = read_full(&mut input, &mut line)?;
+        if len == 0 {
+            break;
+        }
+        let data = &line[..len];
+
+        if options.squeeze && len == line.len() && data == &prev[..] {
+            if !squeezing {
+                output.write_all(b"*\n")?;
+                squeezing = true;
+            }
+        } else {
+            squeezing = false;
+            write_line(output, offset, data)?;
+            prev.clear();
+            prev.extend_from_slice(data);
+        }
+        offset += len as u64;
+    }
+
+    Ok(offset)
+}
+
+/// Format `offset` in the given `radix` (8, 10, or 16), padded with zeros to `width` digits.
+pub fn format_offset(offset: u64, radix: u32, width: usize) -> String {
+    match radix {
+        8 => format!("{:01$o}", offset, width),
+        10 => format!("{:01$}", offset, width),
+        _ => format!("{:01$x}", offset, width),
+    }
+}
+
+// like read_exact(), but stops early at the end of the input (returning how much was read)
+fn read_full<R: Read + ?Sized>(input: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match input.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(count) => len += count,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(len)
+}
+
+#[cfg(test)]
+fn dump_lines(data: &[u8], options: &DumpOptions) -> (Vec<String>, u64) {
+    let mut output = vec![];
+    let end = dump(&mut &data[..], &mut output, options, |output, offset, line| {
+        writeln!(output, "{} {:?}", offset, line)
+    }).unwrap();
+    let lines = String::from_utf8(output).unwrap().lines().map(|s| s.to_owned()).collect();
+    (lines, end)
+}
+
+#[test]
+fn dump_squeeze() {
+    let mut data = vec![0; 20];
+    data.extend_from_slice(b"abcdeabcde");
+
+    let options = DumpOptions {
+        line_len: 5,
+        ..DumpOptions::default()
+    };
+    let (lines, end) = dump_lines(&data, &options);
+    assert_eq!(lines, vec!["0 [0, 0, 0, 0, 0]", "*", "20 [97, 98, 99, 100, 101]", "*"]);
+    assert_eq!(end, 30);
+
+    let options = DumpOptions {
+        line_len: 5,
+        squeeze: false,
+        ..DumpOptions::default()
+    };
+    assert_eq!(dump_lines(&data, &options).0.len(), 6);
+}
+
+#[test]
+fn dump_skip_limit() {
+    let options = DumpOptions {
+        line_len: 4,
+        skip: 3,
+        limit: Some(5),
+        squeeze: true,
+    };
+    let (lines, end) = dump_lines(b"0123456789", &options);
+    assert_eq!(lines, vec!["3 [51, 52, 53, 54]", "7 [55]"]);
+    assert_eq!(end, 8);
+
+    // skipping past the end of the input
+    let options = DumpOptions {
+        skip: 20,
+        ..DumpOptions::default()
+    };
+    assert_eq!(dump_lines(b"0123456789", &options), (vec![], 10));
+}
+
+#[test]
+fn offsets() {
+    assert_eq!(format_offset(255, 16, 7), "00000ff");
+    assert_eq!(format_offset(8, 8, 7), "0000010");
+    assert_eq!(format_offset(42, 10, 4), "0042");
+    assert_eq!(format_offset(0x123456789, 16, 8), "123456789");
+}
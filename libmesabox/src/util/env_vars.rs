@@ -0,0 +1,108 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::iter::FromIterator;
+
+/// The environment variables visible to a utility.
+///
+/// Utilities should look up variables here (through `UtilSetup::env()`) rather than in the
+/// environment of the process, as the two differ when a utility is run by `sh` or from a test.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct EnvVars {
+    vars: Vec<(OsString, OsString)>,
+}
+
+impl EnvVars {
+    /// An empty environment.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The environment of the current process.
+    pub fn from_process() -> Self {
+        env::vars_os().collect()
+    }
+
+    /// Find the value of the variable `name`.
+    pub fn get<K: AsRef<OsStr>>(&self, name: K) -> Option<&OsStr> {
+        let name = name.as_ref();
+        self.vars
+            .iter()
+            .find(|&&(ref key, _)| key == name)
+            .map(|&(_, ref value)| value.as_os_str())
+    }
+
+    /// Like `get()`, but treats variables with an empty value as unset.
+    pub fn get_nonempty<K: AsRef<OsStr>>(&self, name: K) -> Option<&OsStr> {
+        self.get(name).and_then(|value| if value.is_empty() { None } else { Some(value) })
+    }
+
+    /// Set the variable `name` to `value`, replacing its previous value if it was already set.
+    pub fn set<K, V>(&mut self, name: K, value: V)
+    where
+        K: Into<OsString>,
+        V: Into<OsString>,
+    {
+        let name = name.into();
+        let value = value.into();
+        match self.vars.iter().position(|&(ref key, _)| *key == name) {
+            Some(pos) => self.vars[pos].1 = value,
+            None => self.vars.push((name, value)),
+        }
+    }
+
+    /// Remove the variable `name`, returning its value if it was set.
+    pub fn remove<K: AsRef<OsStr>>(&mut self, name: K) -> Option<OsString> {
+        let name = name.as_ref();
+        self.vars
+            .iter()
+            .position(|&(ref key, _)| key == name)
+            .map(|pos| self.vars.remove(pos).1)
+    }
+
+    /// Iterate over the variables in the order they were first set.
+    pub fn iter(&self) -> impl Iterator<Item = (&OsStr, &OsStr)> {
+        self.vars
+            .iter()
+            .map(|&(ref key, ref value)| (key.as_os_str(), value.as_os_str()))
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for EnvVars
+where
+    K: Into<OsString>,
+    V: Into<OsString>,
+{
+    // when a variable is given more than once, the last value wins (as with `env` or a shell)
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut vars = Self::new();
+        for (key, value) in iter {
+            vars.set(key, value);
+        }
+        vars
+    }
+}
+
+#[test]
+fn env_vars_lookup() {
+    let mut vars: EnvVars = vec![("HOME", "/root"), ("EMPTY", ""), ("HOME", "/home/user")]
+        .into_iter()
+        .collect();
+    assert_eq!(vars.get("HOME"), Some(OsStr::new("/home/user")));
+    assert_eq!(vars.get("EMPTY"), Some(OsStr::new("")));
+    assert_eq!(vars.get_nonempty("EMPTY"), None);
+    assert_eq!(vars.get("MISSING"), None);
+
+    vars.set("PATH", "/bin");
+    assert_eq!(vars.remove("EMPTY"), Some(OsString::from("")));
+    assert_eq!(vars.remove("EMPTY"), None);
+    let names: Vec<&OsStr> = vars.iter().map(|(key, _)| key).collect();
+    assert_eq!(names, vec![OsStr::new("HOME"), OsStr::new("PATH")]);
+}
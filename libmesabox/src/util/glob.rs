@@ -0,0 +1,583 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+//! Shell-style pattern matching like `fnmatch(3)`, for things like `find -name` and
+//! `grep --include`.
+//!
+//! Patterns and text are handled as bytes, so names that are not valid UTF-8 can still be matched.
+//! Valid UTF-8 sequences are treated as single characters (so `?` matches `é`), while every other
+//! byte is a character of its own.
+
+use std::ffi::OsStr;
+use std::result::Result as StdResult;
+
+use super::{casefold, os_str_as_bytes, utf8};
+
+// characters that are not valid UTF-8 are stored as one of these values (one for each byte), which
+// are all larger than any valid char
+const INVALID_BASE: u32 = 0x11_0000;
+
+const SLASH: u32 = '/' as u32;
+const PERIOD: u32 = '.' as u32;
+const BACKSLASH: u32 = '\\' as u32;
+
+#[derive(Fail, Debug)]
+pub enum GlobError {
+    #[fail(display = "invalid character class '{}'", _0)]
+    InvalidClass(String),
+
+    #[fail(display = "unsupported collating element '{}'", _0)]
+    InvalidCollatingElement(String),
+}
+
+pub type GlobResult<T> = StdResult<T, GlobError>;
+
+/// Flags that change how a `Pattern` matches, named after the equivalent `fnmatch(3)` flags.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct MatchFlags {
+    /// Wildcards do not match `/` (`FNM_PATHNAME`).  A `**` that makes up an entire component of
+    /// the pattern then matches any number of directories.
+    pub pathname: bool,
+    /// Wildcards do not match a `.` at the start of the text (or, with `pathname`, at the start of
+    /// any component) (`FNM_PERIOD`).
+    pub period: bool,
+    /// Letters match regardless of case (`FNM_CASEFOLD`).
+    pub casefold: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Class {
+    Alnum,
+    Alpha,
+    Blank,
+    Cntrl,
+    Digit,
+    Graph,
+    Lower,
+    Print,
+    Punct,
+    Space,
+    Upper,
+    Xdigit,
+}
+
+impl Class {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "alnum" => Class::Alnum,
+            "alpha" => Class::Alpha,
+            "blank" => Class::Blank,
+            "cntrl" => Class::Cntrl,
+            "digit" => Class::Digit,
+            "graph" => Class::Graph,
+            "lower" => Class::Lower,
+            "print" => Class::Print,
+            "punct" => Class::Punct,
+            "space" => Class::Space,
+            "upper" => Class::Upper,
+            "xdigit" => Class::Xdigit,
+            _ => return None,
+        })
+    }
+
+    fn matches(self, ch: char) -> bool {
+        match self {
+            Class::Alnum => ch.is_alphanumeric(),
+            Class::Alpha => ch.is_alphabetic(),
+            Class::Blank => ch == ' ' || ch == '\t',
+            Class::Cntrl => ch.is_control(),
+            Class::Digit => ch.is_ascii_digit(),
+            Class::Graph => !ch.is_control() && !ch.is_whitespace(),
+            Class::Lower => ch.is_lowercase(),
+            Class::Print => !ch.is_control(),
+            Class::Punct => ch.is_ascii_punctuation(),
+            Class::Space => ch.is_whitespace(),
+            Class::Upper => ch.is_uppercase(),
+            Class::Xdigit => ch.is_ascii_hexdigit(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Item {
+    Char(u32),
+    Range(u32, u32),
+    Class(Class),
+}
+
+impl Item {
+    fn matches(&self, ch: u32) -> bool {
+        match *self {
+            Item::Char(item) => item == ch,
+            Item::Range(start, end) => start <= ch && ch <= end,
+            Item::Class(class) => as_char(ch).map(|ch| class.matches(ch)).unwrap_or(false),
+        }
+    }
+}
+
+/// A bracket expression such as `[a-z]` or `[![:space:]]`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Bracket {
+    negated: bool,
+    items: Vec<Item>,
+}
+
+impl Bracket {
+    fn matches(&self, ch: u32, casefold: bool) -> bool {
+        let found = self.items.iter().any(|item| {
+            item.matches(ch) || (casefold && other_cases(ch).iter().any(|&ch| item.matches(ch)))
+        });
+        found != self.negated
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    Char(u32),
+    /// `?`
+    Any,
+    /// `*`
+    Star,
+    /// `**/` (with `pathname`), which matches any number of directories
+    DirStar,
+    /// `**` at the end of a pattern (with `pathname`), which matches everything
+    TailStar,
+    Bracket(Bracket),
+}
+
+/// A compiled pattern.
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    tokens: Vec<Token>,
+    flags: MatchFlags,
+}
+
+impl Pattern {
+    pub fn new<S: AsRef<OsStr> + ?Sized>(pattern: &S, flags: MatchFlags) -> GlobResult<Self> {
+        let chars = decode(&os_str_as_bytes(pattern.as_ref()));
+        Ok(Self {
+            tokens: parse(&chars, flags)?,
+            flags: flags,
+        })
+    }
+
+    /// Check whether `text` matches the pattern.
+    pub fn is_match<S: AsRef<OsStr> + ?Sized>(&self, text: &S) -> bool {
+        self.is_match_bytes(&os_str_as_bytes(text.as_ref()))
+    }
+
+    pub fn is_match_bytes(&self, text: &[u8]) -> bool {
+        let text = decode(text);
+        let len = self.tokens.len();
+
+        // the tokens are simulated like the states of an NFA (with `len` as the accepting state),
+        // which avoids the exponential backtracking that patterns like `*a*a*a*b` can cause
+        let mut current = vec![false; len + 1];
+        let mut next = vec![false; len + 1];
+        self.add_state(&mut current, 0, &text, 0);
+
+        for pos in 0..text.len() {
+            let ch = text[pos];
+            let at_start = pos == 0 || (self.flags.pathname && text[pos - 1] == SLASH);
+            let hidden = self.flags.period && ch == PERIOD && at_start;
+            let slash = self.flags.pathname && ch == SLASH;
+
+            for state in next.iter_mut() {
+                *state = false;
+            }
+            for i in 0..len {
+                if !current[i] {
+                    continue;
+                }
+                let target = match self.tokens[i] {
+                    Token::Char(expected) if self.chars_eq(expected, ch) => Some(i + 1),
+                    Token::Any if !slash && !hidden => Some(i + 1),
+                    Token::Star if !slash && !hidden => Some(i),
+                    Token::DirStar | Token::TailStar if !hidden => Some(i),
+                    Token::Bracket(ref bracket)
+                        if !slash && !hidden && bracket.matches(ch, self.flags.casefold) =>
+                    {
+                        Some(i + 1)
+                    }
+                    _ => None,
+                };
+                if let Some(target) = target {
+                    self.add_state(&mut next, target, &text, pos + 1);
+                }
+            }
+
+            if !next.iter().any(|&state| state) {
+                return false;
+            }
+            ::std::mem::swap(&mut current, &mut next);
+        }
+
+        current[len]
+    }
+
+    // add `state` and every state that can be reached from it without consuming anything
+    fn add_state(&self, states: &mut [bool], mut state: usize, text: &[u32], pos: usize) {
+        loop {
+            if states[state] {
+                return;
+            }
+            states[state] = true;
+
+            match self.tokens.get(state) {
+                Some(&Token::Star) | Some(&Token::TailStar) => state += 1,
+                // the directories matched by `**/` must be complete
+                Some(&Token::DirStar) if pos == 0 || text[pos - 1] == SLASH => state += 1,
+                _ => return,
+            }
+        }
+    }
+
+    fn chars_eq(&self, a: u32, b: u32) -> bool {
+        a == b || (self.flags.casefold && fold(a) == fold(b))
+    }
+}
+
+/// Check whether `text` matches `pattern` (like `fnmatch(3)`).
+pub fn fnmatch<P, S>(pattern: &P, text: &S, flags: MatchFlags) -> GlobResult<bool>
+where
+    P: AsRef<OsStr> + ?Sized,
+    S: AsRef<OsStr> + ?Sized,
+{
+    Ok(Pattern::new(pattern, flags)?.is_match(text))
+}
+
+fn parse(chars: &[u32], flags: MatchFlags) -> GlobResult<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let ch = chars[i];
+        i += 1;
+
+        let token = match as_char(ch) {
+            Some('\\') if i < chars.len() => {
+                i += 1;
+                Token::Char(chars[i - 1])
+            }
+            Some('?') => Token::Any,
+            Some('*') => {
+                let mut count = 1;
+                while i < chars.len() && chars[i] == '*' as u32 {
+                    count += 1;
+                    i += 1;
+                }
+
+                let component_start = i - count == 0 || chars[i - count - 1] == SLASH;
+                if count > 1 && flags.pathname && component_start {
+                    if i == chars.len() {
+                        Token::TailStar
+                    } else if chars[i] == SLASH {
+                        i += 1;
+                        Token::DirStar
+                    } else {
+                        Token::Star
+                    }
+                } else {
+                    Token::Star
+                }
+            }
+            // an unterminated bracket expression is just a '['
+            Some('[') => match parse_bracket(chars, i)? {
+                Some((bracket, next)) => {
+                    i = next;
+                    Token::Bracket(bracket)
+                }
+                None => Token::Char(ch),
+            },
+            _ => Token::Char(ch),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Parse the bracket expression starting at `start` (just after the `[`), returning it and the
+/// position just after its closing `]` if it is terminated.
+fn parse_bracket(chars: &[u32], start: usize) -> GlobResult<Option<(Bracket, usize)>> {
+    let mut i = start;
+    let negated = i < chars.len() && (chars[i] == '!' as u32 || chars[i] == '^' as u32);
+    if negated {
+        i += 1;
+    }
+
+    let mut items = vec![];
+    let mut first = true;
+    loop {
+        if i >= chars.len() {
+            return Ok(None);
+        }
+        // a ']' right at the start is part of the expression
+        if chars[i] == ']' as u32 && !first {
+            let bracket = Bracket {
+                negated: negated,
+                items: items,
+            };
+            return Ok(Some((bracket, i + 1)));
+        }
+        first = false;
+
+        let (low, next) = match parse_element(chars, i)? {
+            Element::Class(class, next) => {
+                items.push(Item::Class(class));
+                i = next;
+                continue;
+            }
+            Element::Char(ch, next) => (ch, next),
+        };
+        i = next;
+
+        // a '-' just before the closing ']' is taken literally
+        if i + 1 < chars.len() && chars[i] == '-' as u32 && chars[i + 1] != ']' as u32 {
+            match parse_element(chars, i + 1)? {
+                Element::Char(high, next) => {
+                    items.push(Item::Range(low, high));
+                    i = next;
+                }
+                // a range can't end in a character class, so treat the '-' as a character
+                Element::Class(..) => items.push(Item::Char(low)),
+            }
+        } else {
+            items.push(Item::Char(low));
+        }
+    }
+}
+
+enum Element {
+    Char(u32, usize),
+    Class(Class, usize),
+}
+
+// parse a single element of a bracket expression (a character, an escaped character, `[:class:]`,
+// `[=c=]`, or `[.c.]`)
+fn parse_element(chars: &[u32], i: usize) -> GlobResult<Element> {
+    let ch = chars[i];
+    if ch == BACKSLASH && i + 1 < chars.len() {
+        return Ok(Element::Char(chars[i + 1], i + 2));
+    }
+    if ch != '[' as u32 || i + 1 >= chars.len() {
+        return Ok(Element::Char(ch, i + 1));
+    }
+
+    let delim = chars[i + 1];
+    if delim != ':' as u32 && delim != '=' as u32 && delim != '.' as u32 {
+        return Ok(Element::Char(ch, i + 1));
+    }
+
+    let end = (i + 2..chars.len().saturating_sub(1))
+        .find(|&j| chars[j] == delim && chars[j + 1] == ']' as u32);
+    let end = match end {
+        Some(end) => end,
+        None => return Ok(Element::Char(ch, i + 1)),
+    };
+    let content = &chars[i + 2..end];
+    let name: String = content.iter().filter_map(|&ch| as_char(ch)).collect();
+
+    if delim == ':' as u32 {
+        match Class::from_name(&name) {
+            Some(class) => Ok(Element::Class(class, end + 2)),
+            None => Err(GlobError::InvalidClass(name)),
+        }
+    } else if content.len() == 1 {
+        // without locale support, equivalence classes and collating symbols can only be single
+        // characters
+        Ok(Element::Char(content[0], end + 2))
+    } else {
+        Err(GlobError::InvalidCollatingElement(name))
+    }
+}
+
+/// Split `data` into characters, where bytes that are not part of valid UTF-8 sequences are
+/// characters of their own.
+fn decode(data: &[u8]) -> Vec<u32> {
+    let mut chars = Vec::with_capacity(data.len());
+    for chunk in utf8::chunks(data) {
+        chars.extend(chunk.valid.chars().map(|ch| ch as u32));
+        chars.extend(chunk.invalid.iter().map(|&b| INVALID_BASE + b as u32));
+    }
+    chars
+}
+
+fn as_char(ch: u32) -> Option<char> {
+    if ch < INVALID_BASE {
+        ::std::char::from_u32(ch)
+    } else {
+        None
+    }
+}
+
+fn fold(ch: u32) -> u32 {
+    as_char(ch).map(|ch| casefold::fold_char(ch) as u32).unwrap_or(ch)
+}
+
+// the folded and uppercase forms of `ch`, so that ranges like `[A-Z]` and classes like
+// `[[:lower:]]` ignore case as well
+fn other_cases(ch: u32) -> Vec<u32> {
+    match as_char(ch) {
+        Some(c) => {
+            let mut upper = c.to_uppercase();
+            let mut cases = vec![casefold::fold_char(c) as u32];
+            if let (Some(upper), None) = (upper.next(), upper.next()) {
+                cases.push(upper as u32);
+            }
+            cases
+        }
+        None => vec![],
+    }
+}
+
+#[cfg(test)]
+fn check(pattern: &str, text: &str, flags: MatchFlags) -> bool {
+    fnmatch(pattern, text, flags).unwrap()
+}
+
+#[cfg(test)]
+fn plain(pattern: &str, text: &str) -> bool {
+    check(pattern, text, MatchFlags::default())
+}
+
+#[test]
+fn wildcards() {
+    assert!(plain("abc", "abc"));
+    assert!(!plain("abc", "abcd"));
+    assert!(plain("a?c", "abc"));
+    assert!(!plain("a?c", "ac"));
+    assert!(plain("*.rs", "main.rs"));
+    assert!(plain("*", ""));
+    assert!(plain("a*b*c", "aXXbYYc"));
+    assert!(!plain("a*b*c", "aXXbYY"));
+    assert!(plain("*a*a*a*a*a*a*a*b", "aaaaaaaaaaaaaaaaaaaaaaaaaaaaab"));
+    assert!(!plain("*a*a*a*a*a*a*a*b", "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"));
+    // without FNM_PATHNAME, wildcards match slashes
+    assert!(plain("*.rs", "src/main.rs"));
+    assert!(plain("?", "é"));
+}
+
+#[test]
+fn escapes() {
+    assert!(plain("\\*", "*"));
+    assert!(!plain("\\*", "a"));
+    assert!(plain("a\\?", "a?"));
+    assert!(plain("a\\", "a\\"));
+    assert!(plain("[\\]]", "]"));
+}
+
+#[test]
+fn bracket_expressions() {
+    assert!(plain("[abc]", "b"));
+    assert!(!plain("[abc]", "d"));
+    assert!(plain("[a-c]x", "bx"));
+    assert!(!plain("[a-c]x", "dx"));
+    assert!(plain("[!a-c]", "d"));
+    assert!(plain("[^a-c]", "d"));
+    assert!(!plain("[!a-c]", "a"));
+    // ']' at the start and '-' at the edges are literal
+    assert!(plain("[]a]", "]"));
+    assert!(plain("[!]]", "a"));
+    assert!(!plain("[!]]", "]"));
+    assert!(plain("[a-]", "-"));
+    assert!(plain("[-a]", "-"));
+    // a reversed range matches nothing
+    assert!(!plain("[z-a]", "m"));
+    // unterminated brackets are literal
+    assert!(plain("[abc", "[abc"));
+    assert!(plain("a[", "a["));
+    assert!(plain("[é-ê]", "ê"));
+}
+
+#[test]
+fn bracket_classes() {
+    assert!(plain("[[:digit:]]*", "1abc"));
+    assert!(!plain("[[:digit:]]*", "abc"));
+    assert!(plain("[[:alpha:][:digit:]]", "x"));
+    assert!(plain("[[:alpha:][:digit:]]", "7"));
+    assert!(plain("[![:space:]]", "x"));
+    assert!(!plain("[![:space:]]", " "));
+    assert!(plain("[[:upper:]]", "É"));
+    assert!(plain("[[:xdigit:]]", "F"));
+    assert!(plain("[[:punct:]]", "!"));
+    assert!(plain("[[=a=]]", "a"));
+    assert!(plain("[[.-.]]", "-"));
+    // an unterminated class is just a '['
+    assert!(plain("[[:alpha]", ":"));
+    assert!(Pattern::new("[[:foo:]]", MatchFlags::default()).is_err());
+    assert!(Pattern::new("[[.ab.]]", MatchFlags::default()).is_err());
+}
+
+#[test]
+fn pathname_flag() {
+    let flags = MatchFlags {
+        pathname: true,
+        ..MatchFlags::default()
+    };
+    assert!(!check("*.rs", "src/main.rs", flags));
+    assert!(check("*/*.rs", "src/main.rs", flags));
+    assert!(!check("src?main.rs", "src/main.rs", flags));
+    assert!(!check("src[/]main.rs", "src/main.rs", flags));
+
+    assert!(check("**/*.rs", "main.rs", flags));
+    assert!(check("**/*.rs", "a/b/c/main.rs", flags));
+    assert!(check("src/**/mod.rs", "src/util/mod.rs", flags));
+    assert!(check("src/**/mod.rs", "src/mod.rs", flags));
+    assert!(!check("src/**/mod.rs", "src/xmod.rs", flags));
+    assert!(check("src/**", "src/a/b", flags));
+    // `**` that is only part of a component is a normal star
+    assert!(!check("a**", "ab/c", flags));
+}
+
+#[test]
+fn period_flag() {
+    let flags = MatchFlags {
+        period: true,
+        ..MatchFlags::default()
+    };
+    assert!(!check("*", ".hidden", flags));
+    assert!(!check("?hidden", ".hidden", flags));
+    assert!(!check("[.]hidden", ".hidden", flags));
+    assert!(check(".*", ".hidden", flags));
+    assert!(check("a*", "a.b", flags));
+    // without FNM_PATHNAME, only the start of the text counts
+    assert!(check("a/*", "a/.b", flags));
+
+    let flags = MatchFlags {
+        pathname: true,
+        period: true,
+        ..MatchFlags::default()
+    };
+    assert!(!check("a/*", "a/.b", flags));
+    assert!(!check("**/x", ".git/x", flags));
+    assert!(check("a/.*", "a/.b", flags));
+}
+
+#[test]
+fn casefold_flag() {
+    let flags = MatchFlags {
+        casefold: true,
+        ..MatchFlags::default()
+    };
+    assert!(check("*.TXT", "readme.txt", flags));
+    assert!(check("ÉTÉ", "été", flags));
+    assert!(check("[a-z]", "Q", flags));
+    assert!(check("[A-Z]", "q", flags));
+    assert!(check("[[:upper:]]", "q", flags));
+    assert!(!check("[!a-z]", "Q", flags));
+    assert!(!plain("*.TXT", "readme.txt"));
+}
+
+#[test]
+fn invalid_utf8() {
+    let pattern = Pattern::new("a?c", MatchFlags::default()).unwrap();
+    assert!(pattern.is_match_bytes(b"a\xffc"));
+    assert!(!pattern.is_match_bytes(b"a\xff\xfec"));
+
+    let pattern = Pattern::new("*.txt", MatchFlags::default()).unwrap();
+    assert!(pattern.is_match_bytes(b"\xff\xfe.txt"));
+}
@@ -0,0 +1,114 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+//! The headers printed before each file by utilities that show parts of several files one after
+//! another (like `head`).
+//!
+//! By default the headers look like GNU's (`==> NAME <==`, with a blank line before every header
+//! but the first).  As those are awkward to split output on reliably, `--header-format` replaces
+//! them with a line of the user's choosing in which `%f` is the name of the file.
+
+use clap::{Arg, ArgMatches};
+use std::ffi::OsStr;
+use std::io::{self, Write};
+
+use super::os_str_as_bytes;
+
+const HEADER_FORMAT: &str = "header-format";
+
+/// Create the `--header-format` argument.  The result should be passed to
+/// `FileHeader::from_matches()`.
+pub fn header_format_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(HEADER_FORMAT)
+        .long(HEADER_FORMAT)
+        .takes_value(true)
+        .value_name("FORMAT")
+        .help("Print FORMAT (with %f replaced by the file name and %% by %) as each file header")
+}
+
+/// Writes the header before each file.
+#[derive(Clone, Debug, Default)]
+pub struct FileHeader {
+    // None means the GNU format
+    format: Option<Vec<u8>>,
+    printed: bool,
+}
+
+impl FileHeader {
+    /// Determine the format of the headers using the argument created by `header_format_arg()`.
+    pub fn from_matches(matches: &ArgMatches) -> Self {
+        Self {
+            format: matches
+                .value_of_os(HEADER_FORMAT)
+                .map(|format| os_str_as_bytes(format).into_owned()),
+            printed: false,
+        }
+    }
+
+    /// Write the header for the file called `name`.
+    pub fn write<W: Write>(&mut self, output: &mut W, name: &OsStr) -> io::Result<()> {
+        let name = os_str_as_bytes(name);
+        match self.format {
+            Some(ref format) => {
+                output.write_all(&expand_format(format, &name))?;
+            }
+            None => {
+                if self.printed {
+                    output.write_all(b"\n")?;
+                }
+                output.write_all(b"==> ")?;
+                output.write_all(&name)?;
+                output.write_all(b" <==")?;
+            }
+        }
+        self.printed = true;
+        output.write_all(b"\n")
+    }
+}
+
+// replace %f in `format` with `name` and %% with %, leaving any other use of % alone
+fn expand_format(format: &[u8], name: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(format.len() + name.len());
+    let mut bytes = format.iter();
+    while let Some(&byte) = bytes.next() {
+        if byte != b'%' {
+            result.push(byte);
+            continue;
+        }
+        match bytes.as_slice().first() {
+            Some(&b'f') => result.extend_from_slice(name),
+            Some(&b'%') => result.push(b'%'),
+            _ => {
+                result.push(b'%');
+                continue;
+            }
+        }
+        bytes.next();
+    }
+    result
+}
+
+#[test]
+fn header_formats() {
+    let mut output = vec![];
+    let mut header = FileHeader::default();
+    header.write(&mut output, OsStr::new("a")).unwrap();
+    header.write(&mut output, OsStr::new("b")).unwrap();
+    assert_eq!(output, b"==> a <==\n\n==> b <==\n".to_vec());
+
+    let mut output = vec![];
+    let mut header = FileHeader {
+        format: Some(b"--- %f ---".to_vec()),
+        printed: false,
+    };
+    header.write(&mut output, OsStr::new("a")).unwrap();
+    header.write(&mut output, OsStr::new("b")).unwrap();
+    assert_eq!(output, b"--- a ---\n--- b ---\n".to_vec());
+
+    assert_eq!(expand_format(b"%f: 100%% %d%", b"x"), b"x: 100% %d%".to_vec());
+}
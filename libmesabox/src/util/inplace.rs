@@ -0,0 +1,162 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+//! Atomic in-place editing of files (like `sed -i`).
+//!
+//! The new contents are written to a temporary file in the same directory as the original, which
+//! is renamed over the original once everything has been written.  Other processes thus see either
+//! the old contents or the new ones but never a partially written file, and if anything goes wrong
+//! before `InPlace::commit()` is called, dropping the `InPlace` leaves the original untouched.
+
+use std::fs::{File, Metadata};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+use super::backup::{link_backup, Backup};
+#[cfg(unix)]
+use super::tempreg::TempPath;
+use {PathError, PathOperation, Result};
+
+/// A replacement for a file that is being written.
+pub struct InPlace {
+    path: PathBuf,
+    temp: NamedTempFile,
+    // NamedTempFile only removes the file when dropped, which doesn't happen if we are killed
+    #[cfg(unix)]
+    cleanup: TempPath,
+}
+
+impl InPlace {
+    /// Create a temporary file next to `path` that will replace it when `commit()` is called.  The
+    /// temporary file is given the permissions (and, if possible, the owner) in `metadata`, which
+    /// should be the metadata of `path`.
+    pub fn new<P: AsRef<Path>>(path: P, metadata: &Metadata) -> Result<Self> {
+        let path = path.as_ref().to_owned();
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_owned(),
+            _ => PathBuf::from("."),
+        };
+
+        let temp = NamedTempFile::new_in(&dir)
+            .map_err(|e| PathError::new(PathOperation::OpenWrite, &dir, e))?;
+        #[cfg(unix)]
+        let cleanup = TempPath::register(temp.path());
+
+        // the owner is changed first as doing so may clear the setuid and setgid bits
+        copy_owner(temp.as_file(), metadata);
+        temp.as_file()
+            .set_permissions(metadata.permissions())
+            .map_err(|e| PathError::new(PathOperation::Write, temp.path(), e))?;
+
+        Ok(Self {
+            path: path,
+            temp: temp,
+            #[cfg(unix)]
+            cleanup: cleanup,
+        })
+    }
+
+    /// The file the new contents should be written to.
+    pub fn file(&mut self) -> &mut File {
+        self.temp.as_file_mut()
+    }
+
+    /// Replace the original file with the new contents.  If `backup` is given, the original is
+    /// kept under that name.
+    pub fn commit(self, backup: Option<&Path>) -> Result<()> {
+        if let Some(backup) = backup {
+            link_backup(&self.path, backup)
+                .map_err(|e| PathError::new(PathOperation::Write, backup, e))?;
+        }
+        self.persist()
+    }
+
+    /// Like `commit()`, but naming the backup of the original as `backup` says to.  Returns the
+    /// name of the backup (if one was made).
+    pub fn commit_with_backup(self, backup: &Backup) -> Result<Option<PathBuf>> {
+        // as in commit(), the original is linked to the backup before the replacement is renamed
+        // over it rather than being moved away first
+        let backup = backup.link(&self.path)?;
+        self.persist()?;
+        Ok(backup)
+    }
+
+    fn persist(self) -> Result<()> {
+        let path = self.path;
+        self.temp
+            .persist(&path)
+            .map_err(|e| PathError::new(PathOperation::Write, &path, e.error))?;
+        #[cfg(unix)]
+        let _ = self.cleanup.keep();
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn copy_owner(file: &File, metadata: &Metadata) {
+    use nix::libc;
+    use std::os::unix::fs::MetadataExt;
+    use std::os::unix::io::AsRawFd;
+
+    // only root may give a file away, but the group can still be changed to any group we are in
+    let fd = file.as_raw_fd();
+    unsafe {
+        if libc::fchown(fd, metadata.uid(), metadata.gid()) != 0 {
+            libc::fchown(fd, libc::uid_t::max_value(), metadata.gid());
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn copy_owner(_file: &File, _metadata: &Metadata) {}
+
+#[test]
+fn inplace_commit() {
+    use std::fs;
+    use std::io::Write;
+    use tempfile;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    let file = dir.join("file");
+    let backup = dir.join("file.bak");
+    fs::write(&file, "old").unwrap();
+    fs::write(&backup, "older").unwrap();
+    let mut permissions = fs::metadata(&file).unwrap().permissions();
+    permissions.set_readonly(true);
+    fs::set_permissions(&file, permissions).unwrap();
+
+    let mut editor = InPlace::new(&file, &fs::metadata(&file).unwrap()).unwrap();
+    editor.file().write_all(b"new").unwrap();
+    editor.commit(Some(&backup)).unwrap();
+
+    assert_eq!(fs::read(&file).unwrap(), b"new");
+    assert_eq!(fs::read(&backup).unwrap(), b"old");
+    assert!(fs::metadata(&file).unwrap().permissions().readonly());
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+}
+
+#[test]
+fn inplace_discard() {
+    use std::fs;
+    use std::io::Write;
+    use tempfile;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    let file = dir.join("file");
+    fs::write(&file, "old").unwrap();
+
+    {
+        let mut editor = InPlace::new(&file, &fs::metadata(&file).unwrap()).unwrap();
+        editor.file().write_all(b"new").unwrap();
+    }
+
+    assert_eq!(fs::read(&file).unwrap(), b"old");
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+}
@@ -0,0 +1,123 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+//! Formatting of line numbers (for `cat -n` and utilities like `nl`).
+//!
+//! A number is padded to a minimum width using one of the justification styles of `nl -n` and
+//! followed by a separator.  Numbers wider than the field are printed in full rather than being
+//! cut short, so the columns only stop lining up once the numbers get that large.
+
+use std::io::{self, Write};
+
+/// How a line number is placed within its field.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Justify {
+    /// Left justified, padded with spaces (`ln`)
+    Left,
+    /// Right justified, padded with spaces (`rn`)
+    Right,
+    /// Right justified, padded with zeros (`rz`)
+    RightZero,
+}
+
+impl Justify {
+    /// Parse one of the style names accepted by `nl -n`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ln" => Some(Justify::Left),
+            "rn" => Some(Justify::Right),
+            "rz" => Some(Justify::RightZero),
+            _ => None,
+        }
+    }
+}
+
+/// The layout of the line numbers written by a utility.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LineNumberFormat<'a> {
+    pub justify: Justify,
+    /// The minimum width of the number
+    pub width: usize,
+    /// Written after the number, between it and the line
+    pub separator: &'a [u8],
+}
+
+/// The format used by `cat -n` (and by `nl` unless told otherwise).
+pub const DEFAULT_FORMAT: LineNumberFormat<'static> = LineNumberFormat {
+    justify: Justify::Right,
+    width: 6,
+    separator: b"\t",
+};
+
+impl<'a> LineNumberFormat<'a> {
+    /// Write `number` followed by the separator to `output`.
+    pub fn write<W: Write + ?Sized>(&self, output: &mut W, number: i64) -> io::Result<()> {
+        let width = self.width;
+        match self.justify {
+            Justify::Left => write!(output, "{:<1$}", number, width)?,
+            Justify::Right => write!(output, "{:>1$}", number, width)?,
+            // the zeros go after the sign, like with printf("%0*d")
+            Justify::RightZero => write!(output, "{:01$}", number, width)?,
+        }
+        output.write_all(self.separator)
+    }
+}
+
+impl<'a> Default for LineNumberFormat<'a> {
+    fn default() -> Self {
+        DEFAULT_FORMAT
+    }
+}
+
+#[cfg(test)]
+fn format_number(format: &LineNumberFormat, number: i64) -> String {
+    let mut output = vec![];
+    format.write(&mut output, number).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn linenum_styles() {
+    assert_eq!(format_number(&DEFAULT_FORMAT, 42), "    42\t");
+
+    let mut format = LineNumberFormat {
+        justify: Justify::Left,
+        width: 4,
+        separator: b" | ",
+    };
+    assert_eq!(format_number(&format, 7), "7    | ");
+    format.justify = Justify::RightZero;
+    assert_eq!(format_number(&format, 7), "0007 | ");
+    assert_eq!(format_number(&format, -7), "-007 | ");
+    format.justify = Justify::Right;
+    assert_eq!(format_number(&format, -7), "  -7 | ");
+
+    assert_eq!(Justify::from_name("rz"), Some(Justify::RightZero));
+    assert_eq!(Justify::from_name("lz"), None);
+}
+
+#[test]
+fn linenum_overflow() {
+    // numbers wider than the field are never truncated
+    for &justify in &[Justify::Left, Justify::Right, Justify::RightZero] {
+        let format = LineNumberFormat {
+            justify: justify,
+            width: 3,
+            separator: b"\t",
+        };
+        assert_eq!(format_number(&format, 123456), "123456\t");
+        assert_eq!(format_number(&format, -1234), "-1234\t");
+    }
+
+    let format = LineNumberFormat {
+        width: 0,
+        separator: b"",
+        ..DEFAULT_FORMAT
+    };
+    assert_eq!(format_number(&format, 5), "5");
+}
@@ -0,0 +1,137 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use std::io::{self, BufRead, Write};
+
+use super::LineDelimiter;
+
+/// Copy lines `start` through `end` (numbered from 1, inclusive) from `input` to `output`, where
+/// an `end` of `None` means the end of the input.  This works directly on the buffer of `input`,
+/// so lines are never collected in memory, and nothing is read after line `end`.  Returns the
+/// number of lines that were copied.
+pub fn take_range<R, W>(
+    input: &mut R,
+    output: &mut W,
+    start: usize,
+    end: Option<usize>,
+    delimiter: LineDelimiter,
+) -> io::Result<usize>
+where
+    R: BufRead + ?Sized,
+    W: Write + ?Sized,
+{
+    let delim = delimiter.byte();
+    let start = start.max(1);
+    if end.map(|end| end < start).unwrap_or(false) {
+        return Ok(0);
+    }
+
+    // the number of the line at the current position in the input
+    let mut line = 1;
+
+    while line < start {
+        let (consumed, finished_line) = {
+            let buf = input.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(0);
+            }
+            match buf.iter().position(|&b| b == delim) {
+                Some(idx) => (idx + 1, true),
+                None => (buf.len(), false),
+            }
+        };
+        input.consume(consumed);
+        if finished_line {
+            line += 1;
+        }
+    }
+
+    let mut copied = 0;
+    // whether part of the current line has been written already
+    let mut partial = false;
+
+    while end.map(|end| line <= end).unwrap_or(true) {
+        let (consumed, finished_line) = {
+            let buf = input.fill_buf()?;
+            if buf.is_empty() {
+                break;
+            }
+            let (consumed, finished_line) = match buf.iter().position(|&b| b == delim) {
+                Some(idx) => (idx + 1, true),
+                None => (buf.len(), false),
+            };
+            output.write_all(&buf[..consumed])?;
+            (consumed, finished_line)
+        };
+        input.consume(consumed);
+
+        if finished_line {
+            line += 1;
+            copied += 1;
+            partial = false;
+        } else {
+            partial = true;
+        }
+    }
+
+    // a final line without a delimiter still counts
+    if partial {
+        copied += 1;
+    }
+    Ok(copied)
+}
+
+#[cfg(test)]
+fn range_str(data: &str, start: usize, end: Option<usize>) -> (String, usize, String) {
+    let mut input = data.as_bytes();
+    let mut output = vec![];
+    let count = take_range(&mut input, &mut output, start, end, LineDelimiter::Newline).unwrap();
+    (
+        String::from_utf8(output).unwrap(),
+        count,
+        String::from_utf8(input.to_vec()).unwrap(),
+    )
+}
+
+#[test]
+fn take_range_middle() {
+    let (output, count, rest) = range_str("1\n2\n3\n4\n5\n", 2, Some(4));
+    assert_eq!(output, "2\n3\n4\n");
+    assert_eq!(count, 3);
+    // nothing past the last line is consumed
+    assert_eq!(rest, "5\n");
+}
+
+#[test]
+fn take_range_edges() {
+    assert_eq!(range_str("1\n2\n3", 2, None), ("2\n3".to_owned(), 2, "".to_owned()));
+    assert_eq!(range_str("1\n2\n", 5, Some(8)), ("".to_owned(), 0, "".to_owned()));
+    assert_eq!(range_str("1\n2\n", 2, Some(1)), ("".to_owned(), 0, "1\n2\n".to_owned()));
+    assert_eq!(range_str("1\n2\n", 0, Some(1)), ("1\n".to_owned(), 1, "2\n".to_owned()));
+}
+
+#[test]
+fn take_range_small_buffer() {
+    use std::io::BufReader;
+
+    let data = "first line\nsecond line\nthird line\n";
+    let mut input = BufReader::with_capacity(4, data.as_bytes());
+    let mut output = vec![];
+    let count = take_range(&mut input, &mut output, 2, Some(2), LineDelimiter::Newline).unwrap();
+    assert_eq!(output, b"second line\n");
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn take_range_nul() {
+    let mut input = &b"a\nb\0c\0d\0"[..];
+    let mut output = vec![];
+    let count = take_range(&mut input, &mut output, 2, Some(3), LineDelimiter::Nul).unwrap();
+    assert_eq!(output, b"c\0d\0");
+    assert_eq!(count, 2);
+}
@@ -0,0 +1,148 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use nix::libc;
+use std::cmp;
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::unix::io::AsRawFd;
+use std::ptr;
+use std::slice;
+
+/// Mappings smaller than this are not worth the cost of setting up (reading the data normally is
+/// just as fast).
+pub const MMAP_THRESHOLD: u64 = 64 * 1024;
+
+// amount of data written between checks for truncation in write_to()
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// A read-only memory mapping of (part of) a regular file.
+pub struct MappedFile<'a> {
+    file: &'a File,
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+impl<'a> MappedFile<'a> {
+    /// Map at most `limit` bytes from the start of `file`.  If `file` is not a regular file or the
+    /// amount of data that would be mapped is smaller than `MMAP_THRESHOLD`, `Ok(None)` is
+    /// returned and the caller should fall back to reading the file normally.
+    pub fn new(file: &'a File, limit: Option<u64>) -> io::Result<Option<Self>> {
+        let metadata = file.metadata()?;
+        if !metadata.is_file() {
+            return Ok(None);
+        }
+
+        let len = limit.map(|limit| limit.min(metadata.len())).unwrap_or(metadata.len());
+        if len < MMAP_THRESHOLD || len > usize::max_value() as u64 {
+            return Ok(None);
+        }
+
+        Self::map(file, len as usize).map(Some)
+    }
+
+    fn map(file: &'a File, len: usize) -> io::Result<Self> {
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        // this is purely a hint, so ignore failures
+        unsafe {
+            libc::madvise(ptr, len, libc::MADV_SEQUENTIAL);
+        }
+
+        Ok(Self {
+            file: file,
+            ptr: ptr,
+            len: len,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Get the mapped data.
+    ///
+    /// Take note that if the file is truncated while the mapping exists, accessing the part of the
+    /// slice past the new end of the file will raise `SIGBUS`.  Prefer `write_to()` unless the
+    /// file is known not to change.
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+
+    /// Write the mapped data to `output`, returning the number of bytes written.  The size of the
+    /// file is checked again before each chunk is written so that data removed by a concurrent
+    /// truncation is not accessed (in which case fewer than `len()` bytes will be written).
+    pub fn write_to<W: Write>(&self, mut output: W) -> io::Result<u64> {
+        let data = self.as_slice();
+
+        let mut written = 0;
+        while written < data.len() {
+            // NOTE: there is still a tiny window between this check and the write in which the
+            //       file could be truncated, but avoiding that would require a SIGBUS handler
+            let current_len = self.file.metadata()?.len();
+            if current_len <= written as u64 {
+                break;
+            }
+
+            let end = cmp::min(current_len, (written + CHUNK_SIZE) as u64);
+            let end = cmp::min(end, data.len() as u64) as usize;
+            output.write_all(&data[written..end])?;
+            written = end;
+        }
+
+        Ok(written as u64)
+    }
+}
+
+impl<'a> Drop for MappedFile<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr, self.len);
+        }
+    }
+}
+
+#[cfg(test)]
+fn test_file() -> File {
+    File::open(concat!(env!("CARGO_MANIFEST_DIR"), "/src/util/mmap.rs")).unwrap()
+}
+
+#[test]
+fn mmap_matches_read() {
+    use std::io::Read;
+
+    let mut file = test_file();
+    let mut expected = vec![];
+    file.read_to_end(&mut expected).unwrap();
+
+    let mapping = MappedFile::map(&file, expected.len()).unwrap();
+    assert_eq!(mapping.as_slice(), &expected[..]);
+
+    let mut output = vec![];
+    assert_eq!(mapping.write_to(&mut output).unwrap(), expected.len() as u64);
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn mmap_below_threshold() {
+    let file = test_file();
+    assert!(MappedFile::new(&file, Some(MMAP_THRESHOLD - 1)).unwrap().is_none());
+    assert!(MappedFile::new(&file, Some(0)).unwrap().is_none());
+}
@@ -6,22 +6,95 @@
 // For a copy, see the LICENSE file.
 //
 
-pub use self::platform::{is_tty, AsRawObject, OsStrExt, Pipe, RawObject, RawObjectWrapper};
-use super::{LockableRead, LockableWrite, MesaError, Result};
+pub use self::delim::{
+    is_line_buffered, line_buffered_arg, zero_terminated_arg, LineDelimiter, LineFlusher,
+};
+pub use self::platform::{
+    is_tty, os_str_as_bytes, os_str_from_bytes, terminal_columns, AsRawObject, OsStrExt, Pipe,
+    RawObject, RawObjectWrapper,
+};
+pub use self::env_vars::EnvVars;
+pub use self::header::{header_format_arg, FileHeader};
+#[cfg(feature = "tempfile")]
+pub use self::ringtail::RingTail;
+pub use self::write_error::{
+    is_broken_pipe, is_output_error, output_error, OutputError, OutputWriter, WriteError,
+    OUTPUT_ERROR_VAR,
+};
+use super::{LockableRead, LockableWrite, MesaError, PathError, PathOperation, Result};
 
 use failure;
 use std::borrow::Cow;
 use std::error::Error as StdError;
+use std::ffi::OsStr;
+use std::fs::File;
 use std::io::{self, Read, Write};
 use std::path::Path;
 use std::result::Result as StdResult;
 use std::str::FromStr;
 
+pub mod backup;
+pub mod binary;
+pub mod casefold;
+pub mod color;
+#[cfg(feature = "decompress")]
+pub mod decompress;
+mod delim;
+#[cfg(unix)]
+pub mod dirfd;
+pub mod dump;
+mod env_vars;
+pub mod glob;
+mod header;
+#[cfg(feature = "tempfile")]
+pub mod inplace;
+pub mod linenum;
+pub mod lines;
+#[cfg(all(unix, feature = "mmap"))]
+pub mod mmap;
+pub mod path;
 mod platform;
+#[cfg(feature = "tempfile")]
+mod ringtail;
+#[cfg(feature = "regex")]
+pub mod posix_regex;
+#[cfg(unix)]
+pub mod preserve_root;
+pub mod progress;
+pub mod ratelimit;
+#[cfg(unix)]
+pub mod sparse;
+#[cfg(feature = "tempfile")]
+pub mod spill;
+#[cfg(unix)]
+pub mod tempreg;
+pub mod terminfo;
+#[cfg(feature = "chrono")]
+pub mod timefmt;
+#[cfg(unix)]
+pub mod userdb;
+pub mod utf8;
+pub mod vercmp;
+mod write_error;
 
 pub const EXIT_SUCCESS: ExitCode = 0;
 pub const EXIT_FAILURE: ExitCode = 1;
 
+/// The environment variable that overrides the size of the I/O buffers used by streaming
+/// utilities such as `cat`, `head`, and `yes`.  The value may use the same suffixes as other size
+/// arguments (_e.g._ `64K`).
+pub const BUFFER_SIZE_VAR: &str = "MESABOX_BUFSIZE";
+
+/// The width assumed for output that is not going to a terminal (when `COLUMNS` is not set).
+pub const DEFAULT_TERMINAL_WIDTH: usize = 80;
+
+// buffer sizes outside of this range are ignored in favor of each utility's default
+const MIN_BUFFER_SIZE: usize = 512;
+const MAX_BUFFER_SIZE: usize = 64 * 1024 * 1024;
+
+// the largest list of filenames expand_argfiles() reads for a single @FILE operand
+const MAX_ARGFILE_SIZE: u64 = 16 * 1024 * 1024;
+
 // defined out here rather than in parse_num_with_suffix() because we need the array for testing
 const SUFFIXES: [char; 8] = ['K', 'M', 'G', 'T', 'P', 'E', 'Z', 'Y'];
 const OBSOLETE_SUFFIXES: [char; 2] = ['k', 'm'];
@@ -150,6 +223,111 @@ where
     }
 }
 
+/// Open `path` (relative to `current_dir`) for reading.  If this fails, the error will mention
+/// `path` as given rather than the path that was actually opened.
+pub fn open_file<D, P>(current_dir: &Option<D>, path: &P) -> Result<File>
+where
+    D: AsRef<Path>,
+    P: AsRef<Path> + ?Sized,
+{
+    File::open(actual_path(current_dir, path))
+        .map_err(|e| PathError::new(PathOperation::OpenRead, path.as_ref(), e).into())
+}
+
+/// Expand any `@FILE` operands in `args` into the filenames listed in `FILE` (separated by
+/// `delimiter`, with empty entries ignored).  Filenames read from `FILE` are used as-is, so `@`
+/// operands do not nest.  A file whose name actually starts with `@` can still be accessed using
+/// `./@name`.  Lists larger than 16 MiB are rejected rather than read until memory runs out (as
+/// would happen with `@/dev/zero`).
+pub fn expand_argfiles<'a, D, I>(
+    current_dir: &Option<D>,
+    args: I,
+    delimiter: LineDelimiter,
+) -> Result<Vec<Cow<'a, OsStr>>>
+where
+    D: AsRef<Path>,
+    I: IntoIterator<Item = &'a OsStr>,
+{
+    let mut result = vec![];
+    for arg in args {
+        let bytes = os_str_as_bytes(arg);
+        if bytes.len() < 2 || bytes[0] != b'@' {
+            result.push(Cow::Borrowed(arg));
+            continue;
+        }
+
+        let listname = os_str_from_bytes(&bytes[1..]);
+        let file = open_file(current_dir, &*listname)?;
+        let mut data = vec![];
+        file.take(MAX_ARGFILE_SIZE + 1)
+            .read_to_end(&mut data)
+            .map_err(|e| PathError::new(PathOperation::Read, &*listname, e))?;
+        if data.len() as u64 > MAX_ARGFILE_SIZE {
+            let err = io::Error::new(io::ErrorKind::InvalidData, "file list too large");
+            return Err(PathError::new(PathOperation::Read, &*listname, err).into());
+        }
+
+        // empty entries (such as the one after the final delimiter) are not valid filenames
+        let names = data
+            .split(|&byte| byte == delimiter.byte())
+            .filter(|name| !name.is_empty());
+        result.extend(names.map(|name| Cow::Owned(os_str_from_bytes(name).into_owned())));
+    }
+    Ok(result)
+}
+
+/// Determine how many columns of output fit on a line, using `COLUMNS` if it is set and the size
+/// of the terminal `stream` refers to otherwise.
+pub fn terminal_width(env: &EnvVars, stream: Option<RawObject>) -> usize {
+    let columns = env.get("COLUMNS")
+        .and_then(|v| v.to_str())
+        .and_then(|v| v.parse().ok());
+    match columns {
+        Some(width) if width > 0 => width,
+        _ => terminal_columns(stream).unwrap_or(DEFAULT_TERMINAL_WIDTH),
+    }
+}
+
+/// Split a device number (like `st_dev` or `st_rdev`) into its major and minor numbers, using the
+/// encoding of glibc and musl.
+pub fn device_numbers(dev: u64) -> (u64, u64) {
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & 0xffff_f000);
+    let minor = (dev & 0xff) | ((dev >> 12) & 0xffff_ff00);
+    (major, minor)
+}
+
+/// Determine the buffer size requested using `BUFFER_SIZE_VAR`, if any.
+pub(crate) fn buffer_size_from_env(env: &EnvVars) -> Option<usize> {
+    env.get(BUFFER_SIZE_VAR).and_then(parse_buffer_size)
+}
+
+fn parse_buffer_size(value: &OsStr) -> Option<usize> {
+    match value.to_str().and_then(parse_num_with_suffix) {
+        Some(size) if size >= MIN_BUFFER_SIZE && size <= MAX_BUFFER_SIZE => Some(size),
+        _ => None,
+    }
+}
+
+/// Like `io::copy()`, but using a buffer of `buffer_size` bytes.
+pub fn copy_with_buffer<R, W>(reader: &mut R, writer: &mut W, buffer_size: usize) -> io::Result<u64>
+where
+    R: Read + ?Sized,
+    W: Write + ?Sized,
+{
+    let mut buffer = vec![0; buffer_size];
+    let mut written = 0;
+    loop {
+        let n = match reader.read(&mut buffer) {
+            Ok(0) => return Ok(written),
+            Ok(n) => n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        writer.write_all(&buffer[..n])?;
+        written += n as u64;
+    }
+}
+
 /// Parse an integer with a suffix like "kb" or "MB".
 pub fn parse_num_with_suffix(s: &str) -> Option<usize> {
     parse_num_common(s, &SUFFIXES, false)
@@ -223,7 +401,7 @@ fn pow(mut base: usize, mut exp: u32) -> Option<usize> {
 
 #[test]
 fn parse_num_invalid() {
-    let strings = ["  1", "1  ", "  1  ", "1X", "b", "1 b", "-1"];
+    let strings = ["", "  1", "1  ", "  1  ", "1X", "b", "1 b", "-1", "1\u{e9}", "\u{e9}k"];
     for s in strings.iter() {
         assert_eq!(parse_num_with_suffix(s), None);
     }
@@ -285,3 +463,30 @@ fn pow_correct() {
     assert_eq!(pow(2, 16), Some(65536));
     assert_eq!(pow(256, 2), Some(65536));
 }
+
+#[test]
+fn device_number_encoding() {
+    assert_eq!(device_numbers(0x0801), (8, 1));
+    assert_eq!(device_numbers(0x1234_5678_9abc_def0), (0x1234_5cde, 0x6789_abf0));
+}
+
+#[test]
+fn buffer_size_values() {
+    assert_eq!(parse_buffer_size(OsStr::new("4096")), Some(4096));
+    assert_eq!(parse_buffer_size(OsStr::new("64K")), Some(64 * 1024));
+    assert_eq!(parse_buffer_size(OsStr::new("1")), None);
+    assert_eq!(parse_buffer_size(OsStr::new("1G")), None);
+    assert_eq!(parse_buffer_size(OsStr::new("lots")), None);
+
+    let env: EnvVars = vec![(BUFFER_SIZE_VAR, "8K")].into_iter().collect();
+    assert_eq!(buffer_size_from_env(&env), Some(8 * 1024));
+    assert_eq!(buffer_size_from_env(&EnvVars::new()), None);
+}
+
+#[test]
+fn copy_small_buffer() {
+    let mut input = &b"some data that is longer than the buffer"[..];
+    let mut output = vec![];
+    assert_eq!(copy_with_buffer(&mut input, &mut output, 3).unwrap(), 40);
+    assert_eq!(output, b"some data that is longer than the buffer".to_vec());
+}
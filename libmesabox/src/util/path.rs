@@ -0,0 +1,98 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+//! Splitting paths into their last component and the directory containing it, following the
+//! rules POSIX gives for `basename` and `dirname`.
+//!
+//! Unlike `Path::file_name()` and `Path::parent()`, these work on the path as written: trailing
+//! slashes are ignored, a path made only of slashes is `/`, and components like `..` are kept
+//! (so the last component of `a/..` is `..` rather than nothing).
+
+use std::borrow::Cow;
+use std::ffi::OsStr;
+
+use super::{os_str_as_bytes, os_str_from_bytes};
+
+/// Find the last component of `path` (what `basename` prints).
+pub fn split_basename(path: &OsStr) -> Cow<OsStr> {
+    map_bytes(path, basename_bytes)
+}
+
+/// Find the directory containing the last component of `path` (what `dirname` prints).
+pub fn split_dirname(path: &OsStr) -> Cow<OsStr> {
+    map_bytes(path, dirname_bytes)
+}
+
+fn map_bytes<F>(path: &OsStr, func: F) -> Cow<OsStr>
+where
+    F: Fn(&[u8]) -> &[u8],
+{
+    match os_str_as_bytes(path) {
+        Cow::Borrowed(bytes) => os_str_from_bytes(func(bytes)),
+        Cow::Owned(bytes) => Cow::Owned(os_str_from_bytes(func(&bytes)).into_owned()),
+    }
+}
+
+fn basename_bytes(path: &[u8]) -> &[u8] {
+    let path = trim_trailing_slashes(path);
+    match path.iter().rposition(|&b| b == b'/') {
+        // a path made only of slashes (trimmed to one) is kept as it is
+        Some(pos) if path.len() > 1 => &path[pos + 1..],
+        _ => path,
+    }
+}
+
+fn dirname_bytes(path: &[u8]) -> &[u8] {
+    let path = trim_trailing_slashes(path);
+    match path.iter().rposition(|&b| b == b'/') {
+        // a path made only of slashes is its own directory, so this is never empty
+        Some(pos) => trim_trailing_slashes(&path[..pos + 1]),
+        // the empty path is treated like any other relative path
+        None => b".",
+    }
+}
+
+// remove the slashes at the end of `path`, leaving a single slash if there is nothing else
+fn trim_trailing_slashes(path: &[u8]) -> &[u8] {
+    match path.iter().rposition(|&b| b != b'/') {
+        Some(pos) => &path[..pos + 1],
+        None if path.is_empty() => path,
+        None => &path[..1],
+    }
+}
+
+#[test]
+fn path_split_table() {
+    // the path, its basename and its dirname
+    let cases = [
+        ("", "", "."),
+        ("/", "/", "/"),
+        ("//", "/", "/"),
+        ("///", "/", "/"),
+        ("a", "a", "."),
+        ("a/", "a", "."),
+        ("a//", "a", "."),
+        ("/a", "a", "/"),
+        ("//a//", "a", "/"),
+        ("a/b", "b", "a"),
+        ("a/b/", "b", "a"),
+        ("a//b//", "b", "a"),
+        ("/a/b", "b", "/a"),
+        ("/a//b", "b", "/a"),
+        ("a/..", "..", "a"),
+        ("./a", "a", "."),
+        (".", ".", "."),
+        ("..", "..", "."),
+        ("../a/b", "b", "../a"),
+    ];
+    for &(path, basename, dirname) in &cases {
+        let path = OsStr::new(path);
+        assert_eq!(split_basename(path), OsStr::new(basename), "basename of {:?}", path);
+        assert_eq!(split_dirname(path), OsStr::new(dirname), "dirname of {:?}", path);
+    }
+}
@@ -1,7 +1,9 @@
-use nix::{fcntl, unistd};
+use nix::{fcntl, libc, unistd};
 
+use std::borrow::Cow;
 use std::ffi::OsStr;
 use std::io::{self, Read, Write};
+use std::mem;
 use std::os::unix::ffi::OsStrExt;
 use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
 use std::process::Stdio;
@@ -14,6 +16,16 @@ impl super::OsStrExt for OsStr {
     }
 }
 
+/// Get the bytes that make up `s`.  On Unix, this is free.
+pub fn os_str_as_bytes(s: &OsStr) -> Cow<[u8]> {
+    Cow::Borrowed(s.as_bytes())
+}
+
+/// Create an `OsStr` from bytes in the format returned by `os_str_as_bytes()`.
+pub fn os_str_from_bytes(bytes: &[u8]) -> Cow<OsStr> {
+    Cow::Borrowed(OsStr::from_bytes(bytes))
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct RawObject(RawFd);
 
@@ -196,8 +208,6 @@ impl Drop for Pipe {
 
 impl From<Pipe> for Stdio {
     fn from(pipe: Pipe) -> Self {
-        use std::mem;
-
         let obj = pipe.as_raw_object();
         let stdio = unsafe { Stdio::from_raw_fd(obj.raw_value()) };
 
@@ -215,6 +225,18 @@ pub fn is_tty(stream: Option<RawObject>) -> bool {
         .unwrap_or(false)
 }
 
+/// Get the number of columns of the terminal `stream` refers to (if it is a terminal).
+pub fn terminal_columns(stream: Option<RawObject>) -> Option<usize> {
+    let fd = stream?.raw_value();
+    let mut size: libc::winsize = unsafe { mem::zeroed() };
+    let res = unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut size) };
+    if res == 0 && size.ws_col > 0 {
+        Some(size.ws_col as usize)
+    } else {
+        None
+    }
+}
+
 impl<T: AsRawFd> AsRawObject for T {
     fn as_raw_object(&self) -> RawObject {
         RawObject(self.as_raw_fd())
@@ -4,13 +4,17 @@ use winapi::um::minwinbase::SECURITY_ATTRIBUTES;
 use winapi::um::winnt::DUPLICATE_SAME_ACCESS;
 use winapi::um::{consoleapi, fileapi, handleapi, namedpipeapi};
 
-use std::ffi::OsStr;
+use std::borrow::Cow;
+use std::char;
+use std::ffi::{OsStr, OsString};
 use std::fs::File;
 use std::io::{self, Read, Write};
 use std::mem;
 use std::net::TcpStream;
+use std::os::windows::ffi::{OsStrExt, OsStringExt};
 use std::os::windows::io::{AsRawHandle, AsRawSocket, RawHandle, RawSocket};
 use std::ptr;
+use std::str;
 
 use super::{AsRawObject, PipeKind};
 
@@ -23,6 +27,82 @@ impl super::OsStrExt for OsStr {
     }
 }
 
+/// Get the bytes that make up `s` encoded as WTF-8 (so unpaired surrogates, which can occur in
+/// file names, survive a round trip through `os_str_from_bytes()`).  Strings that are valid
+/// Unicode are not copied.
+pub fn os_str_as_bytes(s: &OsStr) -> Cow<[u8]> {
+    if let Some(s) = s.to_str() {
+        return Cow::Borrowed(s.as_bytes());
+    }
+
+    let mut result = Vec::with_capacity(s.len());
+    for res in char::decode_utf16(s.encode_wide()) {
+        match res {
+            Ok(ch) => {
+                let mut buf = [0; 4];
+                result.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+            }
+            Err(e) => {
+                // WTF-8 encodes unpaired surrogates as if they were normal code points
+                let code = e.unpaired_surrogate() as u32;
+                result.push(0xE0 | (code >> 12) as u8);
+                result.push(0x80 | ((code >> 6) & 0x3F) as u8);
+                result.push(0x80 | (code & 0x3F) as u8);
+            }
+        }
+    }
+    Cow::Owned(result)
+}
+
+/// Create an `OsStr` from WTF-8 encoded bytes (as returned by `os_str_as_bytes()`).  Any byte
+/// sequences that are not valid WTF-8 are replaced with U+FFFD.
+pub fn os_str_from_bytes(bytes: &[u8]) -> Cow<OsStr> {
+    if let Ok(s) = str::from_utf8(bytes) {
+        return Cow::Borrowed(OsStr::new(s));
+    }
+
+    let mut wide = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let (code, width) = decode_wtf8(&bytes[idx..]);
+        if code >= 0x10000 {
+            let code = code - 0x10000;
+            wide.push(0xD800 | (code >> 10) as u16);
+            wide.push(0xDC00 | (code & 0x3FF) as u16);
+        } else {
+            wide.push(code as u16);
+        }
+        idx += width;
+    }
+    Cow::Owned(OsString::from_wide(&wide))
+}
+
+// returns the decoded code point (or U+FFFD if the sequence is invalid) and its width in bytes
+fn decode_wtf8(bytes: &[u8]) -> (u32, usize) {
+    const REPLACEMENT: u32 = 0xFFFD;
+
+    let first = bytes[0];
+    let (width, init) = match first {
+        0x00...0x7F => return (first as u32, 1),
+        0xC2...0xDF => (2, first & 0x1F),
+        0xE0...0xEF => (3, first & 0x0F),
+        0xF0...0xF4 => (4, first & 0x07),
+        _ => return (REPLACEMENT, 1),
+    };
+    if bytes.len() < width {
+        return (REPLACEMENT, 1);
+    }
+
+    let mut code = init as u32;
+    for &byte in &bytes[1..width] {
+        if byte & 0xC0 != 0x80 {
+            return (REPLACEMENT, 1);
+        }
+        code = (code << 6) | (byte & 0x3F) as u32;
+    }
+    (code, width)
+}
+
 // The system native representation of files, stdin/stdout/stderr, etc.
 #[derive(Copy, Clone, Debug)]
 pub enum RawObject {
@@ -315,6 +395,12 @@ pub fn is_tty(stream: Option<RawObject>) -> bool {
         .unwrap_or(false)
 }
 
+/// Get the number of columns of the terminal `stream` refers to.  This is not supported on
+/// Windows yet, so callers always fall back to their defaults.
+pub fn terminal_columns(_stream: Option<RawObject>) -> Option<usize> {
+    None
+}
+
 impl AsRawObject for File {
     fn as_raw_object(&self) -> RawObject {
         RawObject::Handle(self.as_raw_handle())
@@ -0,0 +1,188 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+//! Translation of POSIX basic and extended regular expressions into the syntax used by the
+//! regex crate.
+
+use regex;
+use std::iter::Peekable;
+use std::result::Result as StdResult;
+
+#[derive(Debug, Fail)]
+pub enum PatternError {
+    #[fail(display = "{}", _0)]
+    Regex(#[cause] regex::Error),
+
+    #[fail(display = "back-references are not supported")]
+    BackReference,
+
+    #[fail(display = "unmatched [, [^, [:, [., or [=")]
+    UnmatchedBracket,
+
+    #[fail(display = "trailing backslash (\\)")]
+    TrailingBackslash,
+}
+
+impl From<regex::Error> for PatternError {
+    fn from(err: regex::Error) -> Self {
+        PatternError::Regex(err)
+    }
+}
+
+/// Translate a POSIX basic (or extended, if `extended` is true) regular expression into the
+/// syntax used by the regex crate.
+pub fn translate(pattern: &str, extended: bool) -> StdResult<String, PatternError> {
+    let mut result = String::with_capacity(pattern.len() * 2);
+    let mut chars = pattern.chars().peekable();
+    // whether a * here would be literal (at the start of the expression or a group)
+    let mut at_start = true;
+
+    while let Some(ch) = chars.next() {
+        let was_start = at_start;
+        at_start = false;
+
+        match ch {
+            '\\' => {
+                let next = chars.next().ok_or(PatternError::TrailingBackslash)?;
+                match next {
+                    '(' | ')' | '{' | '}' | '|' | '+' | '?' if !extended => {
+                        result.push(next);
+                        at_start = next == '(' || next == '|';
+                    }
+                    '<' | '>' => result.push_str(r"\b"),
+                    '1'...'9' => return Err(PatternError::BackReference),
+                    'w' | 'W' | 's' | 'S' | 'b' | 'B' => {
+                        result.push('\\');
+                        result.push(next);
+                    }
+                    'n' => result.push_str(r"\n"),
+                    '`' => result.push_str(r"\A"),
+                    '\'' => result.push_str(r"\z"),
+                    _ => push_literal(&mut result, next),
+                }
+            }
+            '[' => translate_bracket(&mut chars, &mut result)?,
+            '*' if was_start => push_literal(&mut result, ch),
+            '^' => {
+                result.push('^');
+                at_start = true;
+            }
+            '(' | ')' | '{' | '}' | '|' | '+' | '?' if !extended => push_literal(&mut result, ch),
+            '(' | '|' if extended => {
+                result.push(ch);
+                at_start = true;
+            }
+            '{' if extended && was_start => push_literal(&mut result, ch),
+            _ => result.push(ch),
+        }
+    }
+
+    Ok(result)
+}
+
+fn push_literal(result: &mut String, ch: char) {
+    let mut buf = [0; 4];
+    result.push_str(&regex::escape(ch.encode_utf8(&mut buf)));
+}
+
+fn translate_bracket<I>(
+    chars: &mut Peekable<I>,
+    result: &mut String,
+) -> StdResult<(), PatternError>
+where
+    I: Iterator<Item = char>,
+{
+    result.push('[');
+    if chars.peek() == Some(&'^') {
+        chars.next();
+        result.push('^');
+    }
+    // a ] right at the start is part of the set rather than the end of it
+    if chars.peek() == Some(&']') {
+        chars.next();
+        result.push_str(r"\]");
+    }
+
+    loop {
+        let ch = chars.next().ok_or(PatternError::UnmatchedBracket)?;
+        match ch {
+            ']' => break,
+            '[' => match chars.peek().cloned() {
+                Some(kind @ ':') | Some(kind @ '=') | Some(kind @ '.') => {
+                    chars.next();
+                    let mut name = String::new();
+                    loop {
+                        let ch = chars.next().ok_or(PatternError::UnmatchedBracket)?;
+                        if ch == kind && chars.peek() == Some(&']') {
+                            chars.next();
+                            break;
+                        }
+                        name.push(ch);
+                    }
+                    if kind == ':' {
+                        result.push_str("[:");
+                        result.push_str(&name);
+                        result.push_str(":]");
+                    } else {
+                        // equivalence classes and collating symbols only match themselves in
+                        // the C locale
+                        for ch in name.chars() {
+                            push_literal(result, ch);
+                        }
+                    }
+                }
+                _ => result.push_str(r"\["),
+            },
+            // these are special to the regex crate but not to POSIX
+            '\\' | '&' | '~' => {
+                result.push('\\');
+                result.push(ch);
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                chars.next();
+                result.push_str(r"\-\-");
+            }
+            _ => result.push(ch),
+        }
+    }
+
+    result.push(']');
+    Ok(())
+}
+
+#[test]
+fn translate_basic() {
+    assert_eq!(translate(r"a\(b\)*c", false).unwrap(), r"a(b)*c");
+    assert_eq!(translate(r"a(b)+?", false).unwrap(), r"a\(b\)\+\?");
+    assert_eq!(translate(r"*a", false).unwrap(), r"\*a");
+    assert_eq!(translate(r"^*a", false).unwrap(), r"^\*a");
+    assert_eq!(translate(r"a\{2\}", false).unwrap(), r"a{2}");
+    assert_eq!(translate(r"\<word\>", false).unwrap(), r"\bword\b");
+    assert_eq!(translate(r"a.b", false).unwrap(), r"a.b");
+    assert_eq!(translate(r"a\nb", false).unwrap(), r"a\nb");
+    assert!(translate(r"\(a\)\1", false).is_err());
+    assert!(translate("a\\", false).is_err());
+}
+
+#[test]
+fn translate_extended() {
+    assert_eq!(translate(r"a(b|c)+", true).unwrap(), r"a(b|c)+");
+    assert_eq!(translate(r"a\(b\)", true).unwrap(), r"a\(b\)");
+    assert_eq!(translate(r"(*a)", true).unwrap(), r"(\*a)");
+}
+
+#[test]
+fn translate_brackets() {
+    assert_eq!(translate(r"[]a]", true).unwrap(), r"[\]a]");
+    assert_eq!(translate(r"[^]a]", true).unwrap(), r"[^\]a]");
+    assert_eq!(translate(r"[[:digit:]x]", true).unwrap(), r"[[:digit:]x]");
+    assert_eq!(translate(r"[a\b]", true).unwrap(), r"[a\\b]");
+    assert_eq!(translate(r"[[]", true).unwrap(), r"[\[]");
+    assert!(translate(r"[abc", true).is_err());
+}
+
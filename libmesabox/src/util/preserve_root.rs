@@ -0,0 +1,151 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+//! The `--preserve-root` failsafe of utilities that operate recursively (like `rm -r` and
+//! `chmod -R`), which keeps them from being run on the whole system by accident.
+//!
+//! Operands are compared with `/` by device and inode number rather than by name, so paths like
+//! `/tmp/..` and symlinks to `/` are caught as well.
+
+use clap::{Arg, ArgMatches};
+use std::ffi::OsStr;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+const PRESERVE_ROOT: &str = "preserve-root";
+const NO_PRESERVE_ROOT: &str = "no-preserve-root";
+
+#[derive(Fail, Debug)]
+pub enum RootError {
+    #[fail(display = "it is dangerous to operate recursively on '{}'", _0)]
+    Root(String),
+
+    #[fail(display = "it is dangerous to operate recursively on '{}' (same as '/')", _0)]
+    SameAsRoot(String),
+
+    #[fail(display = "skipping '{}', since it's on a different device", _0)]
+    Device(String),
+}
+
+impl RootError {
+    /// The line printed after the error to explain how to get past it.
+    pub fn hint(&self) -> &'static str {
+        match *self {
+            RootError::Device(_) => "and --preserve-root=all is in effect",
+            _ => "use --no-preserve-root to override this failsafe",
+        }
+    }
+}
+
+/// What to refuse to operate on recursively.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PreserveRoot {
+    /// Nothing (`--no-preserve-root`)
+    None,
+    /// The root directory (`--preserve-root`, the default)
+    Root,
+    /// The root directory and any operand on a different file system than its parent directory,
+    /// such as the root of another mounted file system (`--preserve-root=all`)
+    All,
+}
+
+impl PreserveRoot {
+    /// Determine what to preserve using the arguments created by `preserve_root_args()`.
+    pub fn from_matches(matches: &ArgMatches) -> Self {
+        if matches.is_present(NO_PRESERVE_ROOT) {
+            PreserveRoot::None
+        } else if matches.value_of(PRESERVE_ROOT) == Some("all") {
+            PreserveRoot::All
+        } else {
+            PreserveRoot::Root
+        }
+    }
+
+    /// Check that `path` may be operated on recursively.  Paths that cannot be accessed are let
+    /// through so that the error from the operation itself is reported instead.
+    pub fn check<P: AsRef<Path>>(self, path: P) -> Result<(), RootError> {
+        let path = path.as_ref();
+        if self == PreserveRoot::None {
+            return Ok(());
+        }
+
+        let (meta, root) = match (fs::metadata(path), fs::metadata("/")) {
+            (Ok(meta), Ok(root)) => (meta, root),
+            _ => return Ok(()),
+        };
+        if meta.dev() == root.dev() && meta.ino() == root.ino() {
+            let name = path.display().to_string();
+            return Err(if path.as_os_str() == OsStr::new("/") {
+                RootError::Root(name)
+            } else {
+                RootError::SameAsRoot(name)
+            });
+        }
+
+        if self == PreserveRoot::All && meta.is_dir() {
+            if let Ok(parent) = fs::metadata(path.join("..")) {
+                if parent.dev() != meta.dev() {
+                    return Err(RootError::Device(path.display().to_string()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Create the `--preserve-root[=all]` and `--no-preserve-root` arguments.  The result should be
+/// passed to `PreserveRoot::from_matches()`.
+pub fn preserve_root_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name(PRESERVE_ROOT)
+            .long(PRESERVE_ROOT)
+            .takes_value(true)
+            .min_values(0)
+            .require_equals(true)
+            .possible_values(&["all"])
+            .value_name("all")
+            .help("Fail to operate recursively on '/' (the default), or with 'all' on anything on \
+                   a different file system than its parent"),
+        Arg::with_name(NO_PRESERVE_ROOT)
+            .long(NO_PRESERVE_ROOT)
+            .overrides_with(PRESERVE_ROOT)
+            .help("Do not treat '/' specially"),
+    ]
+}
+
+#[cfg(feature = "tempfile")]
+#[test]
+fn preserve_root_check() {
+    use std::os::unix::fs::symlink;
+    use tempfile;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    let link = dir.join("link");
+    symlink("/", &link).unwrap();
+
+    for &preserve in &[PreserveRoot::Root, PreserveRoot::All] {
+        match preserve.check("/") {
+            Err(RootError::Root(ref name)) if name == "/" => {}
+            res => panic!("unexpected result for '/': {:?}", res),
+        }
+        for path in &[Path::new("//"), Path::new("/."), Path::new("/.."), link.as_path()] {
+            match preserve.check(path) {
+                Err(RootError::SameAsRoot(_)) => {}
+                res => panic!("unexpected result for {}: {:?}", path.display(), res),
+            }
+        }
+        assert!(preserve.check(dir).is_ok());
+        assert!(preserve.check(dir.join("missing")).is_ok());
+    }
+
+    assert!(PreserveRoot::None.check("/").is_ok());
+    assert!(PreserveRoot::None.check(&link).is_ok());
+}
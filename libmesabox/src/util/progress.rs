@@ -0,0 +1,210 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use super::{is_tty, RawObject};
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+/// The minimum amount of time between two progress lines.
+pub const UPDATE_INTERVAL_MS: u64 = 200;
+
+const SI_UNITS: [&str; 8] = ["kB", "MB", "GB", "TB", "PB", "EB", "ZB", "YB"];
+
+/// Periodic progress reports for utilities that copy large amounts of data.
+///
+/// Reports are written on a single line (which is overwritten by each new report) and are only
+/// shown if the stream they are written to is a terminal, so logs and pipes are left alone.
+pub struct Progress {
+    total: Option<u64>,
+    start: Instant,
+    last_update: Option<Instant>,
+    interval: Duration,
+    enabled: bool,
+    // length of the previous report so that it can be completely overwritten
+    prev_len: usize,
+}
+
+impl Progress {
+    /// Create a progress reporter for data totalling `total` bytes (if known).  `stream` should be
+    /// the raw object for the stream the reports will be written to (usually stderr).
+    pub fn new(stream: Option<RawObject>, total: Option<u64>) -> Self {
+        Self::with_enabled(is_tty(stream), total)
+    }
+
+    fn with_enabled(enabled: bool, total: Option<u64>) -> Self {
+        Self {
+            total: total,
+            start: Instant::now(),
+            last_update: None,
+            interval: Duration::from_millis(UPDATE_INTERVAL_MS),
+            enabled: enabled,
+            prev_len: 0,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Show the reports even if the stream they are written to is not a terminal.
+    pub fn force(&mut self) {
+        self.enabled = true;
+    }
+
+    /// Report that `current` bytes have been processed so far.  Nothing is written if the
+    /// previous report was less than `UPDATE_INTERVAL_MS` milliseconds ago.
+    pub fn update<W: Write>(&mut self, output: &mut W, current: u64) -> io::Result<()> {
+        self.update_at(output, current, Instant::now())
+    }
+
+    /// Like `update()`, but with the report produced by `format`, which is given the number of
+    /// bytes processed and the time elapsed since the start.  `format` is only called if a report
+    /// is actually written.
+    pub fn update_with<W, F>(&mut self, output: &mut W, current: u64, format: F) -> io::Result<()>
+    where
+        W: Write,
+        F: FnOnce(u64, Duration) -> String,
+    {
+        self.write_report(output, current, Instant::now(), format)
+    }
+
+    /// Write a final report (regardless of when the last one was written) and end the line.
+    pub fn finish<W: Write>(&mut self, output: &mut W, current: u64) -> io::Result<()> {
+        let total = self.total;
+        self.finish_with(output, current, |current, elapsed| {
+            copy_report(total, current, elapsed)
+        })
+    }
+
+    /// Like `finish()`, but with the report produced by `format` (as for `update_with()`).
+    pub fn finish_with<W, F>(&mut self, output: &mut W, current: u64, format: F) -> io::Result<()>
+    where
+        W: Write,
+        F: FnOnce(u64, Duration) -> String,
+    {
+        if self.enabled {
+            self.last_update = None;
+            self.update_with(output, current, format)?;
+            writeln!(output)?;
+            self.prev_len = 0;
+        }
+        Ok(())
+    }
+
+    fn update_at<W>(&mut self, output: &mut W, current: u64, now: Instant) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let total = self.total;
+        self.write_report(output, current, now, |current, elapsed| {
+            copy_report(total, current, elapsed)
+        })
+    }
+
+    fn write_report<W, F>(
+        &mut self,
+        output: &mut W,
+        current: u64,
+        now: Instant,
+        format: F,
+    ) -> io::Result<()>
+    where
+        W: Write,
+        F: FnOnce(u64, Duration) -> String,
+    {
+        if !self.enabled {
+            return Ok(());
+        }
+        if let Some(last) = self.last_update {
+            if now.duration_since(last) < self.interval {
+                return Ok(());
+            }
+        }
+        self.last_update = Some(now);
+
+        let line = format(current, now.duration_since(self.start));
+        let padding = self.prev_len.saturating_sub(line.len());
+        self.prev_len = line.len();
+
+        write!(output, "\r{}{:2$}", line, "", padding)?;
+        output.flush()
+    }
+}
+
+// the report used by update() and finish(), in the style of dd's status=progress
+fn copy_report(total: Option<u64>, current: u64, elapsed: Duration) -> String {
+    let secs = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1_000_000_000.0;
+    let rate = if secs > 0.0 {
+        (current as f64 / secs) as u64
+    } else {
+        0
+    };
+
+    let mut line = format!("{} bytes ({}) copied", current, human_size(current));
+    if let Some(total) = total {
+        if total > 0 {
+            let percent = (current as f64 / total as f64 * 100.0).min(100.0);
+            line.push_str(&format!(" ({:.0}%)", percent));
+        }
+    }
+    line.push_str(&format!(", {:.1} s, {}/s", secs, human_size(rate)));
+    line
+}
+
+/// Format `bytes` using SI units (_e.g._ 1.5 MB), matching the style of the GNU utilities.
+pub fn human_size(bytes: u64) -> String {
+    if bytes < 1000 {
+        return format!("{} B", bytes);
+    }
+
+    let mut value = bytes as f64 / 1000.0;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < SI_UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, SI_UNITS[unit])
+}
+
+#[test]
+fn progress_throttled() {
+    let mut progress = Progress::with_enabled(true, Some(200));
+    let start = progress.start;
+    let mut output = vec![];
+
+    progress.update_at(&mut output, 10, start).unwrap();
+    assert!(!output.is_empty());
+
+    output.clear();
+    progress.update_at(&mut output, 20, start + Duration::from_millis(50)).unwrap();
+    assert!(output.is_empty());
+
+    progress.update_at(&mut output, 100, start + Duration::from_millis(500)).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "\r100 bytes (100 B) copied (50%), 0.5 s, 200 B/s"
+    );
+}
+
+#[test]
+fn progress_disabled() {
+    let mut progress = Progress::with_enabled(false, None);
+    let mut output = vec![];
+
+    progress.update(&mut output, 10).unwrap();
+    progress.finish(&mut output, 10).unwrap();
+    assert!(output.is_empty());
+}
+
+#[test]
+fn human_size_units() {
+    assert_eq!(human_size(999), "999 B");
+    assert_eq!(human_size(1000), "1.0 kB");
+    assert_eq!(human_size(1_500_000), "1.5 MB");
+    assert_eq!(human_size(u64::max_value()), "18.4 EB");
+}
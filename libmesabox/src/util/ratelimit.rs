@@ -0,0 +1,114 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+//! Limiting the rate at which utilities copy data (like `pv -L`).
+//!
+//! `RateLimiter` is a token bucket: it fills up at the chosen rate, holds at most a burst's worth
+//! of bytes, and copying data takes bytes out of it.  Once it runs dry, the copier sleeps until
+//! enough has trickled back in.  As the bucket is refilled based on how much time has actually
+//! passed, sleeping for too long (or too short) one time is made up for the next, so the average
+//! rate stays accurate.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// By default, the bucket holds this fraction of a second's worth of data.
+const DEFAULT_BURST_DIVISOR: u64 = 10;
+
+/// Caps the throughput of a copy at a number of bytes per second.
+#[derive(Clone, Debug)]
+pub struct RateLimiter {
+    rate: u64,
+    burst: u64,
+    // may go negative when more than a burst is taken at once, in which case that much has to
+    // be waited for
+    available: f64,
+    last: Instant,
+}
+
+impl RateLimiter {
+    /// Limit the throughput to `rate` bytes per second, allowing bursts of a tenth of a second's
+    /// worth of data.
+    pub fn new(rate: u64) -> Self {
+        Self::with_burst(rate, rate / DEFAULT_BURST_DIVISOR)
+    }
+
+    /// Limit the throughput to `rate` bytes per second, allowing bursts of up to `burst` bytes.
+    pub fn with_burst(rate: u64, burst: u64) -> Self {
+        let burst = burst.max(1);
+        Self {
+            rate: rate.max(1),
+            burst: burst,
+            available: burst as f64,
+            last: Instant::now(),
+        }
+    }
+
+    /// The number of bytes that should be copied at once to keep the flow of data smooth.
+    pub fn chunk_size(&self) -> u64 {
+        self.burst
+    }
+
+    /// Account for `bytes` bytes having been copied, sleeping until copying them is within the
+    /// limit.
+    pub fn consume(&mut self, bytes: u64) {
+        if let Some(wait) = self.consume_at(bytes, Instant::now()) {
+            thread::sleep(wait);
+        }
+    }
+
+    // take `bytes` out of the bucket at `now`, returning how long to wait if it ran dry
+    fn consume_at(&mut self, bytes: u64, now: Instant) -> Option<Duration> {
+        let elapsed = now.duration_since(self.last);
+        self.last = now;
+
+        let refill = duration_secs(elapsed) * self.rate as f64;
+        self.available = (self.available + refill).min(self.burst as f64) - bytes as f64;
+        if self.available < 0.0 {
+            Some(duration_from_secs(-self.available / self.rate as f64))
+        } else {
+            None
+        }
+    }
+}
+
+fn duration_secs(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1e9
+}
+
+fn duration_from_secs(secs: f64) -> Duration {
+    Duration::new(secs.trunc() as u64, (secs.fract() * 1e9) as u32)
+}
+
+#[test]
+fn ratelimit_bucket() {
+    let mut limiter = RateLimiter::with_burst(1000, 100);
+    let start = limiter.last;
+
+    // a full bucket lets a burst through at once
+    assert_eq!(limiter.consume_at(100, start), None);
+    assert_eq!(limiter.consume_at(50, start), Some(Duration::from_millis(50)));
+
+    // the debt is paid off by waiting, and waiting longer than needed only refills up to a burst
+    let later = start + Duration::from_millis(50);
+    assert_eq!(limiter.consume_at(0, later), None);
+    let much_later = later + Duration::from_secs(10);
+    assert_eq!(limiter.consume_at(100, much_later), None);
+    assert_eq!(limiter.consume_at(100, much_later), Some(Duration::from_millis(100)));
+}
+
+#[test]
+fn ratelimit_duration() {
+    // everything beyond the initial burst has to be waited for
+    let mut limiter = RateLimiter::with_burst(10_000, 100);
+    let start = Instant::now();
+    for _ in 0..20 {
+        limiter.consume(100);
+    }
+    assert!(start.elapsed() >= Duration::from_millis(190));
+}
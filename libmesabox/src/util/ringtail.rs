@@ -0,0 +1,175 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::mem;
+
+use super::spill::{self, SpillBuffer};
+
+/// Keeps the last `limit` bytes of a stream without knowing its length in advance (as needed for
+/// `head -c -N` or `tail -c N`).
+///
+/// Data is collected in two buffers of at least `limit` bytes each.  Once the newer buffer is
+/// full, nothing in the older one can be part of the tail anymore, so it is handed off and the
+/// buffers are swapped.  As the limit might be larger than the memory available, the buffers are
+/// `SpillBuffer`s.
+pub struct RingTail {
+    limit: u64,
+    chunk_size: u64,
+    prev: SpillBuffer,
+    cur: SpillBuffer,
+    // the number of bytes at the start of `prev` that have already been handed off
+    skip: u64,
+}
+
+impl RingTail {
+    /// Create a `RingTail` that keeps `limit` bytes, reading at least `buffer_size` bytes at a
+    /// time.
+    pub fn new(limit: u64, buffer_size: usize) -> Self {
+        Self {
+            limit: limit,
+            chunk_size: limit.max(buffer_size as u64).max(1),
+            prev: SpillBuffer::new(spill::DEFAULT_THRESHOLD),
+            cur: SpillBuffer::new(spill::DEFAULT_THRESHOLD),
+            skip: 0,
+        }
+    }
+
+    /// The number of bytes currently kept.
+    pub fn len(&self) -> u64 {
+        self.prev.len() - self.skip + self.cur.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Read `input` until it ends.  The bytes that fall out of the tail are written to `evicted`
+    /// in order (use `io::sink()` if they are not needed).  Returns the number of bytes read.
+    pub fn read_from<R, W>(&mut self, input: &mut R, evicted: &mut W) -> io::Result<u64>
+    where
+        R: Read + ?Sized,
+        W: Write + ?Sized,
+    {
+        let mut total = 0;
+        loop {
+            let space = self.chunk_size - self.cur.len();
+            total += io::copy(&mut (&mut *input).take(space), &mut self.cur)?;
+            if self.cur.len() < self.chunk_size {
+                break;
+            }
+
+            // the newer buffer holds at least `limit` bytes by itself now
+            let count = self.prev.len() - self.skip;
+            self.evict(count, evicted)?;
+        }
+
+        let excess = self.len().saturating_sub(self.limit);
+        self.evict(excess, evicted)?;
+
+        Ok(total)
+    }
+
+    /// Write the bytes that are being kept to `output`, leaving the `RingTail` empty.  Returns
+    /// the number of bytes written.
+    pub fn drain_to<W: Write + ?Sized>(&mut self, output: &mut W) -> io::Result<u64> {
+        let len = self.len();
+        self.evict(len, output)?;
+        Ok(len)
+    }
+
+    /// Hand off the oldest `count` bytes (which must not be more than `self.len()`).
+    fn evict<W: Write + ?Sized>(&mut self, mut count: u64, output: &mut W) -> io::Result<()> {
+        let available = self.prev.len() - self.skip;
+        if count >= available {
+            copy_range(&mut self.prev, self.skip, available, output)?;
+            count -= available;
+
+            mem::swap(&mut self.prev, &mut self.cur);
+            self.cur.clear()?;
+            self.skip = 0;
+        }
+
+        copy_range(&mut self.prev, self.skip, count, output)?;
+        self.skip += count;
+
+        Ok(())
+    }
+}
+
+fn copy_range<W>(buffer: &mut SpillBuffer, start: u64, len: u64, output: &mut W) -> io::Result<()>
+where
+    W: Write + ?Sized,
+{
+    if len > 0 {
+        let mut reader = buffer.reader()?;
+        reader.seek(SeekFrom::Start(start))?;
+        io::copy(&mut reader.take(len), output)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+fn split_tail(data: &[u8], limit: u64, buffer_size: usize) -> (Vec<u8>, Vec<u8>) {
+    let mut tail = RingTail::new(limit, buffer_size);
+    let mut evicted = vec![];
+    let mut kept = vec![];
+    assert_eq!(tail.read_from(&mut &data[..], &mut evicted).unwrap(), data.len() as u64);
+    assert_eq!(tail.len(), (data.len() - evicted.len()) as u64);
+    tail.drain_to(&mut kept).unwrap();
+    assert!(tail.is_empty());
+    (evicted, kept)
+}
+
+#[test]
+fn ring_tail_limits() {
+    let data = b"0123456789";
+
+    // smaller than the input
+    assert_eq!(split_tail(data, 3, 1), (b"0123456".to_vec(), b"789".to_vec()));
+    assert_eq!(split_tail(data, 3, 4), (b"0123456".to_vec(), b"789".to_vec()));
+    // equal to the input
+    assert_eq!(split_tail(data, 10, 1), (vec![], data.to_vec()));
+    // larger than the input
+    assert_eq!(split_tail(data, 64, 4), (vec![], data.to_vec()));
+    assert_eq!(split_tail(b"", 3, 1), (vec![], vec![]));
+}
+
+#[test]
+fn ring_tail_zero() {
+    assert_eq!(split_tail(b"abc", 0, 1), (b"abc".to_vec(), vec![]));
+    assert_eq!(split_tail(b"abc", 0, 16), (b"abc".to_vec(), vec![]));
+}
+
+#[test]
+fn ring_tail_chunk_boundaries() {
+    // inputs that are exact multiples of the chunk size
+    let data: Vec<u8> = (0..64).collect();
+    for &limit in &[1, 4, 8, 16, 63, 64, 65] {
+        for &buffer_size in &[1, 4, 8, 32] {
+            let (evicted, kept) = split_tail(&data, limit, buffer_size);
+            let split = data.len().saturating_sub(limit as usize);
+            assert_eq!(evicted, &data[..split]);
+            assert_eq!(kept, &data[split..]);
+        }
+    }
+}
+
+#[test]
+fn ring_tail_several_readers() {
+    let mut tail = RingTail::new(4, 2);
+    let mut evicted = vec![];
+    tail.read_from(&mut &b"abc"[..], &mut evicted).unwrap();
+    tail.read_from(&mut &b"defg"[..], &mut evicted).unwrap();
+    tail.read_from(&mut &b"h"[..], &mut evicted).unwrap();
+
+    let mut kept = vec![];
+    assert_eq!(tail.drain_to(&mut kept).unwrap(), 4);
+    assert_eq!(evicted, b"abcd");
+    assert_eq!(kept, b"efgh");
+}
@@ -0,0 +1,219 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+//! Copying of sparse files without filling in their holes.
+//!
+//! Rather than writing runs of zeros to the output, `copy_sparse()` seeks past them, which leaves
+//! a hole in the output just like the one in the input.  This only works for outputs that are
+//! regular files opened without `O_APPEND` and positioned at their end (otherwise seeking would
+//! either fail or skip over existing data), so `open_output()` checks for that first.
+
+use nix::fcntl::{self, OFlag};
+use nix::libc;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+
+use super::{output_error, RawObject, RawObjectWrapper};
+
+/// Runs of zeros shorter than this are written normally, as most filesystems cannot store holes
+/// smaller than a block anyway.
+pub const BLOCK_SIZE: usize = 4096;
+
+// defined here because the libc crate leaves these out on some targets (such as musl)
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const SEEK_DATA: libc::c_int = 3;
+
+/// Get a `File` for the output `obj` if holes can be made in it.
+pub fn open_output(obj: RawObject) -> Option<File> {
+    let flags = fcntl::fcntl(obj.raw_value(), fcntl::F_GETFL).ok()?;
+    if OFlag::from_bits_truncate(flags).contains(OFlag::O_APPEND) {
+        return None;
+    }
+
+    let fd = RawObjectWrapper::new(obj, false, true).dup().ok()?;
+    let mut file = unsafe { File::from_raw_fd(fd.raw_value()) };
+    if can_make_holes(&mut file).unwrap_or(false) {
+        Some(file)
+    } else {
+        None
+    }
+}
+
+fn can_make_holes(file: &mut File) -> io::Result<bool> {
+    let metadata = file.metadata()?;
+    Ok(metadata.is_file() && file.seek(SeekFrom::Current(0))? >= metadata.len())
+}
+
+/// Copy at most `limit` bytes from `input` to `output` (which should come from `open_output()`),
+/// leaving holes in place of any blocks of zeros.  Returns the number of bytes copied.  Errors with
+/// the output are marked using `output_error()`.
+///
+/// `input` does not have to be seekable (it may be a pipe opened through `/dev/fd/N`, for
+/// example), in which case it is just read from start to finish.
+pub fn copy_sparse(
+    input: &mut File,
+    output: &mut File,
+    limit: u64,
+    buffer_size: usize,
+) -> io::Result<u64> {
+    let mut buffer = vec![0; buffer_size.max(BLOCK_SIZE)];
+    let mut copied = 0;
+    // the number of zeros that have been read but not yet skipped over in the output
+    let mut pending = 0;
+    // only regular files can have holes, so there is no need to look for them in anything else
+    let seekable = input.metadata()?.is_file();
+
+    while copied < limit {
+        let hole = if seekable { skip_hole(input, limit - copied)? } else { 0 };
+        if hole > 0 {
+            copied += hole;
+            pending += hole;
+            continue;
+        }
+
+        let len = (limit - copied).min(buffer.len() as u64) as usize;
+        let count = match input.read(&mut buffer[..len]) {
+            Ok(0) => break,
+            Ok(count) => count,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        copied += count as u64;
+
+        let data = &buffer[..count];
+        let mut start = 0;
+        while start < data.len() {
+            let end = (start + BLOCK_SIZE).min(data.len());
+            if data[start..end].iter().all(|&byte| byte == 0) {
+                pending += (end - start) as u64;
+                start = end;
+                continue;
+            }
+
+            // write every block up to the next one that is all zeros at once
+            let mut run_end = end;
+            while run_end < data.len() {
+                let next = (run_end + BLOCK_SIZE).min(data.len());
+                if data[run_end..next].iter().all(|&byte| byte == 0) {
+                    break;
+                }
+                run_end = next;
+            }
+            if pending > 0 {
+                output.seek(SeekFrom::Current(pending as i64)).map_err(output_error)?;
+                pending = 0;
+            }
+            output.write_all(&data[start..run_end]).map_err(output_error)?;
+            start = run_end;
+        }
+    }
+
+    if pending > 0 {
+        // seeking alone does not change the size of the file
+        let end = output.seek(SeekFrom::Current(pending as i64)).map_err(output_error)?;
+        output.set_len(end).map_err(output_error)?;
+    }
+
+    Ok(copied)
+}
+
+// if `input` is at the start of a hole, skip to its end (or by `limit` bytes, whichever is closer)
+// and return the number of bytes skipped
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn skip_hole(input: &mut File, limit: u64) -> io::Result<u64> {
+    let fd = input.as_raw_fd();
+    let pos = unsafe { libc::lseek(fd, 0, libc::SEEK_CUR) };
+    if pos < 0 {
+        // not seekable, so there are no holes to look for
+        return Ok(0);
+    }
+
+    let mut data = unsafe { libc::lseek(fd, pos, SEEK_DATA) };
+    if data < 0 {
+        if io::Error::last_os_error().raw_os_error() != Some(libc::ENXIO) {
+            // the filesystem does not know where the holes are
+            return Ok(0);
+        }
+        // there is no data after `pos`, so the rest of the file is a hole
+        data = unsafe { libc::lseek(fd, 0, libc::SEEK_END) };
+        if data < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    let hole = ((data - pos).max(0) as u64).min(limit);
+    input.seek(SeekFrom::Start(pos as u64 + hole))?;
+    Ok(hole)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn skip_hole(_input: &mut File, _limit: u64) -> io::Result<u64> {
+    Ok(0)
+}
+
+#[cfg(all(test, feature = "tempfile"))]
+fn sparse_copy_of(data: &[u8], limit: u64) -> Vec<u8> {
+    use tempfile;
+
+    let mut input = tempfile::tempfile().unwrap();
+    input.write_all(data).unwrap();
+    input.seek(SeekFrom::Start(0)).unwrap();
+
+    let mut output = tempfile::tempfile().unwrap();
+    let copied = copy_sparse(&mut input, &mut output, limit, 1).unwrap();
+    assert_eq!(copied, limit.min(data.len() as u64));
+    assert_eq!(output.metadata().unwrap().len(), copied);
+
+    let mut result = vec![];
+    output.seek(SeekFrom::Start(0)).unwrap();
+    output.read_to_end(&mut result).unwrap();
+    result
+}
+
+#[cfg(feature = "tempfile")]
+#[test]
+fn sparse_copy_contents() {
+    let mut data = vec![0; BLOCK_SIZE * 3];
+    data.extend_from_slice(b"some data");
+    data.extend(vec![0; BLOCK_SIZE * 2 + 5]);
+    data.extend_from_slice(b"end");
+    data.extend(vec![0; BLOCK_SIZE]);
+
+    assert_eq!(sparse_copy_of(&data, data.len() as u64), data);
+    assert_eq!(sparse_copy_of(&data, 100), &data[..100]);
+    assert_eq!(sparse_copy_of(&data, BLOCK_SIZE as u64 * 3 + 4), &data[..BLOCK_SIZE * 3 + 4]);
+    assert_eq!(sparse_copy_of(&data, u64::max_value()), data);
+    assert_eq!(sparse_copy_of(b"", 10), b"");
+}
+
+#[cfg(feature = "tempfile")]
+#[test]
+fn sparse_copy_pipe() {
+    use nix::unistd;
+    use tempfile;
+
+    let mut data = vec![0; BLOCK_SIZE * 2];
+    data.extend_from_slice(b"piped");
+
+    // the data has to fit in the pipe as nothing reads it until the write end is closed
+    let (read, write) = unistd::pipe().unwrap();
+    let mut input = unsafe { File::from_raw_fd(read) };
+    {
+        let mut write = unsafe { File::from_raw_fd(write) };
+        write.write_all(&data).unwrap();
+    }
+
+    let mut output = tempfile::tempfile().unwrap();
+    assert_eq!(copy_sparse(&mut input, &mut output, u64::max_value(), 1).unwrap(), data.len() as u64);
+
+    let mut result = vec![];
+    output.seek(SeekFrom::Start(0)).unwrap();
+    output.read_to_end(&mut result).unwrap();
+    assert_eq!(result, data);
+}
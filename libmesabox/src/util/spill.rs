@@ -0,0 +1,189 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use tempfile;
+
+/// A reasonable amount of data to keep in memory before spilling to disk.
+pub const DEFAULT_THRESHOLD: usize = 8 * 1024 * 1024;
+
+/// An append-only buffer that stores data in memory until it grows larger than a threshold, after
+/// which everything is moved into an anonymous temporary file.  The temporary file is deleted by
+/// the operating system once it is closed, so nothing is left behind even if the utility fails.
+pub struct SpillBuffer {
+    threshold: usize,
+    memory: Vec<u8>,
+    file: Option<File>,
+    len: u64,
+    // set when a reader may have moved the file's position away from the end
+    needs_seek: bool,
+}
+
+impl SpillBuffer {
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            threshold: threshold,
+            memory: vec![],
+            file: None,
+            len: 0,
+            needs_seek: false,
+        }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Check whether the data is currently stored in a temporary file.
+    pub fn is_spilled(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// Remove all data from the buffer.  If the buffer has spilled, the temporary file is reused
+    /// for future writes.
+    pub fn clear(&mut self) -> io::Result<()> {
+        if let Some(ref mut file) = self.file {
+            file.set_len(0)?;
+            file.seek(SeekFrom::Start(0))?;
+        }
+        self.memory.clear();
+        self.len = 0;
+        self.needs_seek = false;
+        Ok(())
+    }
+
+    /// Get a reader positioned at the start of the buffered data.
+    pub fn reader<'a>(&'a mut self) -> io::Result<SpillReader<'a>> {
+        Ok(match self.file {
+            Some(ref mut file) => {
+                file.seek(SeekFrom::Start(0))?;
+                self.needs_seek = true;
+                SpillReader::File(file)
+            }
+            None => SpillReader::Memory(Cursor::new(&self.memory)),
+        })
+    }
+
+    /// Write at most `limit` bytes from the start of the buffer to `output`, returning the number
+    /// of bytes written.
+    pub fn copy_to<W: Write>(&mut self, output: &mut W, limit: u64) -> io::Result<u64> {
+        io::copy(&mut self.reader()?.take(limit), output)
+    }
+
+    fn spill(&mut self) -> io::Result<()> {
+        let mut file = tempfile::tempfile()?;
+        file.write_all(&self.memory)?;
+        self.file = Some(file);
+
+        // release the memory as we will not be using it again
+        self.memory = vec![];
+        Ok(())
+    }
+}
+
+impl Write for SpillBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.file.is_none() && self.memory.len() + buf.len() > self.threshold {
+            self.spill()?;
+        }
+
+        let written = match self.file {
+            Some(ref mut file) => {
+                if self.needs_seek {
+                    file.seek(SeekFrom::End(0))?;
+                    self.needs_seek = false;
+                }
+                file.write(buf)?
+            }
+            None => {
+                self.memory.extend_from_slice(buf);
+                buf.len()
+            }
+        };
+        self.len += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.file {
+            Some(ref mut file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Sequential (and seekable) access to the data in a `SpillBuffer`.
+pub enum SpillReader<'a> {
+    Memory(Cursor<&'a Vec<u8>>),
+    File(&'a mut File),
+}
+
+impl<'a> Read for SpillReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match *self {
+            SpillReader::Memory(ref mut cursor) => cursor.read(buf),
+            SpillReader::File(ref mut file) => file.read(buf),
+        }
+    }
+}
+
+impl<'a> Seek for SpillReader<'a> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match *self {
+            SpillReader::Memory(ref mut cursor) => cursor.seek(pos),
+            SpillReader::File(ref mut file) => file.seek(pos),
+        }
+    }
+}
+
+#[cfg(test)]
+fn read_all(buffer: &mut SpillBuffer) -> Vec<u8> {
+    let mut data = vec![];
+    buffer.reader().unwrap().read_to_end(&mut data).unwrap();
+    data
+}
+
+#[test]
+fn spill_past_threshold() {
+    let mut buffer = SpillBuffer::new(8);
+
+    buffer.write_all(b"12345").unwrap();
+    assert!(!buffer.is_spilled());
+    assert_eq!(read_all(&mut buffer), b"12345");
+
+    buffer.write_all(b"6789").unwrap();
+    assert!(buffer.is_spilled());
+    assert_eq!(buffer.len(), 9);
+    assert_eq!(read_all(&mut buffer), b"123456789");
+
+    // make sure writing after reading appends rather than overwriting
+    buffer.write_all(b"0").unwrap();
+    assert_eq!(read_all(&mut buffer), b"1234567890");
+
+    let mut output = vec![];
+    assert_eq!(buffer.copy_to(&mut output, 4).unwrap(), 4);
+    assert_eq!(output, b"1234");
+}
+
+#[test]
+fn spill_clear() {
+    let mut buffer = SpillBuffer::new(4);
+
+    buffer.write_all(b"abcdef").unwrap();
+    buffer.clear().unwrap();
+    assert!(buffer.is_empty());
+    assert_eq!(read_all(&mut buffer), b"");
+
+    buffer.write_all(b"gh").unwrap();
+    assert_eq!(read_all(&mut buffer), b"gh");
+}
@@ -0,0 +1,251 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+//! Removal of named temporary files when a utility is interrupted.
+//!
+//! Anonymous temporary files (like those used by `SpillBuffer`) disappear on their own, but files
+//! that need a name (such as the output of `sed -i` before it is renamed over the input) are left
+//! behind if the utility is killed by a signal before it can remove them.  Registering such a file
+//! using `TempPath` makes sure it is removed when the utility receives `SIGHUP`, `SIGINT`, or
+//! `SIGTERM`, as well as when the `TempPath` is dropped.
+//!
+//! As the signal handler may only use async-signal-safe functions, the paths are kept in a fixed
+//! number of pre-allocated slots and removed using `unlink()` directly.  Paths that do not fit
+//! (because they are too long or all the slots are in use) are still removed on drop.
+
+use nix::libc;
+use std::fs;
+use std::marker::PhantomData;
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use std::sync::{Once, ONCE_INIT};
+
+/// The number of paths that can be registered at once.
+pub const MAX_PATHS: usize = 32;
+
+// the longest path (including the terminating NUL) that fits in a slot
+const PATH_LEN: usize = 4096;
+
+const SIGNALS: [libc::c_int; 3] = [libc::SIGHUP, libc::SIGINT, libc::SIGTERM];
+
+type Paths = [[u8; PATH_LEN]; MAX_PATHS];
+
+static CLAIMED: AtomicUsize = ATOMIC_USIZE_INIT;
+static ACTIVE: AtomicUsize = ATOMIC_USIZE_INIT;
+static mut PATHS: Paths = [[0; PATH_LEN]; MAX_PATHS];
+
+static INSTALL_HANDLERS: Once = ONCE_INIT;
+
+/// A temporary file that is removed when dropped or when the utility is interrupted.
+#[derive(Debug)]
+pub struct TempPath {
+    path: PathBuf,
+    slot: Option<usize>,
+}
+
+impl TempPath {
+    /// Start tracking `path`, which should have just been created.
+    pub fn register<P: AsRef<Path>>(path: P) -> Self {
+        INSTALL_HANDLERS.call_once(install_handlers);
+
+        let path = path.as_ref().to_owned();
+        let slot = global_registry().claim_slot(&path);
+        Self {
+            path: path,
+            slot: slot,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Stop tracking the path without removing it (for example, because the file has been renamed
+    /// to its final name).
+    pub fn keep(mut self) -> PathBuf {
+        self.release();
+        mem::replace(&mut self.path, PathBuf::new())
+    }
+
+    fn release(&mut self) {
+        if let Some(slot) = self.slot.take() {
+            global_registry().release_slot(slot);
+        }
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        self.release();
+        if !self.path.as_os_str().is_empty() {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+// a table of slots holding the registered paths.  bit i is set in claimed while slot i belongs to
+// a TempPath and in active once the path in the slot is complete (so only slots in active may be
+// looked at by the signal handler)
+struct Registry<'a> {
+    claimed: &'a AtomicUsize,
+    active: &'a AtomicUsize,
+    paths: *mut Paths,
+    _paths: PhantomData<&'a mut Paths>,
+}
+
+impl<'a> Registry<'a> {
+    // the caller must make sure that paths is only ever used through registries sharing claimed
+    // and active
+    unsafe fn new(claimed: &'a AtomicUsize, active: &'a AtomicUsize, paths: *mut Paths) -> Self {
+        Self {
+            claimed: claimed,
+            active: active,
+            paths: paths,
+            _paths: PhantomData,
+        }
+    }
+
+    fn claim_slot(&self, path: &Path) -> Option<usize> {
+        let bytes = path.as_os_str().as_bytes();
+        if bytes.len() >= PATH_LEN || bytes.contains(&0) {
+            return None;
+        }
+
+        let mut claimed = self.claimed.load(Ordering::SeqCst);
+        loop {
+            let slot = (0..MAX_PATHS).find(|&i| claimed & (1 << i) == 0)?;
+            match self.claimed.compare_exchange(
+                claimed,
+                claimed | (1 << slot),
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => {
+                    unsafe {
+                        let buf = &mut (*self.paths)[slot];
+                        buf[..bytes.len()].copy_from_slice(bytes);
+                        buf[bytes.len()] = 0;
+                    }
+                    self.active.fetch_or(1 << slot, Ordering::SeqCst);
+                    return Some(slot);
+                }
+                Err(current) => claimed = current,
+            }
+        }
+    }
+
+    fn release_slot(&self, slot: usize) {
+        self.active.fetch_and(!(1 << slot), Ordering::SeqCst);
+        self.claimed.fetch_and(!(1 << slot), Ordering::SeqCst);
+    }
+
+    // remove every registered file (this must stay async-signal-safe)
+    fn remove_registered(&self) {
+        let active = self.active.load(Ordering::SeqCst);
+        for slot in 0..MAX_PATHS {
+            if active & (1 << slot) != 0 {
+                unsafe {
+                    libc::unlink((*self.paths)[slot].as_ptr() as *const libc::c_char);
+                }
+            }
+        }
+    }
+}
+
+// the registry used by TempPath and the signal handlers
+fn global_registry() -> Registry<'static> {
+    unsafe { Registry::new(&CLAIMED, &ACTIVE, &mut PATHS) }
+}
+
+extern "C" fn handle_signal(signum: libc::c_int) {
+    global_registry().remove_registered();
+
+    // let the default action take place so the utility still dies from the signal
+    unsafe {
+        libc::signal(signum, libc::SIG_DFL);
+        libc::raise(signum);
+    }
+}
+
+fn install_handlers() {
+    for &signum in &SIGNALS {
+        unsafe {
+            let mut old: libc::sigaction = mem::zeroed();
+            if libc::sigaction(signum, ptr::null(), &mut old) != 0 {
+                continue;
+            }
+            // signals that are ignored (e.g. by nohup) or already handled are left alone
+            if old.sa_sigaction != libc::SIG_DFL {
+                continue;
+            }
+
+            let mut action: libc::sigaction = mem::zeroed();
+            action.sa_sigaction = handle_signal as libc::sighandler_t;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(signum, &action, ptr::null_mut());
+        }
+    }
+}
+
+#[cfg(feature = "tempfile")]
+#[test]
+fn temp_path_cleanup() {
+    use tempfile;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+
+    let dropped = dir.join("dropped");
+    let kept = dir.join("kept");
+    fs::write(&dropped, "").unwrap();
+    fs::write(&kept, "").unwrap();
+
+    drop(TempPath::register(&dropped));
+    assert!(!dropped.exists());
+    assert_eq!(TempPath::register(&kept).keep(), kept);
+    assert!(kept.exists());
+}
+
+// uses a registry of its own, as removing everything in the global one would also remove the
+// files of other tests running in parallel
+#[cfg(feature = "tempfile")]
+#[test]
+fn registry_remove_registered() {
+    use tempfile;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+
+    let claimed = AtomicUsize::new(0);
+    let active = AtomicUsize::new(0);
+    let mut paths: Box<Paths> = Box::new([[0; PATH_LEN]; MAX_PATHS]);
+    let registry = unsafe { Registry::new(&claimed, &active, &mut *paths) };
+
+    let files: Vec<PathBuf> = (0..MAX_PATHS + 4).map(|i| dir.join(i.to_string())).collect();
+    let mut slots = vec![];
+    for file in &files {
+        fs::write(file, "").unwrap();
+        slots.push(registry.claim_slot(file));
+    }
+    assert!(slots[..MAX_PATHS].iter().all(|slot| slot.is_some()));
+    assert!(slots[MAX_PATHS..].iter().all(|slot| slot.is_none()));
+
+    // a released slot is no longer removed and can be claimed again
+    registry.release_slot(slots[0].unwrap());
+    assert_eq!(registry.claim_slot(&dir.join("0")), slots[0]);
+    registry.release_slot(slots[0].unwrap());
+
+    // what the signal handler does, minus dying afterwards
+    registry.remove_registered();
+    assert!(files[0].exists());
+    assert!(files[1..MAX_PATHS].iter().all(|file| !file.exists()));
+    assert!(files[MAX_PATHS..].iter().all(|file| file.exists()));
+}
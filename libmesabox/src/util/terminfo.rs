@@ -0,0 +1,269 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+//! Looking up string capabilities in the compiled terminfo database (as described in `term(5)`).
+//!
+//! Only the string capabilities are read, and only the handful of standard ones that utilities
+//! here need are given names (extended capabilities such as `E3` can be looked up by any name).
+//! Parameterized strings are returned as they are, so callers must not use them without
+//! substituting their parameters.
+
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use super::{os_str_as_bytes, EnvVars};
+
+// the standard string capabilities that can be looked up, with their index in the string section
+const STRING_CAPS: &[(&str, usize)] = &[
+    ("clear", 5),
+    ("ed", 7),
+    ("cup", 10),
+    ("home", 12),
+    ("rs1", 122),
+    ("rs2", 123),
+    ("rs3", 124),
+];
+
+const DEFAULT_DIRS: &[&str] = &["/etc/terminfo", "/lib/terminfo", "/usr/share/terminfo"];
+
+// the legacy format stores numbers in two bytes and the newer one (ncurses 6.1) in four
+const MAGIC: i16 = 0o432;
+const MAGIC_32BIT: i16 = 0o1036;
+
+// the largest entry that will be read, which is far more than any real entry needs
+const MAX_SIZE: u64 = 64 * 1024;
+
+/// The string capabilities of a terminal.
+#[derive(Debug, Default)]
+pub struct TermInfo {
+    strings: Vec<Option<Vec<u8>>>,
+    extended: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl TermInfo {
+    /// Find and read the entry for `term`, searching the directories given by `TERMINFO`,
+    /// `TERMINFO_DIRS` and `HOME` in `env` before the usual locations.
+    pub fn load(env: &EnvVars, term: &OsStr) -> Option<Self> {
+        let term = os_str_as_bytes(term);
+        let term = &term[..];
+        if term.is_empty() || term.contains(&b'/') || term.starts_with(b".") {
+            return None;
+        }
+
+        for dir in search_dirs(env) {
+            // the subdirectory is named after the first letter, or its hex value on some systems
+            let subdirs = [
+                String::from_utf8_lossy(&term[..1]).into_owned(),
+                format!("{:02x}", term[0]),
+            ];
+            for subdir in &subdirs {
+                let path = dir.join(subdir).join(&*String::from_utf8_lossy(term));
+                if let Some(info) = read_entry(&path) {
+                    return Some(info);
+                }
+            }
+        }
+        None
+    }
+
+    /// Parse a compiled terminfo entry.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let magic = read_i16(data, 0)?;
+        let num_size = match magic {
+            MAGIC => 2,
+            MAGIC_32BIT => 4,
+            _ => return None,
+        };
+        let names_size = read_count(data, 2)?;
+        let bool_count = read_count(data, 4)?;
+        let num_count = read_count(data, 6)?;
+        let str_count = read_count(data, 8)?;
+        let table_size = read_count(data, 10)?;
+
+        let mut pos = 12 + names_size + bool_count;
+        pos += pos % 2;
+        pos += num_count * num_size;
+        let offsets = pos;
+        let table = offsets + str_count * 2;
+        let table_end = table + table_size;
+        let table_data = data.get(table..table_end)?;
+
+        let mut strings = Vec::with_capacity(str_count);
+        for i in 0..str_count {
+            let offset = read_i16(data, offsets + i * 2)?;
+            strings.push(read_string(table_data, offset));
+        }
+
+        let extended = parse_extended(data, table_end + table_end % 2, num_size).unwrap_or_default();
+
+        Some(Self {
+            strings: strings,
+            extended: extended,
+        })
+    }
+
+    /// Look up the string capability `name` (such as `clear` or `E3`).
+    pub fn string(&self, name: &str) -> Option<&[u8]> {
+        match STRING_CAPS.iter().find(|&&(cap, _)| cap == name) {
+            Some(&(_, index)) => self.strings.get(index)?.as_ref().map(|s| &s[..]),
+            None => self.extended
+                .iter()
+                .find(|&&(ref cap, _)| cap == name.as_bytes())
+                .map(|&(_, ref value)| &value[..]),
+        }
+    }
+}
+
+/// Remove the padding specifications (like `$<5>`) from a capability.  These only matter for
+/// terminals old enough to need delays, so they are dropped rather than turned into delays.
+pub fn strip_padding(cap: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(cap.len());
+    let mut i = 0;
+    while i < cap.len() {
+        if cap[i..].starts_with(b"$<") {
+            if let Some(end) = cap[i..].iter().position(|&b| b == b'>') {
+                i += end + 1;
+                continue;
+            }
+        }
+        result.push(cap[i]);
+        i += 1;
+    }
+    result
+}
+
+fn search_dirs(env: &EnvVars) -> Vec<PathBuf> {
+    let mut dirs = vec![];
+    if let Some(dir) = env.get_nonempty("TERMINFO") {
+        dirs.push(PathBuf::from(dir));
+    }
+    if let Some(home) = env.get_nonempty("HOME") {
+        dirs.push(PathBuf::from(home).join(".terminfo"));
+    }
+    if let Some(list) = env.get("TERMINFO_DIRS").and_then(|list| list.to_str()) {
+        for dir in list.split(':') {
+            // an empty entry stands for the default locations
+            if dir.is_empty() {
+                dirs.extend(DEFAULT_DIRS.iter().map(|&dir| PathBuf::from(dir)));
+            } else {
+                dirs.push(PathBuf::from(dir));
+            }
+        }
+    }
+    dirs.extend(DEFAULT_DIRS.iter().map(|&dir| PathBuf::from(dir)));
+    dirs
+}
+
+fn read_entry(path: &Path) -> Option<TermInfo> {
+    let mut data = vec![];
+    File::open(path)
+        .and_then(|file| file.take(MAX_SIZE).read_to_end(&mut data))
+        .ok()?;
+    TermInfo::parse(&data)
+}
+
+// the extended capabilities follow the standard ones, with a header of five counts followed by
+// the values and then the names of the capabilities
+fn parse_extended(data: &[u8], start: usize, num_size: usize) -> Option<Vec<(Vec<u8>, Vec<u8>)>> {
+    let bool_count = read_count(data, start)?;
+    let num_count = read_count(data, start + 2)?;
+    let str_count = read_count(data, start + 4)?;
+    let table_size = read_count(data, start + 8)?;
+
+    let mut pos = start + 10 + bool_count;
+    pos += pos % 2;
+    pos += num_count * num_size;
+    let value_offsets = pos;
+    let name_offsets = value_offsets + str_count * 2;
+    let table = name_offsets + (bool_count + num_count + str_count) * 2;
+    let table_data = data.get(table..table + table_size)?;
+
+    let mut values = Vec::with_capacity(str_count);
+    let mut names_start = 0;
+    for i in 0..str_count {
+        let offset = read_i16(data, value_offsets + i * 2)?;
+        let value = read_string(table_data, offset);
+        if let Some(ref value) = value {
+            names_start = names_start.max(offset as usize + value.len() + 1);
+        }
+        values.push(value);
+    }
+
+    // the names of the booleans and numbers come first
+    let names_data = table_data.get(names_start..)?;
+    let mut result = vec![];
+    for (i, value) in values.into_iter().enumerate() {
+        let offset = read_i16(data, name_offsets + (bool_count + num_count + i) * 2)?;
+        if let (Some(name), Some(value)) = (read_string(names_data, offset), value) {
+            result.push((name, value));
+        }
+    }
+    Some(result)
+}
+
+fn read_i16(data: &[u8], pos: usize) -> Option<i16> {
+    let bytes = data.get(pos..pos + 2)?;
+    Some((u16::from(bytes[0]) | u16::from(bytes[1]) << 8) as i16)
+}
+
+fn read_count(data: &[u8], pos: usize) -> Option<usize> {
+    match read_i16(data, pos)? {
+        count if count >= 0 => Some(count as usize),
+        _ => None,
+    }
+}
+
+// negative offsets mean the capability is absent or cancelled
+fn read_string(table: &[u8], offset: i16) -> Option<Vec<u8>> {
+    if offset < 0 {
+        return None;
+    }
+    let data = table.get(offset as usize..)?;
+    let end = data.iter().position(|&b| b == 0)?;
+    Some(data[..end].to_vec())
+}
+
+#[test]
+fn terminfo_parse() {
+    // names "t|test", no booleans, one number, 13 strings and an extended string E3
+    let mut data = vec![];
+    for &value in &[MAGIC, 7, 0, 1, 13, 9] {
+        data.extend_from_slice(&[value as u8, (value >> 8) as u8]);
+    }
+    data.extend_from_slice(b"t|test\0\0");
+    data.extend_from_slice(&[80, 0]);
+    let mut offsets = [-1i16; 13];
+    offsets[5] = 0;
+    offsets[12] = 5;
+    for &value in &offsets {
+        data.extend_from_slice(&[value as u8, (value >> 8) as u8]);
+    }
+    data.extend_from_slice(b"\x1b[2J\0\x1b[H\0\0");
+    for &value in &[0i16, 0, 1, 2, 8, 0, 0] {
+        data.extend_from_slice(&[value as u8, (value >> 8) as u8]);
+    }
+    data.extend_from_slice(b"\x1b[3J\0E3\0");
+
+    let info = TermInfo::parse(&data).unwrap();
+    assert_eq!(info.string("clear"), Some(&b"\x1b[2J"[..]));
+    assert_eq!(info.string("home"), Some(&b"\x1b[H"[..]));
+    assert_eq!(info.string("ed"), None);
+    assert_eq!(info.string("rs1"), None);
+    assert_eq!(info.string("E3"), Some(&b"\x1b[3J"[..]));
+
+    assert!(TermInfo::parse(b"").is_none());
+    assert!(TermInfo::parse(&data[..20]).is_none());
+}
+
+#[test]
+fn terminfo_padding() {
+    assert_eq!(strip_padding(b"\x1b[H\x1b[2J$<50>"), b"\x1b[H\x1b[2J");
+    assert_eq!(strip_padding(b"a$<5*/>b$<"), b"ab$<");
+}
@@ -0,0 +1,212 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+//! Timestamps formatted the way the GNU utilities show file times, as chosen with
+//! `--time-style` (or the `TIME_STYLE` environment variable).
+//!
+//! Formats given as `+FORMAT` use the conversions of `strftime()` (plus `%N` for nanoseconds, as
+//! in `date`).  Conversions that are not understood are printed as they are.
+
+use chrono::{Local, TimeZone};
+use clap::{Arg, ArgMatches};
+use std::ffi::OsStr;
+
+const TIME_STYLE: &str = "time-style";
+
+// roughly six months, which is how GNU ls decides whether a timestamp is "recent"
+const RECENT_SECS: i64 = 31_556_952 / 2;
+
+// the conversions chrono handles like strftime() does
+const CONVERSIONS: &str = "aAbBcCdDeFgGhHIjklmMnpPrRsStTuUvVwWxXyYzZ%";
+
+#[derive(Debug, Fail)]
+pub enum TimeStyleError {
+    #[fail(display = "invalid argument '{}' for 'time style'", _0)]
+    Invalid(String),
+}
+
+/// How to format timestamps, with separate formats for recent times (from the past six months)
+/// and for older or future ones.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TimeStyle {
+    recent: String,
+    old: String,
+}
+
+impl TimeStyle {
+    /// Parse a style accepted by `--time-style`: `full-iso`, `long-iso`, `iso`, `locale`, or
+    /// `+FORMAT`.  A `+FORMAT` containing a newline gives the format for older times before it
+    /// and the one for recent times after it.  The styles may be prefixed with `posix-`, which
+    /// selects `locale` as the POSIX locale is the only one supported.
+    pub fn parse(s: &str) -> Option<Self> {
+        if s.starts_with('+') {
+            let (old, recent) = match s[1..].find('\n') {
+                Some(pos) => (&s[1..pos + 1], &s[pos + 2..]),
+                None => (&s[1..], &s[1..]),
+            };
+            return Some(Self::new(&convert_format(old), &convert_format(recent)));
+        }
+
+        if s.starts_with("posix-") {
+            return Self::parse(&s["posix-".len()..]).map(|_| Self::default());
+        }
+        Some(match s {
+            "full-iso" => Self::new("%Y-%m-%d %H:%M:%S.%f %z", "%Y-%m-%d %H:%M:%S.%f %z"),
+            "long-iso" => Self::new("%Y-%m-%d %H:%M", "%Y-%m-%d %H:%M"),
+            "iso" => Self::new("%Y-%m-%d ", "%m-%d %H:%M"),
+            "locale" => Self::new("%b %e  %Y", "%b %e %H:%M"),
+            _ => return None,
+        })
+    }
+
+    fn new(old: &str, recent: &str) -> Self {
+        Self {
+            recent: recent.to_owned(),
+            old: old.to_owned(),
+        }
+    }
+
+    /// Determine the style using the argument created by `time_style_arg()`, falling back to
+    /// `time_style_env` (the value of `TIME_STYLE`) and then to `locale`.
+    pub fn from_matches(
+        matches: &ArgMatches,
+        time_style_env: Option<&OsStr>,
+    ) -> Result<Self, TimeStyleError> {
+        let style = match matches.value_of(TIME_STYLE) {
+            // the validator in time_style_arg() ensures the value is valid
+            Some(style) => return Ok(Self::parse(style).unwrap()),
+            None => match time_style_env {
+                Some(style) if !style.is_empty() => style.to_string_lossy(),
+                _ => return Ok(Self::default()),
+            },
+        };
+        Self::parse(&style).ok_or_else(|| TimeStyleError::Invalid(style.into_owned()))
+    }
+
+    /// Format the time `secs`.`nsec` (since the epoch) in the local time zone.  `now` (also in
+    /// seconds since the epoch) decides whether the time is recent.
+    pub fn format(&self, secs: i64, nsec: u32, now: i64) -> String {
+        let format = if secs <= now && now - secs < RECENT_SECS {
+            &self.recent
+        } else {
+            &self.old
+        };
+        match Local.timestamp_opt(secs, nsec).single() {
+            Some(time) => time.format(format).to_string(),
+            // like GNU, show the number of seconds for times that cannot be represented
+            None => secs.to_string(),
+        }
+    }
+}
+
+impl Default for TimeStyle {
+    fn default() -> Self {
+        Self::parse("locale").unwrap()
+    }
+}
+
+/// Create the `--time-style=STYLE` argument.
+pub fn time_style_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(TIME_STYLE)
+        .long(TIME_STYLE)
+        .takes_value(true)
+        .value_name("STYLE")
+        .validator(|s| match TimeStyle::parse(&s) {
+            Some(_) => Ok(()),
+            None => Err(format!(
+                "invalid argument '{}' (expected full-iso, long-iso, iso, locale, or +FORMAT)",
+                s
+            )),
+        })
+        .help("Show times using STYLE, which can be 'full-iso', 'long-iso', 'iso', 'locale', or \
+               '+FORMAT' (interpreted like in 'date')")
+}
+
+// turn a strftime() format into one chrono accepts, keeping anything it doesn't understand as
+// text
+fn convert_format(format: &str) -> String {
+    let mut result = String::with_capacity(format.len());
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+
+        let mut spec = String::from("%");
+        if let Some(&flag) = chars.peek() {
+            if flag == '-' || flag == '_' || flag == '0' || flag == ':' {
+                spec.push(flag);
+                chars.next();
+            }
+        }
+        // only the widths that chrono supports for nanoseconds are recognized
+        if let Some(&width) = chars.peek() {
+            if spec.len() == 1 && (width == '3' || width == '6' || width == '9') {
+                spec.push(width);
+                chars.next();
+            }
+        }
+
+        let conv = chars.next();
+        let valid = match (conv, &spec[1..]) {
+            (Some('N'), "") | (Some('N'), "3") | (Some('N'), "6") | (Some('N'), "9") => true,
+            (Some('z'), ":") => true,
+            (Some(conv), "") => CONVERSIONS.contains(conv),
+            (Some(conv), "-") | (Some(conv), "_") | (Some(conv), "0") => {
+                conv != '%' && CONVERSIONS.contains(conv)
+            }
+            _ => false,
+        };
+
+        if valid {
+            result.push_str(&spec);
+            result.push(match conv {
+                Some('N') => 'f',
+                Some(conv) => conv,
+                None => unreachable!(),
+            });
+        } else {
+            result.push_str(&spec.replace('%', "%%"));
+            match conv {
+                Some('%') => result.push_str("%%"),
+                Some(conv) => result.push(conv),
+                None => {}
+            }
+        }
+    }
+    result
+}
+
+#[test]
+fn time_style_parse() {
+    assert_eq!(TimeStyle::parse("iso"), Some(TimeStyle::new("%Y-%m-%d ", "%m-%d %H:%M")));
+    assert_eq!(TimeStyle::parse("posix-iso"), Some(TimeStyle::default()));
+    assert_eq!(TimeStyle::parse("+%H"), Some(TimeStyle::new("%H", "%H")));
+    assert_eq!(TimeStyle::parse("+%Y\n%H"), Some(TimeStyle::new("%Y", "%H")));
+    assert_eq!(TimeStyle::parse("isoo"), None);
+    assert_eq!(TimeStyle::parse(""), None);
+}
+
+#[test]
+fn time_style_convert_format() {
+    assert_eq!(convert_format("%Y-%m-%d %H:%M"), "%Y-%m-%d %H:%M");
+    assert_eq!(convert_format("%N %3N %-d %:z"), "%f %3f %-d %:z");
+    assert_eq!(convert_format("100%% %q %_%"), "100%% %%q %%_%%");
+    assert_eq!(convert_format("%4N %.f end%"), "%%4N %%.f end%%");
+}
+
+#[test]
+fn time_style_recent() {
+    let style = TimeStyle::parse("+old\nrecent").unwrap();
+    let now = 1_500_000_000;
+    assert_eq!(style.format(now, 0, now), "recent");
+    assert_eq!(style.format(now - RECENT_SECS + 1, 0, now), "recent");
+    assert_eq!(style.format(now - RECENT_SECS, 0, now), "old");
+    assert_eq!(style.format(now + 1, 0, now), "old");
+}
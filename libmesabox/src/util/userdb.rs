@@ -0,0 +1,129 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+//! Lookups in the user and group databases (`/etc/passwd` and `/etc/group`, or whatever NSS is
+//! configured to use) by name or by ID.
+
+use nix::libc;
+use std::ffi::{CStr, CString};
+use std::mem;
+use std::ptr;
+
+// large enough for the vast majority of passwd/group entries (the buffer grows if it is not)
+const LOOKUP_BUF_SIZE: usize = 16 * 1024;
+const MAX_BUF_SIZE: usize = 1024 * 1024;
+
+/// An entry in the user database.
+#[derive(Clone, Debug)]
+pub struct User {
+    pub name: CString,
+    pub uid: libc::uid_t,
+    /// The user's login group
+    pub gid: libc::gid_t,
+}
+
+/// An entry in the group database.
+#[derive(Clone, Debug)]
+pub struct Group {
+    pub name: CString,
+    pub gid: libc::gid_t,
+}
+
+/// Look up the user named `name`.
+pub fn user_by_name(name: &str) -> Option<User> {
+    let cname = CString::new(name).ok()?;
+    lookup(
+        |entry, buf, len, result| unsafe {
+            libc::getpwnam_r(cname.as_ptr(), entry, buf, len, result)
+        },
+        |entry: &libc::passwd| user_from_entry(entry),
+    )
+}
+
+/// Look up the user with ID `uid`.
+pub fn user_by_uid(uid: libc::uid_t) -> Option<User> {
+    lookup(
+        |entry, buf, len, result| unsafe { libc::getpwuid_r(uid, entry, buf, len, result) },
+        |entry: &libc::passwd| user_from_entry(entry),
+    )
+}
+
+/// Look up the group named `name`.
+pub fn group_by_name(name: &str) -> Option<Group> {
+    let cname = CString::new(name).ok()?;
+    lookup(
+        |entry, buf, len, result| unsafe {
+            libc::getgrnam_r(cname.as_ptr(), entry, buf, len, result)
+        },
+        |entry: &libc::group| group_from_entry(entry),
+    )
+}
+
+/// Look up the group with ID `gid`.
+pub fn group_by_gid(gid: libc::gid_t) -> Option<Group> {
+    lookup(
+        |entry, buf, len, result| unsafe { libc::getgrgid_r(gid, entry, buf, len, result) },
+        |entry: &libc::group| group_from_entry(entry),
+    )
+}
+
+fn user_from_entry(entry: &libc::passwd) -> User {
+    User {
+        name: unsafe { CStr::from_ptr(entry.pw_name) }.to_owned(),
+        uid: entry.pw_uid,
+        gid: entry.pw_gid,
+    }
+}
+
+fn group_from_entry(entry: &libc::group) -> Group {
+    Group {
+        name: unsafe { CStr::from_ptr(entry.gr_name) }.to_owned(),
+        gid: entry.gr_gid,
+    }
+}
+
+// run one of the get*_r() functions, retrying with a larger buffer if the entry doesn't fit, and
+// convert the entry found (if any) before the buffer it points into goes away
+fn lookup<E, T, F, C>(mut func: F, convert: C) -> Option<T>
+where
+    F: FnMut(*mut E, *mut libc::c_char, libc::size_t, *mut *mut E) -> libc::c_int,
+    C: FnOnce(&E) -> T,
+{
+    let mut buf = vec![0 as libc::c_char; LOOKUP_BUF_SIZE];
+    let mut entry: E = unsafe { mem::zeroed() };
+    loop {
+        let mut result = ptr::null_mut();
+        let res = func(&mut entry, buf.as_mut_ptr(), buf.len(), &mut result);
+        if res == libc::ERANGE && buf.len() < MAX_BUF_SIZE {
+            let len = buf.len() * 2;
+            buf.resize(len, 0);
+            continue;
+        }
+        return if res == 0 && !result.is_null() {
+            Some(convert(&entry))
+        } else {
+            None
+        };
+    }
+}
+
+#[test]
+fn userdb_lookup() {
+    let root = user_by_uid(0).unwrap();
+    assert_eq!(root.uid, 0);
+    let by_name = user_by_name(&root.name.to_string_lossy()).unwrap();
+    assert_eq!(by_name.uid, 0);
+    assert_eq!(by_name.gid, root.gid);
+
+    let group = group_by_gid(root.gid).unwrap();
+    assert_eq!(group_by_name(&group.name.to_string_lossy()).unwrap().gid, root.gid);
+
+    assert!(user_by_name("no such user here").is_none());
+    assert!(group_by_name("no such group here").is_none());
+    assert!(user_by_name("nul\0byte").is_none());
+}
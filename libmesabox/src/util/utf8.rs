@@ -0,0 +1,270 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+//! Decoding of data that is supposed to be UTF-8 but may not be, either all at once (`chunks()`)
+//! or piece by piece as it is read (`Decoder`).
+
+use std::str;
+
+/// How utilities should treat bytes that are not part of valid UTF-8 sequences when counting
+/// characters.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InvalidPolicy {
+    /// Each invalid sequence counts as a single character (like U+FFFD REPLACEMENT CHARACTER)
+    Replacement,
+    /// Each invalid byte counts as a character of its own
+    PerByte,
+}
+
+/// Some valid UTF-8 followed by an invalid sequence (which is empty at the end of the data).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Chunk<'a> {
+    pub valid: &'a str,
+    pub invalid: &'a [u8],
+}
+
+impl<'a> Chunk<'a> {
+    /// The number of characters in the chunk.
+    pub fn char_count(&self, policy: InvalidPolicy) -> usize {
+        let invalid = match policy {
+            InvalidPolicy::Replacement if !self.invalid.is_empty() => 1,
+            InvalidPolicy::Replacement => 0,
+            InvalidPolicy::PerByte => self.invalid.len(),
+        };
+        self.valid.chars().count() + invalid
+    }
+}
+
+/// Iterator over the `Chunk`s of some data, created by `chunks()`.
+pub struct Chunks<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = Chunk<'a>;
+
+    fn next(&mut self) -> Option<Chunk<'a>> {
+        if self.data.is_empty() {
+            return None;
+        }
+
+        let (valid_len, invalid_len, _) = split_chunk(self.data);
+        let valid = unsafe { str::from_utf8_unchecked(&self.data[..valid_len]) };
+        let invalid = &self.data[valid_len..valid_len + invalid_len];
+        self.data = &self.data[valid_len + invalid_len..];
+
+        Some(Chunk {
+            valid: valid,
+            invalid: invalid,
+        })
+    }
+}
+
+/// Split `data` into valid UTF-8 and invalid sequences.  An incomplete sequence at the end of the
+/// data is considered invalid.
+pub fn chunks(data: &[u8]) -> Chunks {
+    Chunks { data: data }
+}
+
+/// An incremental decoder for data that is read in pieces.  Multibyte characters that are split
+/// between two pieces are held back until the rest of the character arrives, so they are neither
+/// lost nor reported as invalid.
+#[derive(Default)]
+pub struct Decoder {
+    partial: [u8; 4],
+    partial_len: usize,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decode the next piece of the data, passing each `Chunk` to `func`.
+    pub fn decode<F>(&mut self, mut data: &[u8], mut func: F)
+    where
+        F: FnMut(Chunk),
+    {
+        if self.partial_len > 0 {
+            // try to complete the character left over from the previous piece
+            let take = data.len().min(self.partial.len() - self.partial_len);
+            let mut buf = self.partial;
+            buf[self.partial_len..self.partial_len + take].copy_from_slice(&data[..take]);
+            let buf = &buf[..self.partial_len + take];
+
+            let (valid_len, invalid_len, incomplete) = split_chunk(buf);
+            if valid_len == 0 && incomplete {
+                // still not enough data
+                self.partial[..buf.len()].copy_from_slice(buf);
+                self.partial_len = buf.len();
+                return;
+            }
+
+            let len = if valid_len > 0 {
+                utf8_width(buf[0])
+            } else {
+                invalid_len
+            };
+            let (valid, invalid) = buf[..len].split_at(valid_len.min(len));
+            func(Chunk {
+                valid: unsafe { str::from_utf8_unchecked(valid) },
+                invalid: invalid,
+            });
+
+            // the previous sequence was a valid prefix, so anything that ended it came from `data`
+            data = &data[len.saturating_sub(self.partial_len)..];
+            self.partial_len = 0;
+        }
+
+        while !data.is_empty() {
+            let (valid_len, invalid_len, incomplete) = split_chunk(data);
+            let end = valid_len + invalid_len;
+            let invalid = if incomplete {
+                self.partial[..invalid_len].copy_from_slice(&data[valid_len..end]);
+                self.partial_len = invalid_len;
+                &[]
+            } else {
+                &data[valid_len..end]
+            };
+
+            if valid_len > 0 || !invalid.is_empty() {
+                func(Chunk {
+                    valid: unsafe { str::from_utf8_unchecked(&data[..valid_len]) },
+                    invalid: invalid,
+                });
+            }
+            data = &data[end..];
+        }
+    }
+
+    /// Finish decoding.  If the data ended in the middle of a character, it is passed to `func`
+    /// as an invalid sequence.
+    pub fn finish<F>(&mut self, mut func: F)
+    where
+        F: FnMut(Chunk),
+    {
+        if self.partial_len > 0 {
+            func(Chunk {
+                valid: "",
+                invalid: &self.partial[..self.partial_len],
+            });
+            self.partial_len = 0;
+        }
+    }
+}
+
+// returns the length of the valid UTF-8 at the start of `data`, the length of the invalid sequence
+// following it, and whether that sequence is just incomplete (which is only possible at the end of
+// `data`)
+fn split_chunk(data: &[u8]) -> (usize, usize, bool) {
+    match str::from_utf8(data) {
+        Ok(_) => (data.len(), 0, false),
+        Err(e) => match e.error_len() {
+            Some(len) => (e.valid_up_to(), len, false),
+            None => (e.valid_up_to(), data.len() - e.valid_up_to(), true),
+        },
+    }
+}
+
+// the length of the UTF-8 sequence started by `byte` (which must start a valid sequence)
+fn utf8_width(byte: u8) -> usize {
+    match byte {
+        0x00...0x7F => 1,
+        0xC0...0xDF => 2,
+        0xE0...0xEF => 3,
+        _ => 4,
+    }
+}
+
+#[cfg(test)]
+fn decode_pieces(pieces: &[&[u8]]) -> Vec<(String, Vec<u8>)> {
+    let mut decoder = Decoder::new();
+    let mut result = vec![];
+    for piece in pieces {
+        decoder.decode(piece, |chunk| result.push((chunk.valid.to_owned(), chunk.invalid.to_vec())));
+    }
+    decoder.finish(|chunk| result.push((chunk.valid.to_owned(), chunk.invalid.to_vec())));
+    result
+}
+
+// join adjacent valid chunks, as how the text is split between them doesn't matter
+#[cfg(test)]
+fn normalize(chunks: Vec<(String, Vec<u8>)>) -> Vec<(String, Vec<u8>)> {
+    let mut result: Vec<(String, Vec<u8>)> = vec![];
+    for (valid, invalid) in chunks {
+        let merge = result.last().map(|last| last.1.is_empty()).unwrap_or(false);
+        if merge {
+            let last = result.last_mut().unwrap();
+            last.0.push_str(&valid);
+            last.1 = invalid;
+        } else {
+            result.push((valid, invalid));
+        }
+    }
+    result
+}
+
+#[test]
+fn chunks_split() {
+    let data = b"ab\xc3\xa9\xff\xfecd\xe2\x82";
+    let result: Vec<_> = chunks(data).collect();
+    assert_eq!(
+        result,
+        vec![
+            Chunk { valid: "abé", invalid: b"\xff" },
+            Chunk { valid: "", invalid: b"\xfe" },
+            Chunk { valid: "cd", invalid: b"\xe2\x82" },
+        ]
+    );
+    assert_eq!(chunks(b"").count(), 0);
+}
+
+#[test]
+fn char_counts() {
+    let total = |policy| -> usize { chunks(b"a\xc3\xa9\xe2\x82x\xff").map(|c| c.char_count(policy)).sum() };
+    assert_eq!(total(InvalidPolicy::Replacement), 5);
+    assert_eq!(total(InvalidPolicy::PerByte), 6);
+}
+
+#[test]
+fn decoder_split_characters() {
+    let text = "añ€😀z";
+    let bytes = text.as_bytes();
+    // split the text at every possible position (including in the middle of characters)
+    for i in 0..bytes.len() + 1 {
+        for j in i..bytes.len() + 1 {
+            let result = normalize(decode_pieces(&[&bytes[..i], &bytes[i..j], &bytes[j..]]));
+            assert_eq!(result, vec![(text.to_owned(), vec![])], "split at {} and {}", i, j);
+        }
+    }
+
+    // one byte at a time
+    let pieces: Vec<&[u8]> = bytes.chunks(1).collect();
+    assert_eq!(normalize(decode_pieces(&pieces)), vec![(text.to_owned(), vec![])]);
+}
+
+#[test]
+fn decoder_invalid() {
+    // an incomplete character followed by something that can't continue it
+    let result = normalize(decode_pieces(&[b"a\xe2\x82", b"b"]));
+    assert_eq!(result, vec![("a".to_owned(), b"\xe2\x82".to_vec()), ("b".to_owned(), vec![])]);
+
+    let result = normalize(decode_pieces(&[b"a\xe2", b"\xff", b"c"]));
+    assert_eq!(
+        result,
+        vec![
+            ("a".to_owned(), b"\xe2".to_vec()),
+            ("".to_owned(), b"\xff".to_vec()),
+            ("c".to_owned(), vec![]),
+        ]
+    );
+
+    // the input ends in the middle of a character
+    let result = normalize(decode_pieces(&[b"ab\xf0\x9f", b"\x98"]));
+    assert_eq!(result, vec![("ab".to_owned(), b"\xf0\x9f\x98".to_vec())]);
+}
@@ -0,0 +1,193 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+//! Natural ordering of version numbers within file names (like `ls -v` and `sort -V`), so that
+//! `v1.9` comes before `v1.10`.
+//!
+//! This is the `filevercmp()` algorithm from gnulib, which the GNU utilities use.  Names are split
+//! into runs of digits, which are compared as numbers, and runs of other characters, which are
+//! compared byte by byte except that letters come before everything else and `~` comes before
+//! even the end of the name (so `1.0~rc1` is older than `1.0`).  File name suffixes like `.tar.gz`
+//! are only taken into account if the names are equal without them.
+
+use std::cmp::Ordering;
+
+/// Compare `a` and `b` as names containing version numbers.  Names that differ only in ways the
+/// comparison ignores (such as leading zeros) are considered equal, so callers usually want to
+/// fall back on comparing the bytes.
+pub fn filevercmp(a: &[u8], b: &[u8]) -> Ordering {
+    // the empty name comes first, then ".", then "..", then other hidden files
+    if a.is_empty() || b.is_empty() {
+        return a.len().min(1).cmp(&b.len().min(1));
+    }
+    match (a[0] == b'.', b[0] == b'.') {
+        (true, false) => return Ordering::Less,
+        (false, true) => return Ordering::Greater,
+        (true, true) => {
+            for special in &[&b"."[..], b".."] {
+                match (a == *special, b == *special) {
+                    (true, true) => return Ordering::Equal,
+                    (true, false) => return Ordering::Less,
+                    (false, true) => return Ordering::Greater,
+                    (false, false) => {}
+                }
+            }
+        }
+        (false, false) => {}
+    }
+
+    let a_prefix = &a[..prefix_len(a)];
+    let b_prefix = &b[..prefix_len(b)];
+    match verrevcmp(a_prefix, b_prefix) {
+        Ordering::Equal if a_prefix.len() != a.len() || b_prefix.len() != b.len() => {
+            verrevcmp(a, b)
+        }
+        ordering => ordering,
+    }
+}
+
+// the length of `name` without its suffix, which is the longest match of the regular expression
+// (\.[A-Za-z~][A-Za-z0-9~]*)*$
+fn prefix_len(name: &[u8]) -> usize {
+    let mut i = 0;
+    loop {
+        let prefix_len = i;
+        while i + 1 < name.len() && name[i] == b'.' && is_suffix_start(name[i + 1]) {
+            i += 2;
+            while i < name.len() && (name[i].is_ascii_alphanumeric() || name[i] == b'~') {
+                i += 1;
+            }
+        }
+        if i == name.len() {
+            return prefix_len;
+        }
+        i += 1;
+    }
+}
+
+fn is_suffix_start(byte: u8) -> bool {
+    byte.is_ascii_alphabetic() || byte == b'~'
+}
+
+// the weight of the non-digit at `pos` in `s` (where the end of `s` counts as a character)
+fn order(s: &[u8], pos: usize) -> i32 {
+    match s.get(pos) {
+        None => -1,
+        Some(&byte) if byte.is_ascii_digit() => 0,
+        Some(&byte) if byte.is_ascii_alphabetic() => i32::from(byte),
+        Some(&b'~') => -2,
+        Some(&byte) => i32::from(byte) + 256,
+    }
+}
+
+fn is_digit_at(s: &[u8], pos: usize) -> bool {
+    s.get(pos).map(|c| c.is_ascii_digit()).unwrap_or(false)
+}
+
+fn verrevcmp(a: &[u8], b: &[u8]) -> Ordering {
+    let mut a_pos = 0;
+    let mut b_pos = 0;
+    while a_pos < a.len() || b_pos < b.len() {
+        // compare the runs of non-digits
+        while (a_pos < a.len() && !is_digit_at(a, a_pos))
+            || (b_pos < b.len() && !is_digit_at(b, b_pos))
+        {
+            let a_order = order(a, a_pos);
+            let b_order = order(b, b_pos);
+            if a_order != b_order {
+                return a_order.cmp(&b_order);
+            }
+            a_pos += 1;
+            b_pos += 1;
+        }
+
+        // compare the runs of digits as numbers
+        while a.get(a_pos) == Some(&b'0') {
+            a_pos += 1;
+        }
+        while b.get(b_pos) == Some(&b'0') {
+            b_pos += 1;
+        }
+        let mut first_diff = Ordering::Equal;
+        while is_digit_at(a, a_pos) && is_digit_at(b, b_pos) {
+            if first_diff == Ordering::Equal {
+                first_diff = a[a_pos].cmp(&b[b_pos]);
+            }
+            a_pos += 1;
+            b_pos += 1;
+        }
+        // the longer number is larger
+        if is_digit_at(a, a_pos) {
+            return Ordering::Greater;
+        }
+        if is_digit_at(b, b_pos) {
+            return Ordering::Less;
+        }
+        if first_diff != Ordering::Equal {
+            return first_diff;
+        }
+    }
+    Ordering::Equal
+}
+
+#[test]
+fn version_order() {
+    // the order GNU sort -V puts these in
+    let sorted: &[&str] = &[
+        "",
+        ".",
+        "..",
+        ".A",
+        ".Z",
+        ".a~",
+        ".a",
+        ".0",
+        ".9",
+        "0",
+        "9",
+        "A",
+        "Z",
+        "a~",
+        "a",
+        "a.b~",
+        "a.b",
+        "a.bc",
+        "a+",
+        "a.",
+        "a..a",
+        "a.+",
+        "b~",
+        "b",
+        "file9.txt",
+        "file10.txt",
+        "gcc-c++-10.fc9.tar.gz",
+        "gcc-c++-10.8.12-0.7rc2.fc9.tar.bz2",
+        "nss_ldap-1-0.2b.fc9.tar.bz2",
+        "nss_ldap-1-0.6rc2.fc8.tar.gz",
+        "nss_ldap-1.0-0.1a.tar.gz",
+        "nss_ldap-10beta1.fc8.tar.gz",
+        "nss_ldap-10.11.8.6.20040204cvs.fc10.ebuild",
+        "v1.9",
+        "v1.10",
+        "z",
+        "zz~",
+        "zz",
+        "zz.~1~",
+        "zz.0",
+        "zz.0.txt",
+    ];
+
+    for (i, a) in sorted.iter().enumerate() {
+        for (j, b) in sorted.iter().enumerate() {
+            let ordering = filevercmp(a.as_bytes(), b.as_bytes());
+            assert_eq!(ordering, i.cmp(&j), "{:?} {:?}", a, b);
+        }
+    }
+
+    assert_eq!(filevercmp(b"v1.009", b"v1.9"), Ordering::Equal);
+}
@@ -0,0 +1,234 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use failure::Fail;
+use std::error::Error as StdError;
+use std::fmt::{self, Display};
+use std::io::{self, Write};
+
+use super::EnvVars;
+use error::io_error_msg;
+use MesaError;
+
+/// The environment variable that selects the `WriteError` policy used by every utility.
+pub const OUTPUT_ERROR_VAR: &str = "MESABOX_OUTPUT_ERROR";
+
+/// What to do when writing to an output fails, using the modes of the `--output-error` option
+/// of GNU `tee`.
+///
+/// Utilities with only one output cannot do anything useful once it fails, so for them the modes
+/// only decide whether a broken pipe is reported as an error or ends the utility quietly (and
+/// successfully).  Utilities with several outputs also use the mode to decide whether to keep
+/// writing to the outputs that still work.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum WriteError {
+    /// Report errors writing to any output
+    Warn,
+    /// Report errors writing to any output other than a pipe
+    WarnNoPipe,
+    /// Exit on the first error writing to any output
+    Exit,
+    /// Exit on the first error writing to any output other than a pipe
+    ExitNoPipe,
+}
+
+impl WriteError {
+    /// Parse one of the mode names accepted by `--output-error`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "warn" => Some(WriteError::Warn),
+            "warn-nopipe" => Some(WriteError::WarnNoPipe),
+            "exit" => Some(WriteError::Exit),
+            "exit-nopipe" => Some(WriteError::ExitNoPipe),
+            _ => None,
+        }
+    }
+
+    /// Determine the policy requested using `OUTPUT_ERROR_VAR`.  Invalid values are ignored in
+    /// favor of the default.
+    pub(crate) fn from_env(env: &EnvVars) -> Self {
+        env.get(OUTPUT_ERROR_VAR)
+            .and_then(|value| value.to_str().and_then(Self::from_name))
+            .unwrap_or_default()
+    }
+
+    /// Whether an error writing to one output should stop the utility from writing to the rest.
+    pub fn exits(self) -> bool {
+        match self {
+            WriteError::Exit | WriteError::ExitNoPipe => true,
+            WriteError::Warn | WriteError::WarnNoPipe => false,
+        }
+    }
+
+    /// Whether writing to a pipe without any readers should quietly end the utility.
+    pub fn ignores_pipe(self) -> bool {
+        match self {
+            WriteError::WarnNoPipe | WriteError::ExitNoPipe => true,
+            WriteError::Warn | WriteError::Exit => false,
+        }
+    }
+
+    /// Whether `err` should be treated as success rather than reported.
+    pub fn ignores(self, err: &MesaError) -> bool {
+        self.ignores_pipe() && err.is_broken_pipe()
+    }
+}
+
+impl Default for WriteError {
+    fn default() -> Self {
+        WriteError::WarnNoPipe
+    }
+}
+
+/// Check whether `err` (or anything that caused it) is the result of writing to a pipe without
+/// any readers.  Utilities that process several inputs should stop once this happens, as there
+/// is nowhere left to write the output for the remaining inputs.
+pub fn is_broken_pipe(err: &Fail) -> bool {
+    err.causes().any(|cause| {
+        cause
+            .downcast_ref::<io::Error>()
+            .map(|e| e.kind() == io::ErrorKind::BrokenPipe)
+            .unwrap_or(false)
+    })
+}
+
+/// An error writing to the output of a utility, as opposed to one reading its input.  As both
+/// tend to come out of the same `io::copy()`, the output is wrapped in an `OutputWriter` that
+/// marks its errors with this.
+#[derive(Debug)]
+pub struct OutputError(io::Error);
+
+impl Display for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "error writing to output: {}", io_error_msg(&self.0))
+    }
+}
+
+impl StdError for OutputError {
+    fn description(&self) -> &str {
+        "error writing to output"
+    }
+
+    fn cause(&self) -> Option<&StdError> {
+        Some(&self.0)
+    }
+}
+
+/// Mark `err` as having occurred while writing to the output.  The result keeps the kind of
+/// `err`, so checks like `is_broken_pipe()` still work.
+pub fn output_error(err: io::Error) -> io::Error {
+    if is_output_io_error(&err) {
+        err
+    } else {
+        io::Error::new(err.kind(), OutputError(err))
+    }
+}
+
+/// Check whether `err` (or anything that caused it) was marked by `output_error()`.  Once writing
+/// to the output has failed (for instance because the disk is full), utilities should stop
+/// rather than go on to the next input.
+pub fn is_output_error(err: &Fail) -> bool {
+    err.causes().any(|cause| {
+        cause
+            .downcast_ref::<io::Error>()
+            .map(is_output_io_error)
+            .unwrap_or(false)
+    })
+}
+
+fn is_output_io_error(err: &io::Error) -> bool {
+    err.get_ref().map(|inner| inner.is::<OutputError>()).unwrap_or(false)
+}
+
+/// A writer that marks every error of the writer it wraps using `output_error()`.
+pub struct OutputWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> OutputWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner: inner }
+    }
+}
+
+impl<W: Write> Write for OutputWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf).map_err(output_error)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush().map_err(output_error)
+    }
+}
+
+#[test]
+fn write_error_names() {
+    assert_eq!(WriteError::from_name("warn"), Some(WriteError::Warn));
+    assert_eq!(WriteError::from_name("exit-nopipe"), Some(WriteError::ExitNoPipe));
+    assert_eq!(WriteError::from_name("nopipe"), None);
+    assert!(WriteError::default().ignores_pipe());
+    assert!(!WriteError::default().exits());
+
+    let env: EnvVars = vec![(OUTPUT_ERROR_VAR, "exit")].into_iter().collect();
+    assert_eq!(WriteError::from_env(&env), WriteError::Exit);
+    let env: EnvVars = vec![(OUTPUT_ERROR_VAR, "bogus")].into_iter().collect();
+    assert_eq!(WriteError::from_env(&env), WriteError::default());
+}
+
+#[test]
+fn broken_pipe_errors() {
+    let pipe = MesaError::from(io::Error::new(io::ErrorKind::BrokenPipe, "broken pipe"));
+    let other = MesaError::from(io::Error::new(io::ErrorKind::Other, "other"));
+
+    assert!(WriteError::WarnNoPipe.ignores(&pipe));
+    assert!(WriteError::ExitNoPipe.ignores(&pipe));
+    assert!(!WriteError::Warn.ignores(&pipe));
+    assert!(!WriteError::WarnNoPipe.ignores(&other));
+    assert!(!WriteError::WarnNoPipe.ignores(&MesaError::new(None, 1, None)));
+}
+
+#[cfg(all(test, unix))]
+struct FullDisk {
+    space: usize,
+}
+
+#[cfg(all(test, unix))]
+impl Write for FullDisk {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.space == 0 {
+            // ENOSPC
+            return Err(io::Error::from_raw_os_error(28));
+        }
+        let len = buf.len().min(self.space);
+        self.space -= len;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn output_errors() {
+    let mut output = OutputWriter::new(FullDisk { space: 4 });
+    let err = io::copy(&mut &b"too much data"[..], &mut output).unwrap_err();
+    assert_eq!(err.to_string(), "error writing to output: No space left on device");
+
+    // marking an error twice does not repeat the message
+    let err = MesaError::from(output_error(err));
+    assert!(err.is_output_error());
+    assert_eq!(err.to_string(), "error writing to output: No space left on device");
+
+    let read_err = MesaError::from(io::Error::new(io::ErrorKind::Other, "other"));
+    assert!(!read_err.is_output_error());
+    let pipe = MesaError::from(output_error(io::ErrorKind::BrokenPipe.into()));
+    assert!(pipe.is_output_error());
+    assert!(pipe.is_broken_pipe());
+}
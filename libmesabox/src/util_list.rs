@@ -3,6 +3,9 @@ generate_fns! {
         (arch, "arch"),
         (base32, "base32"),
         (base64, "base64"),
+        (chroot, "chroot"),
+        (fmt, "fmt"),
+        (shred, "shred"),
         (yes, "yes")
     },
     loginutils {
@@ -11,22 +14,55 @@ generate_fns! {
     lsb {
         (tar, "tar_util")
     },
+    misc {
+        (pv, "pv")
+    },
+    moreutils {
+        (errno, "errno"),
+        (sponge, "sponge"),
+        (ts, "ts")
+    },
+    ncurses {
+        (clear, "clear"),
+        (reset, "reset")
+    },
     networking {
         (ping, "ping"),
         (nc, "nc")
     },
     posix {
+        (awk, "awk"),
         (cat, "cat"),
         (chmod, "chmod"),
+        (cmp, "cmp"),
+        (diff, "diff"),
         (echo, "echo"),
         (false, "false"),
+        (find, "find"),
+        (grep, "grep"),
         (head, "head"),
+        (iconv, "iconv"),
+        (ls, "ls"),
+        (sed, "sed"),
         (sh, "sh"),
         (sleep, "sleep"),
+        (stty, "stty"),
+        (tee, "tee"),
         (true, "true"),
-        (uname, "uname")
+        (uname, "uname"),
+        (xargs, "xargs")
+    },
+    procps {
+        (watch, "watch")
     },
     sysinit {
         (init, "init")
+    },
+    utillinux {
+        (column, "column"),
+        (getopt, "getopt"),
+        (hexdump, "hexdump"),
+        (mountpoint, "mountpoint"),
+        (rev, "rev")
     }
 }
@@ -0,0 +1,284 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use util::utf8::{self, InvalidPolicy};
+use util;
+use {ArgsIter, MesaError, Result, UtilRead, UtilSetup, UtilWrite};
+
+use clap::Arg;
+use std::ffi::OsStr;
+use std::io::{Read, Write};
+use std::result::Result as StdResult;
+
+pub(crate) const NAME: &str = "column";
+pub(crate) const DESCRIPTION: &str = "Format input into multiple columns";
+
+const TAB_WIDTH: usize = 8;
+const DEFAULT_SEPARATORS: &[u8] = b" \t";
+const DEFAULT_OUTPUT_SEPARATOR: &[u8] = b"  ";
+
+struct Options<'a> {
+    table: bool,
+    separators: &'a [u8],
+    output_separator: &'a [u8],
+    width: usize,
+    fill_rows: bool,
+}
+
+pub fn execute<S, T>(setup: &mut S, args: T) -> Result<()>
+where
+    S: UtilSetup,
+    T: ArgsIter,
+{
+    let matches = {
+        let app = util_app!(NAME)
+                    .arg(Arg::with_name("table")
+                            .short("t")
+                            .help("Split each line into fields and align the fields in a table"))
+                    .arg(Arg::with_name("separator")
+                            .short("s")
+                            .takes_value(true)
+                            .value_name("CHARS")
+                            .help("Split fields at any of CHARS rather than at whitespace (with -t)"))
+                    .arg(Arg::with_name("output-separator")
+                            .short("o")
+                            .takes_value(true)
+                            .value_name("STRING")
+                            .help("Separate the columns of a table with STRING rather than two spaces"))
+                    .arg(Arg::with_name("columns")
+                            .short("c")
+                            .takes_value(true)
+                            .value_name("WIDTH")
+                            .validator(is_valid_width)
+                            .help("Fit the output in WIDTH columns rather than the width of the terminal"))
+                    .arg(Arg::with_name("fill-rows")
+                            .short("x")
+                            .help("Fill each row before moving on to the next (rather than filling columns)"))
+                    .arg(Arg::with_name("FILES")
+                            .index(1)
+                            .multiple(true));
+
+        app.get_matches_from_safe(args)?
+    };
+
+    // .unwrap() is fine because of the validator
+    let width = match matches.value_of("columns") {
+        Some(width) => width.parse().unwrap(),
+        None => {
+            let stdout = setup.output().raw_object();
+            util::terminal_width(setup.env(), stdout)
+        }
+    };
+    let separators = matches.value_of_os("separator").map(util::os_str_as_bytes);
+    let output_separator = matches.value_of_os("output-separator").map(util::os_str_as_bytes);
+    let options = Options {
+        table: matches.is_present("table"),
+        separators: separators.as_ref().map(|s| &s[..]).unwrap_or(DEFAULT_SEPARATORS),
+        output_separator: output_separator
+            .as_ref()
+            .map(|s| &s[..])
+            .unwrap_or(DEFAULT_OUTPUT_SEPARATOR),
+        width: width,
+        fill_rows: matches.is_present("fill-rows"),
+    };
+
+    let current_dir = setup.current_dir().map(|p| p.to_owned());
+    let (input, output, error) = setup.stdio();
+    let mut output = output.lock()?;
+    let mut err_stream = error.lock()?;
+
+    // everything has to be read before the width of the columns is known
+    let mut result = Ok(());
+    let mut data = vec![];
+    match matches.values_of_os("FILES") {
+        Some(files) => {
+            for file in files {
+                let mut contents = vec![];
+                let res = if file == OsStr::new("-") {
+                    input
+                        .lock()
+                        .map_err(MesaError::from)
+                        .and_then(|mut stdin| Ok(stdin.read_to_end(&mut contents)?))
+                } else {
+                    util::open_file(&current_dir, file)
+                        .and_then(|mut file| Ok(file.read_to_end(&mut contents)?))
+                };
+
+                match res {
+                    Ok(_) => data.push(contents),
+                    Err(mut e) => {
+                        display_msg!(err_stream, "{}", e)?;
+                        e.err = None;
+                        result = Err(e);
+                    }
+                }
+            }
+        }
+        None => {
+            let mut contents = vec![];
+            input.lock()?.read_to_end(&mut contents)?;
+            data.push(contents);
+        }
+    }
+
+    // like other implementations, empty lines are ignored
+    let lines: Vec<&[u8]> = data
+        .iter()
+        .flat_map(|contents| contents.split(|&byte| byte == b'\n'))
+        .filter(|line| !line.is_empty())
+        .collect();
+
+    if options.table {
+        write_table(&mut output, &lines, &options)?;
+    } else {
+        write_columns(&mut output, &lines, &options)?;
+    }
+    output.flush()?;
+
+    result
+}
+
+fn write_table<W: Write>(output: &mut W, lines: &[&[u8]], options: &Options) -> Result<()> {
+    let rows: Vec<Vec<&[u8]>> = lines
+        .iter()
+        .map(|line| split_fields(line, options.separators))
+        .filter(|fields| !fields.is_empty())
+        .collect();
+
+    let mut widths: Vec<usize> = vec![];
+    for fields in &rows {
+        for (i, field) in fields.iter().enumerate() {
+            let width = text_width(field);
+            if i < widths.len() {
+                widths[i] = widths[i].max(width);
+            } else {
+                widths.push(width);
+            }
+        }
+    }
+
+    for fields in &rows {
+        for (i, field) in fields.iter().enumerate() {
+            output.write_all(field)?;
+            // the last field of each row is left unpadded to avoid trailing whitespace
+            if i + 1 < fields.len() {
+                write_spaces(output, widths[i] - text_width(field))?;
+                output.write_all(options.output_separator)?;
+            }
+        }
+        output.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
+
+fn write_columns<W: Write>(output: &mut W, items: &[&[u8]], options: &Options) -> Result<()> {
+    let widths: Vec<usize> = items.iter().map(|item| text_width(item)).collect();
+    // each column starts at a tab stop with at least one tab between columns
+    let col_width = match widths.iter().max() {
+        Some(&max) => (max / TAB_WIDTH + 1) * TAB_WIDTH,
+        None => return Ok(()),
+    };
+    let cols = (options.width / col_width).max(1);
+
+    for row in column_rows(items.len(), cols, options.fill_rows) {
+        for (i, &index) in row.iter().enumerate() {
+            output.write_all(items[index])?;
+            if i + 1 < row.len() {
+                write_tabs(output, widths[index], col_width)?;
+            }
+        }
+        output.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
+
+// determine which item goes where when laying out `count` items in (at most) `cols` columns
+fn column_rows(count: usize, cols: usize, fill_rows: bool) -> Vec<Vec<usize>> {
+    if count == 0 {
+        return vec![];
+    }
+
+    let rows = (count + cols - 1) / cols;
+    if fill_rows {
+        (0..rows)
+            .map(|row| (row * cols..count.min((row + 1) * cols)).collect())
+            .collect()
+    } else {
+        (0..rows)
+            .map(|row| {
+                (0..cols)
+                    .map(|col| col * rows + row)
+                    .take_while(|&index| index < count)
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+// split `line` at any of the bytes in `separators`, treating a run of separators as a single one
+fn split_fields<'a>(line: &'a [u8], separators: &[u8]) -> Vec<&'a [u8]> {
+    line.split(|byte| separators.contains(byte))
+        .filter(|field| !field.is_empty())
+        .collect()
+}
+
+// the number of columns `text` takes up, counting each character (rather than each byte) as one
+fn text_width(text: &[u8]) -> usize {
+    utf8::chunks(text)
+        .map(|chunk| chunk.char_count(InvalidPolicy::PerByte))
+        .sum()
+}
+
+fn write_spaces<W: Write>(output: &mut W, count: usize) -> Result<()> {
+    for _ in 0..count {
+        output.write_all(b" ")?;
+    }
+    Ok(())
+}
+
+// pad with tabs from column `start` to column `end` (which should be at a tab stop)
+fn write_tabs<W: Write>(output: &mut W, start: usize, end: usize) -> Result<()> {
+    let mut pos = start;
+    while pos < end {
+        output.write_all(b"\t")?;
+        pos = (pos / TAB_WIDTH + 1) * TAB_WIDTH;
+    }
+    Ok(())
+}
+
+fn is_valid_width(val: String) -> StdResult<(), String> {
+    match val.parse::<usize>() {
+        Ok(0) => Err(format!("width must be at least 1: '{}'", val)),
+        Ok(_) => Ok(()),
+        Err(_) => Err(format!("invalid width '{}'", val)),
+    }
+}
+
+#[test]
+fn column_layouts() {
+    assert_eq!(
+        column_rows(5, 2, false),
+        vec![vec![0, 3], vec![1, 4], vec![2]]
+    );
+    assert_eq!(
+        column_rows(5, 2, true),
+        vec![vec![0, 1], vec![2, 3], vec![4]]
+    );
+    assert_eq!(column_rows(3, 10, false), vec![vec![0, 1, 2]]);
+    assert_eq!(column_rows(0, 4, false), Vec::<Vec<usize>>::new());
+}
+
+#[test]
+fn fields_and_widths() {
+    assert_eq!(split_fields(b"  a  b\tc ", b" \t"), vec![&b"a"[..], b"b", b"c"]);
+    assert_eq!(split_fields(b"a:b::c", b":"), vec![&b"a"[..], b"b", b"c"]);
+    assert_eq!(text_width("añ€".as_bytes()), 3);
+    assert_eq!(text_width(b"a\xff\xfe"), 3);
+}
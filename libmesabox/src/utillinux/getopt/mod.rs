@@ -0,0 +1,287 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use util::os_str_as_bytes;
+use {ArgsIter, MesaError, Result, UtilSetup, UtilWrite, EXIT_FAILURE};
+
+use clap::{AppSettings, Arg, ArgMatches};
+use std::ffi::OsString;
+use std::io::Write;
+
+use self::parser::{Parsed, Spec, Word};
+
+mod parser;
+
+pub(crate) const NAME: &str = "getopt";
+pub(crate) const DESCRIPTION: &str = "Parse command options for shell scripts";
+
+const AFTER_HELP: &str = "
+getopt can be run in three ways:
+
+  getopt OPTSTRING PARAMETERS
+  getopt [OPTIONS] [--] OPTSTRING PARAMETERS
+  getopt [OPTIONS] -o|--options OPTSTRING [OPTIONS] [--] PARAMETERS
+
+The PARAMETERS are printed in a canonical form, with the options first (each followed by its
+argument if it takes one) and the remaining parameters after '--', quoted for the shell so that
+the result can be given to 'eval set --'.  The first way is compatible with older versions of
+getopt, so nothing is quoted (as is also the case if GETOPT_COMPATIBLE is set).
+
+getopt exits with 1 if PARAMETERS contain an invalid option, 2 if its own options are wrong, and
+4 for --test.
+";
+
+const EXIT_USAGE: i32 = 2;
+const EXIT_TEST: i32 = 4;
+
+/// The environment variable that makes the first argument the option string.
+const COMPATIBLE_VAR: &str = "GETOPT_COMPATIBLE";
+
+#[derive(Debug, Fail)]
+enum GetoptError {
+    #[fail(display = "missing optstring argument")]
+    MissingOptstring,
+}
+
+/// How to quote arguments for the shell that evaluates the output.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Quoting {
+    None,
+    Sh,
+    Tcsh,
+}
+
+struct Options {
+    quoting: Quoting,
+    quiet: bool,
+    quiet_output: bool,
+    name: String,
+}
+
+pub fn execute<S, T>(setup: &mut S, args: T) -> Result<()>
+where
+    S: UtilSetup,
+    T: ArgsIter,
+{
+    let args: Vec<OsString> = args.map(|arg| arg.into()).collect();
+    let posixly_correct = setup.env().get("POSIXLY_CORRECT").is_some();
+
+    // the original getopt only took an option string and the parameters
+    let compatible = setup.env().get(COMPATIBLE_VAR).is_some();
+    if args.len() > 1 && (compatible || !os_str_as_bytes(&args[1]).starts_with(b"-")) {
+        let spec = Spec::new(&os_str_as_bytes(&args[1]), posixly_correct);
+        let options = Options {
+            quoting: Quoting::None,
+            quiet: false,
+            quiet_output: false,
+            name: NAME.to_owned(),
+        };
+        return run(setup, &spec, &args[2..], &options);
+    }
+
+    let matches = {
+        let app = util_app!(NAME)
+                    .after_help(AFTER_HELP)
+                    .setting(AppSettings::TrailingVarArg)
+                    .arg(Arg::with_name("alternative")
+                            .short("a")
+                            .long("alternative")
+                            .help("Allow long options to start with a single '-'"))
+                    .arg(Arg::with_name("longoptions")
+                            .short("l")
+                            .long("longoptions")
+                            .visible_alias("long")
+                            .takes_value(true)
+                            .multiple(true)
+                            .number_of_values(1)
+                            .value_name("LONGOPTS")
+                            .help("Recognize the long options in LONGOPTS (separated by commas)"))
+                    .arg(Arg::with_name("name")
+                            .short("n")
+                            .long("name")
+                            .takes_value(true)
+                            .value_name("NAME")
+                            .help("Report errors as coming from NAME"))
+                    .arg(Arg::with_name("options")
+                            .short("o")
+                            .long("options")
+                            .takes_value(true)
+                            .allow_hyphen_values(true)
+                            .value_name("OPTSTRING")
+                            .help("Recognize the short options in OPTSTRING"))
+                    .arg(Arg::with_name("quiet")
+                            .short("q")
+                            .long("quiet")
+                            .help("Do not report invalid options in PARAMETERS"))
+                    .arg(Arg::with_name("quiet-output")
+                            .short("Q")
+                            .long("quiet-output")
+                            .help("Only check PARAMETERS rather than printing them"))
+                    .arg(Arg::with_name("shell")
+                            .short("s")
+                            .long("shell")
+                            .takes_value(true)
+                            .value_name("SHELL")
+                            .possible_values(&["sh", "bash", "csh", "tcsh"])
+                            .help("Quote the output for SHELL (sh by default)"))
+                    .arg(Arg::with_name("test")
+                            .short("T")
+                            .long("test")
+                            .help("Exit with 4 to show that this getopt quotes its output"))
+                    .arg(Arg::with_name("unquoted")
+                            .short("u")
+                            .long("unquoted")
+                            .help("Do not quote the output"))
+                    .arg(Arg::with_name("PARAMETERS")
+                            .index(1)
+                            .multiple(true)
+                            .allow_hyphen_values(true));
+
+        app.get_matches_from_safe(args)
+            .map_err(|e| MesaError::from(e).with_exitcode(EXIT_USAGE))?
+    };
+
+    if matches.is_present("test") {
+        return Err(MesaError::new(None, EXIT_TEST, None));
+    }
+
+    let mut params: Vec<OsString> = matches
+        .values_of_os("PARAMETERS")
+        .map(|values| values.map(|value| value.to_owned()).collect())
+        .unwrap_or_default();
+    let optstring = match matches.value_of_os("options") {
+        Some(optstring) => optstring.to_owned(),
+        None if !params.is_empty() => params.remove(0),
+        None => {
+            return Err(MesaError::from(GetoptError::MissingOptstring).with_exitcode(EXIT_USAGE))
+        }
+    };
+
+    let mut spec = Spec::new(&os_str_as_bytes(&optstring), posixly_correct);
+    if let Some(longopts) = matches.values_of_os("longoptions") {
+        for longopts in longopts {
+            spec.add_long(&os_str_as_bytes(longopts));
+        }
+    }
+    spec.set_alternative(matches.is_present("alternative"));
+
+    let options = Options {
+        quoting: quoting(&matches),
+        quiet: matches.is_present("quiet"),
+        quiet_output: matches.is_present("quiet-output"),
+        name: matches
+            .value_of_lossy("name")
+            .map(|name| name.into_owned())
+            .unwrap_or_else(|| NAME.to_owned()),
+    };
+
+    run(setup, &spec, &params, &options)
+}
+
+fn quoting(matches: &ArgMatches) -> Quoting {
+    if matches.is_present("unquoted") {
+        return Quoting::None;
+    }
+    match matches.value_of("shell") {
+        Some("csh") | Some("tcsh") => Quoting::Tcsh,
+        _ => Quoting::Sh,
+    }
+}
+
+fn run<S>(setup: &mut S, spec: &Spec, params: &[OsString], options: &Options) -> Result<()>
+where
+    S: UtilSetup,
+{
+    let params: Vec<Vec<u8>> = params
+        .iter()
+        .map(|param| os_str_as_bytes(param).into_owned())
+        .collect();
+    let parsed = parser::parse(spec, &params, &options.name);
+
+    let (_, stdout, stderr) = setup.stdio();
+    if !options.quiet {
+        let mut stderr = stderr.lock()?;
+        for msg in &parsed.errors {
+            writeln!(stderr, "{}", msg)?;
+        }
+    }
+
+    if !options.quiet_output {
+        let mut stdout = stdout.lock()?;
+        stdout.write_all(&format_output(&parsed, options.quoting))?;
+    }
+
+    if parsed.failed {
+        Err(MesaError::new(None, EXIT_FAILURE, None))
+    } else {
+        Ok(())
+    }
+}
+
+// the options followed by '--' and the operands, all on one line
+fn format_output(parsed: &Parsed, quoting: Quoting) -> Vec<u8> {
+    let mut output = vec![];
+    for word in &parsed.options {
+        output.push(b' ');
+        match *word {
+            Word::Option(ref option) => output.extend_from_slice(option),
+            Word::Arg(ref arg) => quote(&mut output, arg, quoting),
+        }
+    }
+    output.extend_from_slice(b" --");
+    for operand in &parsed.operands {
+        output.push(b' ');
+        quote(&mut output, operand, quoting);
+    }
+    output.push(b'\n');
+    output
+}
+
+/// Add `arg` to `output` in single quotes (unless `quoting` is `None`), so that the shell reads
+/// it back unchanged.
+fn quote(output: &mut Vec<u8>, arg: &[u8], quoting: Quoting) {
+    if quoting == Quoting::None {
+        output.extend_from_slice(arg);
+        return;
+    }
+
+    output.push(b'\'');
+    for &b in arg {
+        match b {
+            // there is no way to escape anything inside single quotes, so the quotes are closed
+            // around an escaped character instead
+            b'\'' => output.extend_from_slice(b"'\\''"),
+            b'!' if quoting == Quoting::Tcsh => output.extend_from_slice(b"'\\!'"),
+            b'\n' if quoting == Quoting::Tcsh => output.extend_from_slice(b"\\n"),
+            b' ' | b'\t' | b'\r' | b'\x0b' | b'\x0c' if quoting == Quoting::Tcsh => {
+                output.extend_from_slice(b"'\\");
+                output.push(b);
+                output.push(b'\'');
+            }
+            _ => output.push(b),
+        }
+    }
+    output.push(b'\'');
+}
+
+#[test]
+fn getopt_quote() {
+    let cases: &[(&[u8], Quoting, &[u8])] = &[
+        (b"it's $x", Quoting::Sh, b"'it'\\''s $x'"),
+        (b"", Quoting::Sh, b"''"),
+        (b"a!b", Quoting::Sh, b"'a!b'"),
+        (b"a!b", Quoting::Tcsh, b"'a'\\!'b'"),
+        (b"x\ny z", Quoting::Tcsh, b"'x\\ny'\\ 'z'"),
+        (b"it's", Quoting::None, b"it's"),
+    ];
+    for &(arg, quoting, expected) in cases {
+        let mut output = vec![];
+        quote(&mut output, arg, quoting);
+        assert_eq!(output, expected);
+    }
+}
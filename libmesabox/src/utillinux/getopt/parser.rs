@@ -0,0 +1,411 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+//! The option parsing of `getopt_long()` (and `getopt_long_only()` for `--alternative`), which
+//! `getopt` applies to the parameters it is given.  Error messages match those of glibc.
+
+/// Whether an option takes an argument (no colons, one colon, or two colons in the option
+/// string).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum HasArg {
+    No,
+    Required,
+    Optional,
+}
+
+/// What to do with parameters that are not options.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Ordering {
+    /// Move them after the options (the default)
+    Permute,
+    /// Stop looking for options at the first one (a leading `+`, or `POSIXLY_CORRECT`)
+    RequireOrder,
+    /// Keep them where they are among the options (a leading `-`)
+    ReturnInOrder,
+}
+
+/// The options to recognize.
+pub struct Spec {
+    short: Vec<(u8, HasArg)>,
+    long: Vec<(Vec<u8>, HasArg)>,
+    ordering: Ordering,
+    print_errors: bool,
+    alternative: bool,
+}
+
+impl Spec {
+    /// Read the short options from `optstring` (like `ab:c::`).
+    pub fn new(optstring: &[u8], posixly_correct: bool) -> Self {
+        let (ordering, mut rest) = match optstring.first() {
+            Some(&b'+') => (Ordering::RequireOrder, &optstring[1..]),
+            Some(&b'-') => (Ordering::ReturnInOrder, &optstring[1..]),
+            _ if posixly_correct => (Ordering::RequireOrder, optstring),
+            _ => (Ordering::Permute, optstring),
+        };
+        // a leading colon asks for errors to be left to the caller
+        let print_errors = rest.first() != Some(&b':');
+        if !print_errors {
+            rest = &rest[1..];
+        }
+
+        let mut short = vec![];
+        let mut i = 0;
+        while i < rest.len() {
+            let c = rest[i];
+            let colons = rest[i + 1..].iter().take(2).take_while(|&&b| b == b':').count();
+            i += 1 + colons;
+            if c != b':' {
+                short.push((c, has_arg(colons)));
+            }
+        }
+
+        Self {
+            short: short,
+            long: vec![],
+            ordering: ordering,
+            print_errors: print_errors,
+            alternative: false,
+        }
+    }
+
+    /// Add the long options in `longopts`, which are separated by commas or whitespace (like
+    /// `all,file:,color::`).
+    pub fn add_long(&mut self, longopts: &[u8]) {
+        let names = longopts.split(|&b| b == b',' || (b as char).is_ascii_whitespace());
+        for name in names.filter(|name| !name.is_empty()) {
+            let end = name.iter().rposition(|&b| b != b':').map(|pos| pos + 1).unwrap_or(0);
+            self.long.push((name[..end].to_vec(), has_arg((name.len() - end).min(2))));
+        }
+    }
+
+    /// Allow long options to start with a single `-` (`--alternative`).
+    pub fn set_alternative(&mut self, alternative: bool) {
+        self.alternative = alternative;
+    }
+
+    fn find_short(&self, c: u8) -> Option<HasArg> {
+        self.short.iter().find(|&&(s, _)| s == c).map(|&(_, has_arg)| has_arg)
+    }
+
+    // find the long option `name` (or the only one starting with it)
+    fn find_long(&self, name: &[u8]) -> LongMatch {
+        if let Some(index) = self.long.iter().position(|&(ref long, _)| &long[..] == name) {
+            return LongMatch::Found(index);
+        }
+        let matches: Vec<usize> = (0..self.long.len())
+            .filter(|&i| self.long[i].0.starts_with(name))
+            .collect();
+        match matches.len() {
+            0 => LongMatch::None,
+            1 => LongMatch::Found(matches[0]),
+            _ => LongMatch::Ambiguous(matches),
+        }
+    }
+}
+
+enum LongMatch {
+    Found(usize),
+    Ambiguous(Vec<usize>),
+    None,
+}
+
+fn has_arg(colons: usize) -> HasArg {
+    match colons {
+        0 => HasArg::No,
+        1 => HasArg::Required,
+        _ => HasArg::Optional,
+    }
+}
+
+/// A piece of the options part of the output.
+#[derive(Debug, Eq, PartialEq)]
+pub enum Word {
+    /// An option (like `-a` or `--all`), which never needs quoting
+    Option(Vec<u8>),
+    /// The argument of an option, or a parameter kept in place by a leading `-`
+    Arg(Vec<u8>),
+}
+
+/// The parameters sorted into options and operands.
+#[derive(Debug, Default)]
+pub struct Parsed {
+    pub options: Vec<Word>,
+    pub operands: Vec<Vec<u8>>,
+    /// Whether any of the options were invalid (and left out of `options`)
+    pub failed: bool,
+    /// The messages describing the invalid options, unless they were turned off
+    pub errors: Vec<String>,
+}
+
+struct Parser<'a> {
+    spec: &'a Spec,
+    name: &'a str,
+    args: &'a [Vec<u8>],
+    // the index of the parameter after the one being parsed
+    next: usize,
+    parsed: Parsed,
+}
+
+/// Parse `args`, using `name` in error messages.
+pub fn parse(spec: &Spec, args: &[Vec<u8>], name: &str) -> Parsed {
+    let mut parser = Parser {
+        spec: spec,
+        name: name,
+        args: args,
+        next: 0,
+        parsed: Parsed::default(),
+    };
+    parser.parse();
+    parser.parsed
+}
+
+impl<'a> Parser<'a> {
+    fn parse(&mut self) {
+        let args = self.args;
+        while self.next < args.len() {
+            let arg = &args[self.next];
+            self.next += 1;
+
+            if &arg[..] == b"--" {
+                break;
+            } else if arg.starts_with(b"--") {
+                self.long_option(&arg[2..], "--");
+            } else if arg.len() > 1 && arg[0] == b'-' {
+                if !self.spec.alternative || !self.alternative_option(arg) {
+                    self.short_options(&arg[1..]);
+                }
+            } else {
+                match self.spec.ordering {
+                    Ordering::Permute => self.parsed.operands.push(arg.clone()),
+                    Ordering::ReturnInOrder => self.parsed.options.push(Word::Arg(arg.clone())),
+                    Ordering::RequireOrder => {
+                        self.next -= 1;
+                        break;
+                    }
+                }
+            }
+        }
+
+        let rest = &args[self.next.min(args.len())..];
+        self.parsed.operands.extend(rest.iter().cloned());
+    }
+
+    // with --alternative, `-name` is a long option unless it can only be a short one.  Returns
+    // whether `arg` was handled
+    fn alternative_option(&mut self, arg: &[u8]) -> bool {
+        let name = &arg[1..];
+        let short = self.spec.find_short(name[0]);
+        if name.len() == 1 && short.is_some() {
+            return false;
+        }
+        let end = name.iter().position(|&b| b == b'=').unwrap_or(name.len());
+        match self.spec.find_long(&name[..end]) {
+            LongMatch::None if short.is_some() => false,
+            _ => {
+                self.long_option(name, "-");
+                true
+            }
+        }
+    }
+
+    fn long_option(&mut self, text: &[u8], prefix: &str) {
+        let (name, value) = match text.iter().position(|&b| b == b'=') {
+            Some(pos) => (&text[..pos], Some(&text[pos + 1..])),
+            None => (text, None),
+        };
+
+        let index = match self.spec.find_long(name) {
+            LongMatch::Found(index) => index,
+            LongMatch::Ambiguous(indices) => {
+                let mut msg = format!(
+                    "option '{}{}' is ambiguous; possibilities:",
+                    prefix,
+                    String::from_utf8_lossy(text)
+                );
+                for index in indices {
+                    msg.push_str(&format!(" '{}{}'", prefix, self.long_name(index)));
+                }
+                self.error(msg);
+                return;
+            }
+            LongMatch::None => {
+                let text = String::from_utf8_lossy(text);
+                let msg = format!("unrecognized option '{}{}'", prefix, text);
+                self.error(msg);
+                return;
+            }
+        };
+
+        let spec = self.spec;
+        let (ref long, has_arg) = spec.long[index];
+        let arg = match (has_arg, value) {
+            (HasArg::No, None) => None,
+            (HasArg::No, Some(_)) => {
+                let name = self.long_name(index);
+                let msg = format!("option '{}{}' doesn't allow an argument", prefix, name);
+                self.error(msg);
+                return;
+            }
+            (HasArg::Optional, value) => Some(value.unwrap_or(&b""[..])),
+            (HasArg::Required, Some(value)) => Some(value),
+            (HasArg::Required, None) => match self.take_next() {
+                Some(value) => Some(value),
+                None => {
+                    let name = self.long_name(index);
+                    let msg = format!("option '{}{}' requires an argument", prefix, name);
+                    self.error(msg);
+                    return;
+                }
+            },
+        };
+
+        let mut option = b"--".to_vec();
+        option.extend_from_slice(long);
+        self.parsed.options.push(Word::Option(option));
+        if let Some(arg) = arg {
+            self.parsed.options.push(Word::Arg(arg.to_vec()));
+        }
+    }
+
+    fn short_options(&mut self, chars: &[u8]) {
+        for (i, &c) in chars.iter().enumerate() {
+            let has_arg = match self.spec.find_short(c) {
+                Some(has_arg) => has_arg,
+                None => {
+                    let msg = format!("invalid option -- '{}'", c as char);
+                    self.error(msg);
+                    continue;
+                }
+            };
+
+            // an argument is the rest of the parameter (or the next parameter if it is required)
+            let rest = &chars[i + 1..];
+            let arg = match has_arg {
+                HasArg::No => None,
+                HasArg::Optional => Some(rest),
+                HasArg::Required if !rest.is_empty() => Some(rest),
+                HasArg::Required => match self.take_next() {
+                    Some(value) => Some(value),
+                    None => {
+                        let msg = format!("option requires an argument -- '{}'", c as char);
+                        self.error(msg);
+                        return;
+                    }
+                },
+            };
+
+            self.parsed.options.push(Word::Option(vec![b'-', c]));
+            if let Some(arg) = arg {
+                self.parsed.options.push(Word::Arg(arg.to_vec()));
+                return;
+            }
+        }
+    }
+
+    fn take_next(&mut self) -> Option<&'a [u8]> {
+        let args = self.args;
+        let value = args.get(self.next).map(|arg| &arg[..]);
+        if value.is_some() {
+            self.next += 1;
+        }
+        value
+    }
+
+    fn long_name(&self, index: usize) -> String {
+        String::from_utf8_lossy(&self.spec.long[index].0).into_owned()
+    }
+
+    fn error(&mut self, msg: String) {
+        self.parsed.failed = true;
+        if self.spec.print_errors {
+            self.parsed.errors.push(format!("{}: {}", self.name, msg));
+        }
+    }
+}
+
+#[cfg(test)]
+fn parse_strs(spec: &Spec, args: &[&str]) -> (Vec<String>, Vec<String>, Vec<String>) {
+    let args: Vec<Vec<u8>> = args.iter().map(|arg| arg.as_bytes().to_vec()).collect();
+    let parsed = parse(spec, &args, "getopt");
+    let options = parsed
+        .options
+        .iter()
+        .map(|word| match *word {
+            Word::Option(ref option) => String::from_utf8_lossy(option).into_owned(),
+            Word::Arg(ref arg) => format!("[{}]", String::from_utf8_lossy(arg)),
+        })
+        .collect();
+    let operands = parsed
+        .operands
+        .iter()
+        .map(|operand| String::from_utf8_lossy(operand).into_owned())
+        .collect();
+    (options, operands, parsed.errors)
+}
+
+#[test]
+fn getopt_parse_options() {
+    let mut spec = Spec::new(b"ab:c::", false);
+    spec.add_long(b"foo,bar: baz::");
+
+    let args = [
+        "-a", "x", "-bB", "-cC", "-c", "-b", "-a", "--foo", "--bar=1", "--bar", "2", "--baz",
+        "--baz=3", "y", "--", "-z",
+    ];
+    let (options, operands, errors) = parse_strs(&spec, &args);
+    let expected = [
+        "-a", "-b", "[B]", "-c", "[C]", "-c", "[]", "-b", "[-a]", "--foo", "--bar", "[1]", "--bar",
+        "[2]", "--baz", "[]", "--baz", "[3]",
+    ];
+    assert_eq!(options, expected);
+    assert_eq!(operands, ["x", "y", "-z"]);
+    assert!(errors.is_empty());
+
+    // operands stop the options with a leading + and stay in place with a leading -
+    let (options, operands, _) = parse_strs(&Spec::new(b"+a", false), &["-a", "x", "-a"]);
+    assert_eq!((options, operands), (vec!["-a".to_owned()], vec!["x".to_owned(), "-a".to_owned()]));
+    let (options, operands, _) = parse_strs(&Spec::new(b"a", true), &["-a", "x", "-a"]);
+    assert_eq!((options, operands), (vec!["-a".to_owned()], vec!["x".to_owned(), "-a".to_owned()]));
+    let (options, operands, _) = parse_strs(&Spec::new(b"-a", false), &["-a", "x", "-a", "-"]);
+    assert_eq!(options, ["-a", "[x]", "-a", "[-]"]);
+    assert!(operands.is_empty());
+}
+
+#[test]
+fn getopt_parse_alternative() {
+    let mut spec = Spec::new(b"a", false);
+    spec.add_long(b"foo,all");
+    spec.set_alternative(true);
+
+    let (options, _, errors) = parse_strs(&spec, &["-foo", "-a", "-fo", "-aa", "-al", "-x"]);
+    assert_eq!(options, ["--foo", "-a", "--foo", "-a", "-a", "--all"]);
+    assert_eq!(errors, ["getopt: unrecognized option '-x'"]);
+}
+
+#[test]
+fn getopt_parse_errors() {
+    let mut spec = Spec::new(b"ab:", false);
+    spec.add_long(b"foo,fob,bar:");
+
+    let args = ["-xa", "--fo", "--foo=1", "--zz=1", "--foo", "-b"];
+    let (options, _, errors) = parse_strs(&spec, &args);
+    assert_eq!(options, ["-a", "--foo"]);
+    assert_eq!(
+        errors,
+        [
+            "getopt: invalid option -- 'x'",
+            "getopt: option '--fo' is ambiguous; possibilities: '--foo' '--fob'",
+            "getopt: option '--foo' doesn't allow an argument",
+            "getopt: unrecognized option '--zz=1'",
+            "getopt: option requires an argument -- 'b'",
+        ]
+    );
+
+    let (_, _, errors) = parse_strs(&spec, &["--bar"]);
+    assert_eq!(errors, ["getopt: option '--bar' requires an argument"]);
+}
@@ -0,0 +1,363 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use util::dump::{self, DumpOptions};
+use util;
+use {ArgsIter, MesaError, Result, UtilRead, UtilSetup, UtilWrite};
+
+use clap::Arg;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Read, Write};
+use std::result::Result as StdResult;
+
+pub(crate) const NAME: &str = "hexdump";
+pub(crate) const DESCRIPTION: &str = "Display the contents of files in hexadecimal, decimal, or ASCII";
+
+const AFTER_HELP: &str = "
+Without any display options, the input is shown as two-byte hexadecimal units.  If several display
+options are given, each line of input is shown in every format in the order the options appear.
+
+OFFSET and LENGTH are decimal by default, hexadecimal with a leading 0x, or octal with a leading 0.
+They may be followed by b (512), k (1024), or m (1048576).
+";
+
+const LINE_LEN: usize = 16;
+
+// the output formats, which match those of BSD hexdump
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Format {
+    // two-byte units in hexadecimal without any padding
+    Default,
+    // -C: hexadecimal bytes followed by the bytes as ASCII
+    Canonical,
+    // -c: single characters
+    Char,
+    // -d: two-byte units in decimal
+    Decimal,
+    // -x: two-byte units in hexadecimal
+    Hex,
+}
+
+const FORMAT_FLAGS: [(&str, Format); 4] = [
+    ("canonical", Format::Canonical),
+    ("char", Format::Char),
+    ("decimal", Format::Decimal),
+    ("hex", Format::Hex),
+];
+
+impl Format {
+    fn offset_width(self) -> usize {
+        match self {
+            Format::Canonical => 8,
+            _ => 7,
+        }
+    }
+
+    fn write_line<W: Write + ?Sized>(self, output: &mut W, offset: u64, data: &[u8]) -> io::Result<()> {
+        if self == Format::Canonical {
+            return write_canonical(output, offset, data);
+        }
+
+        // like BSD hexdump, every unit is followed by a space (even the last one)
+        let mut line = dump::format_offset(offset, 16, self.offset_width());
+        line.push(' ');
+        match self {
+            Format::Char => {
+                for i in 0..LINE_LEN {
+                    match data.get(i) {
+                        Some(&byte) => line.push_str(&format_char(byte)),
+                        None => line.push_str("   "),
+                    }
+                    line.push(' ');
+                }
+            }
+            _ => {
+                for i in 0..LINE_LEN / 2 {
+                    let unit = match data.get(i * 2..) {
+                        Some(rest) if !rest.is_empty() => Some(two_byte_unit(rest)),
+                        _ => None,
+                    };
+                    line.push_str(&match (self, unit) {
+                        (Format::Default, Some(unit)) => format!("{:04x}", unit),
+                        (Format::Default, None) => " ".repeat(4),
+                        (Format::Decimal, Some(unit)) => format!("  {:05}", unit),
+                        (Format::Hex, Some(unit)) => format!("   {:04x}", unit),
+                        (_, _) => " ".repeat(7),
+                    });
+                    line.push(' ');
+                }
+            }
+        }
+        line.push('\n');
+        output.write_all(line.as_bytes())
+    }
+}
+
+pub fn execute<S, T>(setup: &mut S, args: T) -> Result<()>
+where
+    S: UtilSetup,
+    T: ArgsIter,
+{
+    let matches = {
+        let app = util_app!(NAME)
+                    .after_help(AFTER_HELP)
+                    .arg(Arg::with_name("canonical")
+                            .short("C")
+                            .multiple(true)
+                            .help("Show each byte in hexadecimal followed by the bytes as ASCII"))
+                    .arg(Arg::with_name("char")
+                            .short("c")
+                            .multiple(true)
+                            .help("Show each byte as a character (or as octal if it cannot be printed)"))
+                    .arg(Arg::with_name("decimal")
+                            .short("d")
+                            .multiple(true)
+                            .help("Show two-byte units in decimal"))
+                    .arg(Arg::with_name("hex")
+                            .short("x")
+                            .multiple(true)
+                            .help("Show two-byte units in hexadecimal"))
+                    .arg(Arg::with_name("length")
+                            .short("n")
+                            .takes_value(true)
+                            .value_name("LENGTH")
+                            .validator_os(is_valid_num)
+                            .help("Show only LENGTH bytes of input"))
+                    .arg(Arg::with_name("skip")
+                            .short("s")
+                            .takes_value(true)
+                            .value_name("OFFSET")
+                            .validator_os(is_valid_num)
+                            .help("Skip OFFSET bytes from the start of the input"))
+                    .arg(Arg::with_name("verbose")
+                            .short("v")
+                            .help("Show every line, rather than replacing repeated lines with '*'"))
+                    .arg(Arg::with_name("FILES")
+                            .index(1)
+                            .multiple(true));
+
+        app.get_matches_from_safe(args)?
+    };
+
+    // the .unwrap()s are fine because of the validators above
+    let options = DumpOptions {
+        line_len: LINE_LEN,
+        skip: matches.value_of("skip").map(|s| parse_num(s).unwrap()).unwrap_or(0),
+        limit: matches.value_of("length").map(|s| parse_num(s).unwrap()),
+        squeeze: !matches.is_present("verbose"),
+    };
+
+    let mut formats: Vec<(usize, Format)> = FORMAT_FLAGS
+        .iter()
+        .filter_map(|&(name, format)| matches.indices_of(name).map(|indices| (indices, format)))
+        .flat_map(|(indices, format)| indices.map(move |index| (index, format)))
+        .collect();
+    formats.sort_by_key(|&(index, _)| index);
+    let mut formats: Vec<Format> = formats.into_iter().map(|(_, format)| format).collect();
+    formats.dedup();
+    if formats.is_empty() {
+        formats.push(Format::Default);
+    }
+
+    let current_dir = setup.current_dir().map(|p| p.to_owned());
+    let (input, output, error) = setup.stdio();
+    let mut output = output.lock()?;
+    let mut err_stream = error.lock()?;
+
+    let mut result = Ok(());
+    // all the files are dumped as if they were one long input
+    let mut readers: Vec<Box<Read>> = vec![];
+    let mut stdin = None;
+    match matches.values_of_os("FILES") {
+        Some(files) => {
+            for file in files {
+                if file == OsStr::new("-") {
+                    if stdin.is_none() {
+                        stdin = Some(input.lock()?);
+                    }
+                    continue;
+                }
+                match util::open_file(&current_dir, file) {
+                    Ok(file) => readers.push(Box::new(file)),
+                    Err(mut e) => {
+                        display_msg!(err_stream, "{}", e)?;
+                        e.err = None;
+                        result = Err(e);
+                    }
+                }
+            }
+        }
+        None => stdin = Some(input.lock()?),
+    }
+
+    let mut data: Box<Read> = Box::new(io::empty());
+    for reader in readers {
+        data = Box::new(data.chain(reader));
+    }
+    let end = match stdin {
+        Some(stdin) => dump_data(&mut output, &mut data.chain(stdin), &options, &formats)?,
+        None => dump_data(&mut output, &mut data, &options, &formats)?,
+    };
+    if end > options.skip {
+        writeln!(output, "{}", dump::format_offset(end, 16, formats[0].offset_width()))?;
+    }
+    output.flush()?;
+
+    result.map_err(|e: MesaError| e)
+}
+
+fn dump_data<W, R>(output: &mut W, input: &mut R, options: &DumpOptions, formats: &[Format]) -> Result<u64>
+where
+    W: Write,
+    R: Read,
+{
+    let end = dump::dump(input, output, options, |output, offset, data| {
+        for format in formats {
+            format.write_line(output, offset, data)?;
+        }
+        Ok(())
+    })?;
+    Ok(end)
+}
+
+fn write_canonical<W: Write + ?Sized>(output: &mut W, offset: u64, data: &[u8]) -> io::Result<()> {
+    let mut line = dump::format_offset(offset, 16, 8);
+    line.push_str("  ");
+    for i in 0..LINE_LEN {
+        if i == LINE_LEN / 2 {
+            line.push(' ');
+        }
+        match data.get(i) {
+            Some(byte) => line.push_str(&format!("{:02x} ", byte)),
+            None => line.push_str("   "),
+        }
+    }
+    line.push_str(" |");
+    line.extend(data.iter().map(|&byte| {
+        if byte >= 0x20 && byte < 0x7f {
+            byte as char
+        } else {
+            '.'
+        }
+    }));
+    line.push_str("|\n");
+    output.write_all(line.as_bytes())
+}
+
+fn format_char(byte: u8) -> String {
+    match byte {
+        0 => " \\0".to_owned(),
+        7 => " \\a".to_owned(),
+        8 => " \\b".to_owned(),
+        b'\t' => " \\t".to_owned(),
+        b'\n' => " \\n".to_owned(),
+        11 => " \\v".to_owned(),
+        12 => " \\f".to_owned(),
+        b'\r' => " \\r".to_owned(),
+        0x20...0x7e => format!("  {}", byte as char),
+        _ => format!("{:03o}", byte),
+    }
+}
+
+// a two-byte unit in the byte order of the machine (a missing second byte counts as zero)
+fn two_byte_unit(data: &[u8]) -> u16 {
+    let first = u16::from(data[0]);
+    let second = u16::from(if data.len() > 1 { data[1] } else { 0 });
+    if cfg!(target_endian = "big") {
+        first << 8 | second
+    } else {
+        second << 8 | first
+    }
+}
+
+fn parse_num(s: &str) -> Option<u64> {
+    let (radix, digits) = if s.starts_with("0x") || s.starts_with("0X") {
+        (16, &s[2..])
+    } else if s.starts_with('0') && s.len() > 1 {
+        (8, &s[1..])
+    } else {
+        (10, s)
+    };
+
+    let len = digits.chars().take_while(|c| c.is_digit(radix)).count();
+    if len == 0 {
+        return None;
+    }
+    let multiplier = match &digits[len..] {
+        "" => 1,
+        "b" => 512,
+        "k" => 1024,
+        "m" => 1024 * 1024,
+        _ => return None,
+    };
+    u64::from_str_radix(&digits[..len], radix)
+        .ok()?
+        .checked_mul(multiplier)
+}
+
+fn is_valid_num(val: &OsStr) -> StdResult<(), OsString> {
+    match val.to_str().and_then(parse_num) {
+        Some(_) => Ok(()),
+        None => Err(OsString::from(format!(
+            "'{}' is not a number or is too large",
+            val.to_string_lossy()
+        ))),
+    }
+}
+
+#[cfg(test)]
+fn line(format: Format, data: &[u8]) -> String {
+    let mut output = vec![];
+    format.write_line(&mut output, 16, data).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn canonical_lines() {
+    assert_eq!(
+        line(Format::Canonical, b"hello world\n"),
+        "00000010  68 65 6c 6c 6f 20 77 6f  72 6c 64 0a              |hello world.|\n"
+    );
+    assert_eq!(
+        line(Format::Canonical, b"\x00\x01abcdefghijklm\xff"),
+        "00000010  00 01 61 62 63 64 65 66  67 68 69 6a 6b 6c 6d ff  |..abcdefghijklm.|\n"
+    );
+}
+
+#[test]
+fn unit_lines() {
+    assert_eq!(
+        line(Format::Default, b"hello"),
+        "0000010 6568 6c6c 006f                          \n"
+    );
+    assert_eq!(
+        line(Format::Hex, b"AB"),
+        "0000010    4241                                                         \n"
+    );
+    assert_eq!(
+        line(Format::Decimal, b"\x01\x01"),
+        "0000010   00257                                                         \n"
+    );
+    assert_eq!(
+        line(Format::Char, b"a\n\x01"),
+        "0000010   a  \\n 001                                                     \n"
+    );
+}
+
+#[test]
+fn offsets_and_lengths() {
+    assert_eq!(parse_num("10"), Some(10));
+    assert_eq!(parse_num("0x1b"), Some(27));
+    assert_eq!(parse_num("010"), Some(8));
+    assert_eq!(parse_num("0"), Some(0));
+    assert_eq!(parse_num("2k"), Some(2048));
+    assert_eq!(parse_num("1b"), Some(512));
+    assert_eq!(parse_num("x"), None);
+    assert_eq!(parse_num("10q"), None);
+    assert_eq!(parse_num(""), None);
+}
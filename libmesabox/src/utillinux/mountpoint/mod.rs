@@ -0,0 +1,215 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use util;
+use {ArgsIter, MesaError, PathError, PathOperation, Result, UtilSetup, UtilWrite, EXIT_FAILURE};
+
+use clap::Arg;
+use std::ffi::OsString;
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::ffi::OsStringExt;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::{Path, PathBuf};
+
+pub(crate) const NAME: &str = "mountpoint";
+pub(crate) const DESCRIPTION: &str = "Check whether a directory or file is a mount point";
+
+const AFTER_HELP: &str = "
+The mount points are read from /proc/self/mountinfo.  If that is unavailable (e.g. in a chroot
+without /proc), PATH is considered a mount point if it is on a different device than its parent
+directory, which misses bind mounts of directories on the same filesystem.
+
+The exit status is 0 if PATH is a mount point and 1 if it is not (or if an error occurred).
+";
+
+const MOUNTINFO: &str = "/proc/self/mountinfo";
+
+#[derive(Debug, Fail)]
+enum MountpointError {
+    #[fail(display = "{}: not a block device", _0)]
+    NotBlockDevice(String),
+}
+
+/// One entry in the mount table.
+#[derive(Debug, Eq, PartialEq)]
+struct Mount {
+    mount_point: PathBuf,
+    // the major and minor device numbers of the mounted filesystem
+    device: (u64, u64),
+}
+
+pub fn execute<S, T>(setup: &mut S, args: T) -> Result<()>
+where
+    S: UtilSetup,
+    T: ArgsIter,
+{
+    let matches = {
+        let app = util_app!(NAME)
+                    .after_help(AFTER_HELP)
+                    .arg(Arg::with_name("quiet")
+                            .short("q")
+                            .long("quiet")
+                            .help("Print nothing, only set the exit status"))
+                    .arg(Arg::with_name("fs-devno")
+                            .short("d")
+                            .long("fs-devno")
+                            .help("Print the device number (as MAJOR:MINOR) of the filesystem mounted at PATH"))
+                    .arg(Arg::with_name("devno")
+                            .short("x")
+                            .long("devno")
+                            .conflicts_with("fs-devno")
+                            .help("Print the device number (as MAJOR:MINOR) of the block device PATH"))
+                    .arg(Arg::with_name("PATH")
+                            .index(1)
+                            .required(true));
+
+        app.get_matches_from_safe(args)?
+    };
+
+    let quiet = matches.is_present("quiet");
+    // this .unwrap() is fine as PATH is required
+    let name = matches.value_of_os("PATH").unwrap();
+    let display_name = name.to_string_lossy();
+
+    let current_dir = setup.current_dir().map(|p| p.to_owned());
+    let path = util::actual_path(&current_dir, name);
+    let metadata = fs::metadata(&path).map_err(|e| PathError::new(PathOperation::Stat, name, e))?;
+
+    let output = setup.output();
+    let mut output = output.lock()?;
+
+    if matches.is_present("devno") {
+        if !metadata.file_type().is_block_device() {
+            return Err(MountpointError::NotBlockDevice(display_name.into_owned()).into());
+        }
+        let (major, minor) = util::device_numbers(metadata.rdev());
+        writeln!(output, "{}:{}", major, minor)?;
+        return Ok(());
+    }
+
+    let mount = find_mount(&path, &metadata);
+    match mount.map_err(|e| PathError::new(PathOperation::Stat, name, e))? {
+        Some((major, minor)) if matches.is_present("fs-devno") => {
+            writeln!(output, "{}:{}", major, minor)?;
+        }
+        Some(_) => {
+            if !quiet {
+                writeln!(output, "{} is a mountpoint", display_name)?;
+            }
+        }
+        None => {
+            if !quiet {
+                writeln!(output, "{} is not a mountpoint", display_name)?;
+            }
+            return Err(MesaError::new(None, EXIT_FAILURE, None));
+        }
+    }
+
+    Ok(())
+}
+
+// returns the device numbers of the filesystem mounted at `path`, or None if it isn't a mount point
+fn find_mount(path: &Path, metadata: &Metadata) -> io::Result<Option<(u64, u64)>> {
+    let path = fs::canonicalize(path)?;
+
+    if let Ok(mounts) = read_mountinfo() {
+        // a later mount at the same place hides the earlier ones
+        return Ok(mounts
+            .into_iter()
+            .rev()
+            .find(|mount| mount.mount_point == path)
+            .map(|mount| mount.device));
+    }
+
+    // the parent of / is / itself, which is always a mount point
+    let parent = path.parent().unwrap_or(&path);
+    let parent_metadata = fs::metadata(parent)?;
+    if metadata.dev() != parent_metadata.dev() || metadata.ino() == parent_metadata.ino() {
+        Ok(Some(util::device_numbers(metadata.dev())))
+    } else {
+        Ok(None)
+    }
+}
+
+fn read_mountinfo() -> io::Result<Vec<Mount>> {
+    let mut input = BufReader::new(File::open(MOUNTINFO)?);
+    let mut mounts = vec![];
+    let mut line = vec![];
+    loop {
+        line.clear();
+        if input.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        if let Some(mount) = parse_mountinfo_line(&line) {
+            mounts.push(mount);
+        }
+    }
+    Ok(mounts)
+}
+
+// lines look like "36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw", where the
+// fifth field is the mount point and the third is the device number
+fn parse_mountinfo_line(line: &[u8]) -> Option<Mount> {
+    let mut fields = line.split(|&b| b == b' ' || b == b'\n');
+    let device = fields.nth(2)?;
+    let mount_point = fields.nth(1)?;
+
+    let device = String::from_utf8_lossy(device);
+    let mut numbers = device.splitn(2, ':').map(|num| num.parse::<u64>().ok());
+    let major = numbers.next()??;
+    let minor = numbers.next()??;
+
+    Some(Mount {
+        mount_point: PathBuf::from(OsString::from_vec(unescape(mount_point))),
+        device: (major, minor),
+    })
+}
+
+// the kernel writes spaces, tabs, newlines and backslashes in paths as octal escapes like \040
+fn unescape(field: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(field.len());
+    let mut i = 0;
+    while i < field.len() {
+        let escape = field.get(i + 1..i + 4).and_then(|digits| {
+            if field[i] == b'\\' && digits.iter().all(|&d| d >= b'0' && d <= b'7') {
+                Some(digits.iter().fold(0u32, |val, &d| val * 8 + u32::from(d - b'0')))
+            } else {
+                None
+            }
+        });
+        match escape {
+            Some(byte) if byte <= 0xff => {
+                result.push(byte as u8);
+                i += 4;
+            }
+            _ => {
+                result.push(field[i]);
+                i += 1;
+            }
+        }
+    }
+    result
+}
+
+#[test]
+fn mountinfo_lines() {
+    let mount = parse_mountinfo_line(b"36 35 98:0 /mnt1 /mnt\\040two rw,noatime master:1 - ext3 /dev/root rw\n");
+    assert_eq!(
+        mount,
+        Some(Mount {
+            mount_point: PathBuf::from("/mnt two"),
+            device: (98, 0),
+        })
+    );
+    assert!(parse_mountinfo_line(b"36 35 98:0\n").is_none());
+    assert!(parse_mountinfo_line(b"").is_none());
+
+    assert_eq!(unescape(b"a\\134b\\011"), b"a\\b\t");
+    assert_eq!(unescape(b"\\99\\"), b"\\99\\");
+}
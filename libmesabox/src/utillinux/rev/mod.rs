@@ -0,0 +1,145 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use util::{self, utf8, LineDelimiter, LineFlusher};
+use {ArgsIter, MesaError, Result, UtilRead, UtilSetup, UtilWrite};
+
+use clap::Arg;
+use std::ffi::OsStr;
+use std::io::{BufRead, BufReader, Write};
+
+pub(crate) const NAME: &str = "rev";
+pub(crate) const DESCRIPTION: &str = "Reverse the characters of each line";
+
+pub fn execute<S, T>(setup: &mut S, args: T) -> Result<()>
+where
+    S: UtilSetup,
+    T: ArgsIter,
+{
+    let matches = {
+        let app = util_app!(NAME)
+                    .arg(util::zero_terminated_arg())
+                    .arg(util::line_buffered_arg())
+                    .arg(Arg::with_name("FILES")
+                            .index(1)
+                            .multiple(true));
+
+        app.get_matches_from_safe(args)?
+    };
+
+    let delimiter = LineDelimiter::from_matches(&matches);
+    let current_dir = setup.current_dir().map(|p| p.to_owned());
+    let (input, output, error) = setup.stdio();
+
+    let line_buffered = util::is_line_buffered(&matches, output.raw_object());
+    let mut output = LineFlusher::new(output.lock()?, delimiter, line_buffered);
+    let mut err_stream = error.lock()?;
+
+    // reading from stdin without any operands is handled exactly like an operand of "-"
+    let files: Vec<&OsStr> = match matches.values_of_os("FILES") {
+        Some(values) => values.collect(),
+        None => vec![OsStr::new("-")],
+    };
+
+    let mut result = Ok(());
+    for &file in &files {
+        let res = if file == OsStr::new("-") {
+            input
+                .lock()
+                .map_err(MesaError::from)
+                .and_then(|stdin| reverse_lines(&mut output, stdin, delimiter))
+        } else {
+            util::open_file(&current_dir, file)
+                .and_then(|file| reverse_lines(&mut output, BufReader::new(file), delimiter))
+        };
+
+        if let Err(mut e) = res {
+            display_msg!(err_stream, "{}", e)?;
+            e.err = None;
+            result = Err(e);
+        }
+    }
+
+    result
+}
+
+fn reverse_lines<W, R>(output: &mut W, mut input: R, delimiter: LineDelimiter) -> Result<()>
+where
+    W: Write,
+    R: BufRead,
+{
+    let mut line = vec![];
+    let mut reversed = vec![];
+    loop {
+        line.clear();
+        if delimiter.read_line(&mut input, &mut line)? == 0 {
+            break;
+        }
+
+        // the delimiter stays at the end of the line
+        let has_delim = line.last() == Some(&delimiter.byte());
+        let content = if has_delim {
+            &line[..line.len() - 1]
+        } else {
+            &line[..]
+        };
+
+        reversed.clear();
+        reverse(content, &mut reversed);
+        if has_delim {
+            reversed.push(delimiter.byte());
+        }
+        output.write_all(&reversed)?;
+    }
+    output.flush()?;
+    Ok(())
+}
+
+/// Append the characters of `data` to `result` in reverse order.  Bytes that are not part of a
+/// valid UTF-8 sequence are treated as characters of their own.
+fn reverse(data: &[u8], result: &mut Vec<u8>) {
+    // the (start, end) offsets of every character
+    let mut chars = vec![];
+    let mut pos = 0;
+    for chunk in utf8::chunks(data) {
+        for (i, ch) in chunk.valid.char_indices() {
+            chars.push((pos + i, pos + i + ch.len_utf8()));
+        }
+        pos += chunk.valid.len();
+        for i in pos..pos + chunk.invalid.len() {
+            chars.push((i, i + 1));
+        }
+        pos += chunk.invalid.len();
+    }
+
+    for &(start, end) in chars.iter().rev() {
+        result.extend_from_slice(&data[start..end]);
+    }
+}
+
+#[cfg(test)]
+fn reverse_vec(data: &[u8]) -> Vec<u8> {
+    let mut result = vec![];
+    reverse(data, &mut result);
+    result
+}
+
+#[test]
+fn reverse_utf8() {
+    assert_eq!(reverse_vec(b"abc"), b"cba");
+    assert_eq!(reverse_vec("añ€😀".as_bytes()), "😀€ña".as_bytes());
+    assert_eq!(reverse_vec(b""), b"");
+}
+
+#[test]
+fn reverse_invalid() {
+    assert_eq!(reverse_vec(b"a\xff\xfeb"), b"b\xfe\xffa");
+    // a truncated sequence is reversed byte by byte
+    assert_eq!(reverse_vec(b"x\xe2\x82"), b"\x82\xe2x");
+    assert_eq!(reverse_vec(b"a\xc3\xa9\xff"), b"\xff\xc3\xa9a");
+}
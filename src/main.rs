@@ -54,19 +54,10 @@ fn main() {
     let mut setup = UtilData::new(&mut input, &mut output, &mut error, env::vars_os(), None);
 
     let code = mesabox::execute(&mut setup, &mut env::args_os()).unwrap_or_else(|f| {
-        if let Some(ref err) = f.err {
-            let mut skip = false;
-            // XXX: should this be checked in lib.rs?  i feel like it might be useful if people can detect this, so it is being done this way atm
-            if let Some(e) = err.downcast_ref::<io::Error>() {
-                if e.kind() == io::ErrorKind::BrokenPipe {
-                    skip = true;
-                }
-            }
-
-            if !skip {
-                let _ = writeln!(setup.stderr, "{}", f);
-                let _ = setup.stderr.flush();
-            }
+        // broken pipes have already been handled according to setup.write_error
+        if f.err.is_some() {
+            let _ = writeln!(setup.stderr, "{}", f);
+            let _ = setup.stderr.flush();
         }
 
         f.exitcode
@@ -72,7 +72,7 @@ fn test_garbage() {
         .assert()
         .failure()
         .stdout("")
-        .stderr(predicate::str::contains("invalid length at 16").from_utf8());
+        .stderr(pred_str_contains!("base32: invalid input\n"));
 }
 
 #[test]
@@ -162,3 +162,40 @@ fn test_invalid_wrap() {
             .stderr(pred_str_contains!("'a' is not a number"));
     }
 }
+
+#[test]
+fn test_decode_split_input() {
+    // wrap the input at odd places and read it in small pieces, so that the groups of characters
+    // are split between reads in many different ways
+    let encoded = "JBSWY3DPFQQFO33SNRSCCSDFNRWG6LBAK5XXE3DEEFEGK3DMN4WCAV3POJWGIIKIMVWGY3ZMEBLW64TMMQQUQZLMNRXSYICXN5ZGYZBB".repeat(20);
+    let mut input = String::new();
+    for (i, c) in encoded.chars().enumerate() {
+        input.push(c);
+        if i % 7 == 6 {
+            input.push('\n');
+        } else if i % 11 == 10 {
+            input.push(' ');
+        }
+    }
+
+    new_cmd!()
+        .env("MESABOX_BUFSIZE", "512")
+        .arg("-d")
+        .with_stdin().buffer(input)
+        .assert()
+        .success()
+        .stdout("Hello, World!".repeat(100))
+        .stderr("");
+}
+
+#[test]
+fn test_decode_invalid_padding() {
+    for input in &["ME=====", "M=======", "MFR====="] {
+        new_cmd!()
+            .arg("-d")
+            .with_stdin().buffer(*input)
+            .assert()
+            .failure()
+            .stderr("base32: invalid input\n");
+    }
+}
@@ -71,8 +71,8 @@ fn test_garbage() {
         .with_stdin().buffer(input)
         .assert()
         .failure()
-        .stderr(predicate::str::contains("invalid length at 20").from_utf8())
-        .stdout("");
+        .stderr(pred_str_contains!("base64: invalid input\n"))
+        .stdout("hello, world!");
 }
 
 #[test]
@@ -126,3 +126,40 @@ fn test_wrap_bad_arg() {
             .stderr(pred_str_contains!("'b' is not a number\n"));
     }
 }
+
+#[test]
+fn test_decode_split_input() {
+    // wrap the input at odd places and read it in small pieces, so that the groups of characters
+    // are split between reads in many different ways
+    let encoded = "aGVsbG8sIHdvcmxkIWhlbGxvLCB3b3JsZCFoZWxsbywgd29ybGQh".repeat(33);
+    let mut input = String::new();
+    for (i, c) in encoded.chars().enumerate() {
+        input.push(c);
+        if i % 7 == 6 {
+            input.push('\n');
+        } else if i % 11 == 10 {
+            input.push(' ');
+        }
+    }
+
+    new_cmd!()
+        .env("MESABOX_BUFSIZE", "512")
+        .arg("-d")
+        .with_stdin().buffer(input)
+        .assert()
+        .success()
+        .stdout("hello, world!".repeat(99))
+        .stderr("");
+}
+
+#[test]
+fn test_decode_invalid_padding() {
+    for input in &["YQ", "YQ=", "=YQ=", "YQ=a"] {
+        new_cmd!()
+            .arg("-d")
+            .with_stdin().buffer(*input)
+            .assert()
+            .failure()
+            .stderr("base64: invalid input\n");
+    }
+}
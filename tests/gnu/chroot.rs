@@ -0,0 +1,98 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use assert_cmd::prelude::*;
+use assert_fs;
+use libc;
+use predicates::prelude::*;
+use std::process::Command;
+
+const NAME: &str = "chroot";
+
+#[test]
+fn test_missing_newroot() {
+    new_cmd!()
+        .assert()
+        .failure()
+        .stdout("");
+}
+
+#[test]
+fn test_nonexistent_newroot() {
+    new_cmd!()
+        .args(&["/nonexistent/mesabox/root", "true"])
+        .assert()
+        .code(125)
+        .stdout("")
+        .stderr(pred_str_contains!("cannot change root directory to '/nonexistent/mesabox/root'"));
+}
+
+#[test]
+fn test_invalid_user() {
+    new_cmd!()
+        .args(&["--userspec=mesabox_no_such_user:", "/", "true"])
+        .assert()
+        .code(125)
+        .stdout("")
+        .stderr(pred_str_contains!("invalid user 'mesabox_no_such_user'"));
+}
+
+#[test]
+fn test_invalid_group_list() {
+    new_cmd!()
+        .args(&["--groups=0,,1", "/", "true"])
+        .assert()
+        .code(125)
+        .stdout("")
+        .stderr(pred_str_contains!("invalid group list '0,,1'"));
+}
+
+#[test]
+fn test_skip_chdir_not_old_root() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+
+    new_cmd!()
+        .arg("--skip-chdir")
+        .arg(temp_dir.path())
+        .arg("true")
+        .assert()
+        .code(125)
+        .stdout("")
+        .stderr(pred_str_contains!("option --skip-chdir only permitted if NEWROOT is old '/'"));
+}
+
+#[test]
+fn test_unknown_uid_without_group() {
+    // there is no login group to switch to, so the command must not run with our groups
+    new_cmd!()
+        .args(&["--userspec=3999999", "/", "true"])
+        .assert()
+        .code(125)
+        .stdout("")
+        .stderr(pred_str_contains!("no group specified for unknown uid: 3999999"));
+}
+
+#[test]
+fn test_numeric_uid_drops_groups() {
+    if unsafe { libc::geteuid() } != 0 {
+        return;
+    }
+
+    // uid 1 is normally bin or daemon, neither of which has root's groups
+    let output = new_cmd!()
+        .args(&["--userspec=1", "/", "id", "-G"])
+        .output()
+        .unwrap();
+    if output.status.success() {
+        let groups = String::from_utf8(output.stdout).unwrap();
+        assert!(groups.split_whitespace().all(|gid| gid != "0"), "groups: {}", groups);
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("no group specified for unknown uid: 1"), "{}", stderr);
+    }
+}
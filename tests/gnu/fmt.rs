@@ -0,0 +1,106 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use assert_cmd::prelude::*;
+use std::process::Command;
+
+const NAME: &str = "fmt";
+
+#[test]
+fn test_join_paragraphs() {
+    new_cmd!()
+        .with_stdin().buffer("one\ntwo three\n\n  four\n  five\nsix\n")
+        .assert()
+        .success()
+        .stdout("one two three\n\n  four five\nsix\n")
+        .stderr("");
+}
+
+#[test]
+fn test_width() {
+    new_cmd!()
+        .args(&["-w", "10"])
+        .with_stdin().buffer("aaa bbb ccc ddd eee\n")
+        .assert()
+        .success()
+        .stdout("aaa bbb\nccc ddd\neee\n")
+        .stderr("");
+}
+
+#[test]
+fn test_invalid_width() {
+    new_cmd!()
+        .args(&["-w", "0"])
+        .assert()
+        .failure()
+        .stdout("");
+}
+
+#[test]
+fn test_split_only() {
+    new_cmd!()
+        .args(&["-s", "-w", "10"])
+        .with_stdin().buffer("aaa bbb ccc\nddd\n")
+        .assert()
+        .success()
+        .stdout("aaa bbb\nccc\nddd\n")
+        .stderr("");
+}
+
+#[test]
+fn test_uniform_spacing() {
+    new_cmd!()
+        .arg("-u")
+        .with_stdin().buffer("one   two.  Three\nfour.\nfive\n")
+        .assert()
+        .success()
+        .stdout("one two.  Three four.  five\n")
+        .stderr("");
+}
+
+#[test]
+fn test_prefix() {
+    new_cmd!()
+        .args(&["-p", "#", "-w", "12"])
+        .with_stdin().buffer("# aaa bbb\n  # ccc\ncode\n# ddd eee fff\n")
+        .assert()
+        .success()
+        .stdout("# aaa bbb\n  # ccc\ncode\n# ddd eee\n# fff\n")
+        .stderr("");
+}
+
+#[test]
+fn test_crown_margin() {
+    new_cmd!()
+        .args(&["-c", "-w", "12"])
+        .with_stdin().buffer("  aaa bbb\nccc ddd eee\nfff\n")
+        .assert()
+        .success()
+        .stdout("  aaa bbb\nccc ddd eee\nfff\n")
+        .stderr("");
+}
+
+#[test]
+fn test_tagged_paragraph() {
+    new_cmd!()
+        .arg("-t")
+        .with_stdin().buffer("tag one\n  two\n  three\nnext\nline\n")
+        .assert()
+        .success()
+        .stdout("tag one two three\nnext\nline\n")
+        .stderr("");
+}
+
+#[test]
+fn test_missing_file() {
+    new_cmd!()
+        .arg("nonexistent")
+        .assert()
+        .failure()
+        .stdout("");
+}
@@ -0,0 +1,131 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use assert_cmd::prelude::*;
+use assert_fs;
+use predicates::prelude::*;
+use std::fs;
+use std::process::Command;
+
+const NAME: &str = "shred";
+
+#[test]
+fn test_zero_pass() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    let file = temp_dir.path().join("file");
+    fs::write(&file, "secret data\n").unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-n", "1", "-z", "-x", "file"])
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("");
+
+    assert_eq!(fs::read(&file).unwrap(), vec![0; 12]);
+}
+
+#[test]
+fn test_random_pass() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    let file = temp_dir.path().join("file");
+    fs::write(&file, vec![0; 4096]).unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-x", "file"])
+        .assert()
+        .success();
+
+    let data = fs::read(&file).unwrap();
+    assert_eq!(data.len(), 4096);
+    assert!(data.iter().any(|&b| b != 0));
+}
+
+#[test]
+fn test_round_up() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    let file = temp_dir.path().join("file");
+    fs::write(&file, "abc").unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-n", "0", "-z", "file"])
+        .assert()
+        .success();
+
+    // the rest of the last block is overwritten too
+    let data = fs::read(&file).unwrap();
+    assert!(data.len() >= 512 && data.len() % 512 == 0);
+    assert!(data.iter().all(|&b| b == 0));
+}
+
+#[test]
+fn test_size() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    let file = temp_dir.path().join("file");
+    fs::write(&file, "0123456789").unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-n", "0", "-z", "-s", "4", "file"])
+        .assert()
+        .success();
+
+    assert_eq!(fs::read(&file).unwrap(), b"\0\0\0\0456789");
+}
+
+#[test]
+fn test_remove_verbose() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("ab"), "secret").unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-v", "-u", "-n", "1", "-z", "ab"])
+        .assert()
+        .success()
+        .stdout("")
+        .stderr(
+            "shred: ab: pass 1/2 (random)...\n\
+             shred: ab: pass 2/2 (000000)...\n\
+             shred: ab: removing\n\
+             shred: ab: renamed to 00\n\
+             shred: 00: renamed to 0\n\
+             shred: ab: removed\n",
+        );
+
+    assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn test_missing_file() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("file"), "data").unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-u", "missing", "file"])
+        .assert()
+        .failure()
+        .stderr(pred_str_contains!("shred: cannot open 'missing' for writing: "));
+
+    // the other files are still shredded
+    assert!(!temp_dir.path().join("file").exists());
+}
+
+#[test]
+fn test_invalid_iterations() {
+    new_cmd!()
+        .args(&["-n", "x", "file"])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(pred_str_contains!("'x' is not a valid number of iterations"));
+}
@@ -5,3 +5,80 @@
 // This work is licensed under the terms of the BSD 3-Clause License.
 // For a copy, see the LICENSE file.
 //
+
+use assert_cmd::prelude::*;
+use golden::Golden;
+use std::io::Read;
+use std::process::{Command, Stdio};
+
+const NAME: &str = "yes";
+
+#[test]
+fn test_golden() {
+    Golden::new(NAME).stdout_limit(64 * 1024).assert_matches();
+    Golden::new(NAME)
+        .args(&["a", "bc", "def"])
+        .stdout_limit(64 * 1024)
+        .assert_matches();
+}
+
+#[test]
+fn test_broken_pipe() {
+    // by default, yes should stop quietly once nothing is reading its output
+    let mut child = new_cmd!()
+        .env_remove("MESABOX_OUTPUT_ERROR")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    drop(child.stdout.take());
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+
+    let mut child = new_cmd!()
+        .env("MESABOX_OUTPUT_ERROR", "warn")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    drop(child.stdout.take());
+    let output = child.wait_with_output().unwrap();
+    assert!(!output.status.success());
+    assert!(!output.stderr.is_empty());
+}
+
+#[test]
+fn test_end_of_options() {
+    let mut child = new_cmd!()
+        .args(&["--", "-n"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut buf = [0; 9];
+    child.stdout.take().unwrap().read_exact(&mut buf).unwrap();
+    let _ = child.wait();
+    assert_eq!(&buf, b"-n\n-n\n-n\n");
+}
+
+#[test]
+fn test_number() {
+    let mut child = new_cmd!()
+        .arg("--number")
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut buf = [0; 24];
+    child.stdout.take().unwrap().read_exact(&mut buf).unwrap();
+    let _ = child.wait();
+    assert_eq!(&buf[..], &b"1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n"[..]);
+}
+
+#[test]
+fn test_number_with_string() {
+    new_cmd!()
+        .args(&["--number", "a"])
+        .assert()
+        .failure()
+        .stdout("");
+}
@@ -0,0 +1,172 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+//! Helpers to compare the behavior of our utilities with that of the GNU utilities installed on
+//! the system.  If the reference utility cannot be found (or is not the GNU version), the
+//! comparison is skipped.
+
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+struct RunResult {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    code: Option<i32>,
+}
+
+/// A single invocation to run using both mesabox and the reference implementation.
+pub struct Golden {
+    name: &'static str,
+    args: Vec<OsString>,
+    stdin: Option<PathBuf>,
+    current_dir: Option<PathBuf>,
+    stdout_limit: Option<usize>,
+    compare_stderr: bool,
+}
+
+impl Golden {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name: name,
+            args: vec![],
+            stdin: None,
+            current_dir: None,
+            stdout_limit: None,
+            compare_stderr: true,
+        }
+    }
+
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        self.args.extend(args.into_iter().map(|arg| arg.into()));
+        self
+    }
+
+    pub fn stdin<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.stdin = Some(path.as_ref().to_owned());
+        self
+    }
+
+    pub fn current_dir<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.current_dir = Some(path.as_ref().to_owned());
+        self
+    }
+
+    /// Only compare the first `limit` bytes of stdout and then kill the process.  This is meant
+    /// for utilities that never exit on their own (such as `yes`), so stderr and the exit status
+    /// are not compared.
+    pub fn stdout_limit(mut self, limit: usize) -> Self {
+        self.stdout_limit = Some(limit);
+        self
+    }
+
+    /// Don't compare stderr (for cases where the wording of error messages is expected to differ).
+    pub fn ignore_stderr(mut self) -> Self {
+        self.compare_stderr = false;
+        self
+    }
+
+    pub fn assert_matches(self) {
+        if !reference_available(self.name) {
+            eprintln!("skipping comparison as GNU {} could not be found", self.name);
+            return;
+        }
+
+        let mut ours = Command::new(&*::BIN_PATH);
+        ours.arg(self.name);
+        let ours = self.run(ours);
+
+        let reference = self.run(Command::new(self.name));
+
+        assert_eq!(
+            String::from_utf8_lossy(&ours.stdout),
+            String::from_utf8_lossy(&reference.stdout),
+            "stdout differs for {} {:?}",
+            self.name,
+            self.args
+        );
+        if self.stdout_limit.is_none() {
+            if self.compare_stderr {
+                assert_eq!(
+                    String::from_utf8_lossy(&ours.stderr),
+                    String::from_utf8_lossy(&reference.stderr),
+                    "stderr differs for {} {:?}",
+                    self.name,
+                    self.args
+                );
+            }
+            assert_eq!(
+                ours.code, reference.code,
+                "exit status differs for {} {:?}",
+                self.name, self.args
+            );
+        }
+    }
+
+    fn run(&self, mut cmd: Command) -> RunResult {
+        cmd.args(&self.args)
+            // make sure both utilities use the same messages and formatting
+            .env("LC_ALL", "C")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        match self.stdin {
+            Some(ref path) => cmd.stdin(File::open(path).unwrap()),
+            None => cmd.stdin(Stdio::null()),
+        };
+        if let Some(ref dir) = self.current_dir {
+            cmd.current_dir(dir);
+        }
+
+        match self.stdout_limit {
+            Some(limit) => {
+                let mut child = cmd.spawn().unwrap();
+                let mut stdout = vec![];
+                child
+                    .stdout
+                    .take()
+                    .unwrap()
+                    .take(limit as u64)
+                    .read_to_end(&mut stdout)
+                    .unwrap();
+                let _ = child.kill();
+                let _ = child.wait();
+
+                RunResult {
+                    stdout: stdout,
+                    stderr: vec![],
+                    code: None,
+                }
+            }
+            None => {
+                let output = cmd.output().unwrap();
+                RunResult {
+                    stdout: output.stdout,
+                    stderr: output.stderr,
+                    code: output.status.code(),
+                }
+            }
+        }
+    }
+}
+
+fn reference_available(name: &str) -> bool {
+    Command::new(name)
+        .arg("--version")
+        .env("LC_ALL", "C")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).contains("GNU coreutils"))
+        .unwrap_or(false)
+}
@@ -0,0 +1,36 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+//! Runs every utility in the build with `--help`, which makes each of them construct its
+//! argument parser.  Mistakes in argument definitions (such as conflicting names) only show up
+//! once the parser is built, so this catches them even for utilities without tests of their own.
+
+use mesabox;
+use std::process::Command;
+
+// utilities that do not take options at all (as required by POSIX for `echo`, `true` and
+// `false`) or that should never be started by a test (`init`)
+const NO_HELP: &[&str] = &["echo", "false", "init", "true"];
+
+#[test]
+fn test_all_utils_help() {
+    for name in mesabox::util_names() {
+        if NO_HELP.contains(&name) {
+            continue;
+        }
+
+        let output = Command::new(&*::BIN_PATH).args(&[name, "--help"]).output().unwrap();
+        assert!(
+            output.status.success(),
+            "{} --help failed: {}",
+            name,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(!output.stdout.is_empty(), "{} --help printed nothing", name);
+    }
+}
@@ -0,0 +1,78 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use assert_cmd::prelude::*;
+use assert_fs;
+use predicates::prelude::*;
+use std::fs;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+const NAME: &str = "pv";
+
+#[test]
+fn test_pass_through() {
+    // standard error is not a terminal here, so the meter is not shown
+    new_cmd!()
+        .with_stdin().buffer("some data\n")
+        .assert()
+        .success()
+        .stdout("some data\n")
+        .stderr("");
+}
+
+#[test]
+fn test_files() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("a"), "one\n").unwrap();
+    fs::write(temp_dir.path().join("b"), "two\n").unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["a", "missing", "-", "b"])
+        .with_stdin().buffer("stdin\n")
+        .assert()
+        .failure()
+        .stdout("one\nstdin\ntwo\n")
+        .stderr(pred_str_contains!("missing"));
+}
+
+#[test]
+fn test_forced_meter() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("file"), "12345").unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-f", "-b", "-e", "file"])
+        .assert()
+        .success()
+        .stdout("12345")
+        .stderr(pred_str_contains!("\r5 B ETA 0:00:00\n"));
+}
+
+#[test]
+fn test_rate_limit() {
+    let start = Instant::now();
+    new_cmd!()
+        .args(&["-L", "1000"])
+        .with_stdin().buffer("x".repeat(500))
+        .assert()
+        .success()
+        .stdout(pred_str_contains!("xxxxx"));
+    assert!(start.elapsed() >= Duration::from_millis(400));
+}
+
+#[test]
+fn test_invalid_rate() {
+    new_cmd!()
+        .args(&["-L", "0"])
+        .assert()
+        .failure()
+        .stderr(pred_str_contains!("rate must be at least 1"));
+}
@@ -0,0 +1,60 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+
+const NAME: &str = "errno";
+
+#[test]
+fn test_lookup() {
+    new_cmd!()
+        .args(&["ENOENT", "eacces", "1"])
+        .assert()
+        .success()
+        .stdout("ENOENT 2 No such file or directory\nEACCES 13 Permission denied\nEPERM 1 Operation not permitted\n")
+        .stderr("");
+}
+
+#[test]
+fn test_unknown() {
+    new_cmd!()
+        .args(&["EBOGUS", "ENOENT"])
+        .assert()
+        .failure()
+        .stdout("ENOENT 2 No such file or directory\n")
+        .stderr("errno: unknown errno value or name 'EBOGUS'\n");
+}
+
+#[test]
+fn test_list() {
+    new_cmd!()
+        .arg("-l")
+        .assert()
+        .success()
+        .stdout(pred_str_contains!("EPERM 1 Operation not permitted\nENOENT 2 "))
+        .stderr("");
+}
+
+#[test]
+fn test_search() {
+    new_cmd!()
+        .args(&["-s", "SUCH", "file"])
+        .assert()
+        .success()
+        .stdout("ENOENT 2 No such file or directory\n")
+        .stderr("");
+
+    new_cmd!()
+        .args(&["-s", "nothing matches this"])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr("");
+}
@@ -0,0 +1,158 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use assert_cmd::prelude::*;
+use assert_fs;
+use std::fs;
+use std::process::Command;
+
+const NAME: &str = "sponge";
+
+#[test]
+fn test_stdout() {
+    new_cmd!()
+        .with_stdin().buffer("some data\n")
+        .assert()
+        .success()
+        .stdout("some data\n")
+        .stderr("");
+}
+
+#[test]
+fn test_replace() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    let file = temp_dir.path().join("file");
+    fs::write(&file, "old contents\n").unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .arg("file")
+        .with_stdin().buffer("new\n")
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("");
+
+    assert_eq!(fs::read_to_string(&file).unwrap(), "new\n");
+    // nothing but the file itself should be left behind
+    assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_replace_keeps_permissions() {
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    let file = temp_dir.path().join("file");
+    fs::write(&file, "old\n").unwrap();
+    fs::set_permissions(&file, fs::Permissions::from_mode(0o640)).unwrap();
+    symlink("file", temp_dir.path().join("link")).unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .arg("link")
+        .with_stdin().buffer("new\n")
+        .assert()
+        .success()
+        .stderr("");
+
+    assert_eq!(fs::read_to_string(&file).unwrap(), "new\n");
+    let mode = fs::metadata(&file).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o640);
+    let link = fs::symlink_metadata(temp_dir.path().join("link")).unwrap();
+    assert!(link.file_type().is_symlink());
+}
+
+#[test]
+fn test_append() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    let file = temp_dir.path().join("file");
+    fs::write(&file, "old\n").unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-a", "file"])
+        .with_stdin().buffer("new\n")
+        .assert()
+        .success()
+        .stderr("");
+
+    assert_eq!(fs::read_to_string(&file).unwrap(), "old\nnew\n");
+}
+
+#[test]
+fn test_backup() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    let file = temp_dir.path().join("file");
+    fs::write(&file, "1\n").unwrap();
+
+    let cases: &[(&[&str], &str)] = &[
+        (&["-b", "file"], "2\n"),
+        (&["--backup=numbered", "file"], "3\n"),
+        (&["--backup", "file"], "4\n"),
+        (&["--backup=simple", "-S", ".old", "file"], "5\n"),
+    ];
+    for &(args, data) in cases {
+        new_cmd!()
+            .current_dir(temp_dir.path())
+            .env_remove("VERSION_CONTROL")
+            .env_remove("SIMPLE_BACKUP_SUFFIX")
+            .args(args)
+            .with_stdin().buffer(data)
+            .assert()
+            .success()
+            .stderr("");
+    }
+
+    let read = |name| fs::read_to_string(temp_dir.path().join(name)).unwrap();
+    assert_eq!(read("file"), "5\n");
+    assert_eq!(read("file~"), "1\n");
+    assert_eq!(read("file.~1~"), "2\n");
+    assert_eq!(read("file.~2~"), "3\n");
+    assert_eq!(read("file.old"), "4\n");
+}
+
+#[test]
+fn test_invalid_backup() {
+    new_cmd!()
+        .arg("--backup=bogus")
+        .arg("file")
+        .with_stdin().buffer("data\n")
+        .assert()
+        .failure()
+        .stderr("sponge: invalid backup type 'bogus'\n");
+}
+
+#[test]
+fn test_new_file() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .arg("file")
+        .with_stdin().buffer("data\n")
+        .assert()
+        .success()
+        .stderr("");
+
+    assert_eq!(fs::read_to_string(temp_dir.path().join("file")).unwrap(), "data\n");
+}
+
+#[test]
+fn test_missing_dir() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .arg("missing/file")
+        .with_stdin().buffer("data\n")
+        .assert()
+        .failure()
+        .stderr("sponge: cannot open 'missing/file' for writing: No such file or directory\n");
+}
@@ -0,0 +1,66 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+
+const NAME: &str = "ts";
+
+#[test]
+fn test_format() {
+    new_cmd!()
+        .arg("[%%]")
+        .with_stdin().buffer("one\ntwo\n")
+        .assert()
+        .success()
+        .stdout("[%] one\n[%] two\n")
+        .stderr("");
+}
+
+#[test]
+fn test_since_start() {
+    // the input is all there at once, so no time passes between the lines
+    for flag in &["-s", "-i"] {
+        new_cmd!()
+            .arg(flag)
+            .with_stdin().buffer("one\ntwo")
+            .assert()
+            .success()
+            .stdout("00:00:00 one\n00:00:00 two")
+            .stderr("");
+    }
+}
+
+#[test]
+fn test_convert() {
+    new_cmd!()
+        .args(&["-r", "%d/%m/%Y %H.%M"])
+        .with_stdin().buffer("2001-02-03 04:05:06 event\nplain line\n")
+        .assert()
+        .success()
+        .stdout("03/02/2001 04.05 event\nplain line\n")
+        .stderr("");
+
+    new_cmd!()
+        .arg("-r")
+        .with_stdin().buffer("2001-02-03T04:05:06 event\n")
+        .assert()
+        .success()
+        .stdout(pred_str_contains!(" ago event\n"))
+        .stderr("");
+}
+
+#[test]
+fn test_invalid_format() {
+    new_cmd!()
+        .arg("%Q")
+        .assert()
+        .failure()
+        .stderr(pred_str_contains!("'%Q' is not a valid format"));
+}
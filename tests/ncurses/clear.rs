@@ -0,0 +1,73 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use assert_cmd::prelude::*;
+use assert_fs;
+use std::fs;
+use std::process::Command;
+
+const NAME: &str = "clear";
+
+#[test]
+fn test_not_a_terminal() {
+    new_cmd!()
+        .env("TERM", "xterm")
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("");
+}
+
+#[test]
+fn test_force_unknown_terminal() {
+    new_cmd!()
+        .env("TERM", "no-such-terminal")
+        .arg("--force")
+        .assert()
+        .success()
+        .stdout("\x1b[H\x1b[2J\x1b[3J")
+        .stderr("");
+}
+
+#[test]
+fn test_keep_scrollback() {
+    new_cmd!()
+        .env("TERM", "no-such-terminal")
+        .args(&["-x", "-f"])
+        .assert()
+        .success()
+        .stdout("\x1b[H\x1b[2J")
+        .stderr("");
+}
+
+#[test]
+fn test_terminfo_entry() {
+    // an entry with only the clear capability (with padding), in the legacy format
+    let mut entry = vec![];
+    for &value in &[0o432i16, 5, 0, 0, 6, 7] {
+        entry.extend_from_slice(&[value as u8, (value >> 8) as u8]);
+    }
+    entry.extend_from_slice(b"test\0\0");
+    for &value in &[-1i16, -1, -1, -1, -1, 0] {
+        entry.extend_from_slice(&[value as u8, (value >> 8) as u8]);
+    }
+    entry.extend_from_slice(b"\x1bc$<5>\0");
+
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    fs::create_dir(temp_dir.path().join("t")).unwrap();
+    fs::write(temp_dir.path().join("t").join("test"), &entry).unwrap();
+
+    new_cmd!()
+        .env("TERMINFO", temp_dir.path())
+        .env("TERM", "no-such-terminal")
+        .args(&["-T", "test", "-f"])
+        .assert()
+        .success()
+        .stdout("\x1bc")
+        .stderr("");
+}
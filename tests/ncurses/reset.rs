@@ -0,0 +1,25 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+
+const NAME: &str = "reset";
+
+// none of the standard streams are terminals when the tests are run
+#[test]
+fn test_not_a_terminal() {
+    new_cmd!()
+        .env("TERM", "xterm")
+        .with_stdin().buffer("")
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(pred_str_contains!("reset: 'standard error': "));
+}
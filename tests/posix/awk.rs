@@ -0,0 +1,197 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use assert_cmd::prelude::*;
+use assert_fs;
+use std::fs;
+use std::process::Command;
+
+const NAME: &str = "awk";
+
+const INPUT: &str = "alice 30 paris\nbob 25 rome\ncarol 35 oslo\n";
+
+#[test]
+fn test_fields() {
+    new_cmd!()
+        .arg("{ print $3, $1 }")
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("paris alice\nrome bob\noslo carol\n")
+        .stderr("");
+
+    new_cmd!()
+        .args(&["-F", ":", "{ print NR \": \" $NF \" (\" NF \")\" }"])
+        .with_stdin().buffer("a:b:c\nd\n")
+        .assert()
+        .success()
+        .stdout("1: c (3)\n2: d (1)\n");
+
+    new_cmd!()
+        .args(&["-F", "t", "{ print $2 }"])
+        .with_stdin().buffer("a b\tc d\n")
+        .assert()
+        .success()
+        .stdout("c d\n");
+}
+
+#[test]
+fn test_begin_end() {
+    new_cmd!()
+        .arg("BEGIN { print \"start\" } { sum += $2 } END { print sum, NR, sum / NR }")
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("start\n90 3 30\n");
+
+    // no input is read if there is only a BEGIN action
+    new_cmd!()
+        .args(&["BEGIN { x = 1 + 2; print x }", "missing"])
+        .assert()
+        .success()
+        .stdout("3\n");
+}
+
+#[test]
+fn test_patterns() {
+    new_cmd!()
+        .arg("$2 > 26")
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("alice 30 paris\ncarol 35 oslo\n");
+
+    new_cmd!()
+        .arg("/^b/,/^c/ { print $1 }")
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("bob\ncarol\n");
+
+    new_cmd!()
+        .arg("$3 ~ /o/ && !($1 == \"bob\")")
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("carol 35 oslo\n");
+}
+
+#[test]
+fn test_printf() {
+    new_cmd!()
+        .arg("{ printf \"%-6s|%4d|%5.1f|%x\\n\", $1, $2, $2 / 7, $2 }")
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("alice |  30|  4.3|1e\nbob   |  25|  3.6|19\ncarol |  35|  5.0|23\n");
+}
+
+#[test]
+fn test_control_flow() {
+    new_cmd!()
+        .arg("{ if ($2 >= 30) print $1, \"old\"; else print $1, \"young\" }")
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("alice old\nbob young\ncarol old\n");
+
+    new_cmd!()
+        .arg("{ for (i = NF; i > 0; i--) printf \"%s%s\", $i, (i > 1 ? \" \" : \"\\n\") }")
+        .with_stdin().buffer("a b c\n")
+        .assert()
+        .success()
+        .stdout("c b a\n");
+
+    new_cmd!()
+        .arg("NR == 2 { exit 3 } { print } END { print \"done\" }")
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .failure()
+        .code(3)
+        .stdout("alice 30 paris\ndone\n");
+}
+
+#[test]
+fn test_functions() {
+    new_cmd!()
+        .arg("{ n = gsub(/o/, \"0\"); print n, $0, length($1), toupper(substr($1, 2, 2)) }")
+        .with_stdin().buffer("foo boo\n")
+        .assert()
+        .success()
+        .stdout("4 f00 b00 3 00\n");
+
+    new_cmd!()
+        .arg("{ print index($0, \"lo\"), match($0, /o+/), RSTART, RLENGTH, int(-3.7) }")
+        .with_stdin().buffer("hello fooo\n")
+        .assert()
+        .success()
+        .stdout("4 5 5 1 -3\n");
+}
+
+#[test]
+fn test_assignments() {
+    new_cmd!()
+        .args(&["-v", "greeting=hi", "{ print greeting, $1 }"])
+        .with_stdin().buffer("there\n")
+        .assert()
+        .success()
+        .stdout("hi there\n");
+
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("a"), "1\n").unwrap();
+    fs::write(temp_dir.path().join("b"), "2\n").unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["{ print x, FILENAME, FNR, $0 }", "x=one", "a", "x=two", "b"])
+        .assert()
+        .success()
+        .stdout("one a 1 1\ntwo b 1 2\n");
+}
+
+#[test]
+fn test_progfile() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    let program = "# count lines\n{ n++ }\nEND { print n }\n";
+    fs::write(temp_dir.path().join("prog.awk"), program).unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-f", "prog.awk"])
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("3\n");
+}
+
+#[test]
+fn test_errors() {
+    new_cmd!()
+        .arg("{ print $1 ")
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .failure()
+        .code(2)
+        .stdout("");
+
+    new_cmd!()
+        .args(&["{ print }", "missing"])
+        .assert()
+        .failure()
+        .code(2)
+        .stderr("awk: cannot open 'missing' for reading: No such file or directory\n");
+
+    for prog in &["BEGIN { $(2^64) = \"x\" }", "BEGIN { $(1e10) = 1 }", "BEGIN { NF = 1e18 }"] {
+        new_cmd!()
+            .arg(prog)
+            .assert()
+            .failure()
+            .code(2)
+            .stderr(pred_str_contains!("field index too large"));
+    }
+}
@@ -222,3 +222,30 @@ fn test_domain_socket() {
 
     thread.join().unwrap();
 }
+
+// pipes opened through /dev/fd (as with process substitution) are read like any other file
+#[cfg(target_os = "linux")]
+#[test]
+fn test_dev_fd_pipe() {
+    for path in &["/dev/fd/0", "/proc/self/fd/0"] {
+        new_cmd!()
+            .args(&["-n", path])
+            .with_stdin().buffer("a\nb\n")
+            .assert()
+            .success()
+            .stdout("     1\ta\n     2\tb\n")
+            .stderr("");
+    }
+}
+
+#[test]
+fn test_binary_check_not_terminal() {
+    // the check only applies when standard output is a terminal
+    new_cmd!()
+        .arg("--binary-check")
+        .with_stdin().buffer(&b"a\x00b"[..])
+        .assert()
+        .success()
+        .stdout("a\x00b")
+        .stderr("");
+}
@@ -167,3 +167,132 @@ fn test_chmod_many_options() {
         umask(original_umask);
     }
 }
+
+#[test]
+fn test_chmod_recursive() {
+    use std::fs;
+    use std::os::unix::fs::symlink;
+
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    let root = temp_dir.path().join("dir");
+    fs::create_dir_all(root.join("sub")).unwrap();
+    fs::write(root.join("file"), "").unwrap();
+    fs::write(root.join("sub").join("nested"), "").unwrap();
+    let outside = temp_dir.path().join("outside");
+    fs::write(&outside, "").unwrap();
+    set_permissions(&outside, fs::Permissions::from_mode(0o644)).unwrap();
+    symlink(&outside, root.join("link")).unwrap();
+
+    new_cmd!()
+        .args(&["-R", "0700", root.to_str().unwrap()])
+        .assert()
+        .success()
+        .stderr("");
+
+    let mode = |path: &::std::path::Path| metadata(path).unwrap().permissions().mode() & 0o7777;
+    assert_eq!(mode(&root), 0o700);
+    assert_eq!(mode(&root.join("file")), 0o700);
+    assert_eq!(mode(&root.join("sub")), 0o700);
+    assert_eq!(mode(&root.join("sub").join("nested")), 0o700);
+    // symlinks found while recursing are not followed
+    assert_eq!(mode(&outside), 0o644);
+}
+
+#[test]
+fn test_chmod_recursive_symlinks() {
+    use std::fs;
+    use std::os::unix::fs::symlink;
+
+    // the extra arguments and which of the files below end up changed when the operand is a
+    // symlink to "dir"
+    let cases: &[(&[&str], &[&str])] = &[
+        (&[], &[]),
+        (&["-P"], &[]),
+        (&["-H"], &["dir", "dir/file", "dir/sub"]),
+        (&["-L"], &["dir", "dir/file", "dir/sub", "target", "target/nested", "target_file"]),
+        (&["-L", "-P"], &[]),
+        (&["-P", "-H"], &["dir", "dir/file", "dir/sub"]),
+    ];
+    let paths = ["dir", "dir/file", "dir/sub", "target", "target/nested", "target_file"];
+
+    for &(args, changed) in cases {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("dir").join("sub")).unwrap();
+        fs::create_dir(root.join("target")).unwrap();
+        fs::write(root.join("dir").join("file"), "").unwrap();
+        fs::write(root.join("target").join("nested"), "").unwrap();
+        fs::write(root.join("target_file"), "").unwrap();
+        symlink("../target", root.join("dir").join("link_dir")).unwrap();
+        symlink("../target_file", root.join("dir").join("link_file")).unwrap();
+        // a loop that -L must not follow forever
+        symlink("..", root.join("dir").join("sub").join("loop")).unwrap();
+        symlink("dir", root.join("operand")).unwrap();
+        for path in &paths {
+            set_permissions(root.join(path), fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        new_cmd!()
+            .current_dir(root)
+            .arg("-R")
+            .args(args)
+            .args(&["0700", "operand"])
+            .assert()
+            .success()
+            .stderr("");
+
+        for path in &paths {
+            let mode = metadata(root.join(path)).unwrap().permissions().mode() & 0o7777;
+            let expected = if changed.contains(path) { 0o700 } else { 0o755 };
+            assert_eq!(mode, expected, "{:?}: {}", args, path);
+        }
+    }
+
+    // symlinks below a directory given directly are only followed with -L
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::create_dir(root.join("dir")).unwrap();
+    fs::create_dir(root.join("target")).unwrap();
+    symlink("../target", root.join("dir").join("link")).unwrap();
+    set_permissions(root.join("target"), fs::Permissions::from_mode(0o755)).unwrap();
+
+    new_cmd!()
+        .current_dir(root)
+        .args(&["-R", "-H", "0700", "dir"])
+        .assert()
+        .success();
+    assert_eq!(metadata(root.join("dir")).unwrap().permissions().mode() & 0o7777, 0o700);
+    assert_eq!(metadata(root.join("target")).unwrap().permissions().mode() & 0o7777, 0o755);
+}
+
+#[test]
+fn test_chmod_preserve_root() {
+    use std::os::unix::fs::symlink;
+
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    symlink("/", temp_dir.path().join("root")).unwrap();
+
+    // "u+" leaves every mode as it is, so nothing would change even if the check were missed
+    new_cmd!()
+        .args(&["-R", "u+", "/"])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(
+            "chmod: it is dangerous to operate recursively on '/'\n\
+             chmod: use --no-preserve-root to override this failsafe\n",
+        );
+
+    for path in &["/..", "root", "root/."] {
+        new_cmd!()
+            .current_dir(temp_dir.path())
+            .args(&["-R", "-H", "--preserve-root", "u+", path])
+            .assert()
+            .failure()
+            .stderr(format!(
+                "chmod: it is dangerous to operate recursively on '{}' (same as '/')\n\
+                 chmod: use --no-preserve-root to override this failsafe\n",
+                path
+            ));
+    }
+}
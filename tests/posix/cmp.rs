@@ -0,0 +1,163 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use assert_cmd::prelude::*;
+use assert_fs;
+use std::fs;
+use std::process::Command;
+
+const NAME: &str = "cmp";
+
+fn setup_files(files: &[(&str, &str)]) -> assert_fs::TempDir {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    for &(name, data) in files {
+        fs::write(temp_dir.path().join(name), data).unwrap();
+    }
+    temp_dir
+}
+
+#[test]
+fn test_identical() {
+    let temp_dir = setup_files(&[("a", "hello\nworld\n"), ("b", "hello\nworld\n")]);
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["a", "b"])
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .arg("a")
+        .with_stdin().buffer("hello\nworld\n")
+        .assert()
+        .success()
+        .stdout("");
+}
+
+#[test]
+fn test_first_difference() {
+    let temp_dir = setup_files(&[("a", "hello\nworld\n"), ("b", "hello\nwxrld\n")]);
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["a", "b"])
+        .assert()
+        .failure()
+        .code(1)
+        .stdout("a b differ: byte 8, line 2\n");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-b", "a", "b"])
+        .assert()
+        .failure()
+        .code(1)
+        .stdout("a b differ: byte 8, line 2 is 157 o 170 x\n");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-s", "a", "b"])
+        .assert()
+        .failure()
+        .code(1)
+        .stdout("")
+        .stderr("");
+}
+
+#[test]
+fn test_list() {
+    let temp_dir = setup_files(&[("a", "abc"), ("b", "axz")]);
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-l", "a", "b"])
+        .assert()
+        .failure()
+        .code(1)
+        .stdout("2 142 170\n3 143 172\n");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-l", "-b", "a", "b"])
+        .assert()
+        .failure()
+        .code(1)
+        .stdout("2 142 b    170 x\n3 143 c    172 z\n");
+}
+
+#[test]
+fn test_eof() {
+    let temp_dir = setup_files(&[("long", "ab\ncd"), ("short", "ab\n"), ("empty", "")]);
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["long", "short"])
+        .assert()
+        .failure()
+        .code(1)
+        .stdout("")
+        .stderr("cmp: EOF on short after byte 3, line 1\n");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["empty", "long"])
+        .assert()
+        .failure()
+        .code(1)
+        .stderr("cmp: EOF on empty which is empty\n");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-s", "long", "short"])
+        .assert()
+        .failure()
+        .code(1)
+        .stderr("");
+}
+
+#[test]
+fn test_skip_and_limit() {
+    let temp_dir = setup_files(&[("a", "xxabcY"), ("b", "abcZ")]);
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-i", "2:0", "-n", "3", "a", "b"])
+        .assert()
+        .success();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-i", "2:0", "a", "b"])
+        .assert()
+        .failure()
+        .code(1)
+        .stdout("a b differ: byte 4, line 1\n");
+}
+
+#[test]
+fn test_errors() {
+    let temp_dir = setup_files(&[("a", "abc")]);
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["a", "missing"])
+        .assert()
+        .failure()
+        .code(2)
+        .stdout("");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-n", "x", "a", "a"])
+        .assert()
+        .failure()
+        .code(2);
+}
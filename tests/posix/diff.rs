@@ -0,0 +1,163 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use assert_cmd::prelude::*;
+use assert_fs;
+use std::fs;
+use std::process::Command;
+
+const NAME: &str = "diff";
+
+fn setup_files(files: &[(&str, &str)]) -> assert_fs::TempDir {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    for &(name, data) in files {
+        fs::write(temp_dir.path().join(name), data).unwrap();
+    }
+    temp_dir
+}
+
+#[test]
+fn test_identical() {
+    let temp_dir = setup_files(&[("a", "one\ntwo\n"), ("b", "one\ntwo\n")]);
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["a", "b"])
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-u", "a", "-"])
+        .with_stdin().buffer("one\ntwo\n")
+        .assert()
+        .success()
+        .stdout("");
+}
+
+#[test]
+fn test_normal() {
+    let temp_dir = setup_files(&[("a", "one\ntwo\nthree\n"), ("b", "one\n2\nthree\nfour\n")]);
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["a", "b"])
+        .assert()
+        .failure()
+        .code(1)
+        .stdout("2c2\n< two\n---\n> 2\n3a4\n> four\n");
+}
+
+#[test]
+fn test_unified() {
+    let temp_dir = setup_files(&[
+        ("a", "1\n2\n3\n4\n5\n6\n7\n8\n9\n"),
+        ("b", "1\n2\nthree\n4\n5\n6\n7\n8\n9\nten\n"),
+    ]);
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-u", "--label", "old", "--label", "new", "a", "b"])
+        .assert()
+        .failure()
+        .code(1)
+        .stdout("--- old\n+++ new\n@@ -1,9 +1,10 @@\n 1\n 2\n-3\n+three\n 4\n 5\n 6\n 7\n 8\n 9\n+ten\n");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-U", "1", "--label", "old", "--label", "new", "a", "b"])
+        .assert()
+        .failure()
+        .code(1)
+        .stdout("--- old\n+++ new\n@@ -2,3 +2,3 @@\n 2\n-3\n+three\n 4\n@@ -9 +9,2 @@\n 9\n+ten\n");
+}
+
+#[test]
+fn test_context() {
+    let temp_dir = setup_files(&[("a", "a\nb\nc\n"), ("b", "a\nx\nc\n")]);
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-c", "--label", "old", "--label", "new", "a", "b"])
+        .assert()
+        .failure()
+        .code(1)
+        .stdout("*** old\n--- new\n***************\n*** 1,3 ****\n  a\n! b\n  c\n--- 1,3 ----\n  a\n! x\n  c\n");
+}
+
+#[test]
+fn test_missing_newline() {
+    let temp_dir = setup_files(&[("a", "a\nb"), ("b", "a\nb\n")]);
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["a", "b"])
+        .assert()
+        .failure()
+        .code(1)
+        .stdout("2c2\n< b\n\\ No newline at end of file\n---\n> b\n");
+}
+
+#[test]
+fn test_ignore() {
+    let temp_dir = setup_files(&[("a", "Hello  World\n"), ("b", "hello world \n"), ("c", "helloworld\n")]);
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-i", "-b", "a", "b"])
+        .assert()
+        .success();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-i", "-b", "a", "c"])
+        .assert()
+        .failure()
+        .code(1);
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-i", "-w", "a", "c"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_brief() {
+    let temp_dir = setup_files(&[("a", "a\n"), ("b", "b\n")]);
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-q", "a", "b"])
+        .assert()
+        .failure()
+        .code(1)
+        .stdout("Files a and b differ\n");
+}
+
+#[test]
+fn test_errors() {
+    let temp_dir = setup_files(&[("a", "a\n")]);
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["a", "missing"])
+        .assert()
+        .failure()
+        .code(2)
+        .stdout("");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-u", "-c", "a", "a"])
+        .assert()
+        .failure()
+        .code(2);
+}
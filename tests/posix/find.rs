@@ -0,0 +1,222 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use assert_cmd::prelude::*;
+use assert_fs;
+use std::fs;
+use std::process::Command;
+
+const NAME: &str = "find";
+
+fn setup_tree() -> assert_fs::TempDir {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::create_dir_all(root.join("dir/sub")).unwrap();
+    fs::create_dir_all(root.join("skip")).unwrap();
+    fs::write(root.join("a.txt"), "hello\n").unwrap();
+    fs::write(root.join("B.TXT"), "").unwrap();
+    fs::write(root.join("dir/c.rs"), vec![b'x'; 2000]).unwrap();
+    fs::write(root.join("dir/sub/d.txt"), "").unwrap();
+    fs::write(root.join("skip/e.txt"), "").unwrap();
+    temp_dir
+}
+
+#[test]
+fn test_print_all() {
+    let temp_dir = setup_tree();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .assert()
+        .success()
+        .stdout(".\n./B.TXT\n./a.txt\n./dir\n./dir/c.rs\n./dir/sub\n./dir/sub/d.txt\n./skip\n./skip/e.txt\n")
+        .stderr("");
+}
+
+#[test]
+fn test_name_and_type() {
+    let temp_dir = setup_tree();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["dir", "-name", "*.rs"])
+        .assert()
+        .success()
+        .stdout("dir/c.rs\n");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&[".", "-iname", "*.txt", "-type", "f"])
+        .assert()
+        .success()
+        .stdout("./B.TXT\n./a.txt\n./dir/sub/d.txt\n./skip/e.txt\n");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-type", "d"])
+        .assert()
+        .success()
+        .stdout(".\n./dir\n./dir/sub\n./skip\n");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-path", "./dir/*.txt"])
+        .assert()
+        .success()
+        .stdout("./dir/sub/d.txt\n");
+}
+
+#[test]
+fn test_name_of_starting_point() {
+    let temp_dir = setup_tree();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["dir/..", "dir//", "-maxdepth", "0", "-name", "[.d]*"])
+        .assert()
+        .success()
+        .stdout("dir/..\ndir//\n");
+}
+
+#[test]
+fn test_depth() {
+    let temp_dir = setup_tree();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-maxdepth", "1", "-type", "d"])
+        .assert()
+        .success()
+        .stdout(".\n./dir\n./skip\n");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["dir", "-mindepth", "2"])
+        .assert()
+        .success()
+        .stdout("dir/sub/d.txt\n");
+}
+
+#[test]
+fn test_operators() {
+    let temp_dir = setup_tree();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-name", "a.txt", "-o", "-name", "*.rs"])
+        .assert()
+        .success()
+        .stdout("./a.txt\n./dir/c.rs\n");
+
+    // -a binds more tightly than -o, so only the -type d files are printed
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-name", "*.txt", "-o", "-type", "d", "-print"])
+        .assert()
+        .success()
+        .stdout(".\n./dir\n./dir/sub\n./skip\n");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["(", "-name", "*.txt", "-o", "-name", "*.rs", ")", "!", "-path", "./skip/*", "-type", "f"])
+        .assert()
+        .success()
+        .stdout("./a.txt\n./dir/c.rs\n./dir/sub/d.txt\n");
+}
+
+#[test]
+fn test_prune() {
+    let temp_dir = setup_tree();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-name", "skip", "-prune", "-o", "-name", "*.txt", "-print"])
+        .assert()
+        .success()
+        .stdout("./a.txt\n./dir/sub/d.txt\n");
+}
+
+#[test]
+fn test_size() {
+    let temp_dir = setup_tree();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-type", "f", "-size", "+1k"])
+        .assert()
+        .success()
+        .stdout("./dir/c.rs\n");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-type", "f", "-size", "6c"])
+        .assert()
+        .success()
+        .stdout("./a.txt\n");
+}
+
+#[test]
+fn test_print0() {
+    let temp_dir = setup_tree();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["dir", "-type", "f", "-print0"])
+        .assert()
+        .success()
+        .stdout("dir/c.rs\0dir/sub/d.txt\0");
+}
+
+#[test]
+fn test_exec() {
+    let temp_dir = setup_tree();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["dir", "-type", "f", "-exec", "echo", "file:{}", ";"])
+        .assert()
+        .success()
+        .stdout("file:dir/c.rs\nfile:dir/sub/d.txt\n");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["dir", "-type", "f", "-exec", "echo", "files", "{}", "+"])
+        .assert()
+        .success()
+        .stdout("files dir/c.rs dir/sub/d.txt\n");
+
+    // -exec ... ; is true only if the command succeeds
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["dir", "-type", "f", "-exec", "grep", "-q", "x", "{}", ";", "-print"])
+        .assert()
+        .success()
+        .stdout("dir/c.rs\n");
+}
+
+#[test]
+fn test_errors() {
+    let temp_dir = setup_tree();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["missing", "a.txt"])
+        .assert()
+        .failure()
+        .code(1)
+        .stdout("a.txt\n")
+        .stderr("find: cannot access 'missing': No such file or directory\n");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .arg("-bogus")
+        .assert()
+        .failure()
+        .code(1)
+        .stdout("");
+}
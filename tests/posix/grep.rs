@@ -0,0 +1,370 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use assert_cmd::prelude::*;
+use assert_fs;
+use predicates::prelude::*;
+use std::fs;
+use std::process::Command;
+
+const NAME: &str = "grep";
+
+const INPUT: &str = "apple\nBanana\ncherry\napricot\nbanana split\n";
+
+fn setup_dir() -> assert_fs::TempDir {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    let path = temp_dir.path();
+
+    fs::write(path.join("fruit"), INPUT).unwrap();
+    fs::write(path.join("other"), "cherry pie\nlemon\n").unwrap();
+    fs::create_dir(path.join("dir")).unwrap();
+    fs::write(path.join("dir").join("nested.txt"), "nested cherry\n").unwrap();
+    fs::write(path.join("dir").join("skipped.log"), "cherry log\n").unwrap();
+
+    temp_dir
+}
+
+#[test]
+fn test_basic() {
+    new_cmd!()
+        .arg("an")
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("Banana\nbanana split\n")
+        .stderr("");
+}
+
+#[test]
+fn test_no_match() {
+    new_cmd!()
+        .arg("durian")
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .failure()
+        .code(1)
+        .stdout("")
+        .stderr("");
+}
+
+#[test]
+fn test_basic_vs_extended() {
+    // in a basic regex, + and | are literal unless escaped
+    new_cmd!()
+        .arg("ap+le")
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .failure()
+        .code(1);
+
+    new_cmd!()
+        .arg(r"ap\+le\|cherry")
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("apple\ncherry\n");
+
+    new_cmd!()
+        .args(&["-E", "ap+le|cherry"])
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("apple\ncherry\n");
+}
+
+#[test]
+fn test_fixed_strings() {
+    new_cmd!()
+        .args(&["-F", "a.p"])
+        .with_stdin().buffer("a.pple\napple\n")
+        .assert()
+        .success()
+        .stdout("a.pple\n");
+}
+
+#[test]
+fn test_multiple_patterns() {
+    new_cmd!()
+        .args(&["-e", "apple", "-e", "cherry"])
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("apple\ncherry\n");
+
+    new_cmd!()
+        .arg("apple\ncherry")
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("apple\ncherry\n");
+}
+
+#[test]
+fn test_flags() {
+    new_cmd!()
+        .args(&["-i", "banana"])
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("Banana\nbanana split\n");
+
+    new_cmd!()
+        .args(&["-v", "an"])
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("apple\ncherry\napricot\n");
+
+    new_cmd!()
+        .args(&["-c", "an"])
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("2\n");
+
+    new_cmd!()
+        .args(&["-n", "an"])
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("2:Banana\n5:banana split\n");
+
+    new_cmd!()
+        .args(&["-o", "an"])
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("an\nan\nan\nan\n");
+
+    new_cmd!()
+        .args(&["-w", "banana"])
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("banana split\n");
+
+    new_cmd!()
+        .args(&["-x", "cherry"])
+        .with_stdin().buffer("cherry\ncherry pie\n")
+        .assert()
+        .success()
+        .stdout("cherry\n");
+
+    new_cmd!()
+        .args(&["-q", "apple"])
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("");
+}
+
+#[test]
+fn test_context() {
+    new_cmd!()
+        .args(&["-A", "1", "cherry"])
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("cherry\napricot\n");
+
+    new_cmd!()
+        .args(&["-B", "1", "-n", "cherry"])
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("2-Banana\n3:cherry\n");
+
+    new_cmd!()
+        .args(&["-A", "1", "-e", "apple", "-e", "apricot"])
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("apple\nBanana\n--\napricot\nbanana split\n");
+}
+
+#[test]
+fn test_files() {
+    let temp_dir = setup_dir();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["cherry", "fruit", "other"])
+        .assert()
+        .success()
+        .stdout("fruit:cherry\nother:cherry pie\n")
+        .stderr("");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-h", "cherry", "fruit", "other"])
+        .assert()
+        .success()
+        .stdout("cherry\ncherry pie\n");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-l", "lemon", "fruit", "other"])
+        .assert()
+        .success()
+        .stdout("other\n");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-L", "lemon", "fruit", "other"])
+        .assert()
+        .success()
+        .stdout("fruit\n");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-c", "cherry", "fruit", "-"])
+        .with_stdin().buffer("nothing\n")
+        .assert()
+        .success()
+        .stdout("fruit:1\n(standard input):0\n");
+}
+
+#[test]
+fn test_missing_file() {
+    let temp_dir = setup_dir();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["cherry", "fruit", "missing"])
+        .assert()
+        .failure()
+        .code(2)
+        .stdout("fruit:cherry\n")
+        .stderr("grep: cannot open 'missing' for reading: No such file or directory\n");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-s", "cherry", "missing"])
+        .assert()
+        .failure()
+        .code(2)
+        .stderr("");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-q", "cherry", "missing", "fruit"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_recursive() {
+    let temp_dir = setup_dir();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-r", "cherry"])
+        .assert()
+        .success()
+        .stdout("dir/nested.txt:nested cherry\ndir/skipped.log:cherry log\nfruit:cherry\nother:cherry pie\n");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-r", "--exclude", "*.log", "cherry", "dir"])
+        .assert()
+        .success()
+        .stdout("dir/nested.txt:nested cherry\n");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-r", "--include", "*.log", "cherry", "dir"])
+        .assert()
+        .success()
+        .stdout("dir/skipped.log:cherry log\n");
+}
+
+#[test]
+fn test_directory_without_recursion() {
+    let temp_dir = setup_dir();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["cherry", "dir", "fruit"])
+        .assert()
+        .failure()
+        .code(2)
+        .stdout("fruit:cherry\n")
+        .stderr("grep: error reading 'dir': Is a directory\n");
+}
+
+#[test]
+fn test_max_depth() {
+    let temp_dir = setup_dir();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-r", "--max-depth", "0", "cherry"])
+        .assert()
+        .success()
+        .stdout("fruit:cherry\nother:cherry pie\n");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-r", "--max-depth=1", "--one-file-system", "nested", "."])
+        .assert()
+        .success()
+        .stdout("./dir/nested.txt:nested cherry\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_symlink_loop() {
+    use std::os::unix::fs::symlink;
+
+    let temp_dir = setup_dir();
+    symlink(".", temp_dir.path().join("dir").join("loop")).unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-R", "cherry", "dir"])
+        .assert()
+        .failure()
+        .code(2)
+        .stdout("dir/nested.txt:nested cherry\ndir/skipped.log:cherry log\n")
+        .stderr(pred_str_contains!("File system loop found"));
+
+    // without -R the link is not followed at all
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-r", "cherry", "dir"])
+        .assert()
+        .success()
+        .stdout("dir/nested.txt:nested cherry\ndir/skipped.log:cherry log\n");
+}
+
+#[test]
+fn test_binary() {
+    new_cmd!()
+        .arg("b")
+        .with_stdin().buffer("a\0b\n")
+        .assert()
+        .success()
+        .stdout("Binary file (standard input) matches\n");
+
+    new_cmd!()
+        .args(&["-a", "b"])
+        .with_stdin().buffer("a\0b\n")
+        .assert()
+        .success()
+        .stdout("a\0b\n");
+}
+
+#[test]
+fn test_invalid_regex() {
+    new_cmd!()
+        .args(&["-E", "a("])
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .failure()
+        .code(2);
+}
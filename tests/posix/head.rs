@@ -32,7 +32,10 @@
 //
 
 use assert_cmd::prelude::*;
+use assert_fs;
+use golden::Golden;
 use predicates::prelude::*;
+use std::fs;
 use std::process::Command;
 
 const NAME: &str = "head";
@@ -149,6 +152,147 @@ fn test_minus_5_chars() {
         .stderr("");
 }
 
+#[test]
+fn test_negative_spellings() {
+    let cases: &[(&[&str], &str)] = &[
+        (&["-n-1"], "lorem_ipsum_minus_1_line.expected"),
+        (&["-n", "-1"], "lorem_ipsum_minus_1_line.expected"),
+        (&["--lines=-1"], "lorem_ipsum_minus_1_line.expected"),
+        (&["--lines", "-1"], "lorem_ipsum_minus_1_line.expected"),
+        (&["-c-5"], "lorem_ipsum_minus_5_chars.expected"),
+        (&["-c", "-5"], "lorem_ipsum_minus_5_chars.expected"),
+        (&["--bytes=-5"], "lorem_ipsum_minus_5_chars.expected"),
+        (&["--bytes", "-5"], "lorem_ipsum_minus_5_chars.expected"),
+    ];
+    for &(args, expected) in cases {
+        new_cmd!()
+            .current_dir(fixtures_dir!())
+            .args(args)
+            .arg(INPUT)
+            .assert()
+            .success()
+            .stdout(pred_eq_file!(expected))
+            .stderr("");
+    }
+}
+
+#[test]
+fn test_double_minus_number() {
+    let cases: &[&[&str]] = &[&["-n", "--1"], &["--lines=--1"], &["-c--5"]];
+    for args in cases {
+        new_cmd!()
+            .current_dir(fixtures_dir!())
+            .args(args.iter())
+            .arg(INPUT)
+            .assert()
+            .failure()
+            .stdout("")
+            .stderr(pred_str_contains!("is not a number"));
+    }
+}
+
+#[test]
+fn test_end_of_options() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("-5"), "a\nb\n").unwrap();
+    fs::write(temp_dir.path().join("file"), "c\nd\n").unwrap();
+
+    // -5 must not be taken as the obsolete form of -n 5
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["--", "-5"])
+        .assert()
+        .success()
+        .stdout("a\nb\n")
+        .stderr("");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-n", "1", "file", "--", "-5"])
+        .assert()
+        .success()
+        .stdout("==> file <==\nc\n\n==> -5 <==\na\n")
+        .stderr("");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_sparse_file() {
+    use std::fs::File;
+    use std::io::{Seek, SeekFrom, Write};
+    use std::os::unix::fs::MetadataExt;
+
+    const SIZE: u64 = 16 * 1024 * 1024;
+
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    let input = temp_dir.path().join("sparse");
+    let output = temp_dir.path().join("copy");
+    {
+        let mut file = File::create(&input).unwrap();
+        file.write_all(b"start").unwrap();
+        file.set_len(SIZE).unwrap();
+        file.seek(SeekFrom::End(0)).unwrap();
+        file.write_all(b"end").unwrap();
+    }
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-c", "20M", "sparse"])
+        .stdout(File::create(&output).unwrap())
+        .assert()
+        .success()
+        .stderr("");
+
+    assert_eq!(fs::read(&input).unwrap(), fs::read(&output).unwrap());
+
+    // only check the output for holes if the filesystem supports them
+    let input_meta = fs::metadata(&input).unwrap();
+    let output_meta = fs::metadata(&output).unwrap();
+    if input_meta.blocks() * 512 < SIZE {
+        assert!(output_meta.blocks() * 512 < SIZE / 2);
+    }
+}
+
+// pipes opened through /dev/fd (as with process substitution) cannot use any of the faster paths
+// that seek in the input
+#[cfg(target_os = "linux")]
+#[test]
+fn test_dev_fd_pipe() {
+    use std::fs::File;
+
+    let cases: &[(&[&str], &str)] = &[
+        (&["-n", "2", "/dev/fd/0"], "one\ntwo\n"),
+        (&["-n", "-2", "/dev/fd/0"], "one\ntwo\n"),
+        (&["-c", "-5", "/proc/self/fd/0"], "one\ntwo\nthree\n"),
+        (&["-c", "6", "/proc/self/fd/0"], "one\ntw"),
+    ];
+    for &(args, expected) in cases {
+        new_cmd!()
+            .args(args)
+            .with_stdin().buffer("one\ntwo\nthree\nfour\n")
+            .assert()
+            .success()
+            .stdout(expected)
+            .stderr("");
+    }
+
+    // the sparse copy is used when standard output is a regular file
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    let output = temp_dir.path().join("copy");
+    let mut data = vec![0; 64 * 1024];
+    data.extend_from_slice(b"end");
+
+    new_cmd!()
+        .args(&["-c", "1M", "/dev/fd/0"])
+        .stdout(File::create(&output).unwrap())
+        .with_stdin().buffer(data.clone())
+        .assert()
+        .success()
+        .stderr("");
+
+    assert_eq!(fs::read(&output).unwrap(), data);
+}
+
 #[test]
 fn test_multiple_input_files() {
     new_cmd!()
@@ -171,6 +315,114 @@ fn test_verbose() {
         .stderr("");
 }
 
+#[test]
+fn test_header_format() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("a"), "one\ntwo\n").unwrap();
+    fs::write(temp_dir.path().join("b"), "three\n").unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-n", "1", "--header-format", "### %f (100%%)", "a", "b"])
+        .assert()
+        .success()
+        .stdout("### a (100%)\none\n### b (100%)\nthree\n")
+        .stderr("");
+}
+
+#[test]
+fn test_max_bytes() {
+    // the line limit is hit first
+    new_cmd!()
+        .args(&["-n", "2", "--max-bytes", "100"])
+        .with_stdin().buffer("one\ntwo\nthree\n")
+        .assert()
+        .success()
+        .stdout("one\ntwo\n")
+        .stderr("");
+
+    // the byte limit is hit first, in the middle of a line
+    new_cmd!()
+        .args(&["-n", "2", "--max-bytes", "6"])
+        .with_stdin().buffer("one\ntwo\nthree\n")
+        .assert()
+        .success()
+        .stdout("one\ntw")
+        .stderr("");
+
+    new_cmd!()
+        .args(&["-n", "-1", "--max-bytes", "6"])
+        .with_stdin().buffer("one\ntwo\nthree\n")
+        .assert()
+        .failure()
+        .stderr(pred_str_contains!("--max-bytes"))
+        .stdout("");
+}
+
+#[test]
+fn test_line_buffered() {
+    new_cmd!()
+        .args(&["--line-buffered", "-n", "2"])
+        .with_stdin().buffer("one\ntwo\nthree\n")
+        .assert()
+        .success()
+        .stdout("one\ntwo\n")
+        .stderr("");
+}
+
+#[test]
+fn test_zero_lines() {
+    new_cmd!()
+        .current_dir(fixtures_dir!())
+        .args(&["-v", "-n", "0", INPUT])
+        .assert()
+        .success()
+        .stdout("==> lorem_ipsum.txt <==\n")
+        .stderr("");
+
+    new_cmd!()
+        .current_dir(fixtures_dir!())
+        .args(&["-n", "0", INPUT, INPUT2])
+        .assert()
+        .success()
+        .stdout("==> lorem_ipsum.txt <==\n\n==> lorem_ipsum_reverse.txt <==\n")
+        .stderr("");
+
+    new_cmd!()
+        .args(&["-n", "0"])
+        .with_stdin().buffer("some input\n")
+        .assert()
+        .success()
+        .stdout("");
+}
+
+#[test]
+fn test_zero_bytes() {
+    new_cmd!()
+        .current_dir(fixtures_dir!())
+        .args(&["-c", "0", INPUT])
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("");
+
+    new_cmd!()
+        .current_dir(fixtures_dir!())
+        .args(&["-c", "0", INPUT, INPUT2])
+        .assert()
+        .success()
+        .stdout("==> lorem_ipsum.txt <==\n\n==> lorem_ipsum_reverse.txt <==\n")
+        .stderr("");
+
+    new_cmd!()
+        .current_dir(fixtures_dir!())
+        .args(&["-q", "-c", "0", INPUT, INPUT2])
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("");
+}
+
 #[test]
 fn test_invalid_numbers() {
     new_cmd!()
@@ -189,3 +441,307 @@ fn test_invalid_numbers() {
         .stdout("")
         .stderr(pred_str_contains!("'a' is not a number or is too large"));
 }
+
+#[test]
+fn test_minus_lines_large_file() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    let path = temp_dir.path().join("large.txt");
+
+    let contents = (0..20000).map(|i| format!("line {}\n", i)).collect::<String>();
+    fs::write(&path, &contents).unwrap();
+
+    let expected = (0..10).map(|i| format!("line {}\n", i)).collect::<String>();
+    new_cmd!()
+        .args(&["-n", "-19990"])
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(expected.into_bytes())
+        .stderr("");
+
+    new_cmd!()
+        .args(&["-n", "-20000"])
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("");
+}
+
+#[test]
+fn test_presume_input_pipe() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    let path = temp_dir.path().join("large.txt");
+
+    let contents = (0..2000).map(|i| format!("line {}\n", i)).collect::<String>();
+    fs::write(&path, &contents).unwrap();
+
+    // the streaming code has to give the same results as the ways of reading regular files
+    let last = contents.len() - "line 1999\n".len();
+    let cases: &[(&[&str], &str)] = &[
+        (&["-n", "-1"], &contents[..last]),
+        (&["-n", "-2000"], ""),
+        (&["-c", "-10"], &contents[..last]),
+        (&["-c", "15"], "line 0\nline 1\nl"),
+        (&["-c", "1M"], &contents),
+    ];
+    for &(args, expected) in cases {
+        for flags in &[&[][..], &["--presume-input-pipe"][..]] {
+            new_cmd!()
+                .env("MESABOX_BUFSIZE", "512")
+                .args(*flags)
+                .args(args)
+                .arg(&path)
+                .assert()
+                .success()
+                .stdout(expected)
+                .stderr("");
+        }
+    }
+}
+
+#[test]
+fn test_argfile() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    let list = temp_dir.path().join("list");
+    fs::write(&list, format!("{}\n{}\n", INPUT, INPUT2)).unwrap();
+
+    new_cmd!()
+        .current_dir(fixtures_dir!())
+        .arg(format!("@{}", list.display()))
+        .assert()
+        .success()
+        .stdout(pred_eq_file!("lorem_ipsum_multiple_input_files.expected"))
+        .stderr("");
+
+    let zero_list = temp_dir.path().join("zero_list");
+    fs::write(&zero_list, format!("{}\0{}", INPUT, INPUT2)).unwrap();
+
+    new_cmd!()
+        .current_dir(fixtures_dir!())
+        .args(&["-z", "-n", "1000"])
+        .arg(format!("@{}", zero_list.display()))
+        .assert()
+        .success()
+        .stdout(pred_str_contains!("==> lorem_ipsum_reverse.txt <=="))
+        .stderr("");
+}
+
+#[test]
+fn test_argfile_errors() {
+    new_cmd!()
+        .arg("@missing_list")
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr("head: cannot open 'missing_list' for reading: No such file or directory\n");
+
+    new_cmd!()
+        .arg("@/dev/zero")
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr("head: error reading '/dev/zero': file list too large\n");
+}
+
+#[test]
+fn test_argfile_literal() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("@list"), "not a list\n").unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .arg("./@list")
+        .assert()
+        .success()
+        .stdout("not a list\n")
+        .stderr("");
+}
+
+#[test]
+fn test_argfile_not_nested() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("outer"), "@inner\n").unwrap();
+    fs::write(temp_dir.path().join("inner"), "inner contents\n").unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .arg("@outer")
+        .assert()
+        .failure()
+        .stdout("");
+}
+
+#[test]
+fn test_nonexistent_file() {
+    new_cmd!()
+        .current_dir(fixtures_dir!())
+        .args(&["nonexistent.txt", INPUT])
+        .assert()
+        .failure()
+        .stdout(pred_str_contains!("==> lorem_ipsum.txt <=="))
+        .stderr("head: cannot open 'nonexistent.txt' for reading: No such file or directory\n");
+}
+
+#[test]
+fn test_directory() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    fs::create_dir(temp_dir.path().join("somedir")).unwrap();
+    fs::write(temp_dir.path().join("file"), "contents\n").unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["somedir", "file"])
+        .assert()
+        .failure()
+        .code(1)
+        .stdout("==> file <==\ncontents\n")
+        .stderr("head: error reading 'somedir': Is a directory\n");
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_full_disk() {
+    // every write to /dev/full fails with ENOSPC
+    let full = fs::OpenOptions::new().write(true).open("/dev/full").unwrap();
+
+    new_cmd!()
+        .current_dir(fixtures_dir!())
+        .args(&[INPUT, INPUT2])
+        .stdout(full)
+        .assert()
+        .failure()
+        .code(1)
+        .stderr("head: error writing to output: No space left on device\n");
+}
+
+#[test]
+fn test_stdin_labels() {
+    const STDIN: &str = "from stdin\n";
+    const FIRST_LINE: &str = "Lorem ipsum dolor sit amet,\n";
+
+    let cases: &[(&[&str], String)] = &[
+        (&["-n", "1"], STDIN.to_owned()),
+        (&["-n", "1", "-"], STDIN.to_owned()),
+        (&["-n", "1", "-v"], format!("==> standard input <==\n{}", STDIN)),
+        (&["-n", "1", "-v", "-"], format!("==> standard input <==\n{}", STDIN)),
+        (
+            &["-n", "1", "-", INPUT],
+            format!(
+                "==> standard input <==\n{}\n==> {} <==\n{}",
+                STDIN, INPUT, FIRST_LINE
+            ),
+        ),
+        (
+            &["-n", "1", INPUT, "-"],
+            format!(
+                "==> {} <==\n{}\n==> standard input <==\n{}",
+                INPUT, FIRST_LINE, STDIN
+            ),
+        ),
+        (
+            &["-n", "1", INPUT, "-", INPUT],
+            format!(
+                "==> {0} <==\n{1}\n==> standard input <==\n{2}\n==> {0} <==\n{1}",
+                INPUT, FIRST_LINE, STDIN
+            ),
+        ),
+        (&["-n", "1", "-q", "-", INPUT], format!("{}{}", STDIN, FIRST_LINE)),
+        (&["-n", "1", "-q", "-v", "-"], format!("==> standard input <==\n{}", STDIN)),
+    ];
+
+    for &(args, ref expected) in cases {
+        new_cmd!()
+            .current_dir(fixtures_dir!())
+            .args(args)
+            .with_stdin().buffer(STDIN)
+            .assert()
+            .success()
+            .stdout(expected.clone().into_bytes())
+            .stderr("");
+    }
+}
+
+#[test]
+fn test_zero_terminated() {
+    new_cmd!()
+        .args(&["-z", "-n", "2"])
+        .with_stdin().buffer("first\0second\nstill second\0third\0")
+        .assert()
+        .success()
+        .stdout("first\0second\nstill second\0")
+        .stderr("");
+
+    new_cmd!()
+        .args(&["--zero-terminated", "-n", "-1"])
+        .with_stdin().buffer("first\0second\0third")
+        .assert()
+        .success()
+        .stdout("first\0second\0")
+        .stderr("");
+}
+
+#[cfg(feature = "decompress")]
+#[test]
+fn test_decompress() {
+    for input in &["lorem_ipsum.txt.gz", INPUT] {
+        new_cmd!()
+            .current_dir(fixtures_dir!())
+            .args(&["-Z", input])
+            .assert()
+            .success()
+            .stdout(pred_eq_file!("lorem_ipsum_default.expected"))
+            .stderr("");
+    }
+
+    new_cmd!()
+        .arg("-Z")
+        .with_stdin().path(fixtures_path!("lorem_ipsum.txt.gz")).unwrap()
+        .assert()
+        .success()
+        .stdout(pred_eq_file!("lorem_ipsum_default.expected"))
+        .stderr("");
+}
+
+#[test]
+fn test_golden() {
+    let cases: &[&[&str]] = &[
+        &[INPUT],
+        &["-n", "3", INPUT],
+        &["-c", "100", INPUT],
+        &["-n", "-3", INPUT],
+        &["-c", "-100", INPUT],
+        &["-n-3", INPUT],
+        &["--lines=-3", INPUT],
+        &["--lines", "-3", INPUT],
+        &["-c-100", INPUT],
+        &["--bytes=-100", INPUT],
+        &["--bytes", "-100", INPUT],
+        &["-v", INPUT],
+        &["-q", INPUT, INPUT2],
+        &[INPUT, INPUT2],
+    ];
+    for args in cases {
+        Golden::new(NAME)
+            .current_dir(fixtures_dir!())
+            .args(args.iter())
+            .assert_matches();
+    }
+
+    Golden::new(NAME)
+        .args(&["-n", "2"])
+        .stdin(fixtures_path!(INPUT))
+        .assert_matches();
+}
+
+#[test]
+fn test_binary_check_not_terminal() {
+    // the check only applies when standard output is a terminal
+    new_cmd!()
+        .args(&["-b", "-c", "4"])
+        .with_stdin().buffer(&b"\x7fELF\x00\x01"[..])
+        .assert()
+        .success()
+        .stdout("\x7fELF")
+        .stderr("");
+}
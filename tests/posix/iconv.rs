@@ -0,0 +1,121 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+
+const NAME: &str = "iconv";
+
+#[test]
+fn test_utf8_to_utf16le() {
+    new_cmd!()
+        .args(&["-f", "utf-8", "-t", "UTF-16LE"])
+        .with_stdin().buffer("aé😀")
+        .assert()
+        .success()
+        .stdout(&b"a\x00\xe9\x00\x3d\xd8\x00\xde"[..])
+        .stderr("");
+}
+
+#[test]
+fn test_utf16_bom_to_utf8() {
+    new_cmd!()
+        .args(&["-f", "UTF-16", "-t", "UTF-8"])
+        .with_stdin().buffer(&b"\xff\xfea\x00\xe9\x00"[..])
+        .assert()
+        .success()
+        .stdout("aé")
+        .stderr("");
+}
+
+#[test]
+fn test_latin1_to_utf8() {
+    new_cmd!()
+        .args(&["-f", "LATIN1"])
+        .with_stdin().buffer(&b"caf\xe9\n"[..])
+        .assert()
+        .success()
+        .stdout("café\n")
+        .stderr("");
+}
+
+#[test]
+fn test_unconvertible() {
+    new_cmd!()
+        .args(&["-t", "ASCII"])
+        .with_stdin().buffer("abé\n")
+        .assert()
+        .failure()
+        .stdout("ab")
+        .stderr("iconv: cannot convert U+00E9 at position 2\n");
+}
+
+#[test]
+fn test_illegal_input() {
+    new_cmd!()
+        .args(&["-f", "UTF-8", "-t", "UTF-16BE"])
+        .with_stdin().buffer(&b"a\xffb"[..])
+        .assert()
+        .failure()
+        .stdout(&b"\x00a"[..])
+        .stderr("iconv: illegal input sequence at position 1\n");
+}
+
+#[test]
+fn test_incomplete_input() {
+    new_cmd!()
+        .args(&["-f", "UTF-16LE"])
+        .with_stdin().buffer(&b"a\x00b"[..])
+        .assert()
+        .failure()
+        .stdout("a")
+        .stderr("iconv: incomplete character at end of input (position 2)\n");
+}
+
+#[test]
+fn test_discard() {
+    new_cmd!()
+        .args(&["-c", "-t", "ASCII"])
+        .with_stdin().buffer(&b"a\xc3\xa9\xffb\n"[..])
+        .assert()
+        .failure()
+        .stdout("ab\n")
+        .stderr("");
+}
+
+#[test]
+fn test_unicode_subst() {
+    new_cmd!()
+        .args(&["-t", "ISO-8859-1", "--unicode-subst=<U+%04X>"])
+        .with_stdin().buffer("é€\n")
+        .assert()
+        .success()
+        .stdout(&b"\xe9<U+20AC>\n"[..])
+        .stderr("");
+}
+
+#[test]
+fn test_unsupported_encoding() {
+    new_cmd!()
+        .args(&["-t", "EBCDIC"])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(pred_str_contains!("unsupported encoding 'EBCDIC'"));
+}
+
+#[test]
+fn test_list() {
+    new_cmd!()
+        .arg("-l")
+        .assert()
+        .success()
+        .stdout(pred_str_contains!("UTF-16LE UTF16LE\n"))
+        .stderr("");
+}
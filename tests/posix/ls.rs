@@ -0,0 +1,284 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use assert_cmd::prelude::*;
+use assert_fs;
+use libc;
+use predicates::prelude::*;
+use std::ffi::CString;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::Command;
+
+const NAME: &str = "ls";
+
+fn setup_dir() -> assert_fs::TempDir {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    let path = temp_dir.path();
+
+    fs::write(path.join("small"), "a").unwrap();
+    fs::write(path.join("large"), vec![b'a'; 4096]).unwrap();
+    fs::write(path.join(".hidden"), "").unwrap();
+    fs::create_dir(path.join("subdir")).unwrap();
+    fs::write(path.join("subdir").join("inner"), "").unwrap();
+
+    let script = path.join("script");
+    fs::write(&script, "#!/bin/sh\n").unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+    temp_dir
+}
+
+#[test]
+fn test_default() {
+    let temp_dir = setup_dir();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .assert()
+        .success()
+        .stdout("large\nscript\nsmall\nsubdir\n")
+        .stderr("");
+}
+
+#[test]
+fn test_all() {
+    let temp_dir = setup_dir();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .arg("-a")
+        .assert()
+        .success()
+        .stdout(".\n..\n.hidden\nlarge\nscript\nsmall\nsubdir\n")
+        .stderr("");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .arg("-A")
+        .assert()
+        .success()
+        .stdout(".hidden\nlarge\nscript\nsmall\nsubdir\n")
+        .stderr("");
+}
+
+#[test]
+fn test_sort() {
+    let temp_dir = setup_dir();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .arg("-r")
+        .assert()
+        .success()
+        .stdout("subdir\nsmall\nscript\nlarge\n")
+        .stderr("");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-S", "large", "small", "script"])
+        .assert()
+        .success()
+        .stdout("large\nscript\nsmall\n")
+        .stderr("");
+}
+
+#[test]
+fn test_version_sort() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    for name in &["v1.10", "v1.9", "v1.9~rc1", "v1.2.tar.gz", "v1.2"] {
+        fs::write(temp_dir.path().join(name), "").unwrap();
+    }
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .arg("-v")
+        .assert()
+        .success()
+        .stdout("v1.2\nv1.2.tar.gz\nv1.9~rc1\nv1.9\nv1.10\n")
+        .stderr("");
+}
+
+#[test]
+fn test_classify() {
+    let temp_dir = setup_dir();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .arg("-F")
+        .assert()
+        .success()
+        .stdout("large\nscript*\nsmall\nsubdir/\n")
+        .stderr("");
+}
+
+#[test]
+fn test_directory() {
+    let temp_dir = setup_dir();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-d", "subdir", "small"])
+        .assert()
+        .success()
+        .stdout("small\nsubdir\n")
+        .stderr("");
+}
+
+#[test]
+fn test_multiple_dirs() {
+    let temp_dir = setup_dir();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["small", "subdir", "."])
+        .assert()
+        .success()
+        .stdout("small\n\n.:\nlarge\nscript\nsmall\nsubdir\n\nsubdir:\ninner\n")
+        .stderr("");
+}
+
+#[test]
+fn test_recursive() {
+    let temp_dir = setup_dir();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .arg("-R")
+        .assert()
+        .success()
+        .stdout(".:\nlarge\nscript\nsmall\nsubdir\n\n./subdir:\ninner\n")
+        .stderr("");
+}
+
+#[test]
+fn test_long() {
+    let temp_dir = setup_dir();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-l", "script"])
+        .assert()
+        .success()
+        .stdout(pred_str_contains!("-rwxr-xr-x 1 "))
+        .stdout(pred_str_contains!(" 10 "))
+        .stdout(pred_str_contains!(" script\n"))
+        .stderr("");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-l", "subdir"])
+        .assert()
+        .success()
+        .stdout(pred_str_contains!("total "))
+        .stdout(pred_str_contains!(" inner\n"))
+        .stderr("");
+}
+
+#[test]
+fn test_human_readable() {
+    let temp_dir = setup_dir();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-lh", "large"])
+        .assert()
+        .success()
+        .stdout(pred_str_contains!(" 4.0K "))
+        .stderr("");
+}
+
+#[test]
+fn test_nonexistent() {
+    let temp_dir = setup_dir();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["nonexistent", "small"])
+        .assert()
+        .code(2)
+        .stdout("small\n")
+        .stderr("ls: cannot access 'nonexistent': No such file or directory\n");
+}
+
+fn set_mtime(path: &Path, secs: i64) {
+    let path = CString::new(path.as_os_str().as_bytes()).unwrap();
+    let time = libc::timeval {
+        tv_sec: secs as libc::time_t,
+        tv_usec: 0,
+    };
+    let times = [time, time];
+    assert_eq!(unsafe { libc::utimes(path.as_ptr(), times.as_ptr()) }, 0);
+}
+
+#[test]
+fn test_time_style() {
+    let temp_dir = setup_dir();
+    set_mtime(&temp_dir.path().join("small"), 0);
+
+    let cases = [
+        ("locale", "Jan  1  1970 small\n"),
+        ("posix-long-iso", "Jan  1  1970 small\n"),
+        ("long-iso", " 1970-01-01 00:00 small\n"),
+        ("full-iso", " 1970-01-01 00:00:00.000000000 +0000 small\n"),
+        ("iso", " 1970-01-01  small\n"),
+        ("+%Y/%j %q", " 1970/001 %q small\n"),
+    ];
+    for &(style, expected) in &cases {
+        new_cmd!()
+            .current_dir(temp_dir.path())
+            .env("TZ", "UTC")
+            .args(&["-l", "small"])
+            .arg(format!("--time-style={}", style))
+            .assert()
+            .success()
+            .stdout(pred_str_contains!(expected))
+            .stderr("");
+    }
+
+    // the second format is for recent files
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .env("TZ", "UTC")
+        .args(&["-l", "--time-style=+old\nrecent", "small", "large"])
+        .assert()
+        .success()
+        .stdout(pred_str_contains!(" recent large\n"))
+        .stdout(pred_str_contains!(" old small\n"))
+        .stderr("");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .env("TZ", "UTC")
+        .env("TIME_STYLE", "long-iso")
+        .args(&["-l", "small"])
+        .assert()
+        .success()
+        .stdout(pred_str_contains!(" 1970-01-01 00:00 small\n"))
+        .stderr("");
+}
+
+#[test]
+fn test_invalid_time_style() {
+    new_cmd!()
+        .args(&["-l", "--time-style=bogus"])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(pred_str_contains!("invalid argument 'bogus'"));
+
+    new_cmd!()
+        .env("TIME_STYLE", "bogus")
+        .arg("-l")
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr("ls: invalid argument 'bogus' for 'time style'\n");
+}
@@ -0,0 +1,199 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use assert_cmd::prelude::*;
+use assert_fs;
+use std::fs;
+use std::process::Command;
+
+const NAME: &str = "sed";
+
+const INPUT: &str = "one\ntwo\nthree\nfour\nfive\n";
+
+#[test]
+fn test_substitute() {
+    new_cmd!()
+        .arg("s/o/0/")
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("0ne\ntw0\nthree\nf0ur\nfive\n")
+        .stderr("");
+
+    new_cmd!()
+        .arg("s/e/E/g")
+        .with_stdin().buffer("eee\n")
+        .assert()
+        .success()
+        .stdout("EEE\n");
+
+    new_cmd!()
+        .arg("s/e/E/2")
+        .with_stdin().buffer("eee\n")
+        .assert()
+        .success()
+        .stdout("eEe\n");
+}
+
+#[test]
+fn test_backreferences() {
+    new_cmd!()
+        .arg(r"s/\([a-z]*\) \([a-z]*\)/\2 \1 [&]/")
+        .with_stdin().buffer("hello world\n")
+        .assert()
+        .success()
+        .stdout("world hello [hello world]\n");
+
+    new_cmd!()
+        .args(&["-E", r"s/(o+)/<\1>/g"])
+        .with_stdin().buffer("foo bo\n")
+        .assert()
+        .success()
+        .stdout("f<oo> b<o>\n");
+}
+
+#[test]
+fn test_quiet_print() {
+    new_cmd!()
+        .args(&["-n", "2p"])
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("two\n");
+
+    new_cmd!()
+        .args(&["-n", "s/t/T/p"])
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("Two\nThree\n");
+}
+
+#[test]
+fn test_delete_ranges() {
+    new_cmd!()
+        .arg("2,4d")
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("one\nfive\n");
+
+    new_cmd!()
+        .arg("/two/,/four/d")
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("one\nfive\n");
+
+    new_cmd!()
+        .arg("3,$!d")
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("three\nfour\nfive\n");
+
+    // the end of a range is never checked against the line that starts it
+    new_cmd!()
+        .args(&["-n", "/t/,/t/p"])
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("two\nthree\n");
+}
+
+#[test]
+fn test_multiple_scripts() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("script"), "s/two/2/\n# comment\n$d\n").unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-e", "s/one/1/", "-f", "script", "-e", "s/three/3/"])
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("1\n2\n3\nfour\n");
+
+    new_cmd!()
+        .arg("s/one/1/;s/two/2/;4q")
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .success()
+        .stdout("1\n2\nthree\nfour\n");
+}
+
+#[test]
+fn test_files() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("a"), "a1\na2\n").unwrap();
+    fs::write(temp_dir.path().join("b"), "b1\nb2").unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-n", "$p", "a", "b"])
+        .assert()
+        .success()
+        .stdout("b2");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-s", "-n", "$p", "a", "b"])
+        .assert()
+        .success()
+        .stdout("a2\nb2");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["p", "a", "missing"])
+        .assert()
+        .failure()
+        .code(2)
+        .stdout("a1\na1\na2\na2\n")
+        .stderr("sed: cannot open 'missing' for reading: No such file or directory\n");
+}
+
+#[test]
+fn test_in_place() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    let path = temp_dir.path();
+    fs::write(path.join("file"), INPUT).unwrap();
+
+    new_cmd!()
+        .current_dir(path)
+        .args(&["-i", "s/one/1/", "file"])
+        .assert()
+        .success()
+        .stdout("");
+    assert_eq!(fs::read_to_string(path.join("file")).unwrap(), "1\ntwo\nthree\nfour\nfive\n");
+
+    new_cmd!()
+        .current_dir(path)
+        .args(&["-i.bak", "2d", "file"])
+        .assert()
+        .success()
+        .stdout("");
+    assert_eq!(fs::read_to_string(path.join("file")).unwrap(), "1\nthree\nfour\nfive\n");
+    assert_eq!(fs::read_to_string(path.join("file.bak")).unwrap(), "1\ntwo\nthree\nfour\nfive\n");
+}
+
+#[test]
+fn test_invalid_script() {
+    new_cmd!()
+        .arg("s/a/b")
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .failure()
+        .stdout("");
+
+    new_cmd!()
+        .arg(r"s/a/\1/")
+        .with_stdin().buffer(INPUT)
+        .assert()
+        .failure()
+        .stdout("");
+}
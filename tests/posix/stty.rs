@@ -0,0 +1,53 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+
+const NAME: &str = "stty";
+
+// the tests are never run with a terminal as standard input, so only the errors can be checked
+#[test]
+fn test_not_a_terminal() {
+    new_cmd!()
+        .with_stdin().buffer("")
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(pred_str_contains!("stty: 'standard input': "));
+}
+
+#[test]
+fn test_device_not_a_terminal() {
+    new_cmd!()
+        .args(&["-F", "/dev/null", "-echo"])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(pred_str_contains!("stty: '/dev/null': "));
+}
+
+#[test]
+fn test_missing_device() {
+    new_cmd!()
+        .args(&["-F", "/nonexistent/tty", "-a"])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(pred_str_contains!("stty: '/nonexistent/tty': "));
+}
+
+#[test]
+fn test_all_and_save() {
+    new_cmd!()
+        .args(&["-a", "-g"])
+        .assert()
+        .failure()
+        .stdout("");
+}
@@ -0,0 +1,190 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use assert_cmd::prelude::*;
+use assert_fs;
+use predicates::prelude::*;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+const NAME: &str = "tee";
+
+#[test]
+fn test_copy() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["a", "b"])
+        .with_stdin().buffer("hello\n")
+        .assert()
+        .success()
+        .stdout("hello\n")
+        .stderr("");
+
+    assert_eq!(fs::read_to_string(temp_dir.path().join("a")).unwrap(), "hello\n");
+    assert_eq!(fs::read_to_string(temp_dir.path().join("b")).unwrap(), "hello\n");
+}
+
+#[test]
+fn test_append() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    let file = temp_dir.path().join("file");
+    fs::write(&file, "old\n").unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-a", "file"])
+        .with_stdin().buffer("new\n")
+        .assert()
+        .success()
+        .stdout("new\n");
+    assert_eq!(fs::read_to_string(&file).unwrap(), "old\nnew\n");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .arg("file")
+        .with_stdin().buffer("new\n")
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&file).unwrap(), "new\n");
+}
+
+#[test]
+fn test_open_error() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["missing/file", "file"])
+        .with_stdin().buffer("hello\n")
+        .assert()
+        .failure()
+        .stdout("hello\n")
+        .stderr(pred_str_contains!("tee: cannot open 'missing/file' for writing: "));
+
+    assert_eq!(fs::read_to_string(temp_dir.path().join("file")).unwrap(), "hello\n");
+}
+
+// run tee in `dir` with the reader of its standard output already gone
+fn run_with_closed_stdout(dir: &Path, args: &[&str]) -> Output {
+    let mut child = new_cmd!()
+        .current_dir(dir)
+        .env_remove("MESABOX_OUTPUT_ERROR")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    drop(child.stdout.take());
+    // tee may exit before reading all of its input in the exit modes
+    let _ = child.stdin.take().unwrap().write_all(b"data\n");
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_broken_pipe_modes() {
+    // the arguments, whether the broken pipe is reported (and tee fails), and what ends up in the
+    // file written after standard output.  by default, tee exits at the broken pipe like GNU tee
+    let cases: &[(&[&str], bool, &str)] = &[
+        (&[], false, ""),
+        (&["-p"], false, "data\n"),
+        (&["--output-error"], false, "data\n"),
+        (&["--output-error=warn-nopipe"], false, "data\n"),
+        (&["--output-error=exit-nopipe"], false, "data\n"),
+        (&["--output-error=warn"], true, "data\n"),
+        (&["--output-error=exit"], true, ""),
+        (&["--output-error=exit", "-p"], false, "data\n"),
+    ];
+
+    for &(args, reported, contents) in cases {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let mut args = args.to_vec();
+        args.push("file");
+
+        let output = run_with_closed_stdout(temp_dir.path(), &args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if reported {
+            assert!(!output.status.success(), "{:?}", args);
+            assert!(stderr.starts_with("tee: error writing to output: "), "{:?}: {}", args, stderr);
+        } else {
+            assert!(output.status.success(), "{:?}", args);
+            assert_eq!(stderr, "", "{:?}", args);
+        }
+        let file = fs::read_to_string(temp_dir.path().join("file")).unwrap();
+        assert_eq!(file, contents, "{:?}", args);
+    }
+}
+
+#[test]
+fn test_mode_overrides_env() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+
+    // a broken pipe is still ignored by -p even if the environment asks for it to be reported
+    let mut child = new_cmd!()
+        .current_dir(temp_dir.path())
+        .env("MESABOX_OUTPUT_ERROR", "exit")
+        .args(&["-p", "file"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    drop(child.stdout.take());
+    child.stdin.take().unwrap().write_all(b"data\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    assert_eq!(fs::read_to_string(temp_dir.path().join("file")).unwrap(), "data\n");
+
+    // and a broken pipe is reported with --output-error=exit even though it is quiet by default
+    let output = run_with_closed_stdout(temp_dir.path(), &["--output-error=exit", "file"]);
+    assert!(!output.status.success());
+    assert!(!output.stderr.is_empty());
+}
+
+// errors writing to outputs other than pipes are reported in every mode
+#[cfg(target_os = "linux")]
+#[test]
+fn test_full_disk() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .env_remove("MESABOX_OUTPUT_ERROR")
+        .args(&["/dev/full", "file"])
+        .with_stdin().buffer("data\n")
+        .assert()
+        .failure()
+        .stdout("data\n")
+        .stderr("tee: error writing '/dev/full': No space left on device\n");
+    assert_eq!(fs::read_to_string(temp_dir.path().join("file")).unwrap(), "data\n");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-p", "/dev/full", "file"])
+        .with_stdin().buffer("data\n")
+        .assert()
+        .failure()
+        .stdout("data\n")
+        .stderr("tee: error writing '/dev/full': No space left on device\n");
+    assert_eq!(fs::read_to_string(temp_dir.path().join("file")).unwrap(), "data\n");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["--output-error=exit-nopipe", "/dev/full", "file"])
+        .with_stdin().buffer("data\n")
+        .assert()
+        .failure()
+        .stdout("data\n")
+        .stderr("tee: error writing '/dev/full': No space left on device\n");
+    assert_eq!(fs::read_to_string(temp_dir.path().join("file")).unwrap(), "");
+}
@@ -0,0 +1,179 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use assert_cmd::prelude::*;
+use assert_fs;
+use std::process::Command;
+
+const NAME: &str = "xargs";
+
+#[test]
+fn test_default_echo() {
+    new_cmd!()
+        .with_stdin().buffer("a  b\nc\n")
+        .assert()
+        .success()
+        .stdout("a b c\n")
+        .stderr("");
+
+    new_cmd!()
+        .with_stdin().buffer("")
+        .assert()
+        .success()
+        .stdout("\n");
+
+    new_cmd!()
+        .arg("-r")
+        .with_stdin().buffer("")
+        .assert()
+        .success()
+        .stdout("");
+}
+
+#[test]
+fn test_quoting() {
+    new_cmd!()
+        .args(&["-n", "1", "echo", "x"])
+        .with_stdin().buffer("'a  b' \"c'd\" e\\ f\n")
+        .assert()
+        .success()
+        .stdout("x a  b\nx c'd\nx e f\n");
+
+    new_cmd!()
+        .with_stdin().buffer("a 'b\n")
+        .assert()
+        .failure()
+        .code(1)
+        .stdout("");
+}
+
+#[test]
+fn test_max_args() {
+    new_cmd!()
+        .args(&["-n", "2"])
+        .with_stdin().buffer("1 2 3 4 5\n")
+        .assert()
+        .success()
+        .stdout("1 2\n3 4\n5\n");
+}
+
+#[test]
+fn test_max_lines() {
+    // a trailing blank continues the line
+    new_cmd!()
+        .args(&["-L", "1"])
+        .with_stdin().buffer("a b\nc \nd\n\ne\n")
+        .assert()
+        .success()
+        .stdout("a b\nc d\ne\n");
+}
+
+#[test]
+fn test_max_chars() {
+    new_cmd!()
+        .args(&["-s", "12"])
+        .with_stdin().buffer("aa bb cc dd\n")
+        .assert()
+        .success()
+        .stdout("aa bb\ncc dd\n");
+
+    new_cmd!()
+        .args(&["-s", "7"])
+        .with_stdin().buffer("aaaaaaaa\n")
+        .assert()
+        .failure()
+        .code(1)
+        .stderr("xargs: argument line too long\n");
+}
+
+#[test]
+fn test_replace() {
+    new_cmd!()
+        .args(&["-I", "{}", "echo", "[{}]", "{}{}"])
+        .with_stdin().buffer("  one two\nthree\n")
+        .assert()
+        .success()
+        .stdout("[one two] one twoone two\n[three] threethree\n");
+}
+
+#[test]
+fn test_delimiters() {
+    new_cmd!()
+        .args(&["-0", "-n", "1"])
+        .with_stdin().buffer("a b\0c\0")
+        .assert()
+        .success()
+        .stdout("a b\nc\n");
+
+    new_cmd!()
+        .args(&["-d", ",", "-n", "2"])
+        .with_stdin().buffer("a,'b,c d")
+        .assert()
+        .success()
+        .stdout("a 'b\nc d\n");
+
+    new_cmd!()
+        .args(&["-d", "ab"])
+        .with_stdin().buffer("")
+        .assert()
+        .failure()
+        .stdout("");
+}
+
+#[test]
+fn test_trace() {
+    new_cmd!()
+        .args(&["-t", "echo", "x"])
+        .with_stdin().buffer("y\n")
+        .assert()
+        .success()
+        .stdout("x y\n")
+        .stderr("echo x y\n");
+}
+
+#[test]
+fn test_parallel() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-P", "3", "-n", "1", "touch"])
+        .with_stdin().buffer("a b c d e\n")
+        .assert()
+        .success();
+
+    for name in &["a", "b", "c", "d", "e"] {
+        assert!(temp_dir.path().join(name).exists());
+    }
+}
+
+#[test]
+fn test_exit_status() {
+    new_cmd!()
+        .arg("false")
+        .with_stdin().buffer("a\n")
+        .assert()
+        .failure()
+        .code(123);
+
+    new_cmd!()
+        .args(&["-n", "1", "sh", "-c", "echo $0; exit 255"])
+        .with_stdin().buffer("a b\n")
+        .assert()
+        .failure()
+        .code(124)
+        .stdout("a\n")
+        .stderr("xargs: sh: exited with status 255; aborting\n");
+
+    new_cmd!()
+        .arg("this-command-does-not-exist")
+        .with_stdin().buffer("a\n")
+        .assert()
+        .failure()
+        .code(127);
+}
@@ -0,0 +1,64 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use assert_cmd::prelude::*;
+use assert_fs;
+use predicates::prelude::*;
+use std::process::Command;
+
+const NAME: &str = "watch";
+
+#[test]
+fn test_exit_on_change() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+
+    // each run appends a line, so the second run's output differs from the first's
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-g", "-t", "--no-clear", "-n", "0.1", "echo x >> file; cat file"])
+        .assert()
+        .success()
+        .stdout("x\nx\nx\n")
+        .stderr("");
+}
+
+#[test]
+fn test_joined_arguments() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-g", "-t", "--no-clear", "-n", "0", "echo", "x", ">>", "file;", "wc", "-l", "<", "file"])
+        .assert()
+        .success()
+        .stdout(pred_str_contains!("1\n"))
+        .stdout(pred_str_contains!("2\n"));
+}
+
+#[test]
+fn test_title_and_beep() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-g", "-b", "--no-clear", "-n", "0.1", "echo x >> file; cat file; false"])
+        .assert()
+        .success()
+        .stdout(pred_str_contains!("Every 0.1s: echo x >> file; cat file; false"))
+        .stdout(pred_str_contains!("\x07"));
+}
+
+#[test]
+fn test_invalid_interval() {
+    new_cmd!()
+        .args(&["-n", "abc", "true"])
+        .assert()
+        .failure()
+        .stderr(pred_str_contains!("invalid interval"))
+        .stdout("");
+}
@@ -0,0 +1,83 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+//! Runs utilities through the library rather than through the binary.
+
+use mesabox;
+use std::io;
+
+fn has_util(name: &str) -> bool {
+    mesabox::util_names().contains(&name)
+}
+
+#[test]
+fn test_run_util_streams() {
+    if !has_util("cat") {
+        return;
+    }
+
+    let mut stdout = vec![];
+    let mut stderr = vec![];
+    let code = mesabox::run_util("cat", &["-"], &b"hello\n"[..], &mut stdout, &mut stderr);
+
+    assert_eq!(code.unwrap(), 0);
+    assert_eq!(stdout, b"hello\n");
+    assert_eq!(stderr, b"");
+}
+
+#[test]
+fn test_run_util_failure() {
+    if !has_util("cat") {
+        return;
+    }
+
+    let mut stdout = vec![];
+    let mut stderr = vec![];
+    let code = mesabox::run_util("cat", &["nonexistent"], io::empty(), &mut stdout, &mut stderr);
+
+    assert_eq!(code.unwrap(), 1);
+    assert_eq!(stdout, b"");
+    assert!(String::from_utf8(stderr).unwrap().starts_with("cat: "));
+}
+
+#[test]
+fn test_run_util_endless_input() {
+    if !has_util("head") {
+        return;
+    }
+
+    // the input is used as head reads it, so it stops after the lines it needs
+    let mut stdout = vec![];
+    let code = mesabox::run_util("head", &["-n", "2"], io::repeat(b'\n'), &mut stdout, io::sink());
+
+    assert_eq!(code.unwrap(), 0);
+    assert_eq!(stdout, b"\n\n");
+}
+
+#[test]
+fn test_run_util_child_output() {
+    if !has_util("xargs") {
+        return;
+    }
+
+    // the commands xargs runs have to write to our streams rather than those of the process
+    let mut stdout = vec![];
+    let mut stderr = vec![];
+    let code = mesabox::run_util("xargs", &["-n", "1"], &b"a b\n"[..], &mut stdout, &mut stderr);
+
+    assert_eq!(code.unwrap(), 0);
+    assert_eq!(stdout, b"a\nb\n");
+    assert_eq!(stderr, b"");
+}
+
+#[test]
+fn test_run_util_unknown() {
+    let res = mesabox::run_util("nonexistent", &[] as &[&str], io::empty(), io::sink(), io::sink());
+
+    assert_eq!(res.unwrap_err().kind(), io::ErrorKind::NotFound);
+}
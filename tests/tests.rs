@@ -18,6 +18,9 @@ extern crate timebomb;
 
 #[macro_use]
 mod macros;
+mod golden;
+mod help;
+mod run_util;
 
 use std::path::PathBuf;
 use assert_cmd::cargo;
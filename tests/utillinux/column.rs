@@ -0,0 +1,90 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use assert_cmd::prelude::*;
+use assert_fs;
+use std::fs;
+use std::process::Command;
+
+const NAME: &str = "column";
+
+#[test]
+fn test_fill_columns() {
+    new_cmd!()
+        .args(&["-c", "40"])
+        .with_stdin().buffer("a\nbbbbbbbbb\nc\n\nd\ne\n")
+        .assert()
+        .success()
+        .stdout("a\t\td\nbbbbbbbbb\te\nc\n")
+        .stderr("");
+}
+
+#[test]
+fn test_fill_rows() {
+    new_cmd!()
+        .args(&["-x", "-c", "40"])
+        .with_stdin().buffer("a\nbbbbbbbbb\nc\nd\ne\n")
+        .assert()
+        .success()
+        .stdout("a\t\tbbbbbbbbb\nc\t\td\ne\n");
+}
+
+#[test]
+fn test_narrow() {
+    new_cmd!()
+        .args(&["-c", "1"])
+        .with_stdin().buffer("a\nb\n")
+        .assert()
+        .success()
+        .stdout("a\nb\n");
+}
+
+#[test]
+fn test_table() {
+    new_cmd!()
+        .arg("-t")
+        .with_stdin().buffer("name  size\tdate\nñandú 12 today\n\nx\n")
+        .assert()
+        .success()
+        .stdout("name   size  date\nñandú  12    today\nx\n");
+}
+
+#[test]
+fn test_table_separators() {
+    new_cmd!()
+        .args(&["-t", "-s", ":", "-o", " | "])
+        .with_stdin().buffer("root:x:0\nnobody::65534\n")
+        .assert()
+        .success()
+        .stdout("root   | x     | 0\nnobody | 65534\n");
+}
+
+#[test]
+fn test_files() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("a"), "one 1\n").unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-t", "a", "missing", "-"])
+        .with_stdin().buffer("three 3\n")
+        .assert()
+        .failure()
+        .code(1)
+        .stdout("one    1\nthree  3\n")
+        .stderr("column: cannot open 'missing' for reading: No such file or directory\n");
+}
+
+#[test]
+fn test_invalid_width() {
+    new_cmd!()
+        .args(&["-c", "0"])
+        .assert()
+        .failure()
+        .stdout("");
+}
@@ -0,0 +1,159 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+
+const NAME: &str = "getopt";
+
+#[test]
+fn test_canonical_output() {
+    new_cmd!()
+        .args(&["-o", "ab:c::", "-l", "foo,bar:,baz::", "--"])
+        .args(&["-a", "x", "-bB", "-cC", "-c", "--foo", "--bar=1", "--bar", "2"])
+        .args(&["--baz", "--baz=3", "y", "--", "-z"])
+        .assert()
+        .success()
+        .stdout(
+            " -a -b 'B' -c 'C' -c '' --foo --bar '1' --bar '2' --baz '' --baz '3' \
+             -- 'x' 'y' '-z'\n",
+        )
+        .stderr("");
+}
+
+#[test]
+fn test_abbreviated_long() {
+    new_cmd!()
+        .args(&["-o", "", "--long", "verbose,version", "--", "--verb", "--vers", "--verbose"])
+        .assert()
+        .success()
+        .stdout(" --verbose --version --verbose --\n");
+
+    new_cmd!()
+        .args(&["-o", "", "--long", "verbose,version", "--", "--ver"])
+        .assert()
+        .code(1)
+        .stdout(" --\n")
+        .stderr(pred_str_contains!("getopt: option '--ver' is ambiguous"));
+}
+
+#[test]
+fn test_alternative() {
+    new_cmd!()
+        .args(&["-a", "-o", "x", "-l", "foo:", "--", "-foo", "1", "-x"])
+        .assert()
+        .success()
+        .stdout(" --foo '1' -x --\n");
+}
+
+#[test]
+fn test_permutation() {
+    new_cmd!()
+        .args(&["-o", "+a", "--", "x", "-a"])
+        .assert()
+        .success()
+        .stdout(" -- 'x' '-a'\n");
+
+    new_cmd!()
+        .env("POSIXLY_CORRECT", "1")
+        .args(&["-o", "a", "--", "x", "-a"])
+        .assert()
+        .success()
+        .stdout(" -- 'x' '-a'\n");
+}
+
+#[test]
+fn test_compatible() {
+    new_cmd!()
+        .args(&["ab", "-a", "it's", "-b"])
+        .assert()
+        .success()
+        .stdout(" -a -b -- it's\n");
+
+    new_cmd!()
+        .env("GETOPT_COMPATIBLE", "1")
+        .args(&["-a:", "-a", "x y"])
+        .assert()
+        .success()
+        .stdout(" -a x y --\n");
+}
+
+#[test]
+fn test_quoting() {
+    new_cmd!()
+        .args(&["-o", "a:", "--", "-a", "it's $x", "", "a!b"])
+        .assert()
+        .success()
+        .stdout(" -a 'it'\\''s $x' -- '' 'a!b'\n");
+
+    new_cmd!()
+        .args(&["-s", "tcsh", "-o", "a:", "--", "-a", "a!b"])
+        .assert()
+        .success()
+        .stdout(" -a 'a'\\!'b' --\n");
+
+    new_cmd!()
+        .args(&["-u", "-o", "a:", "--", "-a", "it's"])
+        .assert()
+        .success()
+        .stdout(" -a it's --\n");
+}
+
+#[test]
+fn test_invalid_option() {
+    new_cmd!()
+        .args(&["-n", "prog", "-o", "a", "--", "-z", "-a"])
+        .assert()
+        .code(1)
+        .stdout(" -a --\n")
+        .stderr("prog: invalid option -- 'z'\n");
+
+    new_cmd!()
+        .args(&["-o", "a:", "--", "-a"])
+        .assert()
+        .code(1)
+        .stdout(" --\n")
+        .stderr("getopt: option requires an argument -- 'a'\n");
+}
+
+#[test]
+fn test_quiet() {
+    new_cmd!()
+        .args(&["-q", "-o", "a", "--", "-z"])
+        .assert()
+        .code(1)
+        .stdout(" --\n")
+        .stderr("");
+
+    new_cmd!()
+        .args(&["-Q", "-o", "a", "--", "-a", "x"])
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("");
+}
+
+#[test]
+fn test_test_mode() {
+    new_cmd!()
+        .arg("-T")
+        .assert()
+        .code(4)
+        .stdout("")
+        .stderr("");
+}
+
+#[test]
+fn test_missing_optstring() {
+    new_cmd!()
+        .assert()
+        .code(2)
+        .stdout("")
+        .stderr("getopt: missing optstring argument\n");
+}
@@ -0,0 +1,118 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use assert_cmd::prelude::*;
+use assert_fs;
+use std::fs;
+use std::process::Command;
+
+const NAME: &str = "hexdump";
+
+#[test]
+fn test_canonical() {
+    new_cmd!()
+        .arg("-C")
+        .with_stdin().buffer("hello, world!\n\x01\x7f")
+        .assert()
+        .success()
+        .stdout(concat!(
+            "00000000  68 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a 01 7f  |hello, world!...|\n",
+            "00000010\n",
+        ))
+        .stderr("");
+}
+
+#[test]
+fn test_squeeze() {
+    let data = vec![b'a'; 64];
+
+    new_cmd!()
+        .arg("-C")
+        .with_stdin().buffer(data.clone())
+        .assert()
+        .success()
+        .stdout(concat!(
+            "00000000  61 61 61 61 61 61 61 61  61 61 61 61 61 61 61 61  |aaaaaaaaaaaaaaaa|\n",
+            "*\n",
+            "00000040\n",
+        ));
+
+    new_cmd!()
+        .args(&["-C", "-v", "-n", "32"])
+        .with_stdin().buffer(data)
+        .assert()
+        .success()
+        .stdout(concat!(
+            "00000000  61 61 61 61 61 61 61 61  61 61 61 61 61 61 61 61  |aaaaaaaaaaaaaaaa|\n",
+            "00000010  61 61 61 61 61 61 61 61  61 61 61 61 61 61 61 61  |aaaaaaaaaaaaaaaa|\n",
+            "00000020\n",
+        ));
+}
+
+#[test]
+fn test_skip_length() {
+    new_cmd!()
+        .args(&["-C", "-s", "0x4", "-n", "3"])
+        .with_stdin().buffer("0123456789")
+        .assert()
+        .success()
+        .stdout(concat!(
+            "00000004  34 35 36                                          |456|\n",
+            "00000007\n",
+        ));
+}
+
+#[test]
+fn test_char() {
+    new_cmd!()
+        .arg("-c")
+        .with_stdin().buffer("a\tb\n\x01")
+        .assert()
+        .success()
+        .stdout(concat!(
+            "0000000   a  \\t   b  \\n 001                                             \n",
+            "0000005\n",
+        ));
+}
+
+#[test]
+fn test_empty() {
+    new_cmd!()
+        .with_stdin().buffer("")
+        .assert()
+        .success()
+        .stdout("");
+}
+
+#[test]
+fn test_files() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("a"), "abc").unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-C", "a", "missing", "-"])
+        .with_stdin().buffer("def")
+        .assert()
+        .failure()
+        .code(1)
+        .stdout(concat!(
+            "00000000  61 62 63 64 65 66                                 |abcdef|\n",
+            "00000006\n",
+        ))
+        .stderr("hexdump: cannot open 'missing' for reading: No such file or directory\n");
+}
+
+#[test]
+fn test_invalid_length() {
+    new_cmd!()
+        .args(&["-n", "12q"])
+        .assert()
+        .failure()
+        .stdout("");
+}
@@ -0,0 +1,90 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use assert_cmd::prelude::*;
+use assert_fs;
+use predicates::prelude::*;
+use std::fs;
+use std::process::Command;
+
+const NAME: &str = "mountpoint";
+
+#[test]
+fn test_root() {
+    new_cmd!()
+        .arg("/")
+        .assert()
+        .success()
+        .stdout("/ is a mountpoint\n")
+        .stderr("");
+}
+
+#[test]
+fn test_not_mountpoint() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    fs::create_dir(temp_dir.path().join("dir")).unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .arg("dir")
+        .assert()
+        .failure()
+        .stdout("dir is not a mountpoint\n")
+        .stderr("");
+}
+
+#[test]
+fn test_quiet() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    fs::create_dir(temp_dir.path().join("dir")).unwrap();
+
+    new_cmd!()
+        .args(&["-q", "/"])
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("");
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["-q", "dir"])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr("");
+}
+
+#[test]
+fn test_fs_devno() {
+    new_cmd!()
+        .args(&["-d", "/"])
+        .assert()
+        .success()
+        .stdout(pred_str_contains!(":"))
+        .stderr("");
+}
+
+#[test]
+fn test_not_block_device() {
+    new_cmd!()
+        .args(&["-x", "/dev/null"])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr("mountpoint: /dev/null: not a block device\n");
+}
+
+#[test]
+fn test_missing() {
+    new_cmd!()
+        .arg("nonexistent")
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(pred_str_contains!("nonexistent"));
+}
@@ -0,0 +1,50 @@
+//
+// Copyright (c) 2018, The MesaLock Linux Project Contributors
+// All rights reserved.
+//
+// This work is licensed under the terms of the BSD 3-Clause License.
+// For a copy, see the LICENSE file.
+//
+
+use assert_cmd::prelude::*;
+use assert_fs;
+use std::fs;
+use std::process::Command;
+
+const NAME: &str = "rev";
+
+#[test]
+fn test_stdin() {
+    new_cmd!()
+        .with_stdin().buffer("hello world\n\nañ€\nno newline")
+        .assert()
+        .success()
+        .stdout("dlrow olleh\n\n€ña\nenilwen on")
+        .stderr("");
+}
+
+#[test]
+fn test_zero_terminated() {
+    new_cmd!()
+        .arg("-z")
+        .with_stdin().buffer("ab\ncd\0ef\0")
+        .assert()
+        .success()
+        .stdout("dc\nba\0fe\0");
+}
+
+#[test]
+fn test_files() {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("a"), "abc\n").unwrap();
+
+    new_cmd!()
+        .current_dir(temp_dir.path())
+        .args(&["a", "-", "missing", "a"])
+        .with_stdin().buffer("xyz\n")
+        .assert()
+        .failure()
+        .code(1)
+        .stdout("cba\nzyx\ncba\n")
+        .stderr("rev: cannot open 'missing' for reading: No such file or directory\n");
+}